@@ -6,7 +6,7 @@ use pdf::error::PdfError;
 use pdf::file::FileOptions;
 
 use pdf::object::*;
-use pdf::primitive::{Dictionary, Name, PdfString, Primitive};
+use pdf::primitive::{Dictionary, Name, Primitive};
 
 fn run() -> Result<(), PdfError> {
     let path = args().nth(1).expect("no file given");
@@ -35,9 +35,11 @@ fn run() -> Result<(), PdfError> {
     // dbg!(&annot.other);
     // }
 
-    let mut bs = Dictionary::new();
-    bs.insert(Name::from("S"), PdfString::from("/S"));
-    bs.insert(Name::from("W"), PdfString::from("3"));
+    let bs = BorderStyle {
+        width: 3.,
+        style: BorderStyleKind::Solid,
+        dash: None,
+    };
     let new_annot = Annot {
         subtype: Name::from("Line"),
         // rect: Some(Rectangle {
@@ -74,9 +76,11 @@ fn run() -> Result<(), PdfError> {
         ])),
         // creation_date: None,
         // uuid: None,
-        // border_style: Some(bs),
-        // border_style: None,
-        // popup: None,
+        border_style: Some(bs),
+        border_effect: None,
+        popup: None,
+        in_reply_to: None,
+        reply_type: None,
         other: Dictionary::new(),
         // transparency: Some(1.0),
         // transparency: None,
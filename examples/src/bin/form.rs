@@ -88,9 +88,9 @@ fn run() -> Result<(), PdfError> {
         for field in forms.fields.iter().take(1) {
             print!("  {:?} = ", field.name);
             match field.value {
-                Primitive::String(ref s) => println!("{}", s.to_string_lossy()),
-                Primitive::Integer(i) => println!("{}", i),
-                Primitive::Name(ref s) => println!("{}", s),
+                Nullable::Value(Primitive::String(ref s)) => println!("{}", s.to_string_lossy()),
+                Nullable::Value(Primitive::Integer(i)) => println!("{}", i),
+                Nullable::Value(Primitive::Name(ref s)) => println!("{}", s),
                 ref p => println!("{:?}", p),
             }
 
@@ -107,7 +107,7 @@ fn run() -> Result<(), PdfError> {
         let text = "Hello World!";
         let new_value: PdfString = PdfString::new(text.into());
         let mut updated_field = (*to_update_field).clone();
-        updated_field.value = Primitive::String(new_value);
+        updated_field.value = Nullable::Value(Primitive::String(new_value));
 
         //dbg!(&updated_field);
 
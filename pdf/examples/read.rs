@@ -92,8 +92,9 @@ fn main() -> Result<(), PdfError> {
     println!("Found {} image(s).", images.len());
 
     for (name, font) in fonts.iter() {
-        let fname = format!("font_{}", name);
-        if let Some(Ok(data)) = font.embedded_data(&resolver) {
+        if let Some(Ok(font)) = font.embedded_data(&resolver) {
+            let fname = format!("font_{}.{}", name, font.format.extension());
+            let data = font.to_pfb().unwrap_or_else(|| font.data.to_vec());
             fs::write(fname.as_str(), data).unwrap();
             println!("Wrote file {}", fname);
         }
@@ -105,9 +106,9 @@ fn main() -> Result<(), PdfError> {
         for field in forms.fields.iter() {
             print!("  {:?} = ", field.name);
             match field.value {
-                Primitive::String(ref s) => println!("{}", s.to_string_lossy()),
-                Primitive::Integer(i) => println!("{}", i),
-                Primitive::Name(ref s) => println!("{}", s),
+                Nullable::Value(Primitive::String(ref s)) => println!("{}", s.to_string_lossy()),
+                Nullable::Value(Primitive::Integer(i)) => println!("{}", i),
+                Nullable::Value(Primitive::Name(ref s)) => println!("{}", s),
                 ref p => println!("{:?}", p),
             }
         }
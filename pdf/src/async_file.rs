@@ -0,0 +1,228 @@
+//! Loading files whose data arrives asynchronously, like over HTTP range requests.
+//!
+//! The parser itself stays synchronous. [`AsyncFile`] keeps the parts of the file fetched so far
+//! in memory ([`Blocks`]). When an operation needs bytes that are not there yet, it fails with
+//! [`PdfError::NotLoaded`], the missing range is fetched from the [`AsyncBackend`] and the
+//! operation runs again. Only the parts of the file that are actually used are transferred,
+//! so the first page of a linearized file is available after a few requests.
+//!
+//! Errors that the parser tolerates (see [`ParseOptions`](crate::object::ParseOptions)) can't
+//! trigger a fetch. Use strict options, or [`AsyncFile::fetch`] the whole file in that case.
+//!
+//! No runtime is needed, the futures only wait for the backend.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::any::AnySync;
+use crate::backend::{Backend, IndexRange};
+use crate::error::*;
+use crate::file::{Cache, File, FileOptions, Log};
+
+/// A source of bytes that are fetched asynchronously.
+pub trait AsyncBackend {
+    /// The size of the file in bytes.
+    fn size(&self) -> impl Future<Output = Result<usize>>;
+
+    /// Fetch the bytes in `range`, which is within the file.
+    fn read(&self, range: Range<usize>) -> impl Future<Output = Result<Vec<u8>>>;
+}
+
+/// The blocks of a file fetched so far.
+///
+/// Clones share the same blocks. Reading bytes that have not been fetched fails with
+/// [`PdfError::NotLoaded`] for the blocks that are missing.
+#[derive(Clone)]
+pub struct Blocks {
+    len: usize,
+    block_size: usize,
+    blocks: Arc<Mutex<HashMap<usize, Arc<[u8]>>>>,
+}
+impl Blocks {
+    pub fn new(len: usize, block_size: usize) -> Self {
+        Blocks {
+            len,
+            block_size: block_size.max(1),
+            blocks: Arc::default(),
+        }
+    }
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The blocks needed for `range` that have not been fetched, as a range of bytes.
+    pub fn missing(&self, range: Range<usize>) -> Option<Range<usize>> {
+        if range.is_empty() {
+            return None;
+        }
+        let blocks = self.blocks.lock().unwrap();
+        let mut missing = (range.start / self.block_size .. (range.end - 1) / self.block_size + 1)
+            .filter(|n| !blocks.contains_key(n));
+        let first = missing.next()?;
+        let last = missing.next_back().unwrap_or(first);
+        Some(first * self.block_size .. ((last + 1) * self.block_size).min(self.len))
+    }
+
+    /// Store `data` fetched for `range`, which starts and ends at block boundaries
+    /// (or the end of the file).
+    pub fn insert(&self, range: Range<usize>, data: &[u8]) -> Result<()> {
+        if data.len() != range.len() {
+            bail!("expected {} bytes for {:?}, got {}", range.len(), range, data.len());
+        }
+        if !range.start.is_multiple_of(self.block_size) || (!range.end.is_multiple_of(self.block_size) && range.end != self.len) {
+            bail!("{:?} is not aligned to blocks of {} bytes", range, self.block_size);
+        }
+        let mut blocks = self.blocks.lock().unwrap();
+        for (i, chunk) in data.chunks(self.block_size).enumerate() {
+            blocks.insert(range.start / self.block_size + i, chunk.into());
+        }
+        Ok(())
+    }
+
+    /// How many bytes have been fetched.
+    pub fn loaded(&self) -> usize {
+        self.blocks.lock().unwrap().values().map(|b| b.len()).sum()
+    }
+}
+impl Backend for Blocks {
+    type Data<'a> = Vec<u8>;
+
+    fn read<T: IndexRange>(&self, range: T) -> Result<Vec<u8>> {
+        let range = t!(range.to_range(self.len));
+        if let Some(Range { start, end }) = self.missing(range.clone()) {
+            return Err(PdfError::NotLoaded { start, end });
+        }
+        let blocks = self.blocks.lock().unwrap();
+        let mut data = Vec::with_capacity(range.len());
+        let mut pos = range.start;
+        while pos < range.end {
+            let n = pos / self.block_size;
+            let offset = pos - n * self.block_size;
+            let block = &blocks[&n];
+            let end = block.len().min(offset + range.end - pos);
+            data.extend_from_slice(&block[offset .. end]);
+            pos += end - offset;
+        }
+        Ok(data)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn window(&self) -> Option<usize> {
+        Some(self.block_size)
+    }
+}
+
+/// A [`File`] that fetches its data from an [`AsyncBackend`] as needed.
+pub struct AsyncFile<A, OC, SC, L> {
+    source: A,
+    blocks: Blocks,
+    file: File<Blocks, OC, SC, L>,
+}
+impl<A, OC, SC, L> AsyncFile<A, OC, SC, L>
+where
+    A: AsyncBackend,
+    OC: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L: Log,
+{
+    /// Load the file, fetching blocks of `block_size` bytes from `source`.
+    ///
+    /// This reads the header, the cross reference table and the trailer.
+    pub async fn load(options: FileOptions<'_, OC, SC, L>, source: A, block_size: usize) -> Result<Self>
+    where
+        OC: Clone,
+        SC: Clone,
+        L: Clone,
+    {
+        let blocks = Blocks::new(t!(source.size().await), block_size);
+        loop {
+            match options.clone().load(blocks.clone()) {
+                Ok(file) => return Ok(AsyncFile { source, blocks, file }),
+                Err(e) => {
+                    fetch_missing(&source, &blocks, e).await?;
+                    // clones of the options share the caches
+                    options.clear_caches();
+                }
+            }
+        }
+    }
+
+    /// The file with the data fetched so far.
+    ///
+    /// Its methods fail with [`PdfError::NotLoaded`] if they need more, see [`AsyncFile::with`].
+    pub fn file(&self) -> &File<Blocks, OC, SC, L> {
+        &self.file
+    }
+    pub fn blocks(&self) -> &Blocks {
+        &self.blocks
+    }
+
+    /// Make sure the bytes in `range` are available.
+    pub async fn fetch(&self, range: impl IndexRange) -> Result<()> {
+        let range = t!(range.to_range(self.blocks.len));
+        fetch_blocks(&self.source, &self.blocks, range).await
+    }
+
+    /// Run `f` on the file, fetching missing data until it succeeds or fails for another reason.
+    ///
+    /// `f` may run several times. The object caches are cleared after each fetch,
+    /// as they also keep the errors.
+    pub async fn with<T>(&self, mut f: impl FnMut(&File<Blocks, OC, SC, L>) -> Result<T>) -> Result<T> {
+        loop {
+            match f(&self.file) {
+                Err(e) => {
+                    fetch_missing(&self.source, &self.blocks, e).await?;
+                    self.file.clear_caches();
+                }
+                r => return r,
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> File<Blocks, OC, SC, L> {
+        self.file
+    }
+}
+
+async fn fetch_blocks(source: &impl AsyncBackend, blocks: &Blocks, range: Range<usize>) -> Result<()> {
+    if let Some(range) = blocks.missing(range) {
+        let data = t!(source.read(range.clone()).await);
+        t!(blocks.insert(range, &data));
+    }
+    Ok(())
+}
+
+/// Fetch what `error` asks for, or return it if it is about something else.
+///
+/// An error about bytes that are already there would come back forever, so it is returned as well.
+async fn fetch_missing(source: &impl AsyncBackend, blocks: &Blocks, error: PdfError) -> Result<()> {
+    match *error.root_cause() {
+        PdfError::NotLoaded { start, end } if blocks.missing(start .. end).is_some() => {
+            fetch_blocks(source, blocks, start .. end).await
+        }
+        _ => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks() {
+        let blocks = Blocks::new(10, 4);
+        assert_eq!(blocks.missing(3 .. 5), Some(0 .. 8));
+        blocks.insert(4 .. 10, b"456789").unwrap();
+        assert_eq!(blocks.missing(3 .. 5), Some(0 .. 4));
+        assert!(matches!(blocks.read(2 .. 6), Err(PdfError::NotLoaded { start: 0, end: 4 })));
+        assert_eq!(blocks.read(5 ..).unwrap(), b"56789");
+        assert!(blocks.insert(1 .. 2, b"1").is_err());
+        blocks.insert(0 .. 4, b"0123").unwrap();
+        assert_eq!(blocks.read(..).unwrap(), b"0123456789");
+        assert_eq!(blocks.missing(0 .. 10), None);
+        assert_eq!(blocks.loaded(), 10);
+    }
+}
@@ -4,7 +4,9 @@ use crate::parser::read_xref_and_trailer_at;
 use crate::xref::XRefTable;
 use crate::primitive::Dictionary;
 use crate::object::*;
+use std::io::{Read, Seek, SeekFrom};
 use std::ops::Deref;
+use std::sync::Mutex;
 
 use std::ops::{
     RangeFull,
@@ -16,13 +18,51 @@ use std::ops::{
 pub const MAX_ID: u32 = 1_000_000;
 
 pub trait Backend: Sized {
-    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]>;
+    /// The bytes returned by [`Backend::read`]. In-memory backends lend a slice of their data,
+    /// others can return an owned buffer.
+    type Data<'a>: Deref<Target=[u8]> where Self: 'a;
+
+    fn read<T: IndexRange>(&self, range: T) -> Result<Self::Data<'_>>;
     //fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]>;
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// How many bytes to read at once when the length of an object is not known in advance.
+    ///
+    /// `None` (the default) hands the parser everything up to the end of the file, which is free
+    /// for data in memory. Backends that read on demand return a size, see [`Backend::parse_at`].
+    fn window(&self) -> Option<usize> {
+        None
+    }
+
+    /// Run `parse` on the data starting at `pos`.
+    ///
+    /// With a [`window`](Backend::window), `parse` only gets that many bytes. If it fails or reaches
+    /// the end of them, it runs again on twice as many, until the end of the file is reached.
+    fn parse_at<T>(&self, pos: usize, mut parse: impl FnMut(&mut Lexer) -> Result<T>) -> Result<T> {
+        let len = self.len();
+        let mut size = match self.window() {
+            Some(size) => size.max(1),
+            None => {
+                let data = t!(self.read(pos ..));
+                return parse(&mut Lexer::with_offset(&data, pos));
+            }
+        };
+        loop {
+            let end = pos.saturating_add(size).min(len);
+            let data = t!(self.read(pos .. end));
+            let mut lexer = Lexer::with_offset(&data, pos);
+            let result = parse(&mut lexer);
+            // the last token may have been cut off
+            if end == len || (result.is_ok() && lexer.get_pos() < data.len()) {
+                return result;
+            }
+            size = size.saturating_mul(2);
+        }
+    }
+
     /// Returns the offset of the beginning of the file, i.e., where the `%PDF-1.5` header is.
     /// (currently only used internally!)
     fn locate_start_offset(&self) -> Result<usize> {
@@ -43,10 +83,42 @@ pub trait Backend: Sized {
         // `\nPOS\n%%EOF` where POS is the position encoded as base 10 integer.
         // u64::MAX has 20 digits + \n\n(2) + %%EOF(5) = 27 bytes max.
 
-        let mut lexer = Lexer::new(t!(self.read(..)));
-        lexer.set_pos_from_end(0);
-        t!(lexer.seek_substr_back(b"startxref"));
-        t!(lexer.next()).to::<usize>()
+        let len = self.len();
+        let mut size = self.window().unwrap_or(len).max(1);
+        loop {
+            let start = len.saturating_sub(size);
+            let data = t!(self.read(start ..));
+            let mut lexer = Lexer::new(&data);
+            lexer.set_pos_from_end(0);
+            let result = lexer.seek_substr_back(b"startxref").and_then(|_| lexer.next()?.to::<usize>());
+            match result {
+                Err(_) if start > 0 => size = size.saturating_mul(2),
+                result => return Ok(t!(result)),
+            }
+        }
+    }
+
+    /// The offsets of the cross-reference sections (relative to `start_offset`), from the one `startxref`
+    /// points to along the `Prev` entries of the trailers, each with its `Prev`.
+    fn xref_chain(&self, start_offset: usize, resolve: &impl Resolve) -> Result<Vec<(usize, Option<usize>)>> {
+        let mut chain: Vec<(usize, Option<usize>)> = vec![];
+        let mut next = Some(t!(self.locate_xref_offset()));
+        while let Some(offset) = next {
+            if chain.iter().any(|&(o, _)| o == offset) {
+                bail!("xref offsets loop");
+            }
+            let pos = t!(start_offset.checked_add(offset).ok_or(PdfError::Invalid));
+            if pos >= self.len() {
+                bail!("XRef offset outside file bounds");
+            }
+            let (_, trailer) = t!(self.parse_at(pos, |lexer| read_xref_and_trailer_at(lexer, resolve)));
+            next = match trailer.get("Prev") {
+                Some(p) => Some(t!(p.as_usize())),
+                None => None,
+            };
+            chain.push((offset, next));
+        }
+        Ok(chain)
     }
 
     /// Used internally by File, but could also be useful for applications that want to look at the raw PDF objects.
@@ -57,9 +129,7 @@ pub trait Backend: Sized {
             bail!("XRef offset outside file bounds");
         }
 
-        let mut lexer = Lexer::with_offset(t!(self.read(pos ..)), pos);
-        
-        let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
+        let (xref_sections, trailer) = t!(self.parse_at(pos, |lexer| read_xref_and_trailer_at(lexer, resolve)));
         
         let highest_id = t!(trailer.get("Size")
             .ok_or_else(|| PdfError::MissingEntry {field: "Size".into(), typ: "XRefTable"})?
@@ -68,6 +138,10 @@ pub trait Backend: Sized {
         if highest_id > MAX_ID {
             bail!("too many objects");
         }
+        let max_objects = resolve.options().limits.max_objects;
+        if highest_id as usize > max_objects {
+            return Err(PdfError::LimitExceeded { what: "number of objects", max: max_objects as u64 });
+        }
         let mut refs = XRefTable::new(highest_id as ObjNr);
         for section in xref_sections {
             refs.add_entries_from(section)?;
@@ -88,8 +162,7 @@ pub trait Backend: Sized {
             seen.push(prev_xref_offset);
 
             let pos = t!(start_offset.checked_add(prev_xref_offset).ok_or(PdfError::Invalid));
-            let mut lexer = Lexer::with_offset(t!(self.read(pos..)), pos);
-            let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
+            let (xref_sections, trailer) = t!(self.parse_at(pos, |lexer| read_xref_and_trailer_at(lexer, resolve)));
             
             for section in xref_sections {
                 refs.add_entries_from(section)?;
@@ -111,6 +184,8 @@ pub trait Backend: Sized {
 
 
 impl<T> Backend for T where T: Deref<Target=[u8]> { //+ DerefMut<Target=[u8]> {
+    type Data<'a> = &'a [u8] where T: 'a;
+
     fn read<R: IndexRange>(&self, range: R) -> Result<&[u8]> {
         let r = t!(range.to_range(self.len()));
         Ok(&self[r])
@@ -126,6 +201,53 @@ impl<T> Backend for T where T: Deref<Target=[u8]> { //+ DerefMut<Target=[u8]> {
     }
 }
 
+/// Reads the data on demand from anything that implements `Read` and `Seek`, like a `std::fs::File`,
+/// so files larger than the available memory can be opened.
+///
+/// Only the parts that are needed are read, in windows of `window` bytes (64 KiB by default).
+/// Combine it with a bounded cache like [`FileOptions::cached_bounded`](crate::file::FileOptions::cached_bounded)
+/// to keep the memory use bounded as well.
+pub struct ReadBackend<R> {
+    reader: Mutex<R>,
+    len: usize,
+    window: usize,
+}
+impl<R: Read + Seek> ReadBackend<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        Ok(ReadBackend {
+            reader: Mutex::new(reader),
+            len: len.try_into()?,
+            window: 64 * 1024,
+        })
+    }
+    /// Read `window` bytes at once when parsing objects.
+    pub fn with_window(self, window: usize) -> Self {
+        ReadBackend { window, ..self }
+    }
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner().unwrap()
+    }
+}
+impl<R: Read + Seek> Backend for ReadBackend<R> {
+    type Data<'a> = Vec<u8> where R: 'a;
+
+    fn read<T: IndexRange>(&self, range: T) -> Result<Vec<u8>> {
+        let range = t!(range.to_range(self.len));
+        let mut data = vec![0; range.len()];
+        let mut reader = self.reader.lock().unwrap();
+        reader.seek(SeekFrom::Start(range.start as u64))?;
+        reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn window(&self) -> Option<usize> {
+        Some(self.window)
+    }
+}
+
 /// `IndexRange` is implemented by Rust's built-in range types, produced
 /// by range syntax like `..`, `a..`, `..b` or `c..d`.
 pub trait IndexRange
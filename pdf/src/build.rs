@@ -6,24 +6,40 @@ use std::sync::Arc;
 use datasize::DataSize;
 
 use crate::any::AnySync;
-use crate::content::{deep_clone_op, Content, Op};
+use crate::content::{deep_clone_op, serialize_ops, Content, FormXObject, Op};
 use crate::enc::StreamFilter;
 use crate::error::Result;
 use crate::file::Cache;
 use crate::file::FileOptions;
 use crate::file::Log;
+use crate::file::NoCache;
+use crate::file::NoLog;
 use crate::file::Storage;
 use crate::file::Trailer;
+use crate::object::Annot;
 use crate::object::Catalog;
 use crate::object::Cloner;
 use crate::object::DeepClone;
+use crate::object::Dest;
+use crate::object::DestView;
+use crate::object::ImageXObject;
+use crate::object::FormDict;
 use crate::object::InfoDict;
+use crate::object::Lazy;
+use crate::object::MarkInformation;
+use crate::object::MaybeRef;
+use crate::object::NumberTree;
+use crate::object::NumberTreeNode;
+use crate::object::ParentTreeEntry;
 use crate::object::Object;
 use crate::object::ObjectWrite;
+use crate::object::OpenAction;
 use crate::object::Page;
 use crate::object::PageTree;
 use crate::object::PagesNode;
+use crate::object::PageRc;
 use crate::object::PagesRc;
+use crate::object::PieceInfo;
 use crate::object::ParseOptions;
 use crate::object::PlainRef;
 use crate::object::PromisedRef;
@@ -33,10 +49,23 @@ use crate::object::Ref;
 use crate::object::Resolve;
 use crate::object::Resources;
 use crate::object::Shared;
+use crate::object::StampPlacement;
+use crate::object::Stream;
+use crate::object::StructElem;
+use crate::object::StructKid;
+use crate::object::StructTreeRoot;
+use crate::object::StructType;
 use crate::object::Updater;
+use crate::object::XObject;
+use crate::object::stamp_matrix;
 use crate::parser::ParseFlags;
+use crate::object::NoUpdate;
+use crate::primitive::Date;
 use crate::primitive::Dictionary;
+use crate::primitive::Name;
+use crate::primitive::PdfString;
 use crate::primitive::Primitive;
+use crate::text::{replace_text, ReplaceStats};
 use crate::PdfError;
 
 #[derive(Default)]
@@ -50,6 +79,13 @@ pub struct PageBuilder {
     pub metadata: Option<Primitive>,
     pub lgi: Option<Primitive>,
     pub vp: Option<Primitive>,
+    pub struct_parents: Option<i32>,
+    pub thumb: Option<Ref<ImageXObject>>,
+    pub last_modified: Option<Date>,
+    pub piece_info: Option<PieceInfo>,
+    pub annotations: Lazy<Vec<MaybeRef<Annot>>>,
+    /// The reference the page will get, if it is needed before the page is built.
+    pub page_ref: Option<PromisedRef<PagesNode>>,
     pub other: Dictionary,
 }
 impl PageBuilder {
@@ -75,28 +111,17 @@ impl PageBuilder {
             metadata: page.metadata.clone(),
             lgi: page.lgi.clone(),
             vp: page.vp.clone(),
+            struct_parents: page.struct_parents,
+            thumb: page.thumb,
+            last_modified: page.last_modified.clone(),
+            piece_info: page.piece_info.clone(),
+            annotations: page.annotations.clone(),
+            page_ref: None,
             other: page.other.clone(),
         })
     }
     pub fn clone_page(page: &Page, cloner: &mut impl Cloner) -> Result<PageBuilder> {
-        let old_resources = &**page.resources()?.data();
-
-        let mut resources = Resources::default();
-        let ops = page
-            .contents
-            .as_ref()
-            .map(|content| content.operations(cloner))
-            .transpose()?
-            .map(|ops| {
-                ops.into_iter()
-                    .map(|op| -> Result<Op, PdfError> {
-                        deep_clone_op(&op, cloner, old_resources, &mut resources)
-                    })
-                    .collect()
-            })
-            .transpose()?
-            .unwrap_or_default();
-
+        let (ops, resources) = clone_ops(page, cloner)?;
         Ok(PageBuilder {
             ops,
             media_box: Some(page.media_box()?),
@@ -107,36 +132,222 @@ impl PageBuilder {
             metadata: page.metadata.deep_clone(cloner)?,
             lgi: page.lgi.deep_clone(cloner)?,
             vp: page.vp.deep_clone(cloner)?,
+            // the structure tree is not cloned along with the page
+            struct_parents: None,
+            thumb: page.thumb.deep_clone(cloner)?,
+            last_modified: page.last_modified.clone(),
+            piece_info: page.piece_info.deep_clone(cloner)?,
+            annotations: Default::default(),
+            page_ref: None,
             other: page.other.deep_clone(cloner)?,
         })
     }
+    /// Clone the annotations of `page`, which this page was cloned from, so that their `/P` entries
+    /// point to the new page.
+    ///
+    /// Everything else they reference is cloned as well, including pages that links lead to.
+    pub fn clone_annotations(&mut self, page: &Page, cloner: &mut impl Cloner) -> Result<()> {
+        // the new page needs its reference before the annotations pointing back at it are cloned
+        let old_page = t!(page.annotations.load(cloner))
+            .iter()
+            .find_map(|annot| annot.page.as_ref().map(|page| page.get_ref()));
+        if let Some(old) = old_page {
+            let promise = self.page_ref.take().unwrap_or_else(|| cloner.promise());
+            cloner.map_ref(old.get_inner(), promise.get_inner());
+            self.page_ref = Some(promise);
+        }
+        self.annotations = t!(page.annotations.deep_clone(cloner));
+        Ok(())
+    }
+    /// Set the MediaBox to `width` × `height` with the lower left corner at the origin.
     pub fn size(&mut self, width: f32, height: f32) {
         self.media_box = Some(Rectangle {
-            top: 0.,
             left: 0.,
-            bottom: height,
+            bottom: 0.,
             right: width,
+            top: height,
         });
     }
+    /// Replace all occurrences of `find` in the text of this page. See [`replace_text`](crate::text::replace_text).
+    pub fn replace_text(&mut self, find: &str, replace: &str, resolve: &impl Resolve, update: &mut impl Updater) -> Result<ReplaceStats> {
+        let (ops, stats) = replace_text(&self.ops, &mut self.resources, find, replace, resolve, update)?;
+        self.ops = ops;
+        Ok(stats)
+    }
+}
+
+/// The operators of `page` with the resources they use, cloned with `cloner`.
+fn clone_ops(page: &Page, cloner: &mut impl Cloner) -> Result<(Vec<Op>, Resources)> {
+    let old_resources = &**page.resources()?.data();
+
+    let mut resources = Resources::default();
+    let ops = page
+        .contents
+        .as_ref()
+        .map(|content| content.operations(cloner))
+        .transpose()?
+        .map(|ops| {
+            ops.into_iter()
+                .map(|op| -> Result<Op, PdfError> {
+                    deep_clone_op(&op, cloner, old_resources, &mut resources)
+                })
+                .collect()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    Ok((ops, resources))
+}
+
+/// Copy `page` as a form XObject with the crop box as its bounding box.
+///
+/// Annotations are not part of the form.
+pub fn page_to_form(page: &Page, cloner: &mut impl Cloner) -> Result<FormXObject> {
+    let (ops, resources) = clone_ops(page, cloner)?;
+    let dict = FormDict {
+        bbox: page.crop_box()?,
+        resources: Some(cloner.create(resources)?.into()),
+        ..Default::default()
+    };
+    Ok(FormXObject { stream: Stream::new(dict, serialize_ops(&ops)?) })
+}
+
+/// The order in which [`PageBuilder::n_up`] fills the cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NUpOrder {
+    /// Left to right, then top to bottom.
+    RowsFirst,
+    /// Top to bottom, then left to right.
+    ColumnsFirst,
+    /// Printed on both sides and folded in the middle, the sheets make a booklet.
+    /// Meant for two cells per sheet. Blank cells are added to fill the last sheet.
+    Booklet,
+}
+
+/// How [`PageBuilder::n_up`] arranges pages on a sheet.
+#[derive(Debug, Clone, Copy)]
+pub struct NUp {
+    /// The media box of the new pages.
+    pub sheet: Rectangle,
+    pub columns: u32,
+    pub rows: u32,
+    /// Space between the edge of the sheet and the cells.
+    pub margin: f32,
+    /// Space between the cells.
+    pub gap: f32,
+    pub order: NUpOrder,
+}
+impl NUp {
+    /// Two pages side by side on each sheet.
+    pub fn two_up(sheet: Rectangle) -> NUp {
+        NUp { sheet, columns: 2, rows: 1, margin: 0., gap: 0., order: NUpOrder::RowsFirst }
+    }
+    /// Two pages side by side on each sheet, ordered for a booklet.
+    pub fn booklet(sheet: Rectangle) -> NUp {
+        NUp { order: NUpOrder::Booklet, ..NUp::two_up(sheet) }
+    }
+
+    /// The page shown in each cell, `None` for a blank cell.
+    fn slots(&self, pages: usize) -> Vec<Option<usize>> {
+        let cells = (self.columns * self.rows) as usize;
+        let mut slots: Vec<Option<usize>> = match self.order {
+            NUpOrder::Booklet => {
+                let n = pages.div_ceil(4) * 4;
+                (0..n / 2).step_by(2)
+                    .flat_map(|k| [n - 1 - k, k, k + 1, n - 2 - k])
+                    .map(|i| (i < pages).then_some(i))
+                    .collect()
+            }
+            _ => (0..pages).map(Some).collect(),
+        };
+        slots.resize(slots.len().div_ceil(cells) * cells, None);
+        slots
+    }
+
+    /// The rectangle of cell `i` on a sheet.
+    fn cell(&self, i: usize) -> Rectangle {
+        let (columns, rows) = (self.columns as usize, self.rows as usize);
+        let (column, row) = match self.order {
+            NUpOrder::ColumnsFirst => (i / rows, i % rows),
+            _ => (i % columns, i / columns),
+        };
+        let sheet = self.sheet;
+        let width = (sheet.right - sheet.left - 2. * self.margin - (columns - 1) as f32 * self.gap) / columns as f32;
+        let height = (sheet.top - sheet.bottom - 2. * self.margin - (rows - 1) as f32 * self.gap) / rows as f32;
+        // rows are counted from the top
+        let left = sheet.left + self.margin + column as f32 * (width + self.gap);
+        let top = sheet.top - self.margin - row as f32 * (height + self.gap);
+        Rectangle { left, bottom: top - height, right: left + width, top }
+    }
+}
+
+impl PageBuilder {
+    /// New pages that each show several of `pages`, scaled to fit their cells and upright.
+    ///
+    /// The pages are copied with `cloner` as form XObjects, see [`page_to_form`].
+    pub fn n_up(pages: &[PageRc], layout: &NUp, cloner: &mut impl Cloner) -> Result<Vec<PageBuilder>> {
+        if layout.columns == 0 || layout.rows == 0 {
+            bail!("an N-up layout needs at least one cell");
+        }
+        let cells = (layout.columns * layout.rows) as usize;
+        let mut sheets = vec![];
+        for slots in layout.slots(pages.len()).chunks(cells) {
+            let mut sheet = PageBuilder::default();
+            sheet.media_box = Some(layout.sheet);
+            for (i, &slot) in slots.iter().enumerate() {
+                let page = match slot {
+                    Some(n) => &pages[n],
+                    None => continue,
+                };
+                let form = page_to_form(page, cloner)?;
+                let matrix = stamp_matrix(StampPlacement::Fit { margin: 0. }, form.dict().bbox, layout.cell(i), page.rotate);
+                let form = cloner.create(XObject::Form(form))?;
+                let name = Name::from(format!("P{}", i));
+                sheet.resources.xobjects.insert(name.clone(), form.get_ref());
+                sheet.ops.extend([
+                    Op::Save,
+                    Op::Transform { matrix },
+                    Op::XObject { name },
+                    Op::Restore,
+                ]);
+            }
+            sheets.push(sheet);
+        }
+        Ok(sheets)
+    }
 }
 
 pub struct CatalogBuilder {
     pages: Vec<PageBuilder>,
+    structure: Option<StructureBuilder>,
+    open_at: Option<(usize, DestView)>,
 }
 impl CatalogBuilder {
     pub fn from_pages(pages: Vec<PageBuilder>) -> CatalogBuilder {
-        CatalogBuilder { pages }
+        CatalogBuilder { pages, structure: None, open_at: None }
     }
-    pub fn build(self, update: &mut impl Updater) -> Result<Catalog> {
+    /// Tag the document with the structure tree of `structure`.
+    ///
+    /// The page numbers used with the `StructureBuilder` refer to the pages of this builder.
+    pub fn structure(mut self, structure: StructureBuilder) -> CatalogBuilder {
+        self.structure = Some(structure);
+        self
+    }
+    /// Open the document at page `page_nr` (counting from zero) of this builder, showing it with `view`.
+    pub fn open_at(mut self, page_nr: usize, view: DestView) -> CatalogBuilder {
+        self.open_at = Some((page_nr, view));
+        self
+    }
+    pub fn build(mut self, update: &mut impl Updater) -> Result<Catalog> {
         let kids_promise: Vec<_> = self
             .pages
-            .iter()
-            .map(|_page| update.promise::<PagesNode>())
+            .iter_mut()
+            .map(|page| page.page_ref.take().unwrap_or_else(|| update.promise::<PagesNode>()))
             .collect();
         let kids: Vec<_> = kids_promise
             .iter()
             .map(|p| Ref::new(p.get_inner()))
             .collect();
+        let page_refs: Vec<PlainRef> = kids.iter().map(|r| r.get_inner()).collect();
 
         let tree = PagesRc::create(
             PageTree {
@@ -150,7 +361,8 @@ impl CatalogBuilder {
             update,
         )?;
 
-        for (page, promise) in self.pages.into_iter().zip(kids_promise) {
+        let structure = self.structure;
+        for (page_nr, (page, promise)) in self.pages.into_iter().zip(kids_promise).enumerate() {
             let content = Content::from_ops(page.ops);
             let resources = update.create(page.resources)?.into();
             let page = Page {
@@ -165,11 +377,39 @@ impl CatalogBuilder {
                 lgi: page.lgi,
                 vp: page.vp,
                 other: page.other,
-                annotations: Default::default(),
+                annotations: page.annotations,
+                thumb: page.thumb,
+                last_modified: page.last_modified,
+                piece_info: page.piece_info,
+                struct_parents: match structure {
+                    Some(ref s) if s.has_content(page_nr) => Some(page_nr as i32),
+                    _ => page.struct_parents,
+                },
             };
             update.fulfill(promise, PagesNode::Leaf(page))?;
         }
 
+        let (struct_tree_root, mark_info) = match structure {
+            Some(structure) => {
+                let root = structure.build(&page_refs, update)?;
+                let mark_info = MarkInformation {
+                    marked: true,
+                    user_properties: false,
+                    suspects: false,
+                };
+                (Some(root.into()), Some(mark_info))
+            }
+            None => (None, None),
+        };
+
+        let open_action = match self.open_at {
+            Some((page_nr, view)) => match page_refs.get(page_nr) {
+                Some(&page) => Some(OpenAction::Dest(Dest { page: Some(Ref::new(page)), view })),
+                None => bail!("open_at: page {} out of range", page_nr),
+            },
+            None => None,
+        };
+
         Ok(Catalog {
             version: Some("1.7".into()),
             pages: tree,
@@ -177,13 +417,188 @@ impl CatalogBuilder {
             dests: None,
             metadata: None,
             outlines: None,
-            struct_tree_root: None,
+            struct_tree_root,
+            mark_info,
             forms: None,
             page_labels: None,
+            viewer_preferences: None,
+            page_layout: None,
+            page_mode: None,
+            open_action,
+            threads: None,
+            lang: None,
+            output_intents: None,
+            collection: None,
+            requirements: vec![],
+            extensions: None,
+            perms: None,
+            piece_info: None,
+            spider_info: None,
+            legal: None,
         })
     }
 }
 
+/// Identifies a structure element of a [`StructureBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StructElemId(usize);
+
+struct PendingElem {
+    struct_type: StructType,
+    parent: Option<usize>,
+    kids: Vec<PendingKid>,
+    alt: Option<PdfString>,
+    actual_text: Option<PdfString>,
+    lang: Option<PdfString>,
+}
+enum PendingKid {
+    Elem(usize),
+    Content { page_nr: usize, mcid: i32 },
+}
+
+/// Builds the logical structure of a document (Tagged PDF) while its content is generated.
+///
+/// Content is assigned to structure elements with [`StructureBuilder::mark`], which
+/// wraps the operators in a `BDC`/`EMC` pair with a fresh MCID.
+/// Pass the builder to [`CatalogBuilder::structure`] to write the `StructTreeRoot`,
+/// `ParentTree` and `MarkInfo`.
+#[derive(Default)]
+pub struct StructureBuilder {
+    elems: Vec<PendingElem>,
+    /// for each page, the element each MCID belongs to
+    parents: Vec<Vec<usize>>,
+}
+impl StructureBuilder {
+    pub fn new() -> Self {
+        StructureBuilder::default()
+    }
+
+    /// Add a structure element. Elements without parent are children of the structure tree root.
+    pub fn add(&mut self, parent: Option<StructElemId>, struct_type: StructType) -> StructElemId {
+        let id = self.elems.len();
+        self.elems.push(PendingElem {
+            struct_type,
+            parent: parent.map(|p| p.0),
+            kids: vec![],
+            alt: None,
+            actual_text: None,
+            lang: None,
+        });
+        if let Some(StructElemId(parent)) = parent {
+            self.elems[parent].kids.push(PendingKid::Elem(id));
+        }
+        StructElemId(id)
+    }
+    pub fn set_alt(&mut self, elem: StructElemId, alt: PdfString) {
+        self.elems[elem.0].alt = Some(alt);
+    }
+    pub fn set_actual_text(&mut self, elem: StructElemId, text: PdfString) {
+        self.elems[elem.0].actual_text = Some(text);
+    }
+    pub fn set_lang(&mut self, elem: StructElemId, lang: PdfString) {
+        self.elems[elem.0].lang = Some(lang);
+    }
+
+    /// Wrap `ops` in a marked content sequence that belongs to `elem`.
+    ///
+    /// `page_nr` is the index of the page the returned operators are added to.
+    pub fn mark(&mut self, elem: StructElemId, page_nr: usize, ops: impl IntoIterator<Item = Op>) -> Vec<Op> {
+        if self.parents.len() <= page_nr {
+            self.parents.resize_with(page_nr + 1, Vec::new);
+        }
+        let mcid = self.parents[page_nr].len() as i32;
+        self.parents[page_nr].push(elem.0);
+        self.elems[elem.0].kids.push(PendingKid::Content { page_nr, mcid });
+
+        let tag = match self.elems[elem.0].struct_type.to_primitive(&mut NoUpdate) {
+            Ok(Primitive::Name(name)) => name.into(),
+            _ => "Span".into(),
+        };
+        let mut properties = Dictionary::new();
+        properties.insert("MCID", mcid);
+
+        let mut out = vec![Op::BeginMarkedContent { tag, properties: Some(properties.into()) }];
+        out.extend(ops);
+        out.push(Op::EndMarkedContent);
+        out
+    }
+
+    /// Whether any content on page `page_nr` has been marked.
+    pub fn has_content(&self, page_nr: usize) -> bool {
+        self.parents.get(page_nr).map(|p| !p.is_empty()).unwrap_or(false)
+    }
+
+    /// Write the structure tree. `pages` are the references of the pages, by page number.
+    pub fn build(self, pages: &[PlainRef], update: &mut impl Updater) -> Result<RcRef<StructTreeRoot>> {
+        let page_ref = |page_nr: usize| -> Result<PlainRef> {
+            pages.get(page_nr).cloned().ok_or(PdfError::PageOutOfBounds { page_nr: page_nr as u32, max: pages.len() as u32 })
+        };
+
+        let root_promise = update.promise::<StructTreeRoot>();
+        let promises: Vec<_> = self.elems.iter().map(|_| update.promise::<StructElem>()).collect();
+        let elem_refs: Vec<PlainRef> = promises.iter().map(|p| p.get_inner()).collect();
+
+        // children have higher ids than their parent, so fulfilling in reverse order
+        // makes sure the kids are available when the parent is written.
+        let mut done: Vec<Option<RcRef<StructElem>>> = self.elems.iter().map(|_| None).collect();
+        for (i, (elem, promise)) in self.elems.into_iter().zip(promises).enumerate().rev() {
+            let page_nr = elem.kids.iter().find_map(|kid| match *kid {
+                PendingKid::Content { page_nr, .. } => Some(page_nr),
+                _ => None,
+            });
+            let page = page_nr.map(page_ref).transpose()?;
+            let mut children = Vec::with_capacity(elem.kids.len());
+            for kid in elem.kids {
+                children.push(match kid {
+                    PendingKid::Elem(k) => StructKid::Elem(done[k].take().unwrap().into()),
+                    PendingKid::Content { page_nr, mcid } => {
+                        let kid_page = page_ref(page_nr)?;
+                        StructKid::MarkedContent {
+                            mcid,
+                            page: if Some(kid_page) == page { None } else { Some(Ref::new(kid_page)) },
+                            stream: None,
+                        }
+                    }
+                });
+            }
+            let parent = elem.parent.map(|p| elem_refs[p]).unwrap_or(root_promise.get_inner());
+            let new = StructElem {
+                struct_type: elem.struct_type,
+                parent: Ref::new(parent),
+                id: None,
+                page: page.map(Ref::new),
+                children,
+                title: None,
+                lang: elem.lang,
+                alt: elem.alt,
+                expansion: None,
+                actual_text: elem.actual_text,
+            };
+            done[i] = Some(update.fulfill(promise, new)?);
+        }
+
+        let children = done.into_iter().flatten().map(MaybeRef::Indirect).collect();
+        let nums = self.parents.iter().enumerate()
+            .filter(|(_, mcids)| !mcids.is_empty())
+            .map(|(page_nr, mcids)| {
+                let entry = ParentTreeEntry::Array(mcids.iter().map(|&e| Some(elem_refs[e])).collect());
+                (page_nr as i32, entry)
+            })
+            .collect();
+        let root = StructTreeRoot {
+            children,
+            parent_tree: Some(NumberTree {
+                limits: None,
+                node: NumberTreeNode::Leaf(nums),
+            }),
+            parent_tree_next_key: Some(self.parents.len() as i32),
+            role_map: HashMap::new(),
+            class_map: None,
+        };
+        update.fulfill(root_promise, root)
+    }
+}
+
 pub struct PdfBuilder<SC, OC, L> {
     pub storage: Storage<Vec<u8>, SC, OC, L>,
     pub info: Option<InfoDict>,
@@ -211,6 +626,11 @@ where
         self.id = Some([a, b]);
         self
     }
+    /// Merge identical fonts, images and other resources when writing, see [`Storage::dedup`].
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.storage.set_dedup(dedup);
+        self
+    }
     pub fn build(mut self, catalog: CatalogBuilder) -> Result<Vec<u8>> {
         let catalog = catalog.build(&mut self.storage)?;
 
@@ -226,11 +646,53 @@ where
         Ok(self.storage.into_inner())
     }
 }
+/// A page copied out of its document, together with everything it references.
+///
+/// The objects live in an in-memory storage owned by the snapshot and share nothing
+/// with the original `File`, so the snapshot can be moved to another thread.
+/// The content stream is re-encoded and only the resources it uses are copied.
+/// Annotations and the structure tree are not part of the snapshot.
+pub struct PageSnapshot {
+    storage: Storage<Vec<u8>, NoCache, NoCache, NoLog>,
+    root: RcRef<Catalog>,
+}
+impl PageSnapshot {
+    pub fn new(page: &Page, resolve: impl Resolve) -> Result<PageSnapshot> {
+        let mut storage = Storage::empty(NoCache, NoCache, NoLog);
+        let mut importer = Importer::new(resolve, &mut storage);
+        let page = PageBuilder::clone_page(page, &mut importer)?;
+        let catalog = CatalogBuilder::from_pages(vec![page]).build(&mut storage)?;
+        let root = storage.create(catalog)?;
+        Ok(PageSnapshot { storage, root })
+    }
+    pub fn resolver(&self) -> impl Resolve + '_ {
+        self.storage.resolver()
+    }
+    pub fn page(&self) -> Result<PageRc> {
+        self.root.pages.page(&self.resolver(), 0)
+    }
+    /// Write the snapshot as a PDF file with a single page.
+    pub fn into_pdf(mut self) -> Result<Vec<u8>> {
+        let mut trailer = Trailer {
+            root: self.root,
+            encrypt_dict: None,
+            size: 0,
+            id: vec![],
+            info_dict: None,
+            prev_trailer_pos: None,
+        };
+        self.storage.save(&mut trailer)?;
+        Ok(self.storage.into_inner())
+    }
+}
+
 pub struct Importer<'a, R, U> {
     resolver: R,
     map: HashMap<PlainRef, PlainRef>,
     updater: &'a mut U,
     rcrefs: HashMap<PlainRef, AnySync>,
+    // old references of the `RcRef`s that are being cloned
+    pending: Vec<PlainRef>,
     // ptr of old -> (old, new)
     shared: HashMap<usize, (AnySync, AnySync)>,
 }
@@ -247,6 +709,7 @@ impl<'a, R, U> Importer<'a, R, U> {
             updater,
             map: Default::default(),
             rcrefs: Default::default(),
+            pending: Default::default(),
             shared: Default::default(),
         }
     }
@@ -401,6 +864,8 @@ impl<'a, R, U: Updater> Updater for Importer<'a, R, U> {
     }
 }
 impl<'a, R: Resolve, U: Updater> Cloner for Importer<'a, R, U> {
+    // The new reference is reserved before the object is cloned,
+    // so that references back to it from within (like /Parent) are cloned to it.
     fn clone_ref<T: DeepClone + Object + DataSize + ObjectWrite>(
         &mut self,
         old: Ref<T>,
@@ -409,44 +874,74 @@ impl<'a, R: Resolve, U: Updater> Cloner for Importer<'a, R, U> {
             return Ok(Ref::new(new_ref));
         }
         let obj = self.resolver.get(old)?;
-        let clone = obj.deep_clone(self)?;
-
-        let r = self.updater.create(clone)?;
-        self.map.insert(old.get_inner(), r.get_ref().get_inner());
+        let promise = self.updater.promise::<T>();
+        self.map.insert(old.get_inner(), promise.get_inner());
+        let clone = match obj.deep_clone(self) {
+            Ok(clone) => clone,
+            Err(e) => {
+                // don't hand out the reference of a clone that doesn't exist
+                self.map.remove(&old.get_inner());
+                return Err(e);
+            }
+        };
 
-        Ok(r.get_ref())
+        Ok(self.updater.fulfill(promise, clone)?.get_ref())
     }
     fn clone_plainref(&mut self, old: PlainRef) -> Result<PlainRef> {
         if let Some(&new_ref) = self.map.get(&old) {
             return Ok(new_ref);
         }
         let obj = self.resolver.resolve(old)?;
-        let clone = obj.deep_clone(self)?;
-
-        let new = self.updater.create(clone)?.get_ref().get_inner();
-
-        self.map.insert(old, new);
+        let promise = self.updater.promise::<Primitive>();
+        self.map.insert(old, promise.get_inner());
+        let clone = match obj.deep_clone(self) {
+            Ok(clone) => clone,
+            Err(e) => {
+                self.map.remove(&old);
+                return Err(e);
+            }
+        };
 
-        Ok(new)
+        Ok(self.updater.fulfill(promise, clone)?.get_ref().get_inner())
     }
+    // An `RcRef` needs the cloned data, so cycles of them can't be cloned.
     fn clone_rcref<T: DeepClone + ObjectWrite + DataSize>(
         &mut self,
         old: &RcRef<T>,
     ) -> Result<RcRef<T>> {
         let old_ref = old.get_ref().get_inner();
+        if self.pending.contains(&old_ref) {
+            return Err(PdfError::ReferenceCycle {
+                obj_nr: old_ref.id,
+                chain: self.pending.iter().map(|r| r.id).collect(),
+            });
+        }
         if let Some(&new_ref) = self.map.get(&old_ref) {
-            let arc = self.rcrefs.get(&new_ref).unwrap().clone().downcast()?;
-            return Ok(RcRef::new(new_ref, arc));
+            if let Some(any) = self.rcrefs.get(&new_ref) {
+                return Ok(RcRef::new(new_ref, any.clone().downcast()?));
+            }
+            // cloned as a plain reference, only the data is needed
+            self.pending.push(old_ref);
+            let data = old.data().deep_clone(self);
+            self.pending.pop();
+            let data = Shared::new(data?);
+            self.rcrefs.insert(new_ref, AnySync::new(data.clone()));
+            return Ok(RcRef::new(new_ref, data));
         }
 
-        let new = old.data().deep_clone(self)?;
-        let new = self.updater.create::<T>(new)?;
+        self.pending.push(old_ref);
+        let new = old.data().deep_clone(self);
+        self.pending.pop();
+        let new = self.updater.create::<T>(new?)?;
         self.rcrefs
             .insert(new.get_ref().get_inner(), AnySync::new(new.data().clone()));
         self.map.insert(old_ref, new.get_ref().get_inner());
 
         Ok(new)
     }
+    fn map_ref(&mut self, old: PlainRef, new: PlainRef) {
+        self.map.insert(old, new);
+    }
     fn clone_shared<T: DeepClone>(&mut self, old: &Shared<T>) -> Result<Shared<T>> {
         let key = &**old as *const T as usize;
         if let Some((_old, new)) = self.shared.get(&key) {
@@ -0,0 +1,386 @@
+//! CMaps of Type 0 fonts: how the bytes of a string are split into character codes and
+//! which CID each code selects.
+//!
+//! A Type 0 font either embeds its CMap or names a predefined one, like `UniJIS-UCS2-H`.
+//! `Identity-H` and `Identity-V` are built in. The others are not part of the PDF file and
+//! are loaded through the [`CMapProvider`] set with [`ParseOptions::cmaps`], for example
+//! a [`CMapDirectory`] pointing to a copy of Adobe's `cmap-resources`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::error::*;
+use crate::object::{NoResolve, ParseOptions};
+use crate::parser::{parse_with_lexer, Lexer, ParseFlags};
+use crate::primitive::{Name, PdfString, Primitive};
+
+/// How many CMaps may be chained with `usecmap`.
+const MAX_USECMAP_DEPTH: usize = 8;
+
+/// A range of codes with the same number of bytes. Each byte of a code lies between
+/// the bytes of `low` and `high` at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CodespaceRange {
+    len: usize,
+    low: [u8; 4],
+    high: [u8; 4],
+}
+impl CodespaceRange {
+    fn contains(&self, bytes: &[u8]) -> bool {
+        bytes.len() == self.len
+            && bytes.iter().enumerate().all(|(i, &b)| self.low[i] <= b && b <= self.high[i])
+    }
+}
+
+/// Codes `start..=end` of `len` bytes map to consecutive CIDs starting at `cid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CidRange {
+    len: usize,
+    start: u32,
+    end: u32,
+    cid: u32,
+}
+
+/// A parsed CMap.
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    name: Option<String>,
+    wmode: i32,
+    use_cmap: Option<String>,
+    codespace: Vec<CodespaceRange>,
+    cids: Vec<CidRange>,
+    notdef: Vec<CidRange>,
+}
+impl CMap {
+    /// `Identity-H` or, if `vertical`, `Identity-V`: two byte codes that are their own CIDs.
+    pub fn identity(vertical: bool) -> CMap {
+        let range = CodespaceRange { len: 2, low: [0; 4], high: [0xff, 0xff, 0, 0] };
+        CMap {
+            name: Some(if vertical { "Identity-V" } else { "Identity-H" }.into()),
+            wmode: vertical as i32,
+            use_cmap: None,
+            codespace: vec![range],
+            cids: vec![CidRange { len: 2, start: 0, end: 0xffff, cid: 0 }],
+            notdef: vec![],
+        }
+    }
+    /// Parse the CMap program in `data`.
+    ///
+    /// A CMap based on another one with `usecmap` only contains its own mappings;
+    /// see [`use_cmap`](CMap::use_cmap) and [`with_parent`](CMap::with_parent).
+    pub fn parse(data: &[u8]) -> Result<CMap> {
+        let mut lexer = Lexer::new(data);
+        let mut cmap = CMap::default();
+        // the two tokens before the current one, for `/Key value def` and `/Name usecmap`
+        let mut prev: [&[u8]; 2] = [b"", b""];
+        while let Ok(token) = lexer.next() {
+            let token = token.as_slice();
+            match token {
+                b"begincodespacerange" => loop {
+                    let (low, high) = match (code_string(&mut lexer), code_string(&mut lexer)) {
+                        (Some(low), Some(high)) => (low, high),
+                        _ => break,
+                    };
+                    let (low, high) = (low.as_bytes(), high.as_bytes());
+                    if low.len() != high.len() || low.is_empty() || low.len() > 4 {
+                        bail!("invalid codespace range {:?}..{:?}", low, high);
+                    }
+                    let mut range = CodespaceRange { len: low.len(), low: [0; 4], high: [0; 4] };
+                    range.low[..low.len()].copy_from_slice(low);
+                    range.high[..high.len()].copy_from_slice(high);
+                    cmap.codespace.push(range);
+                },
+                b"begincidrange" | b"beginnotdefrange" => loop {
+                    let (start, end) = match (code_string(&mut lexer), code_string(&mut lexer)) {
+                        (Some(start), Some(end)) => (start, end),
+                        _ => break,
+                    };
+                    let cid = t!(parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::INTEGER)).as_u32()?;
+                    let range = t!(code_range(&start, &end, cid));
+                    if token == b"begincidrange" {
+                        cmap.cids.push(range);
+                    } else {
+                        cmap.notdef.push(range);
+                    }
+                },
+                b"begincidchar" | b"beginnotdefchar" => loop {
+                    let code = match code_string(&mut lexer) {
+                        Some(code) => code,
+                        None => break,
+                    };
+                    let cid = t!(parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::INTEGER)).as_u32()?;
+                    let range = t!(code_range(&code, &code, cid));
+                    if token == b"begincidchar" {
+                        cmap.cids.push(range);
+                    } else {
+                        cmap.notdef.push(range);
+                    }
+                },
+                b"usecmap" => cmap.use_cmap = name_token(prev[1]),
+                b"def" => match prev[0] {
+                    b"/CMapName" => cmap.name = name_token(prev[1]),
+                    b"/WMode" => cmap.wmode = std::str::from_utf8(prev[1]).ok()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0),
+                    _ => {}
+                },
+                _ => {}
+            }
+            prev = [prev[1], token];
+        }
+        Ok(cmap)
+    }
+    /// The `CMapName`, if it is given.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// Whether the glyphs are laid out vertically (`WMode` 1).
+    pub fn is_vertical(&self) -> bool {
+        self.wmode == 1
+    }
+    /// Override the `WMode` of the program, as the stream dictionary of an embedded CMap can.
+    pub fn set_vertical(&mut self, vertical: bool) {
+        self.wmode = vertical as i32;
+    }
+    /// The CMap this one is based on, by name.
+    pub fn use_cmap(&self) -> Option<&str> {
+        self.use_cmap.as_deref()
+    }
+    /// Add the codespace and mappings of `parent` to this CMap. Mappings of this CMap
+    /// take precedence.
+    pub fn with_parent(mut self, parent: &CMap) -> CMap {
+        self.codespace.splice(0..0, parent.codespace.iter().cloned());
+        self.cids.splice(0..0, parent.cids.iter().cloned());
+        self.notdef.splice(0..0, parent.notdef.iter().cloned());
+        self.use_cmap = None;
+        self
+    }
+    /// Split off the code at the start of `data`. Returns the code and its length in bytes.
+    ///
+    /// Bytes that do not start a code in the codespace are taken as one code of the length
+    /// of the shortest codespace range.
+    pub fn next_code(&self, data: &[u8]) -> Option<(u32, usize)> {
+        if data.is_empty() {
+            return None;
+        }
+        for len in 1..=data.len().min(4) {
+            if self.codespace.iter().any(|r| r.contains(&data[..len])) {
+                return Some((code_value(&data[..len]), len));
+            }
+        }
+        let len = self.codespace.iter().map(|r| r.len).min().unwrap_or(1).min(data.len());
+        Some((code_value(&data[..len]), len))
+    }
+    /// Split `data` into codes, each with its length in bytes.
+    pub fn codes<'a>(&'a self, mut data: &'a [u8]) -> impl Iterator<Item = (u32, usize)> + 'a {
+        std::iter::from_fn(move || {
+            let (code, len) = self.next_code(data)?;
+            data = &data[len..];
+            Some((code, len))
+        })
+    }
+    /// The CID of the `len` byte `code`, or the one for undefined characters if it is
+    /// not mapped.
+    pub fn cid(&self, code: u32, len: usize) -> Option<u32> {
+        let find = |ranges: &[CidRange]| ranges.iter().rev()
+            .find(|r| r.len == len && r.start <= code && code <= r.end).copied();
+        match find(&self.cids) {
+            Some(r) => Some(r.cid + (code - r.start)),
+            // all codes of a notdef range map to the same CID
+            None => find(&self.notdef).map(|r| r.cid),
+        }
+    }
+    /// The number of bytes a code takes: the length of the shortest codespace range that
+    /// contains it.
+    pub fn code_len(&self, code: u32) -> Option<usize> {
+        self.codespace.iter()
+            .filter(|r| r.contains(&code.to_be_bytes()[4 - r.len..]) && (r.len == 4 || code >> (8 * r.len) == 0))
+            .map(|r| r.len)
+            .min()
+    }
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |v, &b| v << 8 | b as u32)
+}
+
+fn code_string(lexer: &mut Lexer) -> Option<PdfString> {
+    match parse_with_lexer(lexer, &NoResolve, ParseFlags::STRING) {
+        Ok(Primitive::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn code_range(start: &PdfString, end: &PdfString, cid: u32) -> Result<CidRange> {
+    let (start, end) = (start.as_bytes(), end.as_bytes());
+    if start.len() != end.len() || start.is_empty() || start.len() > 4 {
+        bail!("invalid code range {:?}..{:?}", start, end);
+    }
+    Ok(CidRange { len: start.len(), start: code_value(start), end: code_value(end), cid })
+}
+
+fn name_token(token: &[u8]) -> Option<String> {
+    token.strip_prefix(b"/").map(|name| String::from_utf8_lossy(name).into_owned())
+}
+
+/// Loads predefined CMaps by name.
+pub trait CMapProvider: fmt::Debug + Send + Sync {
+    /// The predefined CMap `name`, with the CMaps it uses merged in, or `None` if it is
+    /// not available.
+    fn load(&self, name: &str) -> Option<Result<Arc<CMap>>>;
+}
+
+/// Loads CMaps from the files in a directory, each named like its CMap,
+/// like the `CMap` directories of Adobe's `cmap-resources`.
+///
+/// Parsed CMaps are kept for later use.
+#[derive(Debug)]
+pub struct CMapDirectory {
+    path: PathBuf,
+    cache: Mutex<HashMap<String, Arc<CMap>>>,
+}
+impl CMapDirectory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CMapDirectory {
+            path: path.into(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+    fn load_nested(&self, name: &str, depth: usize) -> Option<Result<Arc<CMap>>> {
+        if let Some(cmap) = self.cache.lock().unwrap().get(name) {
+            return Some(Ok(cmap.clone()));
+        }
+        // the name comes from the file, it must not leave the directory
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return None;
+        }
+        let path = self.path.join(name);
+        if !path.is_file() {
+            return None;
+        }
+        let load = || -> Result<Arc<CMap>> {
+            let mut cmap = CMap::parse(&std::fs::read(&path)?)?;
+            if let Some(parent) = cmap.use_cmap.clone() {
+                if depth >= MAX_USECMAP_DEPTH {
+                    bail!("too many nested usecmap in {}", name);
+                }
+                let parent = match parent.as_str() {
+                    "Identity-H" | "Identity-V" => Arc::new(CMap::identity(parent == "Identity-V")),
+                    _ => self.load_nested(&parent, depth + 1)
+                        .ok_or_else(|| other!("CMap {} used by {} not found", parent, name))??,
+                };
+                cmap = cmap.with_parent(&parent);
+            }
+            Ok(Arc::new(cmap))
+        };
+        let result = load();
+        if let Ok(ref cmap) = result {
+            self.cache.lock().unwrap().insert(name.into(), cmap.clone());
+        }
+        Some(result)
+    }
+}
+impl CMapProvider for CMapDirectory {
+    fn load(&self, name: &str) -> Option<Result<Arc<CMap>>> {
+        self.load_nested(name, 0)
+    }
+}
+
+/// Look up a predefined CMap: `Identity-H` and `Identity-V` are built in,
+/// all others come from the provider in `options`.
+pub fn predefined_cmap(name: &Name, options: &ParseOptions) -> Result<Arc<CMap>> {
+    match name.as_str() {
+        "Identity-H" => Ok(Arc::new(CMap::identity(false))),
+        "Identity-V" => Ok(Arc::new(CMap::identity(true))),
+        name => match options.cmaps {
+            Some(ref provider) => provider.load(name)
+                .ok_or_else(|| other!("predefined CMap {} not found", name))?,
+            None => Err(other!("predefined CMap {} needs a CMapProvider", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CMAP: &[u8] = b"%!PS-Adobe-3.0 Resource-CMap
+/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo 3 dict dup begin
+  /Registry (Adobe) def
+  /Ordering (Japan1) def
+  /Supplement 6 def
+end def
+/CMapName /Test-H def
+/CMapVersion 1.0 def
+/CMapType 1 def
+/WMode 0 def
+2 begincodespacerange
+<00> <80>
+<8140> <9ffc>
+endcodespacerange
+2 begincidrange
+<20> <7e> 1
+<8140> <817e> 633
+endcidrange
+1 begincidchar
+<41> 900
+endcidchar
+1 beginnotdefrange
+<00> <1f> 231
+endnotdefrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end
+";
+
+    #[test]
+    fn parse_and_map() {
+        let cmap = CMap::parse(CMAP).unwrap();
+        assert_eq!(cmap.name(), Some("Test-H"));
+        assert!(!cmap.is_vertical());
+        let codes: Vec<_> = cmap.codes(b"A\x81\x42\x05\xff").collect();
+        assert_eq!(codes, [(0x41, 1), (0x8142, 2), (0x05, 1), (0xff, 1)]);
+        // the later cidchar wins over the range
+        assert_eq!(cmap.cid(0x41, 1), Some(900));
+        assert_eq!(cmap.cid(0x42, 1), Some(35));
+        assert_eq!(cmap.cid(0x8142, 2), Some(635));
+        assert_eq!(cmap.cid(0x05, 1), Some(231));
+        assert_eq!(cmap.cid(0x8142, 1), None);
+        assert_eq!(cmap.code_len(0x41), Some(1));
+        assert_eq!(cmap.code_len(0x8142), Some(2));
+        assert_eq!(cmap.code_len(0xa0), None);
+
+        let vertical = CMap::parse(b"/CMapName /Test-V def /WMode 1 def /Test-H usecmap").unwrap();
+        assert!(vertical.is_vertical());
+        assert_eq!(vertical.use_cmap(), Some("Test-H"));
+        let merged = vertical.with_parent(&cmap);
+        assert_eq!(merged.cid(0x8142, 2), Some(635));
+        assert!(merged.is_vertical());
+    }
+
+    #[test]
+    fn directory() {
+        let dir = std::env::temp_dir().join(format!("pdf-cmaps-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Test-H"), CMAP).unwrap();
+        std::fs::write(dir.join("Test-V"), b"/CMapName /Test-V def /WMode 1 def /Test-H usecmap").unwrap();
+        let provider = CMapDirectory::new(&dir);
+        let cmap = provider.load("Test-V").unwrap().unwrap();
+        assert!(cmap.is_vertical());
+        assert_eq!(cmap.cid(0x41, 1), Some(900));
+        assert!(provider.load("Missing-H").is_none());
+        assert!(provider.load("../Test-H").is_none());
+
+        let options = ParseOptions::tolerant().cmaps(Arc::new(provider));
+        assert!(predefined_cmap(&"Test-H".into(), &options).is_ok());
+        assert!(predefined_cmap(&"Test-H".into(), &ParseOptions::tolerant()).is_err());
+        assert!(predefined_cmap(&"Identity-V".into(), &ParseOptions::tolerant()).unwrap().is_vertical());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
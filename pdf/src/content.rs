@@ -1,10 +1,11 @@
 /// PDF content streams.
 use std::fmt::{self, Display};
 use std::cmp::Ordering;
-use itertools::Itertools;
 use istring::SmallString;
 use datasize::DataSize;
 use std::sync::Arc;
+use std::collections::HashSet;
+use std::collections::hash_map::Entry;
 
 use crate::error::*;
 use crate::object::*;
@@ -15,18 +16,38 @@ use crate as pdf;
 
 /// Represents a PDF content stream - a `Vec` of `Operator`s
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Content {
     /// The raw content stream parts. usually one, but could be any number.
     pub parts: Vec<Stream<()>>,
 }
 
 impl Content {
-    pub fn operations(&self, resolve: &impl Resolve) -> Result<Vec<Op>> {
+    /// The data of all parts as one content stream.
+    ///
+    /// A part can end between the operands and their operator, but not inside a token, so the
+    /// parts are joined with a line break where they do not end with white space already.
+    pub fn data(&self, resolve: &impl Resolve) -> Result<Vec<u8>> {
         let mut data = vec![];
         for part in self.parts.iter() {
+            if data.last().is_some_and(|b: &u8| !b.is_ascii_whitespace()) {
+                data.push(b'\n');
+            }
             data.extend_from_slice(&t!(part.data(resolve)));
         }
-        parse_ops(&data, resolve)
+        Ok(data)
+    }
+
+    pub fn operations(&self, resolve: &impl Resolve) -> Result<Vec<Op>> {
+        parse_ops(&t!(self.data(resolve)), resolve)
+    }
+
+    /// The content as a single stream.
+    pub fn normalized(&self, resolve: &impl Resolve) -> Result<Content> {
+        if self.parts.len() == 1 {
+            return Ok(self.clone());
+        }
+        Ok(Content { parts: vec![Stream::new((), t!(self.data(resolve)))] })
     }
 }
 
@@ -125,8 +146,41 @@ fn expand_abbr(p: Primitive, alt: &[(&str, &str)]) -> Primitive {
     }
 }
 
-fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXObject>> {
-    let mut dict = Dictionary::new();
+/// An inline image (`BI` … `ID` … `EI`).
+///
+/// The parameters and data are kept as written so the image is serialized unchanged.
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    /// The parameters in their original order, with abbreviated keys and values.
+    pub params: Vec<(Name, Primitive)>,
+
+    /// The encoded data between `ID` and `EI`.
+    pub data: Arc<[u8]>,
+
+    /// The image with all abbreviations expanded.
+    pub image: Arc<ImageXObject>,
+}
+impl InlineImage {
+    /// Parse the image from its parameters and encoded data.
+    pub fn new(params: Vec<(Name, Primitive)>, data: impl Into<Arc<[u8]>>, resolve: &impl Resolve) -> Result<InlineImage> {
+        let data = data.into();
+        let image = t!(inline_image_object(&params, data.clone(), resolve));
+        Ok(InlineImage { params, data, image: Arc::new(image) })
+    }
+}
+impl DataSize for InlineImage {
+    const IS_DYNAMIC: bool = true;
+    const STATIC_HEAP_SIZE: usize = 0;
+
+    fn estimate_heap_size(&self) -> usize {
+        self.params.iter().map(|(k, v)| k.estimate_heap_size() + v.estimate_heap_size()).sum::<usize>()
+            + self.data.len()
+            + self.image.estimate_heap_size()
+    }
+}
+
+fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<InlineImage> {
+    let mut params = vec![];
     loop {
         let backup_pos = lexer.get_pos();
         let obj = parse_with_lexer(lexer, &NoResolve, ParseFlags::ANY);
@@ -139,7 +193,26 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
             }
             Ok(_) => bail!("invalid key type")
         };
-        let key = expand_abbr_name(key, &[
+        let val = parse_with_lexer(lexer, &NoResolve, ParseFlags::ANY)?;
+        params.push((Name(key), val));
+    }
+    lexer.next_expect("ID")?;
+    let data_start = lexer.get_pos() + 1;
+
+    // find the end before try parsing.
+    if lexer.seek_substr("\nEI").is_none() {
+        bail!("inline image exceeds expected data range");
+    }    
+    let data_end = lexer.get_pos() - 3;
+    let data = lexer.new_substr(data_start .. data_end).to_vec();
+
+    InlineImage::new(params, data, resolve)
+}
+
+fn inline_image_object(params: &[(Name, Primitive)], data: Arc<[u8]>, resolve: &impl Resolve) -> Result<ImageXObject> {
+    let mut dict = Dictionary::new();
+    for (key, val) in params {
+        let key = expand_abbr_name(key.0.clone(), &[
             ("BPC", "BitsPerComponent"),
             ("CS", "ColorSpace"),
             ("D", "Decode"),
@@ -150,17 +223,8 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
             ("I", "Interpolate"),
             ("W", "Width"),
         ]);
-        let val = parse_with_lexer(lexer, &NoResolve, ParseFlags::ANY)?;
-        dict.insert(key, val);
+        dict.insert(key, val.clone());
     }
-    lexer.next_expect("ID")?;
-    let data_start = lexer.get_pos() + 1;
-
-    // find the end before try parsing.
-    if lexer.seek_substr("\nEI").is_none() {
-        bail!("inline image exceeds expected data range");
-    }    
-    let data_end = lexer.get_pos() - 3;
 
     // ugh
     let bits_per_component = dict.get("BitsPerComponent").map(|p| p.as_integer()).transpose()?;
@@ -213,12 +277,11 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
         struct_parent: None,
         id: None,
         smask: None,
+        matte: None,
         other: dict,
     };
 
-    let data = lexer.new_substr(data_start .. data_end).to_vec();
-
-    Ok(Arc::new(ImageXObject { inner: Stream::from_compressed(image_dict, data, filters) }))
+    Ok(ImageXObject { inner: Stream::from_compressed(image_dict, data, filters) })
 }
 
 struct OpBuilder {
@@ -292,7 +355,7 @@ impl OpBuilder {
                 tag: name(&mut args)?,
                 properties: Some(args.next().ok_or(PdfError::NoOpArg)?)
             }),
-            "BI"  => push(Op::InlineImage { image: inline_image(lexer, resolve)? }),
+            "BI"  => push(Op::InlineImage { image: Arc::new(inline_image(lexer, resolve)?) }),
             "BMC" => push(Op::BeginMarkedContent {
                 tag: name(&mut args)?,
                 properties: None
@@ -516,6 +579,7 @@ impl Object for Content {
 }
 
 #[derive(Debug, DataSize, DeepClone, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FormXObject {
     pub stream: Stream<FormDict>,
 }
@@ -548,9 +612,16 @@ impl ObjectWrite for FormXObject {
 }
 
 #[allow(clippy::float_cmp)]  // TODO
-pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
+pub fn serialize_ops(ops: &[Op]) -> Result<Vec<u8>> {
+    serialize_ops_with(ops, NumberFormat::EXACT)
+}
+
+/// Like [`serialize_ops`], writing the numeric operands with `format`.
+#[allow(clippy::float_cmp)]  // TODO
+pub fn serialize_ops_with(mut ops: &[Op], format: NumberFormat) -> Result<Vec<u8>> {
     use std::io::Write;
 
+    let options = WriteOptions { numbers: format, ..Default::default() };
     let mut data = Vec::new();
     let mut current_point = None;
     let f = &mut data;
@@ -561,7 +632,7 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
             Op::BeginMarkedContent { ref tag, properties: Some(ref name) } => {
                 serialize_name(tag, f)?;
                 write!(f, " ")?;
-                name.serialize(f)?;
+                name.serialize_with(f, options)?;
                 writeln!(f, " BDC")?;
             }
             Op::BeginMarkedContent { ref tag, properties: None } => {
@@ -571,7 +642,7 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
             Op::MarkedContentPoint { ref tag, properties: Some(ref name) } => {
                 serialize_name(tag, f)?;
                 write!(f, " ")?;
-                name.serialize(f)?;
+                name.serialize_with(f, options)?;
                 writeln!(f, " DP")?;
             }
             Op::MarkedContentPoint { ref tag, properties: None } => {
@@ -595,24 +666,24 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                 _ => writeln!(f, "h")?,
             }
             Op::MoveTo { p } => {
-                writeln!(f, "{} m", p)?;
+                writeln!(f, "{} m", Numbers(&[p.x, p.y], format))?;
                 current_point = Some(p);
             }
             Op::LineTo { p } => {
-                writeln!(f, "{} l", p)?;
+                writeln!(f, "{} l", Numbers(&[p.x, p.y], format))?;
                 current_point = Some(p);
             },
             Op::CurveTo { c1, c2, p } => {
                 if Some(c1) == current_point {
-                    writeln!(f, "{} {} v", c2, p)?;
+                    writeln!(f, "{} v", Numbers(&[c2.x, c2.y, p.x, p.y], format))?;
                 } else if c2 == p {
-                    writeln!(f, "{} {} y", c1, p)?;
+                    writeln!(f, "{} y", Numbers(&[c1.x, c1.y, p.x, p.y], format))?;
                 } else {
-                    writeln!(f, "{} {} {} c", c1, c2, p)?;
+                    writeln!(f, "{} c", Numbers(&[c1.x, c1.y, c2.x, c2.y, p.x, p.y], format))?;
                 }
                 current_point = Some(p);
             },
-            Op::Rect { rect } => writeln!(f, "{} re", rect)?,
+            Op::Rect { rect } => writeln!(f, "{} re", Numbers(&[rect.x, rect.y, rect.width, rect.height], format))?,
             Op::EndPath => writeln!(f, "n")?,
             Op::Stroke => writeln!(f, "S")?,
             Op::FillAndStroke { winding: Winding::NonZero } => writeln!(f, "B")?,
@@ -627,33 +698,33 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
             Op::Clip { winding: Winding::EvenOdd } => writeln!(f, "W*")?,
             Op::Save => writeln!(f, "q")?,
             Op::Restore => writeln!(f, "Q")?,
-            Op::Transform { matrix } => writeln!(f, "{} cm", matrix)?,
-            Op::LineWidth { width } => writeln!(f, "{} w", width)?,
-            Op::Dash { ref pattern, phase } => writeln!(f, "[{}] {} d", pattern.iter().format(" "), phase)?,
+            Op::Transform { matrix } => writeln!(f, "{} cm", Numbers(&[matrix.a, matrix.b, matrix.c, matrix.d, matrix.e, matrix.f], format))?,
+            Op::LineWidth { width } => writeln!(f, "{} w", Numbers(&[width], format))?,
+            Op::Dash { ref pattern, phase } => writeln!(f, "[{}] {} d", Numbers(pattern, format), Numbers(&[phase], format))?,
             Op::LineJoin { join } => writeln!(f, "{} j", join as u8)?,
             Op::LineCap { cap } => writeln!(f, "{} J", cap as u8)?,
-            Op::MiterLimit { limit } => writeln!(f, "{} M", limit)?,
-            Op::Flatness { tolerance } => writeln!(f, "{} i", tolerance)?,
+            Op::MiterLimit { limit } => writeln!(f, "{} M", Numbers(&[limit], format))?,
+            Op::Flatness { tolerance } => writeln!(f, "{} i", Numbers(&[tolerance], format))?,
             Op::GraphicsState { ref name } => {
                 serialize_name(name, f)?;
                 writeln!(f, " gs")?;
             },
-            Op::StrokeColor { color: Color::Gray(g) } => writeln!(f, "{} G", g)?,
-            Op::StrokeColor { color: Color::Rgb(rgb) } => writeln!(f, "{} RG", rgb)?,
-            Op::StrokeColor { color: Color::Cmyk(cmyk) } => writeln!(f, "{} K", cmyk)?,
+            Op::StrokeColor { color: Color::Gray(g) } => writeln!(f, "{} G", Numbers(&[g], format))?,
+            Op::StrokeColor { color: Color::Rgb(rgb) } => writeln!(f, "{} RG", Numbers(&[rgb.red, rgb.green, rgb.blue], format))?,
+            Op::StrokeColor { color: Color::Cmyk(cmyk) } => writeln!(f, "{} K", Numbers(&[cmyk.cyan, cmyk.magenta, cmyk.yellow, cmyk.key], format))?,
             Op::StrokeColor { color: Color::Other(ref args) } =>  {
                 for p in args {
-                    p.serialize(f)?;
+                    p.serialize_with(f, options)?;
                     write!(f, " ")?;
                 }
                 writeln!(f, "SCN")?;
             }
-            Op::FillColor { color: Color::Gray(g) } => writeln!(f, "{} g", g)?,
-            Op::FillColor { color: Color::Rgb(rgb) } => writeln!(f, "{} rg", rgb)?,
-            Op::FillColor { color: Color::Cmyk(cmyk) } => writeln!(f, "{} k", cmyk)?,
+            Op::FillColor { color: Color::Gray(g) } => writeln!(f, "{} g", Numbers(&[g], format))?,
+            Op::FillColor { color: Color::Rgb(rgb) } => writeln!(f, "{} rg", Numbers(&[rgb.red, rgb.green, rgb.blue], format))?,
+            Op::FillColor { color: Color::Cmyk(cmyk) } => writeln!(f, "{} k", Numbers(&[cmyk.cyan, cmyk.magenta, cmyk.yellow, cmyk.key], format))?,
             Op::FillColor { color: Color::Other(ref args) } => {
                 for p in args {
-                    p.serialize(f)?;
+                    p.serialize_with(f, options)?;
                     write!(f, " ")?;
                 }
                 writeln!(f, "scn")?;
@@ -670,7 +741,7 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
             Op::RenderingIntent { intent } => writeln!(f, "{} ri", intent.to_str())?,
             Op::BeginText => writeln!(f, "BT")?,
             Op::EndText => writeln!(f, "ET")?,
-            Op::CharSpacing { char_space } => writeln!(f, "{} Tc", char_space)?,
+            Op::CharSpacing { char_space } => writeln!(f, "{} Tc", Numbers(&[char_space], format))?,
             Op::WordSpacing { word_space } => {
                 if let [
                     Op::CharSpacing { char_space },
@@ -678,32 +749,32 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                     Op::TextDraw { ref text },
                     ..
                 ] = ops[1..] {
-                    write!(f, "{} {} ", word_space, char_space)?;
+                    write!(f, "{} ", Numbers(&[word_space, char_space], format))?;
                     text.serialize(f)?;
                     writeln!(f, " \"")?;
                     advance += 3;
                 } else {
-                    writeln!(f, "{} Tw", word_space)?;
+                    writeln!(f, "{} Tw", Numbers(&[word_space], format))?;
                 }
             }
-            Op::TextScaling { horiz_scale } => writeln!(f, "{} Tz", horiz_scale)?,
+            Op::TextScaling { horiz_scale } => writeln!(f, "{} Tz", Numbers(&[horiz_scale], format))?,
             Op::Leading { leading } => match ops[1..] {
                 [Op::MoveTextPosition { translation }, ..] if leading == -translation.x => {
-                    writeln!(f, "{} {} TD", translation.x, translation.y)?;
+                    writeln!(f, "{} TD", Numbers(&[translation.x, translation.y], format))?;
                     advance += 1;
                 }
                 _ => {
-                    writeln!(f, "{} TL", leading)?;
+                    writeln!(f, "{} TL", Numbers(&[leading], format))?;
                 }
             }
             Op::TextFont { ref name, ref size } => {
                 serialize_name(name, f)?;
-                writeln!(f, " {} Tf", size)?;
+                writeln!(f, " {} Tf", Numbers(&[*size], format))?;
             },
             Op::TextRenderMode { mode } => writeln!(f, "{} Tr", mode as u8)?,
-            Op::TextRise { rise } => writeln!(f, "{} Ts", rise)?,
-            Op::MoveTextPosition { translation } => writeln!(f, "{} {} Td", translation.x, translation.y)?,
-            Op::SetTextMatrix { matrix } => writeln!(f, "{} Tm", matrix)?,
+            Op::TextRise { rise } => writeln!(f, "{} Ts", Numbers(&[rise], format))?,
+            Op::MoveTextPosition { translation } => writeln!(f, "{} Td", Numbers(&[translation.x, translation.y], format))?,
+            Op::SetTextMatrix { matrix } => writeln!(f, "{} Tm", Numbers(&[matrix.a, matrix.b, matrix.c, matrix.d, matrix.e, matrix.f], format))?,
             Op::TextNewline => {
                 if let [Op::TextDraw { ref text }, ..] = ops[1..] {
                     text.serialize(f)?;
@@ -724,13 +795,24 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                         write!(f, " ")?;
                     }
                     match val {
-                        TextDrawAdjusted::Spacing(s) => write!(f, "{s}")?,
+                        TextDrawAdjusted::Spacing(s) => write!(f, "{}", format.display(*s))?,
                         TextDrawAdjusted::Text(data) => data.serialize(f)?,
                     }
                 }
                 writeln!(f, "] TJ")?;
             },
-            Op::InlineImage { image: _ } => unimplemented!(),
+            Op::InlineImage { ref image } => {
+                writeln!(f, "BI")?;
+                for (key, val) in image.params.iter() {
+                    serialize_name(key, f)?;
+                    write!(f, " ")?;
+                    val.serialize_with(f, options)?;
+                    writeln!(f)?;
+                }
+                write!(f, "ID ")?;
+                f.extend_from_slice(&image.data);
+                writeln!(f, "\nEI")?;
+            },
             Op::XObject { ref name } => {
                 serialize_name(name, f)?;
                 writeln!(f, " Do")?;
@@ -741,6 +823,20 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
     Ok(data)
 }
 
+/// Operands written with a [`NumberFormat`], separated by spaces.
+struct Numbers<'a>(&'a [f32], NumberFormat);
+impl Display for Numbers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &n) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", self.1.display(n))?;
+        }
+        Ok(())
+    }
+}
+
 impl Content {
     pub fn from_ops(operations: Vec<Op>) -> Self {
         let data = serialize_ops(&operations).unwrap();
@@ -750,6 +846,97 @@ impl Content {
     }
 }
 
+/// Maps the operators of a content stream, see [`Content::rewrite`].
+pub trait OpTransformer {
+    /// Called for every operator in order. The replacement is pushed to `out`,
+    /// so pushing nothing removes `op`. New resources can be added to `resources`.
+    fn transform(&mut self, op: Op, resources: &mut Resources, out: &mut Vec<Op>) -> Result<()>;
+
+    /// Called after the last operator.
+    fn finish(&mut self, _resources: &mut Resources, _out: &mut Vec<Op>) -> Result<()> {
+        Ok(())
+    }
+}
+impl<F> OpTransformer for F where F: FnMut(Op, &mut Resources, &mut Vec<Op>) -> Result<()> {
+    fn transform(&mut self, op: Op, resources: &mut Resources, out: &mut Vec<Op>) -> Result<()> {
+        self(op, resources, out)
+    }
+}
+
+impl Content {
+    /// Parse the content, pass all operators through `transformer` and serialize the result.
+    ///
+    /// Returns the new content together with a copy of `resources` that only contains
+    /// the entries the new operators still refer to.
+    pub fn rewrite(&self, transformer: &mut impl OpTransformer, resources: &Resources, resolve: &impl Resolve) -> Result<(Content, Resources)> {
+        let mut resources = resources.clone();
+        let mut out = vec![];
+        for op in t!(self.operations(resolve)) {
+            t!(transformer.transform(op, &mut resources, &mut out));
+        }
+        t!(transformer.finish(&mut resources, &mut out));
+
+        let used = ResourceNames::new(&out);
+        used.retain(&mut resources);
+        let data = t!(serialize_ops(&out));
+        Ok((Content { parts: vec![Stream::new((), data)] }, resources))
+    }
+}
+
+/// The names of resources referred to by a list of operators.
+#[derive(Default)]
+struct ResourceNames {
+    graphics_states: HashSet<Name>,
+    color_spaces: HashSet<Name>,
+    patterns: HashSet<Name>,
+    shadings: HashSet<Name>,
+    xobjects: HashSet<Name>,
+    fonts: HashSet<Name>,
+    properties: HashSet<Name>,
+}
+impl ResourceNames {
+    fn new(ops: &[Op]) -> Self {
+        let mut names = ResourceNames::default();
+        for op in ops {
+            match *op {
+                Op::GraphicsState { ref name } => { names.graphics_states.insert(name.clone()); }
+                Op::FillColorSpace { ref name } | Op::StrokeColorSpace { ref name } => { names.color_spaces.insert(name.clone()); }
+                Op::FillColor { color: Color::Other(ref args) } | Op::StrokeColor { color: Color::Other(ref args) } => {
+                    if let Some(Primitive::Name(name)) = args.last() {
+                        names.patterns.insert(Name(name.clone()));
+                    }
+                }
+                Op::Shade { ref name } => { names.shadings.insert(name.clone()); }
+                Op::XObject { ref name } => { names.xobjects.insert(name.clone()); }
+                Op::TextFont { ref name, .. } => { names.fonts.insert(name.clone()); }
+                Op::BeginMarkedContent { properties: Some(Primitive::Name(ref name)), .. }
+                | Op::MarkedContentPoint { properties: Some(Primitive::Name(ref name)), .. } => {
+                    names.properties.insert(Name(name.clone()));
+                }
+                Op::InlineImage { ref image } => {
+                    // anything but a device color space is looked up in the resources
+                    for (key, val) in image.params.iter() {
+                        if let ("CS" | "ColorSpace", Primitive::Name(name)) = (key.as_str(), val) {
+                            names.color_spaces.insert(Name(name.clone()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+    fn retain(&self, resources: &mut Resources) {
+        resources.graphics_states.retain(|name, _| self.graphics_states.contains(name));
+        resources.color_spaces.retain(|name, _| self.color_spaces.contains(name));
+        resources.pattern.retain(|name, _| self.patterns.contains(name));
+        resources.shadings.retain(|name, _| self.shadings.contains(name));
+        resources.xobjects.retain(|name, _| self.xobjects.contains(name));
+        resources.fonts.retain(|name, _| self.fonts.contains(name));
+        resources.properties.retain(|name, _| self.properties.contains(name));
+    }
+}
+
 impl ObjectWrite for Content {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
         if self.parts.len() == 1 {
@@ -759,7 +946,12 @@ impl ObjectWrite for Content {
                 _ => update.create(obj)?.to_primitive(update)
             }
         } else {
-            self.parts.to_primitive(update)
+            // streams can only be referenced from an array
+            let parts = self.parts.iter().map(|part| match part.to_primitive(update)? {
+                obj @ Primitive::Reference(_) => Ok(obj),
+                obj => update.create(obj)?.to_primitive(update),
+            }).collect::<Result<Vec<_>>>()?;
+            Ok(Primitive::Array(parts))
         }
     }
 }
@@ -789,6 +981,7 @@ pub struct PdfSpace();
 
 #[derive(Debug, Copy, Clone, PartialEq, Default, DataSize)]
 #[repr(C, align(8))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Point {
     pub x: f32,
     pub y: f32
@@ -880,6 +1073,7 @@ impl From<euclid::Box2D<f32, PdfSpace>> for ViewRect {
 
 #[derive(Debug, Copy, Clone, PartialEq, DataSize, DeepClone)]
 #[repr(C, align(8))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Matrix {
     pub a: f32,
     pub b: f32,
@@ -895,19 +1089,130 @@ impl Display for Matrix {
 }
 impl Default for Matrix {
     fn default() -> Self {
+        Matrix::IDENTITY
+    }
+}
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix { a: 1., b: 0., c: 0., d: 1., e: 0., f: 0. };
+
+    pub fn translate(x: f32, y: f32) -> Matrix {
+        Matrix { e: x, f: y, ..Matrix::IDENTITY }
+    }
+    pub fn scale(sx: f32, sy: f32) -> Matrix {
+        Matrix { a: sx, d: sy, ..Matrix::IDENTITY }
+    }
+    /// Rotate counter-clockwise by `angle` in radians.
+    pub fn rotate(angle: f32) -> Matrix {
+        let (sin, cos) = angle.sin_cos();
+        Matrix { a: cos, b: sin, c: -sin, d: cos, e: 0., f: 0. }
+    }
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+    /// The inverse transformation, `None` if the matrix maps everything onto a line or point.
+    pub fn invert(&self) -> Option<Matrix> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some(Matrix {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+    pub fn transform_point(&self, p: Point) -> Point {
+        Point {
+            x: self.a * p.x + self.c * p.y + self.e,
+            y: self.b * p.x + self.d * p.y + self.f,
+        }
+    }
+    /// Transform a distance, without the translation.
+    pub fn transform_vector(&self, v: Point) -> Point {
+        Point {
+            x: self.a * v.x + self.c * v.y,
+            y: self.b * v.x + self.d * v.y,
+        }
+    }
+    /// The bounding box of the transformed rectangle, see [`Rectangle::transform`].
+    pub fn transform_rect(&self, r: &Rectangle) -> Rectangle {
+        r.transform(self)
+    }
+    /// Split into scaling, skewing, rotation and translation, applied in this order.
+    ///
+    /// Returns `None` if the matrix cannot be inverted.
+    pub fn decompose(&self) -> Option<Decomposition> {
+        let det = self.determinant();
+        let scale_x = self.a.hypot(self.b);
+        if det.abs() < 1e-12 || scale_x == 0. {
+            return None;
+        }
+        Some(Decomposition {
+            scale_x,
+            scale_y: det / scale_x,
+            skew: (self.a * self.c + self.b * self.d) / det,
+            rotation: self.b.atan2(self.a),
+            translate_x: self.e,
+            translate_y: self.f,
+        })
+    }
+}
+/// `a * b` applies `a` first, then `b`, like the operator `a cm` when `b` is the current transformation matrix.
+impl std::ops::Mul for Matrix {
+    type Output = Matrix;
+    fn mul(self, b: Matrix) -> Matrix {
+        let a = self;
         Matrix {
-            a: 1.0,
-            b: 0.0,
-            c: 0.0,
-            d: 1.0,
-            e: 0.0,
-            f: 0.0,
+            a: a.a * b.a + a.b * b.c,
+            b: a.a * b.b + a.b * b.d,
+            c: a.c * b.a + a.d * b.c,
+            d: a.c * b.b + a.d * b.d,
+            e: a.e * b.a + a.f * b.c + b.e,
+            f: a.e * b.b + a.f * b.d + b.f,
         }
     }
 }
+impl std::ops::MulAssign for Matrix {
+    fn mul_assign(&mut self, b: Matrix) {
+        *self = *self * b;
+    }
+}
+/// The parts of a [`Matrix`], see [`Matrix::decompose`].
+///
+/// The matrix is `scale(scale_x, scale_y)`, then a horizontal skew `x += skew * y`,
+/// then `rotate(rotation)` and `translate(translate_x, translate_y)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+    pub scale_x: f32,
+    /// Negative if the matrix mirrors.
+    pub scale_y: f32,
+    pub skew: f32,
+    /// Counter-clockwise, in radians.
+    pub rotation: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
 impl Object for Matrix {
-    fn from_primitive(p: Primitive, _resolve: &impl Resolve) -> Result<Self> {
-        matrix(&mut p.into_array()?.into_iter())
+    /// Malformed matrices are read as the identity, with a warning.
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let numbers = p.resolve(resolve).and_then(|p| p.into_array()).and_then(|arr| {
+            arr.into_iter().map(|p| f32::from_primitive(p, resolve)).collect::<Result<Vec<f32>>>()
+        });
+        match numbers.as_deref() {
+            Ok(&[a, b, c, d, e, f]) => Ok(Matrix { a, b, c, d, e, f }),
+            Ok(numbers) => {
+                resolve.options().warn(Warning::Ignored { error: format!("matrix with {} entries", numbers.len()) });
+                Ok(Matrix::IDENTITY)
+            }
+            Err(e) => {
+                resolve.options().warn(Warning::Ignored { error: format!("invalid matrix: {}", e) });
+                Ok(Matrix::IDENTITY)
+            }
+        }
     }
 }
 impl ObjectWrite for Matrix {
@@ -1094,7 +1399,8 @@ pub enum Op {
 
     XObject { name: Name },
 
-    InlineImage { image: Arc<ImageXObject> },
+    /// `BI` … `ID` … `EI`
+    InlineImage { image: Arc<InlineImage> },
 }
 
 pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resources, resources: &mut Resources) -> Result<Op> {
@@ -1108,9 +1414,11 @@ pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resource
             Ok(Op::GraphicsState { name: name.clone() })
         }
         Op::MarkedContentPoint { ref tag, ref properties } => {
+            deep_clone_properties(properties, cloner, old_resources, resources)?;
             Ok(Op::MarkedContentPoint { tag: tag.clone(), properties: properties.deep_clone(cloner)? })
         }
         Op::BeginMarkedContent { ref tag, ref properties } => {
+            deep_clone_properties(properties, cloner, old_resources, resources)?;
             Ok(Op::BeginMarkedContent { tag: tag.clone(), properties: properties.deep_clone(cloner)? })
         }
         Op::TextFont { ref name, size } => {
@@ -1129,15 +1437,184 @@ pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resource
             }
             Ok(Op::XObject { name: name.clone() })
         }
+        Op::FillColorSpace { ref name } | Op::StrokeColorSpace { ref name } => {
+            if !resources.color_spaces.contains_key(name) {
+                if let Some(cs) = old_resources.color_spaces.get(name) {
+                    resources.color_spaces.insert(name.clone(), cs.deep_clone(cloner)?);
+                }
+            }
+            Ok(op.clone())
+        }
+        // `scn` and `SCN` with a pattern name as the last operand
+        Op::FillColor { color: Color::Other(ref args) } | Op::StrokeColor { color: Color::Other(ref args) } => {
+            if let Some(Primitive::Name(name)) = args.last() {
+                let name = Name(name.clone());
+                if let (Entry::Vacant(e), Some(pattern)) = (resources.pattern.entry(name.clone()), old_resources.pattern.get(&name)) {
+                    e.insert(pattern.deep_clone(cloner)?);
+                }
+            }
+            Ok(op.clone())
+        }
+        Op::Shade { ref name } => {
+            if !resources.shadings.contains_key(name) {
+                if let Some(sh) = old_resources.shadings.get(name) {
+                    resources.shadings.insert(name.clone(), sh.deep_clone(cloner)?);
+                }
+            }
+            Ok(Op::Shade { name: name.clone() })
+        }
         ref op => Ok(op.clone())
     }
 }
 
+/// A property list given by name refers to the `/Properties` resources.
+fn deep_clone_properties(properties: &Option<Primitive>, cloner: &mut impl Cloner, old_resources: &Resources, resources: &mut Resources) -> Result<()> {
+    if let Some(Primitive::Name(ref name)) = *properties {
+        let name = Name(name.clone());
+        if let (Entry::Vacant(e), Some(p)) = (resources.properties.entry(name.clone()), old_resources.properties.get(&name)) {
+            e.insert(p.deep_clone(cloner)?);
+        }
+    }
+    Ok(())
+}
+
+/// Typed information about a marked-content sequence or point.
+///
+/// The property list operand of `BDC` and `DP` is either an inline dictionary
+/// or a name that refers to an entry in the `/Properties` resource dictionary.
+#[derive(Debug, Clone)]
+pub struct MarkedContent {
+    pub tag: Name,
+
+    /// Marked content identifier, used to link content to the structure tree.
+    pub mcid: Option<i32>,
+
+    /// The optional content group (or membership dictionary) controlling the
+    /// visibility of this sequence. Only set for the `OC` tag.
+    pub optional_content: Option<MaybeRef<Dictionary>>,
+
+    /// Replacement text for the content (`/ActualText`)
+    pub actual_text: Option<PdfString>,
+
+    /// The resolved property list, if any.
+    pub properties: Option<MaybeRef<Dictionary>>,
+}
+impl MarkedContent {
+    /// Resolve the operands of a `BMC`, `BDC`, `MP` or `DP` operator.
+    ///
+    /// `resources` are the resources of the page or form the operator belongs to.
+    /// They are only needed when the property list is given by name.
+    pub fn resolve(tag: &Name, properties: Option<&Primitive>, resources: Option<&Resources>, resolve: &impl Resolve) -> Result<MarkedContent> {
+        let properties = match properties {
+            None => None,
+            Some(Primitive::Name(ref name)) => {
+                let resources = try_opt!(resources);
+                match resources.properties.get(name.as_str()) {
+                    Some(dict) => Some(dict.clone()),
+                    None => return Err(PdfError::NotFound { word: name.as_str().into() }),
+                }
+            }
+            Some(p) => Some(t!(MaybeRef::<Dictionary>::from_primitive(p.clone(), resolve))),
+        };
+
+        let mut mcid = None;
+        let mut actual_text = None;
+        if let Some(ref dict) = properties {
+            if let Some(p) = dict.get("MCID") {
+                mcid = Some(t!(t!(p.clone().resolve(resolve)).as_integer()));
+            }
+            if let Some(p) = dict.get("ActualText") {
+                actual_text = Some(t!(t!(p.clone().resolve(resolve)).into_string()));
+            }
+        }
+        let optional_content = match tag.as_str() {
+            "OC" => properties.clone(),
+            _ => None,
+        };
+
+        Ok(MarkedContent {
+            tag: tag.clone(),
+            mcid,
+            optional_content,
+            actual_text,
+            properties,
+        })
+    }
+
+    /// Returns the marked content info for `op`, if it is a `BMC`/`BDC` or `MP`/`DP` operator.
+    pub fn from_op(op: &Op, resources: Option<&Resources>, resolve: &impl Resolve) -> Option<Result<MarkedContent>> {
+        match *op {
+            Op::BeginMarkedContent { ref tag, ref properties } |
+            Op::MarkedContentPoint { ref tag, ref properties } => Some(MarkedContent::resolve(tag, properties.as_ref(), resources, resolve)),
+            _ => None
+        }
+    }
+}
+
+/// Iterator over operators, together with the stack of marked-content sequences
+/// each operator is enclosed in (outermost first).
+///
+/// The `BDC`/`BMC` operator itself is reported inside its own sequence, and so is
+/// the matching `EMC`.
+pub struct MarkedOps<'a, R> {
+    ops: std::slice::Iter<'a, Op>,
+    resources: Option<&'a Resources>,
+    resolve: &'a R,
+    stack: Arc<Vec<MarkedContent>>,
+    pop: bool,
+}
+impl<'a, R: Resolve> MarkedOps<'a, R> {
+    pub fn new(ops: &'a [Op], resources: Option<&'a Resources>, resolve: &'a R) -> Self {
+        MarkedOps {
+            ops: ops.iter(),
+            resources,
+            resolve,
+            stack: Arc::new(vec![]),
+            pop: false,
+        }
+    }
+}
+impl<'a, R: Resolve> Iterator for MarkedOps<'a, R> {
+    type Item = Result<(&'a Op, Arc<Vec<MarkedContent>>)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pop {
+            Arc::make_mut(&mut self.stack).pop();
+            self.pop = false;
+        }
+        let op = self.ops.next()?;
+        match *op {
+            Op::BeginMarkedContent { ref tag, ref properties } => {
+                match MarkedContent::resolve(tag, properties.as_ref(), self.resources, self.resolve) {
+                    Ok(mc) => Arc::make_mut(&mut self.stack).push(mc),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            Op::EndMarkedContent => self.pop = true,
+            _ => {}
+        }
+        Some(Ok((op, self.stack.clone())))
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn content_parts() {
+        // the operands of `cm` are in the first part, and no part ends with white space
+        let parts = ["q 1 0 0 1 5 5", "cm\nBT", "ET Q"];
+        let content = Content { parts: parts.iter().map(|p| Stream::new((), p.as_bytes())).collect() };
+        let ops = content.operations(&NoResolve).unwrap();
+        assert_eq!(ops.len(), 5);
+        assert!(matches!(ops[1], Op::Transform { matrix } if matrix.e == 5.));
+        assert!(matches!(ops[2], Op::BeginText));
+
+        let normalized = content.normalized(&NoResolve).unwrap();
+        assert_eq!(normalized.parts.len(), 1);
+        assert_eq!(normalized.operations(&NoResolve).unwrap().len(), 5);
+    }
+
     #[test]
     fn test_inline_image() {
         let data = br###"
@@ -1154,4 +1631,90 @@ EI
         let mut lexer = Lexer::new(data);
         assert!(inline_image(&mut lexer, &NoResolve).is_ok()); 
     }
+
+    #[test]
+    fn rewrite_strip_text() {
+        let content = Content::from_ops(parse_ops(b"BT /F1 12 Tf (x) Tj ET /OC /MC0 BDC 0 0 m 1 1 l S EMC", &NoResolve).unwrap());
+        let mut resources = Resources::default();
+        resources.properties.insert("MC0".into(), MaybeRef::Direct(Shared::new(Dictionary::new())));
+        resources.properties.insert("MC1".into(), MaybeRef::Direct(Shared::new(Dictionary::new())));
+
+        let mut in_text = false;
+        let mut strip_text = |op: Op, _: &mut Resources, out: &mut Vec<Op>| {
+            match op {
+                Op::BeginText => in_text = true,
+                Op::EndText => in_text = false,
+                op if !in_text => out.push(op),
+                _ => {}
+            }
+            Ok(())
+        };
+        let (content, resources) = content.rewrite(&mut strip_text, &resources, &NoResolve).unwrap();
+        let ops = content.operations(&NoResolve).unwrap();
+        assert_eq!(ops.len(), 5);
+        assert!(matches!(ops[0], Op::BeginMarkedContent { .. }));
+        assert_eq!(resources.properties.keys().collect::<Vec<_>>(), [&Name::from("MC0")]);
+    }
+
+    #[test]
+    fn matrix_ops() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        let m = Matrix::scale(2., 3.) * Matrix::rotate(std::f32::consts::FRAC_PI_2) * Matrix::translate(10., 20.);
+        let p = m.transform_point(Point { x: 1., y: 1. });
+        assert!(close(p.x, 7.) && close(p.y, 22.), "{:?}", p);
+        let q = m.invert().unwrap().transform_point(p);
+        assert!(close(q.x, 1.) && close(q.y, 1.), "{:?}", q);
+        assert!(Matrix::scale(0., 1.).invert().is_none());
+
+        let d = m.decompose().unwrap();
+        assert!(close(d.scale_x, 2.) && close(d.scale_y, 3.) && close(d.skew, 0.));
+        assert!(close(d.rotation, std::f32::consts::FRAC_PI_2));
+        assert_eq!((d.translate_x, d.translate_y), (10., 20.));
+
+        // a matrix with too few entries is read as the identity
+        let short = Primitive::Array(vec![Primitive::Integer(2), Primitive::Integer(0)]);
+        assert_eq!(Matrix::from_primitive(short, &NoResolve).unwrap(), Matrix::IDENTITY);
+    }
+
+    #[test]
+    fn number_format() {
+        let ops = [
+            Op::MoveTo { p: Point { x: 1.23456, y: 0.5 } },
+            Op::FillColor { color: Color::Rgb(Rgb { red: 1. / 3., green: 0., blue: 1. }) },
+            Op::Dash { pattern: vec![3.0, 1.25], phase: 0.0 },
+        ];
+        let data = serialize_ops_with(&ops, NumberFormat::precision(2)).unwrap();
+        assert_eq!(std::str::from_utf8(&data).unwrap(), "1.23 0.5 m\n0.33 0 1 rg\n[3 1.25] 0 d\n");
+        let data = serialize_ops(&ops).unwrap();
+        assert_eq!(std::str::from_utf8(&data).unwrap(), "1.23456 0.5 m\n0.33333334 0 1 rg\n[3 1.25] 0 d\n");
+    }
+
+    #[test]
+    fn inline_image_round_trip() {
+        let data = b"BI\n/W 2\n/H 1\n/BPC 8\n/CS /G\n/F /AHx\nID 00ff>\nEI\n";
+        let ops = parse_ops(data, &NoResolve).unwrap();
+        let image = match ops[0] {
+            Op::InlineImage { ref image } => image,
+            _ => panic!("not an inline image"),
+        };
+        let info = &image.image.inner.info;
+        assert_eq!((info.width, info.height, info.bits_per_component), (2, 1, Some(8)));
+        assert!(matches!(info.color_space, Some(ColorSpace::DeviceGray)));
+        assert_eq!(&*image.image.inner.data(&NoResolve).unwrap(), [0, 255]);
+        assert_eq!(serialize_ops(&ops).unwrap(), data);
+    }
+
+    #[test]
+    fn test_marked_content() {
+        let data = b"/P <</MCID 3 /ActualText (fi)>> BDC /Span BMC (x) Tj EMC EMC (y) Tj";
+        let ops = parse_ops(data, &NoResolve).unwrap();
+        let marked: Vec<_> = MarkedOps::new(&ops, None, &NoResolve).collect::<Result<_>>().unwrap();
+        assert_eq!(marked.len(), 6);
+        assert_eq!(marked[2].1.len(), 2);
+        assert_eq!(marked[2].1[0].mcid, Some(3));
+        assert_eq!(marked[2].1[0].actual_text.as_ref().unwrap().as_bytes(), b"fi");
+        assert_eq!(marked[3].1.len(), 2);
+        assert_eq!(marked[4].1.len(), 1);
+        assert_eq!(marked[5].1.len(), 0);
+    }
 }
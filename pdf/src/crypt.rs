@@ -7,6 +7,7 @@ use aes::cipher::block_padding::{NoPadding, Pkcs7};
 use sha2::{Digest, Sha256, Sha384, Sha512};
 use std::fmt;
 use std::collections::HashMap;
+use std::sync::Arc;
 use datasize::DataSize;
 use crate::object::PlainRef;
 use crate::primitive::{Dictionary, PdfString, Name};
@@ -130,6 +131,24 @@ pub struct CryptFilter {
     _other: Dictionary
 }
 
+/// Decrypts the strings and streams of a file, see [`SecurityHandlerProvider`].
+pub trait SecurityHandler: Send + Sync {
+    /// Decrypt a string or the data of a stream in the object `id` in place,
+    /// returning the part of `data` that holds the plain text.
+    fn decrypt<'buf>(&self, id: PlainRef, data: &'buf mut [u8]) -> Result<&'buf [u8]>;
+}
+
+/// Supplies security handlers other than `Standard`, like those of DRM systems.
+///
+/// Set it with [`FileOptions::security_handler_provider`](crate::file::FileOptions::security_handler_provider).
+pub trait SecurityHandlerProvider: fmt::Debug + Send + Sync {
+    /// The handler for the `Encrypt` dictionary `dict` of the security handler `name` (its `Filter`),
+    /// or `None` if this provider does not know it.
+    ///
+    /// `id` is the first part of the `ID` of the file and `password` the one the file is opened with.
+    fn handler(&self, name: &str, dict: &Dictionary, id: &[u8], password: &[u8]) -> Option<Result<Arc<dyn SecurityHandler>>>;
+}
+
 pub struct Decoder {
     key_size: usize,
     key: Vec<u8>, // maximum length
@@ -146,6 +165,8 @@ pub struct Decoder {
     /// Whether the metadata is encrypted, as indicated by /EncryptMetadata
     /// in the /Encrypt dictionary.
     encrypt_metadata: bool,
+    /// Decrypts instead of the standard security handler.
+    handler: Option<Arc<dyn SecurityHandler>>,
 }
 impl Decoder {
     pub fn default(dict: &CryptDict, id: &[u8]) -> Result<Decoder> {
@@ -164,6 +185,14 @@ impl Decoder {
             encrypt_indirect_object: None,
             metadata_indirect_object: None,
             encrypt_metadata,
+            handler: None,
+        }
+    }
+    /// A decoder for a security handler other than `Standard`.
+    pub fn with_handler(handler: Arc<dyn SecurityHandler>, encrypt_metadata: bool) -> Decoder {
+        Decoder {
+            handler: Some(handler),
+            ..Decoder::new(vec![], 0, CryptMethod::None, encrypt_metadata)
         }
     }
 
@@ -554,6 +583,10 @@ impl Decoder {
             return Ok(data);
         }
 
+        if let Some(ref handler) = self.handler {
+            return handler.decrypt(id, data);
+        }
+
         // Algorithm 1
         // a) we have those already
 
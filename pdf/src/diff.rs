@@ -0,0 +1,326 @@
+//! Comparing two documents, or two revisions of one (see [`File::revisions`]).
+//!
+//! [`compare`] reports the differences on several levels: the objects by number, the pages
+//! by position, the annotations of each page and the values of the form fields. Objects are
+//! compared by what they contain, so an entry that was made an object of its own is no change.
+//! The object numbers only mean something when both documents are revisions of the same file;
+//! the other levels do not depend on them.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::any::AnySync;
+use crate::backend::Backend;
+use crate::error::*;
+use crate::file::{Cache, File, Log};
+use crate::object::*;
+use crate::primitive::{Dictionary, NumberTolerance, PdfString, Primitive};
+
+/// How something differs between the two documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Change {
+    /// only in the second document
+    Added,
+    /// only in the first document
+    Removed,
+    /// in both, with a different content
+    Changed,
+}
+
+/// An object that differs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectChange {
+    pub id: ObjNr,
+    pub change: Change,
+}
+
+/// A page that differs, apart from its annotations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageChange {
+    pub page: u32,
+    pub change: Change,
+}
+
+/// An annotation that differs. Annotations are matched by name (`NM`), or by object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationChange {
+    /// the page it is on
+    pub page: u32,
+    pub subtype: Option<String>,
+    pub name: Option<PdfString>,
+    pub change: Change,
+}
+
+/// A form field whose value differs, see [`InteractiveFormDictionary::export_fdf`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// the fully qualified name
+    pub name: String,
+    pub old: Option<FieldValue>,
+    pub new: Option<FieldValue>,
+}
+
+impl FieldChange {
+    pub fn change(&self) -> Change {
+        match (&self.old, &self.new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ => Change::Changed,
+        }
+    }
+
+    /// Whether a field that had no value has one now.
+    pub fn is_filled(&self) -> bool {
+        matches!(self.old, None | Some(FieldValue::None)) && !matches!(self.new, None | Some(FieldValue::None))
+    }
+}
+
+/// The result of [`compare`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    /// by object number, without cross-reference and object streams
+    pub objects: Vec<ObjectChange>,
+    pub pages: Vec<PageChange>,
+    /// of the pages that are in both documents
+    pub annotations: Vec<AnnotationChange>,
+    pub fields: Vec<FieldChange>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty() && self.pages.is_empty() && self.annotations.is_empty() && self.fields.is_empty()
+    }
+}
+
+/// Compare the document `a` with `b`; what `b` adds is [`Change::Added`].
+///
+/// To see what an incremental update changed, compare a revision loaded with
+/// [`File::load_revision`] with the document.
+pub fn compare<B1, OC1, SC1, L1, B2, OC2, SC2, L2>(a: &File<B1, OC1, SC1, L1>, b: &File<B2, OC2, SC2, L2>) -> Result<Diff>
+where
+    B1: Backend,
+    OC1: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC1: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L1: Log,
+    B2: Backend,
+    OC2: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC2: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L2: Log,
+{
+    let mut diff = Diff::default();
+
+    let by_id = |objects: Vec<PlainRef>| -> HashMap<ObjNr, PlainRef> { objects.into_iter().map(|r| (r.id, r)).collect() };
+    let (a_objects, b_objects) = (by_id(a.objects_in_use()), by_id(b.objects_in_use()));
+    let mut ids: Vec<ObjNr> = a_objects.keys().chain(b_objects.keys()).copied().collect();
+    ids.sort_unstable();
+    ids.dedup();
+    for id in ids {
+        // an object that can't be read is compared as changed
+        let not_structure = |p: &Result<Primitive>| !matches!(p, Ok(p) if is_structure(p));
+        let old = a_objects.get(&id).map(|&r| a.resolve(r)).filter(not_structure);
+        let new = b_objects.get(&id).map(|&r| b.resolve(r)).filter(not_structure);
+        let change = match (old, new) {
+            (None, None) => continue,
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Removed,
+            (Some(Ok(old)), Some(Ok(new))) if Comparer::new(a, b, false).same(&old, &new, 0) => continue,
+            _ => Change::Changed,
+        };
+        diff.objects.push(ObjectChange { id, change });
+    }
+
+    let a_pages = a.pages().collect::<Result<Vec<_>>>()?;
+    let b_pages = b.pages().collect::<Result<Vec<_>>>()?;
+    for n in 0 .. a_pages.len().max(b_pages.len()) {
+        let (old, new) = match (a_pages.get(n), b_pages.get(n)) {
+            (Some(old), Some(new)) => (t!(a.resolve(old.get_plain_ref())), t!(b.resolve(new.get_plain_ref()))),
+            (None, _) => {
+                diff.pages.push(PageChange { page: n as u32, change: Change::Added });
+                continue;
+            }
+            (_, None) => {
+                diff.pages.push(PageChange { page: n as u32, change: Change::Removed });
+                continue;
+            }
+        };
+        let (old, new) = (t!(old.into_dictionary()), t!(new.into_dictionary()));
+        let mut cmp = Comparer::new(a, b, true);
+        if !cmp.same_dict(&old, &new, &["Annots"], 0) {
+            diff.pages.push(PageChange { page: n as u32, change: Change::Changed });
+        }
+        t!(compare_annots(n as u32, &old, &new, &mut cmp, &mut diff.annotations));
+    }
+
+    let old = t!(field_values(a.get_root().forms.as_ref(), a));
+    let new = t!(field_values(b.get_root().forms.as_ref(), b));
+    for (name, value) in &new {
+        let old_value = old.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+        if old_value != Some(value) {
+            diff.fields.push(FieldChange { name: name.clone(), old: old_value.cloned(), new: Some(value.clone()) });
+        }
+    }
+    for (name, value) in &old {
+        if !new.iter().any(|(n, _)| n == name) {
+            diff.fields.push(FieldChange { name: name.clone(), old: Some(value.clone()), new: None });
+        }
+    }
+    Ok(diff)
+}
+
+fn field_values(forms: Option<&InteractiveFormDictionary>, resolve: &impl Resolve) -> Result<Vec<(String, FieldValue)>> {
+    match forms {
+        Some(forms) => Ok(t!(forms.export_fdf(resolve)).fields),
+        None => Ok(vec![]),
+    }
+}
+
+/// Cross-reference and object streams, which are rewritten by every update.
+fn is_structure(p: &Primitive) -> bool {
+    match *p {
+        Primitive::Stream(ref s) => matches!(s.info.get("Type").and_then(|t| t.as_name().ok()), Some("XRef" | "ObjStm")),
+        _ => false,
+    }
+}
+
+fn compare_annots(page: u32, old: &Dictionary, new: &Dictionary, cmp: &mut Comparer<impl Resolve, impl Resolve>, out: &mut Vec<AnnotationChange>) -> Result<()> {
+    fn list(page: &Dictionary, resolve: &impl Resolve) -> Result<Vec<(Option<PlainRef>, Dictionary)>> {
+        let annots = match page.get("Annots") {
+            Some(annots) => t!(annots.clone().resolve(resolve)?.into_array()),
+            None => return Ok(vec![]),
+        };
+        annots.into_iter().map(|p| {
+            let r = match p {
+                Primitive::Reference(r) => Some(r),
+                _ => None,
+            };
+            Ok((r, t!(p.resolve(resolve)?.into_dictionary())))
+        }).collect()
+    }
+    let change = |dict: &Dictionary, change| AnnotationChange {
+        page,
+        subtype: dict.get("Subtype").and_then(|s| s.as_name().ok()).map(String::from),
+        name: dict.get("NM").and_then(|s| s.as_string().ok()).cloned(),
+        change,
+    };
+    let old = t!(list(old, cmp.a));
+    let mut new: Vec<_> = t!(list(new, cmp.b)).into_iter().map(Some).collect();
+    for (r, dict) in &old {
+        let name = dict.get("NM");
+        let matching = new.iter().position(|n| match n {
+            Some((new_r, new_dict)) => match (name, new_dict.get("NM")) {
+                (Some(a), Some(b)) => a == b,
+                _ => r.is_some() && r == new_r,
+            },
+            None => false,
+        });
+        match matching {
+            Some(i) => {
+                let (_, new_dict) = new[i].take().unwrap();
+                if !cmp.same_dict(dict, &new_dict, &[], 0) {
+                    out.push(change(&new_dict, Change::Changed));
+                }
+            }
+            None => out.push(change(dict, Change::Removed)),
+        }
+    }
+    out.extend(new.into_iter().flatten().map(|(_, dict)| change(&dict, Change::Added)));
+    Ok(())
+}
+
+/// Entries that lead back up to the page tree, which is compared page by page.
+const BACK_LINKS: &[&str] = &["Parent", "P"];
+
+struct Comparer<'a, A, B> {
+    a: &'a A,
+    b: &'a B,
+    // follow references, or compare them by number
+    follow: bool,
+    // pairs of objects being compared, which are taken as the same when they are reached again
+    seen: HashSet<(PlainRef, PlainRef)>,
+}
+
+impl<'a, A: Resolve, B: Resolve> Comparer<'a, A, B> {
+    fn new(a: &'a A, b: &'a B, follow: bool) -> Self {
+        Comparer { a, b, follow, seen: HashSet::new() }
+    }
+
+    fn same(&mut self, a: &Primitive, b: &Primitive, depth: usize) -> bool {
+        if depth > self.a.options().max_depth {
+            return a == b;
+        }
+        match (a, b) {
+            (&Primitive::Reference(ra), &Primitive::Reference(rb)) if !self.follow => ra == rb,
+            (&Primitive::Reference(ra), &Primitive::Reference(rb)) => {
+                if !self.seen.insert((ra, rb)) {
+                    return true;
+                }
+                match (self.a.resolve(ra), self.b.resolve(rb)) {
+                    (Ok(a), Ok(b)) => self.same(&a, &b, depth + 1),
+                    _ => false,
+                }
+            }
+            // an entry that was made an object of its own, or the other way around
+            (&Primitive::Reference(ra), b) if self.follow => match self.a.resolve(ra) {
+                Ok(a) => self.same(&a, b, depth + 1),
+                Err(_) => false,
+            },
+            (a, &Primitive::Reference(rb)) if self.follow => match self.b.resolve(rb) {
+                Ok(b) => self.same(a, &b, depth + 1),
+                Err(_) => false,
+            },
+            (Primitive::Dictionary(a), Primitive::Dictionary(b)) => self.same_dict(a, b, &[], depth),
+            (Primitive::Array(a), Primitive::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.same(a, b, depth + 1))
+            }
+            (Primitive::Stream(a), Primitive::Stream(b)) => {
+                self.same_dict(&a.info, &b.info, &[], depth) && match (a.raw_data(self.a), b.raw_data(self.b)) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => false,
+                }
+            }
+            (a, b) => a.eq_with(b, NumberTolerance::default()),
+        }
+    }
+
+    /// Compare the dictionaries without `skip` and the back links.
+    fn same_dict(&mut self, a: &Dictionary, b: &Dictionary, skip: &[&str], depth: usize) -> bool {
+        let compared = |key: &str| !skip.contains(&key) && !BACK_LINKS.contains(&key);
+        for (key, va) in a.iter().filter(|(k, _)| compared(k.as_str())) {
+            let same = match b.get(key) {
+                Some(vb) => self.same(va, vb, depth + 1),
+                None => is_default(key, va),
+            };
+            if !same {
+                return false;
+            }
+        }
+        b.iter()
+            .filter(|(k, _)| compared(k.as_str()) && !a.contains_key(k.as_str()))
+            .all(|(k, v)| is_default(k, v))
+    }
+}
+
+/// Entries that are the same as when they are left out, which are written along with the others
+/// when an object is changed.
+fn is_default(key: &str, value: &Primitive) -> bool {
+    matches!((key, value), ("Rotate", Primitive::Integer(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    fn p(s: &str) -> Primitive {
+        parse(s.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap()
+    }
+
+    #[test]
+    fn same() {
+        let mut cmp = Comparer::new(&NoResolve, &NoResolve, true);
+        assert!(cmp.same(&p("<< /A 1 /B [1 (x)] >>"), &p("<< /B [1.0 (x)] /A 1 >>"), 0));
+        assert!(!cmp.same(&p("<< /A 1 >>"), &p("<< /A 1 /B 2 >>"), 0));
+        assert!(cmp.same(&p("<< /Type /Page /Parent 1 0 R >>"), &p("<< /Type /Page /Parent 2 0 R /Rotate 0 >>"), 0));
+        assert!(!cmp.same(&p("<< /Type /Page /Rotate 90 >>"), &p("<< /Type /Page >>"), 0));
+    }
+}
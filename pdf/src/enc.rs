@@ -5,9 +5,10 @@ use itertools::Itertools;
 
 use crate as pdf;
 use crate::error::*;
-use crate::object::{Object, Resolve, Stream};
-use crate::primitive::{Primitive, Dictionary};
+use crate::object::{Object, ParseOptions, Resolve, Stream};
+use crate::primitive::{Primitive, Dictionary, Name};
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::io::{Read, Write};
 use once_cell::sync::OnceCell;
 use datasize::DataSize;
@@ -26,6 +27,12 @@ pub struct LZWFlateParams {
     #[pdf(key="EarlyChange", default="1")]
     pub early_change: i32,
 }
+impl LZWFlateParams {
+    /// The PNG predictors, chosen row by row, for rows of `columns` samples of `colors` components.
+    pub fn png(colors: i32, bits_per_component: i32, columns: i32) -> LZWFlateParams {
+        LZWFlateParams { predictor: 15, n_components: colors, bits_per_component, columns, early_change: 1 }
+    }
+}
 impl Default for LZWFlateParams {
     fn default() -> LZWFlateParams {
         LZWFlateParams {
@@ -82,6 +89,7 @@ pub struct JBIG2DecodeParams {
     pub globals: Option<Stream<()>>
 }
 #[derive(Debug, Clone, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum StreamFilter {
     ASCIIHexDecode,
     ASCII85Decode,
@@ -92,9 +100,23 @@ pub enum StreamFilter {
     CCITTFaxDecode (CCITTFaxDecodeParams),
     JBIG2Decode(JBIG2DecodeParams),
     Crypt,
-    RunLengthDecode
+    RunLengthDecode,
+    /// zstd compression. Not a standard filter: it is written as `PDFRS_ZstdDecode`,
+    /// and only this crate built with the `zstd` feature can read it.
+    ZstdDecode,
+    /// Brotli compression, written as `PDFRS_BrotliDecode` (the `brotli` feature).
+    BrotliDecode,
+    /// A filter this crate does not know. It can be decoded by a [`FilterProvider`].
+    Other { name: Name, params: Dictionary },
 }
 impl StreamFilter {
+    /// Whether this is one of the filters of this crate that other applications don't know.
+    /// Use [`File::downgrade_filters`](crate::file::File::downgrade_filters) before handing
+    /// such a file to them.
+    pub fn is_vendor(&self) -> bool {
+        matches!(self, StreamFilter::ZstdDecode | StreamFilter::BrotliDecode)
+    }
+
     pub fn from_kind_and_params(kind: &str, params: Dictionary, r: &impl Resolve) -> Result<StreamFilter> {
        let params = Primitive::Dictionary (params);
        Ok(
@@ -109,7 +131,9 @@ impl StreamFilter {
            "JBIG2Decode" => StreamFilter::JBIG2Decode(JBIG2DecodeParams::from_primitive(params, r)?),
            "Crypt" => StreamFilter::Crypt,
            "RunLengthDecode" => StreamFilter::RunLengthDecode,
-           ty => bail!("Unrecognized filter type {:?}", ty),
+           "PDFRS_ZstdDecode" => StreamFilter::ZstdDecode,
+           "PDFRS_BrotliDecode" => StreamFilter::BrotliDecode,
+           name => StreamFilter::Other { name: name.into(), params: params.into_dictionary()? },
        }
        )
     }
@@ -257,103 +281,229 @@ fn encode_85(data: &[u8]) -> Vec<u8> {
     buf
 }
 
-fn inflate_bytes_zlib(data: &[u8]) -> Result<Vec<u8>> {
-    use libflate::zlib::Decoder;
-    let mut decoder = Decoder::new(data)?;
+fn too_large(max: usize) -> PdfError {
+    PdfError::LimitExceeded { what: "decoded stream size", max: max as u64 }
+}
+
+/// Read at most `max` bytes, and fail if there are more.
+fn read_limited(reader: impl Read, max: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::new();
-    decoder.read_to_end(&mut decoded)?;
+    reader.take((max as u64).saturating_add(1)).read_to_end(&mut decoded)?;
+    if decoded.len() > max {
+        return Err(too_large(max));
+    }
     Ok(decoded)
 }
 
-fn inflate_bytes(data: &[u8]) -> Result<Vec<u8>> {
+fn inflate_bytes_zlib(data: &[u8], max: usize) -> Result<Vec<u8>> {
+    use libflate::zlib::Decoder;
+    read_limited(Decoder::new(data)?, max)
+}
+
+fn inflate_bytes(data: &[u8], max: usize) -> Result<Vec<u8>> {
     use libflate::deflate::Decoder;
-    let mut decoder = Decoder::new(data);
-    let mut decoded = Vec::new();
-    decoder.read_to_end(&mut decoded)?;
-    Ok(decoded)
+    read_limited(Decoder::new(data), max)
 }
 
 pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    flate_decode_limited(data, params, usize::MAX)
+}
+
+fn flate_decode_limited(data: &[u8], params: &LZWFlateParams, max: usize) -> Result<Vec<u8>> {
+    // First flate decode
+    let decoded = {
+        match inflate_bytes_zlib(data, max) {
+            Ok(data) => data,
+            Err(e @ PdfError::LimitExceeded { .. }) => return Err(e),
+            Err(_) => match inflate_bytes(data, max) {
+                Ok(data) => data,
+                Err(e @ PdfError::LimitExceeded { .. }) => return Err(e),
+                Err(_) => {
+                    dump_data(data);
+                    bail!("can't inflate");
+                }
+            }
+        }
+    };
+    unpredict(decoded, params)
+}
+
+/// The bytes per pixel (at least 1) and per row of the data the predictor of `params` applies to.
+fn predictor_layout(params: &LZWFlateParams) -> Result<(usize, usize)> {
     if !(0..1024).contains(&params.n_components) {
         bail!("n_components out of range");
     }
     if !(0..1<<20).contains(&params.columns) {
         bail!("columns too large");
     }
-    let predictor: usize = params.predictor.try_into()?;
-    let n_components: usize = params.n_components.try_into()?;
+    if !matches!(params.bits_per_component, 1 | 2 | 4 | 8 | 16) {
+        bail!("invalid bits per component {}", params.bits_per_component);
+    }
+    let colors: usize = params.n_components.try_into()?;
+    let bits: usize = params.bits_per_component.try_into()?;
     let columns: usize = params.columns.try_into()?;
-    let stride = columns * n_components;
-
+    Ok(((colors * bits).div_ceil(8).max(1), (columns * colors * bits).div_ceil(8)))
+}
 
-    // First flate decode
-    let decoded = {
-        if let Ok(data) = inflate_bytes_zlib(data) {
-            data
-        } else if let Ok(data) = inflate_bytes(data) {
-            data
-        } else {
-            dump_data(data);
-            bail!("can't inflate");
+/// Undo the predictor of `params` (the TIFF one, `2`, or the PNG ones, `10` and up).
+fn unpredict(data: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    if params.predictor < 2 {
+        return Ok(data);
+    }
+    let (bpp, stride) = t!(predictor_layout(params));
+    if stride == 0 {
+        return Ok(data);
+    }
+    match params.predictor {
+        2 => {
+            let mut data = data;
+            match params.bits_per_component {
+                8 => for row in data.chunks_exact_mut(stride) {
+                    for i in bpp .. stride {
+                        row[i] = row[i].wrapping_add(row[i - bpp]);
+                    }
+                }
+                16 => for row in data.chunks_exact_mut(stride) {
+                    for i in (bpp .. stride - 1).step_by(2) {
+                        let left = u16::from_be_bytes([row[i - bpp], row[i + 1 - bpp]]);
+                        let value = u16::from_be_bytes([row[i], row[i + 1]]).wrapping_add(left);
+                        row[i .. i + 2].copy_from_slice(&value.to_be_bytes());
+                    }
+                }
+                bits => warn!("TIFF predictor with {} bits per component is not supported", bits),
+            }
+            Ok(data)
         }
-    };
-    // Then unfilter (PNG)
-    // For this, take the old out as input, and write output to out
-
-    if predictor > 10 {
-        let inp = decoded; // input buffer
-        let rows = inp.len() / (stride+1);
-
-        // output buffer
-        let mut out = vec![0; rows * stride];
-
-        // Apply inverse predictor
-        let null_vec = vec![0; stride];
-
-        let mut in_off = 0; // offset into input buffer
-
-        let mut out_off = 0; // offset into output buffer
-        let mut last_out_off = 0; // last offset to output buffer
-
-        while in_off + stride < inp.len() {
-            let predictor = PredictorType::from_u8(inp[in_off])?;
-            in_off += 1; // +1 because the first byte on each row is predictor
-
-            let row_in = &inp[in_off .. in_off + stride];
-            let (prev_row, row_out) = if out_off == 0 {
-                (&null_vec[..], &mut out[out_off .. out_off+stride])
-            } else {
-                let (prev, curr) = out.split_at_mut(out_off);
-                (&prev[last_out_off ..], &mut curr[.. stride])
-            };
-            unfilter(predictor, n_components, prev_row, row_in, row_out);
-
-            last_out_off = out_off;
+        10 .. => {
+            // every row starts with the number of its PNG predictor
+            let null_row = vec![0; stride];
+            let mut out = vec![0; data.len() / (stride + 1) * stride];
+            for (i, row) in data.chunks_exact(stride + 1).enumerate() {
+                let predictor = PredictorType::from_u8(row[0])?;
+                let (prev, current) = out.split_at_mut(i * stride);
+                let prev = if i == 0 { &null_row[..] } else { &prev[(i - 1) * stride ..] };
+                unfilter(predictor, bpp, prev, &row[1 ..], &mut current[.. stride]);
+            }
+            Ok(out)
+        }
+        _ => Ok(data),
+    }
+}
 
-            in_off += stride;
-            out_off += stride;
+/// Apply the predictor of `params` to `data`, which has to be whole rows.
+///
+/// With the PNG predictors, `10` to `14` use the same one for every row,
+/// `15` chooses the one that gives the smallest differences row by row.
+pub fn predict(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    if params.predictor < 2 {
+        return Ok(data.to_vec());
+    }
+    let (bpp, stride) = t!(predictor_layout(params));
+    if stride == 0 || !data.len().is_multiple_of(stride) {
+        bail!("{} bytes are not rows of {} bytes", data.len(), stride);
+    }
+    match params.predictor {
+        2 => {
+            let mut data = data.to_vec();
+            match params.bits_per_component {
+                8 => for row in data.chunks_exact_mut(stride) {
+                    for i in (bpp .. stride).rev() {
+                        row[i] = row[i].wrapping_sub(row[i - bpp]);
+                    }
+                }
+                16 => for row in data.chunks_exact_mut(stride) {
+                    for i in (bpp .. stride - 1).step_by(2).rev() {
+                        let left = u16::from_be_bytes([row[i - bpp], row[i + 1 - bpp]]);
+                        let value = u16::from_be_bytes([row[i], row[i + 1]]).wrapping_sub(left);
+                        row[i .. i + 2].copy_from_slice(&value.to_be_bytes());
+                    }
+                }
+                bits => bail!("TIFF predictor with {} bits per component is not supported", bits),
+            }
+            Ok(data)
         }
-        Ok(out)
-    } else {
-        Ok(decoded)
+        10 ..= 15 => {
+            use self::PredictorType::*;
+            let null_row = vec![0; stride];
+            let mut out = Vec::with_capacity(data.len() / stride * (stride + 1));
+            let mut row = vec![0; stride];
+            for (i, current) in data.chunks_exact(stride).enumerate() {
+                let prev = if i == 0 { &null_row[..] } else { &data[(i - 1) * stride .. i * stride] };
+                let predictor = match params.predictor {
+                    15 => *[NoFilter, Sub, Up, Avg, Paeth].iter().min_by_key(|&&p| {
+                        row.copy_from_slice(current);
+                        filter(p, bpp, prev, &mut row);
+                        row.iter().map(|&b| (b as i8).unsigned_abs() as u64).sum::<u64>()
+                    }).unwrap(),
+                    n => PredictorType::from_u8((n - 10) as u8)?,
+                };
+                row.copy_from_slice(current);
+                filter(predictor, bpp, prev, &mut row);
+                out.push(predictor as u8);
+                out.extend_from_slice(&row);
+            }
+            Ok(out)
+        }
+        n => bail!("unknown predictor {}", n),
     }
 }
 fn flate_encode(data: &[u8]) -> Vec<u8> {
-    use libflate::deflate::Encoder;
-    let mut encoded = Vec::new();
-    let mut encoder = Encoder::new(&mut encoded);
+    use libflate::zlib::Encoder;
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
     encoder.write_all(data).unwrap();
-    encoded
+    encoder.finish().into_result().unwrap()
+}
+
+/// How hard `FlateDecode` compresses, see [`StreamOptions`](crate::object::StreamOptions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlateLevel {
+    /// no compression, for data that does not compress or has to be read by eye
+    None,
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+pub fn flate_encode_level(data: &[u8], level: FlateLevel) -> Vec<u8> {
+    use deflate::{deflate_bytes_zlib_conf, Compression, CompressionOptions};
+    match level {
+        FlateLevel::None => {
+            use libflate::zlib::{Encoder, EncodeOptions};
+            let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().no_compression()).unwrap();
+            encoder.write_all(data).unwrap();
+            encoder.finish().into_result().unwrap()
+        }
+        FlateLevel::Fast => deflate_bytes_zlib_conf(data, Compression::Fast),
+        FlateLevel::Default => flate_encode(data),
+        FlateLevel::Best => deflate_bytes_zlib_conf(data, CompressionOptions::high()),
+    }
+}
+
+pub fn dct_decode(data: &[u8], params: &DCTDecodeParams) -> Result<Vec<u8>> {
+    dct_decode_limited(data, params, usize::MAX)
 }
 
-pub fn dct_decode(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
+/// Fails before decoding if the size in the JPEG header is larger than `max`.
+pub(crate) fn dct_decode_limited(data: &[u8], _params: &DCTDecodeParams, max: usize) -> Result<Vec<u8>> {
     use jpeg_decoder::Decoder;
     let mut decoder = Decoder::new(data);
+    decoder.read_info()?;
+    if let Some(info) = decoder.info() {
+        let size = (info.width as u64) * (info.height as u64) * (info.pixel_format.pixel_bytes() as u64);
+        if size > max as u64 {
+            return Err(too_large(max));
+        }
+    }
     let pixels = decoder.decode()?;
     Ok(pixels)
 }
 
 pub fn lzw_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    lzw_decode_limited(data, params, usize::MAX)
+}
+
+fn lzw_decode_limited(data: &[u8], params: &LZWFlateParams, max: usize) -> Result<Vec<u8>> {
     use weezl::{BitOrder, decode::Decoder};
     let mut out = vec![];
 
@@ -364,19 +514,42 @@ pub fn lzw_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     };
 
     decoder
-        .into_stream(&mut out)
-        .decode_all(data).status?;
-    Ok(out)
+        .into_stream(LimitedWriter { out: &mut out, max })
+        .decode_all(data).status
+        .map_err(|e| match e.get_ref().and_then(|e| e.downcast_ref::<PdfError>()) {
+            Some(&PdfError::LimitExceeded { .. }) => too_large(max),
+            _ => e.into(),
+        })?;
+    unpredict(out, params)
+}
+
+/// Fails with [`PdfError::LimitExceeded`] when more than `max` bytes are written.
+struct LimitedWriter<'a> {
+    out: &'a mut Vec<u8>,
+    max: usize,
+}
+impl Write for LimitedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.out.len() + buf.len() > self.max {
+            return Err(std::io::Error::other(too_large(self.max)));
+        }
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 fn lzw_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     use weezl::{BitOrder, encode::Encoder};
     if params.early_change != 0 {
         bail!("encoding early_change != 0 is not supported");
     }
+    let data = t!(predict(data, params));
     let mut compressed = vec![];
     Encoder::new(BitOrder::Msb, 9)
         .into_stream(&mut compressed)
-        .encode_all(data).status?;
+        .encode_all(&data[..]).status?;
     Ok(compressed)
 }
 
@@ -408,6 +581,10 @@ pub fn fax_decode(data: &[u8], params: &CCITTFaxDecodeParams) -> Result<Vec<u8>>
 }
 
 pub fn run_length_decode(data: &[u8]) -> Result<Vec<u8>> {
+    run_length_decode_limited(data, usize::MAX)
+}
+
+fn run_length_decode_limited(data: &[u8], max: usize) -> Result<Vec<u8>> {
     // Used <http://benno.id.au/refs/PDFReference15_v5.pdf> as specification
     let mut buf = Vec::new();
     let d = data;
@@ -430,6 +607,9 @@ pub fn run_length_decode(data: &[u8]) -> Result<Vec<u8>> {
         } else {
             break; // EOD
         }
+        if buf.len() > max {
+            return Err(too_large(max));
+        }
     }
 
     Ok(buf)
@@ -467,16 +647,55 @@ pub fn jbig2_decode(data: &[u8], globals: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
-    match *filter {
+    decode_limited(data, filter, usize::MAX)
+}
+
+/// Like [`decode`], but fails with [`PdfError::LimitExceeded`] instead of producing more than `max` bytes.
+/// The filters that can expand their input stop as soon as the limit is reached.
+pub fn decode_limited(data: &[u8], filter: &StreamFilter, max: usize) -> Result<Vec<u8>> {
+    let decoded = match *filter {
         StreamFilter::ASCIIHexDecode => decode_hex(data),
         StreamFilter::ASCII85Decode => decode_85(data),
-        StreamFilter::LZWDecode(ref params) => lzw_decode(data, params),
-        StreamFilter::FlateDecode(ref params) => flate_decode(data, params),
-        StreamFilter::RunLengthDecode => run_length_decode(data),
-        StreamFilter::DCTDecode(ref params) => dct_decode(data, params),
+        StreamFilter::LZWDecode(ref params) => lzw_decode_limited(data, params, max),
+        StreamFilter::FlateDecode(ref params) => flate_decode_limited(data, params, max),
+        StreamFilter::RunLengthDecode => run_length_decode_limited(data, max),
+        StreamFilter::DCTDecode(ref params) => dct_decode_limited(data, params, max),
+        StreamFilter::ZstdDecode => zstd_decode(data, max),
+        StreamFilter::BrotliDecode => brotli_decode(data, max),
 
         _ => bail!("unimplemented {filter:?}"),
+    }?;
+    if decoded.len() > max {
+        return Err(too_large(max));
+    }
+    Ok(decoded)
+}
+
+/// Decodes filters this crate does not know, like proprietary ones.
+///
+/// Set it with [`ParseOptions::filter_provider`](crate::object::ParseOptions::filter_provider)
+/// or [`FileOptions::filter_provider`](crate::file::FileOptions::filter_provider).
+pub trait FilterProvider: fmt::Debug + Send + Sync {
+    /// Decode `data` with the filter `name` and its `DecodeParms`, producing at most `max` bytes.
+    /// Returns `None` if the filter is unknown to this provider.
+    fn decode(&self, name: &str, params: &Dictionary, data: &[u8], max: usize) -> Option<Result<Vec<u8>>>;
+}
+
+/// Like [`decode_limited`] with the stream size limit of `options`,
+/// and filters this crate does not know decoded by its [`FilterProvider`].
+pub fn decode_with_options(data: &[u8], filter: &StreamFilter, options: &ParseOptions) -> Result<Vec<u8>> {
+    let max = options.limits.max_stream_size;
+    let (name, params) = match *filter {
+        StreamFilter::Other { ref name, ref params } => (name, params),
+        _ => return decode_limited(data, filter, max),
+    };
+    let decoded = options.filters.as_ref()
+        .and_then(|provider| provider.decode(name.as_str(), params, data, max))
+        .ok_or_else(|| other!("no decoder for the filter {}", name))??;
+    if decoded.len() > max {
+        return Err(too_large(max));
     }
+    Ok(decoded)
 }
 
 pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
@@ -484,11 +703,54 @@ pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
         StreamFilter::ASCIIHexDecode => Ok(encode_hex(data)),
         StreamFilter::ASCII85Decode => Ok(encode_85(data)),
         StreamFilter::LZWDecode(ref params) => lzw_encode(data, params),
-        StreamFilter::FlateDecode (ref _params) => Ok(flate_encode(data)),
-        _ => unimplemented!(),
+        StreamFilter::FlateDecode (ref params) => Ok(flate_encode(&t!(predict(data, params)))),
+        StreamFilter::ZstdDecode => zstd_encode(data),
+        StreamFilter::BrotliDecode => brotli_encode(data),
+        _ => bail!("encoding with {filter:?} is not supported"),
     }
 }
 
+#[cfg(feature = "zstd")]
+fn zstd_decode(data: &[u8], max: usize) -> Result<Vec<u8>> {
+    read_limited(zstd::stream::read::Decoder::new(data)?, max)
+}
+#[cfg(feature = "zstd")]
+fn zstd_encode(data: &[u8]) -> Result<Vec<u8>> {
+    // level 0 is the default of the library
+    Ok(zstd::stream::encode_all(data, 0)?)
+}
+#[cfg(not(feature = "zstd"))]
+fn zstd_decode(_data: &[u8], _max: usize) -> Result<Vec<u8>> {
+    bail!("PDFRS_ZstdDecode needs the `zstd` feature")
+}
+#[cfg(not(feature = "zstd"))]
+fn zstd_encode(_data: &[u8]) -> Result<Vec<u8>> {
+    bail!("PDFRS_ZstdDecode needs the `zstd` feature")
+}
+
+#[cfg(feature = "brotli")]
+fn brotli_decode(data: &[u8], max: usize) -> Result<Vec<u8>> {
+    read_limited(brotli::Decompressor::new(data, 4096), max)
+}
+#[cfg(feature = "brotli")]
+fn brotli_encode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    {
+        // quality 9 of 11, 4 MiB window
+        let mut writer = brotli::CompressorWriter::new(&mut encoded, 4096, 9, 22);
+        writer.write_all(data)?;
+    }
+    Ok(encoded)
+}
+#[cfg(not(feature = "brotli"))]
+fn brotli_decode(_data: &[u8], _max: usize) -> Result<Vec<u8>> {
+    bail!("PDFRS_BrotliDecode needs the `brotli` feature")
+}
+#[cfg(not(feature = "brotli"))]
+fn brotli_encode(_data: &[u8]) -> Result<Vec<u8>> {
+    bail!("PDFRS_BrotliDecode needs the `brotli` feature")
+}
+
 /*
  * Predictor - copied and adapted from PNG crate..
  */
@@ -590,7 +852,6 @@ pub fn unfilter(filter: PredictorType, bpp: usize, prev: &[u8], inp: &[u8], out:
     }
 }
 
-#[allow(unused)]
 pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut [u8]) {
     use self::PredictorType::*;
     let len  = current.len();
@@ -609,7 +870,7 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
         }
         Avg => {
             for i in (bpp..len).rev() {
-                current[i] = current[i].wrapping_sub(current[i - bpp].wrapping_add(previous[i]) / 2);
+                current[i] = current[i].wrapping_sub(((current[i - bpp] as u16 + previous[i] as u16) / 2) as u8);
             }
 
             for i in 0..bpp {
@@ -632,6 +893,34 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
 mod tests {
     use super::*;
 
+    #[test]
+    fn predictors() {
+        // a gradient with some noise, 7 pixels of 3 components per row
+        let data: Vec<u8> = (0 .. 5 * 7 * 3).map(|i: u32| (i * 3 + (i * i) % 7) as u8).collect();
+        let params = |predictor, bits, columns| LZWFlateParams { predictor, n_components: 3, bits_per_component: bits, columns, early_change: 1 };
+        for predictor in [2, 10, 11, 12, 13, 14, 15] {
+            for (bits, columns) in [(8, 7), (16, 7 / 2), (4, 14)] {
+                let params = params(predictor, bits, columns);
+                if !data.len().is_multiple_of(predictor_layout(&params).unwrap().1) {
+                    continue;
+                }
+                let predicted = match predict(&data, &params) {
+                    Ok(p) => p,
+                    Err(_) if predictor == 2 && bits == 4 => continue,
+                    Err(e) => panic!("{}", e),
+                };
+                let filter = StreamFilter::FlateDecode(params.clone());
+                assert_eq!(unpredict(predicted, &params).unwrap(), data, "{} {}", predictor, bits);
+                assert_eq!(flate_decode(&encode(&data, &filter).unwrap(), &params).unwrap(), data);
+            }
+        }
+        // the Up predictor turns identical rows into zeros
+        let rows = [1u8, 2, 3, 4].repeat(8);
+        let predicted = predict(&rows, &LZWFlateParams { predictor: 12, columns: 4, ..Default::default() }).unwrap();
+        assert_eq!(&predicted[.. 10], &[2, 1, 2, 3, 4, 2, 0, 0, 0, 0]);
+        assert!(predict(&rows[1 ..], &LZWFlateParams { predictor: 12, columns: 4, ..Default::default() }).is_err());
+    }
+
     #[test]
     fn base_85() {
         fn s(b: &[u8]) -> &str { std::str::from_utf8(b).unwrap() }
@@ -659,4 +948,28 @@ mod tests {
         let x = run_length_decode(&[254, b'a', 255, b'b', 2, b'c', b'b', b'c', 254, b'a', 128]).unwrap();
         assert_eq!(b"aaabbcbcaaa", x.as_slice());
     }
+
+    #[test]
+    fn decode_limits() {
+        let data = vec![0; 1 << 20];
+        let flate = StreamFilter::FlateDecode(LZWFlateParams::default());
+        let compressed = encode(&data, &flate).unwrap();
+        assert_eq!(decode_limited(&compressed, &flate, 1 << 20).unwrap(), data);
+        let e = decode_limited(&compressed, &flate, 1000).unwrap_err();
+        assert!(matches!(e, PdfError::LimitExceeded { max: 1000, .. }));
+
+        let lzw = StreamFilter::LZWDecode(LZWFlateParams { early_change: 0, ..Default::default() });
+        let compressed = encode(&data, &lzw).unwrap();
+        assert_eq!(decode_limited(&compressed, &lzw, 1 << 20).unwrap(), data);
+        assert!(matches!(decode_limited(&compressed, &lzw, 1000), Err(PdfError::LimitExceeded { .. })));
+
+        let runs = [129, b'a'].repeat(100);
+        assert!(matches!(decode_limited(&runs, &StreamFilter::RunLengthDecode, 1000), Err(PdfError::LimitExceeded { .. })));
+
+        // just the header of a 30000×30000 gray JPEG, the size is checked before the (missing) data is read
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x75, 0x30, 0x75, 0x30, 0x01, 0x01, 0x11, 0x00];
+        let dct = StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None });
+        assert!(matches!(decode_limited(&jpeg, &dct, 1 << 20), Err(PdfError::LimitExceeded { max: 0x100000, .. })));
+        assert!(!matches!(decode_limited(&jpeg, &dct, 1 << 30), Err(PdfError::LimitExceeded { .. })));
+    }
 }
@@ -4,9 +4,11 @@ use crate::object::{DeepClone, Object, ObjectWrite, Resolve};
 use crate::primitive::{Dictionary, Primitive};
 use datasize::DataSize;
 use istring::SmallString;
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Encoding {
     pub base: BaseEncoding,
     pub differences: HashMap<u32, SmallString>,
@@ -99,8 +101,310 @@ impl Encoding {
         }
     }
 }
+impl Encoding {
+    /// The glyph name for `code`, from `Differences` or else the base encoding.
+    ///
+    /// `builtin` is the encoding of the font program, used if there is no base encoding.
+    pub fn glyph_name<'a>(&'a self, code: u8, builtin: Option<&BaseEncoding>) -> Option<&'a str> {
+        if let Some(name) = self.differences.get(&(code as u32)) {
+            return Some(name.as_str());
+        }
+        match self.base {
+            BaseEncoding::None => builtin?.glyph_name(code),
+            ref base => base.glyph_name(code),
+        }
+    }
+}
+impl BaseEncoding {
+    /// The glyph names of a built-in encoding, indexed by code, with `""` for unused codes.
+    ///
+    /// `None` for `Identity-H`, `None` and unknown encodings.
+    pub fn glyph_names(&self) -> Option<&'static [&'static str; 256]> {
+        match *self {
+            BaseEncoding::StandardEncoding => Some(&STANDARD),
+            BaseEncoding::SymbolEncoding => Some(&SYMBOL),
+            BaseEncoding::MacRomanEncoding => Some(&MAC_ROMAN),
+            BaseEncoding::WinAnsiEncoding => Some(&WIN_ANSI),
+            BaseEncoding::MacExpertEncoding => Some(&MAC_EXPERT),
+            BaseEncoding::IdentityH | BaseEncoding::None | BaseEncoding::Other(_) => None,
+        }
+    }
+    /// The glyph name for `code` in this encoding.
+    pub fn glyph_name(&self, code: u8) -> Option<&'static str> {
+        match self.glyph_names()?[code as usize] {
+            // all unused codes above 0o40 show a bullet (PDF 32000-1:2008, Annex D.2, note 6)
+            "" if *self == BaseEncoding::WinAnsiEncoding && code > 0o40 => Some("bullet"),
+            "" => None,
+            name => Some(name),
+        }
+    }
+}
+
+/// The glyph names of the Adobe Glyph List that the built-in encodings use, with their unicode values.
+static GLYPH_LIST: Lazy<HashMap<&'static str, String>> = Lazy::new(|| {
+    include_str!("data/glyphlist.txt").lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, values) = line.split_once(';')?;
+            let text = values.split(' ')
+                .map(|v| u32::from_str_radix(v, 16).ok().and_then(char::from_u32))
+                .collect::<Option<String>>()?;
+            Some((name, text))
+        })
+        .collect()
+});
+
+/// The text a glyph name stands for, following the Adobe Glyph List Specification.
+///
+/// Anything after a period is ignored and underscores join the names of ligature components.
+/// The components are looked up in the glyph list or spell out code points as `uniXXXX`
+/// (any number of them) or `uXXXX` to `uXXXXXX`. Names that mean nothing give `None`.
+pub fn glyph_name_to_unicode(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap_or_default();
+    let mut text = String::new();
+    for component in name.split('_') {
+        if let Some(s) = GLYPH_LIST.get(component) {
+            text.push_str(s);
+        } else if let Some(hex) = component.strip_prefix("uni").filter(|h| h.len() % 4 == 0 && !h.is_empty()) {
+            for i in (0..hex.len()).step_by(4) {
+                text.push(code_point(&hex[i..i + 4])?);
+            }
+        } else if let Some(hex) = component.strip_prefix('u').filter(|h| (4..=6).contains(&h.len())) {
+            text.push(code_point(hex)?);
+        }
+    }
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// A code point in upper case hex digits, surrogates excluded.
+fn code_point(hex: &str) -> Option<char> {
+    if !hex.bytes().all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b)) {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// `StandardEncoding`, the built-in encoding of most Latin text fonts.
+static STANDARD: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quoteright",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "quoteleft", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "exclamdown", "cent", "sterling", "fraction", "yen", "florin", "section",
+    "currency", "quotesingle", "quotedblleft", "guillemotleft", "guilsinglleft", "guilsinglright", "fi", "fl",
+    "", "endash", "dagger", "daggerdbl", "periodcentered", "", "paragraph", "bullet",
+    "quotesinglbase", "quotedblbase", "quotedblright", "guillemotright", "ellipsis", "perthousand", "", "questiondown",
+    "", "grave", "acute", "circumflex", "tilde", "macron", "breve", "dotaccent",
+    "dieresis", "", "ring", "cedilla", "", "hungarumlaut", "ogonek", "caron",
+    "emdash", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "AE", "", "ordfeminine", "", "", "", "",
+    "Lslash", "Oslash", "OE", "ordmasculine", "", "", "", "",
+    "", "ae", "", "", "", "dotlessi", "", "",
+    "lslash", "oslash", "oe", "germandbls", "", "", "", "",
+];
+
+/// `WinAnsiEncoding`, Windows code page 1252.
+static WIN_ANSI: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quotesingle",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    "Euro", "", "quotesinglbase", "florin", "quotedblbase", "ellipsis", "dagger", "daggerdbl",
+    "circumflex", "perthousand", "Scaron", "guilsinglleft", "OE", "", "Zcaron", "",
+    "", "quoteleft", "quoteright", "quotedblleft", "quotedblright", "bullet", "endash", "emdash",
+    "tilde", "trademark", "scaron", "guilsinglright", "oe", "", "zcaron", "Ydieresis",
+    "space", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "dieresis", "copyright", "ordfeminine", "guillemotleft", "logicalnot", "hyphen", "registered", "macron",
+    "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph", "periodcentered",
+    "cedilla", "onesuperior", "ordmasculine", "guillemotright", "onequarter", "onehalf", "threequarters", "questiondown",
+    "Agrave", "Aacute", "Acircumflex", "Atilde", "Adieresis", "Aring", "AE", "Ccedilla",
+    "Egrave", "Eacute", "Ecircumflex", "Edieresis", "Igrave", "Iacute", "Icircumflex", "Idieresis",
+    "Eth", "Ntilde", "Ograve", "Oacute", "Ocircumflex", "Otilde", "Odieresis", "multiply",
+    "Oslash", "Ugrave", "Uacute", "Ucircumflex", "Udieresis", "Yacute", "Thorn", "germandbls",
+    "agrave", "aacute", "acircumflex", "atilde", "adieresis", "aring", "ae", "ccedilla",
+    "egrave", "eacute", "ecircumflex", "edieresis", "igrave", "iacute", "icircumflex", "idieresis",
+    "eth", "ntilde", "ograve", "oacute", "ocircumflex", "otilde", "odieresis", "divide",
+    "oslash", "ugrave", "uacute", "ucircumflex", "udieresis", "yacute", "thorn", "ydieresis",
+];
+
+/// `MacRomanEncoding`, without the characters Mac OS takes from the Symbol font.
+static MAC_ROMAN: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quotesingle",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    "Adieresis", "Aring", "Ccedilla", "Eacute", "Ntilde", "Odieresis", "Udieresis", "aacute",
+    "agrave", "acircumflex", "adieresis", "atilde", "aring", "ccedilla", "eacute", "egrave",
+    "ecircumflex", "edieresis", "iacute", "igrave", "icircumflex", "idieresis", "ntilde", "oacute",
+    "ograve", "ocircumflex", "odieresis", "otilde", "uacute", "ugrave", "ucircumflex", "udieresis",
+    "dagger", "degree", "cent", "sterling", "section", "bullet", "paragraph", "germandbls",
+    "registered", "copyright", "trademark", "acute", "dieresis", "", "AE", "Oslash",
+    "", "plusminus", "", "", "yen", "mu", "", "",
+    "", "", "", "ordfeminine", "ordmasculine", "", "ae", "oslash",
+    "questiondown", "exclamdown", "logicalnot", "", "florin", "", "", "guillemotleft",
+    "guillemotright", "ellipsis", "space", "Agrave", "Atilde", "Otilde", "OE", "oe",
+    "endash", "emdash", "quotedblleft", "quotedblright", "quoteleft", "quoteright", "divide", "",
+    "ydieresis", "Ydieresis", "fraction", "currency", "guilsinglleft", "guilsinglright", "fi", "fl",
+    "daggerdbl", "periodcentered", "quotesinglbase", "quotedblbase", "perthousand", "Acircumflex", "Ecircumflex", "Aacute",
+    "Edieresis", "Egrave", "Iacute", "Icircumflex", "Idieresis", "Igrave", "Oacute", "Ocircumflex",
+    "", "Ograve", "Uacute", "Ucircumflex", "Ugrave", "dotlessi", "circumflex", "tilde",
+    "macron", "breve", "dotaccent", "ring", "cedilla", "hungarumlaut", "ogonek", "caron",
+];
+
+/// `MacExpertEncoding`, for expert fonts with small capitals, old style figures and fractions.
+static MAC_EXPERT: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclamsmall", "Hungarumlautsmall", "centoldstyle", "dollaroldstyle", "dollarsuperior", "ampersandsmall", "Acutesmall",
+    "parenleftsuperior", "parenrightsuperior", "twodotenleader", "onedotenleader", "comma", "hyphen", "period", "fraction",
+    "zerooldstyle", "oneoldstyle", "twooldstyle", "threeoldstyle", "fouroldstyle", "fiveoldstyle", "sixoldstyle", "sevenoldstyle",
+    "eightoldstyle", "nineoldstyle", "colon", "semicolon", "", "threequartersemdash", "", "questionsmall",
+    "", "", "", "", "Ethsmall", "", "", "onequarter",
+    "onehalf", "threequarters", "oneeighth", "threeeighths", "fiveeighths", "seveneighths", "onethird", "twothirds",
+    "", "", "", "", "", "", "ff", "fi",
+    "fl", "ffi", "ffl", "parenleftinferior", "", "parenrightinferior", "Circumflexsmall", "hypheninferior",
+    "Gravesmall", "Asmall", "Bsmall", "Csmall", "Dsmall", "Esmall", "Fsmall", "Gsmall",
+    "Hsmall", "Ismall", "Jsmall", "Ksmall", "Lsmall", "Msmall", "Nsmall", "Osmall",
+    "Psmall", "Qsmall", "Rsmall", "Ssmall", "Tsmall", "Usmall", "Vsmall", "Wsmall",
+    "Xsmall", "Ysmall", "Zsmall", "colonmonetary", "onefitted", "rupiah", "Tildesmall", "",
+    "", "asuperior", "centsuperior", "", "", "", "", "Aacutesmall",
+    "Agravesmall", "Acircumflexsmall", "Adieresissmall", "Atildesmall", "Aringsmall", "Ccedillasmall", "Eacutesmall", "Egravesmall",
+    "Ecircumflexsmall", "Edieresissmall", "Iacutesmall", "Igravesmall", "Icircumflexsmall", "Idieresissmall", "Ntildesmall", "Oacutesmall",
+    "Ogravesmall", "Ocircumflexsmall", "Odieresissmall", "Otildesmall", "Uacutesmall", "Ugravesmall", "Ucircumflexsmall", "Udieresissmall",
+    "", "eightsuperior", "fourinferior", "threeinferior", "sixinferior", "eightinferior", "seveninferior", "Scaronsmall",
+    "", "centinferior", "twoinferior", "", "Dieresissmall", "", "Caronsmall", "osuperior",
+    "fiveinferior", "", "commainferior", "periodinferior", "Yacutesmall", "", "dollarinferior", "",
+    "", "Thornsmall", "", "nineinferior", "zeroinferior", "Zcaronsmall", "AEsmall", "Oslashsmall",
+    "questiondownsmall", "oneinferior", "Lslashsmall", "", "", "", "", "",
+    "", "Cedillasmall", "", "", "", "", "", "OEsmall",
+    "figuredash", "hyphensuperior", "", "", "", "", "exclamdownsmall", "",
+    "Ydieresissmall", "", "onesuperior", "twosuperior", "threesuperior", "foursuperior", "fivesuperior", "sixsuperior",
+    "sevensuperior", "ninesuperior", "zerosuperior", "", "esuperior", "rsuperior", "tsuperior", "",
+    "", "isuperior", "ssuperior", "dsuperior", "", "", "", "",
+    "", "lsuperior", "Ogoneksmall", "Brevesmall", "Macronsmall", "bsuperior", "nsuperior", "msuperior",
+    "commasuperior", "periodsuperior", "Dotaccentsmall", "Ringsmall", "", "", "", "",
+];
+
+/// The built-in encoding of the Symbol font.
+static SYMBOL: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "universal", "numbersign", "existential", "percent", "ampersand", "suchthat",
+    "parenleft", "parenright", "asteriskmath", "plus", "comma", "minus", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "congruent", "Alpha", "Beta", "Chi", "Delta", "Epsilon", "Phi", "Gamma",
+    "Eta", "Iota", "theta1", "Kappa", "Lambda", "Mu", "Nu", "Omicron",
+    "Pi", "Theta", "Rho", "Sigma", "Tau", "Upsilon", "sigma1", "Omega",
+    "Xi", "Psi", "Zeta", "bracketleft", "therefore", "bracketright", "perpendicular", "underscore",
+    "radicalex", "alpha", "beta", "chi", "delta", "epsilon", "phi", "gamma",
+    "eta", "iota", "phi1", "kappa", "lambda", "mu", "nu", "omicron",
+    "pi", "theta", "rho", "sigma", "tau", "upsilon", "omega1", "omega",
+    "xi", "psi", "zeta", "braceleft", "bar", "braceright", "similar", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "Euro", "Upsilon1", "minute", "lessequal", "fraction", "infinity", "florin", "club",
+    "diamond", "heart", "spade", "arrowboth", "arrowleft", "arrowup", "arrowright", "arrowdown",
+    "degree", "plusminus", "second", "greaterequal", "multiply", "proportional", "partialdiff", "bullet",
+    "divide", "notequal", "equivalence", "approxequal", "ellipsis", "arrowvertex", "arrowhorizex", "carriagereturn",
+    "aleph", "Ifraktur", "Rfraktur", "weierstrass", "circlemultiply", "circleplus", "emptyset", "intersection",
+    "union", "propersuperset", "reflexsuperset", "notsubset", "propersubset", "reflexsubset", "element", "notelement",
+    "angle", "gradient", "registerserif", "copyrightserif", "trademarkserif", "product", "radical", "dotmath",
+    "logicalnot", "logicaland", "logicalor", "arrowdblboth", "arrowdblleft", "arrowdblup", "arrowdblright", "arrowdbldown",
+    "lozenge", "angleleft", "registersans", "copyrightsans", "trademarksans", "summation", "parenlefttp", "parenleftex",
+    "parenleftbt", "bracketlefttp", "bracketleftex", "bracketleftbt", "bracelefttp", "braceleftmid", "braceleftbt", "braceex",
+    "", "angleright", "integral", "integraltp", "integralex", "integralbt", "parenrighttp", "parenrightex",
+    "parenrightbt", "bracketrighttp", "bracketrightex", "bracketrightbt", "bracerighttp", "bracerightmid", "bracerightbt", "",
+];
+
+
 impl DeepClone for Encoding {
     fn deep_clone(&self, _cloner: &mut impl pdf::object::Cloner) -> Result<Self> {
         Ok(self.clone())
     }
 }
+
+// PDFDocEncoding, where it differs from Latin-1 (PDF 32000-1:2008, Annex D.2)
+const PDF_DOC_18: [char; 8] = ['\u{02D8}', '\u{02C7}', '\u{02C6}', '\u{02D9}', '\u{02DD}', '\u{02DB}', '\u{02DA}', '\u{02DC}'];
+const PDF_DOC_80: [Option<char>; 33] = [
+    Some('\u{2022}'), Some('\u{2020}'), Some('\u{2021}'), Some('\u{2026}'), Some('\u{2014}'), Some('\u{2013}'), Some('\u{0192}'), Some('\u{2044}'),
+    Some('\u{2039}'), Some('\u{203A}'), Some('\u{2212}'), Some('\u{2030}'), Some('\u{201E}'), Some('\u{201C}'), Some('\u{201D}'), Some('\u{2018}'),
+    Some('\u{2019}'), Some('\u{201A}'), Some('\u{2122}'), Some('\u{FB01}'), Some('\u{FB02}'), Some('\u{0141}'), Some('\u{0152}'), Some('\u{0160}'),
+    Some('\u{0178}'), Some('\u{017D}'), Some('\u{0131}'), Some('\u{0142}'), Some('\u{0153}'), Some('\u{0161}'), Some('\u{017E}'), None,
+    Some('\u{20AC}'),
+];
+
+/// The character for `b` in PDFDocEncoding, or `None` if it is undefined.
+pub fn pdf_doc_to_char(b: u8) -> Option<char> {
+    match b {
+        0x18 ..= 0x1F => Some(PDF_DOC_18[(b - 0x18) as usize]),
+        0x80 ..= 0xA0 => PDF_DOC_80[(b - 0x80) as usize],
+        0x7F | 0xAD => None,
+        _ => Some(b as char),
+    }
+}
+
+/// The PDFDocEncoding byte for `c`, if it has one.
+pub fn char_to_pdf_doc(c: char) -> Option<u8> {
+    match c as u32 {
+        0x18 ..= 0x1F | 0x7F ..= 0xA0 | 0xAD => None,
+        n @ 0 ..= 0xFF => Some(n as u8),
+        _ => {
+            if let Some(i) = PDF_DOC_18.iter().position(|&d| d == c) {
+                return Some(0x18 + i as u8);
+            }
+            PDF_DOC_80.iter().position(|&d| d == Some(c)).map(|i| 0x80 + i as u8)
+        }
+    }
+}
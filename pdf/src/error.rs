@@ -1,4 +1,4 @@
-use crate::object::ObjNr;
+use crate::object::{ObjNr, PlainRef};
 use crate::parser::ParseFlags;
 use datasize::{data_size, DataSize};
 use std::error::Error;
@@ -131,6 +131,9 @@ pub enum PdfError {
     #[snafu(display("Tried to dereference non-existing object nr {}.", obj_nr))]
     NullRef { obj_nr: u64 },
 
+    #[snafu(display("Object nr {} references itself (through {:?}).", obj_nr, chain))]
+    ReferenceCycle { obj_nr: u64, chain: Vec<u64> },
+
     #[snafu(display("Expected primitive {}, found primitive {} instead.", expected, found))]
     UnexpectedPrimitive {
         expected: &'static str,
@@ -218,23 +221,188 @@ pub enum PdfError {
     #[snafu(display("Max nesting depth reached"))]
     MaxDepth,
 
+    /// One of the [`Limits`](crate::object::Limits) was exceeded.
+    #[snafu(display("{} exceeds the limit of {}", what, max))]
+    LimitExceeded { what: &'static str, max: u64 },
+
     #[snafu(display("Invalid Run length encoding"))]
     RleError,
 
+    #[snafu(display("Checksum mismatch"))]
+    ChecksumMismatch,
+
     #[snafu(display("Invalid"))]
     Invalid,
+
+    #[snafu(display("Unsupported: {}", feature))]
+    Unsupported { feature: String },
+
+    /// An error within object `obj`. `pos` is where the object, or the data of the stream, starts
+    /// in the file. It is `None` for objects in object streams.
+    #[snafu(display("In object {} {}{}, caused by\n  {}", obj.id, obj.gen, pos.map(|p| format!(" at byte {}", p)).unwrap_or_default(), source))]
+    InObject {
+        obj: PlainRef,
+        pos: Option<usize>,
+        #[snafu(source)]
+        source: Box<PdfError>,
+    },
+
+    /// The bytes are not available yet, see [`AsyncFile`](crate::async_file::AsyncFile).
+    #[snafu(display("Bytes {}..{} are not loaded yet", start, end))]
+    NotLoaded { start: usize, end: usize },
 }
+
+/// Broad classification of a [`PdfError`], see [`PdfError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The requested object or a required dictionary entry is not present.
+    ///
+    /// Many features of PDF are optional, so this is often not a problem.
+    Missing,
+    /// The object is present, but could not be parsed or has the wrong type or value.
+    Malformed,
+    /// The file uses something that is not implemented.
+    Unsupported,
+    /// Wrong password or undecryptable data.
+    Encryption,
+    /// A resource limit (like the nesting depth) was reached.
+    Limit,
+    Io,
+    Other,
+}
+
 impl PdfError {
     pub fn is_eof(&self) -> bool {
         match self {
             PdfError::EOF => true,
-            PdfError::Try { ref source, .. } => source.is_eof(),
+            PdfError::Try { ref source, .. } | PdfError::InObject { ref source, .. } => source.is_eof(),
             _ => false,
         }
     }
+
+    /// The error without the `Try`, `Shared`, `FromPrimitive` and `InObject` context around it.
+    pub fn root_cause(&self) -> &PdfError {
+        match *self {
+            PdfError::Try { ref source, .. } => source.root_cause(),
+            PdfError::InObject { ref source, .. } => source.root_cause(),
+            PdfError::FromPrimitive { ref source, .. } => source.root_cause(),
+            PdfError::Shared { ref source } => source.root_cause(),
+            ref e => e,
+        }
+    }
+
+    /// Whether this error comes from a reference to an object that doesn't exist,
+    /// possibly passed through the cache or a `t!`.
+    pub(crate) fn is_dangling_ref(&self) -> bool {
+        match *self {
+            PdfError::Try { ref source, .. } => source.is_dangling_ref(),
+            PdfError::InObject { ref source, .. } => source.is_dangling_ref(),
+            PdfError::Shared { ref source } => source.is_dangling_ref(),
+            PdfError::NullRef { .. } | PdfError::FreeObject { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this error means that something is absent, or that it is broken.
+    ///
+    /// If a dictionary entry is present but its value can't be read because something
+    /// *within* it is missing, the error is `Malformed`, not `Missing`.
+    /// References to objects that don't exist count as missing.
+    pub fn kind(&self) -> ErrorKind {
+        use PdfError::*;
+        match *self {
+            Try { ref source, .. } | InObject { ref source, .. } => source.kind(),
+            Shared { ref source } => source.kind(),
+            FromPrimitive { ref source, .. } => match source.kind() {
+                ErrorKind::Missing => match source.root_cause() {
+                    NullRef { .. } | FreeObject { .. } => ErrorKind::Missing,
+                    _ => ErrorKind::Malformed,
+                },
+                kind => kind,
+            },
+            MissingEntry { .. } | NotFound { .. } | NullRef { .. } | FreeObject { .. }
+            | PageOutOfBounds { .. } | PageNotFound { .. } => ErrorKind::Missing,
+            EOF | NoOpArg | Parse { .. } | Encoding { .. } | Bounds { .. } | UnexpectedLexeme { .. }
+            | UnknownType { .. } | UnknownVariant { .. } | XRefStreamType { .. } | ContentReadPastBoundary
+            | PrimitiveNotAllowed { .. } | HexDecode { .. } | Ascii85TailError | IncorrectPredictorType { .. }
+            | KeyValueMismatch { .. } | WrongDictionaryType { .. } | ReferenceCycle { .. }
+            | UnexpectedPrimitive { .. } | ObjStmOutOfBounds { .. } | UnspecifiedXRefEntry { .. }
+            | Jpeg { .. } | PostScriptParse | PostScriptExec | Utf16Decode | Utf8Decode | CidDecode
+            | RleError | ChecksumMismatch | Invalid | NoneError { .. } => ErrorKind::Malformed,
+            Unsupported { .. } => ErrorKind::Unsupported,
+            InvalidPassword | DecryptionFailure => ErrorKind::Encryption,
+            MaxDepth | LimitExceeded { .. } => ErrorKind::Limit,
+            Io { .. } | NotLoaded { .. } => ErrorKind::Io,
+            Reference | Other { .. } => ErrorKind::Other,
+        }
+    }
+    pub fn is_missing(&self) -> bool {
+        self.kind() == ErrorKind::Missing
+    }
+    pub fn is_malformed(&self) -> bool {
+        self.kind() == ErrorKind::Malformed
+    }
+
+    /// The innermost dictionary entry that could not be read, as the name of the type and the key.
+    pub fn key(&self) -> Option<(&'static str, &str)> {
+        match *self {
+            PdfError::Try { ref source, .. } | PdfError::InObject { ref source, .. } => source.key(),
+            PdfError::Shared { ref source } => source.key(),
+            PdfError::FromPrimitive { typ, field, ref source } => Some(source.key().unwrap_or((typ, field))),
+            PdfError::MissingEntry { typ, ref field } => Some((typ, field.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Where the error occurred and what it is, for pointing users to the broken object.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut obj = None;
+        let mut pos = None;
+        let mut e = self;
+        loop {
+            match *e {
+                PdfError::Try { ref source, .. } | PdfError::FromPrimitive { ref source, .. } => e = source,
+                PdfError::Shared { ref source } => e = source,
+                PdfError::InObject { obj: o, pos: p, ref source } => {
+                    obj = Some(o);
+                    pos = p;
+                    e = source;
+                }
+                // relative to the start of the object
+                PdfError::UnexpectedLexeme { pos: offset, .. } | PdfError::UnknownType { pos: offset, .. } => {
+                    pos = pos.map(|p| p + offset);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        Diagnostic { obj, pos, message: e.to_string() }
+    }
 }
 datasize::non_dynamic_const_heap_size!(PdfError, 0);
 
+/// See [`PdfError::diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The innermost object the error occurred in.
+    pub obj: Option<PlainRef>,
+    /// The position in the file, as close to the error as known.
+    pub pos: Option<usize>,
+    /// The error without context.
+    pub message: String,
+}
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(obj) = self.obj {
+            write!(f, "object {} {}: ", obj.id, obj.gen)?;
+        }
+        if let Some(pos) = self.pos {
+            write!(f, "byte {}: ", pos)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
 #[cfg(feature = "cache")]
 impl globalcache::ValueSize for PdfError {
     #[inline]
@@ -356,7 +524,7 @@ macro_rules! bail {
 }
 macro_rules! unimplemented {
     () => {
-        bail!("Unimplemented @ {}:{}", file!(), line!())
+        err!($crate::PdfError::Unsupported { feature: format!("{}:{}", file!(), line!()) })
     };
 }
 
@@ -382,7 +550,9 @@ pub fn dump_data(data: &[u8]) {
 
 #[cfg(test)]
 mod tests {
-    use super::PdfError;
+    use super::{ErrorKind, PdfError};
+    use crate::object::PlainRef;
+    use std::sync::Arc;
 
     fn assert_send<T: Send>() {}
 
@@ -394,4 +564,47 @@ mod tests {
         assert_send::<PdfError>();
         assert_sync::<PdfError>();
     }
+
+    fn from_primitive(typ: &'static str, field: &'static str, source: PdfError) -> PdfError {
+        PdfError::FromPrimitive { typ, field, source: Box::new(source) }
+    }
+    fn wrap(source: PdfError) -> PdfError {
+        let e = PdfError::InObject { obj: PlainRef { id: 7, gen: 0 }, pos: Some(100), source: Box::new(source) };
+        let e = PdfError::Shared { source: Arc::new(e) };
+        PdfError::Try { file: file!(), line: line!(), column: column!(), context: super::Context(vec![]), source: Box::new(e) }
+    }
+
+    #[test]
+    fn error_kind() {
+        let missing = PdfError::MissingEntry { typ: "Page", field: "MediaBox".into() };
+        assert_eq!(missing.kind(), ErrorKind::Missing);
+        assert_eq!(missing.key(), Some(("Page", "MediaBox")));
+        assert_eq!(PdfError::MaxDepth.kind(), ErrorKind::Limit);
+        assert_eq!(PdfError::InvalidPassword.kind(), ErrorKind::Encryption);
+        assert_eq!(PdfError::Unsupported { feature: "XFA".into() }.kind(), ErrorKind::Unsupported);
+        assert_eq!(PdfError::Reference.kind(), ErrorKind::Other);
+
+        // the entry is there, but it points to an object that doesn't exist
+        let e = wrap(from_primitive("Page", "Parent", PdfError::NullRef { obj_nr: 3 }));
+        assert!(e.is_missing());
+        assert_eq!(e.key(), Some(("Page", "Parent")));
+        assert!(matches!(e.root_cause(), PdfError::NullRef { obj_nr: 3 }));
+        let e = wrap(from_primitive("Catalog", "Outlines", wrap(PdfError::FreeObject { obj_nr: 4 })));
+        assert!(e.is_missing());
+
+        // the entry is there, but something within it is missing or broken
+        let inner = PdfError::MissingEntry { typ: "Font", field: "Subtype".into() };
+        let e = wrap(from_primitive("Resources", "Font", wrap(inner)));
+        assert!(e.is_malformed());
+        assert_eq!(e.key(), Some(("Font", "Subtype")));
+        assert!(matches!(e.root_cause(), PdfError::MissingEntry { typ: "Font", .. }));
+        let e = wrap(from_primitive("Page", "Rotate", PdfError::UnexpectedPrimitive { expected: "Integer", found: "Name" }));
+        assert!(e.is_malformed());
+        assert_eq!(e.key(), Some(("Page", "Rotate")));
+        let e = from_primitive("Page", "Parent", PdfError::MaxDepth);
+        assert_eq!(e.kind(), ErrorKind::Limit);
+
+        assert_eq!(wrap(PdfError::EOF).key(), None);
+        assert!(matches!(wrap(PdfError::EOF).root_cause(), PdfError::EOF));
+    }
 }
@@ -0,0 +1,1034 @@
+//! Form data in the interchange formats FDF and XFDF.
+//!
+//! [`FormData`] holds the values of form fields by their fully qualified name (the partial names of
+//! the field and its ancestors, joined by `.`). It can be read from and written to FDF (a PDF-like file with
+//! an `/FDF` dictionary in the catalog) and XFDF (the XML version of it).
+//! [`InteractiveFormDictionary::export_fdf`] collects the values of a form, and
+//! [`InteractiveFormDictionary::import_fdf`] fills them in.
+//!
+//! XFDF can also hold markup annotations ([`XfdfAnnot`]), which [`File::export_annotations`] collects
+//! and [`File::import_annotations`] adds to the pages of a document.
+//! Other than that, only field values are handled, not annotations in FDF or the other entries of the `/FDF` dictionary.
+//!
+//! [`File::export_annotations`]: crate::file::File::export_annotations
+//! [`File::import_annotations`]: crate::file::File::import_annotations
+
+use std::collections::HashMap;
+
+use crate::content::{serialize_ops, Color, FormXObject, Op, Point, Rgb, Winding};
+use crate::error::*;
+use crate::object::*;
+use crate::parser::{parse_indirect_object, Lexer, ParseFlags};
+use crate::primitive::*;
+use crate::xml::{self, Event};
+use crate::xref::XRef;
+
+/// Fields nested deeper than this are ignored.
+const MAX_DEPTH: usize = 32;
+
+/// The values of form fields, and the annotations of an XFDF document.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormData {
+    /// The PDF file the data belongs to (`/F` in FDF, `<f href=".."/>` in XFDF).
+    pub file: Option<String>,
+    /// Fully qualified field names and their values.
+    pub fields: Vec<(String, FieldValue)>,
+    /// The markup annotations (`<annots>` in XFDF, not read from or written to FDF).
+    pub annots: Vec<XfdfAnnot>,
+}
+
+impl FormData {
+    /// Read the field values of an FDF file.
+    pub fn parse_fdf(data: &[u8]) -> Result<FormData> {
+        if !data.starts_with(b"%FDF-") {
+            bail!("not an FDF file");
+        }
+        // FDF files often have no cross reference table, so look for the objects directly
+        let rebuilt = t!(crate::parser::rebuild_xref(data));
+        let mut objects = HashMap::new();
+        for id in rebuilt.table.iter() {
+            let pos = match rebuilt.table.get(id as ObjNr) {
+                Ok(XRef::Raw { pos, .. }) => pos,
+                _ => continue,
+            };
+            let mut lexer = Lexer::new(data);
+            lexer.set_pos(pos);
+            match parse_indirect_object(&mut lexer, &NoResolve, None, ParseFlags::ANY) {
+                Ok((r, p)) => {
+                    objects.insert(r.id, p);
+                }
+                Err(e) => warn!("skipping FDF object {}: {}", id, e),
+            }
+        }
+
+        let root = match rebuilt.trailer.get("Root") {
+            Some(root) => deref(root, &objects),
+            // without a trailer, take the object that has the `/FDF` entry
+            None => objects.values()
+                .find(|p| matches!(p, Primitive::Dictionary(d) if d.get("FDF").is_some()))
+                .ok_or_else(|| other!("FDF file without catalog"))?,
+        };
+        let fdf = t!(deref(t!(root.as_dictionary()).get("FDF").ok_or_else(|| other!("FDF catalog without /FDF"))?, &objects).as_dictionary());
+
+        let mut form = FormData {
+            file: fdf.get("F").and_then(|f| file_name(deref(f, &objects))),
+            fields: vec![],
+            annots: vec![],
+        };
+        if let Some(fields) = fdf.get("Fields") {
+            read_fields(t!(deref(fields, &objects).as_array()), "", &objects, &mut form.fields, 0)?;
+        }
+        Ok(form)
+    }
+
+    /// Write the field values as an FDF file.
+    pub fn to_fdf(&self) -> Result<Vec<u8>> {
+        let mut fdf = Dictionary::new();
+        if let Some(ref file) = self.file {
+            fdf.insert("F", text_string(file));
+        }
+        let fields = tree(&self.fields).iter().map(fdf_field).collect::<Vec<_>>();
+        fdf.insert("Fields", Primitive::Array(fields));
+        let mut catalog = Dictionary::new();
+        catalog.insert("FDF", fdf);
+
+        let mut out = b"%FDF-1.2\n%\xe2\xe3\xcf\xd3\n1 0 obj\n".to_vec();
+        Primitive::Dictionary(catalog).serialize(&mut out)?;
+        out.extend_from_slice(b"\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        Ok(out)
+    }
+
+    /// Read the field values of an XFDF document.
+    pub fn parse_xfdf(xml: &str) -> Result<FormData> {
+        struct Frame {
+            name: String,
+            values: Vec<String>,
+            has_kids: bool,
+        }
+        let mut form = FormData::default();
+        let mut stack: Vec<Frame> = vec![];
+        let mut value: Option<String> = None;
+        let mut in_annots = false;
+        let mut annot: Option<XfdfAnnot> = None;
+        // the text of `<contents>` or `<gesture>`
+        let mut text: Option<String> = None;
+        for (_, event) in xml::events(xml)? {
+            match event {
+                Event::Start { name: "f", ref attrs } => {
+                    form.file = xml::attr(attrs, "href").map(|s| s.to_owned());
+                }
+                Event::Start { name: "annots", .. } => in_annots = true,
+                Event::End("annots") => in_annots = false,
+                Event::Start { name, ref attrs } if in_annots && annot.is_none() => {
+                    let subtype = match MARKUP.iter().find(|&&(_, element)| element == name) {
+                        Some(&(subtype, _)) => subtype,
+                        None => {
+                            warn!("skipping XFDF annotation {:?}", name);
+                            continue;
+                        }
+                    };
+                    let page = match xml::attr(attrs, "page") {
+                        Some(page) => page.trim().parse().map_err(|_| other!("invalid XFDF page {:?}", page))?,
+                        None => 0,
+                    };
+                    let attrs = attrs.iter()
+                        .filter(|&&(n, _)| n != "page")
+                        .map(|(n, v)| (n.to_string(), v.clone()))
+                        .collect();
+                    annot = Some(XfdfAnnot { subtype: subtype.into(), page, attrs, ..Default::default() });
+                }
+                Event::Start { name: "popup", ref attrs } if annot.is_some() => {
+                    if let Some(ref mut annot) = annot {
+                        annot.popup = Some(attrs.iter()
+                            .filter(|&&(n, _)| n != "page")
+                            .map(|(n, v)| (n.to_string(), v.clone()))
+                            .collect());
+                    }
+                }
+                Event::Start { name: "contents" | "gesture", .. } if annot.is_some() => text = Some(String::new()),
+                Event::End("contents") if annot.is_some() => {
+                    if let Some(ref mut annot) = annot {
+                        annot.contents = text.take();
+                    }
+                }
+                Event::End("gesture") if annot.is_some() => {
+                    if let (Some(annot), Some(gesture)) = (annot.as_mut(), text.take()) {
+                        annot.ink_list.push(parse_points(&gesture)?);
+                    }
+                }
+                Event::End(name) if annot.as_ref().is_some_and(|a| element_name(&a.subtype) == Some(name)) => {
+                    form.annots.extend(annot.take());
+                }
+                Event::Start { name: "field", ref attrs } => {
+                    let name = xml::attr(attrs, "name").ok_or_else(|| other!("XFDF field without name"))?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.has_kids = true;
+                    }
+                    stack.push(Frame { name: name.to_owned(), values: vec![], has_kids: false });
+                }
+                Event::Start { name: "value", .. } if !stack.is_empty() => value = Some(String::new()),
+                Event::Text(s) => {
+                    if let Some(ref mut value) = value {
+                        value.push_str(&s);
+                    }
+                    if let Some(ref mut text) = text {
+                        text.push_str(&s);
+                    }
+                }
+                Event::End("value") => {
+                    if let (Some(value), Some(frame)) = (value.take(), stack.last_mut()) {
+                        frame.values.push(value);
+                    }
+                }
+                Event::End("field") => {
+                    let frame = stack.pop().ok_or_else(|| other!("unbalanced XFDF field"))?;
+                    if frame.has_kids {
+                        continue;
+                    }
+                    let mut name = String::new();
+                    for part in stack.iter().map(|f| &f.name).chain(Some(&frame.name)) {
+                        if !name.is_empty() {
+                            name.push('.');
+                        }
+                        name.push_str(part);
+                    }
+                    let value = match frame.values.len() {
+                        0 => FieldValue::None,
+                        1 => FieldValue::Text(text_string(&frame.values[0])),
+                        _ => FieldValue::Multiple(frame.values.iter().map(|v| text_string(v)).collect()),
+                    };
+                    form.fields.push((name, value));
+                }
+                _ => {}
+            }
+        }
+        Ok(form)
+    }
+
+    /// Write the field values as an XFDF document.
+    pub fn to_xfdf(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<xfdf xmlns=\"http://ns.adobe.com/xfdf/\" xml:space=\"preserve\">\n");
+        if let Some(ref file) = self.file {
+            out.push_str("  <f href=\"");
+            crate::xmp::escape(file, &mut out);
+            out.push_str("\"/>\n");
+        }
+        out.push_str("  <fields>\n");
+        for node in &tree(&self.fields) {
+            xfdf_field(node, 2, &mut out);
+        }
+        out.push_str("  </fields>\n");
+        if !self.annots.is_empty() {
+            out.push_str("  <annots>\n");
+            for annot in &self.annots {
+                xfdf_annot(annot, &mut out);
+            }
+            out.push_str("  </annots>\n");
+        }
+        out.push_str("</xfdf>\n");
+        out
+    }
+}
+
+/// The markup annotations XFDF can hold: their subtypes and the names of their elements.
+const MARKUP: &[(&str, &str)] = &[
+    ("Text", "text"), ("FreeText", "freetext"), ("Line", "line"), ("Square", "square"), ("Circle", "circle"),
+    ("Polygon", "polygon"), ("PolyLine", "polyline"), ("Highlight", "highlight"), ("Underline", "underline"),
+    ("Squiggly", "squiggly"), ("StrikeOut", "strikeout"), ("Stamp", "stamp"), ("Caret", "caret"), ("Ink", "ink"),
+    ("Redact", "redact"),
+];
+
+/// The names of the annotation flags in the `flags` attribute, from the lowest bit.
+const FLAGS: [&str; 10] = [
+    "invisible", "hidden", "print", "nozoom", "norotate", "noview", "readonly", "locked", "togglenoview", "lockedcontents",
+];
+
+fn element_name(subtype: &str) -> Option<&'static str> {
+    MARKUP.iter().find(|&&(s, _)| s == subtype).map(|&(_, element)| element)
+}
+
+/// A markup annotation in XFDF: an element named after the subtype (like `<highlight>`), with most
+/// entries of the annotation dictionary as attributes.
+///
+/// The attributes are kept as they are written, see [`XfdfAnnot::to_annot`] for those that are understood.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct XfdfAnnot {
+    /// The subtype, like `Highlight`.
+    pub subtype: String,
+    /// The index of the page, starting at 0.
+    pub page: u32,
+    /// The other attributes, like `rect`, `color`, `name`, `inreplyto` or `coords`.
+    pub attrs: Vec<(String, String)>,
+    pub contents: Option<String>,
+    /// The attributes of the `<popup>` element, if there is one.
+    pub popup: Option<Vec<(String, String)>>,
+    /// The paths of an ink annotation (`<inklist>`).
+    pub ink_list: Vec<Vec<Point>>,
+}
+
+impl XfdfAnnot {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.attrs.push((name.to_owned(), value));
+    }
+
+    /// The annotation dictionary, without `P`, `IRT` and `Popup`, which refer to other objects.
+    ///
+    /// These attributes are read: `rect`, `color`, `name`, `date`, `flags`, `title`, `subject`,
+    /// `creationdate`, `opacity`, `width`, `replyType`, `coords`, `icon`, `start` and `end`,
+    /// `vertices` and `interior-color`.
+    /// Text markup with `coords`, squares, circles, lines, polygons, polylines, ink and notes get a
+    /// normal appearance, which is created in `update`. The others are left to the viewer.
+    pub fn to_annot(&self, update: &mut impl Updater) -> Result<Annot> {
+        let color = self.attr("color").map(parse_color).transpose()?;
+        let markup = match self.subtype.as_str() {
+            "Highlight" => Some(TextMarkup::Highlight),
+            "Underline" => Some(TextMarkup::Underline),
+            "Squiggly" => Some(TextMarkup::Squiggly),
+            "StrikeOut" => Some(TextMarkup::StrikeOut),
+            _ => None,
+        };
+        let mut annot = match (markup, self.attr("coords")) {
+            (Some(kind), Some(coords)) => {
+                let coords = parse_numbers(coords)?;
+                if coords.len() % 8 != 0 {
+                    bail!("XFDF coords are no list of quadrilaterals");
+                }
+                let point = |q: &[f32], i: usize| Point { x: q[2 * i], y: q[2 * i + 1] };
+                let quads: Vec<_> = coords.chunks(8).map(|q| [point(q, 0), point(q, 1), point(q, 2), point(q, 3)]).collect();
+                let default = if kind == TextMarkup::Highlight { [1., 1., 0.] } else { [1., 0., 0.] };
+                t!(Annot::text_markup(kind, &quads, color.unwrap_or(default), update))
+            }
+            _ => Annot {
+                subtype: self.subtype.as_str().into(),
+                rect: None,
+                contents: None,
+                page: None,
+                annotation_name: None,
+                date: None,
+                annot_flags: 0,
+                appearance_streams: None,
+                appearance_state: None,
+                border: None,
+                border_style: None,
+                border_effect: None,
+                color: None,
+                ink_list: None,
+                line: None,
+                popup: None,
+                in_reply_to: None,
+                reply_type: None,
+                other: Dictionary::new(),
+            },
+        };
+        let numbers = |n: &[f32]| Primitive::Array(n.iter().map(|&n| Primitive::Number(n)).collect());
+
+        if annot.rect.is_none() {
+            match self.attr("rect") {
+                Some(rect) => annot.rect = Some(parse_rect(rect)?),
+                None => bail!("XFDF {} annotation without rect", self.subtype),
+            }
+        }
+        if let Some(color) = color {
+            annot.color = Some(numbers(&color));
+        }
+        annot.contents = self.contents.as_deref().map(text_string);
+        annot.annotation_name = self.attr("name").map(text_string);
+        if let Some(date) = self.attr("date") {
+            match Date::parse(date) {
+                Ok(date) => annot.date = Some(date),
+                Err(e) => warn!("invalid XFDF date {:?}: {}", date, e),
+            }
+        }
+        if let Some(flags) = self.attr("flags") {
+            annot.annot_flags = flags.split(',')
+                .filter_map(|flag| FLAGS.iter().position(|&f| f == flag.trim()))
+                .fold(0, |flags, bit| flags | 1 << bit);
+        }
+        for (attr, key) in [("title", "T"), ("subject", "Subj"), ("creationdate", "CreationDate")] {
+            if let Some(value) = self.attr(attr) {
+                annot.other.insert(key, text_string(value));
+            }
+        }
+        if let Some(opacity) = self.attr("opacity") {
+            annot.other.insert("CA", parse_number(opacity)?);
+        }
+        let width = self.attr("width").map(parse_number).transpose()?;
+        if let Some(width) = width {
+            annot.border_style = Some(BorderStyle { width, ..Default::default() });
+        }
+        annot.reply_type = match self.attr("replyType") {
+            Some("group") => Some(ReplyType::Group),
+            Some("reply") => Some(ReplyType::Reply),
+            _ => None,
+        };
+        if let (Some(coords), None) = (self.attr("coords"), annot.other.get("QuadPoints")) {
+            annot.other.insert("QuadPoints", numbers(&parse_numbers(coords)?));
+        }
+        if let Some(icon) = self.attr("icon") {
+            annot.other.insert("Name", Name::from(icon));
+        }
+        let line = match (self.attr("start"), self.attr("end")) {
+            (Some(start), Some(end)) => Some([parse_points(start)?, parse_points(end)?].concat()),
+            _ => None,
+        };
+        if let Some(ref line) = line {
+            annot.line = Some(numbers(&line.iter().flat_map(|p| [p.x, p.y]).collect::<Vec<_>>()));
+        }
+        let vertices = self.attr("vertices").map(parse_points).transpose()?;
+        if let Some(ref vertices) = vertices {
+            annot.set_vertices(vertices);
+        }
+        let interior = self.attr("interior-color").map(parse_color).transpose()?;
+        if let Some(interior) = interior {
+            annot.other.insert("IC", numbers(&interior));
+        }
+        if !self.ink_list.is_empty() {
+            annot.set_ink_list(&self.ink_list);
+        }
+
+        if annot.appearance_streams.is_none() {
+            let rect = annot.rect.unwrap_or_default();
+            let stroke = color.unwrap_or([0., 0., 0.]);
+            let rgb = |[red, green, blue]: [f32; 3]| Color::Rgb(Rgb { red, green, blue });
+            let width = width.unwrap_or(1.);
+            let mut ops = vec![Op::LineWidth { width }, Op::StrokeColor { color: rgb(stroke) }];
+            if let Some(interior) = interior {
+                ops.push(Op::FillColor { color: rgb(interior) });
+            }
+            let paint = match interior {
+                Some(_) => Op::FillAndStroke { winding: Winding::NonZero },
+                None => Op::Stroke,
+            };
+            // the border is drawn inside the rectangle
+            let d = width / 2.;
+            let (left, bottom, right, top) = (rect.left + d, rect.bottom + d, rect.right - d, rect.top - d);
+            let path = |points: &[Point], ops: &mut Vec<Op>| {
+                for (i, &p) in points.iter().enumerate() {
+                    ops.push(if i == 0 { Op::MoveTo { p } } else { Op::LineTo { p } });
+                }
+            };
+            let drawn = match self.subtype.as_str() {
+                "Square" => {
+                    path(&[Point { x: left, y: bottom }, Point { x: right, y: bottom }, Point { x: right, y: top }, Point { x: left, y: top }], &mut ops);
+                    ops.extend([Op::Close, paint]);
+                    true
+                }
+                "Circle" => {
+                    // four Bézier curves, one for each quadrant
+                    const K: f32 = 0.552_284_8;
+                    let (cx, cy, rx, ry) = ((left + right) / 2., (bottom + top) / 2., (right - left) / 2., (top - bottom) / 2.);
+                    let p = |x: f32, y: f32| Point { x: cx + x * rx, y: cy + y * ry };
+                    ops.extend([
+                        Op::MoveTo { p: p(1., 0.) },
+                        Op::CurveTo { c1: p(1., K), c2: p(K, 1.), p: p(0., 1.) },
+                        Op::CurveTo { c1: p(-K, 1.), c2: p(-1., K), p: p(-1., 0.) },
+                        Op::CurveTo { c1: p(-1., -K), c2: p(-K, -1.), p: p(0., -1.) },
+                        Op::CurveTo { c1: p(K, -1.), c2: p(1., -K), p: p(1., 0.) },
+                        Op::Close,
+                        paint,
+                    ]);
+                    true
+                }
+                "Polygon" | "PolyLine" if vertices.is_some() => {
+                    path(vertices.as_deref().unwrap_or_default(), &mut ops);
+                    if self.subtype == "Polygon" {
+                        ops.extend([Op::Close, paint]);
+                    } else {
+                        ops.push(Op::Stroke);
+                    }
+                    true
+                }
+                "Line" if line.is_some() => {
+                    path(line.as_deref().unwrap_or_default(), &mut ops);
+                    ops.push(Op::Stroke);
+                    true
+                }
+                "Ink" if !self.ink_list.is_empty() => {
+                    for points in &self.ink_list {
+                        path(points, &mut ops);
+                    }
+                    ops.push(Op::Stroke);
+                    true
+                }
+                "Text" => {
+                    // a note: a box in the color of the annotation
+                    ops = vec![
+                        Op::FillColor { color: rgb(color.unwrap_or([1., 1., 0.])) },
+                        Op::StrokeColor { color: rgb([0., 0., 0.]) },
+                    ];
+                    path(&[Point { x: rect.left + 0.5, y: rect.bottom + 0.5 }, Point { x: rect.right - 0.5, y: rect.bottom + 0.5 },
+                        Point { x: rect.right - 0.5, y: rect.top - 0.5 }, Point { x: rect.left + 0.5, y: rect.top - 0.5 }], &mut ops);
+                    ops.extend([Op::Close, Op::FillAndStroke { winding: Winding::NonZero }]);
+                    true
+                }
+                _ => false,
+            };
+            if drawn {
+                let form = FormXObject { stream: Stream::new(FormDict { bbox: rect, ..Default::default() }, t!(serialize_ops(&ops))) };
+                let appearance = AppearanceStreams {
+                    normal: update.create(AppearanceStreamEntry::Single(form))?.get_ref(),
+                    rollover: None,
+                    down: None,
+                };
+                annot.appearance_streams = Some(MaybeRef::Direct(Shared::new(appearance)));
+            }
+        }
+        Ok(annot)
+    }
+}
+
+/// Describe `annot` of page `page` for XFDF, or return `None` if it is no markup annotation.
+///
+/// `names` has the names of the indirect annotations of the document, for `name` and `inreplyto`.
+pub(crate) fn export_annot(annot: &MaybeRef<Annot>, page: u32, names: &HashMap<PlainRef, String>, resolve: &impl Resolve) -> Result<Option<XfdfAnnot>> {
+    let subtype = match MARKUP.iter().find(|&&(s, _)| s == annot.subtype.as_str()) {
+        Some(&(subtype, _)) => subtype,
+        None => return Ok(None),
+    };
+    let mut out = XfdfAnnot { subtype: subtype.into(), page, ..Default::default() };
+    let numbers = |p: &Primitive| -> Result<Vec<f32>> {
+        t!(p.clone().resolve(resolve)?.into_array()).iter().map(|n| n.clone().resolve(resolve)?.as_number()).collect()
+    };
+    let text = |p: &Primitive| -> Result<String> { Ok(t!(p.clone().resolve(resolve)?.into_string()).to_string_lossy()) };
+
+    let name = match annot.as_ref() {
+        Some(r) => names.get(&r.get_inner()).cloned(),
+        None => annot.annotation_name.as_ref().map(|n| n.to_string_lossy()),
+    };
+    if let Some(name) = name {
+        out.set("name", name);
+    }
+    if let Some(rect) = annot.rect {
+        out.set("rect", rect_attr(&rect));
+    }
+    if let Some(ref color) = annot.color {
+        if let Some(color) = color_attr(&numbers(color)?) {
+            out.set("color", color);
+        }
+    }
+    if let Some(ref date) = annot.date {
+        out.set("date", date.to_string());
+    }
+    if annot.annot_flags != 0 {
+        let flags: Vec<_> = FLAGS.iter().enumerate().filter(|&(bit, _)| annot.annot_flags & 1 << bit != 0).map(|(_, &f)| f).collect();
+        out.set("flags", flags.join(","));
+    }
+    for (key, attr) in [("T", "title"), ("Subj", "subject"), ("CreationDate", "creationdate")] {
+        if let Some(value) = annot.other.get(key) {
+            out.set(attr, text(value)?);
+        }
+    }
+    if let Some(opacity) = annot.other.get("CA") {
+        out.set("opacity", opacity.clone().resolve(resolve)?.as_number()?.to_string());
+    }
+    let width = match (&annot.border_style, &annot.border) {
+        (Some(bs), _) => Some(bs.width),
+        (None, Some(border)) => Some(border.width),
+        (None, None) => None,
+    };
+    if let Some(width) = width {
+        out.set("width", width.to_string());
+    }
+    if let Some(name) = annot.in_reply_to.and_then(|r| names.get(&r.get_inner())) {
+        out.set("inreplyto", name.clone());
+    }
+    match annot.reply_type {
+        Some(ReplyType::Reply) => out.set("replyType", "reply".into()),
+        Some(ReplyType::Group) => out.set("replyType", "group".into()),
+        None => {}
+    }
+    if let Some(quads) = annot.other.get("QuadPoints") {
+        out.set("coords", join(&numbers(quads)?, ","));
+    }
+    if let Some(icon) = annot.other.get("Name") {
+        out.set("icon", icon.clone().resolve(resolve)?.as_name()?.to_owned());
+    }
+    if let Some(ref line) = annot.line {
+        if let [x1, y1, x2, y2] = numbers(line)?[..] {
+            out.set("start", join(&[x1, y1], ","));
+            out.set("end", join(&[x2, y2], ","));
+        }
+    }
+    if let Some(vertices) = t!(annot.vertices(resolve)) {
+        out.set("vertices", points_attr(&vertices));
+    }
+    if let Some(interior) = annot.other.get("IC") {
+        if let Some(interior) = color_attr(&numbers(interior)?) {
+            out.set("interior-color", interior);
+        }
+    }
+    out.contents = annot.contents.as_ref().map(|c| c.to_string_lossy());
+    if let Some(ink_list) = t!(annot.ink_list(resolve)) {
+        out.ink_list = ink_list;
+    }
+    if let Some(popup) = annot.popup {
+        let popup = t!(resolve.get(popup));
+        let mut attrs = vec![];
+        if let Some(rect) = popup.rect {
+            attrs.push(("rect".to_owned(), rect_attr(&rect)));
+        }
+        let open = matches!(popup.other.get("Open"), Some(Primitive::Boolean(true)));
+        attrs.push(("open".to_owned(), if open { "yes" } else { "no" }.to_owned()));
+        out.popup = Some(attrs);
+    }
+    Ok(Some(out))
+}
+
+fn join(numbers: &[f32], sep: &str) -> String {
+    numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+fn rect_attr(rect: &Rectangle) -> String {
+    join(&[rect.left, rect.bottom, rect.right, rect.top], ",")
+}
+
+/// Points as `x,y;x,y`, like XFDF writes vertices and gestures.
+fn points_attr(points: &[Point]) -> String {
+    points.iter().map(|p| join(&[p.x, p.y], ",")).collect::<Vec<_>>().join(";")
+}
+
+/// An RGB color as `#rrggbb`. Gray and CMYK are converted, nothing else is a color.
+fn color_attr(color: &[f32]) -> Option<String> {
+    let [r, g, b] = match *color {
+        [gray] => [gray; 3],
+        [r, g, b] => [r, g, b],
+        [c, m, y, k] => [(1. - c) * (1. - k), (1. - m) * (1. - k), (1. - y) * (1. - k)],
+        _ => return None,
+    };
+    let byte = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+    Some(format!("#{:02X}{:02X}{:02X}", byte(r), byte(g), byte(b)))
+}
+
+fn parse_color(s: &str) -> Result<[f32; 3]> {
+    let hex = s.trim().strip_prefix('#').unwrap_or(s);
+    let byte = |i: usize| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok());
+    match (hex.len(), byte(0), byte(2), byte(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b].map(|c| c as f32 / 255.)),
+        _ => Err(other!("invalid XFDF color {:?}", s)),
+    }
+}
+
+fn parse_number(s: &str) -> Result<f32> {
+    s.trim().parse().map_err(|_| other!("invalid XFDF number {:?}", s))
+}
+
+/// Numbers separated by `,`, `;` or whitespace.
+fn parse_numbers(s: &str) -> Result<Vec<f32>> {
+    s.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(parse_number)
+        .collect()
+}
+
+fn parse_points(s: &str) -> Result<Vec<Point>> {
+    let numbers = parse_numbers(s)?;
+    if numbers.len() % 2 != 0 {
+        bail!("odd number of XFDF coordinates in {:?}", s);
+    }
+    Ok(numbers.chunks_exact(2).map(|p| Point { x: p[0], y: p[1] }).collect())
+}
+
+fn parse_rect(s: &str) -> Result<Rectangle> {
+    match parse_numbers(s)?[..] {
+        [left, bottom, right, top] => Ok(Rectangle { left, bottom, right, top }),
+        _ => Err(other!("invalid XFDF rect {:?}", s)),
+    }
+}
+
+/// The rectangle of a popup without one: next to `rect`, with its top at the same height.
+pub(crate) fn popup_rect(popup: &[(String, String)], rect: Option<Rectangle>) -> Result<Rectangle> {
+    if let Some((_, rect)) = popup.iter().find(|(n, _)| n == "rect") {
+        return parse_rect(rect);
+    }
+    let rect = rect.unwrap_or_default();
+    Ok(Rectangle { left: rect.right, bottom: rect.top - 100., right: rect.right + 180., top: rect.top })
+}
+
+fn xfdf_attrs(attrs: &[(String, String)], out: &mut String) {
+    for (name, value) in attrs {
+        out.push(' ');
+        out.push_str(name);
+        out.push_str("=\"");
+        crate::xmp::escape(value, out);
+        out.push('"');
+    }
+}
+
+fn xfdf_annot(annot: &XfdfAnnot, out: &mut String) {
+    let element = match element_name(&annot.subtype) {
+        Some(element) => element,
+        None => {
+            warn!("not writing {} annotation to XFDF", annot.subtype);
+            return;
+        }
+    };
+    out.push_str("    <");
+    out.push_str(element);
+    out.push_str(&format!(" page=\"{}\"", annot.page));
+    xfdf_attrs(&annot.attrs, out);
+    out.push_str(">\n");
+    if let Some(ref contents) = annot.contents {
+        out.push_str("      <contents>");
+        crate::xmp::escape(contents, out);
+        out.push_str("</contents>\n");
+    }
+    if let Some(ref popup) = annot.popup {
+        out.push_str(&format!("      <popup page=\"{}\"", annot.page));
+        xfdf_attrs(popup, out);
+        out.push_str("/>\n");
+    }
+    if !annot.ink_list.is_empty() {
+        out.push_str("      <inklist>\n");
+        for path in &annot.ink_list {
+            out.push_str("        <gesture>");
+            out.push_str(&points_attr(path));
+            out.push_str("</gesture>\n");
+        }
+        out.push_str("      </inklist>\n");
+    }
+    out.push_str("    </");
+    out.push_str(element);
+    out.push_str(">\n");
+}
+
+/// Follow references to other objects of the file.
+fn deref<'a>(mut p: &'a Primitive, objects: &'a HashMap<ObjNr, Primitive>) -> &'a Primitive {
+    for _ in 0 .. MAX_DEPTH {
+        match *p {
+            Primitive::Reference(r) => p = objects.get(&r.id).unwrap_or(&Primitive::Null),
+            _ => break,
+        }
+    }
+    p
+}
+
+/// A file specification, either a string or a dictionary.
+fn file_name(p: &Primitive) -> Option<String> {
+    match *p {
+        Primitive::String(ref s) => Some(s.to_string_lossy()),
+        Primitive::Dictionary(ref d) => d.get("UF").or_else(|| d.get("F"))
+            .and_then(|f| f.as_string().ok())
+            .map(|s| s.to_string_lossy()),
+        _ => None,
+    }
+}
+
+fn read_fields(fields: &[Primitive], prefix: &str, objects: &HashMap<ObjNr, Primitive>, out: &mut Vec<(String, FieldValue)>, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        bail!("FDF fields nested too deeply");
+    }
+    for field in fields {
+        let field = t!(deref(field, objects).as_dictionary());
+        let name = match field.get("T").map(|t| deref(t, objects).as_string()) {
+            Some(t) if prefix.is_empty() => t?.to_string_lossy(),
+            Some(t) => format!("{}.{}", prefix, t?.to_string_lossy()),
+            None => prefix.to_owned(),
+        };
+        if let Some(value) = field.get("V") {
+            let value = match deref(value, objects) {
+                Primitive::Array(parts) => Primitive::Array(parts.iter().map(|p| deref(p, objects).clone()).collect()),
+                p => p.clone(),
+            };
+            out.push((name.clone(), FieldValue::from_value(Some(&value))));
+        }
+        if let Some(kids) = field.get("Kids") {
+            read_fields(t!(deref(kids, objects).as_array()), &name, objects, out, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// A field of the hierarchy that the fully qualified names describe.
+struct Node<'a> {
+    name: &'a str,
+    value: Option<&'a FieldValue>,
+    kids: Vec<Node<'a>>,
+}
+
+fn tree(fields: &[(String, FieldValue)]) -> Vec<Node<'_>> {
+    let mut roots = vec![];
+    for (name, value) in fields {
+        let mut nodes = &mut roots;
+        let mut parts = name.split('.').peekable();
+        while let Some(part) = parts.next() {
+            let i = match nodes.iter().position(|n: &Node| n.name == part) {
+                Some(i) => i,
+                None => {
+                    nodes.push(Node { name: part, value: None, kids: vec![] });
+                    nodes.len() - 1
+                }
+            };
+            if parts.peek().is_none() {
+                nodes[i].value = Some(value);
+            }
+            nodes = &mut nodes[i].kids;
+        }
+    }
+    roots
+}
+
+fn value_primitive(value: &FieldValue) -> Primitive {
+    match *value {
+        FieldValue::None => Primitive::Null,
+        FieldValue::Text(ref s) => s.clone().into(),
+        FieldValue::Multiple(ref values) => Primitive::Array(values.iter().map(|s| s.clone().into()).collect()),
+        FieldValue::State(ref name) => name.clone().into(),
+    }
+}
+
+fn fdf_field(node: &Node) -> Primitive {
+    let mut dict = Dictionary::new();
+    dict.insert("T", text_string(node.name));
+    if let Some(value) = node.value {
+        dict.insert("V", value_primitive(value));
+    }
+    if !node.kids.is_empty() {
+        dict.insert("Kids", Primitive::Array(node.kids.iter().map(fdf_field).collect()));
+    }
+    dict.into()
+}
+
+fn xfdf_field(node: &Node, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent);
+    out.push_str(&pad);
+    out.push_str("<field name=\"");
+    crate::xmp::escape(node.name, out);
+    out.push_str("\">\n");
+    let values = match node.value {
+        Some(FieldValue::Text(s)) => vec![s.to_string_lossy()],
+        Some(FieldValue::Multiple(values)) => values.iter().map(|s| s.to_string_lossy()).collect(),
+        Some(FieldValue::State(name)) => vec![name.to_string()],
+        Some(FieldValue::None) | None => vec![],
+    };
+    for value in values {
+        out.push_str(&pad);
+        out.push_str("  <value>");
+        crate::xmp::escape(&value, out);
+        out.push_str("</value>\n");
+    }
+    for kid in &node.kids {
+        xfdf_field(kid, indent + 1, out);
+    }
+    out.push_str(&pad);
+    out.push_str("</field>\n");
+}
+
+/// A terminal field (one without named kids) with the attributes it inherits.
+struct Terminal {
+    name: String,
+    typ: Option<FieldType>,
+    value: Nullable<Primitive>,
+    field: RcRef<FieldDictionary>,
+}
+
+fn terminal_fields(fields: &[RcRef<FieldDictionary>], resolve: &impl Resolve) -> Result<Vec<Terminal>> {
+    fn walk(field: RcRef<FieldDictionary>, parent: Option<&Terminal>, resolve: &impl Resolve, out: &mut Vec<Terminal>, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("form fields nested too deeply");
+        }
+        let name = match (parent, field.name.as_ref()) {
+            (Some(p), Some(t)) if !p.name.is_empty() => format!("{}.{}", p.name, t.to_string_lossy()),
+            (_, Some(t)) => t.to_string_lossy(),
+            (Some(p), None) => p.name.clone(),
+            (None, None) => String::new(),
+        };
+        let typ = field.typ.or(parent.and_then(|p| p.typ));
+        let value = match (&field.value, parent) {
+            (Nullable::Absent, Some(p)) => p.value.clone(),
+            (value, _) => value.clone(),
+        };
+        let kids = field.kids.iter().map(|&kid| resolve.get(kid)).collect::<Result<Vec<_>>>()?;
+        let node = Terminal { name, typ, value, field };
+        if kids.iter().any(|kid| kid.name.is_some()) {
+            for kid in kids {
+                walk(kid, Some(&node), resolve, out, depth + 1)?;
+            }
+        } else {
+            out.push(node);
+        }
+        Ok(())
+    }
+    let mut out = vec![];
+    for field in fields {
+        walk(field.clone(), None, resolve, &mut out, 0)?;
+    }
+    Ok(out)
+}
+
+/// Show the appearance `state` in the widget, if it has one for it, and `Off` otherwise.
+fn set_appearance_state(widget: &mut FieldDictionary, state: &str, resolve: &impl Resolve) -> Result<()> {
+    let normal = match widget.other.get("AP") {
+        Some(ap) => t!(ap.clone().resolve(resolve)?.into_dictionary()).get("N").cloned(),
+        None => return Ok(()),
+    };
+    let has_state = match normal.map(|n| n.resolve(resolve)).transpose()? {
+        Some(Primitive::Dictionary(states)) => states.get(state).is_some(),
+        _ => false,
+    };
+    let state = if has_state { state } else { "Off" };
+    widget.other.insert("AS", Name::from(state));
+    Ok(())
+}
+
+impl InteractiveFormDictionary {
+    /// The values of all terminal fields, except signatures and push buttons.
+    ///
+    /// Check boxes and radio buttons have their appearance state as value.
+    pub fn export_fdf(&self, resolve: &impl Resolve) -> Result<FormData> {
+        let fields = terminal_fields(&self.fields, resolve)?.into_iter()
+            .filter(|f| match f.typ {
+                Some(FieldType::Signature | FieldType::SignatureReference) => false,
+                Some(FieldType::Button) => f.field.flags & (1 << 16) == 0,
+                _ => true,
+            })
+            .map(|f| (f.name, FieldValue::from_value(f.value.value())))
+            .collect();
+        Ok(FormData { file: None, fields, annots: vec![] })
+    }
+
+    /// Compute the fields changed by setting the values of `data`.
+    ///
+    /// The changed field dictionaries (and the widgets of buttons, whose appearance state follows
+    /// the new value) are returned and have to be written with [`Updater::update`];
+    /// `File::import_fdf` does that. Fields that are not in the form and signature fields are skipped.
+    ///
+    /// The appearance streams of text and choice fields are not regenerated, so `NeedAppearances`
+    /// should be set.
+    pub fn import_fdf(&self, data: &FormData, resolve: &impl Resolve) -> Result<Vec<(PlainRef, FieldDictionary)>> {
+        let fields = terminal_fields(&self.fields, resolve)?;
+        let mut changes = vec![];
+        for (name, value) in &data.fields {
+            let terminal = match fields.iter().find(|f| f.name == *name) {
+                Some(terminal) => terminal,
+                None => {
+                    warn!("no form field {:?}", name);
+                    continue;
+                }
+            };
+            let mut field = (*terminal.field).clone();
+            match terminal.typ {
+                Some(FieldType::Signature | FieldType::SignatureReference) => {
+                    warn!("not setting signature field {:?}", name);
+                    continue;
+                }
+                Some(FieldType::Button) => {
+                    let state = match *value {
+                        FieldValue::State(ref name) => name.to_string(),
+                        FieldValue::Text(ref s) => s.to_string_lossy(),
+                        FieldValue::Multiple(_) | FieldValue::None => "Off".to_string(),
+                    };
+                    field.value = Nullable::Value(Name::from(state.as_str()).into());
+                    if field.kids.is_empty() {
+                        set_appearance_state(&mut field, &state, resolve)?;
+                    }
+                    for &kid in &field.kids {
+                        let mut widget = (*t!(resolve.get(kid))).clone();
+                        set_appearance_state(&mut widget, &state, resolve)?;
+                        changes.push((kid.get_inner(), widget));
+                    }
+                }
+                _ => {
+                    field.value = match *value {
+                        FieldValue::None => Nullable::Null,
+                        FieldValue::State(ref name) => Nullable::Value(text_string(name).into()),
+                        ref value => Nullable::Value(value_primitive(value)),
+                    };
+                }
+            }
+            changes.push((terminal.field.get_ref().get_inner(), field));
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FormData {
+        FormData {
+            file: Some("form.pdf".into()),
+            fields: vec![
+                ("name.first".into(), FieldValue::Text(text_string("Zoë"))),
+                ("name.last".into(), FieldValue::Text("Smith & <Sons>".into())),
+                ("languages".into(), FieldValue::Multiple(vec!["de".into(), "ja".into()])),
+                ("comment".into(), FieldValue::None),
+            ],
+            annots: vec![],
+        }
+    }
+
+    #[test]
+    fn fdf() {
+        let mut data = sample();
+        data.fields.push(("agree".into(), FieldValue::State("Yes".into())));
+        let fdf = data.to_fdf().unwrap();
+        assert!(fdf.starts_with(b"%FDF-1.2"));
+        assert_eq!(FormData::parse_fdf(&fdf).unwrap(), data);
+    }
+
+    #[test]
+    fn fdf_with_indirect_fields() {
+        let fdf = b"%FDF-1.2\n1 0 obj\n<< /FDF << /Fields 2 0 R /F (a.pdf) >> >>\nendobj\n\
+            2 0 obj\n[ << /T (a) /Kids [ 3 0 R ] >> ]\nendobj\n\
+            3 0 obj\n<< /T (b) /V /On >>\nendobj\n\
+            trailer\n<< /Root 1 0 R >>\n%%EOF\n";
+        let data = FormData::parse_fdf(fdf).unwrap();
+        assert_eq!(data.file.as_deref(), Some("a.pdf"));
+        assert_eq!(data.fields, [("a.b".to_string(), FieldValue::State("On".into()))]);
+    }
+
+    #[test]
+    fn xfdf() {
+        let data = sample();
+        let xml = data.to_xfdf();
+        assert!(xml.contains("<value>Smith &amp; &lt;Sons&gt;</value>"));
+        assert_eq!(FormData::parse_xfdf(&xml).unwrap(), data);
+
+        let xml = r#"<?xml version="1.0"?>
+            <!-- exported -->
+            <xfdf xmlns="http://ns.adobe.com/xfdf/"><f href='x.pdf'/><fields>
+            <field name="a.b"><value>&#x41;&#66;</value></field>
+            <field name="c"><value><![CDATA[<d>]]></value></field>
+            </fields></xfdf>"#;
+        let data = FormData::parse_xfdf(xml).unwrap();
+        assert_eq!(data.file.as_deref(), Some("x.pdf"));
+        assert_eq!(data.fields, [
+            ("a.b".to_string(), FieldValue::Text("AB".into())),
+            ("c".to_string(), FieldValue::Text("<d>".into())),
+        ]);
+    }
+
+    #[test]
+    fn xfdf_annots() {
+        let xml = r##"<?xml version="1.0" encoding="UTF-8"?>
+            <xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve"><annots>
+            <highlight page="1" name="a" color="#FFFF00" coords="10,30,50,30,10,20,50,20" flags="print,nozoom">
+              <contents>look &amp; see</contents>
+              <popup page="1" rect="60,10,160,60" open="yes"/>
+            </highlight>
+            <text page="1" name="b" inreplyto="a" rect="10,20,30,40"><contents-richtext><body><p>x</p></body></contents-richtext></text>
+            <ink page="0" rect="0,0,10,10"><inklist><gesture>1,2;3,4</gesture></inklist></ink>
+            <sound page="0"/>
+            </annots></xfdf>"##;
+        let data = FormData::parse_xfdf(xml).unwrap();
+        assert_eq!(data.annots.len(), 3);
+        let [highlight, reply, ink] = &data.annots[..] else { unreachable!() };
+        assert_eq!((highlight.subtype.as_str(), highlight.page), ("Highlight", 1));
+        assert_eq!(highlight.contents.as_deref(), Some("look & see"));
+        assert_eq!(highlight.popup.as_ref().unwrap()[1], ("open".to_string(), "yes".to_string()));
+        assert_eq!(reply.attr("inreplyto"), Some("a"));
+        assert_eq!(reply.contents, None);
+        assert_eq!(ink.ink_list, [vec![Point { x: 1., y: 2. }, Point { x: 3., y: 4. }]]);
+        assert_eq!(FormData::parse_xfdf(&data.to_xfdf()).unwrap(), data);
+
+        assert_eq!(color_attr(&[0., 0., 0., 1.]).as_deref(), Some("#000000"));
+        assert_eq!(parse_color("#FF8000").unwrap(), [1., 128. / 255., 0.]);
+        assert!(parse_rect("1,2,3").is_err());
+    }
+}
@@ -1,22 +1,27 @@
 //! This is kind of the entry-point of the type-safe PDF functionality.
 use std::marker::PhantomData;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(any(feature = "cache", feature = "json"))]
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::path::Path;
 use std::io::Write;
 
 use crate as pdf;
 use crate::error::*;
 use crate::object::*;
-use crate::primitive::{Primitive, Dictionary, PdfString};
-use crate::backend::Backend;
+use crate::primitive::{Primitive, Dictionary, PdfString, Name, Date, NumberFormat, StringFormat, WriteOptions};
+use crate::backend::{Backend, ReadBackend};
 use crate::any::*;
 use crate::parser::{Lexer, parse_with_lexer};
-use crate::parser::{parse_indirect_object, parse, ParseFlags};
+use crate::parser::{parse_indirect_object, parse, rebuild_xref, ParseFlags};
 use crate::xref::{XRef, XRefTable, XRefInfo};
-use crate::crypt::Decoder;
+use crate::crypt::{Decoder, SecurityHandlerProvider};
 use crate::crypt::CryptDict;
-use crate::enc::{StreamFilter, decode};
+use crate::enc::{StreamFilter, FilterProvider, decode_with_options};
+use crate::visit::ObjectVisitor;
+use crate::sanitize::{SanitizeOptions, SanitizeReport};
 use std::ops::Range;
 use datasize::DataSize;
 
@@ -40,7 +45,26 @@ impl<T> PromisedRef<T> {
 pub trait Cache<T: Clone> {
     fn get_or_compute(&self, key: PlainRef, compute: impl FnOnce() -> T) -> T;
     fn clear(&self);
+    /// Usage of the cache, if it keeps track.
+    fn stats(&self) -> Option<CacheStats> {
+        None
+    }
+}
+
+/// Usage of a cache, see [`File::cache_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of cached entries.
+    pub entries: usize,
+    /// Estimated heap size of the cached values in bytes.
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to stay within the limits.
+    pub evictions: u64,
 }
+
+#[derive(Clone)]
 pub struct NoCache;
 impl<T: Clone> Cache<T> for NoCache {
     fn get_or_compute(&self, _key: PlainRef, compute: impl FnOnce() -> T) -> T {
@@ -59,10 +83,188 @@ impl<T: Clone + ValueSize + Send + 'static> Cache<T> for Arc<SyncCache<PlainRef,
     }
 }
 
+/// Which entry an [`LruCache`] drops when it is full.
+#[cfg(feature="cache")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Eviction {
+    /// The entry that was not used for the longest time.
+    #[default]
+    LeastRecentlyUsed,
+    /// The biggest entry, so many small objects can stay cached while a few large streams come and go.
+    LargestFirst,
+}
+
+/// Limits of an [`LruCache`].
+#[cfg(feature="cache")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheLimits {
+    /// Maximum number of entries.
+    pub entries: usize,
+    /// Maximum estimated size of all values in bytes (see [`ValueSize`]).
+    /// Values larger than this are not cached at all.
+    pub bytes: usize,
+    pub eviction: Eviction,
+}
+#[cfg(feature="cache")]
+impl Default for CacheLimits {
+    /// No limits.
+    fn default() -> Self {
+        CacheLimits {
+            entries: usize::MAX,
+            bytes: usize::MAX,
+            eviction: Eviction::LeastRecentlyUsed,
+        }
+    }
+}
+#[cfg(feature="cache")]
+impl CacheLimits {
+    /// At most `n` entries.
+    pub fn entries(n: usize) -> Self {
+        CacheLimits { entries: n, ..Default::default() }
+    }
+    /// At most `n` bytes.
+    pub fn bytes(n: usize) -> Self {
+        CacheLimits { bytes: n, ..Default::default() }
+    }
+    pub fn eviction(self, eviction: Eviction) -> Self {
+        CacheLimits { eviction, ..self }
+    }
+}
+
+/// A cache with a bound on the number of entries and their size.
+///
+/// [`SyncCache`] keeps everything until the global cache cleans it up. This gives a fixed bound
+/// per cache instead, so the parsed objects and the decoded streams of a `File` can be tuned separately.
+/// Limits of 0 disable caching.
+#[cfg(feature="cache")]
+pub struct LruCache<T> {
+    limits: CacheLimits,
+    inner: Mutex<LruInner<T>>,
+}
+#[cfg(feature="cache")]
+struct LruInner<T> {
+    entries: HashMap<PlainRef, LruEntry<T>>,
+    /// last use -> key
+    order: BTreeMap<u64, PlainRef>,
+    tick: u64,
+    stats: CacheStats,
+}
+#[cfg(feature="cache")]
+struct LruEntry<T> {
+    value: T,
+    last_used: u64,
+    size: usize,
+}
+#[cfg(feature="cache")]
+impl<T: Clone + ValueSize> LruCache<T> {
+    /// Keep the `capacity` most recently used entries.
+    pub fn new(capacity: usize) -> Arc<Self> {
+        LruCache::with_limits(CacheLimits::entries(capacity))
+    }
+    pub fn with_limits(limits: CacheLimits) -> Arc<Self> {
+        Arc::new(LruCache {
+            limits,
+            inner: Mutex::new(LruInner {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                stats: CacheStats::default(),
+            }),
+        })
+    }
+    pub fn capacity(&self) -> usize {
+        self.limits.entries
+    }
+    pub fn limits(&self) -> CacheLimits {
+        self.limits
+    }
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Estimated heap size of the cached values in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().stats.size
+    }
+}
+#[cfg(feature="cache")]
+impl<T: Clone> LruInner<T> {
+    fn get(&mut self, key: PlainRef) -> Option<T> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&key)?;
+        self.order.remove(&entry.last_used);
+        self.order.insert(self.tick, key);
+        entry.last_used = self.tick;
+        Some(entry.value.clone())
+    }
+    fn insert(&mut self, key: PlainRef, value: T, size: usize, limits: &CacheLimits) {
+        self.tick += 1;
+        if let Some(old) = self.entries.insert(key, LruEntry { value, last_used: self.tick, size }) {
+            // computed twice by concurrent calls
+            self.order.remove(&old.last_used);
+            self.stats.size -= old.size;
+        }
+        self.order.insert(self.tick, key);
+        self.stats.size += size;
+        while self.entries.len() > limits.entries || self.stats.size > limits.bytes {
+            // the new entry fits on its own, so it is never the one to go
+            let victim = match limits.eviction {
+                Eviction::LeastRecentlyUsed => self.order.values().find(|&&k| k != key),
+                Eviction::LargestFirst => self.order.values()
+                    .filter(|&&k| k != key)
+                    .max_by_key(|&k| self.entries[k].size),
+            };
+            let Some(&victim) = victim else { break };
+            let entry = self.entries.remove(&victim).unwrap();
+            self.order.remove(&entry.last_used);
+            self.stats.size -= entry.size;
+            self.stats.evictions += 1;
+        }
+        self.stats.entries = self.entries.len();
+    }
+}
+#[cfg(feature="cache")]
+impl<T: Clone + ValueSize> Cache<T> for Arc<LruCache<T>> {
+    fn get_or_compute(&self, key: PlainRef, compute: impl FnOnce() -> T) -> T {
+        if self.limits.entries == 0 || self.limits.bytes == 0 {
+            return compute();
+        }
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(value) = inner.get(key) {
+                inner.stats.hits += 1;
+                return value;
+            }
+            inner.stats.misses += 1;
+        }
+        // computing may need other entries, so the lock is not held meanwhile
+        let value = compute();
+        let size = value.size();
+        if size <= self.limits.bytes {
+            self.inner.lock().unwrap().insert(key, value.clone(), size, &self.limits);
+        }
+        value
+    }
+    fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.order.clear();
+        inner.stats.entries = 0;
+        inner.stats.size = 0;
+    }
+    fn stats(&self) -> Option<CacheStats> {
+        Some(self.inner.lock().unwrap().stats)
+    }
+}
+
 pub trait Log {
     fn load_object(&self, _r: PlainRef) {}
     fn log_get(&self, _r: PlainRef) {}
 }
+#[derive(Clone)]
 pub struct NoLog;
 impl Log for NoLog {}
 
@@ -78,12 +280,26 @@ pub struct Storage<B, OC, SC, L> {
 
     decoder:    Option<Decoder>,
     options:    ParseOptions,
+    // bytes decoded from streams so far, see `Limits::max_total_decoded`
+    decoded:    AtomicU64,
+    // the streams counted in `decoded`, so that decoding one again doesn't add up
+    counted:    Mutex<HashSet<PlainRef>>,
 
     backend:    B,
 
     // Position of the PDF header in the file.
     start_offset: usize,
 
+    // where the last cross-reference section starts, the `Prev` of the next update
+    last_xref: Option<usize>,
+
+    // merge identical new objects when saving
+    dedup: bool,
+    // don't write objects that are the same as in the backend
+    round_trip: bool,
+    // how numbers and strings of objects are written when saving
+    write_options: WriteOptions,
+
     log: L
 }
 
@@ -103,6 +319,12 @@ where
             options: ParseOptions::strict(),
             backend: Vec::from(&b"%PDF-1.7\n"[..]),
             start_offset: 0,
+            last_xref: None,
+            decoded: AtomicU64::new(0),
+            counted: Mutex::new(HashSet::new()),
+            dedup: false,
+            round_trip: false,
+            write_options: WriteOptions::default(),
             log
         }
     }
@@ -118,17 +340,73 @@ where
     pub fn into_inner(self) -> B {
         self.backend
     }
+    /// Forget all cached objects and streams, including cached errors.
+    #[cfg(feature="async")]
+    pub(crate) fn clear_caches(&self) {
+        self.cache.clear();
+        self.stream_cache.clear();
+    }
     pub fn version(&self) -> Result<String> {
-        Ok(String::from_utf8(self.backend.read(self.start_offset+1..self.start_offset+8)?.to_owned())?)
+        Ok(String::from_utf8(self.backend.read(self.start_offset+1..self.start_offset+8)?.to_vec())?)
     }
     pub fn resolver(&self) -> impl Resolve + '_ {
         StorageResolver::new(self)
     }
+    /// Merge identical streams and dictionaries when saving, see [`Storage::dedup`].
+    pub fn set_dedup(&mut self, dedup: bool) {
+        self.dedup = dedup;
+    }
+    /// Only write objects that actually differ from the file when saving.
+    ///
+    /// Objects that were updated with the same content are left out, so the saved file is the
+    /// original followed by an update with just the edits.
+    pub fn set_round_trip(&mut self, round_trip: bool) {
+        self.round_trip = round_trip;
+    }
+    /// How real numbers of objects are written when saving.
+    ///
+    /// Content streams are written when they are created, see [`serialize_ops_with`](crate::content::serialize_ops_with).
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.write_options.numbers = format;
+    }
+    /// How strings of objects are written when saving, like [`StringFormat::Hex`] for binary data.
+    pub fn set_string_format(&mut self, format: StringFormat) {
+        self.write_options.strings = format;
+    }
+    /// Whether `new` is what object `id` already is in the backend.
+    fn is_unchanged(&self, id: ObjNr, new: &Primitive) -> bool {
+        let gen = match self.refs.get(id) {
+            Ok(XRef::Raw { gen_nr, .. }) => gen_nr,
+            Ok(XRef::Stream { .. }) => 0,
+            _ => return false,
+        };
+        let resolve = StorageResolver::new(self);
+        match self.resolve_original(PlainRef { id, gen }, ParseFlags::ANY, &resolve) {
+            Ok(old) => same_primitive(&old, new, &resolve),
+            Err(_) => false,
+        }
+    }
+    /// The numbers of the objects in use.
+    pub(crate) fn object_numbers(&self) -> impl Iterator<Item = ObjNr> + '_ {
+        self.refs.iter().map(|id| id as ObjNr)
+    }
+    /// The objects in use, including those that were changed or created and not saved yet, in order.
+    #[cfg(feature = "json")]
+    pub(crate) fn object_refs(&self) -> Vec<PlainRef> {
+        let mut refs: BTreeMap<ObjNr, GenNr> = self.object_numbers()
+            .map(|id| (id, self.refs.get(id).map(|r| r.get_gen_nr()).unwrap_or(0)))
+            .collect();
+        for (&id, &(_, gen)) in self.changes.iter() {
+            refs.insert(id, gen);
+        }
+        refs.into_iter().map(|(id, gen)| PlainRef { id, gen }).collect()
+    }
     pub fn with_cache(backend: B, options: ParseOptions, object_cache: OC, stream_cache: SC, log: L) -> Result<Self> {
         let start_offset = backend.locate_start_offset()?;
 
         Ok(Storage {
             start_offset,
+            last_xref: None,
             backend,
             refs: XRefTable::new(0),
             cache: object_cache,
@@ -136,18 +414,33 @@ where
             changes: HashMap::new(),
             decoder: None,
             options,
+            decoded: AtomicU64::new(0),
+            counted: Mutex::new(HashSet::new()),
+            dedup: false,
+            round_trip: false,
+            write_options: WriteOptions::default(),
             log
         })
     }
     fn decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
-        let data = self.backend.read(range)?;
-
-        let mut data = Vec::from(data);
+        let pos = range.start;
+        self.decode_inner(id, range, filters)
+            .map_err(|e| PdfError::InObject { obj: id, pos: Some(pos), source: Box::new(e) })
+    }
+    fn decode_inner(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
+        let mut data = self.backend.read(range)?.to_vec();
         if let Some(ref decoder) = self.decoder {
             data = Vec::from(t!(decoder.decrypt(id, &mut data)));
         }
+        let limits = &self.options.limits;
         for filter in filters {
-            data = t!(decode(&data, filter), filter);
+            data = t!(decode_with_options(&data, filter, &self.options), filter);
+        }
+        if !filters.is_empty() && self.counted.lock().unwrap().insert(id) {
+            let total = self.decoded.fetch_add(data.len() as u64, Ordering::Relaxed) + data.len() as u64;
+            if total > limits.max_total_decoded {
+                return Err(PdfError::LimitExceeded { what: "total decoded size", max: limits.max_total_decoded });
+            }
         }
         Ok(data.into())
     }
@@ -159,7 +452,22 @@ where
     pub fn load_storage_and_trailer_password(&mut self, password: &[u8]) -> Result<Dictionary> {
 
         let resolver = StorageResolver::new(self);
-        let (refs, trailer) = t!(self.backend.read_xref_table_and_trailer(self.start_offset, &resolver));
+        let (refs, mut trailer, object_streams) = match self.backend.read_xref_table_and_trailer(self.start_offset, &resolver) {
+            Ok((refs, trailer)) => {
+                self.last_xref = self.backend.locate_xref_offset().ok();
+                (refs, trailer, None)
+            }
+            Err(e) if self.options.allow_xref_error => {
+                warn!("can't read the xref table, rebuilding it: {}", e);
+                let data = t!(self.backend.read(self.start_offset ..));
+                let rebuilt = t!(rebuild_xref(&data));
+                (rebuilt.table, rebuilt.trailer, Some(rebuilt.object_streams))
+            }
+            Err(e) => return Err(e),
+        };
+        if refs.len() > self.options.limits.max_objects {
+            return Err(PdfError::LimitExceeded { what: "number of objects", max: self.options.limits.max_objects as u64 });
+        }
         self.refs = refs;
 
         if let Some(crypt) = trailer.get("Encrypt") {
@@ -179,9 +487,21 @@ where
                 .as_bytes();
 
             let resolver = StorageResolver::new(self);
-            let dict = CryptDict::from_primitive(crypt.clone(), &resolver)?;
-
-            self.decoder = Some(t!(Decoder::from_password(&dict, key, password)));
+            let crypt_dict = t!(crypt.clone().resolve(&resolver)).into_dictionary()?;
+            let decoder = match crypt_dict.get("Filter") {
+                Some(Primitive::Name(name)) if name != "Standard" => {
+                    let handler = self.options.security_handlers.as_ref()
+                        .and_then(|provider| provider.handler(name, &crypt_dict, key, password))
+                        .ok_or_else(|| other!("unsupported security handler {}", name))?;
+                    let encrypt_metadata = crypt_dict.get("EncryptMetadata").map(|p| p.as_bool()).transpose()?.unwrap_or(true);
+                    Decoder::with_handler(t!(handler), encrypt_metadata)
+                }
+                _ => {
+                    let dict = CryptDict::from_primitive(Primitive::Dictionary(crypt_dict), &resolver)?;
+                    t!(Decoder::from_password(&dict, key, password))
+                }
+            };
+            self.decoder = Some(decoder);
             if let Primitive::Reference(reference) = crypt {
                 self.decoder.as_mut().unwrap().encrypt_indirect_object = Some(*reference);
             }
@@ -193,13 +513,66 @@ where
                 }
             }
         }
+        if let Some(object_streams) = object_streams {
+            // they can only be read once the decoder is set up
+            self.add_compressed_objects(&object_streams, &mut trailer);
+            if trailer.get("Root").is_none() {
+                bail!("no catalog found");
+            }
+        }
         Ok(trailer)
     }
+
+    /// Add the objects in `object_streams` to a rebuilt xref table,
+    /// unless the file also has them outside of an object stream.
+    fn add_compressed_objects(&mut self, object_streams: &[ObjNr], trailer: &mut Dictionary) {
+        let mut entries = vec![];
+        let resolver = StorageResolver::new(self);
+        for &stream_id in object_streams {
+            let header = (|| -> Result<()> {
+                let p = t!(resolver.resolve(PlainRef { id: stream_id, gen: 0 }));
+                let stream = t!(Stream::<ObjStmInfo>::from_primitive(p, &resolver));
+                let data = t!(stream.data(&resolver));
+                let mut lexer = Lexer::new(&data);
+                for index in 0 .. stream.info.num_objects {
+                    let id = t!(t!(lexer.next()).to::<ObjNr>());
+                    let offset = t!(t!(lexer.next()).to::<usize>());
+                    entries.push((id, stream_id, index));
+
+                    if trailer.get("Root").is_none() {
+                        let mut lexer = Lexer::new(&data);
+                        lexer.set_pos(stream.info.first + offset);
+                        let dict = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::DICT).and_then(|p| p.into_dictionary());
+                        if matches!(dict, Ok(ref dict) if dict.get("Type").and_then(|t| t.as_name().ok()) == Some("Catalog")) {
+                            trailer.insert("Root", Primitive::Reference(PlainRef { id, gen: 0 }));
+                        }
+                    }
+                }
+                Ok(())
+            })();
+            if let Err(e) = header {
+                warn!("can't read object stream {}: {}", stream_id, e);
+            }
+        }
+        let size = entries.iter().map(|&(id, _, _)| id + 1).max().unwrap_or(0);
+        if trailer.get("Size").and_then(|s| s.as_u32().ok()).is_some_and(|n| (n as ObjNr) < size) {
+            trailer.insert("Size", Primitive::Integer(size as i32));
+        }
+        for (id, stream_id, index) in entries {
+            if matches!(self.refs.get(id), Ok(XRef::Raw { .. })) {
+                continue;
+            }
+            while self.refs.len() as ObjNr <= id {
+                self.refs.push(XRef::Invalid);
+            }
+            self.refs.set(id, XRef::Stream { stream_id, index });
+        }
+    }
     pub fn scan(&self) -> impl Iterator<Item = Result<ScanItem>> + '_ {
         let xref_offset = self.backend.locate_xref_offset().unwrap();
-        let slice = self.backend.read(self.start_offset .. xref_offset).unwrap();
-        let mut lexer = Lexer::with_offset(slice, 0);
-        
+        let data = self.backend.read(self.start_offset .. xref_offset).unwrap();
+        let mut next_pos = 0;
+
         fn skip_xref(lexer: &mut Lexer) -> Result<()> {
             while lexer.next()? != "trailer" {
 
@@ -209,7 +582,9 @@ where
 
         let resolver = StorageResolver::new(self);
         std::iter::from_fn(move || {
-            loop {
+            let mut lexer = Lexer::with_offset(&data, 0);
+            lexer.set_pos(next_pos);
+            let item = (|| loop {
                 let pos = lexer.get_pos();
                 match parse_indirect_object(&mut lexer, &resolver, self.decoder.as_ref(), ParseFlags::all()) {
                     Ok((r, p)) => return Some(Ok(ScanItem::Object(r, p))),
@@ -236,33 +611,83 @@ where
                         return Some(Err(e));
                     }
                 }
-            }
+            })();
+            next_pos = lexer.get_pos();
+            item
         })
     }
+    /// See [`File::decode_streams`].
+    #[cfg(feature="parallel")]
+    fn decode_streams(&self) -> usize
+    where
+        B: Sync,
+        OC: Sync,
+        SC: Sync,
+        L: Sync,
+    {
+        use rayon::prelude::*;
+
+        let mut obj_streams = vec![];
+        let mut objects = vec![];
+        for id in self.refs.iter() {
+            match self.refs.get(id as ObjNr) {
+                Ok(XRef::Stream { stream_id, .. }) => obj_streams.push(stream_id),
+                Ok(XRef::Raw { gen_nr, .. }) => objects.push(PlainRef { id: id as ObjNr, gen: gen_nr }),
+                _ => {}
+            }
+        }
+        obj_streams.sort_unstable();
+        obj_streams.dedup();
+
+        // the resolver keeps track of the objects being loaded, so every task needs its own
+        obj_streams.par_iter().for_each(|&id| {
+            let resolver = StorageResolver::new(self);
+            if let Err(e) = resolver.get::<ObjectStream>(Ref::from_id(id)) {
+                warn!("can't decode object stream {}: {:?}", id, e);
+            }
+        });
+        objects.par_iter().filter(|&&r| {
+            let resolver = StorageResolver::new(self);
+            match resolver.resolve(r) {
+                Ok(Primitive::Stream(stream)) => Stream::<()>::from_stream(stream, &resolver)
+                    .and_then(|stream| stream.data(&resolver))
+                    .is_ok(),
+                _ => false,
+            }
+        }).count()
+    }
     fn resolve_ref(&self, r: PlainRef, flags: ParseFlags, resolve: &impl Resolve) -> Result<Primitive> {
         match self.changes.get(&r.id) {
             Some((p, _)) => Ok((*p).clone()),
-            None => match t!(self.refs.get(r.id)) {
-                XRef::Raw {pos, ..} => {
-                    let mut lexer = Lexer::with_offset(t!(self.backend.read(self.start_offset + pos ..)), self.start_offset + pos);
-                    let p = t!(parse_indirect_object(&mut lexer, resolve, self.decoder.as_ref(), flags)).1;
-                    Ok(p)
+            None => self.resolve_original(r, flags, resolve),
+        }
+    }
+    /// The object as it is in the backend, without changes.
+    fn resolve_original(&self, r: PlainRef, flags: ParseFlags, resolve: &impl Resolve) -> Result<Primitive> {
+        match t!(self.refs.get(r.id)) {
+            XRef::Raw {pos, ..} => {
+                let decoder = self.decoder.as_ref();
+                let pos = self.start_offset + pos;
+                match self.backend.parse_at(pos, |lexer| parse_indirect_object(lexer, resolve, decoder, flags)) {
+                    Ok((_, p)) => Ok(p),
+                    Err(e) => Err(PdfError::InObject { obj: r, pos: Some(pos), source: Box::new(e) }),
                 }
-                XRef::Stream {stream_id, index} => {
-                    if !flags.contains(ParseFlags::STREAM) {
-                        return Err(PdfError::PrimitiveNotAllowed { found: ParseFlags::STREAM, allowed: flags });
-                    }
-                    // use get to cache the object stream
-                    let obj_stream = resolve.get::<ObjectStream>(Ref::from_id(stream_id))?;
-
-                    let (data, range) = t!(obj_stream.get_object_slice(index, resolve));
-                    let slice = data.get(range.clone()).ok_or_else(|| other!("invalid range {:?}, but only have {} bytes", range, data.len()))?;
-                    parse(slice, resolve, flags)
+            }
+            XRef::Stream {stream_id, index} => {
+                if !flags.contains(ParseFlags::STREAM) {
+                    return Err(PdfError::PrimitiveNotAllowed { found: ParseFlags::STREAM, allowed: flags });
                 }
-                XRef::Free {..} => err!(PdfError::FreeObject {obj_nr: r.id}),
-                XRef::Promised => unimplemented!(),
-                XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
+                // use get to cache the object stream
+                let obj_stream = resolve.get::<ObjectStream>(Ref::from_id(stream_id))?;
+
+                let (data, range) = t!(obj_stream.get_object_slice(index, resolve));
+                let slice = data.get(range.clone()).ok_or_else(|| other!("invalid range {:?}, but only have {} bytes", range, data.len()))?;
+                parse(slice, resolve, flags)
+                    .map_err(|e| PdfError::InObject { obj: r, pos: None, source: Box::new(e) })
             }
+            XRef::Free {..} => err!(PdfError::FreeObject {obj_nr: r.id}),
+            XRef::Promised => unimplemented!(),
+            XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
         }
     }
 }
@@ -303,7 +728,25 @@ where
         let storage = self.storage;
         storage.log.load_object(r);
 
-        storage.resolve_ref(r, flags, self)
+        // an object that consists of just a reference is followed,
+        // as long as it doesn't lead back to where it started.
+        let mut seen = vec![];
+        let mut r = r;
+        loop {
+            match t!(storage.resolve_ref(r, flags, self)) {
+                Primitive::Reference(next) => {
+                    seen.push(r.id);
+                    if seen.contains(&next.id) {
+                        return Err(PdfError::ReferenceCycle { obj_nr: next.id, chain: seen });
+                    }
+                    if seen.len() >= storage.options.max_depth {
+                        return Err(PdfError::MaxDepth);
+                    }
+                    r = next;
+                }
+                p => return Ok(p),
+            }
+        }
     }
 
     fn get<T: Object+DataSize>(&self, r: Ref<T>) -> Result<RcRef<T>> {
@@ -314,7 +757,13 @@ where
             debug!("get {key:?} as {}", std::any::type_name::<T>());
             let mut chain = self.chain.lock().unwrap();
             if chain.contains(&key) {
-                bail!("Recursive reference");
+                return Err(PdfError::ReferenceCycle {
+                    obj_nr: key.id,
+                    chain: chain.iter().map(|r| r.id).collect(),
+                });
+            }
+            if chain.len() >= self.storage.options.max_depth {
+                return Err(PdfError::MaxDepth);
             }
             chain.push(key);
         }
@@ -433,18 +882,31 @@ where
     pub fn save(&mut self, trailer: &mut Trailer) -> Result<&[u8]> {
         // writing the trailer generates another id for the info dictionary
         trailer.size = (self.refs.len() + 2) as _;
-        let trailer_dict = trailer.to_dict(self)?;
+        // link the update to the previous revision, see `File::revisions`
+        trailer.prev_trailer_pos = self.last_xref.map(|pos| pos as _);
+        let mut trailer_dict = trailer.to_dict(self)?;
+        if self.dedup {
+            let merged = self.dedup();
+            if !merged.is_empty() {
+                for val in trailer_dict.values_mut() {
+                    replace_refs(val, &merged);
+                }
+            }
+        }
         
         let xref_promise = self.promise::<Stream<XRefInfo>>();
 
         let mut changes: Vec<_> = self.changes.iter().collect();
         changes.sort_unstable_by_key(|&(id, _)| id);
+        if self.round_trip {
+            changes.retain(|&(&id, (primitive, _))| !self.is_unchanged(id, primitive));
+        }
 
         for &(&id, &(ref primitive, gen)) in changes.iter() {
             let pos = self.backend.len();
             self.refs.set(id, XRef::Raw { pos: pos as _, gen_nr: gen });
             writeln!(self.backend, "{} {} obj", id, gen)?;
-            primitive.serialize(&mut self.backend)?;
+            primitive.serialize_with(&mut self.backend, self.write_options)?;
             writeln!(self.backend, "\nendobj")?;
         }
 
@@ -459,12 +921,13 @@ where
             xref_and_trailer.info.insert(k.clone(), v.clone());
         }
 
-        xref_and_trailer.serialize(&mut self.backend)?;
+        xref_and_trailer.serialize_with(&mut self.backend, self.write_options)?;
         writeln!(self.backend, "endobj")?;
 
         let _ = self.fulfill(xref_promise, stream)?;
 
         write!(self.backend, "\nstartxref\n{}\n%%EOF", xref_pos).unwrap();
+        self.last_xref = Some(xref_pos);
 
         // update trailer which may have change now.
         self.cache.clear();
@@ -474,16 +937,152 @@ where
     }
 }
 
+impl<OC, SC, L> Storage<Vec<u8>, OC, SC, L> {
+    /// Merge identical streams and dictionaries that were created since the last save,
+    /// like the copies of a font in merged documents.
+    ///
+    /// Only fonts with their descriptors and programs, images, forms, graphics states and color spaces
+    /// are merged. Page tree nodes, annotations and other objects that must stay distinct are left alone.
+    /// Returns the merged objects and the reference that replaces each of them.
+    /// References to them held elsewhere no longer resolve.
+    pub fn dedup(&mut self) -> HashMap<ObjNr, PlainRef> {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut merged: HashMap<ObjNr, PlainRef> = HashMap::new();
+        loop {
+            let mut ids: Vec<ObjNr> = self.changes.keys().copied()
+                .filter(|&id| matches!(self.refs.get(id), Ok(XRef::Promised)))
+                .collect();
+            ids.sort_unstable();
+
+            // hash of the object -> ids
+            let mut seen: HashMap<u64, Vec<ObjNr>> = HashMap::new();
+            let mut round = HashMap::new();
+            for id in ids {
+                let (ref primitive, gen) = self.changes[&id];
+                if !can_merge(primitive) {
+                    continue;
+                }
+                let mut hasher = DefaultHasher::new();
+                primitive.hash(&mut hasher);
+                let same = seen.entry(hasher.finish()).or_default();
+                match same.iter().find(|other| self.changes[other].0 == *primitive) {
+                    Some(&other) => {
+                        round.insert(id, PlainRef { id: other, gen: self.changes[&other].1 });
+                        self.refs.set(id, XRef::Free { next_obj_nr: 0, gen_nr: gen + 1 });
+                    }
+                    None => same.push(id),
+                }
+            }
+            if round.is_empty() {
+                break;
+            }
+            for id in round.keys() {
+                self.changes.remove(id);
+            }
+            for (primitive, _) in self.changes.values_mut() {
+                replace_refs(primitive, &round);
+            }
+            // objects merged earlier may point to objects merged now
+            for r in merged.values_mut() {
+                if let Some(&new) = round.get(&r.id) {
+                    *r = new;
+                }
+            }
+            merged.extend(round);
+        }
+        merged
+    }
+}
+
+/// Equal, including the order of dictionary keys. Stream data is compared after reading it.
+fn same_primitive(a: &Primitive, b: &Primitive, resolve: &impl Resolve) -> bool {
+    let same_dict = |a: &Dictionary, b: &Dictionary| {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|((ka, va), (kb, vb))| ka == kb && same_primitive(va, vb, resolve))
+    };
+    match (a, b) {
+        (Primitive::Dictionary(a), Primitive::Dictionary(b)) => same_dict(a, b),
+        (Primitive::Array(a), Primitive::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_primitive(a, b, resolve))
+        }
+        (Primitive::Stream(a), Primitive::Stream(b)) => same_dict(&a.info, &b.info) && match (a.raw_data(resolve), b.raw_data(resolve)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        },
+        (a, b) => a == b,
+    }
+}
+
+/// Only resources are merged: two identical pages, annotations or form fields are still different objects.
+fn can_merge(primitive: &Primitive) -> bool {
+    fn name<'a>(dict: &'a Dictionary, key: &str) -> Option<&'a str> {
+        dict.get(key).and_then(|p| p.as_name().ok())
+    }
+    match *primitive {
+        Primitive::Dictionary(ref dict) => matches!(name(dict, "Type"), Some("Font" | "FontDescriptor" | "ExtGState")),
+        Primitive::Stream(ref stream) => {
+            let info = &stream.info;
+            match (name(info, "Type"), name(info, "Subtype")) {
+                // images and forms
+                (None | Some("XObject"), Some("Image" | "Form")) => true,
+                // font programs
+                (None, Some("Type1C" | "CIDFontType0C" | "OpenType")) => true,
+                (None, None) if info.get("Length1").is_some() => true,
+                // ICC profiles
+                (None, None) => info.get("N").is_some(),
+                _ => false,
+            }
+        }
+        // color spaces
+        Primitive::Array(ref parts) => matches!(parts.first(), Some(Primitive::Name(family)) if matches!(family.as_str(),
+            "ICCBased" | "Indexed" | "Separation" | "DeviceN" | "CalRGB" | "CalGray" | "Lab" | "Pattern"
+        )),
+        _ => false,
+    }
+}
+
+fn replace_refs(primitive: &mut Primitive, map: &HashMap<ObjNr, PlainRef>) {
+    match *primitive {
+        Primitive::Reference(ref mut r) => {
+            if let Some(&new) = map.get(&r.id) {
+                *r = new;
+            }
+        }
+        Primitive::Array(ref mut parts) => parts.iter_mut().for_each(|p| replace_refs(p, map)),
+        Primitive::Dictionary(ref mut dict) => dict.values_mut().for_each(|p| replace_refs(p, map)),
+        Primitive::Stream(ref mut stream) => stream.info.values_mut().for_each(|p| replace_refs(p, map)),
+        _ => {}
+    }
+}
+
 #[cfg(feature="cache")]
 pub type ObjectCache = Arc<SyncCache<PlainRef, Result<AnySync, Arc<PdfError>>>>;
 #[cfg(feature="cache")]
 pub type StreamCache = Arc<SyncCache<PlainRef, Result<Arc<[u8]>, Arc<PdfError>>>>;
 #[cfg(feature="cache")]
 pub type CachedFile<B> = File<B, ObjectCache, StreamCache, NoLog>;
+#[cfg(feature="cache")]
+pub type BoundedObjectCache = Arc<LruCache<Result<AnySync, Arc<PdfError>>>>;
+#[cfg(feature="cache")]
+pub type BoundedStreamCache = Arc<LruCache<Result<Arc<[u8]>, Arc<PdfError>>>>;
+
+/// One saved state of a document, see [`File::revisions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    /// where its cross-reference section starts, relative to the `%PDF` header
+    pub xref_offset: usize,
+    /// the length of the file up to the end of the revision
+    pub len: usize,
+}
+
+/// A revision of a document, loaded by [`File::load_revision`].
+pub type RevisionFile = File<Vec<u8>, NoCache, NoCache, NoLog>;
 
 pub struct File<B, OC, SC, L> {
     storage:        Storage<B, OC, SC, L>,
     pub trailer:    Trailer,
+    info_policy:    InfoPolicy,
 }
 impl<B, OC, SC, L> Updater for File<B, OC, SC, L>
 where
@@ -506,6 +1105,31 @@ where
     }
 }
 
+/// Resolves like [`File::resolver`], so a `File` can be passed where objects are both read and written.
+impl<B, OC, SC, L> Resolve for File<B, OC, SC, L>
+where
+    B: Backend,
+    OC: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L: Log,
+{
+    fn resolve_flags(&self, r: PlainRef, flags: ParseFlags, depth: usize) -> Result<Primitive> {
+        StorageResolver::new(&self.storage).resolve_flags(r, flags, depth)
+    }
+    fn get<T: Object + DataSize>(&self, r: Ref<T>) -> Result<RcRef<T>> {
+        StorageResolver::new(&self.storage).get(r)
+    }
+    fn options(&self) -> &ParseOptions {
+        &self.storage.options
+    }
+    fn stream_data(&self, id: PlainRef, range: Range<usize>) -> Result<Arc<[u8]>> {
+        StorageResolver::new(&self.storage).stream_data(id, range)
+    }
+    fn get_data_or_decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
+        StorageResolver::new(&self.storage).get_data_or_decode(id, range, filters)
+    }
+}
+
 impl<OC, SC, L> File<Vec<u8>, OC, SC, L>
 where
     OC: Cache<Result<AnySync, Arc<PdfError>>>,
@@ -513,12 +1137,78 @@ where
     L: Log,
 {
     pub fn save_to(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        t!(self.apply_info_policy());
         std::fs::write(path, self.storage.save(&mut self.trailer)?)?;
         Ok(())
     }
+
+    /// Write the document as a new file that only has the objects the current revision uses.
+    ///
+    /// [`save_to`](File::save_to) appends an update, so replaced objects and older revisions stay
+    /// in the file. They are left out here, which is what [redacting](crate::redact) needs.
+    /// The objects are renumbered, the result is not encrypted and signatures no longer match.
+    pub fn rewrite(&mut self) -> Result<Vec<u8>> {
+        t!(self.apply_info_policy());
+        let mut storage = Storage::empty(NoCache, NoCache, NoLog);
+        let root = {
+            let mut importer = crate::build::Importer::new(self.resolver(), &mut storage);
+            t!(importer.clone_plainref(self.trailer.root.get_ref().get_inner()))
+        };
+        let info_dict = match self.trailer.info_dict {
+            Some(ref info) => Some(t!(InfoDict::from_primitive(t!(info.to_primitive(&mut NoUpdate)), &NoResolve))),
+            None => None,
+        };
+        let mut trailer = Trailer {
+            root: t!(storage.resolver().get(Ref::new(root))),
+            encrypt_dict: None,
+            size: 0,
+            id: self.trailer.id.clone(),
+            info_dict,
+            prev_trailer_pos: None,
+        };
+        t!(storage.save(&mut trailer));
+        Ok(storage.into_inner())
+    }
 }
 
+/// See [`File::cache_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileCacheStats {
+    pub objects: Option<CacheStats>,
+    pub streams: Option<CacheStats>,
+}
+
+/// What to do with an entry of the document information dictionary on save.
+#[derive(Debug, Clone, Default)]
+pub enum InfoValue {
+    /// Leave the value as it is.
+    #[default]
+    Keep,
+    /// Replace the value.
+    Set(String),
+    /// Remove the entry.
+    Remove,
+}
 
+/// Controls how the document information dictionary (`/Info` in the trailer) is written on save.
+///
+/// Changes to `Producer`, `Creator` and `ModDate` are applied to the XMP metadata stream
+/// of the catalog as well (as `pdf:Producer` and `xmp:CreatorTool`), so both stay consistent.
+#[derive(Debug, Clone, Default)]
+pub struct InfoPolicy {
+    /// Drop the Info dictionary entirely. It is deprecated in PDF 2.0 in favor of the metadata stream.
+    pub remove_info: bool,
+    pub producer: InfoValue,
+    pub creator: InfoValue,
+    /// Don't touch the modification date (`ModDate`, `xmp:ModifyDate` and `xmp:MetadataDate`)
+    /// and the second part of the file identifier (`ID` in the trailer).
+    ///
+    /// By default they are updated, as the saved file is a new revision of the document.
+    pub keep_mod_date: bool,
+}
+
+
+#[derive(Clone)]
 pub struct FileOptions<'a, OC, SC, L> {
     oc: OC,
     sc: SC,
@@ -550,6 +1240,29 @@ impl FileOptions<'static, ObjectCache, StreamCache, NoLog> {
         }
     }
 }
+#[cfg(feature="cache")]
+impl FileOptions<'static, BoundedObjectCache, BoundedStreamCache, NoLog> {
+    /// Keep at most `objects` parsed objects and `streams` decoded streams.
+    ///
+    /// Extracting text or images benefits from a large stream cache,
+    /// while reading only metadata needs none (0 disables a cache).
+    pub fn cached_bounded(objects: usize, streams: usize) -> Self {
+        Self::cached_limited(CacheLimits::entries(objects), CacheLimits::entries(streams))
+    }
+    /// Caches with the given limits for parsed objects and decoded streams.
+    ///
+    /// A byte budget keeps the memory use of long-lived files in check:
+    /// `FileOptions::cached_limited(CacheLimits::entries(1000), CacheLimits::bytes(64 << 20))`
+    pub fn cached_limited(objects: CacheLimits, streams: CacheLimits) -> Self {
+        FileOptions {
+            oc: LruCache::with_limits(objects),
+            sc: LruCache::with_limits(streams),
+            password: b"",
+            parse_options: ParseOptions::strict(),
+            log: NoLog
+        }
+    }
+}
 impl<'a, OC, SC, L> FileOptions<'a, OC, SC, L>
 where
     OC: Cache<Result<AnySync, Arc<PdfError>>>,
@@ -585,12 +1298,27 @@ where
     pub fn parse_options(self, parse_options: ParseOptions) -> Self {
         FileOptions { parse_options, .. self }
     }
+    /// Decode filters this crate does not know with `provider`.
+    pub fn filter_provider(self, provider: Arc<dyn FilterProvider>) -> Self {
+        let parse_options = self.parse_options.clone().filter_provider(provider);
+        FileOptions { parse_options, .. self }
+    }
+    /// Open files protected by a security handler other than `Standard` with `provider`.
+    pub fn security_handler_provider(self, provider: Arc<dyn SecurityHandlerProvider>) -> Self {
+        let parse_options = self.parse_options.clone().security_handler_provider(provider);
+        FileOptions { parse_options, .. self }
+    }
 
     /// open a file
     pub fn open(self, path: impl AsRef<Path>) -> Result<File<Vec<u8>, OC, SC, L>> {
         let data = std::fs::read(path)?;
         self.load(data)
     }
+    /// Open a file without reading it into memory, see [`ReadBackend`].
+    pub fn open_lazy(self, path: impl AsRef<Path>) -> Result<File<ReadBackend<std::fs::File>, OC, SC, L>> {
+        let file = std::fs::File::open(path)?;
+        self.load(t!(ReadBackend::new(file)))
+    }
     pub fn storage(self) -> Storage<Vec<u8>, OC, SC, L> {
         let FileOptions { oc, sc, log, .. } = self;
         Storage::empty(oc, sc, log)
@@ -601,6 +1329,18 @@ where
         let FileOptions { oc, sc, password, parse_options, log } = self;
         File::load_data(backend, password, parse_options, oc, sc, log)
     }
+
+    /// Load a document in qpdf's JSON format, see [`crate::json::import`].
+    #[cfg(feature = "json")]
+    pub fn load_json(self, json: &str) -> Result<File<Vec<u8>, OC, SC, L>> {
+        self.load(t!(crate::json::import(json)))
+    }
+
+    #[cfg(feature="async")]
+    pub(crate) fn clear_caches(&self) {
+        self.oc.clear();
+        self.sc.clear();
+    }
 }
 
 
@@ -620,15 +1360,22 @@ where
             Primitive::Dictionary(trailer),
             &resolver,
         ));
-        Ok(File { storage, trailer })
+        Ok(File { storage, trailer, info_policy: InfoPolicy::default() })
     }
     pub fn new(storage: Storage<B, OC, SC, L>, trailer: Trailer) -> Self {
-        File { storage, trailer }
+        File { storage, trailer, info_policy: InfoPolicy::default() }
     }
     pub fn resolver(&self) -> impl Resolve + '_ {
         StorageResolver::new(&self.storage)
     }
 
+    /// The objects in use, without the changes that are not saved yet.
+    pub(crate) fn objects_in_use(&self) -> Vec<PlainRef> {
+        self.storage.object_numbers()
+            .map(|id| PlainRef { id, gen: self.storage.refs.get(id).map(|r| r.get_gen_nr()).unwrap_or(0) })
+            .collect()
+    }
+
     pub fn get_root(&self) -> &Catalog {
         &self.trailer.root
     }
@@ -645,11 +1392,524 @@ where
         self.trailer.root.pages.page(&resolver, n)
     }
 
+    /// Write page `n` as a standalone PDF with only that page.
+    ///
+    /// Only the resources used by the content of the page are copied, see [`PageSnapshot`](crate::build::PageSnapshot).
+    pub fn extract_page_pdf(&self, n: u32) -> Result<Vec<u8>> {
+        let page = self.get_page(n)?;
+        crate::build::PageSnapshot::new(&page, self.resolver())?.into_pdf()
+    }
+
+    /// Walk every primitive that can be reached from the trailer, see [`crate::visit`].
+    ///
+    /// The trailer is visited first, with the info dictionary inside of it.
+    pub fn visit(&self, visitor: &mut impl ObjectVisitor) {
+        crate::visit::visit(&Primitive::Dictionary(self.trailer_dict()), &self.resolver(), visitor);
+    }
+
+    /// The document in qpdf's JSON format, see [`crate::json`].
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String> {
+        let version = self.version()?;
+        let version = version.strip_prefix("PDF-").unwrap_or(&version);
+        let json = t!(crate::json::export(&self.resolver(), self.storage.object_refs(), &self.trailer_dict(), version));
+        Ok(json.to_string())
+    }
+
+    /// The trailer with the info dictionary inside of it and without `Size` and `Prev`.
+    fn trailer_dict(&self) -> Dictionary {
+        let mut trailer = Dictionary::new();
+        trailer.insert("Root", self.trailer.root.get_ref().get_inner());
+        if let Some(ref info) = self.trailer.info_dict {
+            if let Ok(info) = info.to_primitive(&mut NoUpdate) {
+                trailer.insert("Info", info);
+            }
+        }
+        if let Some(ref encrypt) = self.trailer.encrypt_dict {
+            trailer.insert("Encrypt", encrypt.get_ref().get_inner());
+        }
+        if !self.trailer.id.is_empty() {
+            trailer.insert("ID", Primitive::Array(self.trailer.id.iter().cloned().map(Primitive::String).collect()));
+        }
+        trailer
+    }
+
+    /// Merge identical streams and dictionaries when saving, see [`Storage::dedup`].
+    pub fn set_dedup(&mut self, dedup: bool) {
+        self.storage.set_dedup(dedup);
+    }
+
+    /// Only write objects that actually changed when saving, see [`Storage::set_round_trip`].
+    pub fn set_round_trip(&mut self, round_trip: bool) {
+        self.storage.set_round_trip(round_trip);
+    }
+
+    /// How real numbers of objects are written when saving, see [`Storage::set_number_format`].
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.storage.set_number_format(format);
+    }
+
+    /// How strings of objects are written when saving, see [`Storage::set_string_format`].
+    pub fn set_string_format(&mut self, format: StringFormat) {
+        self.storage.set_string_format(format);
+    }
+
+    /// Set the policy for the document information dictionary that is applied when saving.
+    pub fn set_info_policy(&mut self, policy: InfoPolicy) {
+        self.info_policy = policy;
+    }
+
+    /// Apply the `InfoPolicy` to the trailer and the XMP metadata.
+    ///
+    /// This is done automatically by `save_to`.
+    pub fn apply_info_policy(&mut self) -> Result<()> {
+        fn apply(value: &InfoValue, field: &mut Option<PdfString>) {
+            match *value {
+                InfoValue::Keep => {}
+                InfoValue::Set(ref s) => *field = Some(PdfString::from(s.as_str())),
+                InfoValue::Remove => *field = None,
+            }
+        }
+        let policy = self.info_policy.clone();
+        let now = Date::now();
+        let sets_value = matches!(policy.producer, InfoValue::Set(_)) || matches!(policy.creator, InfoValue::Set(_));
+        if policy.remove_info {
+            self.trailer.info_dict = None;
+        } else if sets_value && self.trailer.info_dict.is_none() {
+            self.trailer.info_dict = Some(InfoDict::default());
+        }
+        if let Some(ref mut info) = self.trailer.info_dict {
+            apply(&policy.producer, &mut info.producer);
+            apply(&policy.creator, &mut info.creator);
+            if !policy.keep_mod_date {
+                info.mod_date = Some(now.clone());
+            }
+        }
+        if !policy.keep_mod_date {
+            self.touch_id(&now);
+        }
+
+        let metadata = match self.trailer.root.metadata {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let (xml, mut dict) = {
+            let resolver = self.resolver();
+            let stream = t!(resolver.get(metadata));
+            let data = t!((*stream).data(&resolver));
+            let dict = match t!(resolver.resolve(metadata.get_inner())) {
+                Primitive::Stream(stream) => stream.info,
+                _ => Dictionary::new(),
+            };
+            match std::str::from_utf8(&data) {
+                Ok(s) => (s.to_owned(), dict),
+                Err(_) => {
+                    warn!("metadata stream is not UTF-8, not updating it");
+                    return Ok(());
+                }
+            }
+        };
+        let mut new_xml = xml.clone();
+        for (value, name) in [(&policy.producer, "pdf:Producer"), (&policy.creator, "xmp:CreatorTool")] {
+            new_xml = match *value {
+                InfoValue::Keep => new_xml,
+                InfoValue::Set(ref s) => crate::xmp::set_property(&new_xml, name, Some(s)),
+                InfoValue::Remove => crate::xmp::set_property(&new_xml, name, None),
+            };
+        }
+        if !policy.keep_mod_date {
+            let date = now.to_xmp();
+            new_xml = crate::xmp::set_property(&new_xml, "xmp:ModifyDate", Some(&date));
+            new_xml = crate::xmp::set_property(&new_xml, "xmp:MetadataDate", Some(&date));
+        }
+        if new_xml != xml {
+            // the other entries stay, but the new data is written without filters
+            for key in ["Length", "Filter", "DecodeParms", "DL"] {
+                dict.shift_remove(key);
+            }
+            dict.insert("Type", Name::from("Metadata"));
+            dict.insert("Subtype", Name::from("XML"));
+            self.update(metadata.get_inner(), Stream::new(dict, new_xml.into_bytes()))?;
+        }
+        Ok(())
+    }
+
+    /// Give the file a new second identifier (the first one stays the same for all revisions).
+    fn touch_id(&mut self, now: &Date) {
+        let mut seed = format!("{:?}{}", now, self.trailer.size).into_bytes();
+        for id in &self.trailer.id {
+            seed.extend_from_slice(id.as_bytes());
+        }
+        let new_id = PdfString::new(md5::compute(&seed).0.as_slice().into());
+        match self.trailer.id.len() {
+            0 => self.trailer.id = vec![new_id.clone(), new_id],
+            1 => self.trailer.id.push(new_id),
+            _ => self.trailer.id[1] = new_id,
+        }
+    }
+
     pub fn update_catalog(&mut self, catalog: Catalog) -> Result<()> {
         self.trailer.root = self.create(catalog)?;
         Ok(())
     }
 
+    /// The markup annotations of all pages, for [`FormData::annots`](crate::fdf::FormData::annots).
+    ///
+    /// Popups are written along with the annotation they belong to. Annotations without `NM`
+    /// are named after their object number, so replies can refer to them.
+    pub fn export_annotations(&self) -> Result<Vec<crate::fdf::XfdfAnnot>> {
+        let pages = self.pages().collect::<Result<Vec<_>>>()?;
+        let annots = pages.iter().map(|page| page.annotations(self)).collect::<Result<Vec<_>>>()?;
+        let mut names = HashMap::new();
+        for annot in annots.iter().flatten() {
+            if let Some(r) = annot.as_ref() {
+                let r = r.get_inner();
+                let name = match annot.annotation_name {
+                    Some(ref name) => name.to_string_lossy(),
+                    None => format!("pdf-{}-{}", r.id, r.gen),
+                };
+                names.insert(r, name);
+            }
+        }
+        let mut out = vec![];
+        for (n, annots) in annots.iter().enumerate() {
+            for annot in annots {
+                out.extend(crate::fdf::export_annot(annot, n as u32, &names, self)?);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Add `annots` to their pages, see [`XfdfAnnot::to_annot`](crate::fdf::XfdfAnnot::to_annot),
+    /// and return the new annotations.
+    ///
+    /// `inreplyto` can name annotations of the document (by `NM`) or of `annots`.
+    /// Popups are added after the annotation they belong to.
+    pub fn import_annotations(&mut self, annots: &[crate::fdf::XfdfAnnot]) -> Result<Vec<RcRef<Annot>>> {
+        let mut names = HashMap::new();
+        for page in self.pages().collect::<Result<Vec<_>>>()? {
+            for annot in page.annotations(self)? {
+                if let (Some(r), Some(name)) = (annot.as_ref(), annot.annotation_name.as_ref()) {
+                    names.insert(name.to_string_lossy(), r.get_inner());
+                }
+            }
+        }
+        // replies may come before the annotation they refer to
+        let promises: Vec<PromisedRef<Annot>> = annots.iter().map(|_| self.promise()).collect();
+        for (data, promise) in annots.iter().zip(&promises) {
+            if let Some(name) = data.attr("name") {
+                names.insert(name.to_owned(), promise.get_inner());
+            }
+        }
+
+        let mut added: Vec<(u32, Vec<MaybeRef<Annot>>)> = vec![];
+        let mut created = Vec::with_capacity(annots.len());
+        for (data, promise) in annots.iter().zip(promises) {
+            let page = self.get_page(data.page)?;
+            let mut annot = data.to_annot(self)?;
+            annot.page = Some(page);
+            if let Some(name) = data.attr("inreplyto") {
+                match names.get(name) {
+                    Some(&r) => annot.in_reply_to = Some(Ref::new(r)),
+                    None => warn!("no annotation named {:?} to reply to", name),
+                }
+            }
+            let popup = match data.popup {
+                Some(ref attrs) => {
+                    let popup = self.promise::<Annot>();
+                    annot.popup = Some(popup.get_ref());
+                    Some((popup, crate::fdf::popup_rect(attrs, annot.rect)?, attrs.iter().any(|(n, v)| n == "open" && v == "yes")))
+                }
+                None => None,
+            };
+            let annot = self.fulfill(promise, annot)?;
+            let mut new = vec![MaybeRef::Indirect(annot.clone())];
+            if let Some((promise, rect, open)) = popup {
+                let mut popup = Annot::popup(&annot, rect);
+                if open {
+                    popup.other.insert("Open", true);
+                }
+                new.push(MaybeRef::Indirect(self.fulfill(promise, popup)?));
+            }
+            match added.iter_mut().find(|(n, _)| *n == data.page) {
+                Some((_, annots)) => annots.extend(new),
+                None => added.push((data.page, new)),
+            }
+            created.push(annot);
+        }
+
+        for (n, new) in added {
+            let old = self.get_page(n)?;
+            let mut page = (*old).clone();
+            let mut annots = page.annotations(self)?;
+            annots.extend(new);
+            page.set_annotations(annots, self)?;
+            PageRc::update(page, &old, self)?;
+        }
+        Ok(created)
+    }
+
+    /// Compare the changes saved after the document was certified with what the certification
+    /// allows, see [`crate::mdp`]. `None` if the document is not certified.
+    pub fn check_certification(&self) -> Result<Option<crate::mdp::CertificationCheck>> {
+        let permission = match t!(self.trailer.root.certification(self)) {
+            Some(permission) => permission,
+            None => return Ok(None),
+        };
+        let sig = match self.trailer.root.perms.as_ref().and_then(|p| p.doc_mdp.as_ref()) {
+            Some(sig) => sig,
+            None => return Ok(None),
+        };
+        let signed_len = t!(crate::mdp::signed_len(sig));
+        if signed_len > self.storage.backend.len() {
+            bail!("the certification signature covers {} bytes of a file of {}", signed_len, self.storage.backend.len());
+        }
+        // the certified revision is a complete file on its own
+        let certified = t!(self.load_prefix(signed_len));
+
+        let in_use = |xref: &Result<XRef>| matches!(xref, Ok(XRef::Raw { .. } | XRef::Stream { .. }));
+        let len = self.storage.refs.len().max(certified.storage.refs.len()) as ObjNr;
+        let (mut modifications, mut objects) = (vec![], vec![]);
+        for id in 1 .. len {
+            let (old_xref, new_xref) = (certified.storage.refs.get(id), self.storage.refs.get(id));
+            if (!in_use(&old_xref) && !in_use(&new_xref)) || old_xref.as_ref().ok() == new_xref.as_ref().ok() {
+                continue;
+            }
+            let gen = |xref: &Result<XRef>| xref.as_ref().map(|x| x.get_gen_nr()).unwrap_or(0);
+            let old = match in_use(&old_xref) {
+                true => Some(t!(certified.resolve(PlainRef { id, gen: gen(&old_xref) }))),
+                false => None,
+            };
+            let new = match in_use(&new_xref) {
+                true => Some(t!(self.resolve(PlainRef { id, gen: gen(&new_xref) }))),
+                false => None,
+            };
+            let gen = gen(if new.is_some() { &new_xref } else { &old_xref });
+            modifications.push(crate::mdp::Modification {
+                id: PlainRef { id, gen },
+                existed: old.is_some(),
+                exists: new.is_some(),
+                kind: crate::mdp::classify(old.as_ref(), new.as_ref(), &certified, self),
+            });
+            objects.push(new);
+        }
+        crate::mdp::inherit_kinds(&mut modifications, &objects);
+        Ok(Some(crate::mdp::CertificationCheck { permission, signed_len, modifications }))
+    }
+
+    /// The revisions of the document, the oldest first. Each incremental update adds one;
+    /// the last one is the document as it is now.
+    pub fn revisions(&self) -> Result<Vec<Revision>> {
+        let chain = t!(self.storage.backend.xref_chain(self.storage.start_offset, &self.storage.resolver()));
+        let len = self.storage.backend.len();
+        let mut revisions = vec![];
+        for (xref_offset, prev) in chain {
+            // the first page section of a linearized file points forward to the main one,
+            // they make one revision together
+            if prev.is_some_and(|prev| prev > xref_offset) {
+                continue;
+            }
+            let pos = self.storage.start_offset + xref_offset;
+            let end = match self.storage.backend.parse_at(pos, |lexer| {
+                lexer.seek_substr(b"%%EOF").ok_or(PdfError::EOF)?;
+                Ok(pos + lexer.get_pos())
+            }) {
+                Ok(end) => end,
+                // a missing marker at the end of the file
+                Err(_) => len,
+            };
+            // include the end of line after the marker
+            let tail = t!(self.storage.backend.read(end .. (end + 2).min(len)));
+            let end = end + match &*tail {
+                [b'\r', b'\n', ..] => 2,
+                [b'\r' | b'\n', ..] => 1,
+                _ => 0,
+            };
+            revisions.push(Revision { xref_offset, len: end });
+        }
+        revisions.sort_by_key(|r| r.len);
+        revisions.dedup_by_key(|r| r.len);
+        Ok(revisions)
+    }
+
+    /// Open the document as it was in `revision`, which resolves the objects as they were then.
+    ///
+    /// An encrypted document is opened without password.
+    pub fn load_revision(&self, revision: &Revision) -> Result<RevisionFile> {
+        if revision.len > self.storage.backend.len() {
+            bail!("revision of {} bytes in a file of {}", revision.len, self.storage.backend.len());
+        }
+        self.load_prefix(revision.len)
+    }
+
+    /// The first `len` bytes of the file as a file on their own.
+    fn load_prefix(&self, len: usize) -> Result<RevisionFile> {
+        let data = t!(self.storage.backend.read(.. len)).to_vec();
+        FileOptions::uncached().parse_options(self.storage.options.clone()).load(data)
+    }
+
+    /// Turn the document into a portfolio of `files`, which are embedded along with the existing
+    /// attachments. `collection` replaces the collection of the document, if any.
+    pub fn make_portfolio(&mut self, files: Vec<PortfolioFile>, collection: Collection) -> Result<()> {
+        let mut specs = Vec::with_capacity(files.len());
+        for file in files {
+            let mut spec = FileSpec::embed(file.name, file.data, file.options, self)?;
+            spec.description = file.description.map(PdfString::from);
+            spec.collection_item = file.item;
+            specs.push((file.name, spec));
+        }
+
+        // read the catalog and name dictionary again, to get versions that can be changed
+        let root = self.trailer.root.get_ref().get_inner();
+        let (mut catalog, names) = {
+            let resolver = self.resolver();
+            let dict = t!(resolver.resolve(root)?.into_dictionary());
+            let mut names = match dict.get("Names") {
+                Some(names) => t!(NameDictionary::from_primitive(names.clone(), &resolver)),
+                None => t!(NameDictionary::from_primitive(Primitive::Dictionary(Dictionary::new()), &resolver)),
+            };
+            let tree = names.embedded_files.get_or_insert_with(|| NameTree::from_entries(vec![]));
+            for (name, spec) in specs {
+                t!(tree.insert(PdfString::from(name), spec, &resolver));
+            }
+            (t!(Catalog::from_primitive(Primitive::Dictionary(dict), &resolver)), names)
+        };
+        catalog.names = Some(MaybeRef::Indirect(self.create(names)?));
+        catalog.collection = Some(collection);
+        self.update_catalog(catalog)
+    }
+
+    /// Fill the form with the values of `data`, see [`InteractiveFormDictionary::import_fdf`].
+    ///
+    /// `NeedAppearances` is set, so viewers regenerate the appearance of the changed fields.
+    pub fn import_fdf(&mut self, data: &crate::fdf::FormData) -> Result<()> {
+        let (changes, need_appearances) = {
+            let resolver = self.resolver();
+            let forms = match self.trailer.root.forms {
+                Some(ref forms) => forms,
+                None => bail!("the document has no form"),
+            };
+            let changes = forms.import_fdf(data, &resolver)?;
+            (changes, forms.need_appearences)
+        };
+        let catalog = if need_appearances {
+            None
+        } else {
+            let mut catalog = self.editable_catalog()?;
+            if let Some(ref mut forms) = catalog.forms {
+                forms.need_appearences = true;
+            }
+            Some(catalog)
+        };
+        for (r, field) in changes {
+            self.update(r, field)?;
+        }
+        if let Some(catalog) = catalog {
+            self.update_catalog(catalog)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the XFA form, see [`InteractiveFormDictionary::set_xfa`].
+    pub fn set_xfa(&mut self, xfa: &crate::xfa::Xfa) -> Result<()> {
+        let mut catalog = self.editable_catalog()?;
+        match catalog.forms {
+            Some(ref mut forms) => forms.set_xfa(xfa, self)?,
+            None => bail!("the document has no form"),
+        }
+        self.update_catalog(catalog)
+    }
+
+    /// All JavaScript actions of the document, see [`crate::sanitize`].
+    pub fn javascript(&self) -> Vec<crate::sanitize::FoundScript> {
+        crate::sanitize::find_javascript(&Primitive::Dictionary(self.trailer_dict()), &self.resolver())
+    }
+
+    /// Check the document against rules of the specification that reading does not enforce,
+    /// like the length of names or required entries. See [`crate::verify`].
+    pub fn verify(&self) -> Vec<crate::verify::Issue> {
+        crate::verify::strict(&Primitive::Dictionary(self.trailer_dict()), &self.resolver())
+    }
+
+    /// Remove all JavaScript actions from the document. Returns how many were removed.
+    pub fn strip_javascript(&mut self) -> Result<usize> {
+        Ok(self.sanitize(&SanitizeOptions::javascript())?.removed.len())
+    }
+
+    /// Remove active content from every object reachable from the trailer, see [`crate::sanitize`].
+    ///
+    /// Objects that only the removed entries refer to stay in the file until it is written anew.
+    pub fn sanitize(&mut self, options: &SanitizeOptions) -> Result<SanitizeReport> {
+        let root = self.trailer.root.get_ref().get_inner();
+        let mut report = SanitizeReport::default();
+        let mut changes = vec![];
+        {
+            let resolver = self.resolver();
+            let trailer = Primitive::Dictionary(self.trailer_dict());
+            for r in crate::sanitize::reachable_objects(&trailer, &resolver) {
+                let mut p = t!(resolver.resolve(r));
+                let before = report.removed.len();
+                crate::sanitize::sanitize(&mut p, r, options, &resolver, &mut report);
+                if report.removed.len() > before {
+                    changes.push((r, p));
+                }
+            }
+        }
+        for (r, p) in changes {
+            if r == root {
+                let catalog = t!(Catalog::from_primitive(p, &self.resolver()));
+                self.trailer.root = self.update(r, catalog)?;
+            } else {
+                self.update(r, p)?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Encode the streams that `select` picks anew according to `options`, see [`Stream::encoded`].
+    /// Returns how many were changed.
+    ///
+    /// With `StreamOptions::zstd()` this turns a file into an archive that takes less space,
+    /// and [`downgrade_filters`](File::downgrade_filters) turns it back.
+    pub fn recompress_streams(&mut self, options: &StreamOptions, mut select: impl FnMut(&Stream<Dictionary>) -> bool) -> Result<usize> {
+        let mut changes = vec![];
+        {
+            let resolver = self.resolver();
+            for r in self.objects_in_use() {
+                let stream = match resolver.resolve(r) {
+                    Ok(Primitive::Stream(stream)) => stream,
+                    _ => continue,
+                };
+                // cross-reference streams are written anew when saving
+                if stream.info.get("Type").is_some_and(|t| matches!(t, Primitive::Name(n) if n == "XRef")) {
+                    continue;
+                }
+                let stream = t!(Stream::<Dictionary>::from_stream(stream, &resolver));
+                if select(&stream) {
+                    changes.push((r, t!(stream.encoded(options, &resolver))));
+                }
+            }
+        }
+        let n = changes.len();
+        for (r, stream) in changes {
+            self.update(r, stream)?;
+        }
+        Ok(n)
+    }
+
+    /// Encode the streams that use the filters of this crate ([`StreamFilter::is_vendor`]) with
+    /// `FlateDecode`, so that other applications can read the file. Returns how many were changed.
+    pub fn downgrade_filters(&mut self) -> Result<usize> {
+        self.recompress_streams(&StreamOptions::default(), |stream| stream.filters.iter().any(|f| f.is_vendor()))
+    }
+
+    /// Read the catalog again, to get a version that can be changed and passed to `update_catalog`.
+    fn editable_catalog(&self) -> Result<Catalog> {
+        let resolver = self.resolver();
+        let root = t!(resolver.resolve(self.trailer.root.get_ref().get_inner()));
+        Ok(t!(Catalog::from_primitive(root, &resolver)))
+    }
+
     pub fn set_options(&mut self, options: ParseOptions) {
         self.storage.options = options;
     }
@@ -662,11 +1922,42 @@ where
         &self.storage.log
     }
 
+    /// Decode all streams of the file in parallel, so they are in the stream cache when needed.
+    ///
+    /// Object streams come first, as the objects in them are parsed from the decoded data.
+    /// Streams that fail to decode are skipped, the error comes up again when they are used.
+    /// Returns how many streams were decoded.
+    ///
+    /// This only helps with caches that keep the data, like [`FileOptions::cached`].
+    #[cfg(feature="parallel")]
+    pub fn decode_streams(&self) -> usize
+    where
+        B: Sync,
+        OC: Sync,
+        SC: Sync,
+        L: Sync,
+    {
+        self.storage.decode_streams()
+    }
+
+    /// Usage of the object and the stream cache, for caches that keep track (like [`LruCache`]).
+    pub fn cache_stats(&self) -> FileCacheStats {
+        FileCacheStats {
+            objects: self.storage.cache.stats(),
+            streams: self.storage.stream_cache.stats(),
+        }
+    }
+
     /// the version string in the file header.
     /// if the version field in the Catalog is set, this should be used instead.
     pub fn version(&self) -> Result<String> {
         self.storage.version()
     }
+
+    #[cfg(feature="async")]
+    pub(crate) fn clear_caches(&self) {
+        self.storage.clear_caches()
+    }
 }
 
 #[derive(Object, ObjectWrite, DataSize)]
@@ -1,6 +1,8 @@
 use crate as pdf;
-use crate::encoding::Encoding;
+use crate::cmap::{predefined_cmap, CMap};
+use crate::encoding::{glyph_name_to_unicode, BaseEncoding, Encoding};
 use crate::error::*;
+use crate::glyph::{GlyphOutlines, PathSegment};
 use crate::object::*;
 use crate::parser::{parse_with_lexer, Lexer, ParseFlags};
 use crate::primitive::*;
@@ -37,6 +39,7 @@ pub enum FontType {
 }
 
 #[derive(Debug, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Font {
     pub subtype: FontType,
     pub name: Option<Name>,
@@ -52,6 +55,7 @@ pub struct Font {
 }
 
 #[derive(Debug, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FontData {
     Type1(TFont),
     Type0(Type0Font),
@@ -62,6 +66,7 @@ pub enum FontData {
 }
 
 #[derive(Debug, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CidToGidMap {
     Identity,
     Table(Vec<u16>),
@@ -126,8 +131,12 @@ impl Object for Font {
             }
         };
 
-        let encoding = dict
-            .remove("Encoding")
+        // Type 0 fonts read their CMap from the same entry
+        let encoding_primitive = match subtype {
+            FontType::Type0 => dict.get("Encoding").cloned(),
+            _ => dict.remove("Encoding"),
+        };
+        let encoding = encoding_primitive
             .map(|p| Object::from_primitive(p, resolve))
             .transpose()?;
 
@@ -167,8 +176,12 @@ impl ObjectWrite for Font {
         if let Some(ref to_unicode) = self.to_unicode {
             dict.insert("ToUnicode", to_unicode.to_primitive(update)?);
         }
-        if let Some(ref encoding) = self.encoding {
-            dict.insert("Encoding", encoding.to_primitive(update)?);
+        match (&self.data, &self.encoding) {
+            (FontData::Type0(Type0Font { encoding: Some(_), .. }), _) => {}
+            (_, Some(encoding)) => {
+                dict.insert("Encoding", encoding.to_primitive(update)?);
+            }
+            _ => {}
         }
         if let Some(ref name) = self.name {
             dict.insert("BaseFont", name.to_primitive(update)?);
@@ -260,28 +273,88 @@ impl Widths {
         self.values[cid - self.first_char] = width;
     }
 }
+/// The metrics of a CID font for vertical writing, see [`Font::vertical_metrics`].
+#[derive(Debug, Clone)]
+pub struct VerticalMetrics {
+    /// `[vy w1]` from `DW2`
+    default: [f32; 2],
+    /// `(first cid, last cid, [w1 vx vy])` from `W2`
+    ranges: Vec<(usize, usize, [f32; 3])>,
+}
+/// How one glyph is placed in vertical writing, in thousandths of text space units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalMetric {
+    /// The vertical displacement to the next glyph, usually negative.
+    pub w1: f32,
+    /// The position vector from the vertical origin of the glyph to its horizontal one:
+    /// the glyph is drawn with its horizontal origin at the current point minus `v`.
+    pub v: [f32; 2],
+}
+impl VerticalMetrics {
+    /// The metrics of `cid`, given its horizontal width for the default position vector.
+    pub fn get(&self, cid: usize, width: f32) -> VerticalMetric {
+        match self.ranges.iter().rev().find(|&&(first, last, _)| first <= cid && cid <= last) {
+            Some(&(_, _, [w1, vx, vy])) => VerticalMetric { w1, v: [vx, vy] },
+            None => VerticalMetric { w1: self.default[1], v: [width / 2., self.default[0]] },
+        }
+    }
+}
 impl Font {
-    pub fn embedded_data(&self, resolve: &impl Resolve) -> Option<Result<Arc<[u8]>>> {
+    /// The embedded font program, if there is one. For Type 0 fonts the one of the descendant font.
+    pub fn embedded_data(&self, resolve: &impl Resolve) -> Option<Result<EmbeddedFont>> {
         match self.data {
             FontData::Type0(ref t) => t
                 .descendant_fonts
                 .get(0)
                 .and_then(|f| f.embedded_data(resolve)),
             FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => {
-                c.font_descriptor.data(resolve)
+                c.font_descriptor.embedded_font(resolve)
             }
             FontData::Type1(ref t) | FontData::TrueType(ref t) => {
-                t.font_descriptor.as_ref().and_then(|d| d.data(resolve))
+                t.font_descriptor.as_ref().and_then(|d| d.embedded_font(resolve))
             }
             _ => None,
         }
     }
+    /// Parse the outlines of the embedded font program, if there is one.
+    pub fn glyph_outlines(&self, resolve: &impl Resolve) -> Option<Result<GlyphOutlines>> {
+        self.embedded_data(resolve).map(|font| GlyphOutlines::parse(font?.data))
+    }
+    /// Map a character code to the glyph id in `outlines`.
+    ///
+    /// For CID fonts pass the CID instead, see [`cmap`](Font::cmap).
+    pub fn glyph_id(&self, code: u16, outlines: &GlyphOutlines) -> Option<u16> {
+        if self.is_cid() {
+            return match self.cid_to_gid_map() {
+                Some(CidToGidMap::Identity) => Some(code),
+                Some(CidToGidMap::Table(ref table)) => table.get(code as usize).cloned(),
+                None => outlines.gid_for_cid(code),
+            };
+        }
+        let name = self.encoding().and_then(|e| e.differences.get(&(code as u32)));
+        name.and_then(|name| outlines.gid_for_name(name.as_str()))
+            .or_else(|| outlines.gid_for_code(code))
+    }
+    /// The outline of the glyph for `code`, in units of `outlines.units_per_em()`.
+    pub fn glyph_outline(&self, code: u16, outlines: &GlyphOutlines) -> Result<Option<Vec<PathSegment>>> {
+        self.glyph_id(code, outlines).map(|gid| outlines.outline(gid)).transpose()
+    }
     pub fn is_cid(&self) -> bool {
         matches!(
             self.data,
             FontData::Type0(_) | FontData::CIDFontType0(_) | FontData::CIDFontType2(_)
         )
     }
+    /// The CMap of a Type 0 font, which splits strings into codes and maps them to CIDs.
+    ///
+    /// Without an `Encoding` the font is taken to use `Identity-H`.
+    pub fn cmap(&self, resolve: &impl Resolve) -> Result<Option<Arc<CMap>>> {
+        match self.data {
+            FontData::Type0(Type0Font { encoding: Some(ref encoding), .. }) => Ok(Some(t!(encoding.load(resolve)))),
+            FontData::Type0(_) => Ok(Some(Arc::new(CMap::identity(false)))),
+            _ => Ok(None),
+        }
+    }
     pub fn cid_to_gid_map(&self) -> Option<&CidToGidMap> {
         match self.data {
             FontData::Type0(ref inner) => inner
@@ -297,6 +370,46 @@ impl Font {
     pub fn encoding(&self) -> Option<&Encoding> {
         self.encoding.as_ref()
     }
+    /// The encoding built into the font program of a simple font, as far as it is known
+    /// without reading the program: the Symbol font has its own and other fonts not flagged
+    /// as symbolic use `StandardEncoding`.
+    pub fn builtin_encoding(&self) -> Option<BaseEncoding> {
+        let info = self.info()?;
+        let name = self.name.as_ref().map(|n| n.as_str()).unwrap_or_default();
+        // without the subset prefix
+        let name = match name.split_once('+') {
+            Some((prefix, rest)) if prefix.len() == 6 => rest,
+            _ => name,
+        };
+        if name.starts_with("Symbol") {
+            return Some(BaseEncoding::SymbolEncoding);
+        }
+        if name.starts_with("ZapfDingbats") || name.starts_with("Dingbats") {
+            return None;
+        }
+        match info.font_descriptor {
+            Some(ref d) if d.flags & flags::Symbolic != 0 && d.flags & flags::Nonsymbolic == 0 => None,
+            _ => Some(BaseEncoding::StandardEncoding),
+        }
+    }
+    /// The glyph name for `code` in a simple font, from the `Encoding` entry or the encoding
+    /// built into the font.
+    pub fn glyph_name(&self, code: u8) -> Option<&str> {
+        if self.is_cid() {
+            return None;
+        }
+        let builtin = self.builtin_encoding();
+        match self.encoding {
+            Some(ref encoding) => encoding.glyph_name(code, builtin.as_ref()),
+            None => builtin?.glyph_name(code),
+        }
+    }
+    /// The text of `code` in a simple font, from its glyph name, see [`glyph_name_to_unicode`].
+    ///
+    /// The `ToUnicode` map is not consulted.
+    pub fn decode_byte(&self, code: u8) -> Option<String> {
+        glyph_name_to_unicode(self.glyph_name(code)?)
+    }
     pub fn info(&self) -> Option<&TFont> {
         match self.data {
             FontData::Type1(ref info) => Some(info),
@@ -362,6 +475,47 @@ impl Font {
             _ => Ok(None),
         }
     }
+    /// The metrics of a CID font for vertical writing, from `W2` and `DW2`.
+    pub fn vertical_metrics(&self, resolve: &impl Resolve) -> Result<Option<VerticalMetrics>> {
+        match self.data {
+            FontData::Type0(ref t0) => t0.descendant_fonts[0].vertical_metrics(resolve),
+            FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => {
+                let default = match cid.default_vertical.as_deref() {
+                    Some(&[vy, w1]) => [vy, w1],
+                    Some(dw2) => bail!("DW2 has {} entries, not 2", dw2.len()),
+                    None => [880., -1000.],
+                };
+                let mut metrics = VerticalMetrics { default, ranges: vec![] };
+                let mut iter = cid.vertical_widths.iter();
+                while let Some(p) = iter.next() {
+                    let c1 = p.as_usize()?;
+                    let next = match iter.next() {
+                        Some(&Primitive::Reference(r)) => resolve.resolve(r)?,
+                        Some(p) => p.clone(),
+                        None => bail!("missing entry in W2 array"),
+                    };
+                    match next {
+                        Primitive::Array(array) => {
+                            for (i, m) in array.chunks_exact(3).enumerate() {
+                                let m = [m[0].as_number()?, m[1].as_number()?, m[2].as_number()?];
+                                metrics.ranges.push((c1 + i, c1 + i, m));
+                            }
+                        }
+                        Primitive::Integer(c2) => {
+                            let mut m = [0.; 3];
+                            for v in m.iter_mut() {
+                                *v = try_opt!(iter.next()).as_number()?;
+                            }
+                            metrics.ranges.push((c1, c2 as usize, m));
+                        }
+                        p => bail!("unexpected primitive in W2 array: {:?}", p),
+                    }
+                }
+                Ok(Some(metrics))
+            }
+            _ => Ok(None),
+        }
+    }
     pub fn to_unicode(&self, resolve: &impl Resolve) -> Option<Result<ToUnicodeMap>> {
         self.to_unicode
             .as_ref()
@@ -393,10 +547,85 @@ pub struct Type0Font {
     #[pdf(key = "DescendantFonts")]
     pub descendant_fonts: Vec<MaybeRef<Font>>,
 
+    /// The CMap from codes to CIDs.
+    #[pdf(key = "Encoding")]
+    pub encoding: Option<CMapEncoding>,
+
     #[pdf(key = "ToUnicode")]
     pub to_unicode: Option<RcRef<Stream<()>>>,
 }
 
+/// The `Encoding` of a Type 0 font.
+#[derive(Debug, DataSize, Clone, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CMapEncoding {
+    /// The name of a predefined CMap, see [`predefined_cmap`].
+    Predefined(Name),
+    /// A CMap program in a stream.
+    Embedded(RcRef<Stream<CMapDict>>),
+}
+impl Object for CMapEncoding {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Name(name) => Ok(CMapEncoding::Predefined(Name(name))),
+            p => Ok(CMapEncoding::Embedded(RcRef::from_primitive(p, resolve)?)),
+        }
+    }
+}
+impl ObjectWrite for CMapEncoding {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            CMapEncoding::Predefined(ref name) => name.to_primitive(update),
+            CMapEncoding::Embedded(ref stream) => stream.to_primitive(update),
+        }
+    }
+}
+impl CMapEncoding {
+    /// Parse the CMap, loading predefined ones (also those the embedded one is based on)
+    /// with the provider of `resolve.options()`.
+    pub fn load(&self, resolve: &impl Resolve) -> Result<Arc<CMap>> {
+        self.load_nested(resolve, 0)
+    }
+    fn load_nested(&self, resolve: &impl Resolve, depth: usize) -> Result<Arc<CMap>> {
+        let stream = match *self {
+            CMapEncoding::Predefined(ref name) => return predefined_cmap(name, resolve.options()),
+            CMapEncoding::Embedded(ref stream) => stream,
+        };
+        let mut cmap = t!(CMap::parse(&t!((**stream).data(resolve))));
+        if let Some(wmode) = stream.wmode {
+            cmap.set_vertical(wmode == 1);
+        }
+        let parent = match (&stream.use_cmap, cmap.use_cmap()) {
+            (Some(parent), _) => Some((**parent).clone()),
+            (None, Some(name)) => Some(CMapEncoding::Predefined(name.into())),
+            (None, None) => None,
+        };
+        if let Some(parent) = parent {
+            if depth > resolve.options().max_depth {
+                bail!("too many nested CMaps");
+            }
+            cmap = cmap.with_parent(&*parent.load_nested(resolve, depth + 1)?);
+        }
+        Ok(Arc::new(cmap))
+    }
+}
+
+/// The dictionary of an embedded CMap stream.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone, Default)]
+pub struct CMapDict {
+    #[pdf(key = "CMapName")]
+    pub name: Option<Name>,
+
+    #[pdf(key = "CIDSystemInfo")]
+    pub system_info: Option<Dictionary>,
+
+    #[pdf(key = "WMode")]
+    pub wmode: Option<i32>,
+
+    #[pdf(key = "UseCMap")]
+    pub use_cmap: Option<Box<CMapEncoding>>,
+}
+
 #[derive(Object, ObjectWrite, Debug, DataSize, DeepClone)]
 pub struct CIDFont {
     #[pdf(key = "CIDSystemInfo")]
@@ -411,6 +640,13 @@ pub struct CIDFont {
     #[pdf(key = "W")]
     pub widths: Vec<Primitive>,
 
+    /// `[vy w1]` for glyphs not in `W2`, by default `[880 -1000]`.
+    #[pdf(key = "DW2")]
+    pub default_vertical: Option<Vec<f32>>,
+
+    #[pdf(key = "W2")]
+    pub vertical_widths: Vec<Primitive>,
+
     #[pdf(key = "CIDToGIDMap")]
     pub cid_to_gid_map: Option<CidToGidMap>,
 
@@ -473,10 +709,10 @@ pub struct FontDescriptor {
     pub missing_width: f32,
 
     #[pdf(key = "FontFile")]
-    pub font_file: Option<RcRef<Stream<()>>>,
+    pub font_file: Option<RcRef<Stream<FontStream>>>,
 
     #[pdf(key = "FontFile2")]
-    pub font_file2: Option<RcRef<Stream<()>>>,
+    pub font_file2: Option<RcRef<Stream<FontStream>>>,
 
     #[pdf(key = "FontFile3")]
     pub font_file3: Option<RcRef<Stream<FontStream3>>>,
@@ -486,18 +722,131 @@ pub struct FontDescriptor {
 }
 impl FontDescriptor {
     pub fn data(&self, resolve: &impl Resolve) -> Option<Result<Arc<[u8]>>> {
+        self.embedded_font(resolve).map(|font| Ok(font?.data))
+    }
+    /// The embedded font program with its format.
+    pub fn embedded_font(&self, resolve: &impl Resolve) -> Option<Result<EmbeddedFont>> {
+        let font = |format, data: Result<Arc<[u8]>>, lengths| Some(data.map(|data| EmbeddedFont { format, data, lengths }));
         if let Some(ref s) = self.font_file {
-            Some((**s).data(resolve))
+            font(FontFormat::Type1, (**s).data(resolve), [s.length1, s.length2, s.length3].map(|l| l.map(|l| l as usize)))
         } else if let Some(ref s) = self.font_file2 {
-            Some((**s).data(resolve))
+            font(FontFormat::TrueType, (**s).data(resolve), [None; 3])
         } else if let Some(ref s) = self.font_file3 {
-            Some((**s).data(resolve))
+            let format = match s.subtype {
+                FontTypeExt::Type1C | FontTypeExt::CIDFontType0C => FontFormat::Cff,
+                FontTypeExt::OpenType => FontFormat::OpenType,
+            };
+            font(format, (**s).data(resolve), [None; 3])
         } else {
             None
         }
     }
 }
 
+/// The stream dictionary of `FontFile` and `FontFile2`.
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize, DeepClone)]
+pub struct FontStream {
+    /// the length of the cleartext portion of a Type 1 font
+    #[pdf(key = "Length1")]
+    pub length1: Option<u32>,
+
+    /// the length of the encrypted portion of a Type 1 font
+    #[pdf(key = "Length2")]
+    pub length2: Option<u32>,
+
+    /// the length of the fixed content portion of a Type 1 font, the zeros and `cleartomark`
+    #[pdf(key = "Length3")]
+    pub length3: Option<u32>,
+}
+
+/// The kinds of font programs that can be embedded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontFormat {
+    /// `FontFile`: a Type 1 font, cleartext followed by the binary encrypted portion
+    Type1,
+    /// `FontFile2`
+    TrueType,
+    /// `FontFile3` with the subtype `Type1C` or `CIDFontType0C`: a bare CFF font
+    Cff,
+    /// `FontFile3` with the subtype `OpenType`
+    OpenType,
+}
+impl FontFormat {
+    /// The usual file extension for fonts in this format, with Type 1 fonts as PFB files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            FontFormat::Type1 => "pfb",
+            FontFormat::TrueType => "ttf",
+            FontFormat::Cff => "cff",
+            FontFormat::OpenType => "otf",
+        }
+    }
+}
+
+/// A font program embedded in a PDF file, see [`Font::embedded_data`].
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    pub format: FontFormat,
+    pub data: Arc<[u8]>,
+    /// `Length1`, `Length2` and `Length3` of a Type 1 font, which are often wrong.
+    pub lengths: [Option<usize>; 3],
+}
+impl EmbeddedFont {
+    /// The cleartext, encrypted and fixed content portions of a Type 1 font.
+    ///
+    /// `Length1` and `Length2` are used if they fit the data. Otherwise the cleartext ends
+    /// after `eexec` and the fixed content starts with the zeros before `cleartomark`.
+    pub fn type1_parts(&self) -> Option<[&[u8]; 3]> {
+        if self.format != FontFormat::Type1 {
+            return None;
+        }
+        let data = &*self.data;
+        let clear = match self.lengths[0] {
+            Some(l1) if l1 <= data.len() && find(&data[..l1], b"eexec").is_some() => l1,
+            _ => {
+                let mut end = find(data, b"eexec")? + 5;
+                while matches!(data.get(end), Some(b'\r' | b'\n' | b' ' | b'\t')) {
+                    end += 1;
+                }
+                end
+            }
+        };
+        let encrypted = match self.lengths[1] {
+            Some(l2) if clear + l2 <= data.len() => clear + l2,
+            _ => match data[clear..].windows(11).rposition(|w| w == b"cleartomark") {
+                Some(pos) => {
+                    let mut end = clear + pos;
+                    while end > clear && matches!(data[end - 1], b'0' | b'\r' | b'\n' | b' ' | b'\t') {
+                        end -= 1;
+                    }
+                    end
+                }
+                None => data.len(),
+            },
+        };
+        Some([&data[..clear], &data[clear..encrypted], &data[encrypted..]])
+    }
+    /// A Type 1 font as a PFB file, which is what most font tools read.
+    pub fn to_pfb(&self) -> Option<Vec<u8>> {
+        let parts = self.type1_parts()?;
+        let mut pfb = Vec::with_capacity(self.data.len() + 18);
+        for (kind, part) in [1, 2, 1].into_iter().zip(parts) {
+            if part.is_empty() {
+                continue;
+            }
+            pfb.extend_from_slice(&[0x80, kind]);
+            pfb.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            pfb.extend_from_slice(part);
+        }
+        pfb.extend_from_slice(&[0x80, 3]);
+        Some(pfb)
+    }
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
 #[pdf(key = "Subtype")]
 pub enum FontTypeExt {
@@ -753,7 +1102,83 @@ pub fn write_cmap(map: &ToUnicodeMap) -> String {
 #[cfg(test)]
 mod tests {
 
-    use crate::font::{utf16be_to_char, utf16be_to_string, utf16be_to_string_lossy};
+    use crate::encoding::glyph_name_to_unicode;
+    use crate::font::{utf16be_to_char, utf16be_to_string, utf16be_to_string_lossy, EmbeddedFont, Font, FontFormat};
+    use crate::object::{NoResolve, Object};
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn type1_portions() {
+        let data = b"%!PS-AdobeFont-1.0\ncurrentfile eexec\r\n\x01\x02\x03\n0000000000\ncleartomark\n";
+        let mut font = EmbeddedFont { format: FontFormat::Type1, data: data[..].into(), lengths: [None; 3] };
+        let [clear, encrypted, fixed] = font.type1_parts().unwrap();
+        let clear_len = clear.len();
+        assert!(clear.ends_with(b"eexec\r\n"));
+        assert_eq!(encrypted, b"\x01\x02\x03");
+        assert!(fixed.starts_with(b"\n0000"));
+
+        // lengths that fit the data are trusted
+        font.lengths = [Some(clear_len), Some(2), Some(0)];
+        assert_eq!(font.type1_parts().unwrap()[1], b"\x01\x02");
+        let pfb = font.to_pfb().unwrap();
+        assert_eq!(&pfb[..6], &[0x80, 1, clear_len as u8, 0, 0, 0]);
+        assert!(pfb.ends_with(&[0x80, 3]));
+        assert_eq!(pfb.len(), data.len() + 3 * 6 + 2);
+
+        font.format = FontFormat::Cff;
+        assert!(font.to_pfb().is_none());
+    }
+
+    #[test]
+    fn simple_font_encodings() {
+        let font = |dict: &str| {
+            let p = parse(dict.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap();
+            Font::from_primitive(p, &NoResolve).unwrap()
+        };
+        let win = font("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding << /BaseEncoding /WinAnsiEncoding /Differences [65 /Aacute /f_i 200 /uni00660069] >> >>");
+        assert_eq!(win.decode_byte(b'A').as_deref(), Some("Á"));
+        assert_eq!(win.decode_byte(b'B').as_deref(), Some("fi"));
+        assert_eq!(win.decode_byte(b'C').as_deref(), Some("C"));
+        assert_eq!(win.decode_byte(200).as_deref(), Some("fi"));
+        assert_eq!(win.decode_byte(0x80).as_deref(), Some("€"));
+        assert_eq!(win.decode_byte(0x81).as_deref(), Some("•"));
+        assert_eq!(win.decode_byte(b'\'').as_deref(), Some("'"));
+
+        // no base encoding: the built-in one, StandardEncoding for nonsymbolic fonts
+        let standard = font("<< /Type /Font /Subtype /Type1 /BaseFont /ABCDEF+Times-Roman /Encoding << /Differences [1 /g.alt] >> >>");
+        assert_eq!(standard.decode_byte(1).as_deref(), Some("g"));
+        assert_eq!(standard.decode_byte(b'\'').as_deref(), Some("’"));
+        assert_eq!(standard.decode_byte(0xE1).as_deref(), Some("Æ"));
+        assert_eq!(standard.decode_byte(0x80), None);
+
+        let symbol = font("<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>");
+        assert_eq!(symbol.decode_byte(b'a').as_deref(), Some("α"));
+        assert_eq!(symbol.decode_byte(0xAE).as_deref(), Some("→"));
+        let mac = font("<< /Type /Font /Subtype /TrueType /BaseFont /Arial /Encoding /MacRomanEncoding >>");
+        assert_eq!(mac.decode_byte(0x8E).as_deref(), Some("é"));
+        assert_eq!(mac.decode_byte(0xDB).as_deref(), Some("¤"));
+
+        assert_eq!(glyph_name_to_unicode("u1F600").as_deref(), Some("😀"));
+        assert_eq!(glyph_name_to_unicode("uniD800"), None);
+        assert_eq!(glyph_name_to_unicode("uni00e9"), None);
+        assert_eq!(glyph_name_to_unicode("foo"), None);
+    }
+    #[test]
+    fn type0_cmaps() {
+        let font = |dict: &str| {
+            let p = parse(dict.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap();
+            Font::from_primitive(p, &NoResolve).unwrap()
+        };
+        let vertical = font("<< /Type /Font /Subtype /Type0 /BaseFont /A /Encoding /Identity-V /DescendantFonts [] >>");
+        assert!(vertical.cmap(&NoResolve).unwrap().unwrap().is_vertical());
+
+        // predefined CMaps other than the identity ones need a provider
+        let jis = font("<< /Type /Font /Subtype /Type0 /BaseFont /B /Encoding /UniJIS-UCS2-H /DescendantFonts [] >>");
+        assert!(jis.cmap(&NoResolve).is_err());
+
+        let simple = font("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+        assert!(simple.cmap(&NoResolve).unwrap().is_none());
+    }
     #[test]
     fn utf16be_to_string_quick() {
         let v = vec![0x20, 0x09];
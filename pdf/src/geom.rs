@@ -0,0 +1,373 @@
+//! Conversion between PDF path construction operators and SVG path data.
+//!
+//! PDF user space has the y axis pointing up, SVG has it pointing down.
+//! Both directions take a `Matrix` that is applied to every point, so
+//! `Matrix { a: 1., b: 0., c: 0., d: -1., e: 0., f: height }` flips a page of the given height.
+
+use std::f32::consts::PI;
+use std::fmt::Write;
+
+use crate::content::{Matrix, Op, Point};
+use crate::error::*;
+
+fn transform(m: &Matrix, p: Point) -> Point {
+    Point {
+        x: m.a * p.x + m.c * p.y + m.e,
+        y: m.b * p.x + m.d * p.y + m.f,
+    }
+}
+
+/// Convert the path construction operators in `ops` to SVG path data.
+///
+/// All other operators are ignored, so this can be given the operators of a whole path
+/// including the painting operator.
+pub fn ops_to_svg_path(ops: &[Op], matrix: Matrix) -> String {
+    let mut d = String::new();
+    let mut push = |cmd: char, points: &[Point]| {
+        if !d.is_empty() {
+            d.push(' ');
+        }
+        d.push(cmd);
+        for p in points {
+            let p = transform(&matrix, *p);
+            let _ = write!(d, " {} {}", p.x, p.y);
+        }
+    };
+    for op in ops {
+        match *op {
+            Op::MoveTo { p } => push('M', &[p]),
+            Op::LineTo { p } => push('L', &[p]),
+            Op::CurveTo { c1, c2, p } => push('C', &[c1, c2, p]),
+            Op::Rect { rect } => {
+                let (x0, y0) = (rect.x, rect.y);
+                let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+                push('M', &[Point { x: x0, y: y0 }]);
+                push('L', &[Point { x: x1, y: y0 }]);
+                push('L', &[Point { x: x1, y: y1 }]);
+                push('L', &[Point { x: x0, y: y1 }]);
+                push('Z', &[]);
+            }
+            Op::Close => push('Z', &[]),
+            _ => {}
+        }
+    }
+    d
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> Lexer<'a> {
+    fn skip_separators(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            if b.is_ascii_whitespace() || b == b',' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.data.get(self.pos) {
+            Some(&b) if b.is_ascii_alphabetic() && b != b'e' && b != b'E' => {
+                self.pos += 1;
+                Some(b)
+            }
+            _ => None,
+        }
+    }
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        matches!(self.data.get(self.pos), Some(b'0'..=b'9' | b'-' | b'+' | b'.'))
+    }
+    fn number(&mut self) -> Result<f32> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut seen_dot = false;
+        let mut seen_exp = false;
+        if matches!(self.data.get(self.pos), Some(b'-' | b'+')) {
+            self.pos += 1;
+        }
+        while let Some(&b) = self.data.get(self.pos) {
+            match b {
+                b'0'..=b'9' => {}
+                b'.' if !seen_dot && !seen_exp => seen_dot = true,
+                b'e' | b'E' if !seen_exp => {
+                    seen_exp = true;
+                    if matches!(self.data.get(self.pos + 1), Some(b'-' | b'+')) {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| other!("invalid number in SVG path at offset {}", start))
+    }
+    /// Arc flags may be written without separators, like `a1 1 0 016 6`.
+    fn flag(&mut self) -> Result<bool> {
+        self.skip_separators();
+        let flag = match self.data.get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => bail!("invalid arc flag in SVG path at offset {}", self.pos),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+    fn point(&mut self) -> Result<Point> {
+        Ok(Point { x: self.number()?, y: self.number()? })
+    }
+}
+
+/// Parse SVG path data into path construction operators.
+///
+/// Quadratic curves and arcs are converted to cubic curves. No painting operator is added.
+pub fn svg_path_to_ops(d: &str, matrix: Matrix) -> Result<Vec<Op>> {
+    let mut lexer = Lexer { data: d.as_bytes(), pos: 0 };
+    let mut path = SvgPath::default();
+
+    let mut cmd = match lexer.command() {
+        Some(c) => c,
+        None if lexer.at_number() => bail!("SVG path data has to start with a command"),
+        None => return Ok(vec![]),
+    };
+    loop {
+        let rel = cmd.is_ascii_lowercase();
+        let offset = |p: Point, current: Point| if rel { Point { x: current.x + p.x, y: current.y + p.y } } else { p };
+        let current = path.current;
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let p = offset(lexer.point()?, current);
+                path.move_to(p);
+                // further coordinate pairs are implicit line-tos
+                cmd = if rel { b'l' } else { b'L' };
+            }
+            b'L' => {
+                let p = offset(lexer.point()?, current);
+                path.line_to(p);
+            }
+            b'H' => {
+                let x = lexer.number()?;
+                path.line_to(Point { x: if rel { current.x + x } else { x }, y: current.y });
+            }
+            b'V' => {
+                let y = lexer.number()?;
+                path.line_to(Point { x: current.x, y: if rel { current.y + y } else { y } });
+            }
+            b'C' => {
+                let c1 = offset(lexer.point()?, current);
+                let c2 = offset(lexer.point()?, current);
+                let p = offset(lexer.point()?, current);
+                path.curve_to(c1, c2, p);
+            }
+            b'S' => {
+                let c1 = path.reflected_cubic();
+                let c2 = offset(lexer.point()?, current);
+                let p = offset(lexer.point()?, current);
+                path.curve_to(c1, c2, p);
+            }
+            b'Q' => {
+                let c = offset(lexer.point()?, current);
+                let p = offset(lexer.point()?, current);
+                path.quad_to(c, p);
+            }
+            b'T' => {
+                let c = path.reflected_quad();
+                let p = offset(lexer.point()?, current);
+                path.quad_to(c, p);
+            }
+            b'A' => {
+                let rx = lexer.number()?;
+                let ry = lexer.number()?;
+                let angle = lexer.number()?;
+                let large_arc = lexer.flag()?;
+                let sweep = lexer.flag()?;
+                let p = offset(lexer.point()?, current);
+                path.arc_to(rx, ry, angle, large_arc, sweep, p);
+            }
+            b'Z' => path.close(),
+            _ => bail!("unknown SVG path command {:?}", cmd as char),
+        }
+        if let Some(next) = lexer.command() {
+            cmd = next;
+        } else if !lexer.at_number() {
+            break;
+        } else if cmd.eq_ignore_ascii_case(&b'Z') {
+            bail!("numbers after a closepath in SVG path data");
+        }
+    }
+    if lexer.pos < lexer.data.len() {
+        bail!("unexpected {:?} in SVG path at offset {}", lexer.data[lexer.pos] as char, lexer.pos);
+    }
+
+    Ok(path.ops.into_iter().map(|op| match op {
+        Op::MoveTo { p } => Op::MoveTo { p: transform(&matrix, p) },
+        Op::LineTo { p } => Op::LineTo { p: transform(&matrix, p) },
+        Op::CurveTo { c1, c2, p } => Op::CurveTo {
+            c1: transform(&matrix, c1),
+            c2: transform(&matrix, c2),
+            p: transform(&matrix, p),
+        },
+        op => op,
+    }).collect())
+}
+
+#[derive(Default)]
+struct SvgPath {
+    ops: Vec<Op>,
+    start: Point,
+    current: Point,
+    last_cubic: Option<Point>,
+    last_quad: Option<Point>,
+}
+impl SvgPath {
+    fn move_to(&mut self, p: Point) {
+        self.ops.push(Op::MoveTo { p });
+        self.start = p;
+        self.set_current(p);
+    }
+    fn line_to(&mut self, p: Point) {
+        self.ops.push(Op::LineTo { p });
+        self.set_current(p);
+    }
+    fn curve_to(&mut self, c1: Point, c2: Point, p: Point) {
+        self.ops.push(Op::CurveTo { c1, c2, p });
+        self.set_current(p);
+        self.last_cubic = Some(c2);
+    }
+    fn quad_to(&mut self, c: Point, p: Point) {
+        let q = self.current;
+        let c1 = Point { x: q.x + 2. / 3. * (c.x - q.x), y: q.y + 2. / 3. * (c.y - q.y) };
+        let c2 = Point { x: p.x + 2. / 3. * (c.x - p.x), y: p.y + 2. / 3. * (c.y - p.y) };
+        self.ops.push(Op::CurveTo { c1, c2, p });
+        self.set_current(p);
+        self.last_quad = Some(c);
+    }
+    fn close(&mut self) {
+        self.ops.push(Op::Close);
+        let start = self.start;
+        self.set_current(start);
+    }
+    fn set_current(&mut self, p: Point) {
+        self.current = p;
+        self.last_cubic = None;
+        self.last_quad = None;
+    }
+    fn reflect(&self, c: Option<Point>) -> Point {
+        match c {
+            Some(c) => Point { x: 2. * self.current.x - c.x, y: 2. * self.current.y - c.y },
+            None => self.current,
+        }
+    }
+    fn reflected_cubic(&self) -> Point {
+        self.reflect(self.last_cubic)
+    }
+    fn reflected_quad(&self) -> Point {
+        self.reflect(self.last_quad)
+    }
+    /// SVG 1.1 appendix F.6.5: endpoint to center parameterization.
+    fn arc_to(&mut self, rx: f32, ry: f32, angle: f32, large_arc: bool, sweep: bool, p: Point) {
+        let p0 = self.current;
+        let (mut rx, mut ry) = (rx.abs(), ry.abs());
+        if rx == 0. || ry == 0. {
+            return self.line_to(p);
+        }
+        if p0 == p {
+            return;
+        }
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let dx = (p0.x - p.x) / 2.;
+        let dy = (p0.y - p.y) / 2.;
+        let x1 = cos * dx + sin * dy;
+        let y1 = -sin * dx + cos * dy;
+
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1. {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+        let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut coef = (num / den).max(0.).sqrt();
+        if large_arc == sweep {
+            coef = -coef;
+        }
+        let cx1 = coef * rx * y1 / ry;
+        let cy1 = -coef * ry * x1 / rx;
+        let cx = cos * cx1 - sin * cy1 + (p0.x + p.x) / 2.;
+        let cy = sin * cx1 + cos * cy1 + (p0.y + p.y) / 2.;
+
+        let vector_angle = |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+        let ux = (x1 - cx1) / rx;
+        let uy = (y1 - cy1) / ry;
+        let vx = (-x1 - cx1) / rx;
+        let vy = (-y1 - cy1) / ry;
+        let theta = vector_angle(1., 0., ux, uy);
+        let mut delta = vector_angle(ux, uy, vx, vy);
+        if !sweep && delta > 0. {
+            delta -= 2. * PI;
+        } else if sweep && delta < 0. {
+            delta += 2. * PI;
+        }
+
+        // split into parts of at most 90°
+        let n = (delta.abs() / (PI / 2.)).ceil().max(1.) as usize;
+        let step = delta / n as f32;
+        let k = 4. / 3. * (step / 4.).tan();
+        let point = |t: f32| {
+            let (s, c) = t.sin_cos();
+            (
+                Point { x: cx + rx * c * cos - ry * s * sin, y: cy + rx * c * sin + ry * s * cos },
+                // derivative, scaled by k below
+                Point { x: -rx * s * cos - ry * c * sin, y: -rx * s * sin + ry * c * cos },
+            )
+        };
+        for i in 0..n {
+            let t0 = theta + step * i as f32;
+            let t1 = t0 + step;
+            let (a, da) = point(t0);
+            let (b, db) = point(t1);
+            let c1 = Point { x: a.x + k * da.x, y: a.y + k * da.y };
+            let c2 = Point { x: b.x - k * db.x, y: b.y - k * db.y };
+            let end = if i == n - 1 { p } else { b };
+            self.ops.push(Op::CurveTo { c1, c2, p: end });
+        }
+        self.set_current(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let d = "M 10 20 L 30 20 C 40 20 50 30 50 40 Z";
+        let ops = svg_path_to_ops(d, Matrix::default()).unwrap();
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops_to_svg_path(&ops, Matrix::default()), d);
+    }
+
+    #[test]
+    fn relative_and_implicit() {
+        let ops = svg_path_to_ops("m1,1 2,0-1.5.5h1v-1z", Matrix::default()).unwrap();
+        let flip = Matrix { a: 1., b: 0., c: 0., d: -1., e: 0., f: 10. };
+        assert_eq!(ops_to_svg_path(&ops, flip), "M 1 9 L 3 9 L 1.5 8.5 L 2.5 8.5 L 2.5 9.5 Z");
+    }
+
+    #[test]
+    fn arc_ends_at_target() {
+        let ops = svg_path_to_ops("M0 0A5 5 0 1 1 10 0", Matrix::default()).unwrap();
+        assert_eq!(ops.len(), 3);
+        match ops[2] {
+            Op::CurveTo { p, .. } => assert_eq!(p, Point { x: 10., y: 0. }),
+            ref op => panic!("{:?}", op),
+        }
+    }
+}
@@ -0,0 +1,1195 @@
+//! Glyph outlines of embedded TrueType and CFF fonts.
+//!
+//! Only what is needed to get the shape of a glyph is parsed: the `glyf`, `loca` and `cmap`
+//! tables of TrueType fonts and the charstrings (with subroutines), charset and encoding of CFF fonts
+//! (bare or wrapped in OpenType).
+//! Type 1 fonts (`FontFile`) are not supported.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::content::Point;
+use crate::error::*;
+
+/// One segment of a glyph outline, in font units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Point),
+    LineTo(Point),
+    QuadTo(Point, Point),
+    CurveTo(Point, Point, Point),
+    Close,
+}
+
+fn pt(x: f32, y: f32) -> Point {
+    Point { x, y }
+}
+
+fn slice(data: &[u8], range: Range<usize>) -> Result<&[u8]> {
+    data.get(range.clone()).ok_or_else(|| other!("font data truncated: {:?} out of {} bytes", range, data.len()))
+}
+fn u8_at(data: &[u8], pos: usize) -> Result<u8> {
+    Ok(slice(data, pos..pos + 1)?[0])
+}
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    let s = slice(data, pos..pos + 2)?;
+    Ok(u16::from_be_bytes([s[0], s[1]]))
+}
+fn i16_at(data: &[u8], pos: usize) -> Result<i16> {
+    Ok(u16_at(data, pos)? as i16)
+}
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    let s = slice(data, pos..pos + 4)?;
+    Ok(u32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+/// The parsed outline tables of an embedded font.
+#[derive(Debug)]
+pub struct GlyphOutlines {
+    data: Arc<[u8]>,
+    kind: Kind,
+}
+
+#[derive(Debug)]
+enum Kind {
+    TrueType(TrueType),
+    Cff(Cff),
+}
+
+impl GlyphOutlines {
+    /// Parse the data of an embedded font program.
+    pub fn parse(data: Arc<[u8]>) -> Result<GlyphOutlines> {
+        match slice(&data, 0..4)? {
+            [0, 1, 0, 0] | b"true" => {
+                let kind = Kind::TrueType(TrueType::parse(&data)?);
+                Ok(GlyphOutlines { data, kind })
+            }
+            b"OTTO" => {
+                let tables = table_directory(&data)?;
+                let cff: Arc<[u8]> = match tables.get(b"CFF ") {
+                    Some(range) => Arc::from(slice(&data, range.clone())?),
+                    None => bail!("OpenType font without a CFF table"),
+                };
+                let kind = Kind::Cff(Cff::parse(&cff)?);
+                Ok(GlyphOutlines { data: cff, kind })
+            }
+            [1, _, _, _] => {
+                let kind = Kind::Cff(Cff::parse(&data)?);
+                Ok(GlyphOutlines { data, kind })
+            }
+            _ => bail!("unsupported font format"),
+        }
+    }
+
+    pub fn num_glyphs(&self) -> usize {
+        match self.kind {
+            Kind::TrueType(ref tt) => tt.num_glyphs,
+            Kind::Cff(ref cff) => cff.char_strings.len(),
+        }
+    }
+
+    /// The size of the em square. The coordinates of the outlines are relative to it.
+    pub fn units_per_em(&self) -> f32 {
+        match self.kind {
+            Kind::TrueType(ref tt) => tt.units_per_em,
+            Kind::Cff(ref cff) => cff.units_per_em,
+        }
+    }
+
+    /// The outline of the glyph `gid`.
+    ///
+    /// Returns an empty path for glyphs without an outline (like the space).
+    pub fn outline(&self, gid: u16) -> Result<Vec<PathSegment>> {
+        let mut path = vec![];
+        match self.kind {
+            Kind::TrueType(ref tt) => tt.outline(&self.data, gid, &mut path, 0)?,
+            Kind::Cff(ref cff) => cff.outline(&self.data, gid, &mut path)?,
+        }
+        Ok(path)
+    }
+
+    /// Look up the glyph for the character code of a simple font, using the encoding built into the font.
+    pub fn gid_for_code(&self, code: u16) -> Option<u16> {
+        match self.kind {
+            Kind::TrueType(ref tt) => tt.gid_for_code(&self.data, code),
+            Kind::Cff(ref cff) => cff.gid_for_code(code),
+        }
+    }
+
+    /// Look up the glyph by its PostScript name. Only supported for CFF fonts.
+    pub fn gid_for_name(&self, name: &str) -> Option<u16> {
+        match self.kind {
+            Kind::TrueType(_) => None,
+            Kind::Cff(ref cff) => cff.gid_for_name(&self.data, name),
+        }
+    }
+
+    /// Look up the glyph of a CID. For TrueType fonts, the CID is taken to be the glyph id.
+    pub fn gid_for_cid(&self, cid: u16) -> Option<u16> {
+        match self.kind {
+            Kind::TrueType(_) => Some(cid),
+            Kind::Cff(ref cff) if cff.is_cid => cff.charset.iter().position(|&c| c == cid).map(|gid| gid as u16),
+            Kind::Cff(_) => Some(cid),
+        }
+    }
+}
+
+fn table_directory(data: &[u8]) -> Result<HashMap<[u8; 4], Range<usize>>> {
+    let num_tables = u16_at(data, 4)? as usize;
+    let mut tables = HashMap::with_capacity(num_tables);
+    for i in 0..num_tables {
+        let record = 12 + 16 * i;
+        let tag = slice(data, record..record + 4)?;
+        let offset = u32_at(data, record + 8)? as usize;
+        let len = u32_at(data, record + 12)? as usize;
+        tables.insert([tag[0], tag[1], tag[2], tag[3]], offset..offset + len);
+    }
+    Ok(tables)
+}
+
+#[derive(Debug)]
+struct TrueType {
+    glyf: Range<usize>,
+    loca: Range<usize>,
+    long_loca: bool,
+    num_glyphs: usize,
+    units_per_em: f32,
+    /// the subtable used to map codes, and whether it is a (3, 0) symbol cmap
+    cmap: Option<(usize, bool)>,
+}
+
+const ON_CURVE: u8 = 1;
+const X_SHORT: u8 = 2;
+const Y_SHORT: u8 = 4;
+const REPEAT: u8 = 8;
+const X_SAME: u8 = 16;
+const Y_SAME: u8 = 32;
+
+const ARG_WORDS: u16 = 1;
+const ARGS_XY: u16 = 2;
+const SCALE: u16 = 8;
+const MORE_COMPONENTS: u16 = 0x20;
+const XY_SCALE: u16 = 0x40;
+const TWO_BY_TWO: u16 = 0x80;
+
+impl TrueType {
+    fn parse(data: &[u8]) -> Result<TrueType> {
+        let tables = table_directory(data)?;
+        let table = |tag: &[u8; 4]| tables.get(tag).cloned().ok_or_else(|| other!("missing {} table", String::from_utf8_lossy(tag)));
+        let head = table(b"head")?;
+        let maxp = table(b"maxp")?;
+
+        let cmap = match tables.get(b"cmap") {
+            Some(range) => Self::pick_cmap(data, range.start)?,
+            None => None,
+        };
+        Ok(TrueType {
+            glyf: table(b"glyf")?,
+            loca: table(b"loca")?,
+            long_loca: i16_at(data, head.start + 50)? != 0,
+            num_glyphs: u16_at(data, maxp.start + 4)? as usize,
+            units_per_em: u16_at(data, head.start + 18)? as f32,
+            cmap,
+        })
+    }
+
+    fn pick_cmap(data: &[u8], cmap: usize) -> Result<Option<(usize, bool)>> {
+        let num_tables = u16_at(data, cmap + 2)? as usize;
+        let mut best = None;
+        for i in 0..num_tables {
+            let record = cmap + 4 + 8 * i;
+            let platform = u16_at(data, record)?;
+            let encoding = u16_at(data, record + 2)?;
+            let offset = cmap + u32_at(data, record + 4)? as usize;
+            // the symbol cmap is what simple fonts are supposed to use, followed by the mac roman one
+            let rank = match (platform, encoding) {
+                (3, 0) => 0,
+                (1, 0) => 1,
+                (3, 1) => 2,
+                (0, _) => 3,
+                _ => continue,
+            };
+            if best.map(|(r, _, _)| rank < r).unwrap_or(true) {
+                best = Some((rank, offset, platform == 3 && encoding == 0));
+            }
+        }
+        Ok(best.map(|(_, offset, symbol)| (offset, symbol)))
+    }
+
+    fn gid_for_code(&self, data: &[u8], code: u16) -> Option<u16> {
+        let (table, symbol) = self.cmap?;
+        if symbol {
+            // symbol fonts usually map the range F000-F0FF
+            [code, 0xF000 | code, 0xF100 | code, 0xF200 | code].iter()
+                .find_map(|&c| cmap_lookup(data, table, c as u32).ok().flatten())
+        } else {
+            cmap_lookup(data, table, code as u32).ok().flatten()
+        }
+    }
+
+    fn glyph_range(&self, data: &[u8], gid: u16) -> Result<Range<usize>> {
+        let gid = gid as usize;
+        if gid >= self.num_glyphs {
+            bail!("glyph {} out of range", gid);
+        }
+        let (start, end) = if self.long_loca {
+            (u32_at(data, self.loca.start + 4 * gid)? as usize, u32_at(data, self.loca.start + 4 * gid + 4)? as usize)
+        } else {
+            (u16_at(data, self.loca.start + 2 * gid)? as usize * 2, u16_at(data, self.loca.start + 2 * gid + 2)? as usize * 2)
+        };
+        if end < start || self.glyf.start + end > self.glyf.end {
+            bail!("invalid loca entry for glyph {}", gid);
+        }
+        Ok(self.glyf.start + start..self.glyf.start + end)
+    }
+
+    fn outline(&self, data: &[u8], gid: u16, path: &mut Vec<PathSegment>, depth: usize) -> Result<()> {
+        let range = self.glyph_range(data, gid)?;
+        if range.is_empty() {
+            return Ok(());
+        }
+        let glyph = slice(data, range)?;
+        let num_contours = i16_at(glyph, 0)?;
+        if num_contours >= 0 {
+            simple_glyph(glyph, num_contours as usize, path)
+        } else {
+            if depth > 8 {
+                bail!("composite glyphs nested too deeply");
+            }
+            self.composite_glyph(data, glyph, path, depth)
+        }
+    }
+
+    fn composite_glyph(&self, data: &[u8], glyph: &[u8], path: &mut Vec<PathSegment>, depth: usize) -> Result<()> {
+        let mut pos = 10;
+        loop {
+            let flags = u16_at(glyph, pos)?;
+            let component = u16_at(glyph, pos + 2)?;
+            pos += 4;
+            let (dx, dy) = if flags & ARG_WORDS != 0 {
+                pos += 4;
+                (i16_at(glyph, pos - 4)? as f32, i16_at(glyph, pos - 2)? as f32)
+            } else {
+                pos += 2;
+                (u8_at(glyph, pos - 2)? as i8 as f32, u8_at(glyph, pos - 1)? as i8 as f32)
+            };
+            // matching points instead of offsets is not supported, the component is placed at the origin
+            let (dx, dy) = if flags & ARGS_XY != 0 { (dx, dy) } else { (0., 0.) };
+
+            let f2dot14 = |pos: usize| -> Result<f32> { Ok(i16_at(glyph, pos)? as f32 / 16384.) };
+            let (a, b, c, d) = if flags & SCALE != 0 {
+                pos += 2;
+                let s = f2dot14(pos - 2)?;
+                (s, 0., 0., s)
+            } else if flags & XY_SCALE != 0 {
+                pos += 4;
+                (f2dot14(pos - 4)?, 0., 0., f2dot14(pos - 2)?)
+            } else if flags & TWO_BY_TWO != 0 {
+                pos += 8;
+                (f2dot14(pos - 8)?, f2dot14(pos - 6)?, f2dot14(pos - 4)?, f2dot14(pos - 2)?)
+            } else {
+                (1., 0., 0., 1.)
+            };
+
+            let start = path.len();
+            self.outline(data, component, path, depth + 1)?;
+            let transform = |p: Point| pt(a * p.x + c * p.y + dx, b * p.x + d * p.y + dy);
+            for segment in &mut path[start..] {
+                *segment = match *segment {
+                    PathSegment::MoveTo(p) => PathSegment::MoveTo(transform(p)),
+                    PathSegment::LineTo(p) => PathSegment::LineTo(transform(p)),
+                    PathSegment::QuadTo(p1, p) => PathSegment::QuadTo(transform(p1), transform(p)),
+                    PathSegment::CurveTo(p1, p2, p) => PathSegment::CurveTo(transform(p1), transform(p2), transform(p)),
+                    PathSegment::Close => PathSegment::Close,
+                };
+            }
+
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn cmap_lookup(data: &[u8], table: usize, code: u32) -> Result<Option<u16>> {
+    let gid = match u16_at(data, table)? {
+        0 => {
+            if code > 0xFF {
+                return Ok(None);
+            }
+            u8_at(data, table + 6 + code as usize)? as u16
+        }
+        4 => {
+            let seg_count = u16_at(data, table + 6)? as usize / 2;
+            let end_codes = table + 14;
+            let start_codes = end_codes + 2 * seg_count + 2;
+            let deltas = start_codes + 2 * seg_count;
+            let range_offsets = deltas + 2 * seg_count;
+            let mut gid = 0;
+            for i in 0..seg_count {
+                if code > u16_at(data, end_codes + 2 * i)? as u32 {
+                    continue;
+                }
+                let start = u16_at(data, start_codes + 2 * i)? as u32;
+                if code < start {
+                    break;
+                }
+                let delta = u16_at(data, deltas + 2 * i)?;
+                let range_offset = u16_at(data, range_offsets + 2 * i)? as usize;
+                gid = if range_offset == 0 {
+                    (code as u16).wrapping_add(delta)
+                } else {
+                    let pos = range_offsets + 2 * i + range_offset + 2 * (code - start) as usize;
+                    match u16_at(data, pos)? {
+                        0 => 0,
+                        g => g.wrapping_add(delta),
+                    }
+                };
+                break;
+            }
+            gid
+        }
+        6 => {
+            let first = u16_at(data, table + 6)? as u32;
+            let count = u16_at(data, table + 8)? as u32;
+            if code < first || code >= first + count {
+                return Ok(None);
+            }
+            u16_at(data, table + 10 + 2 * (code - first) as usize)?
+        }
+        12 => {
+            let groups = u32_at(data, table + 12)? as usize;
+            let mut gid = 0;
+            for i in 0..groups {
+                let group = table + 16 + 12 * i;
+                let start = u32_at(data, group)?;
+                let end = u32_at(data, group + 4)?;
+                if (start..=end).contains(&code) {
+                    gid = (u32_at(data, group + 8)? + code - start) as u16;
+                    break;
+                }
+            }
+            gid
+        }
+        format => bail!("unsupported cmap format {}", format),
+    };
+    Ok(if gid == 0 { None } else { Some(gid) })
+}
+
+fn simple_glyph(glyph: &[u8], num_contours: usize, path: &mut Vec<PathSegment>) -> Result<()> {
+    let mut end_points = Vec::with_capacity(num_contours);
+    for i in 0..num_contours {
+        end_points.push(u16_at(glyph, 10 + 2 * i)? as usize);
+    }
+    let num_points = match end_points.last() {
+        Some(&last) => last + 1,
+        None => return Ok(()),
+    };
+    let instructions_len = u16_at(glyph, 10 + 2 * num_contours)? as usize;
+    let mut pos = 12 + 2 * num_contours + instructions_len;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = u8_at(glyph, pos)?;
+        pos += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let count = u8_at(glyph, pos)?;
+            pos += 1;
+            for _ in 0..count {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(num_points);
+
+    let mut read_coords = |short: u8, same: u8| -> Result<Vec<f32>> {
+        let mut value = 0i32;
+        let mut coords = Vec::with_capacity(num_points);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let d = u8_at(glyph, pos)? as i32;
+                pos += 1;
+                value += if flag & same != 0 { d } else { -d };
+            } else if flag & same == 0 {
+                value += i16_at(glyph, pos)? as i32;
+                pos += 2;
+            }
+            coords.push(value as f32);
+        }
+        Ok(coords)
+    };
+    let xs = read_coords(X_SHORT, X_SAME)?;
+    let ys = read_coords(Y_SHORT, Y_SAME)?;
+
+    let mut start = 0;
+    for &end in &end_points {
+        if end < start || end >= num_points {
+            bail!("invalid contour end point {}", end);
+        }
+        let points: Vec<(Point, bool)> = (start..=end).map(|i| (pt(xs[i], ys[i]), flags[i] & ON_CURVE != 0)).collect();
+        quad_contour(&points, path);
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// Turn a contour of on- and off-curve points into quadratic segments.
+fn quad_contour(points: &[(Point, bool)], path: &mut Vec<PathSegment>) {
+    let mid = |a: Point, b: Point| pt((a.x + b.x) / 2., (a.y + b.y) / 2.);
+    let n = points.len();
+    if n == 0 {
+        return;
+    }
+    // start at an on-curve point, or the midpoint of the first two if there is none
+    let first_on = points.iter().position(|&(_, on)| on);
+    let (start, offset) = match first_on {
+        Some(i) => (points[i].0, i),
+        None => (mid(points[0].0, points[1 % n].0), 0),
+    };
+    path.push(PathSegment::MoveTo(start));
+
+    let mut control: Option<Point> = None;
+    let steps = if first_on.is_some() { n } else { n + 1 };
+    for k in 1..=steps {
+        let (p, on) = points[(offset + k) % n];
+        if first_on.is_none() && k == steps {
+            // close back to the synthesized start
+            if let Some(c) = control.take() {
+                path.push(PathSegment::QuadTo(c, start));
+            }
+            break;
+        }
+        match (control, on) {
+            (None, true) => path.push(PathSegment::LineTo(p)),
+            (None, false) => control = Some(p),
+            (Some(c), true) => {
+                path.push(PathSegment::QuadTo(c, p));
+                control = None;
+            }
+            (Some(c), false) => {
+                path.push(PathSegment::QuadTo(c, mid(c, p)));
+                control = Some(p);
+            }
+        }
+    }
+    if let Some(c) = control {
+        path.push(PathSegment::QuadTo(c, start));
+    }
+    path.push(PathSegment::Close);
+}
+
+#[derive(Debug)]
+struct Cff {
+    char_strings: Vec<Range<usize>>,
+    global_subrs: Vec<Range<usize>>,
+    /// local subroutines of each font dict
+    local_subrs: Vec<Vec<Range<usize>>>,
+    /// the font dict of each glyph, for CID-keyed fonts
+    fd_select: Option<Vec<u8>>,
+    /// SID (or CID) of each glyph
+    charset: Vec<u16>,
+    /// code to glyph id for fonts with a custom encoding
+    encoding: Option<HashMap<u8, u16>>,
+    strings: Vec<Range<usize>>,
+    is_cid: bool,
+    units_per_em: f32,
+}
+
+const OP_CHARSET: u16 = 15;
+const OP_ENCODING: u16 = 16;
+const OP_CHAR_STRINGS: u16 = 17;
+const OP_PRIVATE: u16 = 18;
+const OP_SUBRS: u16 = 19;
+const OP_FONT_MATRIX: u16 = 1207;
+const OP_ROS: u16 = 1230;
+const OP_FD_ARRAY: u16 = 1236;
+const OP_FD_SELECT: u16 = 1237;
+
+fn index(data: &[u8], pos: usize) -> Result<(Vec<Range<usize>>, usize)> {
+    let count = u16_at(data, pos)? as usize;
+    if count == 0 {
+        return Ok((vec![], pos + 2));
+    }
+    let off_size = u8_at(data, pos + 2)? as usize;
+    if !(1..=4).contains(&off_size) {
+        bail!("invalid CFF offset size {}", off_size);
+    }
+    let offset = |i: usize| -> Result<usize> {
+        let s = slice(data, pos + 3 + i * off_size..pos + 3 + (i + 1) * off_size)?;
+        Ok(s.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+    };
+    // offsets are relative to the byte before the data
+    let base = pos + 3 + (count + 1) * off_size - 1;
+    let mut ranges = Vec::with_capacity(count);
+    let mut start = offset(0)?;
+    for i in 1..=count {
+        let end = offset(i)?;
+        if end < start {
+            bail!("invalid CFF INDEX");
+        }
+        ranges.push(base + start..base + end);
+        start = end;
+    }
+    slice(data, base + 1..base + start)?;
+    Ok((ranges, base + start))
+}
+
+fn dict(data: &[u8]) -> Result<HashMap<u16, Vec<f32>>> {
+    let mut entries = HashMap::new();
+    let mut operands = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let b0 = data[pos];
+        pos += 1;
+        match b0 {
+            0..=21 => {
+                let op = if b0 == 12 {
+                    pos += 1;
+                    1200 + u8_at(data, pos - 1)? as u16
+                } else {
+                    b0 as u16
+                };
+                entries.insert(op, std::mem::take(&mut operands));
+            }
+            28 => {
+                operands.push(i16_at(data, pos)? as f32);
+                pos += 2;
+            }
+            29 => {
+                operands.push(u32_at(data, pos)? as i32 as f32);
+                pos += 4;
+            }
+            30 => {
+                let mut s = String::new();
+                'real: loop {
+                    let b = u8_at(data, pos)?;
+                    pos += 1;
+                    for nibble in [b >> 4, b & 15] {
+                        match nibble {
+                            0..=9 => s.push((b'0' + nibble) as char),
+                            0xa => s.push('.'),
+                            0xb => s.push('E'),
+                            0xc => s.push_str("E-"),
+                            0xe => s.push('-'),
+                            0xf => break 'real,
+                            _ => {}
+                        }
+                    }
+                }
+                operands.push(s.parse().unwrap_or(0.));
+            }
+            32..=246 => operands.push(b0 as f32 - 139.),
+            247..=250 => {
+                operands.push((b0 as f32 - 247.) * 256. + u8_at(data, pos)? as f32 + 108.);
+                pos += 1;
+            }
+            251..=254 => {
+                operands.push(-(b0 as f32 - 251.) * 256. - u8_at(data, pos)? as f32 - 108.);
+                pos += 1;
+            }
+            _ => bail!("invalid CFF DICT byte {}", b0),
+        }
+    }
+    Ok(entries)
+}
+
+fn dict_offset(dict: &HashMap<u16, Vec<f32>>, op: u16) -> Option<usize> {
+    dict.get(&op).and_then(|v| v.first()).map(|&o| o as usize)
+}
+
+fn private_subrs(data: &[u8], dict: &HashMap<u16, Vec<f32>>) -> Result<Vec<Range<usize>>> {
+    match dict.get(&OP_PRIVATE).map(|v| v.as_slice()) {
+        Some(&[size, offset]) => {
+            let (size, offset) = (size as usize, offset as usize);
+            let private = self::dict(slice(data, offset..offset + size)?)?;
+            match dict_offset(&private, OP_SUBRS) {
+                Some(subrs) => Ok(index(data, offset + subrs)?.0),
+                None => Ok(vec![]),
+            }
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+impl Cff {
+    fn parse(data: &[u8]) -> Result<Cff> {
+        let header_size = u8_at(data, 2)? as usize;
+        let (_names, pos) = index(data, header_size)?;
+        let (top_dicts, pos) = index(data, pos)?;
+        let (strings, pos) = index(data, pos)?;
+        let (global_subrs, _) = index(data, pos)?;
+
+        let top = match top_dicts.first() {
+            Some(range) => dict(slice(data, range.clone())?)?,
+            None => bail!("CFF without a Top DICT"),
+        };
+        let char_strings = match dict_offset(&top, OP_CHAR_STRINGS) {
+            Some(offset) => index(data, offset)?.0,
+            None => bail!("CFF without CharStrings"),
+        };
+        let num_glyphs = char_strings.len();
+        let is_cid = top.contains_key(&OP_ROS);
+
+        let (local_subrs, fd_select) = match (dict_offset(&top, OP_FD_ARRAY), dict_offset(&top, OP_FD_SELECT)) {
+            (Some(fd_array), Some(fd_select)) if is_cid => {
+                let mut local_subrs = vec![];
+                for range in index(data, fd_array)?.0 {
+                    local_subrs.push(private_subrs(data, &dict(slice(data, range)?)?)?);
+                }
+                (local_subrs, Some(Self::fd_select(data, fd_select, num_glyphs)?))
+            }
+            _ => (vec![private_subrs(data, &top)?], None),
+        };
+
+        let charset = match dict_offset(&top, OP_CHARSET).unwrap_or(0) {
+            // predefined charsets, only ISOAdobe can be used with the standard strings we know
+            0..=2 => (0..num_glyphs as u16).collect(),
+            offset => Self::charset(data, offset, num_glyphs)?,
+        };
+
+        let encoding = match dict_offset(&top, OP_ENCODING).unwrap_or(0) {
+            _ if is_cid => None,
+            0 | 1 => None,
+            offset => Some(Self::encoding(data, offset)?),
+        };
+
+        let units_per_em = match top.get(&OP_FONT_MATRIX).and_then(|m| m.first()) {
+            Some(&scale) if scale > 0. => (1. / scale).round(),
+            _ => 1000.,
+        };
+
+        Ok(Cff {
+            char_strings,
+            global_subrs,
+            local_subrs,
+            fd_select,
+            charset,
+            encoding,
+            strings,
+            is_cid,
+            units_per_em,
+        })
+    }
+
+    fn fd_select(data: &[u8], pos: usize, num_glyphs: usize) -> Result<Vec<u8>> {
+        match u8_at(data, pos)? {
+            0 => Ok(slice(data, pos + 1..pos + 1 + num_glyphs)?.to_vec()),
+            3 => {
+                let num_ranges = u16_at(data, pos + 1)? as usize;
+                let mut fds = vec![0; num_glyphs];
+                for i in 0..num_ranges {
+                    let range = pos + 3 + 3 * i;
+                    let first = u16_at(data, range)? as usize;
+                    let fd = u8_at(data, range + 2)?;
+                    let end = u16_at(data, range + 3)? as usize;
+                    if first < end.min(num_glyphs) {
+                        fds[first..end.min(num_glyphs)].fill(fd);
+                    }
+                }
+                Ok(fds)
+            }
+            format => bail!("unsupported FDSelect format {}", format),
+        }
+    }
+
+    fn charset(data: &[u8], pos: usize, num_glyphs: usize) -> Result<Vec<u16>> {
+        let mut charset = Vec::with_capacity(num_glyphs);
+        charset.push(0);
+        let format = u8_at(data, pos)?;
+        let mut p = pos + 1;
+        while charset.len() < num_glyphs {
+            match format {
+                0 => {
+                    charset.push(u16_at(data, p)?);
+                    p += 2;
+                }
+                1 | 2 => {
+                    let first = u16_at(data, p)?;
+                    let n_left = if format == 1 { u8_at(data, p + 2)? as u16 } else { u16_at(data, p + 2)? };
+                    p += if format == 1 { 3 } else { 4 };
+                    for sid in first..=first.saturating_add(n_left) {
+                        charset.push(sid);
+                    }
+                }
+                _ => bail!("unsupported charset format {}", format),
+            }
+        }
+        charset.truncate(num_glyphs);
+        Ok(charset)
+    }
+
+    fn encoding(data: &[u8], pos: usize) -> Result<HashMap<u8, u16>> {
+        let mut encoding = HashMap::new();
+        let format = u8_at(data, pos)?;
+        match format & 0x7f {
+            0 => {
+                let n = u8_at(data, pos + 1)? as usize;
+                for gid in 1..=n {
+                    encoding.insert(u8_at(data, pos + 1 + gid)?, gid as u16);
+                }
+            }
+            1 => {
+                let n = u8_at(data, pos + 1)? as usize;
+                let mut gid = 1;
+                for i in 0..n {
+                    let first = u8_at(data, pos + 2 + 2 * i)?;
+                    let n_left = u8_at(data, pos + 3 + 2 * i)?;
+                    for code in first..=first.saturating_add(n_left) {
+                        encoding.insert(code, gid);
+                        gid += 1;
+                    }
+                }
+            }
+            f => bail!("unsupported CFF encoding format {}", f),
+        }
+        // supplements (format & 0x80) only add alternative codes for glyphs and are ignored
+        Ok(encoding)
+    }
+
+    fn gid_for_sid(&self, sid: u16) -> Option<u16> {
+        self.charset.iter().position(|&s| s == sid).map(|gid| gid as u16)
+    }
+
+    fn gid_for_code(&self, code: u16) -> Option<u16> {
+        if let Some(ref encoding) = self.encoding {
+            return encoding.get(&(code as u8)).cloned();
+        }
+        // within the printable ASCII range, the standard encoding maps code c to SID c - 31
+        match code {
+            32..=126 => self.gid_for_sid(code - 31),
+            _ => None,
+        }
+    }
+
+    fn gid_for_name(&self, data: &[u8], name: &str) -> Option<u16> {
+        if self.is_cid {
+            return None;
+        }
+        let sid = match STANDARD_STRINGS.iter().position(|&s| s == name) {
+            Some(sid) => sid,
+            None => 391 + self.strings.iter().position(|r| data.get(r.clone()) == Some(name.as_bytes()))?,
+        };
+        self.gid_for_sid(sid as u16)
+    }
+
+    fn outline(&self, data: &[u8], gid: u16, path: &mut Vec<PathSegment>) -> Result<()> {
+        let char_string = match self.char_strings.get(gid as usize) {
+            Some(range) => slice(data, range.clone())?,
+            None => bail!("glyph {} out of range", gid),
+        };
+        let fd = match self.fd_select {
+            Some(ref fds) => fds.get(gid as usize).cloned().unwrap_or(0) as usize,
+            None => 0,
+        };
+        let local_subrs = self.local_subrs.get(fd).map(|v| v.as_slice()).unwrap_or(&[]);
+        let mut state = CharStringState {
+            data,
+            global_subrs: &self.global_subrs,
+            local_subrs,
+            stack: vec![],
+            num_stems: 0,
+            width_parsed: false,
+            open: false,
+            x: 0.,
+            y: 0.,
+            path,
+        };
+        state.execute(char_string, 0)?;
+        state.close();
+        Ok(())
+    }
+}
+
+fn subr_bias(count: usize) -> i32 {
+    if count < 1240 {
+        107
+    } else if count < 33900 {
+        1131
+    } else {
+        32768
+    }
+}
+
+/// Interpreter for Type 2 charstrings.
+struct CharStringState<'a> {
+    data: &'a [u8],
+    global_subrs: &'a [Range<usize>],
+    local_subrs: &'a [Range<usize>],
+    stack: Vec<f32>,
+    num_stems: usize,
+    width_parsed: bool,
+    open: bool,
+    x: f32,
+    y: f32,
+    path: &'a mut Vec<PathSegment>,
+}
+
+impl<'a> CharStringState<'a> {
+    /// The advance width is an optional first operand of the first stack-clearing operator.
+    fn width(&mut self, expected_args: usize) {
+        if !self.width_parsed {
+            if self.stack.len() > expected_args {
+                self.stack.remove(0);
+            }
+            self.width_parsed = true;
+        }
+    }
+    fn stems(&mut self) {
+        let odd = self.stack.len() % 2 == 1;
+        self.width(if odd { self.stack.len() - 1 } else { self.stack.len() });
+        self.num_stems += self.stack.len() / 2;
+    }
+    fn close(&mut self) {
+        if self.open {
+            self.path.push(PathSegment::Close);
+            self.open = false;
+        }
+    }
+    fn move_to(&mut self, dx: f32, dy: f32) {
+        self.close();
+        self.x += dx;
+        self.y += dy;
+        self.path.push(PathSegment::MoveTo(pt(self.x, self.y)));
+        self.open = true;
+    }
+    fn line_to(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.path.push(PathSegment::LineTo(pt(self.x, self.y)));
+    }
+    fn curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        let c1 = pt(self.x + dx1, self.y + dy1);
+        let c2 = pt(c1.x + dx2, c1.y + dy2);
+        self.x = c2.x + dx3;
+        self.y = c2.y + dy3;
+        self.path.push(PathSegment::CurveTo(c1, c2, pt(self.x, self.y)));
+    }
+
+    fn execute(&mut self, code: &[u8], depth: usize) -> Result<bool> {
+        if depth > 10 {
+            bail!("charstring subroutines nested too deeply");
+        }
+        let mut pos = 0;
+        while pos < code.len() {
+            let b0 = code[pos];
+            pos += 1;
+            match b0 {
+                28 => {
+                    self.stack.push(i16_at(code, pos)? as f32);
+                    pos += 2;
+                }
+                32..=246 => self.stack.push(b0 as f32 - 139.),
+                247..=250 => {
+                    self.stack.push((b0 as f32 - 247.) * 256. + u8_at(code, pos)? as f32 + 108.);
+                    pos += 1;
+                }
+                251..=254 => {
+                    self.stack.push(-(b0 as f32 - 251.) * 256. - u8_at(code, pos)? as f32 - 108.);
+                    pos += 1;
+                }
+                255 => {
+                    self.stack.push(u32_at(code, pos)? as i32 as f32 / 65536.);
+                    pos += 4;
+                }
+                1 | 3 | 18 | 23 => {
+                    self.stems();
+                    self.stack.clear();
+                }
+                19 | 20 => {
+                    // hintmask and cntrmask imply a vstem for remaining operands
+                    self.stems();
+                    self.stack.clear();
+                    pos += self.num_stems.div_ceil(8);
+                }
+                21 => {
+                    self.width(2);
+                    let s = self.args(2)?;
+                    self.move_to(s[0], s[1]);
+                }
+                22 => {
+                    self.width(1);
+                    let s = self.args(1)?;
+                    self.move_to(s[0], 0.);
+                }
+                4 => {
+                    self.width(1);
+                    let s = self.args(1)?;
+                    self.move_to(0., s[0]);
+                }
+                5 => {
+                    let s = std::mem::take(&mut self.stack);
+                    for d in s.chunks_exact(2) {
+                        self.line_to(d[0], d[1]);
+                    }
+                }
+                6 | 7 => {
+                    let s = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 6;
+                    for &d in &s {
+                        if horizontal { self.line_to(d, 0.) } else { self.line_to(0., d) }
+                        horizontal = !horizontal;
+                    }
+                }
+                8 => {
+                    let s = std::mem::take(&mut self.stack);
+                    for d in s.chunks_exact(6) {
+                        self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                    }
+                }
+                24 => {
+                    // rcurveline
+                    let s = std::mem::take(&mut self.stack);
+                    let mut i = 0;
+                    while s.len() - i >= 8 {
+                        self.curve_to(s[i], s[i + 1], s[i + 2], s[i + 3], s[i + 4], s[i + 5]);
+                        i += 6;
+                    }
+                    if s.len() - i >= 2 {
+                        self.line_to(s[i], s[i + 1]);
+                    }
+                }
+                25 => {
+                    // rlinecurve
+                    let s = std::mem::take(&mut self.stack);
+                    let mut i = 0;
+                    while s.len() - i > 6 {
+                        self.line_to(s[i], s[i + 1]);
+                        i += 2;
+                    }
+                    if s.len() - i == 6 {
+                        self.curve_to(s[i], s[i + 1], s[i + 2], s[i + 3], s[i + 4], s[i + 5]);
+                    }
+                }
+                26 | 27 => {
+                    // vvcurveto and hhcurveto
+                    let s = std::mem::take(&mut self.stack);
+                    let (mut first, mut i) = if s.len() % 4 == 1 { (s[0], 1) } else { (0., 0) };
+                    while i + 4 <= s.len() {
+                        if b0 == 27 {
+                            self.curve_to(s[i], first, s[i + 1], s[i + 2], s[i + 3], 0.);
+                        } else {
+                            self.curve_to(first, s[i], s[i + 1], s[i + 2], 0., s[i + 3]);
+                        }
+                        first = 0.;
+                        i += 4;
+                    }
+                }
+                30 | 31 => {
+                    // vhcurveto and hvcurveto
+                    let s = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 31;
+                    let mut i = 0;
+                    while i + 4 <= s.len() {
+                        let last = if s.len() - i == 5 { s[i + 4] } else { 0. };
+                        if horizontal {
+                            self.curve_to(s[i], 0., s[i + 1], s[i + 2], last, s[i + 3]);
+                        } else {
+                            self.curve_to(0., s[i], s[i + 1], s[i + 2], s[i + 3], last);
+                        }
+                        horizontal = !horizontal;
+                        i += 4;
+                    }
+                }
+                10 | 29 => {
+                    let index = match self.stack.pop() {
+                        Some(i) => i as i32,
+                        None => bail!("charstring stack underflow"),
+                    };
+                    let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+                    let range = match usize::try_from(index + subr_bias(subrs.len())).ok().and_then(|i| subrs.get(i)) {
+                        Some(range) => range.clone(),
+                        None => bail!("invalid subroutine {}", index),
+                    };
+                    let data = self.data;
+                    if self.execute(slice(data, range)?, depth + 1)? {
+                        return Ok(true);
+                    }
+                }
+                11 => return Ok(false),
+                14 => {
+                    // endchar, the seac form (with accent) is not supported
+                    let odd = self.stack.len() == 1 || self.stack.len() == 5;
+                    self.width(if odd { self.stack.len() - 1 } else { self.stack.len() });
+                    self.stack.clear();
+                    return Ok(true);
+                }
+                12 => {
+                    let b1 = u8_at(code, pos)?;
+                    pos += 1;
+                    let s = std::mem::take(&mut self.stack);
+                    match (b1, s.len()) {
+                        (35, 13) | (35, 12) => {
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                            self.curve_to(s[6], s[7], s[8], s[9], s[10], s[11]);
+                        }
+                        (34, 7) => {
+                            let y = self.y;
+                            self.curve_to(s[0], 0., s[1], s[2], s[3], 0.);
+                            self.curve_to(s[4], 0., s[5], y - self.y, s[6], 0.);
+                        }
+                        (36, 9) => {
+                            let y = self.y;
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], 0.);
+                            let dy = y - (self.y + s[7]);
+                            self.curve_to(s[5], 0., s[6], s[7], s[8], dy);
+                        }
+                        (37, 11) => {
+                            let (x, y) = (self.x, self.y);
+                            let dx: f32 = s[0..10].iter().step_by(2).sum();
+                            let dy: f32 = s[1..10].iter().step_by(2).sum();
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                            let (x2, y2) = (self.x, self.y);
+                            let c1 = pt(x2 + s[6], y2 + s[7]);
+                            let c2 = pt(c1.x + s[8], c1.y + s[9]);
+                            let end = if dx.abs() > dy.abs() {
+                                pt(c2.x + s[10], y)
+                            } else {
+                                pt(x, c2.y + s[10])
+                            };
+                            self.x = end.x;
+                            self.y = end.y;
+                            self.path.push(PathSegment::CurveTo(c1, c2, end));
+                        }
+                        // arithmetic and storage operators are not used by real-world fonts
+                        _ => {}
+                    }
+                }
+                _ => {
+                    // reserved operators
+                    self.stack.clear();
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn args(&mut self, n: usize) -> Result<Vec<f32>> {
+        if self.stack.len() < n {
+            bail!("charstring stack underflow");
+        }
+        let s = self.stack.split_off(self.stack.len() - n);
+        self.stack.clear();
+        Ok(s)
+    }
+}
+
+/// The first 229 standard strings of CFF fonts (Standard and ISOAdobe charsets).
+/// The expert strings (229 to 390) are not included, so glyphs named that way can't be found by name.
+static STANDARD_STRINGS: [&str; 229] = [
+    ".notdef", "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand",
+    "quoteright", "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period",
+    "slash", "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "colon", "semicolon", "less", "equal", "greater", "question", "at",
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R",
+    "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "bracketleft", "backslash", "bracketright", "asciicircum", "underscore", "quoteleft",
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r",
+    "s", "t", "u", "v", "w", "x", "y", "z",
+    "braceleft", "bar", "braceright", "asciitilde", "exclamdown", "cent", "sterling", "fraction",
+    "yen", "florin", "section", "currency", "quotesingle", "quotedblleft", "guillemotleft",
+    "guilsinglleft", "guilsinglright", "fi", "fl", "endash", "dagger", "daggerdbl",
+    "periodcentered", "paragraph", "bullet", "quotesinglbase", "quotedblbase", "quotedblright",
+    "guillemotright", "ellipsis", "perthousand", "questiondown", "grave", "acute", "circumflex",
+    "tilde", "macron", "breve", "dotaccent", "dieresis", "ring", "cedilla", "hungarumlaut",
+    "ogonek", "caron", "emdash", "AE", "ordfeminine", "Lslash", "Oslash", "OE", "ordmasculine",
+    "ae", "dotlessi", "lslash", "oslash", "oe", "germandbls", "onesuperior", "logicalnot", "mu",
+    "trademark", "Eth", "onehalf", "plusminus", "Thorn", "onequarter", "divide", "brokenbar",
+    "degree", "thorn", "threequarters", "twosuperior", "registered", "minus", "eth", "multiply",
+    "threesuperior", "copyright", "Aacute", "Acircumflex", "Adieresis", "Agrave", "Aring",
+    "Atilde", "Ccedilla", "Eacute", "Ecircumflex", "Edieresis", "Egrave", "Iacute",
+    "Icircumflex", "Idieresis", "Igrave", "Ntilde", "Oacute", "Ocircumflex", "Odieresis",
+    "Ograve", "Otilde", "Scaron", "Uacute", "Ucircumflex", "Udieresis", "Ugrave", "Yacute",
+    "Ydieresis", "Zcaron", "aacute", "acircumflex", "adieresis", "agrave", "aring", "atilde",
+    "ccedilla", "eacute", "ecircumflex", "edieresis", "egrave", "iacute", "icircumflex",
+    "idieresis", "igrave", "ntilde", "oacute", "ocircumflex", "odieresis", "ograve", "otilde",
+    "scaron", "uacute", "ucircumflex", "udieresis", "ugrave", "yacute", "ydieresis", "zcaron",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadratic_contour() {
+        // a square with one off-curve corner
+        let points = [
+            (pt(0., 0.), true),
+            (pt(10., 0.), true),
+            (pt(10., 10.), false),
+            (pt(0., 10.), true),
+        ];
+        let mut path = vec![];
+        quad_contour(&points, &mut path);
+        assert_eq!(path, [
+            PathSegment::MoveTo(pt(0., 0.)),
+            PathSegment::LineTo(pt(10., 0.)),
+            PathSegment::QuadTo(pt(10., 10.), pt(0., 10.)),
+            PathSegment::LineTo(pt(0., 0.)),
+            PathSegment::Close,
+        ]);
+    }
+
+    #[test]
+    fn charstring() {
+        let code = [
+            248, 136, // 500
+            239, 239, 21, // 100 100 rmoveto
+            189, 6, // 50 hlineto
+            189, 7, // 50 vlineto
+            14,
+        ];
+        let mut path = vec![];
+        let mut state = CharStringState {
+            data: &[],
+            global_subrs: &[],
+            local_subrs: &[],
+            stack: vec![],
+            num_stems: 0,
+            width_parsed: false,
+            open: false,
+            x: 0.,
+            y: 0.,
+            path: &mut path,
+        };
+        state.execute(&code, 0).unwrap();
+        state.close();
+        assert_eq!(path, [
+            PathSegment::MoveTo(pt(100., 100.)),
+            PathSegment::LineTo(pt(150., 100.)),
+            PathSegment::LineTo(pt(150., 150.)),
+            PathSegment::Close,
+        ]);
+    }
+
+    #[test]
+    fn hflex1() {
+        let code = [
+            139, 139, 21, // 0 0 rmoveto
+            // 10 20 10 10 10 10 10 -10 10 hflex1
+            149, 159, 149, 149, 149, 149, 149, 129, 149, 12, 36,
+            14,
+        ];
+        let mut path = vec![];
+        let mut state = CharStringState {
+            data: &[],
+            global_subrs: &[],
+            local_subrs: &[],
+            stack: vec![],
+            num_stems: 0,
+            width_parsed: false,
+            open: false,
+            x: 0.,
+            y: 0.,
+            path: &mut path,
+        };
+        state.execute(&code, 0).unwrap();
+        // the second curve ends at the height where the first one started
+        assert_eq!(path, [
+            PathSegment::MoveTo(pt(0., 0.)),
+            PathSegment::CurveTo(pt(10., 20.), pt(20., 30.), pt(30., 30.)),
+            PathSegment::CurveTo(pt(40., 30.), pt(50., 20.), pt(60., 0.)),
+        ]);
+    }
+}
@@ -0,0 +1,359 @@
+//! Parsing of ICC profiles (as used by `ICCBased` color spaces) and conversion to sRGB.
+//!
+//! Supported are matrix/TRC profiles (RGB and gray) and profiles with an `A2B0` lookup table
+//! of type `lut8` or `lut16`, which covers most CMYK output profiles.
+//! Rendering intents other than perceptual (`A2B0`) are not distinguished.
+
+use crate::error::*;
+use crate::object::{lab_to_xyz, xyz_to_srgb, D50};
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    match data.get(pos..pos + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => bail!("ICC profile truncated"),
+    }
+}
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => bail!("ICC profile truncated"),
+    }
+}
+fn s15f16_at(data: &[u8], pos: usize) -> Result<f32> {
+    Ok(u32_at(data, pos)? as i32 as f32 / 65536.)
+}
+
+/// A tone reproduction curve.
+#[derive(Debug, Clone)]
+enum Curve {
+    Identity,
+    Gamma(f32),
+    Table(Vec<f32>),
+    Parametric(u16, [f32; 7]),
+}
+impl Curve {
+    fn parse(data: &[u8]) -> Result<Curve> {
+        match data.get(0..4) {
+            Some(b"curv") => {
+                let count = u32_at(data, 8)? as usize;
+                match count {
+                    0 => Ok(Curve::Identity),
+                    1 => Ok(Curve::Gamma(u16_at(data, 12)? as f32 / 256.)),
+                    _ => (0..count)
+                        .map(|i| Ok(u16_at(data, 12 + 2 * i)? as f32 / 65535.))
+                        .collect::<Result<_>>()
+                        .map(Curve::Table),
+                }
+            }
+            Some(b"para") => {
+                let function = u16_at(data, 8)?;
+                let n = match function {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => bail!("unknown parametric curve type {}", function),
+                };
+                let mut params = [0.; 7];
+                for (i, p) in params.iter_mut().take(n).enumerate() {
+                    *p = s15f16_at(data, 12 + 4 * i)?;
+                }
+                Ok(Curve::Parametric(function, params))
+            }
+            _ => bail!("unsupported curve type"),
+        }
+    }
+    fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0., 1.);
+        match *self {
+            Curve::Identity => x,
+            Curve::Gamma(g) => x.powf(g),
+            Curve::Table(ref table) => interpolate(table, x),
+            Curve::Parametric(function, [g, a, b, c, d, e, f]) => match function {
+                0 => x.powf(g),
+                1 => if x >= -b / a { (a * x + b).powf(g) } else { 0. },
+                2 => if x >= -b / a { (a * x + b).powf(g) + c } else { c },
+                3 => if x >= d { (a * x + b).powf(g) } else { c * x },
+                _ => if x >= d { (a * x + b).powf(g) + e } else { c * x + f },
+            },
+        }
+    }
+}
+
+/// Linear interpolation in a table spanning [0, 1].
+fn interpolate(table: &[f32], x: f32) -> f32 {
+    match table.len() {
+        0 => x,
+        1 => table[0],
+        n => {
+            let pos = x.clamp(0., 1.) * (n - 1) as f32;
+            let i = (pos as usize).min(n - 2);
+            let t = pos - i as f32;
+            table[i] * (1. - t) + table[i + 1] * t
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Lut {
+    inputs: usize,
+    outputs: usize,
+    grid_points: usize,
+    input_curves: Vec<Vec<f32>>,
+    /// `grid_points ^ inputs` entries of `outputs` values each, the first input varying slowest
+    clut: Vec<f32>,
+    output_curves: Vec<Vec<f32>>,
+}
+impl Lut {
+    fn parse(data: &[u8]) -> Result<Lut> {
+        let sixteen = match data.get(0..4) {
+            Some(b"mft1") => false,
+            Some(b"mft2") => true,
+            _ => bail!("unsupported lookup table type"),
+        };
+        let inputs = *data.get(8).ok_or_else(|| other!("ICC profile truncated"))? as usize;
+        let outputs = *data.get(9).ok_or_else(|| other!("ICC profile truncated"))? as usize;
+        let grid_points = *data.get(10).ok_or_else(|| other!("ICC profile truncated"))? as usize;
+        if inputs == 0 || inputs > 8 || outputs == 0 || grid_points < 2 {
+            bail!("invalid lookup table dimensions");
+        }
+        let (input_entries, output_entries, mut pos) = if sixteen {
+            (u16_at(data, 48)? as usize, u16_at(data, 50)? as usize, 52)
+        } else {
+            (256, 256, 48)
+        };
+        let size = if sixteen { 2 } else { 1 };
+        let mut read = |n: usize| -> Result<Vec<f32>> {
+            let values = match data.get(pos..pos + n * size) {
+                Some(bytes) if sixteen => bytes.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.).collect(),
+                Some(bytes) => bytes.iter().map(|&b| b as f32 / 255.).collect(),
+                None => bail!("ICC profile truncated"),
+            };
+            pos += n * size;
+            Ok(values)
+        };
+        let input_curves = (0..inputs).map(|_| read(input_entries)).collect::<Result<_>>()?;
+        let clut = read(grid_points.pow(inputs as u32) * outputs)?;
+        let output_curves = (0..outputs).map(|_| read(output_entries)).collect::<Result<_>>()?;
+        Ok(Lut { inputs, outputs, grid_points, input_curves, clut, output_curves })
+    }
+
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let g = self.grid_points;
+        // grid cell and position within it for each input
+        let mut cell = Vec::with_capacity(self.inputs);
+        for (i, curve) in self.input_curves.iter().enumerate() {
+            let x = interpolate(curve, input.get(i).cloned().unwrap_or(0.)) * (g - 1) as f32;
+            let idx = (x as usize).min(g - 2);
+            cell.push((idx, x - idx as f32));
+        }
+
+        // multilinear interpolation over the 2^inputs corners of the cell
+        let mut out = vec![0.; self.outputs];
+        for corner in 0..1usize << self.inputs {
+            let mut weight = 1.;
+            let mut offset = 0;
+            for (i, &(idx, t)) in cell.iter().enumerate() {
+                let upper = corner >> (self.inputs - 1 - i) & 1 == 1;
+                weight *= if upper { t } else { 1. - t };
+                offset = offset * g + idx + upper as usize;
+            }
+            if weight == 0. {
+                continue;
+            }
+            for (o, v) in out.iter_mut().enumerate() {
+                *v += weight * self.clut[offset * self.outputs + o];
+            }
+        }
+        for (v, curve) in out.iter_mut().zip(&self.output_curves) {
+            *v = interpolate(curve, *v);
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Transform {
+    /// columns are the XYZ of the red, green and blue primaries
+    Matrix { matrix: [[f32; 3]; 3], curves: [Curve; 3] },
+    Gray(Curve),
+    Lut(Lut),
+}
+
+/// The color space of the profile's input (or PCS) data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IccColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Lab,
+    Xyz,
+    /// Another color space with the given number of components.
+    Other(u8),
+}
+impl IccColorSpace {
+    fn from_signature(sig: &[u8]) -> IccColorSpace {
+        match sig {
+            b"GRAY" => IccColorSpace::Gray,
+            b"RGB " => IccColorSpace::Rgb,
+            b"CMYK" => IccColorSpace::Cmyk,
+            b"Lab " => IccColorSpace::Lab,
+            b"XYZ " => IccColorSpace::Xyz,
+            [n @ b'2'..=b'9', b'C', b'L', b'R'] => IccColorSpace::Other(n - b'0'),
+            _ => IccColorSpace::Other(0),
+        }
+    }
+    pub fn components(&self) -> usize {
+        match *self {
+            IccColorSpace::Gray => 1,
+            IccColorSpace::Rgb | IccColorSpace::Lab | IccColorSpace::Xyz => 3,
+            IccColorSpace::Cmyk => 4,
+            IccColorSpace::Other(n) => n as usize,
+        }
+    }
+}
+
+/// A parsed ICC profile that can convert colors to sRGB.
+#[derive(Debug, Clone)]
+pub struct IccProfile {
+    color_space: IccColorSpace,
+    pcs_lab: bool,
+    lut16: bool,
+    transform: Transform,
+}
+impl IccProfile {
+    pub fn parse(data: &[u8]) -> Result<IccProfile> {
+        if data.len() < 132 || data.get(36..40) != Some(b"acsp") {
+            bail!("not an ICC profile");
+        }
+        let color_space = IccColorSpace::from_signature(&data[16..20]);
+        let pcs_lab = &data[20..24] == b"Lab ";
+
+        let tag_count = u32_at(data, 128)? as usize;
+        let mut tags = Vec::with_capacity(tag_count.min(256));
+        for i in 0..tag_count {
+            let entry = 132 + 12 * i;
+            let sig = u32_at(data, entry)?.to_be_bytes();
+            let offset = u32_at(data, entry + 4)? as usize;
+            let size = u32_at(data, entry + 8)? as usize;
+            tags.push((sig, offset, size));
+        }
+        let tag = |sig: &[u8; 4]| -> Option<&[u8]> {
+            tags.iter()
+                .find(|(s, _, _)| s == sig)
+                .and_then(|&(_, offset, size)| data.get(offset..offset + size))
+        };
+        let xyz = |sig: &[u8; 4]| -> Result<[f32; 3]> {
+            let data = tag(sig).ok_or_else(|| other!("missing ICC tag {}", String::from_utf8_lossy(sig)))?;
+            Ok([s15f16_at(data, 8)?, s15f16_at(data, 12)?, s15f16_at(data, 16)?])
+        };
+        let curve = |sig: &[u8; 4]| -> Result<Curve> {
+            Curve::parse(tag(sig).ok_or_else(|| other!("missing ICC tag {}", String::from_utf8_lossy(sig)))?)
+        };
+
+        let (transform, lut16) = if let Some(lut) = tag(b"A2B0") {
+            (Transform::Lut(Lut::parse(lut)?), lut.starts_with(b"mft2"))
+        } else {
+            match color_space {
+                IccColorSpace::Rgb => {
+                    let (r, g, b) = (xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?);
+                    let matrix = [
+                        [r[0], g[0], b[0]],
+                        [r[1], g[1], b[1]],
+                        [r[2], g[2], b[2]],
+                    ];
+                    let curves = [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?];
+                    (Transform::Matrix { matrix, curves }, false)
+                }
+                IccColorSpace::Gray => (Transform::Gray(curve(b"kTRC")?), false),
+                _ => bail!("unsupported ICC profile: {:?} without A2B0 table", color_space),
+            }
+        };
+        Ok(IccProfile { color_space, pcs_lab, lut16, transform })
+    }
+
+    pub fn color_space(&self) -> IccColorSpace {
+        self.color_space
+    }
+
+    pub fn components(&self) -> usize {
+        match self.transform {
+            Transform::Lut(ref lut) => lut.inputs,
+            _ => self.color_space.components(),
+        }
+    }
+
+    /// Convert a color (with components in 0..1) to CIE XYZ relative to D50.
+    pub fn to_xyz(&self, color: &[f32]) -> [f32; 3] {
+        let c = |i: usize| color.get(i).cloned().unwrap_or(0.);
+        match self.transform {
+            Transform::Matrix { ref matrix, ref curves } => {
+                let lin = [curves[0].eval(c(0)), curves[1].eval(c(1)), curves[2].eval(c(2))];
+                let mut xyz = [0.; 3];
+                for (row, out) in matrix.iter().zip(xyz.iter_mut()) {
+                    *out = row[0] * lin[0] + row[1] * lin[1] + row[2] * lin[2];
+                }
+                xyz
+            }
+            Transform::Gray(ref curve) => {
+                let y = curve.eval(c(0));
+                [D50[0] * y, D50[1] * y, D50[2] * y]
+            }
+            Transform::Lut(ref lut) => {
+                let out = lut.eval(color);
+                let v = |i: usize| out.get(i).cloned().unwrap_or(0.);
+                if self.pcs_lab {
+                    // legacy 16 bit Lab encoding has 0xFF00 as maximum
+                    let scale = if self.lut16 { 65535. / 65280. } else { 1. };
+                    lab_to_xyz(v(0) * scale * 100., v(1) * scale * 255. - 128., v(2) * scale * 255. - 128.)
+                } else {
+                    // u1Fixed15
+                    let scale = 65535. / 32768.;
+                    [v(0) * scale, v(1) * scale, v(2) * scale]
+                }
+            }
+        }
+    }
+
+    /// Convert a color (with components in 0..1) to sRGB (with components in 0..1).
+    pub fn to_srgb(&self, color: &[f32]) -> [f32; 3] {
+        xyz_to_srgb(self.to_xyz(color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white() {
+        let rgb = xyz_to_srgb(lab_to_xyz(100., 0., 0.));
+        for c in rgb {
+            assert!((c - 1.).abs() < 1e-3, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn lut_interpolation() {
+        // identity for two inputs and outputs on a 2x2 grid
+        let lut = Lut {
+            inputs: 2,
+            outputs: 2,
+            grid_points: 2,
+            input_curves: vec![vec![], vec![]],
+            clut: vec![0., 0., 0., 1., 1., 0., 1., 1.],
+            output_curves: vec![vec![], vec![]],
+        };
+        let out = lut.eval(&[0.25, 0.75]);
+        assert!((out[0] - 0.25).abs() < 1e-6 && (out[1] - 0.75).abs() < 1e-6, "{:?}", out);
+    }
+
+    #[test]
+    fn parametric_curve() {
+        // the sRGB curve
+        let curve = Curve::Parametric(3, [2.4, 1. / 1.055, 0.055 / 1.055, 1. / 12.92, 0.04045, 0., 0.]);
+        assert!((curve.eval(1.) - 1.).abs() < 1e-4);
+        assert!((curve.eval(0.02) - 0.02 / 12.92).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,818 @@
+//! Walking content streams while keeping track of the graphics state.
+//!
+//! [`ContentInterpreter`] follows the operators the way a renderer would and reports
+//! what is painted as [`Event`]s, with all coordinates in device space.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::content::{Color, FormXObject, InlineImage, LineCap, LineJoin, Matrix, Op, Point, TextDrawAdjusted, TextMode, Winding};
+use crate::error::*;
+use crate::font::Font;
+use crate::glyph::{self, GlyphOutlines};
+use crate::object::*;
+use crate::primitive::Name;
+use crate::text::FontCodec;
+
+/// Form XObjects nested deeper than this are not drawn.
+const MAX_FORM_DEPTH: usize = 32;
+
+/// The transformation from the default user space of `page` to a device space with the origin
+/// at the top left of the crop box, `scale` units per point and `Rotate` applied.
+///
+/// Returns the matrix with the width and height of the device space.
+pub fn device_matrix(page: &Page, scale: f32) -> Result<(Matrix, f32, f32)> {
+    let crop = page.crop_box()?;
+    let s = scale;
+    let (left, right) = (crop.left.min(crop.right), crop.left.max(crop.right));
+    let (bottom, top) = (crop.bottom.min(crop.top), crop.bottom.max(crop.top));
+    let (w, h) = ((right - left) * s, (top - bottom) * s);
+    Ok(match page.rotate.rem_euclid(360) / 90 * 90 {
+        90 => (Matrix { a: 0., b: s, c: s, d: 0., e: -bottom * s, f: -left * s }, h, w),
+        180 => (Matrix { a: -s, b: 0., c: 0., d: s, e: right * s, f: -bottom * s }, w, h),
+        270 => (Matrix { a: 0., b: -s, c: -s, d: 0., e: top * s, f: right * s }, h, w),
+        _ => (Matrix { a: s, b: 0., c: 0., d: -s, e: -left * s, f: top * s }, w, h),
+    })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Point),
+    LineTo(Point),
+    CurveTo(Point, Point, Point),
+    Close,
+}
+
+/// A path in device space.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+}
+impl Path {
+    /// The bounding box of all points, including the control points of curves.
+    pub fn bounds(&self) -> Option<Rectangle> {
+        let mut points = self.segments.iter().flat_map(|s| match *s {
+            PathSegment::MoveTo(p) | PathSegment::LineTo(p) => vec![p],
+            PathSegment::CurveTo(c1, c2, p) => vec![c1, c2, p],
+            PathSegment::Close => vec![],
+        });
+        let first = points.next()?;
+        let mut r = Rectangle { left: first.x, bottom: first.y, right: first.x, top: first.y };
+        for p in points {
+            r.left = r.left.min(p.x);
+            r.bottom = r.bottom.min(p.y);
+            r.right = r.right.max(p.x);
+            r.top = r.top.max(p.y);
+        }
+        Some(r)
+    }
+
+    /// Map a glyph outline to device space with the glyph's text rendering matrix.
+    pub fn from_outline(segments: &[glyph::PathSegment], glyph: &Glyph, units_per_em: f32) -> Path {
+        let m = Matrix::scale(1. / units_per_em, 1. / units_per_em) * glyph.matrix;
+        let t = |p: Point| m.transform_point(p);
+        let mut path = Path::default();
+        let mut last = Point { x: 0., y: 0. };
+        for segment in segments {
+            path.segments.push(match *segment {
+                glyph::PathSegment::MoveTo(p) => {
+                    last = p;
+                    PathSegment::MoveTo(t(p))
+                }
+                glyph::PathSegment::LineTo(p) => {
+                    last = p;
+                    PathSegment::LineTo(t(p))
+                }
+                glyph::PathSegment::QuadTo(c, p) => {
+                    // elevate to a cubic curve
+                    let c1 = Point { x: last.x + 2. / 3. * (c.x - last.x), y: last.y + 2. / 3. * (c.y - last.y) };
+                    let c2 = Point { x: p.x + 2. / 3. * (c.x - p.x), y: p.y + 2. / 3. * (c.y - p.y) };
+                    last = p;
+                    PathSegment::CurveTo(t(c1), t(c2), t(p))
+                }
+                glyph::PathSegment::CurveTo(c1, c2, p) => {
+                    last = p;
+                    PathSegment::CurveTo(t(c1), t(c2), t(p))
+                }
+                glyph::PathSegment::Close => PathSegment::Close,
+            });
+        }
+        path
+    }
+}
+
+/// One path of the clipping region.
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub path: Path,
+    pub winding: Winding,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextState {
+    pub font: Option<Name>,
+    pub font_size: f32,
+    pub char_space: f32,
+    pub word_space: f32,
+    /// `1.0` is 100%
+    pub horiz_scale: f32,
+    pub leading: f32,
+    pub rise: f32,
+    pub render_mode: TextMode,
+}
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            font: None,
+            font_size: 0.,
+            char_space: 0.,
+            word_space: 0.,
+            horiz_scale: 1.,
+            leading: 0.,
+            rise: 0.,
+            render_mode: TextMode::Fill,
+        }
+    }
+}
+
+/// The parameters that are saved and restored by `q` and `Q`.
+#[derive(Debug, Clone)]
+pub struct GraphicsState {
+    /// Maps user space to device space.
+    pub ctm: Matrix,
+    /// The clipping region is the intersection of these paths.
+    pub clip: Vec<Arc<Clip>>,
+
+    pub line_width: f32,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub miter_limit: f32,
+    pub dash_pattern: Vec<f32>,
+    pub dash_phase: f32,
+
+    pub fill_color_space: Name,
+    pub fill_color: Color,
+    pub stroke_color_space: Name,
+    pub stroke_color: Color,
+    pub fill_alpha: f32,
+    pub stroke_alpha: f32,
+    pub blend_mode: BlendMode,
+    pub rendering_intent: RenderingIntent,
+
+    pub text: TextState,
+}
+impl GraphicsState {
+    pub fn new(ctm: Matrix) -> Self {
+        GraphicsState {
+            ctm,
+            clip: vec![],
+            line_width: 1.,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.,
+            dash_pattern: vec![],
+            dash_phase: 0.,
+            fill_color_space: "DeviceGray".into(),
+            fill_color: Color::Gray(0.),
+            stroke_color_space: "DeviceGray".into(),
+            stroke_color: Color::Gray(0.),
+            fill_alpha: 1.,
+            stroke_alpha: 1.,
+            blend_mode: BlendMode::Normal,
+            rendering_intent: RenderingIntent::RelativeColorimetric,
+            text: TextState::default(),
+        }
+    }
+    /// The fill color in RGB, see [`approx_rgb`].
+    pub fn fill_rgb(&self) -> [f32; 3] {
+        approx_rgb(&self.fill_color)
+    }
+    /// The stroke color in RGB, see [`approx_rgb`].
+    pub fn stroke_rgb(&self) -> [f32; 3] {
+        approx_rgb(&self.stroke_color)
+    }
+    fn apply(&mut self, gs: &GraphicsStateParameters) {
+        if let Some(w) = gs.line_width {
+            self.line_width = w;
+        }
+        // the ExtGState has its own copies of these enums
+        if let Some(cap) = gs.line_cap {
+            self.line_cap = match cap {
+                crate::object::LineCap::Butt => LineCap::Butt,
+                crate::object::LineCap::Round => LineCap::Round,
+                crate::object::LineCap::Square => LineCap::Square,
+            };
+        }
+        if let Some(join) = gs.line_join {
+            self.line_join = match join {
+                crate::object::LineJoin::Miter => LineJoin::Miter,
+                crate::object::LineJoin::Round => LineJoin::Round,
+                crate::object::LineJoin::Bevel => LineJoin::Bevel,
+            };
+        }
+        if let Some(limit) = gs.miter_limit {
+            self.miter_limit = limit;
+        }
+        // [[dash array] phase]
+        if let Some([pattern, phase]) = gs.dash_pattern.as_deref() {
+            if let (Ok(pattern), Ok(phase)) = (pattern.as_array(), phase.as_number()) {
+                self.dash_pattern = pattern.iter().filter_map(|p| p.as_number().ok()).collect();
+                self.dash_phase = phase;
+            }
+        }
+        if let Some(intent) = gs.rendering_intent.as_ref().and_then(|n| RenderingIntent::from_str(n)) {
+            self.rendering_intent = intent;
+        }
+        if let Some(alpha) = gs.fill_alpha {
+            self.fill_alpha = alpha;
+        }
+        if let Some(alpha) = gs.stroke_alpha {
+            self.stroke_alpha = alpha;
+        }
+        if let Some(mode) = gs.blend_mode {
+            self.blend_mode = mode;
+        }
+    }
+}
+
+/// A glyph shown by a text operator.
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub code: u16,
+    /// The number of bytes the code takes in the string.
+    pub code_len: usize,
+    /// The CID the code selects, for CID fonts.
+    pub cid: Option<u16>,
+    /// The text of the glyph, if the font tells.
+    pub unicode: Option<String>,
+    /// The text rendering matrix: maps glyph coordinates in em units to device space.
+    pub matrix: Matrix,
+    /// The displacement in text space along the writing direction: to the right, or
+    /// downwards if `vertical`. `0` if the width is unknown.
+    pub advance: f32,
+    /// The font writes vertically (its CMap has `WMode` 1).
+    pub vertical: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImageSource<'a> {
+    XObject { name: &'a Name, image: &'a ImageXObject },
+    Inline(&'a InlineImage),
+}
+
+/// What a content stream paints.
+#[derive(Debug)]
+pub enum Event<'a> {
+    PathFilled { path: &'a Path, winding: Winding },
+    PathStroked { path: &'a Path },
+    TextShown { glyphs: &'a [Glyph], font: Option<&'a MaybeRef<Font>> },
+    /// The image fills the unit square mapped to device space by `matrix`.
+    ImageDrawn { image: ImageSource<'a>, matrix: Matrix },
+    /// The shading fills the current clipping region.
+    ShadingPainted { name: &'a Name },
+}
+
+/// Glyph outlines of the fonts seen so far, for drawing text as paths.
+#[derive(Default)]
+pub struct OutlineCache {
+    /// keyed by the address of the font, which is kept alive
+    fonts: HashMap<usize, (MaybeRef<Font>, Option<Arc<GlyphOutlines>>)>,
+}
+impl OutlineCache {
+    pub fn new() -> Self {
+        OutlineCache::default()
+    }
+
+    /// The outline of `glyph` in device space, `None` if the font has no embedded outlines.
+    pub fn glyph_path(&mut self, font: &MaybeRef<Font>, glyph: &Glyph, resolve: &impl Resolve) -> Option<Path> {
+        let key = &**font as *const Font as usize;
+        let outlines = match self.fonts.get(&key) {
+            Some((_, outlines)) => outlines.clone(),
+            None => {
+                let outlines = match font.glyph_outlines(resolve) {
+                    Some(Ok(outlines)) => Some(Arc::new(outlines)),
+                    Some(Err(e)) => {
+                        warn!("can't read font {:?}: {:?}", font.name, e);
+                        None
+                    }
+                    None => None,
+                };
+                self.fonts.insert(key, (font.clone(), outlines.clone()));
+                outlines
+            }
+        }?;
+        match font.glyph_outline(glyph.cid.unwrap_or(glyph.code), &outlines) {
+            Ok(segments) => Some(Path::from_outline(&segments?, glyph, outlines.units_per_em())),
+            Err(e) => {
+                warn!("glyph {}: {:?}", glyph.code, e);
+                None
+            }
+        }
+    }
+}
+
+type LoadedFont = Option<(MaybeRef<Font>, Arc<FontCodec>)>;
+
+/// The resources of the content stream or form XObject being interpreted.
+struct Level {
+    resources: Option<MaybeRef<Resources>>,
+    fonts: HashMap<Name, LoadedFont>,
+}
+
+/// Follows the operators of a content stream, maintaining the graphics state.
+pub struct ContentInterpreter<'a, R> {
+    resolve: &'a R,
+    levels: Vec<Level>,
+    state: GraphicsState,
+    stack: Vec<GraphicsState>,
+
+    path: Path,
+    pending_clip: Option<Winding>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    glyphs: Vec<Glyph>,
+}
+
+impl<'a, R: Resolve> ContentInterpreter<'a, R> {
+    /// `ctm` is the initial transformation from user space to device space.
+    pub fn new(resources: Option<MaybeRef<Resources>>, ctm: Matrix, resolve: &'a R) -> Self {
+        ContentInterpreter {
+            resolve,
+            levels: vec![Level { resources, fonts: HashMap::new() }],
+            state: GraphicsState::new(ctm),
+            stack: vec![],
+            path: Path::default(),
+            pending_clip: None,
+            text_matrix: Matrix::default(),
+            line_matrix: Matrix::default(),
+            glyphs: vec![],
+        }
+    }
+
+    /// Interpret the content of `page` in its default user space.
+    pub fn for_page(page: &Page, resolve: &'a R) -> Result<Self> {
+        let resources = page.resources().ok().cloned();
+        Ok(ContentInterpreter::new(resources, Matrix::default(), resolve))
+    }
+
+    pub fn state(&self) -> &GraphicsState {
+        &self.state
+    }
+
+    /// Interpret `ops` and call `cb` for everything painted.
+    pub fn run(&mut self, ops: &[Op], cb: &mut impl FnMut(&Event, &GraphicsState) -> Result<()>) -> Result<()> {
+        for op in ops {
+            t!(self.op(op, cb));
+        }
+        Ok(())
+    }
+
+    fn resources(&self) -> Option<&Resources> {
+        self.levels.last().and_then(|l| l.resources.as_deref())
+    }
+
+    fn user_point(&self, p: Point) -> Point {
+        self.state.ctm.transform_point(p)
+    }
+
+    fn font(&mut self) -> Result<LoadedFont> {
+        let name = match self.state.text.font {
+            Some(ref name) => name.clone(),
+            None => return Ok(None),
+        };
+        let level = self.levels.last_mut().unwrap();
+        if let Some(font) = level.fonts.get(&name) {
+            return Ok(font.clone());
+        }
+        let font = match level.resources.as_ref().and_then(|r| r.fonts.get(&name)) {
+            Some(font) => {
+                let font = t!(font.load(self.resolve));
+                let codec = Arc::new(t!(FontCodec::new(&font, self.resolve)));
+                Some((font, codec))
+            }
+            None => None,
+        };
+        level.fonts.insert(name, font.clone());
+        Ok(font)
+    }
+
+    fn end_path(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        if let Some(winding) = self.pending_clip.take() {
+            self.state.clip.push(Arc::new(Clip { path, winding }));
+        }
+    }
+
+    fn paint(&mut self, fill: Option<Winding>, stroke: bool, cb: &mut impl FnMut(&Event, &GraphicsState) -> Result<()>) -> Result<()> {
+        if let Some(winding) = fill {
+            cb(&Event::PathFilled { path: &self.path, winding }, &self.state)?;
+        }
+        if stroke {
+            cb(&Event::PathStroked { path: &self.path }, &self.state)?;
+        }
+        self.end_path();
+        Ok(())
+    }
+
+    fn show(&mut self, items: &[TextDrawAdjusted], cb: &mut impl FnMut(&Event, &GraphicsState) -> Result<()>) -> Result<()> {
+        let (font, codec) = t!(self.font()).unzip();
+        let text = &self.state.text;
+        let size = text.font_size;
+        let scale = Matrix { a: size * text.horiz_scale, b: 0., c: 0., d: size, e: 0., f: text.rise };
+
+        let vertical = codec.as_ref().is_some_and(|c| c.is_vertical());
+        self.glyphs.clear();
+        for item in items {
+            let data = match *item {
+                TextDrawAdjusted::Spacing(n) if vertical => {
+                    let ty = -n / 1000. * size;
+                    self.text_matrix = Matrix::translate(0., ty) * self.text_matrix;
+                    continue;
+                }
+                TextDrawAdjusted::Spacing(n) => {
+                    let tx = -n / 1000. * size * text.horiz_scale;
+                    self.text_matrix = Matrix::translate(tx, 0.) * self.text_matrix;
+                    continue;
+                }
+                TextDrawAdjusted::Text(ref data) => data,
+            };
+            let codes = match codec {
+                Some(ref codec) => codec.codes_with_len(data.as_bytes()),
+                None => data.as_bytes().iter().map(|&b| (b as u16, 1)).collect(),
+            };
+            for (code, code_len) in codes {
+                let is_space = codec.as_ref().map_or(code == 32, |c| c.is_space(code));
+                let word_space = if is_space { text.word_space } else { 0. };
+                let metric = codec.as_ref().and_then(|c| c.vertical(code));
+                if let Some(metric) = metric {
+                    // the glyph hangs from the current point by its position vector
+                    let origin = Matrix::translate(-metric.v[0] / 1000., -metric.v[1] / 1000.);
+                    let matrix = origin * scale * self.text_matrix * self.state.ctm;
+                    let ty = metric.w1 / 1000. * size + text.char_space + word_space;
+                    self.glyphs.push(Glyph {
+                        code,
+                        code_len,
+                        cid: codec.as_ref().and_then(|c| c.cid(code)),
+                        unicode: codec.as_ref().and_then(|c| c.decode(code)).map(String::from),
+                        matrix,
+                        advance: -ty,
+                        vertical: true,
+                    });
+                    self.text_matrix = Matrix::translate(0., ty) * self.text_matrix;
+                    continue;
+                }
+                let matrix = scale * self.text_matrix * self.state.ctm;
+                let width = codec.as_ref().and_then(|c| c.width(code)).unwrap_or(0.);
+                let advance = (width / 1000. * size + text.char_space + word_space) * text.horiz_scale;
+                self.glyphs.push(Glyph {
+                    code,
+                    code_len,
+                    cid: codec.as_ref().and_then(|c| c.cid(code)),
+                    unicode: codec.as_ref().and_then(|c| c.decode(code)).map(String::from),
+                    matrix,
+                    advance,
+                    vertical: false,
+                });
+                self.text_matrix = Matrix::translate(advance, 0.) * self.text_matrix;
+            }
+        }
+        cb(&Event::TextShown { glyphs: &self.glyphs, font: font.as_ref() }, &self.state)
+    }
+
+    fn form(&mut self, form: &FormXObject, cb: &mut impl FnMut(&Event, &GraphicsState) -> Result<()>) -> Result<()> {
+        if self.levels.len() > MAX_FORM_DEPTH {
+            warn!("form XObjects nested too deep");
+            return Ok(());
+        }
+        let dict = form.dict();
+        let matrix = dict.matrix.unwrap_or_default();
+        let ops = t!(form.operations(self.resolve));
+
+        self.stack.push(self.state.clone());
+        self.state.ctm = matrix * self.state.ctm;
+        let bbox = dict.bbox;
+        let corners = [(bbox.left, bbox.bottom), (bbox.right, bbox.bottom), (bbox.right, bbox.top), (bbox.left, bbox.top)]
+            .map(|(x, y)| self.user_point(Point { x, y }));
+        let mut clip = Path { segments: vec![PathSegment::MoveTo(corners[0])] };
+        clip.segments.extend(corners[1..].iter().map(|&p| PathSegment::LineTo(p)));
+        clip.segments.push(PathSegment::Close);
+        self.state.clip.push(Arc::new(Clip { path: clip, winding: Winding::NonZero }));
+
+        let resources = dict.resources.clone().or_else(|| self.levels.last().unwrap().resources.clone());
+        self.levels.push(Level { resources, fonts: HashMap::new() });
+        let (text_matrix, line_matrix) = (self.text_matrix, self.line_matrix);
+        let stack_len = self.stack.len();
+
+        let result = self.run(&ops, cb);
+
+        // unbalanced `q` in the form
+        self.stack.truncate(stack_len);
+        self.levels.pop();
+        self.text_matrix = text_matrix;
+        self.line_matrix = line_matrix;
+        self.path = Path::default();
+        self.pending_clip = None;
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+        result
+    }
+
+    /// Interpret a single operator.
+    pub fn op(&mut self, op: &Op, cb: &mut impl FnMut(&Event, &GraphicsState) -> Result<()>) -> Result<()> {
+        match *op {
+            Op::Save => self.stack.push(self.state.clone()),
+            Op::Restore => match self.stack.pop() {
+                Some(state) => self.state = state,
+                None => warn!("Q without matching q"),
+            },
+            Op::Transform { matrix } => self.state.ctm = matrix * self.state.ctm,
+
+            Op::MoveTo { p } => self.path.segments.push(PathSegment::MoveTo(self.user_point(p))),
+            Op::LineTo { p } => self.path.segments.push(PathSegment::LineTo(self.user_point(p))),
+            Op::CurveTo { c1, c2, p } => {
+                let segment = PathSegment::CurveTo(self.user_point(c1), self.user_point(c2), self.user_point(p));
+                self.path.segments.push(segment);
+            }
+            Op::Rect { rect } => {
+                let (x0, y0, x1, y1) = (rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+                self.path.segments.extend([
+                    PathSegment::MoveTo(self.user_point(Point { x: x0, y: y0 })),
+                    PathSegment::LineTo(self.user_point(Point { x: x1, y: y0 })),
+                    PathSegment::LineTo(self.user_point(Point { x: x1, y: y1 })),
+                    PathSegment::LineTo(self.user_point(Point { x: x0, y: y1 })),
+                    PathSegment::Close,
+                ]);
+            }
+            Op::Close => self.path.segments.push(PathSegment::Close),
+            Op::Clip { winding } => self.pending_clip = Some(winding),
+            Op::EndPath => self.end_path(),
+            Op::Stroke => t!(self.paint(None, true, cb)),
+            Op::Fill { winding } => t!(self.paint(Some(winding), false, cb)),
+            Op::FillAndStroke { winding } => t!(self.paint(Some(winding), true, cb)),
+            Op::Shade { ref name } => cb(&Event::ShadingPainted { name }, &self.state)?,
+
+            Op::LineWidth { width } => self.state.line_width = width,
+            Op::Dash { ref pattern, phase } => {
+                self.state.dash_pattern = pattern.clone();
+                self.state.dash_phase = phase;
+            }
+            Op::LineJoin { join } => self.state.line_join = join,
+            Op::LineCap { cap } => self.state.line_cap = cap,
+            Op::MiterLimit { limit } => self.state.miter_limit = limit,
+            Op::RenderingIntent { intent } => self.state.rendering_intent = intent,
+            Op::GraphicsState { ref name } => {
+                match self.resources().and_then(|r| r.graphics_states.get(name)) {
+                    Some(gs) => {
+                        let gs = gs.clone();
+                        self.state.apply(&gs);
+                    }
+                    None => warn!("missing graphics state {}", name),
+                }
+            }
+
+            Op::FillColorSpace { ref name } => {
+                self.state.fill_color = initial_color(name);
+                self.state.fill_color_space = name.clone();
+            }
+            Op::StrokeColorSpace { ref name } => {
+                self.state.stroke_color = initial_color(name);
+                self.state.stroke_color_space = name.clone();
+            }
+            Op::FillColor { ref color } => {
+                if let Some(space) = device_space(color) {
+                    self.state.fill_color_space = space.into();
+                }
+                self.state.fill_color = color.clone();
+            }
+            Op::StrokeColor { ref color } => {
+                if let Some(space) = device_space(color) {
+                    self.state.stroke_color_space = space.into();
+                }
+                self.state.stroke_color = color.clone();
+            }
+
+            Op::BeginText => {
+                self.text_matrix = Matrix::default();
+                self.line_matrix = Matrix::default();
+            }
+            Op::EndText => {}
+            Op::CharSpacing { char_space } => self.state.text.char_space = char_space,
+            Op::WordSpacing { word_space } => self.state.text.word_space = word_space,
+            Op::TextScaling { horiz_scale } => self.state.text.horiz_scale = horiz_scale / 100.,
+            Op::Leading { leading } => self.state.text.leading = leading,
+            Op::TextFont { ref name, size } => {
+                self.state.text.font = Some(name.clone());
+                self.state.text.font_size = size;
+            }
+            Op::TextRenderMode { mode } => self.state.text.render_mode = mode,
+            Op::TextRise { rise } => self.state.text.rise = rise,
+            Op::MoveTextPosition { translation: t } => {
+                self.line_matrix = Matrix::translate(t.x, t.y) * self.line_matrix;
+                self.text_matrix = self.line_matrix;
+            }
+            Op::SetTextMatrix { matrix } => {
+                self.line_matrix = matrix;
+                self.text_matrix = matrix;
+            }
+            Op::TextNewline => {
+                self.line_matrix = Matrix::translate(0., -self.state.text.leading) * self.line_matrix;
+                self.text_matrix = self.line_matrix;
+            }
+            Op::TextDraw { ref text } => t!(self.show(&[TextDrawAdjusted::Text(text.clone())], cb)),
+            Op::TextDrawAdjusted { ref array } => t!(self.show(array, cb)),
+
+            Op::XObject { ref name } => {
+                let r = match self.resources().and_then(|r| r.xobjects.get(name)) {
+                    Some(&r) => r,
+                    None => {
+                        warn!("missing XObject {}", name);
+                        return Ok(());
+                    }
+                };
+                let xobject = t!(self.resolve.get(r));
+                match *xobject {
+                    XObject::Image(ref image) => {
+                        let event = Event::ImageDrawn { image: ImageSource::XObject { name, image }, matrix: self.state.ctm };
+                        cb(&event, &self.state)?;
+                    }
+                    XObject::Form(ref form) => t!(self.form(form, cb)),
+                    XObject::Postscript(_) => {}
+                }
+            }
+            Op::InlineImage { ref image } => {
+                cb(&Event::ImageDrawn { image: ImageSource::Inline(image), matrix: self.state.ctm }, &self.state)?;
+            }
+
+            Op::BeginMarkedContent { .. } | Op::EndMarkedContent | Op::MarkedContentPoint { .. } | Op::Flatness { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// An image drawn on a page, see [`Page::images`].
+#[derive(Debug, Clone)]
+pub struct PlacedImage {
+    /// The resource name of an image XObject, `None` for inline images.
+    pub name: Option<Name>,
+    pub image: Arc<ImageXObject>,
+    /// Maps the unit square the image fills to the default user space of the page.
+    pub matrix: Matrix,
+}
+impl PlacedImage {
+    /// The bounding box of the image in the default user space of the page.
+    pub fn bounds(&self) -> Rectangle {
+        let corners = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)].map(|(x, y)| self.matrix.transform_point(Point { x, y }));
+        corners[1..].iter().fold(
+            Rectangle { left: corners[0].x, bottom: corners[0].y, right: corners[0].x, top: corners[0].y },
+            |r, p| Rectangle { left: r.left.min(p.x), bottom: r.bottom.min(p.y), right: r.right.max(p.x), top: r.top.max(p.y) },
+        )
+    }
+}
+
+impl Page {
+    /// The images drawn by the content of this page, including those in form XObjects, in drawing order.
+    ///
+    /// An image drawn more than once is listed every time.
+    pub fn images(&self, resolve: &impl Resolve) -> Result<Vec<PlacedImage>> {
+        let ops = match self.contents {
+            Some(ref contents) => t!(contents.operations(resolve)),
+            None => return Ok(vec![]),
+        };
+        let mut images = vec![];
+        let mut interpreter = t!(ContentInterpreter::for_page(self, resolve));
+        t!(interpreter.run(&ops, &mut |event, _| {
+            if let Event::ImageDrawn { image, matrix } = *event {
+                let (name, image) = match image {
+                    ImageSource::XObject { name, image } => (Some(name.clone()), Arc::new(image.clone())),
+                    ImageSource::Inline(inline) => (None, inline.image.clone()),
+                };
+                images.push(PlacedImage { name, image, matrix });
+            }
+            Ok(())
+        }));
+        Ok(images)
+    }
+}
+
+/// Convert a color to RGB without looking at its color space.
+///
+/// Device colors are converted directly, others are guessed by their number of components.
+/// Patterns come out as gray.
+pub fn approx_rgb(color: &Color) -> [f32; 3] {
+    let cmyk = |c: f32, m: f32, y: f32, k: f32| [(1. - c) * (1. - k), (1. - m) * (1. - k), (1. - y) * (1. - k)];
+    match *color {
+        Color::Gray(g) => [g; 3],
+        Color::Rgb(ref c) => [c.red, c.green, c.blue],
+        Color::Cmyk(ref c) => cmyk(c.cyan, c.magenta, c.yellow, c.key),
+        Color::Other(ref args) => {
+            let n: Vec<f32> = args.iter().filter_map(|p| p.as_number().ok()).collect();
+            match n.len() {
+                1 => [n[0]; 3],
+                3 => [n[0], n[1], n[2]],
+                4 => cmyk(n[0], n[1], n[2], n[3]),
+                _ => [0.5; 3],
+            }
+        }
+    }
+}
+
+fn initial_color(space: &str) -> Color {
+    match space {
+        "DeviceGray" | "G" | "CalGray" => Color::Gray(0.),
+        "DeviceRGB" | "RGB" | "CalRGB" => Color::Rgb(crate::content::Rgb { red: 0., green: 0., blue: 0. }),
+        "DeviceCMYK" | "CMYK" => Color::Cmyk(crate::content::Cmyk { cyan: 0., magenta: 0., yellow: 0., key: 1. }),
+        _ => Color::Other(vec![]),
+    }
+}
+fn device_space(color: &Color) -> Option<&'static str> {
+    match color {
+        Color::Gray(_) => Some("DeviceGray"),
+        Color::Rgb(_) => Some("DeviceRGB"),
+        Color::Cmyk(_) => Some("DeviceCMYK"),
+        Color::Other(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+
+    #[test]
+    fn paths_and_clipping() {
+        let ops = parse_ops(b"q 2 0 0 2 10 10 cm 0 0 5 5 re W n 1 0 0 rg 0 0 m 10 0 l 10 10 l f Q 0 0 m 1 1 l S", &NoResolve).unwrap();
+        let mut interpreter = ContentInterpreter::new(None, Matrix::default(), &NoResolve);
+        let mut events = vec![];
+        interpreter.run(&ops, &mut |event, state| {
+            match *event {
+                Event::PathFilled { path, .. } => {
+                    let r = path.bounds().unwrap();
+                    events.push(("fill", [r.left, r.bottom, r.right, r.top], state.clip.len()));
+                    assert!(matches!(state.fill_color, Color::Rgb(_)));
+                    assert_eq!(state.fill_color_space, Name::from("DeviceRGB"));
+                }
+                Event::PathStroked { path } => {
+                    let r = path.bounds().unwrap();
+                    events.push(("stroke", [r.left, r.bottom, r.right, r.top], state.clip.len()));
+                }
+                _ => {}
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!(events, [
+            ("fill", [10., 10., 30., 30.], 1),
+            ("stroke", [0., 0., 1., 1.], 0),
+        ]);
+    }
+
+    #[test]
+    fn text_position() {
+        let ops = parse_ops(b"BT /F1 10 Tf 100 200 Td 0.5 Tz (ab) Tj [(c) -1000 (d)] TJ ET", &NoResolve).unwrap();
+        let mut interpreter = ContentInterpreter::new(None, Matrix::translate(0., 50.), &NoResolve);
+        let mut origins = vec![];
+        interpreter.run(&ops, &mut |event, _| {
+            if let Event::TextShown { glyphs, .. } = *event {
+                origins.extend(glyphs.iter().map(|g| (g.code, g.matrix.e, g.matrix.f)));
+            }
+            Ok(())
+        }).unwrap();
+        // no widths without a font, only the TJ adjustment moves: 1000/1000 * 10 * 0.005
+        assert_eq!(origins, [(97, 100., 250.), (98, 100., 250.), (99, 100., 250.), (100, 100.05, 250.)]);
+    }
+
+    #[test]
+    fn vertical_text() {
+        let resources = b"<< /Font << /F1 << /Type /Font /Subtype /Type0 /BaseFont /X /Encoding /Identity-V
+            /DescendantFonts [<< /Type /Font /Subtype /CIDFontType2 /BaseFont /X /CIDSystemInfo << >>
+                /FontDescriptor << /FontName /X /Flags 4 /FontBBox [0 -120 1000 880] /ItalicAngle 0 >>
+                /W2 [2 [-500 250 880]] >>] >> >> >>";
+        let resources = crate::parser::parse(resources, &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+        let resources = MaybeRef::Direct(Resources::from_primitive(resources, &NoResolve).unwrap().into());
+        let ops = parse_ops(b"BT /F1 10 Tf 100 200 Td <00010002> Tj [<0001> 500 <0001>] TJ ET", &NoResolve).unwrap();
+        let mut interpreter = ContentInterpreter::new(Some(resources), Matrix::default(), &NoResolve);
+        let mut glyphs = vec![];
+        interpreter.run(&ops, &mut |event, _| {
+            if let Event::TextShown { glyphs: shown, .. } = *event {
+                glyphs.extend(shown.iter().map(|g| (g.cid, g.vertical, g.advance, g.matrix.e, (g.matrix.f * 10.).round() / 10.)));
+            }
+            Ok(())
+        }).unwrap();
+        // the glyphs hang from the current point by [w0/2 880] or the W2 position vector,
+        // and move down by 1000 or 500 thousandths of an em
+        assert_eq!(glyphs, [
+            (Some(1), true, 10., 95., 191.2),
+            (Some(2), true, 5., 97.5, 181.2),
+            (Some(1), true, 10., 95., 176.2),
+            (Some(1), true, 10., 95., 161.2),
+        ]);
+    }
+
+    #[test]
+    fn invert_matrix() {
+        let m = Matrix { a: 0., b: 2., c: -3., d: 0., e: 5., f: 7. };
+        let p = Point { x: 1.5, y: -4. };
+        let q = m.invert().unwrap().transform_point(m.transform_point(p));
+        assert!((q.x - p.x).abs() < 1e-5 && (q.y - p.y).abs() < 1e-5);
+    }
+}
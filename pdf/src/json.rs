@@ -0,0 +1,358 @@
+//! The object graph as JSON, in the format of `qpdf --json` (version 2).
+//!
+//! [`export`] writes every object with its stream data inline and not decoded, like
+//! `qpdf --json-output --json-stream-data=inline --decode-level=none`. [`import`] reads such a
+//! document, also one written by qpdf, and writes it as a PDF file. Objects are numbered anew on import.
+//!
+//! Values are written as in qpdf: names as `"/Name"`, references as `"5 0 R"`, text strings as
+//! `"u:text"` and other strings as `"b:hex"`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Map, Number, Value};
+
+use crate::error::*;
+use crate::file::{NoCache, NoLog, PromisedRef, Storage, Trailer};
+use crate::object::*;
+use crate::primitive::*;
+
+/// Objects that only make sense in the file they come from.
+fn is_structural(p: &Primitive) -> bool {
+    matches!(p, Primitive::Stream(s) if matches!(s.info.get("Type"), Some(Primitive::Name(n)) if n == "ObjStm" || n == "XRef"))
+}
+
+/// Write `objects` and the `trailer` as a qpdf JSON document.
+///
+/// `version` is the PDF version, like `1.7`. Object streams and cross reference streams are left out.
+pub fn export(resolve: &impl Resolve, objects: impl IntoIterator<Item = PlainRef>, trailer: &Dictionary, version: &str) -> Result<Value> {
+    let mut out = Map::new();
+    let mut max_id = 0;
+    for r in objects {
+        let p = t!(resolve.resolve(r));
+        if is_structural(&p) {
+            continue;
+        }
+        max_id = max_id.max(r.id);
+        let value = match p {
+            Primitive::Stream(ref stream) => {
+                let data = t!(stream.raw_data(resolve));
+                let mut s = Map::new();
+                s.insert("data".into(), Value::String(base64_encode(&data)));
+                s.insert("dict".into(), export_dict(&stream.info));
+                json!({ "stream": s })
+            }
+            ref p => json!({ "value": export_value(p) }),
+        };
+        out.insert(format!("obj:{} {} R", r.id, r.gen), value);
+    }
+    out.insert("trailer".into(), json!({ "value": export_dict(trailer) }));
+
+    Ok(json!({
+        "version": 2,
+        "parameters": {
+            "decodelevel": "none"
+        },
+        "qpdf": [
+            {
+                "jsonversion": 2,
+                "pdfversion": version,
+                "pushedinheritedpageresources": false,
+                "calledgetallpages": false,
+                "maxobjectid": max_id
+            },
+            out
+        ]
+    }))
+}
+
+/// One primitive as a JSON value. Streams can only be objects of their own, so they become `null` here.
+pub fn export_value(p: &Primitive) -> Value {
+    match *p {
+        Primitive::Null | Primitive::Stream(_) => Value::Null,
+        Primitive::Integer(i) => Value::from(i),
+        // through the shortest decimal form, so 0.1 stays 0.1
+        Primitive::Number(n) => n.to_string().parse::<f64>().ok().and_then(Number::from_f64).map_or(Value::Null, Value::Number),
+        Primitive::Boolean(b) => Value::Bool(b),
+        Primitive::String(ref s) => Value::String(export_string(s)),
+        Primitive::Name(ref name) => Value::String(export_name(name)),
+        Primitive::Reference(r) => Value::String(format!("{} {} R", r.id, r.gen)),
+        Primitive::Array(ref parts) => Value::Array(parts.iter().map(export_value).collect()),
+        Primitive::Dictionary(ref dict) => export_dict(dict),
+    }
+}
+
+fn export_dict(dict: &Dictionary) -> Value {
+    Value::Object(dict.iter().map(|(key, val)| (export_name(key), export_value(val))).collect())
+}
+
+/// `u:` if the string is text and reads back to the same bytes, `b:` and hex digits otherwise.
+fn export_string(s: &PdfString) -> String {
+    match s.to_string() {
+        Ok(text) if PdfString::from_text(&text).as_bytes() == s.as_bytes() => format!("u:{}", text),
+        _ => {
+            let mut out = String::from("b:");
+            for b in s.as_bytes() {
+                out.push_str(&format!("{:02x}", b));
+            }
+            out
+        }
+    }
+}
+
+/// In canonical form: bytes outside of `!`..=`~`, delimiters and `#` are written as `#xx`.
+fn export_name(name: &str) -> String {
+    let mut out = String::from("/");
+    for &b in name.as_bytes() {
+        match b {
+            b'!'..=b'~' if !b"()<>[]{}/%#".contains(&b) => out.push(b as char),
+            _ => out.push_str(&format!("#{:02x}", b)),
+        }
+    }
+    out
+}
+
+/// Read a qpdf JSON document and write it as a PDF file.
+///
+/// Stream data has to be inline (`data`); `datafile` is not supported. References to objects
+/// that are not in the document become `null`.
+pub fn import(json: &str) -> Result<Vec<u8>> {
+    let doc: Value = serde_json::from_str(json).map_err(|e| other!("invalid JSON: {}", e))?;
+    if doc.get("version").and_then(Value::as_u64) != Some(2) {
+        bail!("only version 2 of the qpdf JSON format is supported");
+    }
+    let (header, objects) = match doc.get("qpdf").and_then(Value::as_array).map(Vec::as_slice) {
+        Some([header, Value::Object(objects)]) => (header, objects),
+        _ => bail!("the document has no `qpdf` array with objects"),
+    };
+    let version = header.get("pdfversion").and_then(Value::as_str).unwrap_or("1.7");
+
+    // old references, in order
+    let mut old = BTreeMap::new();
+    for (key, value) in objects {
+        if let Some(r) = key.strip_prefix("obj:") {
+            match parse_ref(r) {
+                Some(r) => {
+                    old.insert((r.id, r.gen), value);
+                }
+                None => bail!("invalid object key {:?}", key),
+            }
+        }
+    }
+
+    let mut header = format!("%PDF-{}\n", version).into_bytes();
+    header.extend_from_slice(b"%\xe2\xe3\xcf\xd3\n");
+    let mut out = t!(Storage::with_cache(header, ParseOptions::strict(), NoCache, NoCache, NoLog));
+    let mut promises: Vec<(PlainRef, PromisedRef<Primitive>)> = Vec::with_capacity(old.len());
+    for &(id, gen) in old.keys() {
+        promises.push((PlainRef { id, gen }, out.promise()));
+    }
+    let refs = promises.iter().map(|(old, new)| (*old, new.get_inner())).collect();
+    let importer = Importer { refs };
+
+    for (r, promise) in promises {
+        let value = old[&(r.id, r.gen)];
+        let p = t!(importer.object(value));
+        t!(out.fulfill(promise, p));
+    }
+
+    let trailer = match objects.get("trailer").and_then(|t| t.get("value")) {
+        Some(trailer) => t!(importer.value(trailer).and_then(Primitive::into_dictionary)),
+        None => bail!("the document has no trailer"),
+    };
+    let root = match trailer.get("Root") {
+        Some(&Primitive::Reference(r)) => r,
+        _ => bail!("the trailer has no catalog"),
+    };
+    let resolve = out.resolver();
+    let info_dict = match trailer.get("Info") {
+        Some(info) => Some(t!(InfoDict::from_primitive(info.clone(), &resolve))),
+        None => None,
+    };
+    let id = match trailer.get("ID") {
+        Some(Primitive::Array(parts)) => parts.iter().filter_map(|p| p.as_string().ok().cloned()).collect(),
+        _ => vec![],
+    };
+    let root = t!(resolve.get(Ref::new(root)));
+    drop(resolve);
+
+    let mut trailer = Trailer {
+        size: 0,
+        prev_trailer_pos: None,
+        root,
+        encrypt_dict: None,
+        info_dict,
+        id,
+    };
+    t!(out.save(&mut trailer));
+    Ok(out.into_inner())
+}
+
+fn parse_ref(s: &str) -> Option<PlainRef> {
+    let mut parts = s.split(' ');
+    let (id, gen) = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+    match (parts.next(), parts.next()) {
+        (Some("R"), None) => Some(PlainRef { id, gen }),
+        _ => None,
+    }
+}
+
+struct Importer {
+    // old -> new
+    refs: HashMap<PlainRef, PlainRef>,
+}
+impl Importer {
+    fn object(&self, value: &Value) -> Result<Primitive> {
+        if let Some(value) = value.get("value") {
+            return self.value(value);
+        }
+        let stream = match value.get("stream") {
+            Some(stream) => stream,
+            None => bail!("an object needs a `value` or a `stream`"),
+        };
+        let mut info = match stream.get("dict") {
+            Some(dict) => t!(self.value(dict).and_then(Primitive::into_dictionary)),
+            None => Dictionary::new(),
+        };
+        let data = match stream.get("data") {
+            Some(Value::String(data)) => t!(base64_decode(data)),
+            _ if stream.get("datafile").is_some() => bail!("stream data in files (`datafile`) is not supported"),
+            _ => bail!("a stream needs `data`"),
+        };
+        info.insert("Length", Primitive::Integer(data.len() as i32));
+        Ok(Primitive::Stream(PdfStream { info, inner: StreamInner::Pending { data: data.into() } }))
+    }
+
+    fn value(&self, value: &Value) -> Result<Primitive> {
+        Ok(match *value {
+            Value::Null => Primitive::Null,
+            Value::Bool(b) => Primitive::Boolean(b),
+            Value::Number(ref n) => match n.as_i64().map(i32::try_from) {
+                Some(Ok(i)) => Primitive::Integer(i),
+                _ => Primitive::Number(n.as_f64().unwrap_or_default() as f32),
+            },
+            Value::String(ref s) => t!(self.string(s)),
+            Value::Array(ref parts) => Primitive::Array(parts.iter().map(|v| self.value(v)).collect::<Result<_>>()?),
+            Value::Object(ref map) => {
+                let mut dict = Dictionary::new();
+                for (key, val) in map {
+                    let key = match key.strip_prefix('/') {
+                        Some(key) => t!(import_name(key)),
+                        None => bail!("dictionary key {:?} is not a name", key),
+                    };
+                    dict.insert(key, t!(self.value(val)));
+                }
+                Primitive::Dictionary(dict)
+            }
+        })
+    }
+
+    fn string(&self, s: &str) -> Result<Primitive> {
+        if let Some(name) = s.strip_prefix('/') {
+            Ok(Primitive::Name(t!(import_name(name)).0))
+        } else if let Some(name) = s.strip_prefix("n:/") {
+            Ok(Primitive::Name(t!(import_name(name)).0))
+        } else if let Some(text) = s.strip_prefix("u:") {
+            Ok(Primitive::String(PdfString::from_text(text)))
+        } else if let Some(hex) = s.strip_prefix("b:") {
+            Ok(Primitive::String(PdfString::new(t!(crate::enc::decode_hex(hex.as_bytes())).as_slice().into())))
+        } else if let Some(r) = parse_ref(s) {
+            Ok(self.refs.get(&r).map_or(Primitive::Null, |&r| Primitive::Reference(r)))
+        } else {
+            bail!("unknown string value {:?}", s)
+        }
+    }
+}
+
+fn import_name(name: &str) -> Result<Name> {
+    if !name.contains('#') {
+        return Ok(name.into());
+    }
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some(idx) = rest.iter().position(|&b| b == b'#') {
+        bytes.extend_from_slice(&rest[..idx]);
+        let hex = rest.get(idx + 1..idx + 3).ok_or(PdfError::EOF)?;
+        bytes.extend(t!(crate::enc::decode_hex(hex)));
+        rest = &rest[idx + 3..];
+    }
+    bytes.extend_from_slice(rest);
+    Ok(String::from_utf8_lossy(&bytes).into_owned().into())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let v = match c {
+            b'=' => break,
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            c => match BASE64.iter().position(|&b| b == c) {
+                Some(v) => v as u32,
+                None => bail!("invalid base64 character {:?}", c as char),
+            },
+        };
+        n = n << 6 | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let mut dict = Dictionary::new();
+        dict.insert("Type", Primitive::name("Test"));
+        dict.insert("A B", Primitive::Number(0.1));
+        dict.insert("Text", PdfString::from_text("héllo"));
+        dict.insert("Bytes", PdfString::new([0x7f, 0xad].as_slice().into()));
+        dict.insert("Ref", Primitive::Reference(PlainRef { id: 3, gen: 0 }));
+        let value = export_dict(&dict);
+        assert_eq!(value, json!({
+            "/Type": "/Test",
+            "/A#20B": 0.1,
+            "/Text": "u:héllo",
+            "/Bytes": "b:7fad",
+            "/Ref": "3 0 R"
+        }));
+
+        let mut refs = HashMap::new();
+        refs.insert(PlainRef { id: 3, gen: 0 }, PlainRef { id: 1, gen: 0 });
+        let back = Importer { refs }.value(&value).unwrap().into_dictionary().unwrap();
+        assert_eq!(back.get("A B"), Some(&Primitive::Number(0.1)));
+        assert_eq!(back.get("Ref"), Some(&Primitive::Reference(PlainRef { id: 1, gen: 0 })));
+        assert_eq!(back.get("Text"), dict.get("Text"));
+        assert_eq!(back.get("Bytes"), dict.get("Bytes"));
+    }
+
+    #[test]
+    fn base64() {
+        for data in [&b""[..], b"a", b"ab", b"abc", b"\x00\xff\x10\x80"] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+        }
+        assert_eq!(base64_encode(b"abcd"), "YWJjZA==");
+    }
+}
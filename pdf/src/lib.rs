@@ -17,9 +17,34 @@ pub mod backend;
 pub mod content;
 pub mod parser;
 pub mod font;
+pub mod glyph;
+pub mod geom;
 pub mod any;
 pub mod encoding;
+pub mod cmap;
 pub mod build;
+pub mod xmp;
+mod xml;
+pub mod text;
+pub mod interpret;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod svg;
+pub mod redact;
+pub mod repair;
+pub mod visit;
+pub mod fdf;
+pub mod xfa;
+pub mod sanitize;
+pub mod verify;
+pub mod mdp;
+pub mod diff;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "async")]
+pub mod async_file;
+#[cfg(feature = "icc")]
+pub mod icc;
 
 // mod content;
 pub mod enc;
@@ -27,3 +52,8 @@ pub mod crypt;
 
 // pub use content::*;
 pub use crate::error::PdfError;
+
+// used by the `Serialize` impls from pdf_derive
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
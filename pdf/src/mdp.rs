@@ -0,0 +1,256 @@
+//! Checking the changes made to a certified document, see [`File::check_certification`](crate::file::File::check_certification).
+//!
+//! The certified revision is the part of the file covered by the `DocMDP` signature. Every object
+//! that the incremental updates after it add, change or free is classified by what it is, and
+//! compared with the changes the certification level ([`MdpPermission`]) allows.
+//!
+//! This looks at objects one by one, like a viewer would to warn about changes. It does not check
+//! the signature itself, nor whether a change really is what its objects look like (a form
+//! field changed through its widget is a form field change, whatever the new appearance shows).
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Dictionary, Primitive};
+
+/// What a changed object is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModificationKind {
+    /// cross-reference and object streams, and the document information dictionary
+    Structure,
+    /// a form field, its widget or the form dictionary
+    FormFill,
+    /// a form XObject, like the new appearance of a filled field
+    Appearance,
+    /// a signature, its field, or the document security store (`DSS`)
+    Signature,
+    /// an annotation other than a widget, or the list of annotations of a page
+    Annotation,
+    /// anything else, like the pages or their contents
+    Other,
+}
+
+impl ModificationKind {
+    /// Whether a certification with `permission` allows this kind of change.
+    pub fn allowed_by(self, permission: MdpPermission) -> bool {
+        match self {
+            ModificationKind::Structure => true,
+            ModificationKind::FormFill | ModificationKind::Appearance | ModificationKind::Signature => permission.allows_form_filling(),
+            ModificationKind::Annotation => permission.allows_annotations(),
+            ModificationKind::Other => false,
+        }
+    }
+}
+
+/// One object that was added, changed or freed after the certification.
+#[derive(Debug, Clone)]
+pub struct Modification {
+    /// the object as it is now, or as it was if it was freed
+    pub id: PlainRef,
+    /// whether it was in the certified revision
+    pub existed: bool,
+    /// whether it is still in use
+    pub exists: bool,
+    pub kind: ModificationKind,
+}
+
+/// The result of [`File::check_certification`](crate::file::File::check_certification).
+#[derive(Debug, Clone)]
+pub struct CertificationCheck {
+    /// the changes the certification allows
+    pub permission: MdpPermission,
+    /// the length of the certified revision, in bytes
+    pub signed_len: usize,
+    /// the objects changed after the certification, by object number
+    pub modifications: Vec<Modification>,
+}
+
+impl CertificationCheck {
+    /// The modifications that the certification does not allow.
+    pub fn violations(&self) -> impl Iterator<Item = &Modification> + '_ {
+        self.modifications.iter().filter(move |m| !m.kind.allowed_by(self.permission))
+    }
+
+    /// Whether all changes after the certification are allowed.
+    pub fn is_allowed(&self) -> bool {
+        self.violations().next().is_none()
+    }
+}
+
+/// The end of the part of the file covered by `sig`.
+pub(crate) fn signed_len(sig: &SignatureDictionary) -> Result<usize> {
+    match sig.byte_range[..] {
+        [_, _, start, len] => start.checked_add(len).ok_or(PdfError::Invalid),
+        _ => bail!("signature byte range with {} numbers", sig.byte_range.len()),
+    }
+}
+
+/// Classify the change of an object from `old` to `new` (`None` if it is not in use),
+/// with the resolvers of the certified and the current version.
+pub(crate) fn classify(old: Option<&Primitive>, new: Option<&Primitive>, old_resolve: &impl Resolve, resolve: &impl Resolve) -> ModificationKind {
+    let kinds = (old.map(kind_of), new.map(kind_of));
+    match kinds {
+        (Some(a), Some(b)) if a == b && a != ModificationKind::Other => a,
+        (None, Some(kind)) | (Some(kind), None) if kind != ModificationKind::Other => kind,
+        _ => match (old, new) {
+            (Some(old), Some(new)) => classify_container(old, new, old_resolve, resolve),
+            _ => ModificationKind::Other,
+        },
+    }
+}
+
+const INFO_KEYS: &[&str] = &["Title", "Author", "Subject", "Keywords", "Creator", "Producer", "CreationDate", "ModDate", "Trapped"];
+
+fn dict(p: &Primitive) -> Option<&Dictionary> {
+    match *p {
+        Primitive::Dictionary(ref dict) => Some(dict),
+        Primitive::Stream(ref stream) => Some(&stream.info),
+        _ => None,
+    }
+}
+
+fn kind_of(p: &Primitive) -> ModificationKind {
+    let dict = match dict(p) {
+        Some(dict) => dict,
+        None => return ModificationKind::Other,
+    };
+    let name = |key| dict.get(key).and_then(|p| p.as_name().ok());
+    let is_field = dict.contains_key("FT") || (dict.contains_key("T") && (dict.contains_key("Parent") || dict.contains_key("Kids")));
+    match (name("Type"), name("Subtype")) {
+        (Some("XRef" | "ObjStm"), _) => ModificationKind::Structure,
+        (Some("Sig" | "DocTimeStamp" | "DSS"), _) => ModificationKind::Signature,
+        _ if name("FT") == Some("Sig") => ModificationKind::Signature,
+        (_, Some("Widget")) => ModificationKind::FormFill,
+        _ if is_field => ModificationKind::FormFill,
+        (Some("Annot"), _) => ModificationKind::Annotation,
+        // markup annotations often leave out the type
+        (None, Some(_)) if dict.contains_key("Rect") => ModificationKind::Annotation,
+        (Some("XObject") | None, Some("Form")) if matches!(p, Primitive::Stream(_)) => ModificationKind::Appearance,
+        // the document information dictionary, which is updated on every save
+        (None, None) if dict.contains_key("ModDate") && dict.iter().all(|(k, _)| INFO_KEYS.contains(&k.as_str())) => ModificationKind::Structure,
+        _ => ModificationKind::Other,
+    }
+}
+
+/// An object that was not changed by itself, but in what it lists: a page or an array of annotations,
+/// the form or the catalog.
+fn classify_container(old: &Primitive, new: &Primitive, old_resolve: &impl Resolve, resolve: &impl Resolve) -> ModificationKind {
+    if let (Primitive::Array(old), Primitive::Array(new)) = (old, new) {
+        // the `Annots` of a page can be an object of its own
+        let is_annot = |p: &Primitive| match p.clone().resolve(resolve) {
+            Ok(p) => matches!(kind_of(&p), ModificationKind::Annotation | ModificationKind::FormFill | ModificationKind::Signature),
+            Err(_) => false,
+        };
+        if new.iter().all(is_annot) {
+            return classify_annots(old, new, resolve);
+        }
+        return ModificationKind::Other;
+    }
+    let (old, new) = match (dict(old), dict(new)) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return ModificationKind::Other,
+    };
+    // entries that were made indirect, or written with their default value, are the same
+    let same = |key: &str| match (old.get(key), new.get(key)) {
+        (Some(a), Some(b)) if a == b => true,
+        (Some(a), Some(b)) => matches!((a.clone().resolve(old_resolve), b.clone().resolve(resolve)), (Ok(a), Ok(b)) if a == b),
+        (None, Some(Primitive::Integer(0))) => key == "Rotate",
+        _ => false,
+    };
+    let mut changed: Vec<&str> = new.iter().map(|(k, _)| k.as_str()).filter(|k| !same(k)).collect();
+    changed.extend(old.iter().map(|(k, _)| k.as_str()).filter(|&k| !new.contains_key(k)));
+    let only = |keys: &[&str]| changed.iter().all(|k| keys.contains(k));
+    match new.get("Type").and_then(|p| p.as_name().ok()) {
+        Some("Page") if only(&["Annots"]) => {
+            let list = |d: &Dictionary| match d.get("Annots") {
+                Some(Primitive::Array(a)) => a.clone(),
+                _ => vec![],
+            };
+            classify_annots(&list(old), &list(new), resolve)
+        }
+        Some("Catalog") if only(&["DSS"]) => ModificationKind::Signature,
+        Some("Catalog") if only(&["AcroForm", "DSS"]) => ModificationKind::FormFill,
+        _ if new.contains_key("Fields") && only(&["Fields", "NeedAppearances", "SigFlags", "DR", "DA"]) => ModificationKind::FormFill,
+        _ => ModificationKind::Other,
+    }
+}
+
+/// Added objects that are nothing by themselves, like the resources of a new appearance, are what
+/// the modified objects that refer to them are. `new` are the objects of `modifications` as they are now.
+pub(crate) fn inherit_kinds(modifications: &mut [Modification], new: &[Option<Primitive>]) {
+    fn refers_to(p: &Primitive, id: ObjNr) -> bool {
+        match *p {
+            Primitive::Reference(r) => r.id == id,
+            Primitive::Array(ref a) => a.iter().any(|p| refers_to(p, id)),
+            Primitive::Dictionary(ref d) => d.values().any(|p| refers_to(p, id)),
+            Primitive::Stream(ref s) => s.info.values().any(|p| refers_to(p, id)),
+            _ => false,
+        }
+    }
+    // repeat for chains of new objects, like a font of the resources
+    loop {
+        let mut changed = false;
+        for i in 0 .. modifications.len() {
+            if modifications[i].kind != ModificationKind::Other || modifications[i].existed {
+                continue;
+            }
+            let id = modifications[i].id.id;
+            let referrer = modifications.iter().zip(new)
+                .find(|(m, p)| m.kind != ModificationKind::Other && p.as_ref().is_some_and(|p| refers_to(p, id)));
+            if let Some((m, _)) = referrer {
+                modifications[i].kind = m.kind;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+/// A list of annotations changed from `old` to `new`: adding widgets (of new signature fields) is
+/// allowed along with forms, adding or removing other annotations only along with annotations.
+fn classify_annots(old: &[Primitive], new: &[Primitive], resolve: &impl Resolve) -> ModificationKind {
+    let removed = old.iter().any(|p| !new.contains(p));
+    let added: Vec<_> = new.iter().filter(|p| !old.contains(p)).collect();
+    let widget = |p: &Primitive| match p.clone().resolve(resolve) {
+        Ok(p) => matches!(kind_of(&p), ModificationKind::FormFill | ModificationKind::Signature),
+        Err(_) => false,
+    };
+    if !removed && added.iter().all(|&p| widget(p)) {
+        ModificationKind::FormFill
+    } else {
+        ModificationKind::Annotation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    fn p(s: &str) -> Primitive {
+        parse(s.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap()
+    }
+
+    #[test]
+    fn kinds() {
+        let text = p("<< /Subtype /Text /Rect [0 0 10 10] >>");
+        assert_eq!(classify(None, Some(&text), &NoResolve, &NoResolve), ModificationKind::Annotation);
+        let field = p("<< /FT /Tx /T (name) /V (Jane) >>");
+        assert_eq!(classify(Some(&p("<< /FT /Tx /T (name) >>")), Some(&field), &NoResolve, &NoResolve), ModificationKind::FormFill);
+        let sig = p("<< /FT /Sig /T (sig) /Subtype /Widget /Rect [0 0 0 0] >>");
+        assert_eq!(classify(None, Some(&sig), &NoResolve, &NoResolve), ModificationKind::Signature);
+
+        let page = p("<< /Type /Page /Annots [<< /Subtype /Widget /FT /Sig >>] >>");
+        assert_eq!(classify(Some(&p("<< /Type /Page >>")), Some(&page), &NoResolve, &NoResolve), ModificationKind::FormFill);
+        let page = p("<< /Type /Page /Annots [<< /Subtype /Text /Rect [0 0 1 1] >>] >>");
+        assert_eq!(classify(Some(&p("<< /Type /Page >>")), Some(&page), &NoResolve, &NoResolve), ModificationKind::Annotation);
+        let page = p("<< /Type /Page /Contents 4 0 R >>");
+        assert_eq!(classify(Some(&p("<< /Type /Page /Contents 3 0 R >>")), Some(&page), &NoResolve, &NoResolve), ModificationKind::Other);
+
+        assert!(ModificationKind::Annotation.allowed_by(MdpPermission::Annotate));
+        assert!(!ModificationKind::Annotation.allowed_by(MdpPermission::FillForms));
+        assert!(!ModificationKind::Signature.allowed_by(MdpPermission::NoChanges));
+        assert!(ModificationKind::Structure.allowed_by(MdpPermission::NoChanges));
+    }
+}
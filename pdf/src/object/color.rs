@@ -18,15 +18,62 @@ pub struct IccInfo {
     pub metadata: Option<Stream<()>>,
 }
 
+/// Parameters of a `CalGray` color space (PDF32000 8.6.5.2).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct CalGray {
+    #[pdf(key="WhitePoint")]
+    pub white_point: Vec<f32>,
+
+    #[pdf(key="BlackPoint")]
+    pub black_point: Option<Vec<f32>>,
+
+    #[pdf(key="Gamma", default="1.")]
+    pub gamma: f32,
+}
+
+/// Parameters of a `CalRGB` color space (PDF32000 8.6.5.3).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct CalRGB {
+    #[pdf(key="WhitePoint")]
+    pub white_point: Vec<f32>,
+
+    #[pdf(key="BlackPoint")]
+    pub black_point: Option<Vec<f32>>,
+
+    /// gamma of the three components
+    #[pdf(key="Gamma")]
+    pub gamma: Option<Vec<f32>>,
+
+    /// the XYZ of the three components at full intensity, `[XA YA ZA XB YB ZB XC YC ZC]`
+    #[pdf(key="Matrix")]
+    pub matrix: Option<Vec<f32>>,
+}
+
+/// Parameters of a `Lab` color space (PDF32000 8.6.5.4).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct Lab {
+    #[pdf(key="WhitePoint")]
+    pub white_point: Vec<f32>,
+
+    #[pdf(key="BlackPoint")]
+    pub black_point: Option<Vec<f32>>,
+
+    /// `[amin amax bmin bmax]`
+    #[pdf(key="Range")]
+    pub range: Option<Vec<f32>>,
+}
+
 #[derive(Debug, Clone, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ColorSpace {
     DeviceGray,
     DeviceRGB,
     DeviceCMYK,
     DeviceN { names: Vec<Name>, alt: Box<ColorSpace>, tint: Function, attr: Option<Dictionary> },
-    CalGray(Dictionary),
-    CalRGB(Dictionary),
+    CalGray(CalGray),
+    CalRGB(CalRGB),
     CalCMYK(Dictionary),
+    Lab(Lab),
     Indexed(Box<ColorSpace>, u8, Arc<[u8]>),
     Separation(Name, Box<ColorSpace>, Function),
     Icc(RcRef<Stream<IccInfo>>),
@@ -48,9 +95,10 @@ impl DataSize for ColorSpace {
                 tint.estimate_heap_size() +
                 attr.estimate_heap_size()
             }
-            ColorSpace::CalGray(ref d) | ColorSpace::CalRGB(ref d) | ColorSpace::CalCMYK(ref d) => {
-                d.estimate_heap_size()
-            }
+            ColorSpace::CalGray(ref p) => p.estimate_heap_size(),
+            ColorSpace::CalRGB(ref p) => p.estimate_heap_size(),
+            ColorSpace::CalCMYK(ref d) => d.estimate_heap_size(),
+            ColorSpace::Lab(ref p) => p.estimate_heap_size(),
             ColorSpace::Indexed(ref cs, _, ref data) => {
                 cs.estimate_heap_size() + data.estimate_heap_size()
             }
@@ -138,29 +186,242 @@ impl ColorSpace {
                 Ok(ColorSpace::DeviceN { names, alt, tint, attr})
             }
             "CalGray" => {
-                let dict = Dictionary::from_primitive(t!(get_index(&arr, 1)).clone(), resolve)?;
-                Ok(ColorSpace::CalGray(dict))
+                let params = t!(CalGray::from_primitive(t!(get_index(&arr, 1)).clone(), resolve));
+                Ok(ColorSpace::CalGray(params))
             }
             "CalRGB" => {
-                let dict = Dictionary::from_primitive(t!(get_index(&arr, 1)).clone(), resolve)?;
-                Ok(ColorSpace::CalRGB(dict))
+                let params = t!(CalRGB::from_primitive(t!(get_index(&arr, 1)).clone(), resolve));
+                Ok(ColorSpace::CalRGB(params))
             }
             "CalCMYK" => {
                 let dict = Dictionary::from_primitive(t!(get_index(&arr, 1)).clone(), resolve)?;
                 Ok(ColorSpace::CalCMYK(dict))
             }
+            "Lab" => {
+                let params = t!(Lab::from_primitive(t!(get_index(&arr, 1)).clone(), resolve));
+                Ok(ColorSpace::Lab(params))
+            }
             "Pattern" => {
                 Ok(ColorSpace::Pattern)
             }
             _ => Ok(ColorSpace::Other(arr))
         }
     }
+
+    /// Parse the ICC profile of an `ICCBased` color space.
+    ///
+    /// Returns `None` for other color spaces.
+    #[cfg(feature = "icc")]
+    pub fn icc_profile(&self, resolve: &impl Resolve) -> Result<Option<crate::icc::IccProfile>> {
+        match *self {
+            ColorSpace::Icc(ref stream) => {
+                let data = t!((**stream).data(resolve));
+                Ok(Some(t!(crate::icc::IccProfile::parse(&data))))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The number of color components, or `None` if it depends on something that isn't known here.
+    pub fn components(&self) -> Option<usize> {
+        match *self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => Some(1),
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) | ColorSpace::Lab(_) => Some(3),
+            ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => Some(4),
+            ColorSpace::DeviceN { ref names, .. } => Some(names.len()),
+            ColorSpace::Indexed(..) | ColorSpace::Separation(..) => Some(1),
+            ColorSpace::Icc(ref stream) => Some(stream.info.info.components as usize),
+            ColorSpace::Pattern | ColorSpace::Named(_) | ColorSpace::Other(_) => None,
+        }
+    }
+
+    /// Convert a color in this color space to sRGB.
+    ///
+    /// `components` are given as they appear in the content stream (an index for `Indexed`,
+    /// tints for `Separation` and `DeviceN`, L*a*b* values for `Lab`, 0..1 for everything else).
+    /// `DeviceCMYK` uses the naive conversion without black generation.
+    /// With the `icc` feature, `ICCBased` colors are converted through the profile,
+    /// otherwise through the alternate color space.
+    pub fn to_rgb(&self, components: &[f32], resolve: &impl Resolve) -> Result<[f32; 3]> {
+        self.to_rgb_depth(components, resolve, 5)
+    }
+    fn to_rgb_depth(&self, components: &[f32], resolve: &impl Resolve, depth: usize) -> Result<[f32; 3]> {
+        if depth == 0 {
+            bail!("ColorSpace base recursion");
+        }
+        let c = |i: usize| components.get(i).cloned().unwrap_or(0.).clamp(0., 1.);
+        match *self {
+            ColorSpace::DeviceGray => Ok([c(0); 3]),
+            ColorSpace::DeviceRGB => Ok([c(0), c(1), c(2)]),
+            ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => {
+                let k = 1. - c(3);
+                Ok([(1. - c(0)) * k, (1. - c(1)) * k, (1. - c(2)) * k])
+            }
+            ColorSpace::CalGray(ref p) => {
+                let y = c(0).powf(p.gamma);
+                let white = white_point(&p.white_point)?;
+                Ok(xyz_to_srgb(adapt_to_d50([white[0] * y, white[1] * y, white[2] * y], white)))
+            }
+            ColorSpace::CalRGB(ref p) => {
+                let white = white_point(&p.white_point)?;
+                let gamma = |i: usize| p.gamma.as_ref().and_then(|g| g.get(i)).cloned().unwrap_or(1.);
+                let abc = [c(0).powf(gamma(0)), c(1).powf(gamma(1)), c(2).powf(gamma(2))];
+                const IDENTITY: [f32; 9] = [1., 0., 0., 0., 1., 0., 0., 0., 1.];
+                let m = |i: usize| p.matrix.as_ref().and_then(|m| m.get(i)).cloned().unwrap_or(IDENTITY[i]);
+                let mut xyz = [0.; 3];
+                for (j, out) in xyz.iter_mut().enumerate() {
+                    *out = m(j) * abc[0] + m(3 + j) * abc[1] + m(6 + j) * abc[2];
+                }
+                Ok(xyz_to_srgb(adapt_to_d50(xyz, white)))
+            }
+            ColorSpace::Lab(ref p) => {
+                let white = white_point(&p.white_point)?;
+                let range = |i: usize, default: f32| p.range.as_ref().and_then(|r| r.get(i)).cloned().unwrap_or(default);
+                let v = |i: usize| components.get(i).cloned().unwrap_or(0.);
+                let l = v(0).clamp(0., 100.);
+                let a = v(1).clamp(range(0, -100.), range(1, 100.));
+                let b = v(2).clamp(range(2, -100.), range(3, 100.));
+                Ok(xyz_to_srgb(adapt_to_d50(lab_to_xyz_white(l, a, b, white), white)))
+            }
+            ColorSpace::Indexed(ref base, hival, ref lookup) => {
+                let n = try_opt!(base.components());
+                let index = (components.first().cloned().unwrap_or(0.).round().max(0.) as usize).min(hival as usize);
+                let entry = lookup.get(index * n .. (index + 1) * n)
+                    .ok_or(PdfError::Bounds { index: (index + 1) * n, len: lookup.len() })?;
+                let base_components: Vec<f32> = match **base {
+                    ColorSpace::Lab(ref p) => {
+                        // lookup values are mapped linearly onto the range of each component
+                        let range = [0., 100., -100., 100., -100., 100.];
+                        entry.iter().enumerate().map(|(i, &b)| {
+                            let min = p.range.as_ref().filter(|_| i > 0).and_then(|r| r.get(2 * i - 2)).cloned().unwrap_or(range[2 * i]);
+                            let max = p.range.as_ref().filter(|_| i > 0).and_then(|r| r.get(2 * i - 1)).cloned().unwrap_or(range[2 * i + 1]);
+                            min + b as f32 / 255. * (max - min)
+                        }).collect()
+                    }
+                    _ => entry.iter().map(|&b| b as f32 / 255.).collect(),
+                };
+                base.to_rgb_depth(&base_components, resolve, depth - 1)
+            }
+            ColorSpace::Separation(ref name, ref alt, ref tint) => {
+                if name == "None" {
+                    bail!("the None separation is never painted");
+                }
+                let mut out = vec![0.; try_opt!(alt.components())];
+                t!(tint.apply(&[c(0)], &mut out));
+                alt.to_rgb_depth(&out, resolve, depth - 1)
+            }
+            ColorSpace::DeviceN { ref names, ref alt, ref tint, .. } => {
+                let input: Vec<f32> = (0..names.len()).map(c).collect();
+                let mut out = vec![0.; try_opt!(alt.components())];
+                t!(tint.apply(&input, &mut out));
+                alt.to_rgb_depth(&out, resolve, depth - 1)
+            }
+            ColorSpace::Icc(ref stream) => {
+                #[cfg(feature = "icc")]
+                {
+                    match self.icc_profile(resolve) {
+                        Ok(Some(profile)) => return Ok(profile.to_srgb(components)),
+                        Ok(None) => {}
+                        Err(e) => warn!("ICC profile can't be used: {}", e),
+                    }
+                }
+                match stream.info.info.alternate {
+                    Some(ref alt) => alt.to_rgb_depth(components, resolve, depth - 1),
+                    None => match stream.info.info.components {
+                        1 => ColorSpace::DeviceGray.to_rgb(components, resolve),
+                        3 => ColorSpace::DeviceRGB.to_rgb(components, resolve),
+                        4 => ColorSpace::DeviceCMYK.to_rgb(components, resolve),
+                        n => bail!("ICCBased color space with {} components", n),
+                    },
+                }
+            }
+            ColorSpace::Pattern => bail!("a Pattern color space has no color values"),
+            ColorSpace::Named(ref name) => bail!("color space {} needs to be looked up in the resources", name),
+            ColorSpace::Other(ref arr) => Err(PdfError::Unsupported { feature: format!("color space {:?}", arr.first()) }),
+        }
+    }
 }
+
+/// The D50 white point of the ICC profile connection space.
+pub const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+fn white_point(p: &[f32]) -> Result<[f32; 3]> {
+    match *p {
+        [x, y, z] if y > 0. => Ok([x, y, z]),
+        _ => bail!("invalid WhitePoint {:?}", p),
+    }
+}
+
+/// Bradford chromatic adaptation of `xyz` from `white` to D50.
+fn adapt_to_d50(xyz: [f32; 3], white: [f32; 3]) -> [f32; 3] {
+    const M: [[f32; 3]; 3] = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    const M_INV: [[f32; 3]; 3] = [
+        [0.986_993, -0.147_054_3, 0.159_962_7],
+        [0.432_305_3, 0.518_360_3, 0.049_291_2],
+        [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+    ];
+    let mul = |m: &[[f32; 3]; 3], v: [f32; 3]| {
+        let mut out = [0.; 3];
+        for (row, o) in m.iter().zip(out.iter_mut()) {
+            *o = row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+        }
+        out
+    };
+    let src = mul(&M, white);
+    let dst = mul(&M, D50);
+    let cone = mul(&M, xyz);
+    mul(&M_INV, [
+        cone[0] * dst[0] / src[0],
+        cone[1] * dst[1] / src[1],
+        cone[2] * dst[2] / src[2],
+    ])
+}
+
+fn lab_to_xyz_white(l: f32, a: f32, b: f32, white: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.) / 116.;
+    let fx = fy + a / 500.;
+    let fz = fy - b / 200.;
+    let f_inv = |t: f32| {
+        if t > 6. / 29. { t * t * t } else { 3. * (6f32 / 29.).powi(2) * (t - 4. / 29.) }
+    };
+    [white[0] * f_inv(fx), white[1] * f_inv(fy), white[2] * f_inv(fz)]
+}
+
+/// CIE L*a*b* (D50) to XYZ.
+pub fn lab_to_xyz(l: f32, a: f32, b: f32) -> [f32; 3] {
+    lab_to_xyz_white(l, a, b, D50)
+}
+
+/// XYZ relative to D50 to sRGB, using the Bradford adaptation to D65.
+pub fn xyz_to_srgb(xyz: [f32; 3]) -> [f32; 3] {
+    const M: [[f32; 3]; 3] = [
+        [3.133_856, -1.616_866_7, -0.490_614_6],
+        [-0.978_768_4, 1.916_141_5, 0.033_454],
+        [0.071_945_3, -0.228_991_4, 1.405_242_7],
+    ];
+    let gamma = |c: f32| {
+        let c = c.clamp(0., 1.);
+        if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1. / 2.4) - 0.055 }
+    };
+    let mut rgb = [0.; 3];
+    for (row, out) in M.iter().zip(rgb.iter_mut()) {
+        *out = gamma(row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2]);
+    }
+    rgb
+}
+
 impl ObjectWrite for ColorSpace {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
         match *self {
+            ColorSpace::DeviceGray => Ok(Primitive::name("DeviceGray")),
             ColorSpace::DeviceCMYK => Ok(Primitive::name("DeviceCMYK")),
             ColorSpace::DeviceRGB => Ok(Primitive::name("DeviceRGB")),
+            ColorSpace::Pattern => Ok(Primitive::name("Pattern")),
+            ColorSpace::Named(ref name) => name.to_primitive(update),
             ColorSpace::Indexed(ref  base, hival, ref lookup) => {
                 let base = base.to_primitive(update)?;
                 let hival = Primitive::Integer(hival.into());
@@ -171,10 +432,50 @@ impl ObjectWrite for ColorSpace {
                 };
                 Ok(Primitive::Array(vec![Primitive::name("Indexed"), base, hival, lookup]))
             }
-            ref p => {
-                dbg!(p);
-                unimplemented!()
+            ColorSpace::Separation(ref name, ref alt, ref tint) => Ok(Primitive::Array(vec![
+                Primitive::name("Separation"),
+                name.to_primitive(update)?,
+                alt.to_primitive(update)?,
+                tint.to_primitive(update)?,
+            ])),
+            ColorSpace::DeviceN { ref names, ref alt, ref tint, ref attr } => {
+                let mut arr = vec![
+                    Primitive::name("DeviceN"),
+                    names.to_primitive(update)?,
+                    alt.to_primitive(update)?,
+                    tint.to_primitive(update)?,
+                ];
+                if let Some(ref attr) = *attr {
+                    arr.push(attr.to_primitive(update)?);
+                }
+                Ok(Primitive::Array(arr))
             }
+            ColorSpace::CalGray(ref p) => Ok(Primitive::Array(vec![Primitive::name("CalGray"), p.to_primitive(update)?])),
+            ColorSpace::CalRGB(ref p) => Ok(Primitive::Array(vec![Primitive::name("CalRGB"), p.to_primitive(update)?])),
+            ColorSpace::CalCMYK(ref d) => Ok(Primitive::Array(vec![Primitive::name("CalCMYK"), d.to_primitive(update)?])),
+            ColorSpace::Lab(ref p) => Ok(Primitive::Array(vec![Primitive::name("Lab"), p.to_primitive(update)?])),
+            ColorSpace::Icc(ref stream) => Ok(Primitive::Array(vec![Primitive::name("ICCBased"), stream.to_primitive(update)?])),
+            ColorSpace::Other(ref arr) => Ok(Primitive::Array(arr.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_to_rgb() {
+        let cs = ColorSpace::Indexed(Box::new(ColorSpace::DeviceRGB), 1, vec![0, 0, 0, 255, 0, 255].into());
+        assert_eq!(cs.to_rgb(&[1.], &NoResolve).unwrap(), [1., 0., 1.]);
+        assert_eq!(cs.to_rgb(&[7.], &NoResolve).unwrap(), [1., 0., 1.]);
+    }
+
+    #[test]
+    fn lab_white_to_rgb() {
+        let cs = ColorSpace::Lab(Lab { white_point: vec![0.9505, 1.0, 1.089], black_point: None, range: None });
+        for c in cs.to_rgb(&[100., 0., 0.], &NoResolve).unwrap() {
+            assert!((c - 1.).abs() < 1e-2, "{}", c);
         }
     }
 }
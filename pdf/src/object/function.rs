@@ -3,6 +3,7 @@ use crate::error::*;
 use crate::object::*;
 use datasize::DataSize;
 use itertools::izip;
+use std::fmt;
 
 #[derive(Object, Debug, Clone, ObjectWrite)]
 struct RawFunction {
@@ -19,7 +20,7 @@ struct RawFunction {
     size: Option<Vec<u32>>,
 
     #[pdf(key = "BitsPerSample")]
-    _bits_per_sample: Option<u32>,
+    bits_per_sample: Option<u32>,
 
     #[pdf(key = "Order", default = "1")]
     order: u32,
@@ -46,12 +47,29 @@ struct Function2 {
     exponent: f32,
 }
 
+#[derive(Object, Debug, Clone)]
+struct Function3 {
+    #[pdf(key = "Functions")]
+    functions: Vec<Function>,
+
+    #[pdf(key = "Bounds")]
+    bounds: Vec<f32>,
+
+    #[pdf(key = "Encode")]
+    encode: Vec<f32>,
+}
+
+/// A PDF function (PDF32000 7.10).
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Function {
+    /// type 0
     Sampled(SampledFunction),
+    /// type 2, one entry per output
     Interpolated(Vec<InterpolatedFunctionDim>),
-    Stiching,
-    Calculator,
+    /// type 3
+    Stitching(StitchingFunction),
+    /// type 4
     PostScript {
         func: PsFunc,
         domain: Vec<f32>,
@@ -59,6 +77,9 @@ pub enum Function {
     },
 }
 impl Function {
+    /// Evaluate the function for the input `x`, writing the results to `out`.
+    ///
+    /// Inputs are clipped to the domain and outputs to the range of the function.
     pub fn apply(&self, x: &[f32], out: &mut [f32]) -> Result<()> {
         match *self {
             Function::Sampled(ref func) => func.apply(x, out),
@@ -70,34 +91,51 @@ impl Function {
                         out.len()
                     )
                 }
+                let x = *try_opt!(x.first());
                 for (f, y) in parts.iter().zip(out) {
-                    *y = f.apply(x[0]);
+                    *y = f.apply(x);
+                }
+                Ok(())
+            }
+            Function::Stitching(ref func) => func.apply(x, out),
+            Function::PostScript { ref func, ref domain, ref range } => {
+                if x.len() * 2 != domain.len() {
+                    bail!("input dimension mismatch 2 * {} != {}", x.len(), domain.len());
+                }
+                let input: Vec<f32> = x.iter().zip(domain.chunks_exact(2))
+                    .map(|(&x, d)| x.clamp(d[0], d[1]))
+                    .collect();
+                func.exec(&input, out)?;
+                for (y, r) in out.iter_mut().zip(range.chunks_exact(2)) {
+                    *y = y.clamp(r[0], r[1]);
                 }
                 Ok(())
             }
-            Function::PostScript { ref func, .. } => func.exec(x, out),
-            _ => bail!("unimplemted function {:?}", self),
         }
     }
     pub fn input_dim(&self) -> usize {
         match *self {
             Function::PostScript { ref domain, .. } => domain.len() / 2,
             Function::Sampled(ref f) => f.input.len(),
-            _ => panic!(),
+            Function::Interpolated(_) | Function::Stitching(_) => 1,
         }
     }
     pub fn output_dim(&self) -> usize {
         match *self {
             Function::PostScript { ref range, .. } => range.len() / 2,
             Function::Sampled(ref f) => f.output.len(),
-            _ => panic!(),
+            Function::Interpolated(ref parts) => parts.len(),
+            Function::Stitching(ref f) => f.functions.first().map(|f| f.output_dim()).unwrap_or(0),
         }
     }
 }
 impl FromDict for Function {
     fn from_dict(dict: Dictionary, resolve: &impl Resolve) -> Result<Self> {
-        use std::f32::INFINITY;
         let raw = RawFunction::from_dict(dict, resolve)?;
+        let input_range = match raw.domain[..] {
+            [a, b, ..] => (a, b),
+            _ => bail!("function Domain has {} entries", raw.domain.len()),
+        };
         match raw.function_type {
             2 => {
                 let f2 = Function2::from_dict(raw.other, resolve)?;
@@ -106,20 +144,19 @@ impl FromDict for Function {
                     (Some(range), _, _) => range.len() / 2,
                     (_, Some(c0), _) => c0.len(),
                     (_, _, Some(c1)) => c1.len(),
-                    _ => bail!("unknown dimensions"),
+                    _ => 1,
                 };
                 let mut parts = Vec::with_capacity(n_dim);
-                let input_range = (raw.domain[0], raw.domain[1]);
                 for dim in 0..n_dim {
                     let output_range = (
                         raw.range
                             .as_ref()
                             .and_then(|r| r.get(2 * dim).cloned())
-                            .unwrap_or(-INFINITY),
+                            .unwrap_or(f32::NEG_INFINITY),
                         raw.range
                             .as_ref()
                             .and_then(|r| r.get(2 * dim + 1).cloned())
-                            .unwrap_or(INFINITY),
+                            .unwrap_or(f32::INFINITY),
                     );
                     let c0 = f2
                         .c0
@@ -142,10 +179,23 @@ impl FromDict for Function {
                 }
                 Ok(Function::Interpolated(parts))
             }
-            i => {
-                dbg!(raw);
-                bail!("unsupported function type {}", i)
+            3 => {
+                let f3 = Function3::from_dict(raw.other, resolve)?;
+                if f3.functions.is_empty() || f3.bounds.len() + 1 != f3.functions.len() {
+                    bail!("stitching function with {} functions and {} bounds", f3.functions.len(), f3.bounds.len());
+                }
+                if f3.encode.len() != 2 * f3.functions.len() {
+                    bail!("stitching function with {} functions and {} Encode values", f3.functions.len(), f3.encode.len());
+                }
+                Ok(Function::Stitching(StitchingFunction {
+                    domain: input_range,
+                    functions: f3.functions,
+                    bounds: f3.bounds,
+                    encode: f3.encode,
+                    range: raw.range,
+                }))
             }
+            i => bail!("function type {} has to be a stream", i),
         }
     }
 }
@@ -164,7 +214,7 @@ impl Object for Function {
                         Ok(Function::PostScript {
                             func,
                             domain: info.domain,
-                            range: info.range.unwrap(),
+                            range: try_opt!(info.range),
                         })
                     }
                     0 => {
@@ -177,35 +227,50 @@ impl Object for Function {
 
                         let size = try_opt!(info.size);
                         let range = try_opt!(info.range);
+                        let bits_per_sample = try_opt!(info.bits_per_sample);
+                        if !matches!(bits_per_sample, 1 | 2 | 4 | 8 | 12 | 16 | 24 | 32) {
+                            bail!("invalid BitsPerSample {}", bits_per_sample);
+                        }
+                        if size.len() * 2 != info.domain.len() || size.contains(&0) {
+                            bail!("Size {:?} does not match Domain {:?}", size, info.domain);
+                        }
                         let encode = info.encode.unwrap_or_else(|| {
                             size.iter().flat_map(|&n| [0.0, (n - 1) as f32]).collect()
                         });
                         let decode = info.decode.unwrap_or_else(|| range.clone());
+                        let max = ((1u64 << bits_per_sample) - 1) as f32;
 
-                        Ok(Function::Sampled(SampledFunction {
+                        let func = SampledFunction {
                             input: izip!(
                                 info.domain.chunks_exact(2),
                                 encode.chunks_exact(2),
                                 size.iter()
                             )
-                            .map(|(c, e, &s)| SampledFunctionInput {
-                                domain: (c[0], c[1]),
-                                encode_offset: e[0],
-                                encode_scale: e[1],
+                            .map(|(d, e, &s)| SampledFunctionInput {
+                                domain: (d[0], d[1]),
+                                encode: (e[0], e[1]),
                                 size: s as usize,
                             })
                             .collect(),
                             output: decode
                                 .chunks_exact(2)
-                                .map(|c| SampledFunctionOutput {
-                                    offset: c[0],
-                                    scale: (c[1] - c[0]) / 255.,
+                                .zip(range.chunks_exact(2))
+                                .map(|(d, r)| SampledFunctionOutput {
+                                    offset: d[0],
+                                    scale: (d[1] - d[0]) / max,
+                                    range: (r[0], r[1]),
                                 })
                                 .collect(),
+                            bits_per_sample,
                             data,
                             order,
-                            range,
-                        }))
+                        };
+                        let needed = func.input.iter().map(|i| i.size).product::<usize>()
+                            * func.output.len() * bits_per_sample as usize;
+                        if func.data.len() * 8 < needed {
+                            bail!("sampled function needs {} bits of data, found {}", needed, func.data.len() * 8);
+                        }
+                        Ok(Function::Sampled(func))
                     }
                     ref p => bail!("found a function stream with type {:?}", p),
                 }
@@ -215,30 +280,56 @@ impl Object for Function {
         }
     }
 }
+/// Sampled and PostScript functions are streams, which are always written as new indirect objects.
 impl ObjectWrite for Function {
-    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
-        unimplemented!()
-        /*
-        let dict = match self {
-            Function::Interpolated(parts) => {
-                let first: &InterpolatedFunctionDim = try_opt!(parts.get(0));
-                let f2 = Function2 {
-                    c0: parts.iter().map(|p| p.c0).collect(),
-                    c1: parts.iter().map(|p| p.c0).collect(),
-                    exponent: first.exponent
-                };
-                let f = RawFunction {
-                    function_type: 2,
-                    domain: vec![first.input_range.0, first.input_range.1],
-                    range: parts.iter().flat_map(|p| [p.output_range.0, p.output_range.1]).collect(),
-                    decode: None,
-                    encode: None,
-                    order
-                };
-
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        match *self {
+            Function::Sampled(ref f) => {
+                let max = ((1u64 << f.bits_per_sample) - 1) as f32;
+                dict.insert("FunctionType", 0);
+                dict.insert("Domain", f.input.iter().flat_map(|i| [i.domain.0, i.domain.1]).collect::<Vec<_>>().to_primitive(update)?);
+                dict.insert("Range", f.output.iter().flat_map(|o| [o.range.0, o.range.1]).collect::<Vec<_>>().to_primitive(update)?);
+                dict.insert("Size", f.input.iter().map(|i| Primitive::Integer(i.size as i32)).collect::<Vec<_>>());
+                dict.insert("BitsPerSample", f.bits_per_sample as i32);
+                if let Interpolation::Cubic = f.order {
+                    dict.insert("Order", 3);
+                }
+                dict.insert("Encode", f.input.iter().flat_map(|i| [i.encode.0, i.encode.1]).collect::<Vec<_>>().to_primitive(update)?);
+                dict.insert("Decode", f.output.iter().flat_map(|o| [o.offset, o.scale.mul_add(max, o.offset)]).collect::<Vec<_>>().to_primitive(update)?);
+                Ok(Primitive::Reference(update.create(Stream::new(dict, f.data.clone()))?.get_ref().get_inner()))
+            }
+            Function::Interpolated(ref parts) => {
+                let first = try_opt!(parts.first());
+                dict.insert("FunctionType", 2);
+                dict.insert("Domain", vec![first.input_range.0, first.input_range.1].to_primitive(update)?);
+                // a missing Range is read as an infinite one
+                if parts.iter().all(|p| p.output_range.0.is_finite() && p.output_range.1.is_finite()) {
+                    dict.insert("Range", parts.iter().flat_map(|p| [p.output_range.0, p.output_range.1]).collect::<Vec<_>>().to_primitive(update)?);
+                }
+                dict.insert("C0", parts.iter().map(|p| p.c0).collect::<Vec<_>>().to_primitive(update)?);
+                dict.insert("C1", parts.iter().map(|p| p.c1).collect::<Vec<_>>().to_primitive(update)?);
+                dict.insert("N", first.exponent);
+                Ok(Primitive::Dictionary(dict))
+            }
+            Function::Stitching(ref f) => {
+                dict.insert("FunctionType", 3);
+                dict.insert("Domain", vec![f.domain.0, f.domain.1].to_primitive(update)?);
+                if let Some(ref range) = f.range {
+                    dict.insert("Range", range.to_primitive(update)?);
+                }
+                dict.insert("Functions", f.functions.to_primitive(update)?);
+                dict.insert("Bounds", f.bounds.to_primitive(update)?);
+                dict.insert("Encode", f.encode.to_primitive(update)?);
+                Ok(Primitive::Dictionary(dict))
+            }
+            Function::PostScript { ref func, ref domain, ref range } => {
+                dict.insert("FunctionType", 4);
+                dict.insert("Domain", domain.to_primitive(update)?);
+                dict.insert("Range", range.to_primitive(update)?);
+                Ok(Primitive::Reference(update.create(Stream::new(dict, func.to_string().into_bytes()))?.get_ref().get_inner()))
             }
         }
-        */
     }
 }
 impl DeepClone for Function {
@@ -247,48 +338,83 @@ impl DeepClone for Function {
     }
 }
 
+/// Map `x` from `[x0, x1]` to `[y0, y1]`.
+fn interpolate(x: f32, (x0, x1): (f32, f32), (y0, y1): (f32, f32)) -> f32 {
+    if x1 == x0 {
+        y0
+    } else {
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct SampledFunctionInput {
     domain: (f32, f32),
-    encode_offset: f32,
-    encode_scale: f32,
+    encode: (f32, f32),
     size: usize,
 }
 impl SampledFunctionInput {
-    fn map(&self, x: f32) -> (usize, usize, f32) {
+    /// The sample index to the left of `x` and the position between it and the next one.
+    fn map(&self, x: f32) -> (usize, f32) {
         let x = x.clamp(self.domain.0, self.domain.1);
-        let y = x.mul_add(self.encode_scale, self.encode_offset);
-        (y.floor() as usize, self.size, y.fract())
+        let max = (self.size - 1) as f32;
+        let e = interpolate(x, self.domain, self.encode).clamp(0., max);
+        if self.size == 1 {
+            return (0, 0.);
+        }
+        let i = (e.floor() as usize).min(self.size - 2);
+        (i, e - i as f32)
     }
 }
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 struct SampledFunctionOutput {
     offset: f32,
     scale: f32,
+    range: (f32, f32),
 }
 impl SampledFunctionOutput {
     fn map(&self, x: f32) -> f32 {
-        x.mul_add(self.scale, self.offset)
+        x.mul_add(self.scale, self.offset).clamp(self.range.0, self.range.1)
     }
 }
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 enum Interpolation {
     Linear,
-    #[allow(dead_code)] // TODO
+    /// evaluated as `Linear`
     Cubic,
 }
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SampledFunction {
     input: Vec<SampledFunctionInput>,
     output: Vec<SampledFunctionOutput>,
+    bits_per_sample: u32,
     data: Arc<[u8]>,
+    #[allow(dead_code)]
     order: Interpolation,
-    range: Vec<f32>,
 }
 impl SampledFunction {
+    /// Sample number `n`, with the bits of all samples packed most significant first.
+    fn sample(&self, n: usize) -> f32 {
+        let mut bit = n * self.bits_per_sample as usize;
+        let mut left = self.bits_per_sample as usize;
+        let mut value = 0u64;
+        while left > 0 {
+            let offset = bit % 8;
+            let take = (8 - offset).min(left);
+            let bits = (self.data[bit / 8] >> (8 - offset - take)) as u64 & ((1 << take) - 1);
+            value = value << take | bits;
+            bit += take;
+            left -= take;
+        }
+        value as f32
+    }
     fn apply(&self, x: &[f32], out: &mut [f32]) -> Result<()> {
         if x.len() != self.input.len() {
             bail!(
@@ -298,85 +424,49 @@ impl SampledFunction {
             );
         }
         let n_out = out.len();
-        if out.len() * 2 != self.range.len() {
+        if n_out != self.output.len() {
             bail!(
-                "output dimension mismatch 2 * {} != {}",
+                "output dimension mismatch {} != {}",
                 out.len(),
-                self.range.len()
+                self.output.len()
             )
         }
-        match x.len() {
-            1 => match self.order {
-                Interpolation::Linear => {
-                    let (i, _, s) = self.input[0].map(x[0]);
-                    let idx = i * n_out;
-
-                    for (o, &a) in out.iter_mut().zip(&self.data[idx..]) {
-                        *o = a as f32 * (1. - s);
-                    }
-                    for (o, &b) in out.iter_mut().zip(&self.data[idx + n_out..]) {
-                        *o += b as f32 * s;
-                    }
-                }
-                _ => unimplemented!(),
-            },
-            2 => match self.order {
-                Interpolation::Linear => {
-                    let (i0, s0, f0) = self.input[0].map(x[0]);
-                    let (i1, _, f1) = self.input[1].map(x[1]);
-                    let (j0, j1) = (i0 + 1, i1 + 1);
-                    let (g0, g1) = (1. - f0, 1. - f1);
-
-                    out.fill(0.0);
-                    let mut add = |i0, i1, f| {
-                        let idx = (i0 + s0 * i1) * n_out;
-
-                        if let Some(part) = self.data.get(idx..idx + n_out) {
-                            for (o, &b) in out.iter_mut().zip(part) {
-                                *o += f * b as f32;
-                            }
-                        }
-                    };
-
-                    add(i0, i1, g0 * g1);
-                    add(j0, i1, f0 * g1);
-                    add(i0, j1, g0 * f1);
-                    add(j0, j1, f0 * f1);
-                }
-                _ => unimplemented!(),
-            },
-            3 => match self.order {
-                Interpolation::Linear => {
-                    let (i0, s0, f0) = self.input[0].map(x[0]);
-                    let (i1, s1, f1) = self.input[1].map(x[1]);
-                    let (i2, _, f2) = self.input[2].map(x[2]);
-                    let (j0, j1, j2) = (i0 + 1, i1 + 1, i2 + 1);
-                    let (g0, g1, g2) = (1. - f0, 1. - f1, 1. - f2);
-
-                    out.fill(0.0);
-                    let mut add = |i0, i1, i2, f| {
-                        let idx = (i0 + s0 * (i1 + s1 * i2)) * n_out;
-
-                        if let Some(part) = self.data.get(idx..idx + n_out) {
-                            for (o, &b) in out.iter_mut().zip(part) {
-                                *o += f * b as f32;
-                            }
-                        }
-                    };
+        let m = x.len();
+        if m > 16 {
+            bail!("sampled function with {} inputs", m);
+        }
 
-                    add(i0, i1, i2, g0 * g1 * g2);
-                    add(j0, i1, i2, f0 * g1 * g2);
-                    add(i0, j1, i2, g0 * f1 * g2);
-                    add(j0, j1, i2, f0 * f1 * g2);
+        let mut base = 0;
+        let mut stride = 1;
+        let mut strides = Vec::with_capacity(m);
+        let mut fracs = Vec::with_capacity(m);
+        for (input, &x) in self.input.iter().zip(x) {
+            let (i, f) = input.map(x);
+            base += i * stride;
+            strides.push(if input.size > 1 { stride } else { 0 });
+            fracs.push(f);
+            stride *= input.size;
+        }
 
-                    add(i0, i1, j2, g0 * g1 * f2);
-                    add(j0, i1, j2, f0 * g1 * f2);
-                    add(i0, j1, j2, g0 * f1 * f2);
-                    add(j0, j1, j2, f0 * f1 * f2);
+        // multilinear interpolation between the 2^m surrounding samples
+        out.fill(0.0);
+        for corner in 0..1usize << m {
+            let mut weight = 1.;
+            let mut idx = base;
+            for (k, (&s, &f)) in strides.iter().zip(&fracs).enumerate() {
+                if corner & (1 << k) != 0 {
+                    weight *= f;
+                    idx += s;
+                } else {
+                    weight *= 1. - f;
                 }
-                _ => unimplemented!(),
-            },
-            n => bail!("Order {}", n),
+            }
+            if weight == 0. {
+                continue;
+            }
+            for (j, o) in out.iter_mut().enumerate() {
+                *o += weight * self.sample(idx * n_out + j);
+            }
         }
         for (o, y) in self.output.iter().zip(out.iter_mut()) {
             *y = o.map(*y);
@@ -386,6 +476,7 @@ impl SampledFunction {
 }
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InterpolatedFunctionDim {
     pub input_range: (f32, f32),
     pub output_range: (f32, f32),
@@ -395,113 +486,529 @@ pub struct InterpolatedFunctionDim {
 }
 impl InterpolatedFunctionDim {
     pub fn apply(&self, x: f32) -> f32 {
+        let x = x.clamp(self.input_range.0, self.input_range.1);
         let y = self.c0 + x.powf(self.exponent) * (self.c1 - self.c0);
         let (y0, y1) = self.output_range;
         y.min(y1).max(y0)
     }
 }
 
+/// Combines several one-input functions, each covering a part of the domain.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StitchingFunction {
+    pub domain: (f32, f32),
+    pub functions: Vec<Function>,
+    pub bounds: Vec<f32>,
+    pub encode: Vec<f32>,
+    pub range: Option<Vec<f32>>,
+}
+impl StitchingFunction {
+    fn apply(&self, x: &[f32], out: &mut [f32]) -> Result<()> {
+        let x = try_opt!(x.first()).clamp(self.domain.0, self.domain.1);
+        let k = self.bounds.iter().position(|&b| x < b).unwrap_or(self.bounds.len());
+        let lo = if k == 0 { self.domain.0 } else { self.bounds[k - 1] };
+        let hi = self.bounds.get(k).cloned().unwrap_or(self.domain.1);
+        let e = interpolate(x, (lo, hi), (self.encode[2 * k], self.encode[2 * k + 1]));
+        self.functions[k].apply(&[e], out)?;
+        if let Some(ref range) = self.range {
+            for (y, r) in out.iter_mut().zip(range.chunks_exact(2)) {
+                *y = y.clamp(r[0], r[1]);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum PostScriptError {
     StackUnderflow,
+    StackOverflow,
     IncorrectStackSize,
+    TypeCheck,
+}
+
+/// A value on the operand stack of a PostScript calculator function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PsValue {
+    Int(i32),
+    Real(f32),
+    Bool(bool),
+}
+impl PsValue {
+    fn real(self) -> Result<f32, PostScriptError> {
+        match self {
+            PsValue::Int(i) => Ok(i as f32),
+            PsValue::Real(r) => Ok(r),
+            PsValue::Bool(_) => Err(PostScriptError::TypeCheck),
+        }
+    }
+    fn int(self) -> Result<i32, PostScriptError> {
+        match self {
+            PsValue::Int(i) => Ok(i),
+            _ => Err(PostScriptError::TypeCheck),
+        }
+    }
+    fn bool(self) -> Result<bool, PostScriptError> {
+        match self {
+            PsValue::Bool(b) => Ok(b),
+            _ => Err(PostScriptError::TypeCheck),
+        }
+    }
 }
+
+/// The operand stack may hold at most 100 values (PDF32000 7.10.5.2).
+const PS_STACK_LIMIT: usize = 100;
+
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PsFunc {
     pub ops: Vec<PsOp>,
 }
 
-macro_rules! op {
-    ($stack:ident; $($v:ident),* => $($e:expr),*) => ( {
-        $(let $v = $stack.pop().ok_or(PostScriptError::StackUnderflow)?;)*
-        $($stack.push($e);)*
-    } )
+struct PsStack(Vec<PsValue>);
+impl PsStack {
+    fn pop(&mut self) -> Result<PsValue, PostScriptError> {
+        self.0.pop().ok_or(PostScriptError::StackUnderflow)
+    }
+    fn push(&mut self, v: PsValue) -> Result<(), PostScriptError> {
+        if self.0.len() >= PS_STACK_LIMIT {
+            return Err(PostScriptError::StackOverflow);
+        }
+        self.0.push(v);
+        Ok(())
+    }
+    fn real(&mut self) -> Result<f32, PostScriptError> {
+        self.pop()?.real()
+    }
+    /// Applies `int` if both operands are integers (and it doesn't overflow), `real` otherwise.
+    fn arith(&mut self, int: fn(i32, i32) -> Option<i32>, real: fn(f32, f32) -> f32) -> Result<(), PostScriptError> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let v = match (a, b) {
+            (PsValue::Int(a), PsValue::Int(b)) => match int(a, b) {
+                Some(i) => PsValue::Int(i),
+                None => PsValue::Real(real(a as f32, b as f32)),
+            },
+            (a, b) => PsValue::Real(real(a.real()?, b.real()?)),
+        };
+        self.push(v)
+    }
+    fn compare(&mut self, f: fn(std::cmp::Ordering) -> bool) -> Result<(), PostScriptError> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let ord = match (a, b) {
+            (PsValue::Bool(a), PsValue::Bool(b)) => a.cmp(&b),
+            (a, b) => a.real()?.partial_cmp(&b.real()?).ok_or(PostScriptError::TypeCheck)?,
+        };
+        self.push(PsValue::Bool(f(ord)))
+    }
+    fn logic(&mut self, int: fn(i32, i32) -> i32, boolean: fn(bool, bool) -> bool) -> Result<(), PostScriptError> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let v = match (a, b) {
+            (PsValue::Int(a), PsValue::Int(b)) => PsValue::Int(int(a, b)),
+            (a, b) => PsValue::Bool(boolean(a.bool()?, b.bool()?)),
+        };
+        self.push(v)
+    }
+    fn unary(&mut self, f: fn(f32) -> f32) -> Result<(), PostScriptError> {
+        let a = self.real()?;
+        self.push(PsValue::Real(f(a)))
+    }
+    /// Rounding operators keep integers as they are.
+    fn round(&mut self, f: fn(f32) -> f32) -> Result<(), PostScriptError> {
+        match self.pop()? {
+            PsValue::Int(i) => self.push(PsValue::Int(i)),
+            v => self.push(PsValue::Real(f(v.real()?))),
+        }
+    }
 }
 
 impl PsFunc {
-    fn exec_inner(&self, stack: &mut Vec<f32>) -> Result<(), PostScriptError> {
-        for &op in &self.ops {
-            match op {
-                PsOp::Int(i) => stack.push(i as f32),
-                PsOp::Value(v) => stack.push(v),
-                PsOp::Dup => op!(stack; v => v, v),
-                PsOp::Exch => op!(stack; b, a => b, a),
-                PsOp::Add => op!(stack; b, a => a + b),
-                PsOp::Sub => op!(stack; b, a => a - b),
-                PsOp::Mul => op!(stack; b, a => a * b),
-                PsOp::Abs => op!(stack; a => a.abs()),
-                PsOp::Roll => {
-                    let j = stack.pop().ok_or(PostScriptError::StackUnderflow)? as isize;
-                    let n = stack.pop().ok_or(PostScriptError::StackUnderflow)? as usize;
-                    let start = stack.len() - n;
-                    let slice = &mut stack[start..];
-                    if j > 0 {
-                        slice.rotate_right(j as usize);
-                    } else {
-                        slice.rotate_left(-j as usize);
+    fn exec_inner(ops: &[PsOp], stack: &mut PsStack) -> Result<(), PostScriptError> {
+        use std::cmp::Ordering;
+        for op in ops {
+            match *op {
+                PsOp::Int(i) => stack.push(PsValue::Int(i))?,
+                PsOp::Value(v) => stack.push(PsValue::Real(v))?,
+                PsOp::Bool(b) => stack.push(PsValue::Bool(b))?,
+
+                PsOp::Add => stack.arith(i32::checked_add, |a, b| a + b)?,
+                PsOp::Sub => stack.arith(i32::checked_sub, |a, b| a - b)?,
+                PsOp::Mul => stack.arith(i32::checked_mul, |a, b| a * b)?,
+                PsOp::Div => {
+                    let b = stack.real()?;
+                    let a = stack.real()?;
+                    stack.push(PsValue::Real(a / b))?;
+                }
+                PsOp::Idiv | PsOp::Mod => {
+                    let b = stack.pop()?.int()?;
+                    let a = stack.pop()?.int()?;
+                    let v = match *op {
+                        PsOp::Idiv => a.checked_div(b),
+                        _ => a.checked_rem(b),
+                    };
+                    stack.push(PsValue::Int(v.ok_or(PostScriptError::TypeCheck)?))?;
+                }
+                PsOp::Neg => match stack.pop()? {
+                    PsValue::Int(i) if i != i32::MIN => stack.push(PsValue::Int(-i))?,
+                    v => stack.push(PsValue::Real(-v.real()?))?,
+                },
+                PsOp::Abs => match stack.pop()? {
+                    PsValue::Int(i) if i != i32::MIN => stack.push(PsValue::Int(i.abs()))?,
+                    v => stack.push(PsValue::Real(v.real()?.abs()))?,
+                },
+                PsOp::Ceiling => stack.round(f32::ceil)?,
+                PsOp::Floor => stack.round(f32::floor)?,
+                PsOp::Round => stack.round(|x| (x + 0.5).floor())?,
+                PsOp::Truncate => stack.round(f32::trunc)?,
+                PsOp::Sqrt => stack.unary(f32::sqrt)?,
+                PsOp::Sin => stack.unary(|x| x.to_radians().sin())?,
+                PsOp::Cos => stack.unary(|x| x.to_radians().cos())?,
+                PsOp::Atan => {
+                    let den = stack.real()?;
+                    let num = stack.real()?;
+                    let angle = num.atan2(den).to_degrees();
+                    stack.push(PsValue::Real(if angle < 0. { angle + 360. } else { angle }))?;
+                }
+                PsOp::Exp => {
+                    let e = stack.real()?;
+                    let base = stack.real()?;
+                    stack.push(PsValue::Real(base.powf(e)))?;
+                }
+                PsOp::Ln => stack.unary(f32::ln)?,
+                PsOp::Log => stack.unary(f32::log10)?,
+                PsOp::Cvi => {
+                    let v = stack.real()?.trunc();
+                    stack.push(PsValue::Int(v as i32))?;
+                }
+                PsOp::Cvr => {
+                    let v = stack.real()?;
+                    stack.push(PsValue::Real(v))?;
+                }
+
+                PsOp::Eq => stack.compare(|o| o == Ordering::Equal)?,
+                PsOp::Ne => stack.compare(|o| o != Ordering::Equal)?,
+                PsOp::Gt => stack.compare(|o| o == Ordering::Greater)?,
+                PsOp::Ge => stack.compare(|o| o != Ordering::Less)?,
+                PsOp::Lt => stack.compare(|o| o == Ordering::Less)?,
+                PsOp::Le => stack.compare(|o| o != Ordering::Greater)?,
+                PsOp::And => stack.logic(|a, b| a & b, |a, b| a & b)?,
+                PsOp::Or => stack.logic(|a, b| a | b, |a, b| a | b)?,
+                PsOp::Xor => stack.logic(|a, b| a ^ b, |a, b| a ^ b)?,
+                PsOp::Not => match stack.pop()? {
+                    PsValue::Int(i) => stack.push(PsValue::Int(!i))?,
+                    v => stack.push(PsValue::Bool(!v.bool()?))?,
+                },
+                PsOp::Bitshift => {
+                    let shift = stack.pop()?.int()?;
+                    let a = stack.pop()?.int()?;
+                    let v = match shift {
+                        0.. => a.checked_shl(shift as u32).unwrap_or(0),
+                        _ => ((a as u32).checked_shr(-shift as u32).unwrap_or(0)) as i32,
+                    };
+                    stack.push(PsValue::Int(v))?;
+                }
+
+                PsOp::Pop => {
+                    stack.pop()?;
+                }
+                PsOp::Exch => {
+                    let b = stack.pop()?;
+                    let a = stack.pop()?;
+                    stack.push(b)?;
+                    stack.push(a)?;
+                }
+                PsOp::Dup => {
+                    let v = stack.pop()?;
+                    stack.push(v)?;
+                    stack.push(v)?;
+                }
+                PsOp::Copy => {
+                    let n = stack.pop()?.int()?;
+                    let n = usize::try_from(n).map_err(|_| PostScriptError::TypeCheck)?;
+                    if n > stack.0.len() {
+                        return Err(PostScriptError::StackUnderflow);
+                    }
+                    let start = stack.0.len() - n;
+                    for i in start..start + n {
+                        let v = stack.0[i];
+                        stack.push(v)?;
                     }
                 }
                 PsOp::Index => {
-                    let n = stack.pop().ok_or(PostScriptError::StackUnderflow)? as usize;
-                    if n >= stack.len() {
+                    let n = stack.pop()?.int()?;
+                    let n = usize::try_from(n).map_err(|_| PostScriptError::TypeCheck)?;
+                    if n >= stack.0.len() {
                         return Err(PostScriptError::StackUnderflow);
                     }
-                    let val = stack[stack.len() - n - 1];
-                    stack.push(val);
+                    let val = stack.0[stack.0.len() - n - 1];
+                    stack.push(val)?;
                 }
-                PsOp::Cvr => {}
-                PsOp::Pop => {
-                    stack.pop().ok_or(PostScriptError::StackUnderflow)?;
+                PsOp::Roll => {
+                    let j = stack.pop()?.int()?;
+                    let n = stack.pop()?.int()?;
+                    let n = usize::try_from(n).map_err(|_| PostScriptError::TypeCheck)?;
+                    if n > stack.0.len() {
+                        return Err(PostScriptError::StackUnderflow);
+                    }
+                    if n > 0 {
+                        let start = stack.0.len() - n;
+                        let j = j.rem_euclid(n as i32) as usize;
+                        stack.0[start..].rotate_right(j);
+                    }
+                }
+
+                PsOp::If(ref then) => {
+                    if stack.pop()?.bool()? {
+                        Self::exec_inner(then, stack)?;
+                    }
+                }
+                PsOp::IfElse(ref then, ref otherwise) => {
+                    if stack.pop()?.bool()? {
+                        Self::exec_inner(then, stack)?;
+                    } else {
+                        Self::exec_inner(otherwise, stack)?;
+                    }
                 }
             }
         }
         Ok(())
     }
     pub fn exec(&self, input: &[f32], output: &mut [f32]) -> Result<()> {
-        let mut stack = Vec::with_capacity(10);
-        stack.extend_from_slice(input);
-        match self.exec_inner(&mut stack) {
-            Ok(()) => {}
-            Err(_) => return Err(PdfError::PostScriptExec),
+        let mut stack = PsStack(Vec::with_capacity(10));
+        stack.0.extend(input.iter().map(|&x| PsValue::Real(x)));
+        if let Err(e) = Self::exec_inner(&self.ops, &mut stack) {
+            debug!("PostScript function failed: {:?}", e);
+            return Err(PdfError::PostScriptExec);
         }
-        if output.len() != stack.len() {
+        if output.len() != stack.0.len() {
             bail!(
                 "incorrect output length: expected {}, found {}.",
-                stack.len(),
+                stack.0.len(),
                 output.len()
             )
         }
-        output.copy_from_slice(&stack);
+        for (out, v) in output.iter_mut().zip(stack.0) {
+            *out = v.real().map_err(|_| PdfError::PostScriptExec)?;
+        }
         Ok(())
     }
     pub fn parse(s: &str) -> Result<Self, PdfError> {
-        let start = s.find('{').ok_or(PdfError::PostScriptParse)?;
-        let end = s.rfind('}').ok_or(PdfError::PostScriptParse)?;
+        let mut tokens = PsTokens { s };
+        match tokens.next() {
+            Some("{") => {}
+            _ => return Err(PdfError::PostScriptParse),
+        }
+        let ops = Self::parse_block(&mut tokens, 0)?;
+        Ok(PsFunc { ops })
+    }
+    /// Parse the operators up to the closing brace of a block.
+    fn parse_block(tokens: &mut PsTokens, depth: usize) -> Result<Vec<PsOp>> {
+        if depth > 32 {
+            return Err(PdfError::PostScriptParse);
+        }
+        let mut ops = vec![];
+        let mut blocks: Vec<Vec<PsOp>> = vec![];
+        loop {
+            let token = tokens.next().ok_or(PdfError::PostScriptParse)?;
+            match token {
+                "}" => break,
+                "{" => blocks.push(Self::parse_block(tokens, depth + 1)?),
+                "if" => {
+                    let then = blocks.pop().ok_or(PdfError::PostScriptParse)?;
+                    ops.push(PsOp::If(then));
+                }
+                "ifelse" => {
+                    let otherwise = blocks.pop().ok_or(PdfError::PostScriptParse)?;
+                    let then = blocks.pop().ok_or(PdfError::PostScriptParse)?;
+                    ops.push(PsOp::IfElse(then, otherwise));
+                }
+                token => {
+                    if !blocks.is_empty() {
+                        return Err(PdfError::PostScriptParse);
+                    }
+                    ops.push(PsOp::parse(token)?);
+                }
+            }
+        }
+        if !blocks.is_empty() {
+            return Err(PdfError::PostScriptParse);
+        }
+        Ok(ops)
+    }
+}
 
-        let ops: Result<Vec<_>, _> = s[start + 1..end]
-            .split_ascii_whitespace()
-            .map(PsOp::parse)
-            .collect();
-        Ok(PsFunc { ops: ops? })
+/// Splits calculator function code into tokens. Braces are tokens of their own.
+struct PsTokens<'a> {
+    s: &'a str,
+}
+impl<'a> Iterator for PsTokens<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            self.s = self.s.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '\0');
+            if self.s.starts_with('%') {
+                let end = self.s.find(['\n', '\r']).unwrap_or(self.s.len());
+                self.s = &self.s[end..];
+            } else {
+                break;
+            }
+        }
+        if self.s.is_empty() {
+            return None;
+        }
+        let len = match self.s.as_bytes()[0] {
+            b'{' | b'}' => 1,
+            _ => self.s.find(|c: char| c.is_ascii_whitespace() || "{}%".contains(c)).unwrap_or(self.s.len()),
+        };
+        let (token, rest) = self.s.split_at(len);
+        self.s = rest;
+        Some(token)
     }
 }
 
-#[derive(Copy, Clone, Debug, DataSize)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PsOp {
     Int(i32),
     Value(f32),
+    Bool(bool),
+
     Add,
     Sub,
-    Abs,
     Mul,
-    Dup,
-    Exch,
-    Roll,
-    Index,
+    Div,
+    Idiv,
+    Mod,
+    Neg,
+    Abs,
+    Ceiling,
+    Floor,
+    Round,
+    Truncate,
+    Sqrt,
+    Sin,
+    Cos,
+    Atan,
+    Exp,
+    Ln,
+    Log,
+    Cvi,
     Cvr,
+
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    And,
+    Or,
+    Xor,
+    Not,
+    Bitshift,
+
     Pop,
+    Exch,
+    Dup,
+    Copy,
+    Index,
+    Roll,
+
+    If(Vec<PsOp>),
+    IfElse(Vec<PsOp>, Vec<PsOp>),
+}
+impl DataSize for PsOp {
+    const IS_DYNAMIC: bool = true;
+    const STATIC_HEAP_SIZE: usize = 0;
+
+    fn estimate_heap_size(&self) -> usize {
+        let block = |ops: &[PsOp]| -> usize {
+            std::mem::size_of_val(ops) + ops.iter().map(|op| op.estimate_heap_size()).sum::<usize>()
+        };
+        match *self {
+            PsOp::If(ref then) => block(then),
+            PsOp::IfElse(ref then, ref otherwise) => block(then) + block(otherwise),
+            _ => 0,
+        }
+    }
+}
+impl fmt::Display for PsFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_ps_block(&self.ops, f)
+    }
+}
+fn write_ps_block(ops: &[PsOp], f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("{")?;
+    for op in ops {
+        write!(f, " {}", op)?;
+    }
+    f.write_str(" }")
+}
+impl fmt::Display for PsOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            PsOp::Int(i) => return write!(f, "{}", i),
+            // keep a decimal point so it is read back as a real
+            PsOp::Value(v) if v.fract() == 0. => return write!(f, "{:.1}", v),
+            PsOp::Value(v) => return write!(f, "{}", v),
+            PsOp::Bool(b) => return write!(f, "{}", b),
+            PsOp::If(ref then) => {
+                write_ps_block(then, f)?;
+                return f.write_str(" if");
+            }
+            PsOp::IfElse(ref then, ref otherwise) => {
+                write_ps_block(then, f)?;
+                f.write_str(" ")?;
+                write_ps_block(otherwise, f)?;
+                return f.write_str(" ifelse");
+            }
+            PsOp::Add => "add",
+            PsOp::Sub => "sub",
+            PsOp::Mul => "mul",
+            PsOp::Div => "div",
+            PsOp::Idiv => "idiv",
+            PsOp::Mod => "mod",
+            PsOp::Neg => "neg",
+            PsOp::Abs => "abs",
+            PsOp::Ceiling => "ceiling",
+            PsOp::Floor => "floor",
+            PsOp::Round => "round",
+            PsOp::Truncate => "truncate",
+            PsOp::Sqrt => "sqrt",
+            PsOp::Sin => "sin",
+            PsOp::Cos => "cos",
+            PsOp::Atan => "atan",
+            PsOp::Exp => "exp",
+            PsOp::Ln => "ln",
+            PsOp::Log => "log",
+            PsOp::Cvi => "cvi",
+            PsOp::Cvr => "cvr",
+            PsOp::Eq => "eq",
+            PsOp::Ne => "ne",
+            PsOp::Gt => "gt",
+            PsOp::Ge => "ge",
+            PsOp::Lt => "lt",
+            PsOp::Le => "le",
+            PsOp::And => "and",
+            PsOp::Or => "or",
+            PsOp::Xor => "xor",
+            PsOp::Not => "not",
+            PsOp::Bitshift => "bitshift",
+            PsOp::Pop => "pop",
+            PsOp::Exch => "exch",
+            PsOp::Dup => "dup",
+            PsOp::Copy => "copy",
+            PsOp::Index => "index",
+            PsOp::Roll => "roll",
+        };
+        f.write_str(name)
+    }
 }
 impl PsOp {
+    /// Parse a single operator or number. Blocks are handled by [`PsFunc::parse`].
     pub fn parse(s: &str) -> Result<Self> {
         if let Ok(i) = s.parse::<i32>() {
             Ok(PsOp::Int(i))
@@ -509,16 +1016,46 @@ impl PsOp {
             Ok(PsOp::Value(f))
         } else {
             Ok(match s {
+                "true" => PsOp::Bool(true),
+                "false" => PsOp::Bool(false),
                 "add" => PsOp::Add,
                 "sub" => PsOp::Sub,
-                "abs" => PsOp::Abs,
                 "mul" => PsOp::Mul,
-                "dup" => PsOp::Dup,
-                "exch" => PsOp::Exch,
-                "roll" => PsOp::Roll,
-                "index" => PsOp::Index,
+                "div" => PsOp::Div,
+                "idiv" => PsOp::Idiv,
+                "mod" => PsOp::Mod,
+                "neg" => PsOp::Neg,
+                "abs" => PsOp::Abs,
+                "ceiling" => PsOp::Ceiling,
+                "floor" => PsOp::Floor,
+                "round" => PsOp::Round,
+                "truncate" => PsOp::Truncate,
+                "sqrt" => PsOp::Sqrt,
+                "sin" => PsOp::Sin,
+                "cos" => PsOp::Cos,
+                "atan" => PsOp::Atan,
+                "exp" => PsOp::Exp,
+                "ln" => PsOp::Ln,
+                "log" => PsOp::Log,
+                "cvi" => PsOp::Cvi,
                 "cvr" => PsOp::Cvr,
+                "eq" => PsOp::Eq,
+                "ne" => PsOp::Ne,
+                "gt" => PsOp::Gt,
+                "ge" => PsOp::Ge,
+                "lt" => PsOp::Lt,
+                "le" => PsOp::Le,
+                "and" => PsOp::And,
+                "or" => PsOp::Or,
+                "xor" => PsOp::Xor,
+                "not" => PsOp::Not,
+                "bitshift" => PsOp::Bitshift,
                 "pop" => PsOp::Pop,
+                "exch" => PsOp::Exch,
+                "dup" => PsOp::Dup,
+                "copy" => PsOp::Copy,
+                "index" => PsOp::Index,
+                "roll" => PsOp::Roll,
                 _ => {
                     bail!("unimplemented op {}", s);
                 }
@@ -526,3 +1063,43 @@ impl PsOp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postscript() {
+        let func = PsFunc::parse("{exch dup 0.5 gt{pop 1}{2 mul}ifelse add}").unwrap();
+        let mut out = [0.];
+        func.exec(&[0.25, 10.], &mut out).unwrap();
+        assert_eq!(out, [10.5]);
+        func.exec(&[0.75, 10.], &mut out).unwrap();
+        assert_eq!(out, [11.]);
+        assert!(func.exec(&[0.75], &mut out).is_err());
+
+        let written = PsFunc::parse(&func.to_string()).unwrap();
+        written.exec(&[0.25, 10.], &mut out).unwrap();
+        assert_eq!(out, [10.5]);
+    }
+
+    #[test]
+    fn sampled() {
+        // 1 input, 2 outputs, 3 samples of 4 bits
+        let func = SampledFunction {
+            input: vec![SampledFunctionInput { domain: (0., 1.), encode: (0., 2.), size: 3 }],
+            output: vec![
+                SampledFunctionOutput { offset: 0., scale: 1. / 15., range: (0., 1.) },
+                SampledFunctionOutput { offset: 0., scale: 1. / 15., range: (0., 1.) },
+            ],
+            bits_per_sample: 4,
+            data: vec![0x0f, 0xf0, 0x0f].into(),
+            order: Interpolation::Linear,
+        };
+        let mut out = [0.; 2];
+        func.apply(&[0.25], &mut out).unwrap();
+        assert_eq!(out, [0.5, 0.5]);
+        func.apply(&[1.], &mut out).unwrap();
+        assert_eq!(out, [0., 1.]);
+    }
+}
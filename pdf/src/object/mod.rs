@@ -13,6 +13,8 @@ pub use self::stream::*;
 pub use self::types::*;
 pub use crate::file::PromisedRef;
 use crate::parser::ParseFlags;
+use crate::cmap::CMapProvider;
+use crate::crypt::SecurityHandlerProvider;
 
 use crate::enc::*;
 use crate::error::*;
@@ -32,11 +34,30 @@ use std::sync::Arc;
 pub type ObjNr = u64;
 pub type GenNr = u64;
 
+#[derive(Debug, Clone)]
 pub struct ParseOptions {
     pub allow_error_in_option: bool,
     pub allow_xref_error: bool,
     pub allow_invalid_ops: bool,
     pub allow_missing_endobj: bool,
+
+    /// How many objects may be nested while resolving references
+    /// (i.e. how long a chain of `/Parent` or `/Next` links can get).
+    pub max_depth: usize,
+
+    /// Repair common mistakes instead of failing: wrong stream lengths, missing `endobj`,
+    /// names and numbers used for each other and malformed dates.
+    pub lenient: bool,
+    /// Where repairs and ignored errors are reported, in addition to the log.
+    pub warnings: Option<Warnings>,
+    /// How much memory decoding streams may take, see [`Limits`].
+    pub limits: Limits,
+    /// Where predefined CMaps of Type 0 fonts are loaded from, see [`crate::cmap`].
+    pub cmaps: Option<Arc<dyn CMapProvider>>,
+    /// Decodes the filters this crate does not know.
+    pub filters: Option<Arc<dyn FilterProvider>>,
+    /// Decrypts files with a security handler other than `Standard`.
+    pub security_handlers: Option<Arc<dyn SecurityHandlerProvider>>,
 }
 impl ParseOptions {
     pub const fn tolerant() -> Self {
@@ -45,6 +66,13 @@ impl ParseOptions {
             allow_xref_error: true,
             allow_invalid_ops: true,
             allow_missing_endobj: true,
+            max_depth: 64,
+            lenient: false,
+            warnings: None,
+            limits: Limits::unlimited(),
+            cmaps: None,
+            filters: None,
+            security_handlers: None,
         }
     }
     pub const fn strict() -> Self {
@@ -53,10 +81,143 @@ impl ParseOptions {
             allow_xref_error: false,
             allow_invalid_ops: true,
             allow_missing_endobj: false,
+            max_depth: 64,
+            lenient: false,
+            warnings: None,
+            limits: Limits::unlimited(),
+            cmaps: None,
+            filters: None,
+            security_handlers: None,
+        }
+    }
+    /// Like [`tolerant`](ParseOptions::tolerant), and repair what can be repaired.
+    pub const fn lenient() -> Self {
+        ParseOptions {
+            allow_error_in_option: true,
+            allow_xref_error: true,
+            allow_invalid_ops: true,
+            allow_missing_endobj: true,
+            max_depth: 64,
+            lenient: true,
+            warnings: None,
+            limits: Limits::unlimited(),
+            cmaps: None,
+            filters: None,
+            security_handlers: None,
+        }
+    }
+    /// Report warnings to `warnings`.
+    pub fn collect_warnings(self, warnings: &Warnings) -> Self {
+        ParseOptions {
+            warnings: Some(warnings.clone()),
+            ..self
+        }
+    }
+    /// Enforce `limits` while reading.
+    pub fn limits(self, limits: Limits) -> Self {
+        ParseOptions { limits, ..self }
+    }
+    /// Load predefined CMaps from `provider`.
+    pub fn cmaps(self, provider: Arc<dyn CMapProvider>) -> Self {
+        ParseOptions { cmaps: Some(provider), ..self }
+    }
+    /// Decode unknown filters with `provider`.
+    pub fn filter_provider(self, provider: Arc<dyn FilterProvider>) -> Self {
+        ParseOptions { filters: Some(provider), ..self }
+    }
+    /// Open files protected by other security handlers than `Standard` with `provider`.
+    pub fn security_handler_provider(self, provider: Arc<dyn SecurityHandlerProvider>) -> Self {
+        ParseOptions { security_handlers: Some(provider), ..self }
+    }
+    pub fn warn(&self, warning: Warning) {
+        warn!("{}", warning);
+        if let Some(ref warnings) = self.warnings {
+            warnings.0.lock().unwrap().push(warning);
+        }
+    }
+}
+
+/// Bounds on what reading a file may cost, so that hostile files (like decompression bombs)
+/// fail with [`PdfError::LimitExceeded`] instead of exhausting memory.
+///
+/// The nesting depth is limited by [`ParseOptions::max_depth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The largest size of a single decoded stream, in bytes.
+    pub max_stream_size: usize,
+    /// The largest number of objects (the `/Size` of the xref table).
+    pub max_objects: usize,
+    /// How many bytes may be decoded from the streams of one file altogether.
+    /// Each stream is counted once, however often it is decoded again.
+    pub max_total_decoded: u64,
+}
+impl Limits {
+    pub const fn unlimited() -> Self {
+        Limits {
+            max_stream_size: usize::MAX,
+            max_objects: usize::MAX,
+            max_total_decoded: u64::MAX,
+        }
+    }
+    /// Limits for files from untrusted sources: 256 MiB per stream, 1 GiB altogether
+    /// and a million objects.
+    pub const fn untrusted() -> Self {
+        Limits {
+            max_stream_size: 256 << 20,
+            max_objects: 1_000_000,
+            max_total_decoded: 1 << 30,
+        }
+    }
+}
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::unlimited()
+    }
+}
+
+/// Something that was repaired or ignored while parsing, see [`ParseOptions::warnings`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The `Length` of the stream at `pos` was wrong, the data ends at `endstream`.
+    StreamLength { pos: usize, length: usize, actual: usize },
+    /// The object was not followed by `endobj`.
+    MissingEndobj { obj: PlainRef },
+    /// A name was used as a number or the other way around.
+    TypeMismatch { expected: &'static str, found: String },
+    /// The date did not follow the `D:YYYYMMDDHHmmSSOHH'mm` format, but could be read.
+    MalformedDate { date: String },
+    /// An error was ignored, like an invalid optional entry.
+    Ignored { error: String },
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::StreamLength { pos, length, actual } => write!(f, "stream at {} has length {}, not {}", pos, actual, length),
+            Warning::MissingEndobj { obj } => write!(f, "missing endobj after object {} {}", obj.id, obj.gen),
+            Warning::TypeMismatch { expected, ref found } => write!(f, "expected {}, found {}", expected, found),
+            Warning::MalformedDate { ref date } => write!(f, "malformed date {:?}", date),
+            Warning::Ignored { ref error } => write!(f, "ignored {}", error),
         }
     }
 }
 
+/// Collects [`Warning`]s. Clones share the same list.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings(Arc<std::sync::Mutex<Vec<Warning>>>);
+impl Warnings {
+    pub fn new() -> Self {
+        Warnings::default()
+    }
+    /// The warnings so far.
+    pub fn get(&self) -> Vec<Warning> {
+        self.0.lock().unwrap().clone()
+    }
+    /// Remove and return the warnings so far.
+    pub fn take(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
 pub trait Resolve {
     fn resolve_flags(&self, r: PlainRef, flags: ParseFlags, depth: usize) -> Result<Primitive>;
     fn resolve(&self, r: PlainRef) -> Result<Primitive> {
@@ -73,6 +234,24 @@ pub trait Resolve {
     ) -> Result<Arc<[u8]>>;
 }
 
+/// Add `r` to `path`, the nodes from the root of a tree (like the page tree) down to the current one.
+///
+/// Fails with `ReferenceCycle` if `r` is already on the path, and with `MaxDepth` if the path
+/// gets longer than [`ParseOptions::max_depth`].
+pub(crate) fn enter_node(path: &mut Vec<PlainRef>, r: PlainRef, resolve: &impl Resolve) -> Result<()> {
+    if path.contains(&r) {
+        return Err(PdfError::ReferenceCycle {
+            obj_nr: r.id,
+            chain: path.iter().map(|r| r.id).collect(),
+        });
+    }
+    if path.len() >= resolve.options().max_depth {
+        return Err(PdfError::MaxDepth);
+    }
+    path.push(r);
+    Ok(())
+}
+
 pub struct NoResolve;
 impl Resolve for NoResolve {
     fn resolve_flags(&self, _: PlainRef, _: ParseFlags, _: usize) -> Result<Primitive> {
@@ -115,6 +294,11 @@ pub trait Cloner: Updater + Resolve {
         old: &RcRef<T>,
     ) -> Result<RcRef<T>>;
     fn clone_shared<T: DeepClone>(&mut self, old: &Shared<T>) -> Result<Shared<T>>;
+    /// Use `new` as the clone of `old` from now on, for objects that are cloned by other means
+    /// (like pages, which the builder creates).
+    ///
+    /// Does nothing by default, for cloners that don't keep track of the objects they cloned.
+    fn map_ref(&mut self, _old: PlainRef, _new: PlainRef) {}
 }
 
 pub trait DeepClone: Sized + Sync + Send + 'static {
@@ -497,6 +681,15 @@ impl<T: Object> DeepClone for Lazy<T> {
         })
     }
 }
+impl<T> Lazy<T> {
+    /// The reference, if the value is an indirect object.
+    pub fn as_ref(&self) -> Option<Ref<T>> {
+        match self.primitive {
+            Primitive::Reference(r) => Some(Ref::new(r)),
+            _ => None,
+        }
+    }
+}
 impl<T: Object + DataSize> Lazy<T> {
     pub fn load(&self, resolve: &impl Resolve) -> Result<MaybeRef<T>> {
         self.cache
@@ -552,11 +745,27 @@ impl<T: Object> From<RcRef<T>> for Lazy<T> {
 // Object for Primitives & other types
 //////////////////////////////////////
 
+/// In lenient mode, a name that spells a number (like `/12`) is taken as that number.
+fn name_as_number(p: Primitive, resolve: &impl Resolve) -> Primitive {
+    match p {
+        Primitive::Name(ref name) if resolve.options().lenient => {
+            let number = match (name.parse::<i32>(), name.parse::<f32>()) {
+                (Ok(i), _) => Primitive::Integer(i),
+                (_, Ok(f)) if name.bytes().all(|b| b.is_ascii_digit() || b"+-.".contains(&b)) => Primitive::Number(f),
+                _ => return p,
+            };
+            resolve.options().warn(Warning::TypeMismatch { expected: "Number", found: format!("/{}", name) });
+            number
+        }
+        p => p,
+    }
+}
+
 impl Object for i32 {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         match p {
-            Primitive::Reference(id) => r.resolve(id)?.as_integer(),
-            p => p.as_integer(),
+            Primitive::Reference(id) => name_as_number(r.resolve(id)?, r).as_integer(),
+            p => name_as_number(p, r).as_integer(),
         }
     }
 }
@@ -569,8 +778,8 @@ impl ObjectWrite for i32 {
 impl Object for u32 {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         match p {
-            Primitive::Reference(id) => r.resolve(id)?.as_u32(),
-            p => p.as_u32(),
+            Primitive::Reference(id) => name_as_number(r.resolve(id)?, r).as_u32(),
+            p => name_as_number(p, r).as_u32(),
         }
     }
 }
@@ -583,8 +792,8 @@ impl ObjectWrite for u32 {
 impl Object for usize {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         match p {
-            Primitive::Reference(id) => Ok(r.resolve(id)?.as_u32()? as usize),
-            p => Ok(p.as_u32()? as usize),
+            Primitive::Reference(id) => Ok(name_as_number(r.resolve(id)?, r).as_u32()? as usize),
+            p => Ok(name_as_number(p, r).as_u32()? as usize),
         }
     }
 }
@@ -597,8 +806,8 @@ impl ObjectWrite for usize {
 impl Object for f32 {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         match p {
-            Primitive::Reference(id) => r.resolve(id)?.as_number(),
-            p => p.as_number(),
+            Primitive::Reference(id) => name_as_number(r.resolve(id)?, r).as_number(),
+            p => name_as_number(p, r).as_number(),
         }
     }
 }
@@ -637,7 +846,18 @@ impl Object for Dictionary {
 
 impl Object for Name {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
-        p.resolve(resolve)?.into_name()
+        match p.resolve(resolve)? {
+            p @ (Primitive::Integer(_) | Primitive::Number(_)) if resolve.options().lenient => {
+                let name = match p {
+                    Primitive::Integer(i) => i.to_string(),
+                    Primitive::Number(f) => f.to_string(),
+                    _ => unreachable!(),
+                };
+                resolve.options().warn(Warning::TypeMismatch { expected: "Name", found: name.clone() });
+                Ok(Name::from(name.as_str()))
+            }
+            p => p.into_name(),
+        }
     }
 }
 impl ObjectWrite for Name {
@@ -657,7 +877,11 @@ impl<T: Object> Object for Vec<T> {
                 .map(|p| T::from_primitive(p, r))
                 .collect::<Result<Vec<T>>>()?,
             Primitive::Null => Vec::new(),
-            Primitive::Reference(id) => Self::from_primitive(r.resolve(id)?, r)?,
+            Primitive::Reference(id) => match r.resolve(id)? {
+                // a single object, passed as the reference so that `T` can keep it (like `Ref` or `Lazy`)
+                Primitive::Dictionary(_) | Primitive::Stream(_) => vec![T::from_primitive(p, r)?],
+                p => Self::from_primitive(p, r)?,
+            },
             _ => vec![T::from_primitive(p, r)?],
         })
     }
@@ -793,10 +1017,9 @@ impl<T: Object> Object for Option<T> {
             p => match T::from_primitive(p, resolve) {
                 Ok(p) => Ok(Some(p)),
                 // References to non-existing objects ought not to be an error
-                Err(PdfError::NullRef { .. }) => Ok(None),
-                Err(PdfError::FreeObject { .. }) => Ok(None),
-                Err(e) if resolve.options().allow_error_in_option => {
-                    warn!("ignoring {:?}", e);
+                Err(e) if e.is_dangling_ref() => Ok(None),
+                Err(e) if resolve.options().allow_error_in_option || resolve.options().lenient => {
+                    resolve.options().warn(Warning::Ignored { error: e.to_string() });
                     Ok(None)
                 }
                 Err(e) => Err(e),
@@ -829,6 +1052,74 @@ impl<T: Trace> Trace for Option<T> {
     }
 }
 
+/// A dictionary entry that distinguishes a missing key from an explicit `null`.
+///
+/// `Option` reads both as `None` and never writes `null`. Where the difference matters
+/// (like `/V null` clearing an inherited field value), derived dictionaries leave the key out
+/// for `Absent` and write `null` for `Null`.
+#[derive(Debug, Clone, PartialEq, Default, DataSize)]
+pub enum Nullable<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+impl<T> Nullable<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Nullable::Absent)
+    }
+    pub fn is_null(&self) -> bool {
+        matches!(self, Nullable::Null)
+    }
+    /// The value, if there is one.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Nullable::Value(t) => Some(t),
+            _ => None,
+        }
+    }
+    pub fn into_value(self) -> Option<T> {
+        match self {
+            Nullable::Value(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+impl<T> From<Option<T>> for Nullable<T> {
+    /// `None` becomes `Null`.
+    fn from(o: Option<T>) -> Self {
+        match o {
+            Some(t) => Nullable::Value(t),
+            None => Nullable::Null,
+        }
+    }
+}
+impl<T: Object> Object for Nullable<T> {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Null => Ok(Nullable::Null),
+            p => T::from_primitive(p, resolve).map(Nullable::Value),
+        }
+    }
+}
+impl<T: ObjectWrite> ObjectWrite for Nullable<T> {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match self {
+            Nullable::Absent | Nullable::Null => Ok(Primitive::Null),
+            Nullable::Value(t) => t.to_primitive(update),
+        }
+    }
+}
+impl<T: DeepClone> DeepClone for Nullable<T> {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        match self {
+            Nullable::Absent => Ok(Nullable::Absent),
+            Nullable::Null => Ok(Nullable::Null),
+            Nullable::Value(t) => t.deep_clone(cloner).map(Nullable::Value),
+        }
+    }
+}
+
 impl<T: Object> Object for Box<T> {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         T::from_primitive(p, resolve).map(Box::new)
@@ -977,3 +1268,55 @@ impl<A: ToDict, B: ToDict> ObjectWrite for Merged<A, B> {
         self.to_dict(update).map(Primitive::Dictionary)
     }
 }
+
+/// References are written as `"5 0 R"` and not followed, so there are no cycles.
+#[cfg(feature = "serde")]
+mod ser {
+    use super::*;
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+    impl Serialize for PlainRef {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.collect_str(&format_args!("{} {} R", self.id, self.gen))
+        }
+    }
+    impl<T> Serialize for Ref<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            Serialize::serialize(&self.inner, serializer)
+        }
+    }
+    impl<T> Serialize for RcRef<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            Serialize::serialize(&self.inner, serializer)
+        }
+    }
+    /// The dictionary entries, but not the data.
+    impl<I: Serialize> Serialize for Stream<I> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Stream", 1)?;
+            s.serialize_field("info", &self.info)?;
+            s.end()
+        }
+    }
+    impl<T: Serialize> Serialize for MaybeRef<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            match *self {
+                MaybeRef::Direct(ref data) => Serialize::serialize(data, serializer),
+                MaybeRef::Indirect(ref r) => Serialize::serialize(r, serializer),
+            }
+        }
+    }
+    impl<T> Serialize for Lazy<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            Serialize::serialize(&self.primitive, serializer)
+        }
+    }
+    impl<T: Serialize> Serialize for Nullable<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            match *self {
+                Nullable::Absent | Nullable::Null => serializer.serialize_none(),
+                Nullable::Value(ref value) => serializer.serialize_some(value),
+            }
+        }
+    }
+}
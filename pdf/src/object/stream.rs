@@ -5,7 +5,7 @@ use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
 use crate::parser::Lexer;
-use crate::enc::{StreamFilter, decode};
+use crate::enc::{StreamFilter, FlateLevel, decode_with_options, encode, flate_encode_level, predict};
 
 use std::ops::{Deref, Range};
 use std::fmt;
@@ -80,7 +80,7 @@ impl<I: Object> Stream<I> {
                     use std::borrow::Cow;
                     let mut data: Cow<[u8]> = (&**data).into();
                     for filter in filters {
-                        data = t!(decode(&data, filter), filter).into();
+                        data = t!(decode_with_options(&data, filter, resolve.options()), filter).into();
                     }
                     Ok(data.into())
                 }
@@ -99,6 +99,83 @@ impl<I: Object> Stream<I> {
     }
 }
 
+/// How the data of a stream is encoded when it is written, see [`Stream::encoded`].
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// the filters, in the order of the `Filter` entry: the first one is decoded first
+    pub filters: Vec<StreamFilter>,
+    /// how hard `FlateDecode` compresses
+    pub flate_level: FlateLevel,
+    /// keep data that is encoded with an image filter (`DCTDecode`, `JPXDecode`, `JBIG2Decode` or
+    /// `CCITTFaxDecode`) as it is, with `filters` applied on top, instead of decoding it
+    pub keep_image_filters: bool,
+}
+impl StreamOptions {
+    /// `FlateDecode` at `level`.
+    pub fn flate(level: FlateLevel) -> Self {
+        StreamOptions { filters: vec![StreamFilter::FlateDecode(Default::default())], flate_level: level, keep_image_filters: true }
+    }
+    /// No filters, apart from the image filters.
+    pub fn uncompressed() -> Self {
+        StreamOptions { filters: vec![], flate_level: FlateLevel::Default, keep_image_filters: true }
+    }
+    /// `ASCIIHexDecode`, which keeps the file readable as text while debugging.
+    pub fn hex() -> Self {
+        StreamOptions { filters: vec![StreamFilter::ASCIIHexDecode], flate_level: FlateLevel::Default, keep_image_filters: true }
+    }
+    /// The non-standard `PDFRS_ZstdDecode`, for files that only this crate reads, like archives.
+    #[cfg(feature = "zstd")]
+    pub fn zstd() -> Self {
+        StreamOptions { filters: vec![StreamFilter::ZstdDecode], flate_level: FlateLevel::Default, keep_image_filters: true }
+    }
+    /// The non-standard `PDFRS_BrotliDecode`.
+    #[cfg(feature = "brotli")]
+    pub fn brotli() -> Self {
+        StreamOptions { filters: vec![StreamFilter::BrotliDecode], flate_level: FlateLevel::Default, keep_image_filters: true }
+    }
+}
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions::flate(FlateLevel::Default)
+    }
+}
+
+fn is_image_filter(filter: &StreamFilter) -> bool {
+    matches!(filter, StreamFilter::DCTDecode(_) | StreamFilter::JPXDecode | StreamFilter::JBIG2Decode(_) | StreamFilter::CCITTFaxDecode(_))
+}
+
+impl<I: Object + Clone> Stream<I> {
+    /// A copy of this stream with the data encoded according to `options`, to be written
+    /// instead of it.
+    ///
+    /// The current filters are decoded first, apart from the image filters if `options` keeps them.
+    pub fn encoded(&self, options: &StreamOptions, resolve: &impl Resolve) -> Result<Stream<I>> {
+        let raw = match self.inner_data {
+            StreamData::Generated(ref data) => data.clone(),
+            StreamData::Original(ref range, id) => t!(resolve.stream_data(id, range.clone())),
+        };
+        let filters = &self.info.filters;
+        let keep = match options.keep_image_filters {
+            true => filters.iter().position(is_image_filter).unwrap_or(filters.len()),
+            false => filters.len(),
+        };
+        let mut data = raw.to_vec();
+        for filter in &filters[.. keep] {
+            data = t!(decode_with_options(&data, filter, resolve.options()), filter);
+        }
+        for filter in options.filters.iter().rev() {
+            data = match filter {
+                StreamFilter::FlateDecode(ref params) => flate_encode_level(&t!(predict(&data, params)), options.flate_level),
+                _ if is_image_filter(filter) => bail!("{:?} can only be kept, not encoded", filter),
+                _ => t!(encode(&data, filter)),
+            };
+        }
+        let mut info = self.info.clone();
+        info.filters = options.filters.iter().chain(&filters[keep ..]).cloned().collect();
+        Ok(Stream { info, inner_data: StreamData::Generated(data.into()) })
+    }
+}
+
 impl<I: Object + fmt::Debug> fmt::Debug for Stream<I> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "Stream info={:?}, len={}", self.info.info, self.len())
@@ -128,6 +205,7 @@ impl<I: ObjectWrite> Stream<I> {
                     StreamFilter::DCTDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::CCITTFaxDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::JBIG2Decode(ref p) => Some(p.to_primitive(update)?),
+                    StreamFilter::Other { ref params, .. } if !params.is_empty() => Some(params.clone().into()),
                     _ => None
                 } {
                     assert!(params.is_none());
@@ -145,6 +223,9 @@ impl<I: ObjectWrite> Stream<I> {
                 StreamFilter::JBIG2Decode(ref _p) => "JBIG2Decode",
                 StreamFilter::Crypt => "Crypt",
                 StreamFilter::RunLengthDecode => "RunLengthDecode",
+                StreamFilter::ZstdDecode => "PDFRS_ZstdDecode",
+                StreamFilter::BrotliDecode => "PDFRS_BrotliDecode",
+                StreamFilter::Other { ref name, .. } => name.as_str(),
             })
             .map(|s| Primitive::Name(s.into()));
             match self.info.filters.len() {
@@ -205,6 +286,7 @@ impl<I: Object> Deref for Stream<I> {
 
 /// General stream type. `I` is the additional information to be read from the stream dict.
 #[derive(Debug, Clone, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StreamInfo<I> {
     // General dictionary entries
     /// Filters that the `data` is currently encoded with (corresponds to both `/Filter` and
@@ -212,7 +294,7 @@ pub struct StreamInfo<I> {
     pub filters: Vec<StreamFilter>,
 
     /// Eventual file containing the stream contentst
-    pub file: Option<FileSpec>,
+    pub file: Option<Box<FileSpec>>,
     /// Filters to apply to external file specified in `file`.
     pub file_filters: Vec<StreamFilter>,
 
@@ -264,29 +346,30 @@ impl<T> StreamInfo<T> {
 impl<T: Object> Object for StreamInfo<T> {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let mut dict = Dictionary::from_primitive(p, resolve)?;
+        // keep the other keys in order, so the stream is written back the way it was read
 
         let _length = usize::from_primitive(
-            dict.remove("Length").ok_or(PdfError::MissingEntry{ typ: "StreamInfo", field: "Length".into() })?,
+            dict.shift_remove("Length").ok_or(PdfError::MissingEntry{ typ: "StreamInfo", field: "Length".into() })?,
             resolve)?;
 
         let filters = Vec::<Name>::from_primitive(
-            dict.remove("Filter").unwrap_or(Primitive::Null),
+            dict.shift_remove("Filter").unwrap_or(Primitive::Null),
             resolve)?;
 
         let decode_params = Vec::<Option<Dictionary>>::from_primitive(
-            dict.remove("DecodeParms").unwrap_or(Primitive::Null),
+            dict.shift_remove("DecodeParms").unwrap_or(Primitive::Null),
             resolve)?;
 
-        let file = Option::<FileSpec>::from_primitive(
-            dict.remove("F").unwrap_or(Primitive::Null),
+        let file = Option::<Box<FileSpec>>::from_primitive(
+            dict.shift_remove("F").unwrap_or(Primitive::Null),
             resolve)?;
 
         let file_filters = Vec::<Name>::from_primitive(
-            dict.remove("FFilter").unwrap_or(Primitive::Null),
+            dict.shift_remove("FFilter").unwrap_or(Primitive::Null),
             resolve)?;
 
         let file_decode_params = Vec::<Dictionary>::from_primitive(
-            dict.remove("FDecodeParms").unwrap_or(Primitive::Null),
+            dict.shift_remove("FDecodeParms").unwrap_or(Primitive::Null),
             resolve)?;
 
 
@@ -392,3 +475,56 @@ impl ObjectStream {
         self.inner.data(resolve)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::DCTDecodeParams;
+
+    #[test]
+    fn stream_options() {
+        let text = b"BT /F1 12 Tf (hello) Tj ET ".repeat(20);
+        let stream = Stream::new((), text.clone());
+        for options in [StreamOptions::hex(), StreamOptions::flate(FlateLevel::None), StreamOptions::flate(FlateLevel::Best)] {
+            let encoded = stream.encoded(&options, &NoResolve).unwrap();
+            assert_eq!(encoded.info.filters.len(), 1);
+            assert_eq!(&*encoded.data(&NoResolve).unwrap(), &text[..]);
+        }
+        let best = stream.encoded(&StreamOptions::flate(FlateLevel::Best), &NoResolve).unwrap();
+        assert!(best.len() < text.len() / 4);
+
+        // the JPEG data is kept as it is when the other filters are replaced
+        let jpeg = b"\xff\xd8 not really a jpeg \xff\xd9";
+        let filters = vec![StreamFilter::ASCIIHexDecode, StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None })];
+        let image = Stream::from_compressed((), crate::enc::encode_hex(jpeg), filters);
+        let plain = image.encoded(&StreamOptions::uncompressed(), &NoResolve).unwrap();
+        assert!(matches!(plain.info.filters[..], [StreamFilter::DCTDecode(_)]));
+        assert!(matches!(plain.inner_data, StreamData::Generated(ref data) if &data[..] == jpeg));
+        let mut options = StreamOptions::uncompressed();
+        options.filters.push(StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None }));
+        assert!(image.encoded(&options, &NoResolve).is_err());
+    }
+
+    #[test]
+    fn key_order() {
+        let p = crate::parser::parse(b"<< /A 1 /Length 0 /B 2 /Filter /FlateDecode /C 3 >>", &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+        let info = StreamInfo::<Dictionary>::from_primitive(p, &NoResolve).unwrap();
+        let keys: Vec<&str> = info.info.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["A", "B", "C"]);
+    }
+
+    #[cfg(all(feature = "zstd", feature = "brotli"))]
+    #[test]
+    fn vendor_filters() {
+        let text = b"q 1 0 0 1 10 10 cm /Im0 Do Q ".repeat(50);
+        let stream = Stream::new((), text.clone());
+        for options in [StreamOptions::zstd(), StreamOptions::brotli()] {
+            let encoded = stream.encoded(&options, &NoResolve).unwrap();
+            assert!(encoded.info.filters[0].is_vendor());
+            assert!(encoded.len() < text.len() / 4);
+            let written = encoded.to_pdf_stream(&mut NoUpdate).unwrap();
+            let read = Stream::<()>::from_stream(written, &NoResolve).unwrap();
+            assert_eq!(&*read.data(&NoResolve).unwrap(), &text[..]);
+        }
+    }
+}
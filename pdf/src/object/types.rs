@@ -3,10 +3,11 @@
 use datasize::DataSize;
 use prelude::Font;
 use std::collections::HashMap;
+use std::fmt;
 
 use crate as pdf;
 use crate::content::deep_clone_op;
-use crate::content::{parse_ops, serialize_ops, Content, FormXObject, Matrix, Op};
+use crate::content::{parse_ops, serialize_ops, Content, FormXObject, Matrix, Op, Point, ViewRect};
 use crate::error::*;
 use crate::object::*;
 
@@ -30,18 +31,33 @@ macro_rules! mods {
 
 // too lazy to keep two sets of mod declarations and imports syncronized, so a macro it is ..
 mods!(
+    annot,
+    collection,
     dest,
+    embedded,
     form,
     graphicsstate,
+    measure,
+    media,
     nametree,
     numbertree,
     outline,
     page,
     pagesnode,
     pattern,
+    perms,
+    pieceinfo,
+    requirements,
+    shading,
     structtree,
+    thread,
+    threed,
     xobject
 );
+#[cfg(feature = "legacy")]
+mod legacy;
+#[cfg(feature = "legacy")]
+pub use legacy::*;
 /*
 use std::iter::once;
 use itertools::Either;
@@ -59,6 +75,7 @@ impl PagesNode {
 /// A `PagesNode::Leaf` wrapped in a `RcRef`
 ///
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PageRc(RcRef<PagesNode>);
 impl Deref for PageRc {
     type Target = Page;
@@ -98,6 +115,10 @@ impl ObjectWrite for PageRc {
     }
 }
 
+/// The root of the document.
+///
+/// The entries added to guide viewers and tools are `lenient`: an invalid one is left out with a
+/// warning, so it doesn't make the whole document unreadable.
 #[derive(Object, ObjectWrite, Debug, DataSize)]
 #[pdf(Type = "Catalog?")]
 pub struct Catalog {
@@ -116,13 +137,23 @@ pub struct Catalog {
     #[pdf(key = "Dests")]
     pub dests: Option<MaybeRef<Dictionary>>,
 
-    // ViewerPreferences: dict
-    // PageLayout: name
-    // PageMode: name
+    #[pdf(key = "ViewerPreferences", lenient)]
+    pub viewer_preferences: Option<ViewerPreferences>,
+
+    #[pdf(key = "PageLayout", lenient)]
+    pub page_layout: Option<PageLayout>,
+
+    #[pdf(key = "PageMode", lenient)]
+    pub page_mode: Option<PageMode>,
+
     #[pdf(key = "Outlines")]
     pub outlines: Option<Outlines>,
-    // Threads: array
-    // OpenAction: array or dict
+    #[pdf(key = "Threads", lenient)]
+    pub threads: Option<Vec<Ref<Thread>>>,
+
+    #[pdf(key = "OpenAction", lenient)]
+    pub open_action: Option<OpenAction>,
+
     // AA: dict
     // URI: dict
     // AcroForm: dict
@@ -133,21 +164,95 @@ pub struct Catalog {
     #[pdf(key = "Metadata")]
     pub metadata: Option<Ref<Stream<()>>>,
 
-    #[pdf(key = "StructTreeRoot")]
-    pub struct_tree_root: Option<StructTreeRoot>,
+    #[pdf(key = "StructTreeRoot", lenient)]
+    pub struct_tree_root: Option<MaybeRef<StructTreeRoot>>,
+
+    #[pdf(key = "MarkInfo", lenient)]
+    pub mark_info: Option<MarkInformation>,
+
+    /// default natural language of the document, like `en-US`
+    #[pdf(key = "Lang", lenient)]
+    pub lang: Option<PdfString>,
+
+    #[pdf(key = "OutputIntents", lenient)]
+    pub output_intents: Option<Vec<OutputIntent>>,
+
+    /// makes the document a portfolio of its embedded files
+    #[pdf(key = "Collection", lenient)]
+    pub collection: Option<Collection>,
+
+    /// features a viewer needs to process the document correctly
+    #[pdf(key = "Requirements", lenient)]
+    pub requirements: Vec<Requirement>,
+
+    /// the developer extensions the document uses
+    #[pdf(key = "Extensions", lenient)]
+    pub extensions: Option<Extensions>,
+
+    /// the signatures that restrict what can be changed, see [`Catalog::certification`]
+    #[pdf(key = "Perms", lenient)]
+    pub perms: Option<Permissions>,
+
+    /// private data of the applications that edited the document
+    #[pdf(key = "PieceInfo", lenient)]
+    pub piece_info: Option<PieceInfo>,
+
+    /// web capture information; typed with the `legacy` feature
+    #[pdf(key = "SpiderInfo", lenient)]
+    pub spider_info: Option<Dictionary>,
+
+    /// the legal attestation of a signed document; typed with the `legacy` feature
+    #[pdf(key = "Legal", lenient)]
+    pub legal: Option<Dictionary>,
 
-// MarkInfo: dict
-// Lang: text string
-// SpiderInfo: dict
-// OutputIntents: array
-// PieceInfo: dict
 // OCProperties: dict
-// Perms: dict
-// Legal: dict
-// Requirements: array
-// Collection: dict
 // NeedsRendering: bool
 }
+impl Catalog {
+    /// The page label ranges, sorted by the index of their first page.
+    fn page_label_ranges(&self, resolve: &impl Resolve) -> Result<Vec<(u32, PageLabel)>> {
+        let mut ranges = vec![];
+        if let Some(ref labels) = self.page_labels {
+            labels.walk(resolve, &mut |idx, label| {
+                if idx >= 0 {
+                    ranges.push((idx as u32, label.clone()));
+                }
+            })?;
+        }
+        ranges.sort_by_key(|&(idx, _)| idx);
+        Ok(ranges)
+    }
+
+    /// The label of the page at `page_index` (counting from zero).
+    ///
+    /// Without a `PageLabels` entry (or before the first range), this is the page number.
+    pub fn page_label(&self, page_index: u32, resolve: &impl Resolve) -> Result<String> {
+        let ranges = self.page_label_ranges(resolve)?;
+        Ok(match ranges.iter().rev().find(|&&(start, _)| start <= page_index) {
+            Some((start, label)) => label.format((page_index - start) as usize),
+            None => (page_index + 1).to_string(),
+        })
+    }
+
+    /// Find the index of the page labelled `label`.
+    pub fn page_index_of_label(&self, label: &str, resolve: &impl Resolve) -> Result<Option<u32>> {
+        let ranges = self.page_label_ranges(resolve)?;
+        let count = self.pages.count;
+        if ranges.is_empty() {
+            return Ok(label.parse::<u32>().ok().filter(|&n| n >= 1 && n <= count).map(|n| n - 1));
+        }
+        for (i, &(start, ref page_label)) in ranges.iter().enumerate() {
+            let end = ranges.get(i + 1).map(|&(next, _)| next).unwrap_or(count);
+            if let Some(offset) = page_label.parse(label) {
+                let index = start as usize + offset;
+                if index < end as usize {
+                    return Ok(Some(index as u32));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
 
 
 #[derive(Object, ObjectWrite, Debug, DataSize, Default, DeepClone, Clone)]
@@ -161,7 +266,9 @@ pub struct Resources {
     #[pdf(key = "Pattern")]
     pub pattern: HashMap<Name, Ref<Pattern>>,
 
-    // shading: Option<Shading>,
+    #[pdf(key = "Shading")]
+    pub shadings: HashMap<Name, MaybeRef<Shading>>,
+
     #[pdf(key = "XObject")]
     pub xobjects: HashMap<Name, Ref<XObject>>,
     // /XObject is a dictionary that map arbitrary names to XObjects
@@ -215,13 +322,13 @@ pub struct FormDict {
     pub bbox: Rectangle,
 
     #[pdf(key="Matrix")]
-    pub matrix: Option<Primitive>,
+    pub matrix: Option<Matrix>,
 
     #[pdf(key="Resources")]
     pub resources: Option<MaybeRef<Resources>>,
 
     #[pdf(key="Group")]
-    pub group: Option<Dictionary>,
+    pub group: Option<Box<TransparencyGroup>>,
 
     #[pdf(key="Ref")]
     pub reference: Option<Dictionary>,
@@ -230,7 +337,7 @@ pub struct FormDict {
     pub metadata: Option<Ref<Stream<()>>>,
 
     #[pdf(key="PieceInfo")]
-    pub piece_info: Option<Dictionary>,
+    pub piece_info: Option<PieceInfo>,
 
     #[pdf(key="StructParent")]
     pub struct_parent: Option<i32>,
@@ -304,7 +411,7 @@ pub struct SeedValueDictionary {
     pub other: Dictionary
 }
 
-#[derive(Object, ObjectWrite, Debug)]
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
 #[pdf(Type="Sig?")]
 pub struct SignatureDictionary {
     #[pdf(key="Filter")]
@@ -329,21 +436,111 @@ pub struct SignatureDictionary {
     pub reason: Option<PdfString>,
     #[pdf(key="ContactInfo")]
     pub contact_info: Option<PdfString>,
-    #[pdf(key="V")]
+    #[pdf(key="V", default="0")]
     pub v: i32,
     #[pdf(key="R")]
-    pub r: i32,
+    pub r: Option<i32>,
     #[pdf(key="Prop_Build")]
-    pub prop_build: Dictionary,
+    pub prop_build: Option<SignatureBuildProperties>,
     #[pdf(key="Prop_AuthTime")]
-    pub prop_auth_time: i32,
+    pub prop_auth_time: Option<i32>,
     #[pdf(key="Prop_AuthType")]
-    pub prop_auth_type: Name,
+    pub prop_auth_type: Option<Name>,
     #[pdf(other)]
     pub other: Dictionary
 }
 
-#[derive(Object, ObjectWrite, Debug)]
+/// The software that created a signature (Adobe's "PDF Signature Build Dictionary Specification").
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize)]
+pub struct SignatureBuildProperties {
+    /// the signature handler
+    #[pdf(key="Filter")]
+    pub filter: Option<BuildData>,
+
+    /// the public-key security handler
+    #[pdf(key="PubSec")]
+    pub pub_sec: Option<BuildData>,
+
+    /// the application that created the signature
+    #[pdf(key="App")]
+    pub app: Option<BuildData>,
+
+    /// the signature quality module
+    #[pdf(key="SigQ")]
+    pub sig_q: Option<BuildData>,
+
+    #[pdf(other)]
+    pub other: Dictionary
+}
+
+/// Name and version of one software module in a [`SignatureBuildProperties`].
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize)]
+pub struct BuildData {
+    #[pdf(key="Name")]
+    pub name: Option<Name>,
+
+    /// build date of the module
+    #[pdf(key="Date")]
+    pub date: Option<PdfString>,
+
+    /// revision number
+    #[pdf(key="R")]
+    pub revision: Option<f32>,
+
+    /// revision as text, like `"11.0.0"`
+    #[pdf(key="REx")]
+    pub revision_text: Option<PdfString>,
+
+    #[pdf(key="PreRelease", default="false")]
+    pub pre_release: bool,
+
+    /// operating systems the module ran on
+    #[pdf(key="OS")]
+    pub os: Option<Vec<Name>>,
+
+    /// minimum version of the handler needed to validate the signature
+    #[pdf(key="V")]
+    pub min_version: Option<f32>,
+
+    #[pdf(key="NonEFontNoWarn")]
+    pub non_embedded_font_no_warn: Option<bool>,
+
+    #[pdf(key="TrustedMode")]
+    pub trusted_mode: Option<bool>,
+
+    #[pdf(other)]
+    pub other: Dictionary
+}
+impl BuildData {
+    /// `REx` if present, otherwise `R`.
+    pub fn version(&self) -> Option<String> {
+        match (&self.revision_text, self.revision) {
+            (Some(text), _) => Some(text.to_string_lossy()),
+            (None, Some(r)) => Some(r.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+impl fmt::Display for BuildData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name.as_deref().unwrap_or("unknown"))?;
+        if let Some(version) = self.version() {
+            write!(f, " {}", version)?;
+        }
+        if self.pre_release {
+            f.write_str(" (pre-release)")?;
+        }
+        Ok(())
+    }
+}
+impl SignatureBuildProperties {
+    /// The application that produced the signature, falling back to the signature handler.
+    pub fn software(&self) -> Option<&BuildData> {
+        self.app.as_ref().or(self.filter.as_ref())
+    }
+}
+
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
 #[pdf(Type="SigRef?")]
 pub struct SignatureReferenceDictionary {
     #[pdf(key="TransformMethod")]
@@ -390,7 +587,13 @@ pub struct Annot {
     pub appearance_state: Option<Name>,
 
     #[pdf(key="Border")]
-    pub border: Option<Primitive>,
+    pub border: Option<BorderArray>,
+
+    #[pdf(key="BS")]
+    pub border_style: Option<BorderStyle>,
+
+    #[pdf(key="BE")]
+    pub border_effect: Option<BorderEffect>,
 
     #[pdf(key="C")]
     pub color: Option<Primitive>,
@@ -401,6 +604,17 @@ pub struct Annot {
     #[pdf(key="L")]
     pub line: Option<Primitive>,
 
+    /// The popup window showing the text of this (markup) annotation.
+    #[pdf(key="Popup")]
+    pub popup: Option<Ref<Annot>>,
+
+    /// The annotation this one replies to, or is grouped with, see `reply_type`.
+    #[pdf(key="IRT")]
+    pub in_reply_to: Option<Ref<Annot>>,
+
+    #[pdf(key="RT")]
+    pub reply_type: Option<ReplyType>,
+
     #[pdf(other)]
     pub other: Dictionary,
 }
@@ -431,11 +645,12 @@ pub struct FieldDictionary {
     #[pdf(key="SigFlags", default="0")]
     pub sig_flags: u32,
 
+    /// `Absent` inherits the value of the parent field, while `Null` has no value.
     #[pdf(key="V")]
-    pub value: Primitive,
+    pub value: Nullable<Primitive>,
 
     #[pdf(key="DV")]
-    pub default_value: Primitive,
+    pub default_value: Nullable<Primitive>,
 
     #[pdf(key="DR")]
     pub default_resources: Option<MaybeRef<Resources>>,
@@ -452,10 +667,165 @@ pub struct FieldDictionary {
     #[pdf(key="Subtype")]
     pub subtype: Option<Name>,
 
+    /// Choices of a choice field, or the export values of the widgets of a check box or radio button.
+    #[pdf(key="Opt")]
+    pub options: Option<Vec<FieldOption>>,
+
     #[pdf(other)]
     pub other: Dictionary
 }
 
+/// An entry of the `Opt` array of a field.
+#[derive(Debug, Clone, PartialEq, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldOption {
+    /// the value stored in `V`
+    pub export: PdfString,
+    /// the text shown to the user, if it differs from the export value
+    pub display: Option<PdfString>,
+}
+impl FieldOption {
+    pub fn new(export: &str, display: Option<&str>) -> FieldOption {
+        FieldOption {
+            export: text_string(export),
+            display: display.map(text_string),
+        }
+    }
+    pub fn display(&self) -> &PdfString {
+        self.display.as_ref().unwrap_or(&self.export)
+    }
+}
+impl Object for FieldOption {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Array(parts) => match <[Primitive; 2]>::try_from(parts) {
+                Ok([export, display]) => Ok(FieldOption {
+                    export: PdfString::from_primitive(export, resolve)?,
+                    display: Some(PdfString::from_primitive(display, resolve)?),
+                }),
+                Err(parts) => bail!("field option with {} entries", parts.len()),
+            },
+            p => Ok(FieldOption { export: PdfString::from_primitive(p, resolve)?, display: None }),
+        }
+    }
+}
+impl ObjectWrite for FieldOption {
+    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
+        Ok(match self.display {
+            Some(ref display) => Primitive::Array(vec![self.export.clone().into(), display.clone().into()]),
+            None => self.export.clone().into(),
+        })
+    }
+}
+
+/// Encode `s` as a PDF text string: plain bytes if it is ASCII, UTF-16BE otherwise.
+pub(crate) fn text_string(s: &str) -> PdfString {
+    if s.is_ascii() {
+        return s.into();
+    }
+    let mut data = vec![0xfe, 0xff];
+    data.extend(s.encode_utf16().flat_map(|c| c.to_be_bytes()));
+    PdfString::new(data.into())
+}
+
+/// The typed value (`V`) of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    None,
+    /// text fields and single-selection choice fields
+    Text(PdfString),
+    /// multiple-selection choice fields
+    Multiple(Vec<PdfString>),
+    /// the appearance state of a check box or radio button
+    State(Name),
+}
+
+impl FieldValue {
+    pub(crate) fn from_value(value: Option<&Primitive>) -> FieldValue {
+        match value {
+            Some(Primitive::String(s)) => FieldValue::Text(s.clone()),
+            Some(Primitive::Name(n)) => FieldValue::State(Name(n.clone())),
+            Some(Primitive::Array(parts)) => FieldValue::Multiple(
+                parts.iter().filter_map(|p| p.as_string().ok().cloned()).collect()
+            ),
+            _ => FieldValue::None,
+        }
+    }
+}
+
+impl FieldDictionary {
+    pub fn field_value(&self) -> FieldValue {
+        FieldValue::from_value(self.value.value())
+    }
+
+    /// The export values of the field, decoded.
+    ///
+    /// For check boxes and radio buttons with an `Opt` array, the state names are indices into it,
+    /// which is how export values that can't be names (like non-ASCII text) are stored.
+    pub fn export_values(&self) -> Vec<String> {
+        match self.field_value() {
+            FieldValue::None => vec![],
+            FieldValue::Text(s) => vec![s.to_string_lossy()],
+            FieldValue::Multiple(v) => v.iter().map(|s| s.to_string_lossy()).collect(),
+            FieldValue::State(name) if &*name == "Off" => vec![],
+            FieldValue::State(name) => {
+                let option = self.options.as_ref()
+                    .zip(name.parse::<usize>().ok())
+                    .and_then(|(options, i)| options.get(i));
+                match option {
+                    Some(option) => vec![option.export.to_string_lossy()],
+                    None => vec![name.to_string()],
+                }
+            }
+        }
+    }
+
+    /// The values as shown to the user: the display text of the selected options of a choice field,
+    /// the export values otherwise.
+    pub fn display_values(&self) -> Vec<String> {
+        let options = match (self.typ, self.options.as_ref()) {
+            (Some(FieldType::Choice), Some(options)) => options,
+            _ => return self.export_values(),
+        };
+        self.export_values().into_iter().map(|value| {
+            options.iter()
+                .find(|o| o.export.to_string_lossy() == value)
+                .map(|o| o.display().to_string_lossy())
+                .unwrap_or(value)
+        }).collect()
+    }
+
+    /// Set the value of a text or choice field. Several values select multiple choices.
+    ///
+    /// No values clear the field, which is written as `/V null` so a value of the parent isn't inherited.
+    pub fn set_text_value(&mut self, values: &[&str]) {
+        self.value = match *values {
+            [] => Nullable::Null,
+            [value] => Nullable::Value(text_string(value).into()),
+            _ => Nullable::Value(Primitive::Array(values.iter().map(|v| text_string(v).into()).collect())),
+        };
+    }
+
+    /// Select the check box or radio button state with the export value `export`, or turn it off with `None`.
+    ///
+    /// If the field has an `Opt` array, the state is the index of `export` in it,
+    /// otherwise `export` is used as the state name directly.
+    pub fn set_button_state(&mut self, export: Option<&str>) -> Result<()> {
+        let state = match (export, self.options.as_ref()) {
+            (None, _) => "Off".to_string(),
+            (Some(export), Some(options)) => {
+                match options.iter().position(|o| o.export.to_string_lossy() == export) {
+                    Some(i) => i.to_string(),
+                    None => bail!("no option with export value {:?}", export),
+                }
+            }
+            (Some(export), None) => export.to_string(),
+        };
+        self.value = Nullable::Value(Primitive::Name(Name::from(state).0));
+        Ok(())
+    }
+}
+
 #[derive(Object, ObjectWrite, Debug, DataSize, Clone, DeepClone)]
 pub struct AppearanceStreams {
     #[pdf(key="N")]
@@ -505,17 +875,18 @@ impl DataSize for AppearanceStreamEntry {
 pub enum Counter {
     #[pdf(name="D")]
     Arabic,
-    #[pdf(name="r")]
-    RomanUpper,
     #[pdf(name="R")]
+    RomanUpper,
+    #[pdf(name="r")]
     RomanLower,
-    #[pdf(name="a")]
-    AlphaUpper,
     #[pdf(name="A")]
+    AlphaUpper,
+    #[pdf(name="a")]
     AlphaLower
 }
 
 #[derive(Debug, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum NameTreeNode<T> {
     ///
     Intermediate (Vec<Ref<NameTree<T>>>),
@@ -526,12 +897,17 @@ pub enum NameTreeNode<T> {
 /// Note: The PDF concept of 'root' node is an intermediate or leaf node which has no 'Limits'
 /// entry. Hence, `limits`,
 #[derive(Debug, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameTree<T> {
     pub limits: Option<(PdfString, PdfString)>,
     pub node: NameTreeNode<T>,
 }
 impl<T: Object+DataSize> NameTree<T> {
+    /// Call `callback` for each entry, in order. Fails with `ReferenceCycle` if a node is its own descendant.
     pub fn walk(&self, r: &impl Resolve, callback: &mut dyn FnMut(&PdfString, &T)) -> Result<(), PdfError> {
+        self.walk_in(r, callback, &mut vec![])
+    }
+    fn walk_in(&self, r: &impl Resolve, callback: &mut dyn FnMut(&PdfString, &T), path: &mut Vec<PlainRef>) -> Result<(), PdfError> {
         match self.node {
             NameTreeNode::Leaf(ref items) => {
                 for (name, val) in items {
@@ -541,7 +917,9 @@ impl<T: Object+DataSize> NameTree<T> {
             NameTreeNode::Intermediate(ref items) => {
                 for &tree_ref in items {
                     let tree = r.get(tree_ref)?;
-                    tree.walk(r, callback)?;
+                    enter_node(path, tree_ref.get_inner(), r)?;
+                    tree.walk_in(r, callback, path)?;
+                    path.pop();
                 }
             }
         }
@@ -605,18 +983,127 @@ impl<T: Object> Object for NameTree<T> {
 }
 
 impl<T: ObjectWrite> ObjectWrite for NameTree<T> {
-    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
-        todo!("impl ObjectWrite for NameTree")
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        if let Some((ref min, ref max)) = self.limits {
+            dict.insert("Limits", vec![min.clone().into(), max.clone().into()]);
+        }
+        match self.node {
+            NameTreeNode::Leaf(ref items) => {
+                let mut names = Vec::with_capacity(items.len() * 2);
+                for (name, value) in items {
+                    names.push(name.clone().into());
+                    names.push(value.to_primitive(update)?);
+                }
+                dict.insert("Names", names);
+            }
+            NameTreeNode::Intermediate(ref kids) => {
+                dict.insert("Kids", kids.iter().map(|r| r.get_inner().into()).collect_vec());
+            }
+        }
+        Ok(dict.into())
+    }
+}
+
+impl<T: Object + DataSize + Clone> NameTree<T> {
+    /// Create a tree with a single node holding `entries`.
+    pub fn from_entries(mut entries: Vec<(PdfString, T)>) -> NameTree<T> {
+        entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        entries.dedup_by(|a, b| a.0.as_bytes() == b.0.as_bytes());
+        NameTree {
+            limits: None,
+            node: NameTreeNode::Leaf(entries),
+        }
+    }
+
+    /// All entries of the tree, in order.
+    pub fn entries(&self, resolve: &impl Resolve) -> Result<Vec<(PdfString, T)>> {
+        let mut entries = vec![];
+        self.walk(resolve, &mut |name, value| entries.push((name.clone(), value.clone())))?;
+        Ok(entries)
+    }
+
+    pub fn get(&self, name: &[u8], resolve: &impl Resolve) -> Result<Option<T>> {
+        self.get_in(name, resolve, &mut vec![])
+    }
+    fn get_in(&self, name: &[u8], resolve: &impl Resolve, path: &mut Vec<PlainRef>) -> Result<Option<T>> {
+        match self.node {
+            NameTreeNode::Leaf(ref items) => {
+                Ok(items.iter().find(|(n, _)| n.as_bytes() == name).map(|(_, value)| value.clone()))
+            }
+            NameTreeNode::Intermediate(ref kids) => {
+                for &kid_ref in kids {
+                    let kid = t!(resolve.get(kid_ref));
+                    let in_range = match kid.limits {
+                        Some((ref min, ref max)) => min.as_bytes() <= name && name <= max.as_bytes(),
+                        None => true,
+                    };
+                    if in_range {
+                        enter_node(path, kid_ref.get_inner(), resolve)?;
+                        if let Some(value) = kid.get_in(name, resolve, path)? {
+                            return Ok(Some(value));
+                        }
+                        path.pop();
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Add or replace the entry `name`, returning the old value.
+    ///
+    /// A tree with intermediate nodes is collapsed into a single node, the old nodes are left alone.
+    pub fn insert(&mut self, name: PdfString, value: T, resolve: &impl Resolve) -> Result<Option<T>> {
+        let mut entries = self.entries(resolve)?;
+        let old = match entries.binary_search_by(|(n, _)| n.as_bytes().cmp(name.as_bytes())) {
+            Ok(i) => Some(std::mem::replace(&mut entries[i].1, value)),
+            Err(i) => {
+                entries.insert(i, (name, value));
+                None
+            }
+        };
+        *self = NameTree::from_entries(entries);
+        Ok(old)
+    }
+
+    /// Remove the entry `name`, returning its value.
+    ///
+    /// A tree with intermediate nodes is collapsed into a single node, the old nodes are left alone.
+    pub fn remove(&mut self, name: &[u8], resolve: &impl Resolve) -> Result<Option<T>> {
+        let mut entries = self.entries(resolve)?;
+        let old = match entries.iter().position(|(n, _)| n.as_bytes() == name) {
+            Some(i) => entries.remove(i).1,
+            None => return Ok(None),
+        };
+        *self = NameTree::from_entries(entries);
+        Ok(Some(old))
+    }
+
+    /// Change the name of an entry. Fails if `new` is already used.
+    pub fn rename(&mut self, old: &[u8], new: PdfString, resolve: &impl Resolve) -> Result<bool> {
+        if self.get(new.as_bytes(), resolve)?.is_some() {
+            bail!("name tree already contains {:?}", new);
+        }
+        match self.remove(old, resolve)? {
+            Some(value) => {
+                self.insert(new, value, resolve)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
     }
 }
 
 #[derive(DataSize, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NumberTree<T> {
     pub limits: Option<(i32, i32)>,
     pub node: NumberTreeNode<T>,
 }
 
 #[derive(DataSize, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum NumberTreeNode<T> {
     Leaf(Vec<(i32, T)>),
     Intermediate(Vec<Ref<NumberTree<T>>>),
@@ -697,7 +1184,11 @@ impl<T: ObjectWrite> ObjectWrite for NumberTree<T> {
     }
 }
 impl<T: Object+DataSize> NumberTree<T> {
+    /// Call `callback` for each entry, in order. Fails with `ReferenceCycle` if a node is its own descendant.
     pub fn walk(&self, r: &impl Resolve, callback: &mut dyn FnMut(i32, &T)) -> Result<(), PdfError> {
+        self.walk_in(r, callback, &mut vec![])
+    }
+    fn walk_in(&self, r: &impl Resolve, callback: &mut dyn FnMut(i32, &T), path: &mut Vec<PlainRef>) -> Result<(), PdfError> {
         match self.node {
             NumberTreeNode::Leaf(ref items) => {
                 for &(idx, ref val) in items {
@@ -707,7 +1198,9 @@ impl<T: Object+DataSize> NumberTree<T> {
             NumberTreeNode::Intermediate(ref items) => {
                 for &tree_ref in items {
                     let tree = r.get(tree_ref)?;
-                    tree.walk(r, callback)?;
+                    enter_node(path, tree_ref.get_inner(), r)?;
+                    tree.walk_in(r, callback, path)?;
+                    path.pop();
                 }
             }
         }
@@ -715,19 +1208,8 @@ impl<T: Object+DataSize> NumberTree<T> {
     }
 }
 
-#[derive(Object, ObjectWrite, Clone, DeepClone, Debug)]
-pub struct LageLabel {
-    #[pdf(key = "S")]
-    style: Option<Counter>,
-
-    #[pdf(key = "P")]
-    prefix: Option<PdfString>,
-
-    #[pdf(key = "St")]
-    start: Option<i32>,
-}
-
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DestView {
     // left, top, zoom
     XYZ {
@@ -783,76 +1265,96 @@ pub struct NameDictionary {
     */
 }
 
-/* Embedded file streams can be associated with the document as a whole through
- * the EmbeddedFiles entry (PDF 1.4) in the PDF document’s name dictionary
- * (see Section 3.6.3, “Name Dictionary”).
- * The associated name tree maps name strings to file specifications that refer
- * to embedded file streams through their EF entries.
-*/
-
-#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
-pub struct FileSpec {
-    #[pdf(key = "EF")]
-    pub ef: Option<Files<Ref<Stream<EmbeddedFile>>>>,
-    /*
-    #[pdf(key="RF")]
-    rf: Option<Files<RelatedFilesArray>>,
-    */
+/// Where the code of a document-level script is stored.
+#[derive(Debug, Clone)]
+pub enum ScriptSource {
+    String(PdfString),
+    Stream(RcRef<Stream<()>>),
 }
 
-/// Used only as elements in `FileSpec`
-#[derive(Object, ObjectWrite, Debug, Clone, DeepClone)]
-pub struct Files<T> {
-    #[pdf(key = "F")]
-    pub f: Option<T>,
-    #[pdf(key = "UF")]
-    pub uf: Option<T>,
-    #[pdf(key = "DOS")]
-    pub dos: Option<T>,
-    #[pdf(key = "Mac")]
-    pub mac: Option<T>,
-    #[pdf(key = "Unix")]
-    pub unix: Option<T>,
-}
-impl<T: DataSize> DataSize for Files<T> {
-    const IS_DYNAMIC: bool = T::IS_DYNAMIC;
-    const STATIC_HEAP_SIZE: usize = 5 * Option::<T>::STATIC_HEAP_SIZE;
-
-    fn estimate_heap_size(&self) -> usize {
-        [&self.f, &self.uf, &self.dos, &self.mac, &self.unix]
-            .into_iter()
-            .filter_map(|o| o.as_ref())
-            .map(|t| t.estimate_heap_size())
-            .sum()
+/// A document-level JavaScript, from the `JavaScript` name tree.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub name: PdfString,
+    pub source: ScriptSource,
+}
+impl Script {
+    fn from_action(name: PdfString, action: &Primitive, resolve: &impl Resolve) -> Result<Script> {
+        let mut dict = t!(action.clone().resolve(resolve)?.into_dictionary());
+        let source = match t!(dict.require("JavaScript", "JS")) {
+            Primitive::String(s) => ScriptSource::String(s),
+            p => ScriptSource::Stream(t!(RcRef::from_primitive(p, resolve))),
+        };
+        Ok(Script { name, source })
+    }
+    /// The source code of the script.
+    pub fn code(&self, resolve: &impl Resolve) -> Result<String> {
+        match self.source {
+            ScriptSource::String(ref s) => Ok(s.to_string_lossy()),
+            ScriptSource::Stream(ref s) => {
+                let data = t!((**s).data(resolve));
+                Ok(PdfString::new(data.as_ref().into()).to_string_lossy())
+            }
+        }
     }
 }
 
-/// PDF Embedded File Stream.
-#[derive(Object, Debug, Clone, DataSize, DeepClone, ObjectWrite)]
-pub struct EmbeddedFile {
-    #[pdf(key = "Subtype")]
-    subtype: Option<Name>,
-
-    #[pdf(key = "Params")]
-    pub params: Option<EmbeddedFileParamDict>,
-}
+impl NameDictionary {
+    /// The document-level scripts, ordered by name.
+    pub fn scripts(&self, resolve: &impl Resolve) -> Result<Vec<Script>> {
+        let tree = match self.javascript {
+            Some(ref tree) => tree,
+            None => return Ok(vec![]),
+        };
+        tree.entries(resolve)?.into_iter()
+            .map(|(name, action)| Script::from_action(name, &action, resolve))
+            .collect()
+    }
 
-#[derive(Object, Debug, Clone, DataSize, DeepClone, ObjectWrite)]
-pub struct EmbeddedFileParamDict {
-    #[pdf(key = "Size")]
-    pub size: Option<i32>,
+    pub fn script(&self, name: &str, resolve: &impl Resolve) -> Result<Option<Script>> {
+        let tree = match self.javascript {
+            Some(ref tree) => tree,
+            None => return Ok(None),
+        };
+        match tree.get(name.as_bytes(), resolve)? {
+            Some(action) => Script::from_action(name.into(), &action, resolve).map(Some),
+            None => Ok(None),
+        }
+    }
 
-    #[pdf(key = "CreationDate")]
-    creationdate: Option<Date>,
+    /// Add (or replace) the script `name`.
+    ///
+    /// With `as_stream` the code is written to a separate stream, otherwise it is stored as a string.
+    pub fn add_script(&mut self, name: &str, code: &str, as_stream: bool, resolve: &impl Resolve, update: &mut impl Updater) -> Result<()> {
+        let js = if as_stream {
+            Primitive::Reference(update.create(Stream::new((), code.as_bytes().to_vec()))?.get_ref().get_inner())
+        } else {
+            PdfString::from(code).into()
+        };
+        let mut action = Dictionary::new();
+        action.insert("S", Name::from("JavaScript"));
+        action.insert("JS", js);
 
-    #[pdf(key = "ModDate")]
-    moddate: Option<Date>,
+        let tree = self.javascript.get_or_insert_with(|| NameTree::from_entries(vec![]));
+        tree.insert(name.into(), action.into(), resolve)?;
+        Ok(())
+    }
 
-    #[pdf(key = "Mac")]
-    mac: Option<Date>,
+    /// Returns whether the script existed.
+    pub fn remove_script(&mut self, name: &str, resolve: &impl Resolve) -> Result<bool> {
+        match self.javascript {
+            Some(ref mut tree) => Ok(tree.remove(name.as_bytes(), resolve)?.is_some()),
+            None => Ok(false),
+        }
+    }
 
-    #[pdf(key = "CheckSum")]
-    checksum: Option<PdfString>,
+    /// Returns whether the script existed. Fails if there already is a script called `new`.
+    pub fn rename_script(&mut self, old: &str, new: &str, resolve: &impl Resolve) -> Result<bool> {
+        match self.javascript {
+            Some(ref mut tree) => tree.rename(old.as_bytes(), new.into(), resolve),
+            None => Ok(false),
+        }
+    }
 }
 
 /// ISO 32000-2:2020(E) 7.9.5 Rectangles (Pg 134)
@@ -864,7 +1366,8 @@ pub struct EmbeddedFileParamDict {
 /// (ur x , ll y ).
 /// Also see Table 74, key BBox definition Pg 221
 /// defining top, left, bottom, right labeling
-#[derive(Debug, Copy, Clone, DataSize, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, DataSize, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Rectangle {
     pub left: f32,
     pub bottom: f32,
@@ -872,6 +1375,88 @@ pub struct Rectangle {
     pub top: f32,
 }
 
+impl Rectangle {
+    /// The rectangle with the lower left corner at `x`, `y` and the given size.
+    pub fn from_size(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle { left: x, bottom: y, right: x + width, top: y + height }
+    }
+    /// The width, negative if `right` is left of `left`.
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+    /// The height, negative if `top` is below `bottom`.
+    pub fn height(&self) -> f32 {
+        self.top - self.bottom
+    }
+    /// The same rectangle with `left <= right` and `bottom <= top`.
+    ///
+    /// Any two opposite corners are a valid rectangle in a file, like a `BBox` of `[0 100 100 0]`.
+    /// The other methods normalize their inputs.
+    pub fn normalize(&self) -> Rectangle {
+        Rectangle {
+            left: self.left.min(self.right),
+            bottom: self.bottom.min(self.top),
+            right: self.left.max(self.right),
+            top: self.bottom.max(self.top),
+        }
+    }
+    /// Whether the rectangle has no area.
+    pub fn is_empty(&self) -> bool {
+        !(self.width() != 0. && self.height() != 0.)
+    }
+    /// The area both rectangles cover, `None` if they share no more than an edge.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        let (a, b) = (self.normalize(), other.normalize());
+        let r = Rectangle {
+            left: a.left.max(b.left),
+            bottom: a.bottom.max(b.bottom),
+            right: a.right.min(b.right),
+            top: a.top.min(b.top),
+        };
+        if r.left < r.right && r.bottom < r.top {
+            Some(r)
+        } else {
+            None
+        }
+    }
+    /// The smallest rectangle covering both.
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let (a, b) = (self.normalize(), other.normalize());
+        Rectangle {
+            left: a.left.min(b.left),
+            bottom: a.bottom.min(b.bottom),
+            right: a.right.max(b.right),
+            top: a.top.max(b.top),
+        }
+    }
+    /// Whether `p` is inside or on the edge.
+    pub fn contains(&self, p: Point) -> bool {
+        let r = self.normalize();
+        r.left <= p.x && p.x <= r.right && r.bottom <= p.y && p.y <= r.top
+    }
+    /// The bounding box of the rectangle transformed by `m`.
+    pub fn transform(&self, m: &Matrix) -> Rectangle {
+        let corners = [(self.left, self.bottom), (self.right, self.bottom), (self.right, self.top), (self.left, self.top)]
+            .map(|(x, y)| m.transform_point(Point { x, y }));
+        Rectangle {
+            left: corners.iter().map(|c| c.x).fold(f32::INFINITY, f32::min),
+            bottom: corners.iter().map(|c| c.y).fold(f32::INFINITY, f32::min),
+            right: corners.iter().map(|c| c.x).fold(f32::NEG_INFINITY, f32::max),
+            top: corners.iter().map(|c| c.y).fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+}
+impl From<ViewRect> for Rectangle {
+    fn from(r: ViewRect) -> Rectangle {
+        Rectangle::from_size(r.x, r.y, r.width, r.height)
+    }
+}
+impl From<Rectangle> for ViewRect {
+    fn from(r: Rectangle) -> ViewRect {
+        ViewRect { x: r.left, y: r.bottom, width: r.width(), height: r.height() }
+    }
+}
+
 impl Object for Rectangle {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         let arr = p.resolve(r)?.into_array()?;
@@ -911,6 +1496,180 @@ pub struct MarkInformation {
     pub suspects: bool,
 }
 
+/// How the pages are arranged when the document is opened.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum PageLayout {
+    SinglePage,
+    OneColumn,
+    TwoColumnLeft,
+    TwoColumnRight,
+    TwoPageLeft,
+    TwoPageRight,
+}
+
+/// What is shown next to the pages when the document is opened.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum PageMode {
+    UseNone,
+    UseOutlines,
+    UseThumbs,
+    FullScreen,
+    UseOC,
+    UseAttachments,
+}
+
+/// Page mode after leaving full-screen mode. Same as [`PageMode`] without the full-screen and attachments modes.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum NonFullScreenPageMode {
+    UseNone,
+    UseOutlines,
+    UseThumbs,
+    UseOC,
+}
+
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum ReadingDirection {
+    L2R,
+    R2L,
+}
+
+/// The page box shown (or printed) by a viewer.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum PageBoundary {
+    MediaBox,
+    CropBox,
+    BleedBox,
+    TrimBox,
+    ArtBox,
+}
+
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum PrintScaling {
+    #[pdf(name = "None")]
+    NoScaling,
+    AppDefault,
+}
+
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum Duplex {
+    Simplex,
+    DuplexFlipShortEdge,
+    DuplexFlipLongEdge,
+}
+
+/// How the document should be presented on screen or in print (PDF32000 12.2).
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, DeepClone, Default)]
+pub struct ViewerPreferences {
+    #[pdf(key = "HideToolbar", default = "false")]
+    pub hide_toolbar: bool,
+
+    #[pdf(key = "HideMenubar", default = "false")]
+    pub hide_menubar: bool,
+
+    #[pdf(key = "HideWindowUI", default = "false")]
+    pub hide_window_ui: bool,
+
+    #[pdf(key = "FitWindow", default = "false")]
+    pub fit_window: bool,
+
+    #[pdf(key = "CenterWindow", default = "false")]
+    pub center_window: bool,
+
+    /// show the document title instead of the file name in the title bar
+    #[pdf(key = "DisplayDocTitle", default = "false")]
+    pub display_doc_title: bool,
+
+    #[pdf(key = "NonFullScreenPageMode")]
+    pub non_full_screen_page_mode: Option<NonFullScreenPageMode>,
+
+    #[pdf(key = "Direction")]
+    pub direction: Option<ReadingDirection>,
+
+    #[pdf(key = "ViewArea")]
+    pub view_area: Option<PageBoundary>,
+
+    #[pdf(key = "ViewClip")]
+    pub view_clip: Option<PageBoundary>,
+
+    #[pdf(key = "PrintArea")]
+    pub print_area: Option<PageBoundary>,
+
+    #[pdf(key = "PrintClip")]
+    pub print_clip: Option<PageBoundary>,
+
+    #[pdf(key = "PrintScaling")]
+    pub print_scaling: Option<PrintScaling>,
+
+    #[pdf(key = "Duplex")]
+    pub duplex: Option<Duplex>,
+
+    #[pdf(key = "PickTrayByPDFSize")]
+    pub pick_tray_by_pdf_size: Option<bool>,
+
+    /// pairs of the first and last page (counting from one) of the ranges to print
+    #[pdf(key = "PrintPageRange")]
+    pub print_page_range: Option<Vec<i32>>,
+
+    #[pdf(key = "NumCopies")]
+    pub num_copies: Option<i32>,
+}
+impl ViewerPreferences {
+    /// `PrintPageRange` as inclusive ranges of page indices (counting from zero).
+    pub fn print_page_ranges(&self) -> Vec<std::ops::RangeInclusive<u32>> {
+        self.print_page_range.iter()
+            .flat_map(|r| r.chunks_exact(2))
+            .filter(|r| r[0] >= 1 && r[1] >= r[0])
+            .map(|r| (r[0] as u32 - 1)..=(r[1] as u32 - 1))
+            .collect()
+    }
+    pub fn set_print_page_ranges(&mut self, ranges: &[std::ops::RangeInclusive<u32>]) {
+        self.print_page_range = Some(ranges.iter()
+            .flat_map(|r| [*r.start() as i32 + 1, *r.end() as i32 + 1])
+            .collect());
+    }
+}
+
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, PartialEq, Eq)]
+pub enum OutputIntentSubtype {
+    /// PDF/X
+    #[pdf(name = "GTS_PDFX")]
+    PdfX,
+    /// PDF/A
+    #[pdf(name = "GTS_PDFA1")]
+    PdfA,
+    /// PDF/E
+    #[pdf(name = "ISO_PDFE1")]
+    PdfE,
+    #[pdf(other)]
+    Other(String),
+}
+
+/// The color characteristics of the output device the document is intended for (PDF32000 14.11.5).
+#[derive(Object, ObjectWrite, Debug, DataSize)]
+#[pdf(Type = "OutputIntent?")]
+pub struct OutputIntent {
+    #[pdf(key = "S")]
+    pub subtype: OutputIntentSubtype,
+
+    /// human readable name of the output condition
+    #[pdf(key = "OutputCondition")]
+    pub output_condition: Option<PdfString>,
+
+    /// identifies the output condition in the registry, like `FOGRA39`, or `Custom`
+    #[pdf(key = "OutputConditionIdentifier")]
+    pub output_condition_identifier: PdfString,
+
+    #[pdf(key = "RegistryName")]
+    pub registry_name: Option<PdfString>,
+
+    #[pdf(key = "Info")]
+    pub info: Option<PdfString>,
+
+    /// required unless the condition is in the registry
+    #[pdf(key = "DestOutputProfile")]
+    pub dest_output_profile: Option<RcRef<Stream<IccInfo>>>,
+}
+
 #[derive(Object, ObjectWrite, Debug, DataSize)]
 pub enum Trapped {
     True,
@@ -968,6 +1727,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn rectangle() {
+        // an inverted BBox, as some writers produce
+        let a = Rectangle { left: 0., bottom: 100., right: 100., top: 0. };
+        let b = Rectangle::from_size(50., 50., 100., 100.);
+        assert_eq!(a.normalize(), Rectangle { left: 0., bottom: 0., right: 100., top: 100. });
+        assert_eq!(a.height(), -100.);
+        assert_eq!(a.intersection(&b), Some(Rectangle { left: 50., bottom: 50., right: 100., top: 100. }));
+        assert_eq!(a.union(&b), Rectangle { left: 0., bottom: 0., right: 150., top: 150. });
+        assert_eq!(a.intersection(&Rectangle::from_size(100., 0., 10., 10.)), None);
+        assert!(a.contains(Point { x: 100., y: 20. }));
+        assert!(!b.contains(Point { x: 20., y: 20. }));
+
+        let rotate = Matrix { a: 0., b: 1., c: -1., d: 0., e: 0., f: 0. };
+        assert_eq!(b.transform(&rotate), Rectangle { left: -150., bottom: 50., right: -50., top: 150. });
+        let view: ViewRect = b.into();
+        assert_eq!(Rectangle::from(view), b);
+    }
+
     #[test]
     fn test_field_type() {
         assert_eq!(
@@ -975,4 +1753,49 @@ mod tests {
             FieldType::Text
         );
     }
+
+    #[test]
+    fn non_ascii_export_values() {
+        let mut dict = Dictionary::new();
+        dict.insert("FT", Name::from("Btn"));
+        dict.insert("Opt", Primitive::Array(vec![text_string("Ja").into(), text_string("Größe").into()]));
+        let mut field = FieldDictionary::from_primitive(dict.into(), &NoResolve).unwrap();
+        field.set_button_state(Some("Größe")).unwrap();
+        assert_eq!(field.field_value(), FieldValue::State("1".into()));
+        assert_eq!(field.export_values(), ["Größe"]);
+
+        let mut dict = Dictionary::new();
+        dict.insert("FT", Name::from("Ch"));
+        dict.insert("Opt", vec![FieldOption::new("de", Some("Deutsch")), FieldOption::new("ja", Some("日本語"))].to_primitive(&mut NoUpdate).unwrap());
+        let mut field = FieldDictionary::from_primitive(dict.into(), &NoResolve).unwrap();
+        field.set_text_value(&["ja"]);
+        assert_eq!(field.display_values(), ["日本語"]);
+        assert_eq!(field.options.as_ref().unwrap()[1].display().as_bytes()[..2], [0xfe, 0xff]);
+    }
+
+    #[test]
+    fn null_field_value() {
+        let mut dict = Dictionary::new();
+        dict.insert("FT", Name::from("Tx"));
+        let mut field = FieldDictionary::from_primitive(dict.clone().into(), &NoResolve).unwrap();
+        assert!(field.value.is_absent());
+        assert!(field.to_dict(&mut NoUpdate).unwrap().get("V").is_none());
+
+        field.set_text_value(&[]);
+        let written = field.to_dict(&mut NoUpdate).unwrap();
+        assert!(matches!(written.get("V"), Some(Primitive::Null)));
+        let field = FieldDictionary::from_primitive(written.into(), &NoResolve).unwrap();
+        assert!(field.value.is_null());
+    }
+
+    #[test]
+    fn build_properties() {
+        let mut app = Dictionary::new();
+        app.insert("Name", Name::from("Adobe Acrobat"));
+        app.insert("REx", PdfString::from("11.0.0"));
+        let mut dict = Dictionary::new();
+        dict.insert("App", app);
+        let props = SignatureBuildProperties::from_primitive(dict.into(), &NoResolve).unwrap();
+        assert_eq!(props.software().unwrap().to_string(), "Adobe Acrobat 11.0.0");
+    }
 }
@@ -0,0 +1,492 @@
+use super::prelude::*;
+use crate::content::{Color, Op, Point, Rgb, Winding};
+
+/// The annotation flag `Print`: the annotation is printed along with the page.
+pub const ANNOT_FLAG_PRINT: u32 = 1 << 2;
+
+/// The kinds of text markup annotations, see [`Annot::text_markup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMarkup {
+    Highlight,
+    Underline,
+    Squiggly,
+    StrikeOut,
+}
+
+impl TextMarkup {
+    pub fn subtype(self) -> &'static str {
+        match self {
+            TextMarkup::Highlight => "Highlight",
+            TextMarkup::Underline => "Underline",
+            TextMarkup::Squiggly => "Squiggly",
+            TextMarkup::StrikeOut => "StrikeOut",
+        }
+    }
+}
+
+/// How an annotation relates to the one in its `IRT` entry.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum ReplyType {
+    /// A reply to it, the default.
+    #[pdf(name = "R")]
+    Reply,
+    /// Grouped with it, to be shown and handled as one annotation.
+    Group,
+}
+
+/// The `Border` array of an annotation: the radii of the corners, the width and an optional dash pattern.
+///
+/// The border style dictionary (`BS`) takes precedence if both are present.
+#[derive(Debug, Clone, PartialEq, DataSize, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BorderArray {
+    pub horizontal_radius: f32,
+    pub vertical_radius: f32,
+    /// `0` draws no border.
+    pub width: f32,
+    pub dash: Option<Vec<f32>>,
+}
+impl Default for BorderArray {
+    fn default() -> Self {
+        BorderArray { horizontal_radius: 0., vertical_radius: 0., width: 1., dash: None }
+    }
+}
+impl Object for BorderArray {
+    /// Arrays with fewer than three numbers are read as the default, with a warning.
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let arr = t!(p.resolve(resolve)?.into_array());
+        let number = |i: usize| arr.get(i).map(|p| f32::from_primitive(p.clone(), resolve));
+        match (number(0), number(1), number(2)) {
+            (Some(Ok(horizontal_radius)), Some(Ok(vertical_radius)), Some(Ok(width))) => {
+                let dash = arr.get(3).map(|p| Vec::<f32>::from_primitive(p.clone(), resolve)).transpose()?;
+                Ok(BorderArray { horizontal_radius, vertical_radius, width, dash })
+            }
+            _ => {
+                resolve.options().warn(Warning::Ignored { error: format!("invalid Border {:?}", arr) });
+                Ok(BorderArray::default())
+            }
+        }
+    }
+}
+impl ObjectWrite for BorderArray {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut arr = vec![
+            Primitive::Number(self.horizontal_radius),
+            Primitive::Number(self.vertical_radius),
+            Primitive::Number(self.width),
+        ];
+        if let Some(ref dash) = self.dash {
+            arr.push(dash.to_primitive(update)?);
+        }
+        Ok(Primitive::Array(arr))
+    }
+}
+
+/// The border style dictionary (`BS`) of an annotation.
+#[derive(Object, ObjectWrite, Debug, Clone, PartialEq, DataSize, DeepClone)]
+#[pdf(Type = "Border?")]
+pub struct BorderStyle {
+    /// In points, `0` draws no border.
+    #[pdf(key = "W", default = "1.")]
+    pub width: f32,
+
+    #[pdf(key = "S", default = "BorderStyleKind::Solid")]
+    pub style: BorderStyleKind,
+
+    /// Dashes and gaps of a [`BorderStyleKind::Dashed`] border, `[3]` if absent.
+    #[pdf(key = "D")]
+    pub dash: Option<Vec<f32>>,
+}
+impl Default for BorderStyle {
+    fn default() -> Self {
+        BorderStyle { width: 1., style: BorderStyleKind::Solid, dash: None }
+    }
+}
+
+/// The `S` entry of a [`BorderStyle`].
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone)]
+pub enum BorderStyleKind {
+    #[pdf(name = "S")]
+    Solid,
+    #[pdf(name = "D")]
+    Dashed,
+    /// Looks embossed.
+    #[pdf(name = "B")]
+    Beveled,
+    /// Looks engraved.
+    #[pdf(name = "I")]
+    Inset,
+    /// Only the bottom edge.
+    #[pdf(name = "U")]
+    Underline,
+}
+
+/// The border effect dictionary (`BE`) of square, circle and polygon annotations.
+#[derive(Object, ObjectWrite, Debug, Clone, PartialEq, DataSize, DeepClone, Default)]
+pub struct BorderEffect {
+    #[pdf(key = "S", default = "BorderEffectKind::None")]
+    pub style: BorderEffectKind,
+
+    /// How cloudy a [`BorderEffectKind::Cloudy`] border is, from `0` to `2`.
+    #[pdf(key = "I", default = "0.")]
+    pub intensity: f32,
+}
+
+/// The `S` entry of a [`BorderEffect`].
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone, Copy, PartialEq, Eq, DeepClone, Default)]
+pub enum BorderEffectKind {
+    #[default]
+    #[pdf(name = "S")]
+    None,
+    #[pdf(name = "C")]
+    Cloudy,
+}
+
+impl Annot {
+    /// A text annotation replying to `parent` with `text`, placed at the same position.
+    pub fn reply(parent: &RcRef<Annot>, text: &str) -> Annot {
+        Annot {
+            subtype: "Text".into(),
+            rect: parent.rect,
+            contents: Some(text.into()),
+            page: parent.page.clone(),
+            annotation_name: None,
+            date: Some(Date::now()),
+            annot_flags: ANNOT_FLAG_PRINT,
+            appearance_streams: None,
+            appearance_state: None,
+            border: None,
+            border_style: None,
+            border_effect: None,
+            color: parent.color.clone(),
+            ink_list: None,
+            line: None,
+            popup: None,
+            in_reply_to: Some(parent.get_ref()),
+            reply_type: None,
+            other: Dictionary::new(),
+        }
+    }
+
+    /// A popup window at `rect` for the text of `parent`, initially closed.
+    pub fn popup(parent: &RcRef<Annot>, rect: Rectangle) -> Annot {
+        let mut other = Dictionary::new();
+        other.insert("Parent", Primitive::Reference(parent.get_ref().get_inner()));
+        Annot {
+            subtype: "Popup".into(),
+            rect: Some(rect),
+            contents: None,
+            page: parent.page.clone(),
+            annotation_name: None,
+            date: None,
+            annot_flags: 0,
+            appearance_streams: None,
+            appearance_state: None,
+            border: None,
+            border_style: None,
+            border_effect: None,
+            color: None,
+            ink_list: None,
+            line: None,
+            popup: None,
+            in_reply_to: None,
+            reply_type: None,
+            other,
+        }
+    }
+
+    /// Whether this is a reply to the annotation in `in_reply_to` (and not grouped with it).
+    pub fn is_reply(&self) -> bool {
+        self.in_reply_to.is_some() && self.reply_type != Some(ReplyType::Group)
+    }
+
+    /// A text markup annotation on `quads`, like those found by [`Page::search`].
+    ///
+    /// The corners of each quadrilateral are upper left, upper right, lower left and lower right.
+    /// `color` is written as `C` and used for the normal appearance, which is created in `update`.
+    /// Highlights are drawn with the `Multiply` blend mode so the text stays readable.
+    pub fn text_markup(kind: TextMarkup, quads: &[Quad], color: [f32; 3], update: &mut impl Updater) -> Result<Annot> {
+        if quads.is_empty() {
+            bail!("a text markup annotation needs at least one quadrilateral");
+        }
+        let [red, green, blue] = color;
+        let mut ops = vec![Op::FillColor { color: Color::Rgb(Rgb { red, green, blue }) }];
+        let mut resources = Resources::default();
+        let mut margin: f32 = 0.;
+        if kind == TextMarkup::Highlight {
+            let mut gs = GraphicsStateParameters::default();
+            gs.blend_mode = Some(BlendMode::Multiply);
+            resources.graphics_states.insert("GS0".into(), gs);
+            ops.push(Op::GraphicsState { name: "GS0".into() });
+        } else {
+            ops.push(Op::StrokeColor { color: Color::Rgb(Rgb { red, green, blue }) });
+        }
+        for &[ul, ur, ll, lr] in quads {
+            // from the bottom to the top edge
+            let up = Point { x: ul.x - ll.x, y: ul.y - ll.y };
+            let height = up.x.hypot(up.y);
+            let at = |p: Point, t: f32| Point { x: p.x + up.x * t, y: p.y + up.y * t };
+            let width = (height / 14.).max(0.5);
+            margin = margin.max(width);
+            match kind {
+                TextMarkup::Highlight => {
+                    ops.extend([Op::MoveTo { p: ul }, Op::LineTo { p: ur }, Op::LineTo { p: lr }, Op::LineTo { p: ll }, Op::Close]);
+                }
+                TextMarkup::Underline | TextMarkup::StrikeOut => {
+                    let t = if kind == TextMarkup::Underline { 1. / 28. } else { 0.5 };
+                    ops.extend([Op::LineWidth { width }, Op::MoveTo { p: at(ll, t) }, Op::LineTo { p: at(lr, t) }]);
+                }
+                TextMarkup::Squiggly => {
+                    let along = Point { x: lr.x - ll.x, y: lr.y - ll.y };
+                    let length = along.x.hypot(along.y);
+                    let steps = (length / (height / 6.).max(1.)).ceil().max(1.) as usize;
+                    ops.extend([Op::LineWidth { width }, Op::MoveTo { p: ll }]);
+                    for i in 1..=steps {
+                        let s = i as f32 / steps as f32;
+                        let p = Point { x: ll.x + along.x * s, y: ll.y + along.y * s };
+                        ops.push(Op::LineTo { p: at(p, if i % 2 == 1 { 1. / 7. } else { 0. }) });
+                    }
+                }
+            }
+        }
+        ops.push(match kind {
+            TextMarkup::Highlight => Op::Fill { winding: Winding::NonZero },
+            _ => Op::Stroke,
+        });
+
+        let points = quads.iter().flatten();
+        let rect = Rectangle {
+            left: points.clone().map(|p| p.x).fold(f32::INFINITY, f32::min) - margin,
+            bottom: points.clone().map(|p| p.y).fold(f32::INFINITY, f32::min) - margin,
+            right: points.clone().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max) + margin,
+            top: points.map(|p| p.y).fold(f32::NEG_INFINITY, f32::max) + margin,
+        };
+        let dict = FormDict {
+            bbox: rect,
+            resources: Some(MaybeRef::Indirect(update.create(resources)?)),
+            ..Default::default()
+        };
+        let form = FormXObject { stream: Stream::new(dict, t!(serialize_ops(&ops))) };
+        let appearance = AppearanceStreams {
+            normal: update.create(AppearanceStreamEntry::Single(form))?.get_ref(),
+            rollover: None,
+            down: None,
+        };
+
+        let numbers = |n: &[f32]| Primitive::Array(n.iter().map(|&n| Primitive::Number(n)).collect());
+        let mut annot = Annot {
+            subtype: kind.subtype().into(),
+            rect: Some(rect),
+            contents: None,
+            page: None,
+            annotation_name: None,
+            date: Some(Date::now()),
+            annot_flags: ANNOT_FLAG_PRINT,
+            appearance_streams: Some(MaybeRef::Direct(Shared::new(appearance))),
+            appearance_state: None,
+            border: None,
+            border_style: None,
+            border_effect: None,
+            color: Some(numbers(&color)),
+            ink_list: None,
+            line: None,
+            popup: None,
+            in_reply_to: None,
+            reply_type: None,
+            other: Dictionary::new(),
+        };
+        annot.set_quad_points(quads);
+        Ok(annot)
+    }
+}
+
+/// A quadrilateral of `QuadPoints`: the upper left, upper right, lower left and lower right corner.
+///
+/// The order is the one of Acrobat, the specification describes a counter-clockwise order instead.
+pub type Quad = [Point; 4];
+
+/// Read an array of `x y` pairs.
+fn points(p: &Primitive, key: &str, resolve: &impl Resolve) -> Result<Vec<Point>> {
+    let numbers = t!(Vec::<f32>::from_primitive(p.clone(), resolve));
+    if numbers.len() % 2 != 0 {
+        bail!("{} has an odd number of coordinates ({})", key, numbers.len());
+    }
+    Ok(numbers.chunks_exact(2).map(|p| Point { x: p[0], y: p[1] }).collect())
+}
+fn points_primitive<'a>(points: impl IntoIterator<Item=&'a Point>) -> Primitive {
+    Primitive::Array(points.into_iter().flat_map(|p| [Primitive::Number(p.x), Primitive::Number(p.y)]).collect())
+}
+
+impl Annot {
+    /// The quadrilaterals of a text markup or link annotation (`QuadPoints`).
+    pub fn quad_points(&self, resolve: &impl Resolve) -> Result<Option<Vec<Quad>>> {
+        let p = match self.other.get("QuadPoints") {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let points = t!(points(p, "QuadPoints", resolve));
+        if points.len() % 4 != 0 {
+            bail!("QuadPoints has {} numbers, not a multiple of 8", points.len() * 2);
+        }
+        Ok(Some(points.chunks_exact(4).map(|q| [q[0], q[1], q[2], q[3]]).collect()))
+    }
+    pub fn set_quad_points(&mut self, quads: &[Quad]) {
+        self.other.insert("QuadPoints", points_primitive(quads.iter().flatten()));
+    }
+
+    /// The paths of an ink annotation (`InkList`).
+    pub fn ink_list(&self, resolve: &impl Resolve) -> Result<Option<Vec<Vec<Point>>>> {
+        let p = match self.ink_list {
+            Some(ref p) => p,
+            None => return Ok(None),
+        };
+        let paths = t!(p.clone().resolve(resolve)?.into_array());
+        paths.iter().map(|path| points(path, "InkList", resolve)).collect::<Result<_>>().map(Some)
+    }
+    pub fn set_ink_list(&mut self, paths: &[Vec<Point>]) {
+        self.ink_list = Some(Primitive::Array(paths.iter().map(points_primitive).collect()));
+    }
+
+    /// The vertices of a polygon or polyline annotation (`Vertices`).
+    pub fn vertices(&self, resolve: &impl Resolve) -> Result<Option<Vec<Point>>> {
+        self.other.get("Vertices").map(|p| points(p, "Vertices", resolve)).transpose()
+    }
+    pub fn set_vertices(&mut self, vertices: &[Point]) {
+        self.other.insert("Vertices", points_primitive(vertices));
+    }
+
+    /// The callout line of a free text annotation (`CL`): from the start to the knee, if any, and the end.
+    pub fn callout_line(&self, resolve: &impl Resolve) -> Result<Option<Vec<Point>>> {
+        let p = match self.other.get("CL") {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let points = t!(points(p, "CL", resolve));
+        if !matches!(points.len(), 2 | 3) {
+            bail!("CL has {} numbers, not 4 or 6", points.len() * 2);
+        }
+        Ok(Some(points))
+    }
+    /// Set the callout line to two or three points.
+    pub fn set_callout_line(&mut self, points: &[Point]) -> Result<()> {
+        if !matches!(points.len(), 2 | 3) {
+            bail!("a callout line has 2 or 3 points, not {}", points.len());
+        }
+        self.other.insert("CL", points_primitive(points));
+        Ok(())
+    }
+}
+
+/// An annotation with the replies to it, see [`Page::comment_threads`].
+#[derive(Debug, Clone)]
+pub struct CommentThread {
+    pub annot: MaybeRef<Annot>,
+    pub replies: Vec<CommentThread>,
+}
+
+impl Page {
+    /// The comments on this page, each with its replies (and their replies), in the order of `/Annots`.
+    ///
+    /// Popups and annotations grouped with others are left out, and so are replies
+    /// to annotations that are not on this page.
+    pub fn comment_threads(&self, resolve: &impl Resolve) -> Result<Vec<CommentThread>> {
+        let annots = t!(self.annotations(resolve));
+        let is_comment = |annot: &Annot| annot.subtype.as_str() != "Popup" && annot.reply_type != Some(ReplyType::Group);
+
+        // replies can only be found through indirect annotations
+        fn thread(annot: &MaybeRef<Annot>, annots: &[MaybeRef<Annot>], seen: &mut Vec<PlainRef>) -> CommentThread {
+            let mut replies = vec![];
+            if let Some(r) = annot.as_ref() {
+                seen.push(r.get_inner());
+                for reply in annots {
+                    let to = reply.in_reply_to.map(|r| r.get_inner());
+                    if reply.is_reply() && to == Some(r.get_inner()) && !reply.as_ref().is_some_and(|r| seen.contains(&r.get_inner())) {
+                        replies.push(thread(reply, annots, seen));
+                    }
+                }
+            }
+            CommentThread { annot: annot.clone(), replies }
+        }
+
+        let refs: Vec<_> = annots.iter().filter_map(|a| a.as_ref()).map(|r| r.get_inner()).collect();
+        let mut seen = vec![];
+        let mut threads = vec![];
+        for annot in annots.iter().filter(|a| is_comment(a)) {
+            let replies_here = annot.is_reply() && annot.in_reply_to.is_some_and(|r| refs.contains(&r.get_inner()));
+            if !replies_here {
+                threads.push(thread(annot, &annots, &mut seen));
+            }
+        }
+        Ok(threads)
+    }
+
+    /// The direct replies to `annot` among the annotations of this page.
+    pub fn replies(&self, annot: Ref<Annot>, resolve: &impl Resolve) -> Result<Vec<MaybeRef<Annot>>> {
+        Ok(t!(self.annotations(resolve))
+            .into_iter()
+            .filter(|reply| reply.is_reply() && reply.in_reply_to == Some(annot))
+            .collect())
+    }
+
+    /// Add a reply to `parent` with `text`, see [`Page::add_annotation`].
+    pub fn add_reply(&mut self, parent: &RcRef<Annot>, text: &str, update: &mut (impl Resolve + Updater)) -> Result<RcRef<Annot>> {
+        self.add_annotation(Annot::reply(parent, text), update)
+    }
+
+    /// Add a popup at `rect` for `parent` and set its `Popup` entry, see [`Page::add_annotation`].
+    ///
+    /// `parent` is updated in place and returned.
+    pub fn add_popup(&mut self, parent: &RcRef<Annot>, rect: Rectangle, update: &mut (impl Resolve + Updater)) -> Result<(RcRef<Annot>, RcRef<Annot>)> {
+        let popup = t!(self.add_annotation(Annot::popup(parent, rect), update));
+        let mut annot = (**parent).clone();
+        annot.popup = Some(popup.get_ref());
+        let parent = update.update(parent.get_ref().get_inner(), annot)?;
+        Ok((parent, popup))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn borders() {
+        let data = b"<< /Type /Annot /Subtype /Square /Rect [0 0 10 10] /Border [0 0 2 [3 2]]
+            /BS << /W 2 /S /D /D [3] >> /BE << /S /C /I 1 >> >>";
+        let annot = Annot::from_primitive(parse(data, &NoResolve, ParseFlags::DICT).unwrap(), &NoResolve).unwrap();
+        assert_eq!(annot.border, Some(BorderArray { horizontal_radius: 0., vertical_radius: 0., width: 2., dash: Some(vec![3., 2.]) }));
+        assert_eq!(annot.border_style, Some(BorderStyle { width: 2., style: BorderStyleKind::Dashed, dash: Some(vec![3.]) }));
+        assert_eq!(annot.border_effect, Some(BorderEffect { style: BorderEffectKind::Cloudy, intensity: 1. }));
+
+        let dict = annot.to_primitive(&mut NoUpdate).unwrap().into_dictionary().unwrap();
+        let again = Annot::from_dict(dict, &NoResolve).unwrap();
+        assert_eq!(again.border, annot.border);
+        assert_eq!(again.border_style, annot.border_style);
+        assert_eq!(again.border_effect, annot.border_effect);
+
+        // defaults of an empty style
+        let style = BorderStyle::from_primitive(Primitive::Dictionary(Dictionary::new()), &NoResolve).unwrap();
+        assert_eq!(style, BorderStyle::default());
+    }
+
+    #[test]
+    fn geometry() {
+        let p = |x, y| Point { x, y };
+        let data = b"<< /Subtype /Highlight /QuadPoints [0 10 20 10 0 0 20 0] /InkList [[1 2 3 4] [5 6]]
+            /Vertices [0 0 10 0 10 10] /CL [0 0 5 5 10 5] >>";
+        let mut annot = Annot::from_primitive(parse(data, &NoResolve, ParseFlags::DICT).unwrap(), &NoResolve).unwrap();
+        assert_eq!(annot.quad_points(&NoResolve).unwrap(), Some(vec![[p(0., 10.), p(20., 10.), p(0., 0.), p(20., 0.)]]));
+        assert_eq!(annot.ink_list(&NoResolve).unwrap(), Some(vec![vec![p(1., 2.), p(3., 4.)], vec![p(5., 6.)]]));
+        assert_eq!(annot.vertices(&NoResolve).unwrap(), Some(vec![p(0., 0.), p(10., 0.), p(10., 10.)]));
+        assert_eq!(annot.callout_line(&NoResolve).unwrap(), Some(vec![p(0., 0.), p(5., 5.), p(10., 5.)]));
+
+        annot.set_ink_list(&[vec![p(7., 8.)]]);
+        assert_eq!(annot.ink_list(&NoResolve).unwrap(), Some(vec![vec![p(7., 8.)]]));
+        assert!(annot.set_callout_line(&[p(0., 0.)]).is_err());
+
+        // QuadPoints need 8 numbers per quadrilateral
+        annot.other.insert("QuadPoints", Primitive::Array((0..6).map(Primitive::Integer).collect()));
+        assert!(annot.quad_points(&NoResolve).is_err());
+    }
+}
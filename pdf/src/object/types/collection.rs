@@ -0,0 +1,397 @@
+use super::prelude::*;
+use crate::object::stream::StreamData;
+
+/// The `Collection` entry of the catalog, which makes the document a portfolio (PDF32000-2 12.3.5):
+/// the embedded files are what the viewer shows, along with the fields of `schema`.
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize)]
+#[pdf(Type = "Collection?")]
+pub struct Collection {
+    /// the fields shown for each file
+    #[pdf(key = "Schema")]
+    pub schema: Option<CollectionSchema>,
+
+    /// the key in `EmbeddedFiles` of the file shown first; the document itself if not given
+    #[pdf(key = "D")]
+    pub initial: Option<PdfString>,
+
+    /// `D` (details, the default), `T` (tiles), `H` (hidden) or `C` (a custom navigator)
+    #[pdf(key = "View")]
+    pub view: Option<Name>,
+
+    #[pdf(key = "Navigator")]
+    pub navigator: Option<Dictionary>,
+
+    #[pdf(key = "Colors")]
+    pub colors: Option<Dictionary>,
+
+    #[pdf(key = "Sort")]
+    pub sort: Option<CollectionSort>,
+
+    /// the root of the folder tree (PDF 2.0); its name is not part of the folder paths
+    #[pdf(key = "Folders")]
+    pub folders: Option<Ref<CollectionFolder>>,
+
+    #[pdf(key = "Split")]
+    pub split: Option<Dictionary>,
+}
+
+/// The fields of a portfolio, by the keys used in collection items.
+#[derive(Debug, Clone, Default, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CollectionSchema {
+    pub fields: Vec<(Name, CollectionField)>,
+}
+impl Object for CollectionSchema {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let dict = t!(p.resolve(resolve)?.into_dictionary());
+        let mut fields = vec![];
+        for (key, field) in dict {
+            if key.as_str() == "Type" {
+                continue;
+            }
+            fields.push((key, t!(CollectionField::from_primitive(field, resolve))));
+        }
+        Ok(CollectionSchema { fields })
+    }
+}
+impl ObjectWrite for CollectionSchema {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        dict.insert("Type", Name::from("CollectionSchema"));
+        for (key, field) in &self.fields {
+            dict.insert(key.clone(), field.to_primitive(update)?);
+        }
+        Ok(dict.into())
+    }
+}
+impl CollectionSchema {
+    /// The fields in the order they are shown: by `order`, then by key.
+    pub fn ordered(&self) -> Vec<(&Name, &CollectionField)> {
+        let mut fields: Vec<_> = self.fields.iter().map(|(k, f)| (k, f)).collect();
+        fields.sort_by_key(|&(key, field)| (field.order.unwrap_or(i32::MAX), key.as_str().to_owned()));
+        fields
+    }
+}
+
+/// A column of a portfolio.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "CollectionField?")]
+pub struct CollectionField {
+    /// where the value comes from: `S` (text), `D` (date) or `N` (number) from the collection item,
+    /// or `F` (file name), `Desc`, `ModDate`, `CreationDate`, `Size` or `CompressedSize` of the file
+    #[pdf(key = "Subtype")]
+    pub subtype: Name,
+
+    /// the title of the column
+    #[pdf(key = "N")]
+    pub name: PdfString,
+
+    #[pdf(key = "O")]
+    pub order: Option<i32>,
+
+    #[pdf(key = "V", default = "true")]
+    pub visible: bool,
+
+    #[pdf(key = "E", default = "false")]
+    pub editable: bool,
+}
+
+/// How the files of a portfolio are sorted.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "CollectionSort?")]
+pub struct CollectionSort {
+    /// the keys of the fields to sort by, the first one first
+    #[pdf(key = "S")]
+    pub fields: Vec<Name>,
+
+    /// for each of `fields`, whether it is ascending (the default)
+    #[pdf(key = "A")]
+    pub ascending: Option<Vec<bool>>,
+}
+
+/// A folder of a portfolio (PDF 2.0). Files are in the folder whose `id` prefixes their key, like `<3>report.pdf`.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Folder?")]
+pub struct CollectionFolder {
+    #[pdf(key = "ID")]
+    pub id: i32,
+
+    #[pdf(key = "Name")]
+    pub name: PdfString,
+
+    #[pdf(key = "Parent")]
+    pub parent: Option<Ref<CollectionFolder>>,
+
+    /// the first subfolder
+    #[pdf(key = "Child")]
+    pub child: Option<Ref<CollectionFolder>>,
+
+    /// the next folder with the same parent
+    #[pdf(key = "Next")]
+    pub next: Option<Ref<CollectionFolder>>,
+
+    #[pdf(key = "CI")]
+    pub item: Option<CollectionItem>,
+
+    #[pdf(key = "Desc")]
+    pub description: Option<PdfString>,
+
+    #[pdf(key = "CreationDate")]
+    pub creation_date: Option<Date>,
+
+    #[pdf(key = "ModDate")]
+    pub mod_date: Option<Date>,
+}
+
+/// The values of the schema fields for one file or folder (the `CI` entry).
+#[derive(Debug, Clone, Default, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CollectionItem {
+    pub values: Vec<(Name, CollectionItemValue)>,
+}
+impl Object for CollectionItem {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let dict = t!(p.resolve(resolve)?.into_dictionary());
+        let mut values = vec![];
+        for (key, value) in dict {
+            if key.as_str() == "Type" {
+                continue;
+            }
+            values.push((key, t!(CollectionItemValue::from_primitive(value, resolve))));
+        }
+        Ok(CollectionItem { values })
+    }
+}
+impl ObjectWrite for CollectionItem {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        dict.insert("Type", Name::from("CollectionItem"));
+        for (key, value) in &self.values {
+            dict.insert(key.clone(), value.to_primitive(update)?);
+        }
+        Ok(dict.into())
+    }
+}
+impl DeepClone for CollectionItem {
+    fn deep_clone(&self, _cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+impl CollectionItem {
+    pub fn get(&self, key: &str) -> Option<&CollectionItemValue> {
+        self.values.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Set the value of `key`, replacing an earlier one.
+    pub fn set(&mut self, key: &str, value: CollectionValue) {
+        let value = CollectionItemValue { value, prefix: None };
+        match self.values.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.values.push((key.into(), value)),
+        }
+    }
+}
+
+/// A value of a collection item, with the text shown before it (but not sorted by).
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CollectionItemValue {
+    pub value: CollectionValue,
+    pub prefix: Option<PdfString>,
+}
+impl Object for CollectionItemValue {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Dictionary(mut dict) => {
+                let value = t!(CollectionValue::from_primitive(t!(dict.require("CollectionSubitem", "D")), resolve));
+                let prefix = dict.remove("P").map(|p| p.resolve(resolve)?.into_string()).transpose()?;
+                Ok(CollectionItemValue { value, prefix })
+            }
+            p => Ok(CollectionItemValue { value: t!(CollectionValue::from_primitive(p, resolve)), prefix: None }),
+        }
+    }
+}
+impl ObjectWrite for CollectionItemValue {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match self.prefix {
+            Some(ref prefix) => {
+                let mut dict = Dictionary::new();
+                dict.insert("Type", Name::from("CollectionSubitem"));
+                dict.insert("D", self.value.to_primitive(update)?);
+                dict.insert("P", prefix.clone());
+                Ok(dict.into())
+            }
+            None => self.value.to_primitive(update),
+        }
+    }
+}
+
+/// A text (which may be a date, for date fields) or a number.
+#[derive(Debug, Clone, PartialEq, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CollectionValue {
+    Text(PdfString),
+    Number(f32),
+}
+impl Object for CollectionValue {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::String(s) => Ok(CollectionValue::Text(s)),
+            Primitive::Integer(n) => Ok(CollectionValue::Number(n as f32)),
+            Primitive::Number(n) => Ok(CollectionValue::Number(n)),
+            p => Err(PdfError::UnexpectedPrimitive { expected: "String or Number", found: p.get_debug_name() }),
+        }
+    }
+}
+impl ObjectWrite for CollectionValue {
+    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
+        Ok(match *self {
+            CollectionValue::Text(ref s) => s.clone().into(),
+            CollectionValue::Number(n) if n.fract() == 0. && n.abs() < i32::MAX as f32 => Primitive::Integer(n as i32),
+            CollectionValue::Number(n) => Primitive::Number(n),
+        })
+    }
+}
+impl CollectionValue {
+    /// The value of a date field.
+    pub fn date(&self) -> Option<Date> {
+        match *self {
+            CollectionValue::Text(ref s) => Date::parse(&s.to_string_lossy()).ok(),
+            CollectionValue::Number(_) => None,
+        }
+    }
+}
+
+impl Collection {
+    /// The path of each folder by its ID, with the names of its ancestors below the root joined by `/`.
+    ///
+    /// Fails with `ReferenceCycle` if a folder is its own ancestor or sibling.
+    pub fn folder_paths(&self, resolve: &impl Resolve) -> Result<Vec<(i32, String)>> {
+        let root = match self.folders {
+            Some(root) => root,
+            None => return Ok(vec![]),
+        };
+        let mut paths = vec![];
+        let mut seen = vec![];
+        let root_folder = t!(resolve.get(root));
+        enter_node(&mut seen, root.get_inner(), resolve)?;
+        paths.push((root_folder.id, String::new()));
+        // folders still to visit, with the path of their parent
+        let mut stack: Vec<_> = root_folder.child.into_iter().map(|r| (r, String::new())).collect();
+        while let Some((r, parent)) = stack.pop() {
+            enter_node(&mut seen, r.get_inner(), resolve)?;
+            let folder = t!(resolve.get(r));
+            let name = folder.name.to_string_lossy();
+            let path = if parent.is_empty() { name } else { format!("{}/{}", parent, name) };
+            if let Some(next) = folder.next {
+                stack.push((next, parent));
+            }
+            if let Some(child) = folder.child {
+                stack.push((child, path.clone()));
+            }
+            paths.push((folder.id, path));
+        }
+        Ok(paths)
+    }
+
+    /// The value of `field` of the schema for `file`: from its collection item, or from the file
+    /// specification and embedded file for the fields about the file.
+    pub fn value(&self, file: &FileSpec, field: &str, resolve: &impl Resolve) -> Result<Option<CollectionValue>> {
+        let subtype = match self.schema.as_ref().and_then(|s| s.fields.iter().find(|(k, _)| k == field)) {
+            Some((_, field)) => field.subtype.as_str().to_owned(),
+            None => "S".to_owned(),
+        };
+        let params = || -> Result<Option<EmbeddedFileParamDict>> {
+            Ok(t!(file.embedded_file(resolve)).and_then(|f| f.info.info.params.clone()))
+        };
+        let date = |d: Option<Date>| d.map(|d| CollectionValue::Text(d.to_string().as_str().into()));
+        Ok(match subtype.as_str() {
+            "F" => file.unicode_file_name.as_ref().or(file.file_name.as_ref()).cloned().map(CollectionValue::Text),
+            "Desc" => file.description.clone().map(CollectionValue::Text),
+            "ModDate" => date(params()?.and_then(|p| p.mod_date)),
+            "CreationDate" => date(params()?.and_then(|p| p.creation_date)),
+            "Size" => params()?.and_then(|p| p.size).map(|s| CollectionValue::Number(s as f32)),
+            "CompressedSize" => t!(file.embedded_file(resolve)).map(|f| CollectionValue::Number(match f.inner_data {
+                StreamData::Generated(ref data) => data.len(),
+                StreamData::Original(ref range, _) => range.len(),
+            } as f32)),
+            _ => file.collection_item.as_ref().and_then(|ci| ci.get(field)).map(|v| v.value.clone()),
+        })
+    }
+}
+
+/// A file of a portfolio, see [`Catalog::portfolio_entries`].
+#[derive(Debug, Clone)]
+pub struct PortfolioEntry {
+    /// the key in the `EmbeddedFiles` name tree
+    pub key: PdfString,
+    /// the path of the folder the file is in, empty at the top
+    pub folder: String,
+    /// the key without the folder ID
+    pub name: String,
+    pub file: FileSpec,
+}
+
+impl Catalog {
+    /// The embedded files, with the folders they are in if the document is a portfolio with folders.
+    ///
+    /// The metadata is in the collection item of each file, see [`Collection::value`].
+    pub fn portfolio_entries(&self, resolve: &impl Resolve) -> Result<Vec<PortfolioEntry>> {
+        let folders = match self.collection {
+            Some(ref collection) => t!(collection.folder_paths(resolve)),
+            None => vec![],
+        };
+        let mut files = vec![];
+        if let Some(tree) = self.names.as_ref().and_then(|n| n.embedded_files.as_ref()) {
+            t!(tree.walk(resolve, &mut |key, file| files.push((key.clone(), file.clone()))));
+        }
+        Ok(files.into_iter().map(|(key, file)| {
+            let text = key.to_string_lossy();
+            // a key like `<3>name` is in the folder with ID 3
+            let in_folder = text.strip_prefix('<')
+                .and_then(|s| s.split_once('>'))
+                .and_then(|(id, name)| Some((id.parse::<i32>().ok()?, name)))
+                .and_then(|(id, name)| folders.iter().find(|&&(i, _)| i == id).map(|(_, path)| (path.clone(), name.to_owned())));
+            let (folder, name) = in_folder.unwrap_or_else(|| (String::new(), text));
+            PortfolioEntry { key, folder, name, file }
+        }).collect())
+    }
+}
+
+/// A file to put in a portfolio, see `File::make_portfolio`.
+#[derive(Debug, Clone, Default)]
+pub struct PortfolioFile<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+    pub options: EmbedOptions,
+    pub description: Option<&'a str>,
+    /// the values of the schema fields
+    pub item: Option<CollectionItem>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn schema() {
+        let p = parse(b"<< /Type /Collection /View /D /Sort << /S [/Date /F] /A [false true] >> /Schema << /Type /CollectionSchema
+            /F << /Subtype /F /N (Name) /O 1 >> /Date << /Subtype /D /N (Received) /O 0 >> /Owner << /Subtype /S /N (Owner) /V false >> >> >>",
+            &NoResolve, ParseFlags::ANY).unwrap();
+        let collection = Collection::from_primitive(p, &NoResolve).unwrap();
+        let schema = collection.schema.as_ref().unwrap();
+        let keys: Vec<_> = schema.ordered().iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["Date", "F", "Owner"]);
+        assert!(!schema.ordered()[2].1.visible);
+        assert_eq!(collection.sort.as_ref().unwrap().ascending, Some(vec![false, true]));
+
+        let p = parse(b"<< /Owner << /D (Jane) /P (by ) >> /Date (D:20240301120000Z) /Pages 12 >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let item = CollectionItem::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(item.get("Owner").unwrap().prefix.as_ref().unwrap().as_bytes(), b"by ");
+        assert_eq!(item.get("Date").unwrap().value.date().unwrap().year, 2024);
+        assert_eq!(item.get("Pages").unwrap().value, CollectionValue::Number(12.));
+        let file = FileSpec { collection_item: Some(item), ..FileSpec::default() };
+        assert_eq!(collection.value(&file, "Pages", &NoResolve).unwrap(), Some(CollectionValue::Number(12.)));
+    }
+}
@@ -1,12 +1,14 @@
 use super::prelude::*;
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MaybeNamedDest {
     Named(PdfString),
     Direct(Dest),
 }
 
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Dest {
     pub page: Option<Ref<Page>>,
     pub view: DestView,
@@ -93,6 +95,44 @@ impl Dest {
         Ok(Dest { page, view })
     }
 }
+/// The `OpenAction` of a [`Catalog`]: a page to show or an action to perform when the document is opened.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OpenAction {
+    Dest(Dest),
+    Action(Action),
+}
+impl Object for OpenAction {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            p @ Primitive::Array(_) => Dest::from_primitive(p, resolve).map(OpenAction::Dest),
+            p @ Primitive::Dictionary(_) => Action::from_primitive(p, resolve).map(OpenAction::Action),
+            p => Err(PdfError::UnexpectedPrimitive {
+                expected: "Array or Dictionary",
+                found: p.get_debug_name(),
+            }),
+        }
+    }
+}
+impl ObjectWrite for OpenAction {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match self {
+            OpenAction::Dest(dest) => dest.to_primitive(update),
+            OpenAction::Action(action) => action.to_primitive(update),
+        }
+    }
+}
+impl From<Dest> for OpenAction {
+    fn from(dest: Dest) -> Self {
+        OpenAction::Dest(dest)
+    }
+}
+impl From<Action> for OpenAction {
+    fn from(action: Action) -> Self {
+        OpenAction::Action(action)
+    }
+}
+
 impl Object for MaybeNamedDest {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let p = match p {
@@ -0,0 +1,238 @@
+use super::prelude::*;
+use crate::enc::{encode, StreamFilter};
+
+/* Embedded file streams can be associated with the document as a whole through
+ * the EmbeddedFiles entry (PDF 1.4) in the PDF document’s name dictionary
+ * (see Section 3.6.3, “Name Dictionary”).
+ * The associated name tree maps name strings to file specifications that refer
+ * to embedded file streams through their EF entries.
+*/
+
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize, DeepClone)]
+#[pdf(Type = "Filespec?")]
+pub struct FileSpec {
+    #[pdf(key = "F")]
+    pub file_name: Option<PdfString>,
+
+    #[pdf(key = "UF")]
+    pub unicode_file_name: Option<PdfString>,
+
+    #[pdf(key = "Desc")]
+    pub description: Option<PdfString>,
+
+    #[pdf(key = "EF")]
+    pub ef: Option<Files<Ref<Stream<EmbeddedFile>>>>,
+
+    /// the values of the schema fields of a portfolio, see [`Collection`]
+    #[pdf(key = "CI")]
+    pub collection_item: Option<CollectionItem>,
+    /*
+    #[pdf(key="RF")]
+    rf: Option<Files<RelatedFilesArray>>,
+    */
+}
+
+/// Used only as elements in `FileSpec`
+#[derive(Object, ObjectWrite, Debug, Clone, DeepClone)]
+pub struct Files<T> {
+    #[pdf(key = "F")]
+    pub f: Option<T>,
+    #[pdf(key = "UF")]
+    pub uf: Option<T>,
+    #[pdf(key = "DOS")]
+    pub dos: Option<T>,
+    #[pdf(key = "Mac")]
+    pub mac: Option<T>,
+    #[pdf(key = "Unix")]
+    pub unix: Option<T>,
+}
+impl<T: DataSize> DataSize for Files<T> {
+    const IS_DYNAMIC: bool = T::IS_DYNAMIC;
+    const STATIC_HEAP_SIZE: usize = 5 * Option::<T>::STATIC_HEAP_SIZE;
+
+    fn estimate_heap_size(&self) -> usize {
+        [&self.f, &self.uf, &self.dos, &self.mac, &self.unix]
+            .into_iter()
+            .filter_map(|o| o.as_ref())
+            .map(|t| t.estimate_heap_size())
+            .sum()
+    }
+}
+
+/// PDF Embedded File Stream.
+#[derive(Object, Debug, Clone, DataSize, DeepClone, ObjectWrite)]
+#[pdf(Type = "EmbeddedFile?")]
+pub struct EmbeddedFile {
+    /// the MIME type of the file
+    #[pdf(key = "Subtype")]
+    pub subtype: Option<Name>,
+
+    #[pdf(key = "Params")]
+    pub params: Option<EmbeddedFileParamDict>,
+}
+
+#[derive(Object, Debug, Clone, DataSize, DeepClone, ObjectWrite)]
+pub struct EmbeddedFileParamDict {
+    #[pdf(key = "Size")]
+    pub size: Option<i32>,
+
+    #[pdf(key = "CreationDate")]
+    pub creation_date: Option<Date>,
+
+    #[pdf(key = "ModDate")]
+    pub mod_date: Option<Date>,
+
+    /// Mac OS file information (Subtype, Creator and ResFork)
+    #[pdf(key = "Mac")]
+    pub mac: Option<Dictionary>,
+
+    /// MD5 digest of the uncompressed file data
+    #[pdf(key = "CheckSum")]
+    pub checksum: Option<PdfString>,
+}
+
+/// Settings for writing an embedded file, see [`EmbeddedFile::create`].
+#[derive(Debug, Clone, Default)]
+pub struct EmbedOptions {
+    /// the MIME type, like `text/plain`
+    pub subtype: Option<Name>,
+
+    /// Filters to compress the data with. `None` chooses them by the subtype, see [`EmbeddedFile::default_filters`].
+    pub filters: Option<Vec<StreamFilter>>,
+
+    /// Defaults to the current time.
+    pub creation_date: Option<Date>,
+
+    /// Defaults to the creation date.
+    pub mod_date: Option<Date>,
+}
+
+/// What to do if the checksum of an embedded file doesn't match its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumPolicy {
+    Ignore,
+    Warn,
+    Fail,
+}
+
+impl EmbeddedFile {
+    /// Formats that are compressed already gain nothing from `FlateDecode`,
+    /// everything else (including an unknown type) is deflated.
+    pub fn default_filters(subtype: Option<&str>) -> Vec<StreamFilter> {
+        let compressed = match subtype {
+            Some(mime) => {
+                let mime = mime.to_ascii_lowercase();
+                let (kind, sub) = mime.split_once('/').unwrap_or((&mime, ""));
+                match kind {
+                    "audio" | "video" => true,
+                    "image" => !matches!(sub, "bmp" | "svg+xml" | "tiff" | "x-portable-anymap"),
+                    "application" => matches!(sub,
+                        "zip" | "gzip" | "x-gzip" | "x-bzip2" | "x-xz" | "zstd" | "x-7z-compressed"
+                        | "vnd.rar" | "pdf" | "epub+zip" | "java-archive"
+                    ) || sub.starts_with("vnd.openxmlformats") || sub.starts_with("vnd.oasis.opendocument"),
+                    _ => false,
+                }
+            }
+            None => false,
+        };
+        if compressed {
+            vec![]
+        } else {
+            vec![StreamFilter::FlateDecode(Default::default())]
+        }
+    }
+
+    /// Build an embedded file stream for `data`.
+    ///
+    /// The size, dates and checksum of the parameter dictionary are always filled in.
+    pub fn create(data: &[u8], options: EmbedOptions) -> Result<Stream<EmbeddedFile>> {
+        let EmbedOptions { subtype, filters, creation_date, mod_date } = options;
+        let filters = filters.unwrap_or_else(|| EmbeddedFile::default_filters(subtype.as_deref()));
+        let creation_date = creation_date.unwrap_or_else(Date::now);
+        let params = EmbeddedFileParamDict {
+            size: Some(data.len().try_into()?),
+            mod_date: Some(mod_date.unwrap_or_else(|| creation_date.clone())),
+            creation_date: Some(creation_date),
+            mac: None,
+            checksum: Some(PdfString::new(md5::compute(data).0.as_slice().into())),
+        };
+        let mut encoded = data.to_vec();
+        for filter in filters.iter().rev() {
+            encoded = t!(encode(&encoded, filter));
+        }
+        Ok(Stream::from_compressed(EmbeddedFile { subtype, params: Some(params) }, encoded, filters))
+    }
+}
+
+impl Stream<EmbeddedFile> {
+    /// The file data, compared against the `CheckSum` parameter if there is one.
+    pub fn checked_data(&self, resolve: &impl Resolve, policy: ChecksumPolicy) -> Result<Arc<[u8]>> {
+        let data = t!(self.data(resolve));
+        let expected = self.info.info.params.as_ref().and_then(|p| p.checksum.as_ref());
+        if let (Some(expected), false) = (expected, policy == ChecksumPolicy::Ignore) {
+            let found = md5::compute(&data).0;
+            if expected.as_bytes() != found {
+                match policy {
+                    ChecksumPolicy::Fail => return Err(PdfError::ChecksumMismatch),
+                    _ => warn!("checksum of embedded file does not match"),
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl FileSpec {
+    /// A file specification for `data`, embedded as a new stream.
+    pub fn embed(file_name: &str, data: &[u8], options: EmbedOptions, update: &mut impl Updater) -> Result<FileSpec> {
+        let stream = update.create(EmbeddedFile::create(data, options)?)?;
+        let r = Some(stream.get_ref());
+        Ok(FileSpec {
+            file_name: Some(file_name.into()),
+            unicode_file_name: Some(file_name.into()),
+            description: None,
+            ef: Some(Files { f: r, uf: r, dos: None, mac: None, unix: None }),
+            collection_item: None,
+        })
+    }
+
+    /// The embedded file stream, preferring the `UF` entry.
+    pub fn embedded_file(&self, resolve: &impl Resolve) -> Result<Option<RcRef<Stream<EmbeddedFile>>>> {
+        let ef = match self.ef {
+            Some(ref ef) => ef,
+            None => return Ok(None),
+        };
+        match ef.uf.or(ef.f).or(ef.unix).or(ef.dos).or(ef.mac) {
+            Some(r) => resolve.get(r).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl NameDictionary {
+    /// Attach a file to the document under `name`, replacing an earlier one with that name.
+    pub fn add_embedded_file(&mut self, name: &str, data: &[u8], options: EmbedOptions, resolve: &impl Resolve, update: &mut impl Updater) -> Result<()> {
+        let spec = FileSpec::embed(name, data, options, update)?;
+        let tree = self.embedded_files.get_or_insert_with(|| NameTree::from_entries(vec![]));
+        tree.insert(name.into(), spec, resolve)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_round_trip() {
+        let data = b"hello hello hello hello";
+        let options = EmbedOptions { subtype: Some("text/plain".into()), ..Default::default() };
+        let mut stream = EmbeddedFile::create(data, options).unwrap();
+        assert_eq!(stream.get_filters().len(), 1);
+        assert_eq!(&*stream.checked_data(&NoResolve, ChecksumPolicy::Fail).unwrap(), data);
+
+        stream.info.info.params.as_mut().unwrap().checksum = Some(PdfString::new(vec![0; 16].into()));
+        assert!(stream.checked_data(&NoResolve, ChecksumPolicy::Fail).is_err());
+        assert!(stream.checked_data(&NoResolve, ChecksumPolicy::Warn).is_ok());
+    }
+}
@@ -16,7 +16,7 @@ pub struct FormDict {
     pub bbox: Rectangle,
 
     #[pdf(key = "Matrix")]
-    pub matrix: Option<Primitive>,
+    pub matrix: Option<Matrix>,
 
     #[pdf(key = "Resources")]
     pub resources: Option<MaybeRef<Resources>>,
@@ -1,4 +1,5 @@
 use super::prelude::*;
+use crate::content::FormXObject;
 
 #[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Copy, Clone)]
 pub enum LineCap {
@@ -13,7 +14,7 @@ pub enum LineJoin {
     Bevel = 2,
 }
 
-#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone, Default)]
 #[pdf(Type = "ExtGState?")]
 /// `ExtGState`
 pub struct GraphicsStateParameters {
@@ -47,21 +48,44 @@ pub struct GraphicsStateParameters {
     #[pdf(key = "Font")]
     pub font: Option<(Ref<Font>, f32)>,
 
-    // BG
-    // BG2
-    // UCR
-    // UCR2
-    // TR
-    // TR2
-    // HT
-    // FL
-    // SM
-    // SA
+    #[pdf(key = "BG")]
+    pub black_generation: Option<TransferFunction>,
+
+    /// takes precedence over `BG`, may be `/Default`
+    #[pdf(key = "BG2")]
+    pub black_generation2: Option<TransferFunction>,
+
+    #[pdf(key = "UCR")]
+    pub undercolor_removal: Option<TransferFunction>,
+
+    /// takes precedence over `UCR`, may be `/Default`
+    #[pdf(key = "UCR2")]
+    pub undercolor_removal2: Option<TransferFunction>,
+
+    #[pdf(key = "TR")]
+    pub transfer: Option<TransferFunction>,
+
+    /// takes precedence over `TR`, may be `/Default`
+    #[pdf(key = "TR2")]
+    pub transfer2: Option<TransferFunction>,
+
+    #[pdf(key = "HT")]
+    pub halftone: Option<Halftone>,
+
+    #[pdf(key = "FL")]
+    pub flatness: Option<f32>,
+
+    #[pdf(key = "SM")]
+    pub smoothness: Option<f32>,
+
+    #[pdf(key = "SA")]
+    pub stroke_adjustment: Option<bool>,
+
     #[pdf(key = "BM")]
-    pub blend_mode: Option<Primitive>,
+    pub blend_mode: Option<BlendMode>,
 
     #[pdf(key = "SMask")]
-    pub smask: Option<Primitive>,
+    pub smask: Option<SoftMask>,
 
     #[pdf(key = "CA")]
     pub stroke_alpha: Option<f32>,
@@ -78,3 +102,375 @@ pub struct GraphicsStateParameters {
     #[pdf(other)]
     _other: Dictionary,
 }
+
+/// Separable and non-separable blend modes (PDF32000 11.3.5).
+#[derive(Debug, DataSize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+const BLEND_MODES: [(&str, BlendMode); 16] = [
+    ("Normal", BlendMode::Normal),
+    ("Multiply", BlendMode::Multiply),
+    ("Screen", BlendMode::Screen),
+    ("Overlay", BlendMode::Overlay),
+    ("Darken", BlendMode::Darken),
+    ("Lighten", BlendMode::Lighten),
+    ("ColorDodge", BlendMode::ColorDodge),
+    ("ColorBurn", BlendMode::ColorBurn),
+    ("HardLight", BlendMode::HardLight),
+    ("SoftLight", BlendMode::SoftLight),
+    ("Difference", BlendMode::Difference),
+    ("Exclusion", BlendMode::Exclusion),
+    ("Hue", BlendMode::Hue),
+    ("Saturation", BlendMode::Saturation),
+    ("Color", BlendMode::Color),
+    ("Luminosity", BlendMode::Luminosity),
+];
+impl BlendMode {
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        match name {
+            // deprecated alias
+            "Compatible" => Some(BlendMode::Normal),
+            name => BLEND_MODES.iter().find(|&&(n, _)| n == name).map(|&(_, mode)| mode),
+        }
+    }
+    pub fn name(self) -> &'static str {
+        BLEND_MODES.iter().find(|&&(_, mode)| mode == self).map(|&(n, _)| n).unwrap()
+    }
+}
+impl Object for BlendMode {
+    /// An array lists blend modes in order of preference, the first known one is used.
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Name(ref name) => {
+                BlendMode::from_name(name).ok_or_else(|| other!("unknown blend mode {}", name))
+            }
+            Primitive::Array(parts) => {
+                for p in parts {
+                    if let Some(mode) = p.as_name().ok().and_then(BlendMode::from_name) {
+                        return Ok(mode);
+                    }
+                }
+                Ok(BlendMode::Normal)
+            }
+            p => Err(PdfError::UnexpectedPrimitive {
+                expected: "Name or Array",
+                found: p.get_debug_name(),
+            }),
+        }
+    }
+}
+impl ObjectWrite for BlendMode {
+    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
+        Ok(Primitive::Name(self.name().into()))
+    }
+}
+impl DeepClone for BlendMode {
+    fn deep_clone(&self, _cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(*self)
+    }
+}
+
+/// The value of the transfer, black-generation and undercolor-removal entries.
+#[derive(Debug, DataSize, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TransferFunction {
+    /// `/Default`, only valid in `TR2`, `BG2` and `UCR2`
+    Default,
+    /// `/Identity`, only valid for transfer functions
+    Identity,
+    Single(Function),
+    /// one transfer function for each of the four colorants
+    PerComponent(Vec<Function>),
+}
+impl Object for TransferFunction {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Name(ref name) if name == "Default" => Ok(TransferFunction::Default),
+            Primitive::Name(ref name) if name == "Identity" => Ok(TransferFunction::Identity),
+            Primitive::Array(parts) => Ok(TransferFunction::PerComponent(
+                parts.into_iter().map(|p| Function::from_primitive(p, resolve)).collect::<Result<_>>()?
+            )),
+            p => Ok(TransferFunction::Single(Function::from_primitive(p, resolve)?)),
+        }
+    }
+}
+impl ObjectWrite for TransferFunction {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            TransferFunction::Default => Ok(Primitive::Name("Default".into())),
+            TransferFunction::Identity => Ok(Primitive::Name("Identity".into())),
+            TransferFunction::Single(ref f) => f.to_primitive(update),
+            TransferFunction::PerComponent(ref fs) => fs.to_primitive(update),
+        }
+    }
+}
+impl DeepClone for TransferFunction {
+    fn deep_clone(&self, _cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+/// The spot function of a type 1 halftone: a predefined one or a function of two inputs.
+#[derive(Debug, DataSize, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SpotFunction {
+    /// the first name a device supports is used
+    Named(Vec<Name>),
+    Function(Function),
+}
+impl Object for SpotFunction {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            p @ (Primitive::Name(_) | Primitive::Array(_)) => Ok(SpotFunction::Named(Vec::from_primitive(p, resolve)?)),
+            p => Ok(SpotFunction::Function(Function::from_primitive(p, resolve)?)),
+        }
+    }
+}
+impl ObjectWrite for SpotFunction {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            SpotFunction::Named(ref names) if names.len() == 1 => names[0].to_primitive(update),
+            SpotFunction::Named(ref names) => names.to_primitive(update),
+            SpotFunction::Function(ref f) => f.to_primitive(update),
+        }
+    }
+}
+impl DeepClone for SpotFunction {
+    fn deep_clone(&self, _cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+/// Type 1 halftone, defined by a screen frequency, angle and spot function.
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+#[pdf(Type = "Halftone?")]
+pub struct ScreenHalftone {
+    #[pdf(key = "HalftoneName")]
+    pub name: Option<PdfString>,
+
+    /// in halftone cells per inch
+    #[pdf(key = "Frequency")]
+    pub frequency: f32,
+
+    /// in degrees counterclockwise
+    #[pdf(key = "Angle")]
+    pub angle: f32,
+
+    #[pdf(key = "SpotFunction")]
+    pub spot_function: SpotFunction,
+
+    #[pdf(key = "AccurateScreens", default = "false")]
+    pub accurate_screens: bool,
+
+    #[pdf(key = "TransferFunction")]
+    pub transfer_function: Option<TransferFunction>,
+}
+
+/// The `HT` entry of a graphics state.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Halftone {
+    /// `/Default`
+    Default,
+    /// type 1
+    Screen(ScreenHalftone),
+    /// type 5: a halftone for each colorant, and one under `/Default` for the others
+    Colorants(HashMap<Name, Halftone>),
+    /// types 6, 10 and 16 are defined by a threshold array in a stream, which is left as it is
+    Threshold(Primitive),
+}
+impl Object for Halftone {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let resolved = p.clone().resolve(resolve)?;
+        let halftone_type = match resolved {
+            Primitive::Name(ref name) if name == "Default" => return Ok(Halftone::Default),
+            Primitive::Dictionary(ref dict) => dict.get("HalftoneType"),
+            Primitive::Stream(ref s) => s.info.get("HalftoneType"),
+            ref p => bail!("unexpected halftone {:?}", p),
+        }.map(|t| t.as_integer()).transpose()?;
+        match halftone_type {
+            Some(1) => Ok(Halftone::Screen(ScreenHalftone::from_primitive(resolved, resolve)?)),
+            Some(5) => {
+                let mut dict = resolved.into_dictionary()?;
+                dict.remove("Type");
+                dict.remove("HalftoneType");
+                dict.remove("HalftoneName");
+                let colorants = dict.iter()
+                    .map(|(name, p)| Ok((name.clone(), Halftone::from_primitive(p.clone(), resolve)?)))
+                    .collect::<Result<_>>()?;
+                Ok(Halftone::Colorants(colorants))
+            }
+            Some(6 | 10 | 16) => Ok(Halftone::Threshold(p)),
+            t => bail!("invalid HalftoneType {:?}", t),
+        }
+    }
+}
+impl ObjectWrite for Halftone {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            Halftone::Default => Ok(Primitive::Name("Default".into())),
+            Halftone::Screen(ref h) => {
+                let mut dict = h.to_dict(update)?;
+                dict.insert("HalftoneType", 1);
+                Ok(Primitive::Dictionary(dict))
+            }
+            Halftone::Colorants(ref colorants) => {
+                let mut dict = Dictionary::new();
+                dict.insert("Type", Name::from("Halftone"));
+                dict.insert("HalftoneType", 5);
+                for (name, h) in colorants {
+                    dict.insert(name.clone(), h.to_primitive(update)?);
+                }
+                Ok(Primitive::Dictionary(dict))
+            }
+            Halftone::Threshold(ref p) => Ok(p.clone()),
+        }
+    }
+}
+impl DeepClone for Halftone {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(match *self {
+            Halftone::Default => Halftone::Default,
+            Halftone::Screen(ref h) => Halftone::Screen(h.deep_clone(cloner)?),
+            Halftone::Colorants(ref colorants) => Halftone::Colorants(colorants.deep_clone(cloner)?),
+            Halftone::Threshold(ref p) => Halftone::Threshold(p.deep_clone(cloner)?),
+        })
+    }
+}
+impl DataSize for Halftone {
+    const IS_DYNAMIC: bool = true;
+    const STATIC_HEAP_SIZE: usize = 0;
+
+    fn estimate_heap_size(&self) -> usize {
+        match *self {
+            Halftone::Default => 0,
+            Halftone::Screen(ref h) => h.estimate_heap_size(),
+            Halftone::Colorants(ref colorants) => colorants.iter()
+                .map(|(name, h)| name.estimate_heap_size() + std::mem::size_of::<Halftone>() + h.estimate_heap_size())
+                .sum(),
+            Halftone::Threshold(ref p) => p.estimate_heap_size(),
+        }
+    }
+}
+
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Copy, Clone, PartialEq, Eq)]
+pub enum SoftMaskType {
+    Alpha,
+    Luminosity,
+}
+
+/// A soft mask derived from the alpha or luminosity of a transparency group.
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+#[pdf(Type = "Mask?")]
+pub struct SoftMaskDict {
+    #[pdf(key = "S")]
+    pub subtype: SoftMaskType,
+
+    /// the transparency group, a form XObject with a `Group` entry
+    #[pdf(key = "G")]
+    pub group: Ref<FormXObject>,
+
+    /// backdrop color, in the color space of the group
+    #[pdf(key = "BC")]
+    pub backdrop: Option<Vec<f32>>,
+
+    /// maps the computed mask values to the final ones
+    #[pdf(key = "TR")]
+    pub transfer: Option<TransferFunction>,
+}
+
+/// The `SMask` entry of a graphics state.
+#[derive(Debug, DataSize, Clone, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SoftMask {
+    /// `/None`, removes the current soft mask
+    None,
+    Mask(SoftMaskDict),
+}
+impl Object for SoftMask {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Name(ref name) if name == "None" => Ok(SoftMask::None),
+            p => Ok(SoftMask::Mask(SoftMaskDict::from_primitive(p, resolve)?)),
+        }
+    }
+}
+impl ObjectWrite for SoftMask {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            SoftMask::None => Ok(Primitive::Name("None".into())),
+            SoftMask::Mask(ref mask) => mask.to_primitive(update),
+        }
+    }
+}
+
+/// The `Group` entry of a form XObject or page. Only transparency groups are defined.
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+#[pdf(Type = "Group?")]
+pub struct TransparencyGroup {
+    /// always `Transparency`
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    /// the blending color space of the group
+    #[pdf(key = "CS")]
+    pub color_space: Option<ColorSpace>,
+
+    #[pdf(key = "I", default = "false")]
+    pub isolated: bool,
+
+    #[pdf(key = "K", default = "false")]
+    pub knockout: bool,
+}
+impl SoftMaskDict {
+    /// The transparency group of the mask.
+    pub fn group(&self, resolve: &impl Resolve) -> Result<RcRef<FormXObject>> {
+        resolve.get(self.group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn typed_entries() {
+        let p = parse(b"<< /Type /ExtGState /BM [/Foo /Multiply] /TR /Identity /SMask << /S /Luminosity /G 5 0 R /BC [0] >> /HT << /HalftoneType 1 /Frequency 60 /Angle 45 /SpotFunction /Round >> >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let gs = GraphicsStateParameters::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(gs.blend_mode, Some(BlendMode::Multiply));
+        assert!(matches!(gs.transfer, Some(TransferFunction::Identity)));
+        match gs.smask {
+            Some(SoftMask::Mask(ref mask)) => {
+                assert_eq!(mask.subtype, SoftMaskType::Luminosity);
+                assert_eq!(mask.group.get_inner().id, 5);
+            }
+            ref m => panic!("{:?}", m),
+        }
+        match gs.halftone {
+            Some(Halftone::Screen(ref h)) => assert_eq!(h.angle, 45.),
+            ref h => panic!("{:?}", h),
+        }
+
+        let written = gs.to_primitive(&mut NoUpdate).unwrap().into_dictionary().unwrap();
+        assert_eq!(written.get("BM"), Some(&Primitive::Name("Multiply".into())));
+        assert_eq!(written.get("TR"), Some(&Primitive::Name("Identity".into())));
+    }
+}
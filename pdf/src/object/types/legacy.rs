@@ -0,0 +1,262 @@
+//! Catalog entries that few documents have and few applications look at: web capture
+//! (PDF32000-2 14.10) and the legal attestation of a signed document (12.8.7).
+//!
+//! The catalog keeps them as plain dictionaries; the `legacy` feature adds the typed versions.
+
+use super::prelude::*;
+
+/// The web capture information of a document (the `SpiderInfo` catalog entry):
+/// the commands that retrieved its pages.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct SpiderInfo {
+    /// the version of the web capture extension, `1.0`
+    #[pdf(key = "V")]
+    pub version: f32,
+
+    /// the commands, in the order they were run
+    #[pdf(key = "C")]
+    pub commands: Vec<WebCaptureCommand>,
+}
+
+/// One request that added content to the document.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct WebCaptureCommand {
+    #[pdf(key = "URL")]
+    pub url: PdfString,
+
+    /// how many levels of links were followed
+    #[pdf(key = "L", default = "1")]
+    pub levels: i32,
+
+    /// bit 1: same site only, bit 2: same path only, bit 3: submit form data
+    #[pdf(key = "F", default = "0")]
+    pub flags: u32,
+
+    /// data sent with a POST request, a string or a stream
+    #[pdf(key = "P")]
+    pub post_data: Option<Primitive>,
+
+    /// the content type of `post_data`
+    #[pdf(key = "CT")]
+    pub content_type: Option<PdfString>,
+
+    /// additional HTTP request headers
+    #[pdf(key = "H")]
+    pub headers: Option<PdfString>,
+
+    /// how the content was converted
+    #[pdf(key = "S")]
+    pub settings: Option<Dictionary>,
+}
+
+/// The pages (`SPS`) or images (`SIS`) retrieved from one source, which the `IDS` and `URLS` name
+/// trees map digital identifiers and URLs to.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "SpiderContentSet?")]
+pub struct WebCaptureContentSet {
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    /// the MD5 digest that identifies the content
+    #[pdf(key = "ID")]
+    pub id: PdfString,
+
+    /// the pages or image XObjects
+    #[pdf(key = "O")]
+    pub objects: Vec<Primitive>,
+
+    /// where the content came from; one or several source information dictionaries
+    #[pdf(key = "SI")]
+    pub source: Vec<WebCaptureSource>,
+
+    #[pdf(key = "CT")]
+    pub content_type: Option<PdfString>,
+
+    /// when the content was retrieved
+    #[pdf(key = "TS")]
+    pub timestamp: Option<Date>,
+
+    /// the title of a page set
+    #[pdf(key = "T")]
+    pub title: Option<PdfString>,
+
+    /// the identifier of the text of a page set
+    #[pdf(key = "TID")]
+    pub text_id: Option<PdfString>,
+
+    /// the reference counts of an image set
+    #[pdf(key = "R")]
+    pub reference_counts: Option<Primitive>,
+}
+
+/// Where and when some content was retrieved.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct WebCaptureSource {
+    /// the URL, or a URL alias dictionary with the redirections that led to it
+    #[pdf(key = "AU")]
+    pub url: Primitive,
+
+    /// when the content was last modified
+    #[pdf(key = "TS")]
+    pub timestamp: Option<Date>,
+
+    /// when the content expires
+    #[pdf(key = "E")]
+    pub expires: Option<Date>,
+
+    /// 0: not a submission, 1: GET, 2: POST
+    #[pdf(key = "S", default = "0")]
+    pub submission: i32,
+
+    /// the command that retrieved the content
+    #[pdf(key = "C")]
+    pub command: Option<Primitive>,
+}
+
+impl WebCaptureSource {
+    /// The URL of the content, after any redirections.
+    pub fn url(&self, resolve: &impl Resolve) -> Result<Option<PdfString>> {
+        match self.url.clone().resolve(resolve)? {
+            Primitive::String(url) => Ok(Some(url)),
+            // a URL alias: `U` is the original one, `C` the chains of redirections
+            Primitive::Dictionary(dict) => match dict.get("U") {
+                Some(u) => Ok(Some(t!(u.clone().resolve(resolve)?.into_string()))),
+                None => Ok(None),
+            },
+            p => Err(PdfError::UnexpectedPrimitive { expected: "String or Dictionary", found: p.get_debug_name() }),
+        }
+    }
+}
+
+/// The content a document contains that could make what it shows differ from what was signed
+/// (the `Legal` catalog entry). Each number counts the occurrences.
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize)]
+pub struct LegalAttestation {
+    #[pdf(key = "JavaScriptActions")]
+    pub javascript_actions: Option<i32>,
+
+    #[pdf(key = "LaunchActions")]
+    pub launch_actions: Option<i32>,
+
+    #[pdf(key = "URIActions")]
+    pub uri_actions: Option<i32>,
+
+    #[pdf(key = "MovieActions")]
+    pub movie_actions: Option<i32>,
+
+    #[pdf(key = "SoundActions")]
+    pub sound_actions: Option<i32>,
+
+    #[pdf(key = "HideAnnotationActions")]
+    pub hide_annotation_actions: Option<i32>,
+
+    #[pdf(key = "GoToRemoteActions")]
+    pub goto_remote_actions: Option<i32>,
+
+    #[pdf(key = "AlternateImages")]
+    pub alternate_images: Option<i32>,
+
+    #[pdf(key = "ExternalStreams")]
+    pub external_streams: Option<i32>,
+
+    #[pdf(key = "TrueTypeFonts")]
+    pub truetype_fonts: Option<i32>,
+
+    #[pdf(key = "ExternalRefXobjects")]
+    pub external_ref_xobjects: Option<i32>,
+
+    #[pdf(key = "ExternalOPIdicts")]
+    pub external_opi_dicts: Option<i32>,
+
+    #[pdf(key = "NonEmbeddedFonts")]
+    pub non_embedded_fonts: Option<i32>,
+
+    #[pdf(key = "DevDepGS_OP")]
+    pub dev_dep_gs_op: Option<i32>,
+
+    #[pdf(key = "DevDepGS_HT")]
+    pub dev_dep_gs_ht: Option<i32>,
+
+    #[pdf(key = "DevDepGS_TR")]
+    pub dev_dep_gs_tr: Option<i32>,
+
+    #[pdf(key = "DevDepGS_UCR")]
+    pub dev_dep_gs_ucr: Option<i32>,
+
+    #[pdf(key = "DevDepGS_BG")]
+    pub dev_dep_gs_bg: Option<i32>,
+
+    #[pdf(key = "DevDepGS_FL")]
+    pub dev_dep_gs_fl: Option<i32>,
+
+    #[pdf(key = "Annotations")]
+    pub annotations: Option<i32>,
+
+    #[pdf(key = "OptionalContent", default = "false")]
+    pub optional_content: bool,
+
+    /// the author's explanation of the content
+    #[pdf(key = "Attestation")]
+    pub attestation: Option<PdfString>,
+}
+
+impl Catalog {
+    pub fn spider_info(&self, resolve: &impl Resolve) -> Result<Option<SpiderInfo>> {
+        self.spider_info.clone().map(|d| SpiderInfo::from_dict(d, resolve)).transpose()
+    }
+
+    pub fn legal(&self, resolve: &impl Resolve) -> Result<Option<LegalAttestation>> {
+        self.legal.clone().map(|d| LegalAttestation::from_dict(d, resolve)).transpose()
+    }
+}
+
+impl NameDictionary {
+    /// The web capture content with the digital identifier `id` (in the `IDS` name tree).
+    pub fn web_capture_by_id(&self, id: &[u8], resolve: &impl Resolve) -> Result<Option<WebCaptureContentSet>> {
+        content_set(self.ids.as_ref(), id, resolve)
+    }
+
+    /// The web capture content retrieved from `url` (in the `URLS` name tree).
+    pub fn web_capture_by_url(&self, url: &str, resolve: &impl Resolve) -> Result<Option<WebCaptureContentSet>> {
+        content_set(self.urls.as_ref(), url.as_bytes(), resolve)
+    }
+}
+
+fn content_set(tree: Option<&NameTree<Primitive>>, key: &[u8], resolve: &impl Resolve) -> Result<Option<WebCaptureContentSet>> {
+    let p = match tree {
+        Some(tree) => t!(tree.get(key, resolve)),
+        None => return Ok(None),
+    };
+    // the value may be an array of content sets with the same identifier or URL
+    match p.map(|p| p.resolve(resolve)).transpose()? {
+        Some(Primitive::Array(sets)) => match sets.into_iter().next() {
+            Some(set) => WebCaptureContentSet::from_primitive(set, resolve).map(Some),
+            None => Ok(None),
+        },
+        Some(p) => WebCaptureContentSet::from_primitive(p, resolve).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn web_capture() {
+        let p = parse(b"<< /V 1.0 /C [<< /URL (https://example.com/) /L 2 /F 1 /CT (text/html) >>] >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let info = SpiderInfo::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!((info.commands[0].levels, info.commands[0].flags), (2, 1));
+
+        let p = parse(b"<< /Type /SpiderContentSet /S /SPS /ID <00112233> /O [] /T (Example)
+            /SI << /AU << /U (https://example.com/) /C [] >> /TS (D:20240101000000Z) /S 1 >> >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let set = WebCaptureContentSet::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(set.source.len(), 1);
+        assert_eq!(set.source[0].url(&NoResolve).unwrap().unwrap().as_bytes(), b"https://example.com/");
+
+        let p = parse(b"<< /JavaScriptActions 2 /TrueTypeFonts 1 /Attestation (reviewed) >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let legal = LegalAttestation::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!((legal.javascript_actions, legal.launch_actions), (Some(2), None));
+    }
+}
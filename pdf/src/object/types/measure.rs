@@ -0,0 +1,297 @@
+use super::prelude::*;
+use crate::content::Point;
+
+/// A measure dictionary (PDF32000-2 12.9), giving the scale and units of a viewport,
+/// an image or a form, or the geospatial coordinates it covers.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Measure {
+    /// `/Subtype /RL`, the default: a rectilinear coordinate system with a scale for each axis.
+    Rectilinear(RectilinearMeasure),
+    /// `/Subtype /GEO`: registration of the content to geographic coordinates.
+    Geospatial(GeoMeasure),
+}
+impl Object for Measure {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let dict = t!(p.resolve(resolve)?.into_dictionary());
+        let subtype = dict.get("Subtype").map(|s| s.as_name().map(|s| s.to_owned())).transpose()?;
+        match subtype.as_deref() {
+            None | Some("RL") => Ok(Measure::Rectilinear(t!(RectilinearMeasure::from_dict(dict, resolve)))),
+            Some("GEO") => Ok(Measure::Geospatial(t!(GeoMeasure::from_dict(dict, resolve)))),
+            Some(s) => Err(PdfError::UnknownVariant { id: "Measure", name: s.to_owned() }),
+        }
+    }
+}
+impl ObjectWrite for Measure {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let (mut dict, subtype) = match *self {
+            Measure::Rectilinear(ref m) => (m.to_dict(update)?, "RL"),
+            Measure::Geospatial(ref m) => (m.to_dict(update)?, "GEO"),
+        };
+        dict.insert("Subtype", Name::from(subtype));
+        Ok(Primitive::Dictionary(dict))
+    }
+}
+
+/// The scale and units of a rectilinear measure.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Measure?")]
+pub struct RectilinearMeasure {
+    /// the scale ratio as text, like `1in = 0.1 mi`
+    #[pdf(key = "R")]
+    pub ratio: PdfString,
+
+    /// units along the x axis, from the largest to the smallest
+    #[pdf(key = "X")]
+    pub x: Vec<NumberFormat>,
+
+    /// units along the y axis, if they differ from `x`
+    #[pdf(key = "Y")]
+    pub y: Option<Vec<NumberFormat>>,
+
+    /// units of distance in any direction, converted from the units of `x`
+    #[pdf(key = "D")]
+    pub distance: Vec<NumberFormat>,
+
+    /// units of area, converted from the units of `x` squared
+    #[pdf(key = "A")]
+    pub area: Vec<NumberFormat>,
+
+    /// units of angles
+    #[pdf(key = "T")]
+    pub angle: Option<Vec<NumberFormat>>,
+
+    /// units of slope (change in y over change in x)
+    #[pdf(key = "S")]
+    pub slope: Option<Vec<NumberFormat>>,
+
+    /// the origin of the measurement coordinate system, in default user space
+    #[pdf(key = "O")]
+    pub origin: Option<Vec<f32>>,
+
+    /// converts the units of `y` to those of `x`, only used with `y`
+    #[pdf(key = "CYX")]
+    pub cyx: Option<f32>,
+}
+
+impl RectilinearMeasure {
+    /// The distance between two points of default user space, in the first unit of `distance`.
+    ///
+    /// `None` if `x` or `distance` has no number format.
+    pub fn distance(&self, from: Point, to: Point) -> Option<f32> {
+        let x = self.x.first()?.conversion;
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        // both in units of x
+        let dy = match self.y.as_ref().and_then(|y| y.first()) {
+            Some(y) => dy * y.conversion * self.cyx.unwrap_or(1.),
+            None => dy * x,
+        };
+        Some((dx * x).hypot(dy) * self.distance.first()?.conversion)
+    }
+
+    /// The label of the units of [`RectilinearMeasure::distance`].
+    pub fn distance_unit(&self) -> Option<&PdfString> {
+        self.distance.first().map(|f| &f.unit)
+    }
+}
+
+/// How to convert and show a measurement in one unit (a number format dictionary).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "NumberFormat?")]
+pub struct NumberFormat {
+    /// the label of the unit, like `mi`
+    #[pdf(key = "U")]
+    pub unit: PdfString,
+
+    /// multiplies the value in the previous unit (or default user space) to get this one
+    #[pdf(key = "C")]
+    pub conversion: f32,
+
+    /// `D` (decimal, the default), `F` (fraction), `R` (round) or `T` (truncate)
+    #[pdf(key = "F")]
+    pub fraction_format: Option<Name>,
+
+    /// the precision (a power of 10) of decimals, or the denominator of fractions; 100 if not given
+    #[pdf(key = "D")]
+    pub denominator: Option<i32>,
+
+    /// whether fractions are not reduced
+    #[pdf(key = "FD", default = "false")]
+    pub no_reduce: bool,
+
+    /// thousands separator, `,` if not given
+    #[pdf(key = "RT")]
+    pub thousands: Option<PdfString>,
+
+    /// decimal separator, `.` if not given
+    #[pdf(key = "RD")]
+    pub decimal: Option<PdfString>,
+
+    /// text before the value
+    #[pdf(key = "PS")]
+    pub prefix: Option<PdfString>,
+
+    /// text after the value
+    #[pdf(key = "SS")]
+    pub suffix: Option<PdfString>,
+
+    /// `S` if the unit comes after the value (the default), `P` if before
+    #[pdf(key = "O")]
+    pub position: Option<Name>,
+}
+
+/// The geographic registration of a viewport, image or form.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Measure?")]
+pub struct GeoMeasure {
+    /// a polygon in the unit square of the viewport (or image, or form) that the map covers,
+    /// as pairs of numbers; the whole square if not given
+    #[pdf(key = "Bounds")]
+    pub bounds: Option<Vec<f32>>,
+
+    /// the geographic coordinate system of `gpts`
+    #[pdf(key = "GCS")]
+    pub gcs: CoordinateSystem,
+
+    /// the coordinate system to show coordinates in, if not `gcs`
+    #[pdf(key = "DCS")]
+    pub dcs: Option<CoordinateSystem>,
+
+    /// preferred units of distance, area and angles, like `M`, `SQKM` and `DEG`
+    #[pdf(key = "PDU")]
+    pub units: Option<Vec<Name>>,
+
+    /// latitude and longitude pairs of the registration points
+    #[pdf(key = "GPTS")]
+    pub gpts: Vec<f32>,
+
+    /// the same points in the unit square, as x and y pairs; the corners of `bounds` if not given
+    #[pdf(key = "LPTS")]
+    pub lpts: Option<Vec<f32>>,
+}
+
+impl GeoMeasure {
+    /// The registration points, as pairs of a point in the unit square and its latitude and longitude.
+    pub fn points(&self) -> Vec<(Point, [f32; 2])> {
+        let local = self.lpts.as_ref().or(self.bounds.as_ref());
+        let geo = self.gpts.chunks_exact(2).map(|g| [g[0], g[1]]);
+        match local {
+            Some(local) => local.chunks_exact(2).map(|l| Point { x: l[0], y: l[1] }).zip(geo).collect(),
+            None => {
+                let corners = [(0., 0.), (0., 1.), (1., 1.), (1., 0.)].map(|(x, y)| Point { x, y });
+                corners.into_iter().zip(geo).collect()
+            }
+        }
+    }
+}
+
+/// A geographic (`GEOGCS`) or projected (`PROJCS`) coordinate system.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct CoordinateSystem {
+    #[pdf(key = "Type")]
+    pub typ: Name,
+
+    /// the EPSG code
+    #[pdf(key = "EPSG")]
+    pub epsg: Option<i32>,
+
+    /// the well-known text of the coordinate system, if there is no `epsg`
+    #[pdf(key = "WKT")]
+    pub wkt: Option<PdfString>,
+}
+
+/// A region of a page with its own measure (an entry of the page's `VP` array).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Viewport?")]
+pub struct Viewport {
+    /// where the viewport is on the page, in default user space
+    #[pdf(key = "BBox")]
+    pub bbox: Rectangle,
+
+    #[pdf(key = "Name")]
+    pub name: Option<PdfString>,
+
+    #[pdf(key = "Measure")]
+    pub measure: Option<Measure>,
+
+    #[pdf(key = "PtData")]
+    pub point_data: Option<PointData>,
+}
+
+impl Viewport {
+    pub fn contains(&self, p: Point) -> bool {
+        let Rectangle { left, bottom, right, top } = self.bbox;
+        (left.min(right)..=left.max(right)).contains(&p.x) && (bottom.min(top)..=bottom.max(top)).contains(&p.y)
+    }
+
+    /// `p` of default user space in the unit square of the viewport, which `GeoMeasure` uses.
+    pub fn unit_point(&self, p: Point) -> Point {
+        let Rectangle { left, bottom, right, top } = self.bbox;
+        Point { x: (p.x - left) / (right - left), y: (p.y - bottom) / (top - bottom) }
+    }
+}
+
+/// Extended geospatial data (a point data dictionary).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "PtData?")]
+pub struct PointData {
+    /// always `Cloud`
+    #[pdf(key = "Subtype")]
+    pub subtype: Name,
+
+    /// what the values of each point are, like `LAT`, `LON` and `ALT`
+    #[pdf(key = "Names")]
+    pub names: Vec<Name>,
+
+    /// the points, each an array with a value for each of `names`
+    #[pdf(key = "XPTS")]
+    pub points: Vec<Vec<Primitive>>,
+}
+
+impl Page {
+    /// The viewports of this page (`VP`).
+    pub fn viewports(&self, resolve: &impl Resolve) -> Result<Vec<Viewport>> {
+        match self.vp {
+            Some(ref vp) => Vec::from_primitive(vp.clone(), resolve),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The viewport that applies at `p`: the last one that contains it.
+    pub fn viewport_at(&self, p: Point, resolve: &impl Resolve) -> Result<Option<Viewport>> {
+        Ok(self.viewports(resolve)?.into_iter().rev().find(|vp| vp.contains(p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn viewports() {
+        let p = parse(b"[<< /Type /Viewport /BBox [0 0 100 100] /Name (plan) /Measure << /Type /Measure /R (1in = 1ft)
+            /X [<< /U (ft) /C 0.0138889 >>] /D [<< /U (ft) /C 1 >> << /U (in) /C 12 >>] /A [<< /U (sq ft) /C 1 >>] >> >>
+            << /BBox [50 50 200 200] /Measure << /Subtype /GEO /GPTS [48 11 49 11 49 12 48 12]
+            /GCS << /Type /GEOGCS /EPSG 4326 >> >> >>]", &NoResolve, ParseFlags::ANY).unwrap();
+        let vps = Vec::<Viewport>::from_primitive(p, &NoResolve).unwrap();
+        let measure = match vps[0].measure {
+            Some(Measure::Rectilinear(ref m)) => m,
+            ref m => panic!("{:?}", m),
+        };
+        let d = measure.distance(Point { x: 0., y: 0. }, Point { x: 72. * 3., y: 72. * 4. }).unwrap();
+        assert!((d - 5.).abs() < 0.001);
+        assert_eq!(measure.distance_unit().unwrap().as_bytes(), b"ft");
+        match vps[1].measure {
+            Some(Measure::Geospatial(ref m)) => {
+                assert_eq!(m.gcs.epsg, Some(4326));
+                assert_eq!(m.points()[2], (Point { x: 1., y: 1. }, [49., 12.]));
+            }
+            ref m => panic!("{:?}", m),
+        }
+        assert_eq!(vps[1].unit_point(Point { x: 125., y: 50. }), Point { x: 0.5, y: 0. });
+
+        let written = vps[1].measure.as_ref().unwrap().to_primitive(&mut NoUpdate).unwrap();
+        assert_eq!(written.into_dictionary().unwrap().get("Subtype").unwrap().as_name().unwrap(), "GEO");
+    }
+}
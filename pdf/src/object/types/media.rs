@@ -0,0 +1,283 @@
+use super::prelude::*;
+
+/// Media clips nested deeper than this (through sections) are ignored.
+const MAX_DEPTH: usize = 16;
+
+/// The entries of a rich media annotation (PDF32000-2 13.7.2), see [`Annot::rich_media`].
+#[derive(Object, ObjectWrite, Debug, DataSize)]
+pub struct RichMedia {
+    #[pdf(key = "RichMediaContent")]
+    pub content: RichMediaContent,
+
+    /// when the annotation is activated and deactivated
+    #[pdf(key = "RichMediaSettings")]
+    pub settings: Option<Dictionary>,
+}
+
+/// The files and configurations of a rich media annotation.
+#[derive(Object, ObjectWrite, Debug, DataSize)]
+#[pdf(Type = "RichMediaContent?")]
+pub struct RichMediaContent {
+    /// the embedded files, by the names the configurations use
+    #[pdf(key = "Assets")]
+    pub assets: Option<NameTree<FileSpec>>,
+
+    /// the ways to show the content, the first one is the default
+    #[pdf(key = "Configurations")]
+    pub configurations: Option<Vec<RichMediaConfiguration>>,
+
+    #[pdf(key = "Views")]
+    pub views: Option<Vec<Primitive>>,
+}
+
+/// A way to show rich media content, with a player for each instance.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "RichMediaConfiguration?")]
+pub struct RichMediaConfiguration {
+    /// `3D`, `Flash`, `Sound` or `Video`
+    #[pdf(key = "Subtype")]
+    pub subtype: Option<Name>,
+
+    #[pdf(key = "Name")]
+    pub name: Option<PdfString>,
+
+    #[pdf(key = "Instances")]
+    pub instances: Option<Vec<RichMediaInstance>>,
+}
+
+/// One asset played in a configuration.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "RichMediaInstance?")]
+pub struct RichMediaInstance {
+    #[pdf(key = "Subtype")]
+    pub subtype: Option<Name>,
+
+    /// the file, one of the assets of the content
+    #[pdf(key = "Asset")]
+    pub asset: Option<Ref<FileSpec>>,
+
+    #[pdf(key = "Params")]
+    pub params: Option<Dictionary>,
+}
+
+/// A rendition (PDF32000-2 13.2.3), what a rendition action of a screen annotation plays.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Rendition?")]
+pub struct Rendition {
+    /// `MR` for a media rendition, `SR` for a selector of `renditions`
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    #[pdf(key = "N")]
+    pub name: Option<PdfString>,
+
+    /// the media of a media rendition
+    #[pdf(key = "C")]
+    pub clip: Option<MediaClip>,
+
+    /// the choices of a selector rendition, in order of preference
+    #[pdf(key = "R")]
+    pub renditions: Option<Vec<Rendition>>,
+}
+
+/// A media clip: data (`MCD`) or a section of another clip (`MCS`).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "MediaClip?")]
+pub struct MediaClip {
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    #[pdf(key = "N")]
+    pub name: Option<PdfString>,
+
+    /// the data (a file specification or a form XObject), or the clip a section is part of
+    #[pdf(key = "D")]
+    pub data: Option<Primitive>,
+
+    /// the MIME type, like `video/mp4`
+    #[pdf(key = "CT")]
+    pub content_type: Option<PdfString>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+
+impl MediaClip {
+    /// The clip with the data: this one, or the one a section is part of.
+    pub fn data_clip(&self, resolve: &impl Resolve) -> Result<MediaClip> {
+        let mut clip = self.clone();
+        for _ in 0 .. MAX_DEPTH {
+            let data = match (clip.subtype.as_str(), clip.data.as_ref()) {
+                ("MCS", Some(data)) => data.clone(),
+                _ => return Ok(clip),
+            };
+            clip = t!(MediaClip::from_primitive(data, resolve));
+        }
+        bail!("media clip sections nested too deeply")
+    }
+
+    /// The file of the data, looking through sections.
+    pub fn file(&self, resolve: &impl Resolve) -> Result<Option<FileSpec>> {
+        match t!(self.data_clip(resolve)).data {
+            Some(data) => file_spec(data, resolve).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The entries of a movie annotation.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct Movie {
+    /// the movie file, usually an external one
+    #[pdf(key = "F")]
+    pub file: Primitive,
+
+    /// width and height in pixels
+    #[pdf(key = "Aspect")]
+    pub aspect: Option<Vec<i32>>,
+
+    #[pdf(key = "Rotate", default = "0")]
+    pub rotate: i32,
+
+    #[pdf(key = "Poster")]
+    pub poster: Option<Primitive>,
+}
+
+/// The dictionary of a sound stream, whose data are the samples.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Sound?")]
+pub struct SoundDict {
+    /// samples per second
+    #[pdf(key = "R")]
+    pub rate: f32,
+
+    #[pdf(key = "C", default = "1")]
+    pub channels: u32,
+
+    #[pdf(key = "B", default = "8")]
+    pub bits_per_sample: u32,
+
+    /// `Raw`, `Signed`, `muLaw` or `ALaw`
+    #[pdf(key = "E")]
+    pub encoding: Option<Name>,
+
+    /// the format of compressed sound data
+    #[pdf(key = "CO")]
+    pub compression: Option<Name>,
+}
+
+/// A file specification, which may be a plain file name.
+fn file_spec(p: Primitive, resolve: &impl Resolve) -> Result<FileSpec> {
+    match p.resolve(resolve)? {
+        Primitive::String(name) => Ok(FileSpec { file_name: Some(name), ..FileSpec::default() }),
+        p => FileSpec::from_primitive(p, resolve),
+    }
+}
+
+/// Where the data of a [`MediaItem`] is.
+#[derive(Debug, Clone)]
+pub enum MediaData {
+    /// an embedded or external file, see [`FileSpec::embedded_file`]
+    File(Box<FileSpec>),
+    /// the samples of a sound annotation
+    Sound(RcRef<Stream<SoundDict>>),
+}
+
+/// Media used by an annotation, see [`Page::media`].
+#[derive(Debug, Clone)]
+pub struct MediaItem {
+    pub annot: MaybeRef<Annot>,
+    /// the name of the asset, rendition or clip
+    pub name: Option<String>,
+    /// the MIME type, if the document gives it
+    pub content_type: Option<String>,
+    pub data: MediaData,
+}
+
+impl Annot {
+    /// The rich media entries, if this is a `RichMedia` annotation.
+    pub fn rich_media(&self, resolve: &impl Resolve) -> Result<Option<RichMedia>> {
+        if self.subtype.as_str() != "RichMedia" {
+            return Ok(None);
+        }
+        RichMedia::from_dict(self.other.clone(), resolve).map(Some)
+    }
+
+    /// The rendition played by the action of a `Screen` annotation.
+    pub fn rendition(&self, resolve: &impl Resolve) -> Result<Option<Rendition>> {
+        if self.subtype.as_str() != "Screen" {
+            return Ok(None);
+        }
+        let action = match self.other.get("A") {
+            Some(action) => t!(action.clone().resolve(resolve)?.into_dictionary()),
+            None => return Ok(None),
+        };
+        match (action.get("S").and_then(|s| s.as_name().ok()), action.get("R")) {
+            (Some("Rendition"), Some(r)) => Rendition::from_primitive(r.clone(), resolve).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The movie, if this is a `Movie` annotation.
+    pub fn movie(&self, resolve: &impl Resolve) -> Result<Option<Movie>> {
+        match (self.subtype.as_str(), self.other.get("Movie")) {
+            ("Movie", Some(movie)) => Movie::from_primitive(movie.clone(), resolve).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The sound stream, if this is a `Sound` annotation.
+    pub fn sound(&self, resolve: &impl Resolve) -> Result<Option<RcRef<Stream<SoundDict>>>> {
+        match (self.subtype.as_str(), self.other.get("Sound")) {
+            ("Sound", Some(sound)) => RcRef::from_primitive(sound.clone(), resolve).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Page {
+    /// The media of the rich media, screen, movie and sound annotations of this page.
+    ///
+    /// Rich media annotations list all of their assets, screen annotations the clips of their
+    /// rendition (of all choices of a selector rendition).
+    pub fn media(&self, resolve: &impl Resolve) -> Result<Vec<MediaItem>> {
+        fn renditions(rendition: &Rendition, annot: &MaybeRef<Annot>, resolve: &impl Resolve, out: &mut Vec<MediaItem>, depth: usize) -> Result<()> {
+            if depth > MAX_DEPTH {
+                bail!("selector renditions nested too deeply");
+            }
+            if let Some(ref clip) = rendition.clip {
+                let data = t!(clip.data_clip(resolve));
+                if let Some(file) = t!(data.file(resolve)) {
+                    let name = clip.name.as_ref().or(rendition.name.as_ref()).map(|n| n.to_string_lossy());
+                    let content_type = data.content_type.as_ref().map(|t| t.to_string_lossy());
+                    out.push(MediaItem { annot: annot.clone(), name, content_type, data: MediaData::File(Box::new(file)) });
+                }
+            }
+            for r in rendition.renditions.iter().flatten() {
+                renditions(r, annot, resolve, out, depth + 1)?;
+            }
+            Ok(())
+        }
+
+        let mut out = vec![];
+        for annot in t!(self.annotations(resolve)) {
+            if let Some(rich_media) = t!(annot.rich_media(resolve)) {
+                if let Some(ref assets) = rich_media.content.assets {
+                    let mut files = vec![];
+                    t!(assets.walk(resolve, &mut |name, spec| files.push((name.to_string_lossy(), spec.clone()))));
+                    for (name, file) in files {
+                        out.push(MediaItem { annot: annot.clone(), name: Some(name), content_type: None, data: MediaData::File(Box::new(file)) });
+                    }
+                }
+            } else if let Some(rendition) = t!(annot.rendition(resolve)) {
+                renditions(&rendition, &annot, resolve, &mut out, 0)?;
+            } else if let Some(movie) = t!(annot.movie(resolve)) {
+                let file = t!(file_spec(movie.file, resolve));
+                out.push(MediaItem { annot: annot.clone(), name: None, content_type: None, data: MediaData::File(Box::new(file)) });
+            } else if let Some(sound) = t!(annot.sound(resolve)) {
+                out.push(MediaItem { annot: annot.clone(), name: None, content_type: None, data: MediaData::Sound(sound) });
+            }
+        }
+        Ok(out)
+    }
+}
@@ -4,13 +4,13 @@ use super::prelude::*;
 pub enum Counter {
     #[pdf(name = "D")]
     Arabic,
-    #[pdf(name = "r")]
-    RomanUpper,
     #[pdf(name = "R")]
+    RomanUpper,
+    #[pdf(name = "r")]
     RomanLower,
-    #[pdf(name = "a")]
-    AlphaUpper,
     #[pdf(name = "A")]
+    AlphaUpper,
+    #[pdf(name = "a")]
     AlphaLower,
 }
 
@@ -1,4 +1,5 @@
 use super::prelude::*;
+use std::collections::HashSet;
 
 #[derive(Object, Debug, Clone, DataSize)]
 pub struct OutlineItem {
@@ -36,9 +37,39 @@ pub struct OutlineItem {
     pub flags: Option<i32>,
 }
 
+impl OutlineItem {
+    /// The direct children of this item, following the `Next` links from `First`.
+    ///
+    /// Fails with `ReferenceCycle` if the links loop.
+    pub fn children(&self, resolve: &impl Resolve) -> Result<Vec<RcRef<OutlineItem>>> {
+        outline_siblings(self.first, resolve)
+    }
+}
+
+fn outline_siblings(first: Option<Ref<OutlineItem>>, resolve: &impl Resolve) -> Result<Vec<RcRef<OutlineItem>>> {
+    let mut items: Vec<RcRef<OutlineItem>> = vec![];
+    let mut seen = HashSet::new();
+    let mut next = first;
+    while let Some(r) = next {
+        if !seen.insert(r.get_inner()) {
+            return Err(PdfError::ReferenceCycle {
+                obj_nr: r.get_inner().id,
+                chain: items.iter().map(|item| item.get_ref().get_inner().id).collect(),
+            });
+        }
+        let item = t!(resolve.get(r));
+        next = item.next;
+        items.push(item);
+    }
+    Ok(items)
+}
+
 #[derive(Clone, Debug, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Action {
     Goto(MaybeNamedDest),
+    /// A predefined viewer action, like `NextPage` or `Print`
+    Named(Name),
     Other(Dictionary),
 }
 impl Object for Action {
@@ -53,6 +84,7 @@ impl Object for Action {
                 ));
                 Ok(Action::Goto(dest))
             }
+            "Named" => Ok(Action::Named(t!(d.require("Action", "N")?.into_name()))),
             _ => Ok(Action::Other(d)),
         }
     }
@@ -62,9 +94,16 @@ impl ObjectWrite for Action {
         match self {
             Action::Goto(dest) => {
                 let mut dict = Dictionary::new();
+                dict.insert("S", Name::from("GoTo"));
                 dict.insert("D", dest.to_primitive(update)?);
                 Ok(Primitive::Dictionary(dict))
             }
+            Action::Named(name) => {
+                let mut dict = Dictionary::new();
+                dict.insert("S", Name::from("Named"));
+                dict.insert("N", name.clone());
+                Ok(Primitive::Dictionary(dict))
+            }
             Action::Other(dict) => Ok(Primitive::Dictionary(dict.clone())),
         }
     }
@@ -82,3 +121,11 @@ pub struct Outlines {
     #[pdf(key = "Last")]
     pub last: Option<Ref<OutlineItem>>,
 }
+impl Outlines {
+    /// The top-level outline items, following the `Next` links from `First`.
+    ///
+    /// Fails with `ReferenceCycle` if the links loop.
+    pub fn children(&self, resolve: &impl Resolve) -> Result<Vec<RcRef<OutlineItem>>> {
+        outline_siblings(self.first, resolve)
+    }
+}
@@ -1,4 +1,5 @@
 use super::prelude::*;
+use crate::enc::{encode, LZWFlateParams, StreamFilter};
 
 /*
 use std::iter::once;
@@ -61,6 +62,7 @@ impl ObjectWrite for PageRc {
 /// A `PagesNode::Tree` wrapped in a `RcRef`
 ///
 #[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PagesRc(RcRef<PagesNode>);
 impl Deref for PagesRc {
     type Target = PageTree;
@@ -130,6 +132,22 @@ pub struct Page {
     #[pdf(key = "Annots")]
     pub annotations: Lazy<Vec<MaybeRef<Annot>>>,
 
+    /// A small image of the page, see [`Page::thumbnail`].
+    #[pdf(key = "Thumb")]
+    pub thumb: Option<Ref<ImageXObject>>,
+
+    /// Key of this page in the `ParentTree` of the structure tree.
+    #[pdf(key = "StructParents")]
+    pub struct_parents: Option<i32>,
+
+    /// when the page was last changed, to compare with the dates in `piece_info`
+    #[pdf(key = "LastModified")]
+    pub last_modified: Option<Date>,
+
+    /// private data of the applications that edited the page
+    #[pdf(key = "PieceInfo")]
+    pub piece_info: Option<PieceInfo>,
+
     #[pdf(other)]
     pub other: Dictionary,
 }
@@ -161,6 +179,10 @@ impl Page {
             vp: None,
             other: Dictionary::new(),
             annotations: Default::default(),
+            thumb: None,
+            struct_parents: None,
+            last_modified: None,
+            piece_info: None,
         }
     }
     pub fn media_box(&self) -> Result<Rectangle> {
@@ -197,7 +219,405 @@ impl Page {
 }
 impl SubType<PagesNode> for Page {}
 
-#[derive(Object, DataSize, Debug, ObjectWrite)]
+/// A problem with the page boundaries, as reported by [`Page::audit_boxes`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageBoxIssue {
+    /// The lower left corner of the MediaBox is not at the origin.
+    MediaBoxOrigin { left: f32, bottom: f32 },
+
+    /// The corners of the box are not given as lower-left, upper-right.
+    Inverted { name: &'static str },
+
+    /// The box has no area.
+    Empty { name: &'static str },
+
+    /// The box extends beyond the MediaBox.
+    ExceedsMediaBox { name: &'static str },
+
+    /// `Rotate` is not a multiple of 90 in the range `0 .. 360`.
+    Rotation { rotate: i32 },
+}
+
+fn translated(r: Rectangle, dx: f32, dy: f32) -> Rectangle {
+    Rectangle {
+        left: r.left + dx,
+        bottom: r.bottom + dy,
+        right: r.right + dx,
+        top: r.top + dy,
+    }
+}
+
+impl Page {
+    /// The thumbnail image of this page, if it has one.
+    pub fn thumbnail(&self, resolve: &impl Resolve) -> Result<Option<RcRef<ImageXObject>>> {
+        self.thumb.map(|r| resolve.get(r)).transpose()
+    }
+
+    /// Attach a thumbnail made of 8 bit RGB pixels, row by row from the top.
+    pub fn set_thumbnail(&mut self, width: u32, height: u32, rgb: &[u8], update: &mut impl Updater) -> Result<()> {
+        if rgb.len() != width as usize * height as usize * 3 {
+            bail!("thumbnail of {}x{} pixels needs {} bytes, got {}", width, height, width as usize * height as usize * 3, rgb.len());
+        }
+        let dict = ImageDict {
+            width,
+            height,
+            color_space: Some(ColorSpace::DeviceRGB),
+            bits_per_component: Some(8),
+            ..Default::default()
+        };
+        let filter = StreamFilter::FlateDecode(LZWFlateParams::png(3, 8, width as i32));
+        let data = t!(encode(rgb, &filter));
+        let image = ImageXObject { inner: Stream::from_compressed(dict, data, vec![filter]) };
+        self.thumb = Some(update.create(image)?.get_ref());
+        Ok(())
+    }
+
+    /// Render this page and attach the result as its thumbnail.
+    ///
+    /// The longer side of the thumbnail is `size` pixels.
+    #[cfg(feature = "render")]
+    pub fn generate_thumbnail(&mut self, size: u32, resolve: &impl Resolve, update: &mut impl Updater) -> Result<()> {
+        let (_, width, height) = t!(crate::interpret::device_matrix(self, 1.));
+        let dpi = 72. * size as f32 / width.max(height).max(1.);
+        let pixmap = t!(crate::render::render_page(self, resolve, dpi));
+        let rgb: Vec<u8> = pixmap.data.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+        self.set_thumbnail(pixmap.width, pixmap.height, &rgb, update)
+    }
+
+    /// Check the page boxes for quirks that confuse many consumers.
+    pub fn audit_boxes(&self) -> Result<Vec<PageBoxIssue>> {
+        let mut issues = vec![];
+        let media_box = self.media_box()?;
+        let boxes = [
+            ("MediaBox", Some(media_box)),
+            ("CropBox", Some(self.crop_box()?)),
+            ("TrimBox", self.trim_box),
+        ];
+        for (name, rect) in boxes {
+            let rect = match rect {
+                Some(r) => r,
+                None => continue,
+            };
+            if rect.left > rect.right || rect.bottom > rect.top {
+                issues.push(PageBoxIssue::Inverted { name });
+            }
+            let rect = rect.normalize();
+            if rect.left == rect.right || rect.bottom == rect.top {
+                issues.push(PageBoxIssue::Empty { name });
+            }
+            let media = media_box.normalize();
+            if name != "MediaBox" && (rect.left < media.left || rect.bottom < media.bottom || rect.right > media.right || rect.top > media.top) {
+                issues.push(PageBoxIssue::ExceedsMediaBox { name });
+            }
+        }
+        let media = media_box.normalize();
+        if media.left != 0. || media.bottom != 0. {
+            issues.push(PageBoxIssue::MediaBoxOrigin { left: media.left, bottom: media.bottom });
+        }
+        if self.rotate.rem_euclid(90) != 0 || !(0..360).contains(&self.rotate) {
+            issues.push(PageBoxIssue::Rotation { rotate: self.rotate });
+        }
+        Ok(issues)
+    }
+
+    /// Returns a copy of this page with normalized boxes.
+    ///
+    /// - boxes are normalized to lower-left, upper-right order and clipped to the MediaBox
+    /// - the MediaBox is moved to the origin. The content is translated accordingly and
+    ///   the annotations are moved along with [`Page::transform_annotations`].
+    ///   Note that this writes the indirect annotations to `update` right away, they are
+    ///   shared with the original page.
+    /// - `Rotate` is reduced to `0`, `90`, `180` or `270`.
+    pub fn normalize_boxes(&self, resolve: &impl Resolve, update: &mut impl Updater) -> Result<Page> {
+        let mut page = self.clone();
+        let media = self.media_box()?.normalize();
+        let (dx, dy) = (-media.left, -media.bottom);
+
+        let crop = self.crop_box()?.intersection(&media).unwrap_or(media);
+        page.media_box = Some(translated(media, dx, dy));
+        page.crop_box = Some(translated(crop, dx, dy));
+        page.trim_box = self.trim_box
+            .map(|r| r.intersection(&crop).unwrap_or(crop))
+            .map(|r| translated(r, dx, dy));
+        page.rotate = (self.rotate.rem_euclid(360) + 45) / 90 * 90 % 360;
+
+        if dx != 0. || dy != 0. {
+            if let Some(ref content) = self.contents {
+                let mut ops = vec![
+                    Op::Save,
+                    Op::Transform { matrix: Matrix { a: 1., b: 0., c: 0., d: 1., e: dx, f: dy } },
+                ];
+                ops.extend(t!(content.operations(resolve)));
+                ops.push(Op::Restore);
+                page.contents = Some(Content::from_ops(ops));
+            }
+
+            let matrix = Matrix { a: 1., b: 0., c: 0., d: 1., e: dx, f: dy };
+            page.annotations = t!(self.transform_annotations(&matrix, resolve, update));
+        }
+        Ok(page)
+    }
+
+    /// Returns a copy of this page with its content streams merged into one, which is written
+    /// when the page is updated. See [`Content::data`] for how they are joined.
+    pub fn normalize_content(&self, resolve: &impl Resolve) -> Result<Page> {
+        let mut page = self.clone();
+        if let Some(ref content) = self.contents {
+            page.contents = Some(t!(content.normalized(resolve)));
+        }
+        Ok(page)
+    }
+
+    /// Returns a copy of this page with `matrix` applied to its content.
+    ///
+    /// The page boxes become the bounding boxes of the transformed boxes
+    /// and the annotations are transformed along, see [`Page::transform_annotations`].
+    pub fn transform(&self, matrix: &Matrix, resolve: &impl Resolve, update: &mut impl Updater) -> Result<Page> {
+        let mut page = self.clone();
+        page.media_box = Some(self.media_box()?.transform(matrix));
+        page.crop_box = Some(self.crop_box()?.transform(matrix));
+        page.trim_box = self.trim_box.map(|r| r.transform(matrix));
+        if let Some(ref content) = self.contents {
+            let mut ops = vec![Op::Save, Op::Transform { matrix: *matrix }];
+            ops.extend(t!(content.operations(resolve)));
+            ops.push(Op::Restore);
+            page.contents = Some(Content::from_ops(ops));
+        }
+        page.annotations = t!(self.transform_annotations(matrix, resolve, update));
+        Ok(page)
+    }
+
+    /// Returns a copy of this page with the form XObject `form` drawn on top of the content,
+    /// like a "DRAFT" watermark.
+    ///
+    /// The content streams are kept as they are, the stamp is added as a new one.
+    /// `form` is added to a copy of the page resources.
+    pub fn stamp_overlay(&self, form: &RcRef<XObject>, placement: StampPlacement, update: &mut impl Updater) -> Result<Page> {
+        self.stamp(form, placement, true, update)
+    }
+
+    /// Like [`Page::stamp_overlay`], but draws `form` below the content, like a letterhead.
+    pub fn stamp_underlay(&self, form: &RcRef<XObject>, placement: StampPlacement, update: &mut impl Updater) -> Result<Page> {
+        self.stamp(form, placement, false, update)
+    }
+
+    fn stamp(&self, form: &RcRef<XObject>, placement: StampPlacement, over: bool, update: &mut impl Updater) -> Result<Page> {
+        let dict = match **form {
+            XObject::Form(ref form) => form.dict(),
+            _ => bail!("a stamp has to be a form XObject"),
+        };
+        let bbox = match dict.matrix {
+            Some(ref m) => dict.bbox.transform(m),
+            None => dict.bbox,
+        };
+        let matrix = stamp_matrix(placement, bbox, self.crop_box()?, self.rotate);
+        self.draw_xobject(form.get_ref(), "Stamp", matrix, over, update)
+    }
+
+    /// Returns a copy of this page with `image` drawn on top of the content, stretched to fill
+    /// `rect` (in default user space).
+    ///
+    /// Create the image with [`ImageXObject::from_jpeg`] or [`ImageXObject::from_png`].
+    /// It is added to a copy of the page resources.
+    pub fn place_image(&self, image: &RcRef<XObject>, rect: Rectangle, update: &mut impl Updater) -> Result<Page> {
+        if !matches!(**image, XObject::Image(_)) {
+            bail!("not an image XObject");
+        }
+        // an image fills the unit square
+        let rect = rect.normalize();
+        let matrix = Matrix {
+            a: rect.right - rect.left,
+            b: 0.,
+            c: 0.,
+            d: rect.top - rect.bottom,
+            e: rect.left,
+            f: rect.bottom,
+        };
+        self.draw_xobject(image.get_ref(), "Im", matrix, true, update)
+    }
+
+    /// Draw `xobject` with `matrix`, above or below the content. New resource names start with `prefix`.
+    fn draw_xobject(&self, xobject: Ref<XObject>, prefix: &str, matrix: Matrix, over: bool, update: &mut impl Updater) -> Result<Page> {
+        let mut resources = (**self.resources()?).clone();
+        let name = match resources.xobjects.iter().find(|&(_, &r)| r == xobject) {
+            Some((name, _)) => name.clone(),
+            None => {
+                let name = (0..).map(|i| Name::from(format!("{}{}", prefix, i)))
+                    .find(|name| !resources.xobjects.contains_key(name))
+                    .unwrap();
+                resources.xobjects.insert(name.clone(), xobject);
+                name
+            }
+        };
+        let draw = t!(serialize_ops(&[
+            Op::Save,
+            Op::Transform { matrix },
+            Op::XObject { name },
+            Op::Restore,
+        ]));
+
+        let mut page = self.clone();
+        page.resources = Some(MaybeRef::Indirect(update.create(resources)?));
+        let mut parts = self.contents.as_ref().map(|c| c.parts.clone()).unwrap_or_default();
+        if over {
+            // whatever state the content leaves behind must not affect the XObject
+            if parts.is_empty() {
+                parts.push(Stream::new((), draw));
+            } else {
+                parts.insert(0, Stream::new((), b"q\n".to_vec()));
+                parts.push(Stream::new((), [&b"Q\n"[..], &draw].concat()));
+            }
+        } else {
+            parts.insert(0, Stream::new((), draw));
+        }
+        page.contents = Some(Content { parts });
+        Ok(page)
+    }
+
+    /// Apply `matrix` to the geometry of all annotations of this page, including field widgets.
+    ///
+    /// This is the pass every operation that moves the page content has to run so the
+    /// annotations stay in place relative to it. Indirect annotations are updated in place.
+    pub fn transform_annotations(&self, matrix: &Matrix, resolve: &impl Resolve, update: &mut impl Updater) -> Result<Lazy<Vec<MaybeRef<Annot>>>> {
+        let annots = t!(self.annotations.load(resolve));
+        let mut new_annots = Vec::with_capacity(annots.len());
+        for annot in annots.iter() {
+            let mut new = (**annot).clone();
+            new.transform(matrix);
+            new_annots.push(match annot.as_ref() {
+                Some(r) => MaybeRef::Indirect(update.update(r.get_inner(), new)?),
+                None => MaybeRef::Direct(Shared::new(new)),
+            });
+        }
+        Lazy::safe(new_annots, update)
+    }
+
+    /// The annotations of this page, in the order of `/Annots`.
+    pub fn annotations(&self, resolve: &impl Resolve) -> Result<Vec<MaybeRef<Annot>>> {
+        Ok(t!(self.annotations.load(resolve)).to_vec())
+    }
+
+    /// Add `annot` as a new object at the end of `/Annots`, which is created if the page has none.
+    ///
+    /// If `/Annots` is an indirect object it is updated in place, otherwise the page gets a new array
+    /// and has to be written with [`PageRc::update`] afterwards. `annot.page` is left as it is.
+    pub fn add_annotation(&mut self, annot: Annot, update: &mut (impl Resolve + Updater)) -> Result<RcRef<Annot>> {
+        let mut annots = t!(self.annotations(update));
+        let annot = update.create(annot)?;
+        annots.push(MaybeRef::Indirect(annot.clone()));
+        t!(self.set_annotations(annots, update));
+        Ok(annot)
+    }
+
+    /// Remove the annotation at `index` from `/Annots` and return it, like [`Page::add_annotation`].
+    ///
+    /// The annotation object itself is not deleted, but nothing refers to it from this page anymore.
+    pub fn remove_annotation(&mut self, index: usize, update: &mut (impl Resolve + Updater)) -> Result<MaybeRef<Annot>> {
+        let mut annots = t!(self.annotations(update));
+        if index >= annots.len() {
+            return Err(PdfError::Bounds { index, len: annots.len() });
+        }
+        let annot = annots.remove(index);
+        t!(self.set_annotations(annots, update));
+        Ok(annot)
+    }
+
+    pub(crate) fn set_annotations(&mut self, annots: Vec<MaybeRef<Annot>>, update: &mut impl Updater) -> Result<()> {
+        self.annotations = match self.annotations.as_ref() {
+            Some(r) => update.update(r.get_inner(), annots)?.into(),
+            None => Lazy::safe(annots, update)?,
+        };
+        Ok(())
+    }
+}
+
+/// Where a stamp is drawn, see [`Page::stamp_overlay`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StampPlacement {
+    /// Maps the form space to the default user space of the page.
+    Matrix(Matrix),
+    /// At its own size, centered on the crop box and upright as the page is displayed.
+    Center,
+    /// Scaled to fit the crop box less `margin` on every side, centered and upright.
+    Fit { margin: f32 },
+}
+
+/// The matrix placing a form with bounding box `bbox` (in the page's user space units) on a page.
+pub(crate) fn stamp_matrix(placement: StampPlacement, bbox: Rectangle, crop: Rectangle, rotate: i32) -> Matrix {
+    let margin = match placement {
+        StampPlacement::Matrix(m) => return m,
+        StampPlacement::Center => None,
+        StampPlacement::Fit { margin } => Some(margin),
+    };
+    let (bbox, crop) = (bbox.normalize(), crop.normalize());
+    let rotate = rotate.rem_euclid(360) / 90 * 90;
+    let scale = match margin {
+        Some(margin) => {
+            let (mut width, mut height) = (crop.right - crop.left - 2. * margin, crop.top - crop.bottom - 2. * margin);
+            if rotate % 180 != 0 {
+                std::mem::swap(&mut width, &mut height);
+            }
+            (width / (bbox.right - bbox.left)).min(height / (bbox.top - bbox.bottom)).max(0.)
+        }
+        None => 1.,
+    };
+    // counter the clockwise page rotation
+    let (sin, cos) = match rotate {
+        90 => (1., 0.),
+        180 => (0., -1.),
+        270 => (-1., 0.),
+        _ => (0., 1.),
+    };
+    let (cx, cy) = ((bbox.left + bbox.right) / 2., (bbox.top + bbox.bottom) / 2.);
+    let (px, py) = ((crop.left + crop.right) / 2., (crop.top + crop.bottom) / 2.);
+    let (a, b, c, d) = (scale * cos, scale * sin, -scale * sin, scale * cos);
+    Matrix { a, b, c, d, e: px - (a * cx + c * cy), f: py - (b * cx + d * cy) }
+}
+
+
+
+/// Transform an array of `x y` pairs. Nested arrays (like `InkList`) are transformed each.
+fn transform_coords(m: &Matrix, p: &mut Primitive) {
+    let arr = match *p {
+        Primitive::Array(ref mut arr) => arr,
+        _ => return,
+    };
+    if arr.iter().all(|p| matches!(p, Primitive::Array(_))) {
+        for p in arr.iter_mut() {
+            transform_coords(m, p);
+        }
+        return;
+    }
+    for pair in arr.chunks_exact_mut(2) {
+        if let (Ok(x), Ok(y)) = (pair[0].as_number(), pair[1].as_number()) {
+            let p = m.transform_point(Point { x, y });
+            pair[0] = Primitive::Number(p.x);
+            pair[1] = Primitive::Number(p.y);
+        }
+    }
+}
+
+impl Annot {
+    /// Apply `matrix` to `Rect`, `QuadPoints`, `InkList`, `L`, `Vertices` and `CL`.
+    ///
+    /// `Rect` becomes the bounding box of the transformed rectangle.
+    pub fn transform(&mut self, matrix: &Matrix) {
+        self.rect = self.rect.map(|r| r.transform(matrix));
+        if let Some(ref mut ink_list) = self.ink_list {
+            transform_coords(matrix, ink_list);
+        }
+        if let Some(ref mut line) = self.line {
+            transform_coords(matrix, line);
+        }
+        for key in ["QuadPoints", "Vertices", "CL"] {
+            if let Some(p) = self.other.get_mut(key) {
+                transform_coords(matrix, p);
+            }
+        }
+    }
+}
+
+#[derive(Object, DataSize, Debug, ObjectWrite, Clone, Default)]
 pub struct PageLabel {
     #[pdf(key = "S")]
     pub style: Option<Counter>,
@@ -208,3 +628,150 @@ pub struct PageLabel {
     #[pdf(key = "St")]
     pub start: Option<usize>,
 }
+impl PageLabel {
+    pub fn new(style: Option<Counter>, prefix: Option<&str>, start: Option<usize>) -> PageLabel {
+        PageLabel {
+            style,
+            prefix: prefix.map(PdfString::from),
+            start,
+        }
+    }
+    fn prefix(&self) -> String {
+        self.prefix.as_ref().map(|p| p.to_string_lossy()).unwrap_or_default()
+    }
+    /// The label of the page `offset` pages after the first page of this range.
+    pub fn format(&self, offset: usize) -> String {
+        let mut label = self.prefix();
+        let n = self.start.unwrap_or(1) + offset;
+        match self.style {
+            None => {}
+            Some(Counter::Arabic) => label.push_str(&n.to_string()),
+            Some(Counter::RomanUpper) => label.push_str(&roman(n)),
+            Some(Counter::RomanLower) => label.push_str(&roman(n).to_lowercase()),
+            Some(Counter::AlphaUpper) => label.push_str(&alpha(n)),
+            Some(Counter::AlphaLower) => label.push_str(&alpha(n).to_lowercase()),
+        }
+        label
+    }
+    /// The inverse of `format`: the offset into this range of the page labelled `label`.
+    pub fn parse(&self, label: &str) -> Option<usize> {
+        let number = label.strip_prefix(self.prefix().as_str())?;
+        let start = self.start.unwrap_or(1);
+        let n = match self.style {
+            None if number.is_empty() => return Some(0),
+            None => return None,
+            Some(Counter::Arabic) => number.parse().ok()?,
+            Some(Counter::RomanUpper) | Some(Counter::RomanLower) => (1..4000).find(|&n| {
+                let r = roman(n);
+                if matches!(self.style, Some(Counter::RomanLower)) { r.to_lowercase() == number } else { r == number }
+            })?,
+            Some(Counter::AlphaUpper) | Some(Counter::AlphaLower) => parse_alpha(number, matches!(self.style, Some(Counter::AlphaLower)))?,
+        };
+        n.checked_sub(start)
+    }
+}
+
+fn roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut s = String::new();
+    for &(value, numeral) in NUMERALS.iter() {
+        while n >= value {
+            s.push_str(numeral);
+            n -= value;
+        }
+    }
+    s
+}
+
+/// A to Z, then AA to ZZ, AAA to ZZZ and so on.
+fn alpha(n: usize) -> String {
+    if n == 0 {
+        return String::new();
+    }
+    let letter = (b'A' + ((n - 1) % 26) as u8) as char;
+    std::iter::repeat_n(letter, (n - 1) / 26 + 1).collect()
+}
+
+fn parse_alpha(s: &str, lower: bool) -> Option<usize> {
+    let first = s.chars().next()?;
+    let base = if lower { 'a' } else { 'A' };
+    if !(base..=(base as u8 + 25) as char).contains(&first) || s.chars().any(|c| c != first) {
+        return None;
+    }
+    Some((s.len() - 1) * 26 + (first as usize - base as usize) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_label_format() {
+        let label = PageLabel::new(Some(Counter::RomanLower), Some("p. "), None);
+        assert_eq!(label.format(3), "p. iv");
+        assert_eq!(label.parse("p. iv"), Some(3));
+        assert_eq!(label.parse("iv"), None);
+
+        let label = PageLabel::new(Some(Counter::AlphaUpper), None, Some(2));
+        assert_eq!(label.format(0), "B");
+        assert_eq!(label.format(26), "BB");
+        assert_eq!(label.parse("BB"), Some(26));
+        assert_eq!(label.parse("BC"), None);
+
+        let label = PageLabel::new(Some(Counter::Arabic), None, Some(10));
+        assert_eq!(label.format(5), "15");
+        assert_eq!(label.parse("9"), None);
+    }
+
+    #[test]
+    fn transform_annot() {
+        let mut other = Dictionary::new();
+        other.insert("QuadPoints", Primitive::array::<f32, _, _, _>([0., 10., 20., 10., 0., 0., 20., 0.].iter(), &mut NoUpdate).unwrap());
+        let mut annot = Annot {
+            subtype: "Highlight".into(),
+            rect: Some(Rectangle { left: 0., bottom: 0., right: 20., top: 10. }),
+            contents: None,
+            page: None,
+            annotation_name: None,
+            date: None,
+            annot_flags: 0,
+            appearance_streams: None,
+            appearance_state: None,
+            border: None,
+            border_style: None,
+            border_effect: None,
+            color: None,
+            ink_list: Some(Primitive::Array(vec![Primitive::Array(vec![Primitive::Integer(1), Primitive::Integer(2)])])),
+            line: None,
+            popup: None,
+            in_reply_to: None,
+            reply_type: None,
+            other,
+        };
+        // rotate by 90° and move right by 100
+        annot.transform(&Matrix { a: 0., b: 1., c: -1., d: 0., e: 100., f: 0. });
+        let rect = annot.rect.unwrap();
+        assert_eq!([rect.left, rect.bottom, rect.right, rect.top], [90., 0., 100., 20.]);
+        let quads: Vec<f32> = annot.other["QuadPoints"].as_array().unwrap().iter().map(|p| p.as_number().unwrap()).collect();
+        assert_eq!(quads, [90., 0., 90., 20., 100., 0., 100., 20.]);
+        let ink = annot.ink_list.unwrap().into_array().unwrap()[0].as_array().unwrap().iter().map(|p| p.as_number().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ink, [98., 1.]);
+    }
+
+    #[test]
+    fn stamp_placement() {
+        let bbox = Rectangle { left: 0., bottom: 0., right: 100., top: 50. };
+        let crop = Rectangle { left: 0., bottom: 0., right: 300., top: 400. };
+        let m = stamp_matrix(StampPlacement::Fit { margin: 50. }, bbox, crop, 0);
+        let r = bbox.transform(&m);
+        assert_eq!((r.left, r.bottom, r.right, r.top), (50., 150., 250., 250.));
+
+        // on a page displayed in landscape, the stamp is turned to read from bottom to top
+        let m = stamp_matrix(StampPlacement::Fit { margin: 0. }, bbox, crop, 90);
+        assert_eq!(m.transform_point(Point { x: 0., y: 0. }), Point { x: 250., y: 0. });
+        assert_eq!(m.transform_point(Point { x: 100., y: 0. }), Point { x: 250., y: 400. });
+    }
+}
@@ -52,20 +52,21 @@ pub struct PageTree {
     pub crop_box: Option<Rectangle>,
 }
 impl PageTree {
+    /// Find page `page_nr` (counting from 0) below this node.
+    ///
+    /// Fails with `ReferenceCycle` if a node is its own descendant.
     pub fn page(&self, resolve: &impl Resolve, page_nr: u32) -> Result<PageRc> {
-        self.page_limited(resolve, page_nr, 16)
+        self.page_in(resolve, page_nr, &mut vec![])
     }
-    fn page_limited(&self, resolve: &impl Resolve, page_nr: u32, depth: usize) -> Result<PageRc> {
-        if depth == 0 {
-            bail!("page tree depth exeeded");
-        }
+    fn page_in(&self, resolve: &impl Resolve, page_nr: u32, path: &mut Vec<PlainRef>) -> Result<PageRc> {
         let mut pos = 0;
         for &kid in &self.kids {
             let node = resolve.get(kid)?;
             match *node {
                 PagesNode::Tree(ref tree) => {
                     if (pos..pos + tree.count).contains(&page_nr) {
-                        return tree.page_limited(resolve, page_nr - pos, depth - 1);
+                        enter_node(path, kid.get_inner(), resolve)?;
+                        return tree.page_in(resolve, page_nr - pos, path);
                     }
                     pos += tree.count;
                 }
@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+/// Entries of a tiling pattern (`PatternType` 1).
 #[derive(Debug, Object, ObjectWrite, DataSize, Clone, DeepClone)]
 pub struct PatternDict {
     #[pdf(key = "PaintType")]
@@ -24,34 +25,88 @@ pub struct PatternDict {
     pub matrix: Option<Matrix>,
 }
 
+/// Entries of a shading pattern (`PatternType` 2).
+#[derive(Debug, Object, ObjectWrite, DataSize, Clone, DeepClone)]
+#[pdf(Type = "Pattern?")]
+pub struct ShadingPattern {
+    #[pdf(key = "Shading")]
+    pub shading: MaybeRef<Shading>,
+
+    #[pdf(key = "Matrix")]
+    pub matrix: Option<Matrix>,
+
+    #[pdf(key = "ExtGState")]
+    pub graphics_state: Option<MaybeRef<GraphicsStateParameters>>,
+}
+
 #[derive(Debug, DataSize)]
 pub enum Pattern {
-    Dict(PatternDict),
-    Stream(PatternDict, Vec<Op>),
+    /// a tiling pattern with its content
+    Tiling(PatternDict, Vec<Op>),
+    Shading(ShadingPattern),
 }
 impl Pattern {
-    pub fn dict(&self) -> &PatternDict {
+    /// The dictionary of a tiling pattern.
+    pub fn dict(&self) -> Option<&PatternDict> {
+        match *self {
+            Pattern::Tiling(ref d, _) => Some(d),
+            Pattern::Shading(_) => None,
+        }
+    }
+    /// The content of a tiling pattern.
+    pub fn ops(&self) -> Option<&[Op]> {
+        match *self {
+            Pattern::Tiling(_, ref ops) => Some(ops),
+            Pattern::Shading(_) => None,
+        }
+    }
+    /// The resources used by the content of a tiling pattern.
+    pub fn resources(&self, resolve: &impl Resolve) -> Result<Option<RcRef<Resources>>> {
         match *self {
-            Pattern::Dict(ref d) => d,
-            Pattern::Stream(ref d, _) => d,
+            Pattern::Tiling(ref d, _) => resolve.get(d.resources).map(Some),
+            Pattern::Shading(_) => Ok(None),
         }
     }
+    /// The shading of a shading pattern.
+    pub fn shading(&self) -> Option<&Shading> {
+        match *self {
+            Pattern::Tiling(..) => None,
+            Pattern::Shading(ref p) => Some(&p.shading),
+        }
+    }
+    /// Maps the pattern space to the default coordinate space of the page (or form) using the pattern.
+    pub fn matrix(&self) -> Matrix {
+        let matrix = match *self {
+            Pattern::Tiling(ref d, _) => d.matrix,
+            Pattern::Shading(ref p) => p.matrix,
+        };
+        matrix.unwrap_or_default()
+    }
 }
 impl Object for Pattern {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let p = p.resolve(resolve)?;
-        match p {
-            Primitive::Dictionary(dict) => {
-                Ok(Pattern::Dict(PatternDict::from_dict(dict, resolve)?))
+        let pattern_type = match p {
+            Primitive::Dictionary(ref dict) => dict.get("PatternType"),
+            Primitive::Stream(ref s) => s.info.get("PatternType"),
+            _ => None,
+        }.and_then(|t| t.as_integer().ok());
+        match (pattern_type, p) {
+            (Some(2), Primitive::Dictionary(dict)) => {
+                Ok(Pattern::Shading(t!(ShadingPattern::from_dict(dict, resolve))))
+            }
+            // tiling patterns are streams, but tolerate one without content
+            (_, Primitive::Dictionary(dict)) => {
+                Ok(Pattern::Tiling(PatternDict::from_dict(dict, resolve)?, vec![]))
             }
-            Primitive::Stream(s) => {
+            (_, Primitive::Stream(s)) => {
                 let stream: Stream<PatternDict> = Stream::from_stream(s, resolve)?;
                 let data = stream.data(resolve)?;
                 let ops = t!(parse_ops(&data, resolve));
                 let dict = stream.info.info;
-                Ok(Pattern::Stream(dict, ops))
+                Ok(Pattern::Tiling(dict, ops))
             }
-            p => Err(PdfError::UnexpectedPrimitive {
+            (_, p) => Err(PdfError::UnexpectedPrimitive {
                 expected: "Dictionary or Stream",
                 found: p.get_debug_name(),
             }),
@@ -61,20 +116,27 @@ impl Object for Pattern {
 impl ObjectWrite for Pattern {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
         match self {
-            Pattern::Dict(ref d) => d.to_primitive(update),
-            Pattern::Stream(ref d, ref ops) => {
+            Pattern::Tiling(ref d, ref ops) => {
+                let mut dict = d.to_dict(update)?;
+                dict.insert("Type", Name::from("Pattern"));
+                dict.insert("PatternType", 1);
                 let data = serialize_ops(ops)?;
-                let stream = Stream::new_with_filters(d.clone(), data, vec![]);
+                let stream = Stream::new_with_filters(dict, data, vec![]);
                 stream.to_primitive(update)
             }
+            Pattern::Shading(ref p) => {
+                let mut dict = p.to_dict(update)?;
+                dict.insert("PatternType", 2);
+                Ok(Primitive::Dictionary(dict))
+            }
         }
     }
 }
 impl DeepClone for Pattern {
     fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
         match *self {
-            Pattern::Dict(ref d) => Ok(Pattern::Dict(d.deep_clone(cloner)?)),
-            Pattern::Stream(ref dict, ref ops) => {
+            Pattern::Shading(ref p) => Ok(Pattern::Shading(p.deep_clone(cloner)?)),
+            Pattern::Tiling(ref dict, ref ops) => {
                 let old_resources = cloner.get(dict.resources)?;
                 let mut resources = Resources::default();
                 let ops: Vec<Op> = ops
@@ -85,8 +147,32 @@ impl DeepClone for Pattern {
                     resources: cloner.create(resources)?.get_ref(),
                     ..*dict
                 };
-                Ok(Pattern::Stream(dict, ops))
+                Ok(Pattern::Tiling(dict, ops))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn shading_pattern() {
+        let p = parse(b"<< /PatternType 2 /Shading << /ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 0 100 0] /Function << /FunctionType 2 /Domain [0 1] /C0 [1 0 0] /C1 [0 0 1] /N 1 >> >> >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let pattern = Pattern::from_primitive(p, &NoResolve).unwrap();
+        assert!(pattern.dict().is_none());
+        let written = pattern.to_primitive(&mut NoUpdate).unwrap();
+        let pattern = Pattern::from_primitive(written, &NoResolve).unwrap();
+        match pattern.shading().unwrap().kind {
+            ShadingKind::Axial(ref axial) => {
+                assert_eq!(axial.coords, [0., 0., 100., 0.]);
+                let mut rgb = [0.; 3];
+                axial.function.apply(&[0.5], &mut rgb).unwrap();
+                assert_eq!(rgb, [0.5, 0., 0.5]);
             }
+            ref kind => panic!("{:?}", kind),
         }
     }
 }
@@ -0,0 +1,248 @@
+use super::prelude::*;
+
+/// The catalog's `Perms` entry (PDF32000-2 12.8.6): signatures that restrict what can be changed in
+/// the document (`DocMDP`) or that grant additional rights to viewers that check them (`UR3`).
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct Permissions {
+    /// the certification signature, with a `DocMDP` transform
+    #[pdf(key = "DocMDP")]
+    pub doc_mdp: Option<MaybeRef<SignatureDictionary>>,
+
+    /// the usage rights signature, with a `UR3` transform
+    #[pdf(key = "UR3")]
+    pub ur3: Option<MaybeRef<SignatureDictionary>>,
+}
+
+/// What a signature reference covers, with the parameters of its transform method.
+#[derive(Debug, Clone)]
+pub enum Transform {
+    /// `DocMDP`: the whole document, with the changes allowed after certification
+    DocMdp(DocMdpParams),
+    /// `UR` or `UR3`: the rights granted to the viewer
+    UsageRights(UsageRightsParams),
+    /// `FieldMDP`: the values of some form fields
+    FieldMdp(FieldMdpParams),
+    /// `Identity` or an unknown method
+    Other(Name),
+}
+
+/// The parameters of a `DocMDP` transform.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "TransformParams?")]
+pub struct DocMdpParams {
+    /// the changes allowed, see [`DocMdpParams::permission`]
+    #[pdf(key = "P", default = "2")]
+    pub p: i32,
+
+    #[pdf(key = "V")]
+    pub version: Option<Name>,
+}
+
+impl DocMdpParams {
+    pub fn permission(&self) -> MdpPermission {
+        match self.p {
+            1 => MdpPermission::NoChanges,
+            3 => MdpPermission::Annotate,
+            // 2 is the default, and what other values are treated as
+            _ => MdpPermission::FillForms,
+        }
+    }
+}
+
+/// The changes that a certification signature allows, from the most to the least restrictive.
+///
+/// Adding signatures and instantiating page templates is allowed unless it is `NoChanges`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MdpPermission {
+    /// `P` 1: no change at all invalidates the signature
+    NoChanges,
+    /// `P` 2: filling in forms
+    FillForms,
+    /// `P` 3: filling in forms, and creating, deleting and changing annotations
+    Annotate,
+}
+
+impl MdpPermission {
+    pub fn allows_form_filling(self) -> bool {
+        self >= MdpPermission::FillForms
+    }
+
+    pub fn allows_annotations(self) -> bool {
+        self >= MdpPermission::Annotate
+    }
+}
+
+/// The parameters of a `UR3` transform: the rights a viewer grants when the signature is valid,
+/// in addition to what it allows anyway.
+#[derive(Object, ObjectWrite, Debug, Clone, Default, DataSize)]
+#[pdf(Type = "TransformParams?")]
+pub struct UsageRightsParams {
+    /// rights on the whole document: `FullSave`
+    #[pdf(key = "Document")]
+    pub document: Vec<Name>,
+
+    /// shown to the user when the rights are granted
+    #[pdf(key = "Msg")]
+    pub message: Option<PdfString>,
+
+    #[pdf(key = "V")]
+    pub version: Option<Name>,
+
+    /// rights on annotations, like `Create`, `Delete`, `Modify`, `Import` and `Export`
+    #[pdf(key = "Annots")]
+    pub annots: Vec<Name>,
+
+    /// rights on forms, like `FillIn`, `Import`, `Export`, `SubmitStandalone` and `SpawnTemplate`
+    #[pdf(key = "Form")]
+    pub form: Vec<Name>,
+
+    /// rights on signatures: `Modify`
+    #[pdf(key = "Signature")]
+    pub signature: Vec<Name>,
+
+    /// rights on embedded files: `Create`, `Delete`, `Modify` and `Import`
+    #[pdf(key = "EF")]
+    pub embedded_files: Vec<Name>,
+
+    /// whether the rights are restricted to those granted even if other signatures allow more
+    #[pdf(key = "P", default = "false")]
+    pub restrict_others: bool,
+}
+
+impl UsageRightsParams {
+    /// Whether `right` is granted on `category`, which is one of the keys like `Annots` or `Form`.
+    pub fn grants(&self, category: &str, right: &str) -> bool {
+        let rights = match category {
+            "Document" => &self.document,
+            "Annots" => &self.annots,
+            "Form" => &self.form,
+            "Signature" => &self.signature,
+            "EF" => &self.embedded_files,
+            _ => return false,
+        };
+        rights.iter().any(|r| r == right)
+    }
+}
+
+/// The parameters of a `FieldMDP` transform.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "TransformParams?")]
+pub struct FieldMdpParams {
+    /// `All`, `Include` or `Exclude`: which fields are locked, along with `fields`
+    #[pdf(key = "Action")]
+    pub action: Name,
+
+    /// the fully qualified names of the fields
+    #[pdf(key = "Fields")]
+    pub fields: Vec<PdfString>,
+
+    #[pdf(key = "V")]
+    pub version: Option<Name>,
+}
+
+impl FieldMdpParams {
+    /// Whether the field named `name` is locked by the signature.
+    pub fn locks(&self, name: &str) -> bool {
+        let listed = self.fields.iter().any(|f| f.to_string_lossy() == name);
+        match self.action.as_str() {
+            "Include" => listed,
+            "Exclude" => !listed,
+            _ => true,
+        }
+    }
+}
+
+impl SignatureReferenceDictionary {
+    pub fn transform(&self, resolve: &impl Resolve) -> Result<Transform> {
+        let params = || self.transform_params.clone().unwrap_or_default();
+        Ok(match self.transform_method.as_str() {
+            "DocMDP" => Transform::DocMdp(t!(DocMdpParams::from_dict(params(), resolve))),
+            "UR" | "UR3" => Transform::UsageRights(t!(UsageRightsParams::from_dict(params(), resolve))),
+            "FieldMDP" => Transform::FieldMdp(t!(FieldMdpParams::from_dict(params(), resolve))),
+            _ => Transform::Other(self.transform_method.clone()),
+        })
+    }
+}
+
+impl SignatureDictionary {
+    /// The signature references (`Reference`), which say what the signature covers.
+    pub fn references(&self, resolve: &impl Resolve) -> Result<Vec<SignatureReferenceDictionary>> {
+        match self.reference {
+            Some(ref r) => Vec::from_primitive(r.clone(), resolve),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The transforms of the signature references.
+    pub fn transforms(&self, resolve: &impl Resolve) -> Result<Vec<Transform>> {
+        self.references(resolve)?.iter().map(|r| r.transform(resolve)).collect()
+    }
+}
+
+impl Catalog {
+    /// The changes allowed by the certification signature, if the document is certified.
+    pub fn certification(&self, resolve: &impl Resolve) -> Result<Option<MdpPermission>> {
+        let sig = match self.perms.as_ref().and_then(|p| p.doc_mdp.as_ref()) {
+            Some(sig) => sig,
+            None => return Ok(None),
+        };
+        for transform in t!(sig.transforms(resolve)) {
+            if let Transform::DocMdp(params) = transform {
+                return Ok(Some(params.permission()));
+            }
+        }
+        // a certification signature without parameters allows what `P` 2 does
+        Ok(Some(MdpPermission::FillForms))
+    }
+
+    /// The rights granted by the usage rights signature, if there is one.
+    pub fn usage_rights(&self, resolve: &impl Resolve) -> Result<Option<UsageRightsParams>> {
+        let sig = match self.perms.as_ref().and_then(|p| p.ur3.as_ref()) {
+            Some(sig) => sig,
+            None => return Ok(None),
+        };
+        for transform in t!(sig.transforms(resolve)) {
+            if let Transform::UsageRights(params) = transform {
+                return Ok(Some(params));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn perms() {
+        let p = parse(b"<< /DocMDP << /Type /Sig /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached
+            /ByteRange [0 10 20 10] /Contents <00> /Reference [<< /Type /SigRef /TransformMethod /DocMDP
+            /TransformParams << /Type /TransformParams /P 3 /V /1.2 >> >>] >>
+            /UR3 << /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached /ByteRange [0 10 20 10] /Contents <00>
+            /Reference [<< /TransformMethod /UR3 /TransformParams << /Annots [/Create /Modify] /Form [/FillIn] /P true >> >>] >> >>",
+            &NoResolve, ParseFlags::ANY).unwrap();
+        let perms = Permissions::from_primitive(p, &NoResolve).unwrap();
+        let transforms = perms.doc_mdp.as_ref().unwrap().transforms(&NoResolve).unwrap();
+        let permission = match transforms[0] {
+            Transform::DocMdp(ref params) => params.permission(),
+            ref t => panic!("{:?}", t),
+        };
+        assert_eq!(permission, MdpPermission::Annotate);
+        assert!(permission.allows_form_filling() && permission.allows_annotations());
+        assert!(!MdpPermission::NoChanges.allows_form_filling());
+
+        match perms.ur3.as_ref().unwrap().transforms(&NoResolve).unwrap()[0] {
+            Transform::UsageRights(ref rights) => {
+                assert!(rights.grants("Annots", "Modify") && rights.grants("Form", "FillIn"));
+                assert!(!rights.grants("EF", "Create"));
+                assert!(rights.restrict_others);
+            }
+            ref t => panic!("{:?}", t),
+        }
+
+        let fields = FieldMdpParams { action: "Exclude".into(), fields: vec!["comments".into()], version: None };
+        assert!(fields.locks("name") && !fields.locks("comments"));
+    }
+}
@@ -0,0 +1,109 @@
+use super::prelude::*;
+
+/// A page-piece dictionary (PDF32000-2 14.5): private data of the applications that edited the
+/// document, a page or a form, by the name of the application.
+///
+/// Other applications keep the data as it is; the application that wrote it can tell from
+/// `last_modified` whether the object was changed since.
+#[derive(Debug, Clone, Default, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PieceInfo {
+    pub entries: Vec<(Name, PieceData)>,
+}
+impl Object for PieceInfo {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let dict = t!(p.resolve(resolve)?.into_dictionary());
+        let mut entries = vec![];
+        for (app, data) in dict {
+            entries.push((app, t!(PieceData::from_primitive(data, resolve))));
+        }
+        Ok(PieceInfo { entries })
+    }
+}
+impl ObjectWrite for PieceInfo {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        for (app, data) in &self.entries {
+            dict.insert(app.clone(), data.to_primitive(update)?);
+        }
+        Ok(dict.into())
+    }
+}
+impl DeepClone for PieceInfo {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        let entries = self.entries.iter()
+            .map(|(app, data)| Ok((app.clone(), data.deep_clone(cloner)?)))
+            .collect::<Result<_>>()?;
+        Ok(PieceInfo { entries })
+    }
+}
+impl PieceInfo {
+    pub fn get(&self, app: &str) -> Option<&PieceData> {
+        self.entries.iter().find(|(a, _)| a == app).map(|(_, data)| data)
+    }
+
+    pub fn get_mut(&mut self, app: &str) -> Option<&mut PieceData> {
+        self.entries.iter_mut().find(|(a, _)| a == app).map(|(_, data)| data)
+    }
+
+    /// Set the data of `app`, replacing what it stored before.
+    pub fn insert(&mut self, app: &str, data: PieceData) {
+        match self.get_mut(app) {
+            Some(old) => *old = data,
+            None => self.entries.push((app.into(), data)),
+        }
+    }
+
+    pub fn remove(&mut self, app: &str) -> Option<PieceData> {
+        let i = self.entries.iter().position(|(a, _)| a == app)?;
+        Some(self.entries.remove(i).1)
+    }
+}
+
+/// The data of one application in a [`PieceInfo`].
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct PieceData {
+    /// when the application last changed `private`; compared with the `LastModified` of the
+    /// document, page or form to tell if someone else changed it since
+    #[pdf(key = "LastModified")]
+    pub last_modified: Option<Date>,
+
+    /// anything the application wants
+    #[pdf(key = "Private")]
+    pub private: Option<Primitive>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+
+impl PieceData {
+    pub fn new(private: Primitive, last_modified: Date) -> PieceData {
+        PieceData { last_modified: Some(last_modified), private: Some(private), other: Dictionary::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn piece_info() {
+        let p = parse(b"<< /Illustrator << /LastModified (D:20240101120000Z) /Private << /Layers 3 >> >>
+            /Other << /Private (data) >> >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let mut info = PieceInfo::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(info.get("Illustrator").unwrap().last_modified.as_ref().unwrap().year, 2024);
+        assert!(info.get("Other").unwrap().last_modified.is_none());
+
+        let date = Date::parse("D:20250601000000Z").unwrap();
+        info.insert("Other", PieceData::new(Primitive::Integer(1), date.clone()));
+        info.insert("New", PieceData::new(Primitive::Null, date));
+        assert!(info.remove("Illustrator").is_some());
+        let written = info.to_primitive(&mut NoUpdate).unwrap();
+        let info = PieceInfo::from_primitive(written, &NoResolve).unwrap();
+        let apps: Vec<_> = info.entries.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(apps, ["Other", "New"]);
+        assert_eq!(info.get("Other").unwrap().private, Some(Primitive::Integer(1)));
+        assert_eq!(info.get("Other").unwrap().last_modified.as_ref().unwrap().month, 6);
+    }
+}
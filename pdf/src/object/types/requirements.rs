@@ -0,0 +1,153 @@
+use super::prelude::*;
+
+/// A feature a viewer needs to process the document correctly (PDF32000-2 12.11),
+/// an entry of the catalog's `Requirements`.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "Requirement?")]
+pub struct Requirement {
+    /// the feature, like `EnableJavaScripts`, `DigSig`, `DigSigMDP`, `OCInteract` or `Collection`
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    /// the PDF version the feature is defined in (PDF 2.0)
+    #[pdf(key = "V")]
+    pub version: Option<Name>,
+
+    /// scripts that check whether the requirement is met, tried in order
+    #[pdf(key = "RH")]
+    pub handlers: Vec<RequirementHandler>,
+
+    /// how badly the document is processed without the feature, from 0 to 100 (PDF 2.0)
+    #[pdf(key = "Penalty", default = "100")]
+    pub penalty: i32,
+}
+
+impl Requirement {
+    /// Whether this is one of the requirements about digital signatures: creating (`DigSig`),
+    /// validating (`DigSigValidation`) or checking the certification of (`DigSigMDP`) them.
+    pub fn is_signature(&self) -> bool {
+        self.subtype.as_str().starts_with("DigSig")
+    }
+}
+
+/// A way to check a requirement: `JS` runs `script`, `NoOp` considers it met.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "ReqHandler?")]
+pub struct RequirementHandler {
+    #[pdf(key = "S")]
+    pub subtype: Name,
+
+    /// the name of a document-level JavaScript, for `JS`
+    #[pdf(key = "Script")]
+    pub script: Option<PdfString>,
+}
+
+/// The catalog's `Extensions` entry: the developer extensions the document uses, by the prefix
+/// registered for the developer (like `ADBE` or `ISO_`).
+#[derive(Debug, Clone, Default, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Extensions {
+    pub extensions: Vec<(Name, Vec<DeveloperExtension>)>,
+}
+impl Object for Extensions {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let dict = t!(p.resolve(resolve)?.into_dictionary());
+        let mut extensions = vec![];
+        for (prefix, ext) in dict {
+            if prefix.as_str() == "Type" {
+                continue;
+            }
+            // a single dictionary, or an array of them (PDF 2.0)
+            extensions.push((prefix, t!(Vec::from_primitive(ext, resolve))));
+        }
+        Ok(Extensions { extensions })
+    }
+}
+impl ObjectWrite for Extensions {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        dict.insert("Type", Name::from("Extensions"));
+        for (prefix, ext) in &self.extensions {
+            let ext = match ext.as_slice() {
+                [single] => single.to_primitive(update)?,
+                _ => ext.to_primitive(update)?,
+            };
+            dict.insert(prefix.clone(), ext);
+        }
+        Ok(dict.into())
+    }
+}
+impl Extensions {
+    /// The extensions of the developer with `prefix`.
+    pub fn get(&self, prefix: &str) -> &[DeveloperExtension] {
+        self.extensions.iter().find(|(p, _)| p == prefix).map(|(_, ext)| ext.as_slice()).unwrap_or_default()
+    }
+}
+
+/// A developer extension: a set of features added by a developer on top of `base_version`.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "DeveloperExtensions?")]
+pub struct DeveloperExtension {
+    /// the PDF version the extension is based on, like `1.7`
+    #[pdf(key = "BaseVersion")]
+    pub base_version: Name,
+
+    /// increases with each release of the extension for the same base version
+    #[pdf(key = "ExtensionLevel")]
+    pub level: i32,
+
+    /// where the extension is documented (PDF 2.0)
+    #[pdf(key = "URL")]
+    pub url: Option<PdfString>,
+
+    /// a revision of `level` (PDF 2.0)
+    #[pdf(key = "ExtensionRevision")]
+    pub revision: Option<PdfString>,
+}
+
+impl Catalog {
+    /// The requirement for `feature`, if the document has one.
+    pub fn requirement(&self, feature: &str) -> Option<&Requirement> {
+        self.requirements.iter().find(|r| r.subtype == *feature)
+    }
+
+    /// The requirements for features not in `supported`, so a viewer can warn about them,
+    /// the ones with the highest penalty first.
+    pub fn unmet_requirements(&self, supported: &[&str]) -> Vec<&Requirement> {
+        let mut unmet: Vec<_> = self.requirements.iter().filter(|r| !supported.contains(&r.subtype.as_str())).collect();
+        unmet.sort_by_key(|r| std::cmp::Reverse(r.penalty));
+        unmet
+    }
+
+    /// The highest extension level of the developer with `prefix`, if the document uses their extensions.
+    pub fn extension_level(&self, prefix: &str) -> Option<i32> {
+        self.extensions.as_ref()?.get(prefix).iter().map(|e| e.level).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn extensions() {
+        let p = parse(b"<< /Type /Extensions /ADBE << /BaseVersion /1.7 /ExtensionLevel 3 >>
+            /ISO_ [<< /Type /DeveloperExtensions /BaseVersion /2.0 /ExtensionLevel 32001 /URL (https://www.iso.org/) >>
+            << /BaseVersion /2.0 /ExtensionLevel 32002 >>] >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let ext = Extensions::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(ext.get("ADBE")[0].base_version.as_str(), "1.7");
+        assert_eq!(ext.get("ISO_").len(), 2);
+        assert!(ext.get("GTS_").is_empty());
+
+        let written = ext.to_primitive(&mut NoUpdate).unwrap().into_dictionary().unwrap();
+        assert!(matches!(written.get("ADBE"), Some(Primitive::Dictionary(_))));
+        assert!(matches!(written.get("ISO_"), Some(Primitive::Array(a)) if a.len() == 2));
+
+        let p = parse(b"[<< /Type /Requirement /S /DigSigMDP /Penalty 50 >> << /S /EnableJavaScripts /RH << /S /NoOp >> >>]",
+            &NoResolve, ParseFlags::ANY).unwrap();
+        let requirements = Vec::<Requirement>::from_primitive(p, &NoResolve).unwrap();
+        assert!(requirements[0].is_signature());
+        assert_eq!((requirements[1].penalty, requirements[1].handlers[0].subtype.as_str()), (100, "NoOp"));
+    }
+}
@@ -0,0 +1,725 @@
+use super::prelude::*;
+use crate::content::Point;
+
+/// Entries common to all shading dictionaries (PDF32000 8.7.4.5).
+#[derive(Object, ObjectWrite, Debug, Clone)]
+struct RawShading {
+    #[pdf(key = "ShadingType")]
+    shading_type: i32,
+
+    #[pdf(key = "ColorSpace")]
+    color_space: ColorSpace,
+
+    #[pdf(key = "Background")]
+    background: Option<Vec<f32>>,
+
+    #[pdf(key = "BBox")]
+    bbox: Option<Rectangle>,
+
+    #[pdf(key = "AntiAlias", default = "false")]
+    anti_alias: bool,
+
+    #[pdf(other)]
+    other: Dictionary,
+}
+
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Shading {
+    pub color_space: ColorSpace,
+
+    /// color of the area outside the shading, only used by `sh` patterns
+    pub background: Option<Vec<f32>>,
+
+    pub bbox: Option<Rectangle>,
+
+    pub anti_alias: bool,
+
+    pub kind: ShadingKind,
+}
+
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ShadingKind {
+    /// type 1
+    Function(FunctionShading),
+    /// type 2
+    Axial(AxialShading),
+    /// type 3
+    Radial(RadialShading),
+    /// type 4
+    FreeForm(FreeFormShading),
+    /// type 5
+    Lattice(LatticeShading),
+    /// type 6
+    Coons(PatchShading),
+    /// type 7
+    Tensor(PatchShading),
+}
+
+/// The `Function` entry of a shading: either one function with n outputs,
+/// or n functions with one output each.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ShadingFunction {
+    Single(Function),
+    Multiple(Vec<Function>),
+}
+impl ShadingFunction {
+    pub fn apply(&self, x: &[f32], out: &mut [f32]) -> Result<()> {
+        match *self {
+            ShadingFunction::Single(ref f) => f.apply(x, out),
+            ShadingFunction::Multiple(ref fs) => {
+                if fs.len() != out.len() {
+                    bail!("{} functions for {} color components", fs.len(), out.len());
+                }
+                for (f, y) in fs.iter().zip(out) {
+                    f.apply(x, std::slice::from_mut(y))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl Object for ShadingFunction {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Array(parts) => Ok(ShadingFunction::Multiple(
+                parts.into_iter().map(|p| Function::from_primitive(p, resolve)).collect::<Result<_>>()?
+            )),
+            p => Ok(ShadingFunction::Single(Function::from_primitive(p, resolve)?)),
+        }
+    }
+}
+impl ObjectWrite for ShadingFunction {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            ShadingFunction::Single(ref f) => f.to_primitive(update),
+            ShadingFunction::Multiple(ref fs) => fs.to_primitive(update),
+        }
+    }
+}
+impl DeepClone for ShadingFunction {
+    fn deep_clone(&self, _cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+/// Color defined by a function of x and y.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct FunctionShading {
+    /// `[xmin xmax ymin ymax]`
+    #[pdf(key = "Domain", default = "vec![0., 1., 0., 1.]")]
+    pub domain: Vec<f32>,
+
+    /// maps the domain into the shading space
+    #[pdf(key = "Matrix")]
+    pub matrix: Option<Matrix>,
+
+    #[pdf(key = "Function")]
+    pub function: ShadingFunction,
+}
+
+/// Color varying along the line between two points.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct AxialShading {
+    /// `[x0 y0 x1 y1]`
+    #[pdf(key = "Coords")]
+    pub coords: Vec<f32>,
+
+    #[pdf(key = "Domain", default = "vec![0., 1.]")]
+    pub domain: Vec<f32>,
+
+    #[pdf(key = "Function")]
+    pub function: ShadingFunction,
+
+    /// whether to extend the shading beyond the start and end point
+    #[pdf(key = "Extend", default = "vec![false, false]")]
+    pub extend: Vec<bool>,
+}
+
+/// Color varying between two circles.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct RadialShading {
+    /// `[x0 y0 r0 x1 y1 r1]`
+    #[pdf(key = "Coords")]
+    pub coords: Vec<f32>,
+
+    #[pdf(key = "Domain", default = "vec![0., 1.]")]
+    pub domain: Vec<f32>,
+
+    #[pdf(key = "Function")]
+    pub function: ShadingFunction,
+
+    /// whether to extend the shading beyond the start and end circle
+    #[pdf(key = "Extend", default = "vec![false, false]")]
+    pub extend: Vec<bool>,
+}
+
+/// How the vertices of a mesh shading (types 4 to 7) are packed into its stream.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+pub struct MeshParams {
+    #[pdf(key = "BitsPerCoordinate")]
+    pub bits_per_coordinate: u32,
+
+    #[pdf(key = "BitsPerComponent")]
+    pub bits_per_component: u32,
+
+    /// not used by lattice meshes
+    #[pdf(key = "BitsPerFlag")]
+    pub bits_per_flag: Option<u32>,
+
+    /// `[xmin xmax ymin ymax c1min c1max … cnmin cnmax]`
+    #[pdf(key = "Decode")]
+    pub decode: Vec<f32>,
+
+    /// If present, every vertex has a single parametric value instead of color components.
+    #[pdf(key = "Function")]
+    pub function: Option<ShadingFunction>,
+}
+
+/// A vertex of a triangle mesh.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MeshVertex {
+    /// how the vertex connects to the previous ones, always 0 in a lattice
+    pub flag: u8,
+    pub point: Point,
+    /// color components, or the parametric value if there is a `Function`
+    pub color: Vec<f32>,
+}
+
+/// Free-form triangle mesh.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FreeFormShading {
+    pub params: MeshParams,
+    pub vertices: Vec<MeshVertex>,
+}
+impl FreeFormShading {
+    /// The triangles as indices into `vertices`.
+    ///
+    /// A vertex with flag 0 starts a new triangle, 1 continues with the edge `bc` of the
+    /// previous triangle and 2 with the edge `ac`.
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let mut triangles: Vec<[usize; 3]> = vec![];
+        let mut i = 0;
+        while i < self.vertices.len() {
+            let triangle = match (self.vertices[i].flag, triangles.last()) {
+                (1, Some(&[_, b, c])) => [b, c, i],
+                (2, Some(&[a, _, c])) => [a, c, i],
+                _ if i + 3 <= self.vertices.len() => {
+                    i += 2;
+                    [i - 2, i - 1, i]
+                }
+                _ => break,
+            };
+            triangles.push(triangle);
+            i += 1;
+        }
+        triangles
+    }
+}
+
+/// Lattice-form triangle mesh.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LatticeShading {
+    pub params: MeshParams,
+    pub vertices_per_row: usize,
+    pub vertices: Vec<MeshVertex>,
+}
+impl LatticeShading {
+    /// The triangles as indices into `vertices`, two for each cell of the lattice.
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let w = self.vertices_per_row;
+        if w < 2 {
+            return vec![];
+        }
+        let rows = self.vertices.len() / w;
+        let mut triangles = Vec::with_capacity(2 * rows.saturating_sub(1) * (w - 1));
+        for row in 1..rows {
+            for col in 1..w {
+                let (top, bottom) = ((row - 1) * w + col, row * w + col);
+                triangles.push([top - 1, top, bottom - 1]);
+                triangles.push([top, bottom - 1, bottom]);
+            }
+        }
+        triangles
+    }
+}
+
+/// A patch as it appears in the stream of a Coons or tensor-product patch mesh.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PatchRecord {
+    /// 0 for a patch of its own, 1 to 3 to share an edge with the previous patch
+    pub flag: u8,
+    /// Control points in stream order. A patch with flag 0 has 12 (Coons) or 16 (tensor),
+    /// the others leave out the four shared ones.
+    pub points: Vec<Point>,
+    /// 4 colors for flag 0, otherwise 2
+    pub colors: Vec<Vec<f32>>,
+}
+
+/// Coons or tensor-product patch mesh.
+#[derive(Debug, Clone, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PatchShading {
+    pub params: MeshParams,
+    pub patches: Vec<PatchRecord>,
+}
+
+/// A patch with the shared edge filled in.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    /// `points[i][j]` is the control point p<sub>ij</sub> of the tensor-product surface.
+    /// The inner points of Coons patches are computed from the boundary.
+    pub points: [[Point; 4]; 4],
+    /// The colors at p<sub>00</sub>, p<sub>03</sub>, p<sub>33</sub> and p<sub>30</sub>.
+    pub colors: [Vec<f32>; 4],
+}
+
+/// Position of the control points of a patch in the stream.
+const PATCH_ORDER: [(usize, usize); 16] = [
+    (0, 0), (0, 1), (0, 2), (0, 3), (1, 3), (2, 3), (3, 3), (3, 2),
+    (3, 1), (3, 0), (2, 0), (1, 0), (1, 1), (1, 2), (2, 2), (2, 1),
+];
+
+impl PatchShading {
+    /// Resolve the shared edges of all patches.
+    pub fn patches(&self) -> Result<Vec<Patch>> {
+        let mut patches = Vec::with_capacity(self.patches.len());
+        let mut prev: Option<(Vec<Point>, Vec<Vec<f32>>)> = None;
+        for record in &self.patches {
+            let (points, colors) = match (record.flag, prev.as_ref()) {
+                (0, _) => (record.points.clone(), record.colors.clone()),
+                (flag @ 1..=3, Some((prev_points, prev_colors))) => {
+                    let (shared, c) = match flag {
+                        1 => ([3, 4, 5, 6], 1),
+                        2 => ([6, 7, 8, 9], 2),
+                        _ => ([9, 10, 11, 0], 3),
+                    };
+                    let points = shared.iter().map(|&i| prev_points[i]).chain(record.points.iter().cloned()).collect();
+                    let colors = [c, (c + 1) % 4].iter().map(|&i| prev_colors[i].clone()).chain(record.colors.iter().cloned()).collect();
+                    (points, colors)
+                }
+                (flag, _) => bail!("patch with flag {} can't continue the previous one", flag),
+            };
+            if !matches!(points.len(), 12 | 16) || colors.len() != 4 {
+                bail!("patch with {} points and {} colors", points.len(), colors.len());
+            }
+            let mut grid = [[Point::default(); 4]; 4];
+            for (&(i, j), &p) in PATCH_ORDER.iter().zip(&points) {
+                grid[i][j] = p;
+            }
+            if points.len() == 12 {
+                coons_inner_points(&mut grid);
+            }
+            let c: [Vec<f32>; 4] = colors.clone().try_into().unwrap();
+            patches.push(Patch { points: grid, colors: c });
+            prev = Some((points, colors));
+        }
+        Ok(patches)
+    }
+}
+
+/// PDF32000 8.7.4.5.8: the implicit inner control points of a Coons patch.
+fn coons_inner_points(p: &mut [[Point; 4]; 4]) {
+    let inner = |corner: (usize, usize), adj: [(usize, usize); 2], far: [(usize, usize); 2], opp: [(usize, usize); 2], across: (usize, usize)| {
+        let get = |(i, j): (usize, usize)| p[i][j];
+        let sum = |a: [(usize, usize); 2]| {
+            let (a, b) = (get(a[0]), get(a[1]));
+            Point { x: a.x + b.x, y: a.y + b.y }
+        };
+        let (c, a, f, o, x) = (get(corner), sum(adj), sum(far), sum(opp), get(across));
+        Point {
+            x: (-4. * c.x + 6. * a.x - 2. * f.x + 3. * o.x - x.x) / 9.,
+            y: (-4. * c.y + 6. * a.y - 2. * f.y + 3. * o.y - x.y) / 9.,
+        }
+    };
+    let p11 = inner((0, 0), [(0, 1), (1, 0)], [(0, 3), (3, 0)], [(3, 1), (1, 3)], (3, 3));
+    let p12 = inner((0, 3), [(0, 2), (1, 3)], [(0, 0), (3, 3)], [(3, 2), (1, 0)], (3, 0));
+    let p21 = inner((3, 0), [(3, 1), (2, 0)], [(3, 3), (0, 0)], [(0, 1), (2, 3)], (0, 3));
+    let p22 = inner((3, 3), [(3, 2), (2, 3)], [(3, 0), (0, 3)], [(0, 2), (2, 0)], (0, 0));
+    p[1][1] = p11;
+    p[1][2] = p12;
+    p[2][1] = p21;
+    p[2][2] = p22;
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> Option<u64> {
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let byte = *self.data.get(self.pos / 8)?;
+            value = value << 1 | (byte >> (7 - self.pos % 8) & 1) as u64;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+    /// Every vertex or patch starts at a byte boundary.
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len() * 8
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    bits: usize,
+}
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for k in (0..bits).rev() {
+            if self.bits == self.data.len() * 8 {
+                self.data.push(0);
+            }
+            if value >> k & 1 != 0 {
+                *self.data.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+    fn align(&mut self) {
+        self.bits = self.data.len() * 8;
+    }
+}
+
+fn max_value(bits: u32) -> f64 {
+    ((1u64 << bits) - 1) as f64
+}
+
+impl MeshParams {
+    /// number of color components (or 1 with a `Function`)
+    pub fn components(&self) -> usize {
+        self.decode.len().saturating_sub(4) / 2
+    }
+    fn validate(&self, flags: bool) -> Result<()> {
+        if !matches!(self.bits_per_coordinate, 1 | 2 | 4 | 8 | 12 | 16 | 24 | 32) {
+            bail!("invalid BitsPerCoordinate {}", self.bits_per_coordinate);
+        }
+        if !matches!(self.bits_per_component, 1 | 2 | 4 | 8 | 12 | 16) {
+            bail!("invalid BitsPerComponent {}", self.bits_per_component);
+        }
+        match (flags, self.bits_per_flag) {
+            (true, Some(2 | 4 | 8)) | (false, _) => {}
+            (true, bits) => bail!("invalid BitsPerFlag {:?}", bits),
+        }
+        if self.decode.len() < 6 || self.decode.len() & 1 != 0 {
+            bail!("mesh Decode array with {} entries", self.decode.len());
+        }
+        Ok(())
+    }
+    fn read_flag(&self, r: &mut BitReader) -> Option<u8> {
+        r.read(self.bits_per_flag.unwrap_or(0)).map(|f| f as u8)
+    }
+    fn read_point(&self, r: &mut BitReader) -> Option<Point> {
+        let bits = self.bits_per_coordinate;
+        let d = &self.decode;
+        let x = r.read(bits)?;
+        let y = r.read(bits)?;
+        Some(Point {
+            x: (d[0] as f64 + x as f64 * (d[1] - d[0]) as f64 / max_value(bits)) as f32,
+            y: (d[2] as f64 + y as f64 * (d[3] - d[2]) as f64 / max_value(bits)) as f32,
+        })
+    }
+    fn read_color(&self, r: &mut BitReader) -> Option<Vec<f32>> {
+        let bits = self.bits_per_component;
+        self.decode[4..].chunks_exact(2).map(|d| {
+            let c = r.read(bits)?;
+            Some((d[0] as f64 + c as f64 * (d[1] - d[0]) as f64 / max_value(bits)) as f32)
+        }).collect()
+    }
+    fn read_vertex(&self, r: &mut BitReader) -> Option<MeshVertex> {
+        let flag = self.read_flag(r)?;
+        let point = self.read_point(r)?;
+        let color = self.read_color(r)?;
+        r.align();
+        Some(MeshVertex { flag, point, color })
+    }
+
+    fn write_flag(&self, w: &mut BitWriter, flag: u8) {
+        w.write(flag as u64, self.bits_per_flag.unwrap_or(0));
+    }
+    fn write_point(&self, w: &mut BitWriter, p: Point) {
+        let bits = self.bits_per_coordinate;
+        w.write(quantize(p.x, self.decode[0], self.decode[1], bits), bits);
+        w.write(quantize(p.y, self.decode[2], self.decode[3], bits), bits);
+    }
+    fn write_color(&self, w: &mut BitWriter, color: &[f32]) -> Result<()> {
+        if color.len() != self.components() {
+            bail!("color with {} components in a mesh with {}", color.len(), self.components());
+        }
+        let bits = self.bits_per_component;
+        for (&c, d) in color.iter().zip(self.decode[4..].chunks_exact(2)) {
+            w.write(quantize(c, d[0], d[1], bits), bits);
+        }
+        Ok(())
+    }
+    fn write_vertex(&self, w: &mut BitWriter, v: &MeshVertex) -> Result<()> {
+        self.write_flag(w, v.flag);
+        self.write_point(w, v.point);
+        self.write_color(w, &v.color)?;
+        w.align();
+        Ok(())
+    }
+}
+
+/// Map `v` from `[min, max]` to an integer of `bits` bits.
+fn quantize(v: f32, min: f32, max: f32, bits: u32) -> u64 {
+    if max == min {
+        return 0;
+    }
+    let scale = max_value(bits);
+    ((v as f64 - min as f64) / (max as f64 - min as f64) * scale).round().clamp(0., scale) as u64
+}
+
+fn decode_vertices(params: &MeshParams, data: &[u8]) -> Vec<MeshVertex> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut vertices = vec![];
+    while !r.at_end() {
+        match params.read_vertex(&mut r) {
+            Some(v) => vertices.push(v),
+            None => break,
+        }
+    }
+    vertices
+}
+
+fn decode_patches(params: &MeshParams, data: &[u8], tensor: bool) -> Result<Vec<PatchRecord>> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut patches = vec![];
+    while !r.at_end() {
+        let patch = (|| {
+            let flag = params.read_flag(&mut r)?;
+            let (n_points, n_colors) = match (flag, tensor) {
+                (0, false) => (12, 4),
+                (0, true) => (16, 4),
+                (_, false) => (8, 2),
+                (_, true) => (12, 2),
+            };
+            let points = (0..n_points).map(|_| params.read_point(&mut r)).collect::<Option<Vec<_>>>()?;
+            let colors = (0..n_colors).map(|_| params.read_color(&mut r)).collect::<Option<Vec<_>>>()?;
+            r.align();
+            Some(PatchRecord { flag, points, colors })
+        })();
+        match patch {
+            Some(p) if p.flag > 3 => bail!("invalid patch flag {}", p.flag),
+            Some(p) => patches.push(p),
+            None => break,
+        }
+    }
+    Ok(patches)
+}
+
+impl Shading {
+    fn from_raw(raw: RawShading, data: Option<Arc<[u8]>>, resolve: &impl Resolve) -> Result<Shading> {
+        let RawShading { shading_type, color_space, background, bbox, anti_alias, mut other } = raw;
+        let mesh = |other: Dictionary, flags: bool| -> Result<(MeshParams, Arc<[u8]>)> {
+            let params = MeshParams::from_dict(other, resolve)?;
+            params.validate(flags)?;
+            match data {
+                Some(ref data) => Ok((params, data.clone())),
+                None => bail!("shading type {} has to be a stream", shading_type),
+            }
+        };
+        let kind = match shading_type {
+            1 => ShadingKind::Function(FunctionShading::from_dict(other, resolve)?),
+            2 => ShadingKind::Axial(AxialShading::from_dict(other, resolve)?),
+            3 => ShadingKind::Radial(RadialShading::from_dict(other, resolve)?),
+            4 => {
+                let (params, data) = mesh(other, true)?;
+                let vertices = decode_vertices(&params, &data);
+                ShadingKind::FreeForm(FreeFormShading { params, vertices })
+            }
+            5 => {
+                let vertices_per_row = u32::from_primitive(other.require("LatticeShading", "VerticesPerRow")?, resolve)? as usize;
+                if vertices_per_row < 2 {
+                    bail!("VerticesPerRow has to be at least 2, found {}", vertices_per_row);
+                }
+                let (params, data) = mesh(other, false)?;
+                let params = MeshParams { bits_per_flag: None, ..params };
+                let vertices = decode_vertices(&params, &data);
+                ShadingKind::Lattice(LatticeShading { params, vertices_per_row, vertices })
+            }
+            6 | 7 => {
+                let (params, data) = mesh(other, true)?;
+                let patches = decode_patches(&params, &data, shading_type == 7)?;
+                let shading = PatchShading { params, patches };
+                if shading_type == 6 {
+                    ShadingKind::Coons(shading)
+                } else {
+                    ShadingKind::Tensor(shading)
+                }
+            }
+            t => bail!("invalid ShadingType {}", t),
+        };
+        Ok(Shading { color_space, background, bbox, anti_alias, kind })
+    }
+
+    /// Pack the vertices or patches of a mesh shading. `None` for types 1 to 3.
+    pub fn mesh_data(&self) -> Result<Option<Vec<u8>>> {
+        let mut w = BitWriter::default();
+        match self.kind {
+            ShadingKind::Function(_) | ShadingKind::Axial(_) | ShadingKind::Radial(_) => return Ok(None),
+            ShadingKind::FreeForm(FreeFormShading { ref params, ref vertices })
+            | ShadingKind::Lattice(LatticeShading { ref params, ref vertices, .. }) => {
+                params.validate(false)?;
+                for v in vertices {
+                    params.write_vertex(&mut w, v)?;
+                }
+            }
+            ShadingKind::Coons(PatchShading { ref params, ref patches })
+            | ShadingKind::Tensor(PatchShading { ref params, ref patches }) => {
+                params.validate(true)?;
+                for patch in patches {
+                    params.write_flag(&mut w, patch.flag);
+                    for &p in &patch.points {
+                        params.write_point(&mut w, p);
+                    }
+                    for c in &patch.colors {
+                        params.write_color(&mut w, c)?;
+                    }
+                    w.align();
+                }
+            }
+        }
+        Ok(Some(w.data))
+    }
+}
+
+impl Object for Shading {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Dictionary(dict) => {
+                Shading::from_raw(RawShading::from_dict(dict, resolve)?, None, resolve)
+            }
+            Primitive::Stream(s) => {
+                let stream: Stream<RawShading> = Stream::from_stream(s, resolve)?;
+                let data = t!(stream.data(resolve));
+                Shading::from_raw(stream.info.info, Some(data), resolve)
+            }
+            p => Err(PdfError::UnexpectedPrimitive {
+                expected: "Dictionary or Stream",
+                found: p.get_debug_name(),
+            }),
+        }
+    }
+}
+impl ObjectWrite for Shading {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let (shading_type, other) = match self.kind {
+            ShadingKind::Function(ref s) => (1, s.to_dict(update)?),
+            ShadingKind::Axial(ref s) => (2, s.to_dict(update)?),
+            ShadingKind::Radial(ref s) => (3, s.to_dict(update)?),
+            ShadingKind::FreeForm(ref s) => (4, s.params.to_dict(update)?),
+            ShadingKind::Lattice(ref s) => {
+                let mut dict = s.params.to_dict(update)?;
+                dict.remove("BitsPerFlag");
+                dict.insert("VerticesPerRow", s.vertices_per_row as i32);
+                (5, dict)
+            }
+            ShadingKind::Coons(ref s) => (6, s.params.to_dict(update)?),
+            ShadingKind::Tensor(ref s) => (7, s.params.to_dict(update)?),
+        };
+        let raw = RawShading {
+            shading_type,
+            color_space: self.color_space.clone(),
+            background: self.background.clone(),
+            bbox: self.bbox,
+            anti_alias: self.anti_alias,
+            other,
+        };
+        match self.mesh_data()? {
+            Some(data) => Stream::new(raw, data).to_primitive(update),
+            None => raw.to_primitive(update),
+        }
+    }
+}
+impl DeepClone for Shading {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        Ok(Shading {
+            color_space: self.color_space.deep_clone(cloner)?,
+            background: self.background.clone(),
+            bbox: self.bbox,
+            anti_alias: self.anti_alias,
+            kind: self.kind.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_form_round_trip() {
+        let params = MeshParams {
+            bits_per_coordinate: 8,
+            bits_per_component: 8,
+            bits_per_flag: Some(2),
+            decode: vec![0., 255., 0., 255., 0., 1.],
+            function: None,
+        };
+        let vertex = |flag, x, y, c| MeshVertex { flag, point: Point { x, y }, color: vec![c] };
+        let shading = Shading {
+            color_space: ColorSpace::DeviceGray,
+            background: None,
+            bbox: None,
+            anti_alias: false,
+            kind: ShadingKind::FreeForm(FreeFormShading {
+                params: params.clone(),
+                vertices: vec![vertex(0, 0., 0., 0.), vertex(0, 10., 0., 1.), vertex(0, 0., 10., 0.), vertex(1, 10., 10., 1.)],
+            }),
+        };
+        let data = shading.mesh_data().unwrap().unwrap();
+        // 2 + 8 + 8 + 8 bits, padded to 4 bytes
+        assert_eq!(data.len(), 16);
+        let decoded = FreeFormShading { vertices: decode_vertices(&params, &data), params };
+        assert_eq!(decoded.vertices[3].point, Point { x: 10., y: 10. });
+        assert_eq!(decoded.vertices[1].color, [1.]);
+        assert_eq!(decoded.triangles(), [[0, 1, 2], [1, 2, 3]]);
+    }
+
+    #[test]
+    fn coons_shared_edge() {
+        let params = MeshParams {
+            bits_per_coordinate: 8,
+            bits_per_component: 8,
+            bits_per_flag: Some(8),
+            decode: vec![0., 255., 0., 255., 0., 255.],
+            function: None,
+        };
+        let square = |x0: f32| -> Vec<Point> {
+            PATCH_ORDER[..12].iter().map(|&(i, j)| Point { x: x0 + 3. * j as f32, y: 3. * i as f32 }).collect()
+        };
+        let first = PatchRecord { flag: 0, points: square(0.), colors: vec![vec![0.], vec![1.], vec![2.], vec![3.]] };
+        let second = PatchRecord { flag: 1, points: square(9.)[4..].to_vec(), colors: vec![vec![4.], vec![5.]] };
+        let mut w = BitWriter::default();
+        for patch in [first, second] {
+            params.write_flag(&mut w, patch.flag);
+            patch.points.iter().for_each(|&p| params.write_point(&mut w, p));
+            patch.colors.iter().for_each(|c| params.write_color(&mut w, c).unwrap());
+            w.align();
+        }
+        let records = decode_patches(&params, &w.data, false).unwrap();
+        let patches = PatchShading { params, patches: records }.patches().unwrap();
+        assert_eq!(patches.len(), 2);
+        // the first patch is flat, so the inner points lie on the grid
+        assert_eq!(patches[0].points[1][1], Point { x: 3., y: 3. });
+        assert_eq!(patches[0].points[2][2], Point { x: 6., y: 6. });
+        // the second one starts at the right edge of the first
+        assert_eq!(patches[1].points[0][0], Point { x: 9., y: 0. });
+        assert_eq!(patches[1].points[0][3], Point { x: 9., y: 9. });
+        assert_eq!(patches[1].colors[0], [1.]);
+        assert_eq!(patches[1].colors[1], [2.]);
+    }
+}
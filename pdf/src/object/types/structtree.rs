@@ -1,11 +1,111 @@
 use super::prelude::*;
+use crate::content::{MarkedOps, Op, TextDrawAdjusted};
+use crate::font::ToUnicodeMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Object, ObjectWrite, Debug, DataSize)]
 #[pdf(Type = "StructTreeRoot")]
 pub struct StructTreeRoot {
     #[pdf(key = "K")]
-    pub children: Vec<StructElem>,
+    pub children: Vec<MaybeRef<StructElem>>,
+
+    /// Maps the `StructParents` of a page (or `StructParent` of an object) to the structure elements.
+    #[pdf(key = "ParentTree")]
+    pub parent_tree: Option<NumberTree<ParentTreeEntry>>,
+
+    #[pdf(key = "ParentTreeNextKey")]
+    pub parent_tree_next_key: Option<i32>,
+
+    #[pdf(key = "RoleMap")]
+    pub role_map: HashMap<Name, Name>,
+
+    #[pdf(key = "ClassMap")]
+    pub class_map: Option<Dictionary>,
+}
+impl StructTreeRoot {
+    /// Find the structure element that the marked content `mcid` on a page with
+    /// the given `StructParents` value belongs to.
+    pub fn parent_of(&self, struct_parents: i32, mcid: i32, resolve: &impl Resolve) -> Result<Option<PlainRef>> {
+        let tree = match self.parent_tree {
+            Some(ref tree) => tree,
+            None => return Ok(None),
+        };
+        let mut found = None;
+        tree.walk(resolve, &mut |key, entry| {
+            if key == struct_parents {
+                found = match *entry {
+                    ParentTreeEntry::Array(ref elems) => elems.get(mcid as usize).cloned().flatten(),
+                    ParentTreeEntry::Elem(r) => Some(r),
+                };
+            }
+        })?;
+        Ok(found)
+    }
+
+    /// Resolve the standard structure type of `elem`, following the `RoleMap`.
+    pub fn standard_type(&self, elem: &StructElem) -> StructType {
+        let mut typ = elem.struct_type.clone();
+        // bounded, so cyclic role maps terminate
+        for _ in 0..self.role_map.len() {
+            let mapped = match typ {
+                StructType::Other(ref name) => match self.role_map.get(name.as_str()) {
+                    Some(mapped) => mapped.clone(),
+                    None => break,
+                },
+                _ => break,
+            };
+            typ = StructType::from_primitive(Primitive::Name(mapped.0), &NoResolve)
+                .unwrap_or(StructType::Other(String::new()));
+        }
+        typ
+    }
+}
+
+/// A value in the `ParentTree`.
+#[derive(Debug, DataSize, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ParentTreeEntry {
+    /// For page content: the structure element for each MCID. (`null` for unused ids)
+    Array(Vec<Option<PlainRef>>),
+
+    /// For annotations and XObjects: the parent structure element.
+    Elem(PlainRef),
 }
+impl Object for ParentTreeEntry {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Reference(r) => match resolve.resolve(r)? {
+                Primitive::Array(arr) => ParentTreeEntry::from_primitive(Primitive::Array(arr), resolve),
+                _ => Ok(ParentTreeEntry::Elem(r)),
+            },
+            Primitive::Array(arr) => Ok(ParentTreeEntry::Array(
+                arr.into_iter()
+                    .map(|p| match p {
+                        Primitive::Reference(r) => Some(r),
+                        _ => None,
+                    })
+                    .collect(),
+            )),
+            p => Err(PdfError::UnexpectedPrimitive {
+                expected: "Array or Reference",
+                found: p.get_debug_name(),
+            }),
+        }
+    }
+}
+impl ObjectWrite for ParentTreeEntry {
+    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
+        Ok(match *self {
+            ParentTreeEntry::Array(ref elems) => Primitive::Array(
+                elems.iter()
+                    .map(|r| r.map(Primitive::Reference).unwrap_or(Primitive::Null))
+                    .collect(),
+            ),
+            ParentTreeEntry::Elem(r) => Primitive::Reference(r),
+        })
+    }
+}
+
 #[derive(Object, ObjectWrite, Debug, DataSize)]
 pub struct StructElem {
     #[pdf(key = "S")]
@@ -20,9 +120,274 @@ pub struct StructElem {
     /// `Pg`: A page object representing a page on which some or all of the content items designated by the K entry are rendered.
     #[pdf(key = "Pg")]
     pub page: Option<Ref<Page>>,
+
+    /// `K`: The children of this element.
+    #[pdf(key = "K")]
+    pub children: Vec<StructKid>,
+
+    #[pdf(key = "T")]
+    pub title: Option<PdfString>,
+
+    #[pdf(key = "Lang")]
+    pub lang: Option<PdfString>,
+
+    #[pdf(key = "Alt")]
+    pub alt: Option<PdfString>,
+
+    #[pdf(key = "E")]
+    pub expansion: Option<PdfString>,
+
+    #[pdf(key = "ActualText")]
+    pub actual_text: Option<PdfString>,
 }
+impl StructElem {
+    /// The marked content sequences that are direct children of this element,
+    /// as `(page, mcid)` pairs.
+    ///
+    /// The page is inherited from this element if the child does not specify one.
+    pub fn marked_content(&self) -> impl Iterator<Item = (Option<Ref<Page>>, i32)> + '_ {
+        self.children.iter().filter_map(move |kid| match *kid {
+            StructKid::MarkedContent { mcid, page, stream: None } => Some((page.or(self.page), mcid)),
+            _ => None,
+        })
+    }
 
-#[derive(Object, ObjectWrite, Debug, DataSize)]
+    /// Calls `callback` for this element and all elements below it, in logical (reading) order.
+    /// The second argument is the nesting depth.
+    ///
+    /// Fails with [`PdfError::ReferenceCycle`] if an element is its own descendant.
+    pub fn walk(&self, resolve: &impl Resolve, callback: &mut dyn FnMut(&StructElem, usize)) -> Result<()> {
+        fn inner(
+            elem: &StructElem,
+            depth: usize,
+            ancestors: &mut (Vec<PlainRef>, HashSet<PlainRef>),
+            resolve: &impl Resolve,
+            callback: &mut dyn FnMut(&StructElem, usize),
+        ) -> Result<()> {
+            callback(elem, depth);
+            for kid in elem.children.iter() {
+                if let StructKid::Elem(ref child) = *kid {
+                    let r = child.as_ref().map(|r| r.get_inner());
+                    if let Some(r) = r {
+                        if !ancestors.1.insert(r) {
+                            return Err(PdfError::ReferenceCycle { obj_nr: r.id, chain: ancestors.0.iter().map(|r| r.id).collect() });
+                        }
+                        ancestors.0.push(r);
+                    }
+                    let child = t!(child.load(resolve));
+                    inner(&child, depth + 1, ancestors, resolve, callback)?;
+                    if let Some(r) = r {
+                        ancestors.0.pop();
+                        ancestors.1.remove(&r);
+                    }
+                }
+            }
+            Ok(())
+        }
+        inner(self, 0, &mut Default::default(), resolve, callback)
+    }
+}
+
+/// A child of a structure element (an item of its `K` entry).
+#[derive(Debug, DataSize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StructKid {
+    /// Loaded on demand, so that reading a deep tree doesn't nest.
+    Elem(Lazy<StructElem>),
+
+    /// A marked content sequence, either given as bare MCID or as marked-content reference dictionary.
+    ///
+    /// `stream` is set if the content is not part of the page content, but of a form XObject or similar.
+    MarkedContent {
+        mcid: i32,
+        page: Option<Ref<Page>>,
+        stream: Option<PlainRef>,
+    },
+
+    /// An object reference dictionary (for annotations and XObjects)
+    Object {
+        page: Option<Ref<Page>>,
+        obj: PlainRef,
+    },
+}
+impl Object for StructKid {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let (mut dict, r) = match p {
+            Primitive::Integer(mcid) => {
+                return Ok(StructKid::MarkedContent { mcid, page: None, stream: None });
+            }
+            Primitive::Reference(r) => (t!(resolve.resolve(r)?.into_dictionary()), Some(r)),
+            Primitive::Dictionary(dict) => (dict, None),
+            p => {
+                return Err(PdfError::UnexpectedPrimitive {
+                    expected: "Integer or Dictionary",
+                    found: p.get_debug_name(),
+                })
+            }
+        };
+        let page = dict.get("Pg").map(|p| Ref::from_primitive(p.clone(), resolve)).transpose()?;
+        let typ = dict.get("Type").and_then(|t| t.as_name().ok()).map(String::from);
+        match typ.as_deref() {
+            Some("MCR") => Ok(StructKid::MarkedContent {
+                mcid: t!(dict.require("MCR", "MCID")?.as_integer()),
+                page,
+                stream: dict.get("Stm").map(|s| s.clone().into_reference()).transpose()?,
+            }),
+            Some("OBJR") => Ok(StructKid::Object {
+                page,
+                obj: t!(dict.require("OBJR", "Obj")?.into_reference()),
+            }),
+            _ => {
+                let p = match r {
+                    Some(r) => Primitive::Reference(r),
+                    None => Primitive::Dictionary(dict),
+                };
+                Ok(StructKid::Elem(Lazy::from_primitive(p, resolve)?))
+            }
+        }
+    }
+}
+impl ObjectWrite for StructKid {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            StructKid::Elem(ref elem) => elem.to_primitive(update),
+            StructKid::MarkedContent { mcid, page: None, stream: None } => Ok(Primitive::Integer(mcid)),
+            StructKid::MarkedContent { mcid, page, stream } => {
+                let mut dict = Dictionary::new();
+                dict.insert("Type", Name::from("MCR"));
+                if let Some(page) = page {
+                    dict.insert("Pg", Primitive::Reference(page.get_inner()));
+                }
+                if let Some(stream) = stream {
+                    dict.insert("Stm", Primitive::Reference(stream));
+                }
+                dict.insert("MCID", Primitive::Integer(mcid));
+                Ok(dict.into())
+            }
+            StructKid::Object { page, obj } => {
+                let mut dict = Dictionary::new();
+                dict.insert("Type", Name::from("OBJR"));
+                if let Some(page) = page {
+                    dict.insert("Pg", Primitive::Reference(page.get_inner()));
+                }
+                dict.insert("Obj", Primitive::Reference(obj));
+                Ok(dict.into())
+            }
+        }
+    }
+}
+
+/// The operators of one content stream, grouped by their marked content id.
+///
+/// Together with [`StructElem::marked_content`] this allows retrieving the content
+/// belonging to a structure element.
+#[derive(Debug, Default)]
+pub struct MarkedContentMap {
+    ops: HashMap<i32, Vec<Op>>,
+    actual_text: HashMap<i32, PdfString>,
+}
+impl MarkedContentMap {
+    /// `resources` are the resources of the page (or form) the operators belong to.
+    pub fn from_ops(ops: &[Op], resources: Option<&Resources>, resolve: &impl Resolve) -> Result<MarkedContentMap> {
+        let mut map = MarkedContentMap::default();
+        for item in MarkedOps::new(ops, resources, resolve) {
+            let (op, stack) = item?;
+            // the innermost sequence with an MCID owns the content
+            if let Some(mc) = stack.iter().rev().find(|mc| mc.mcid.is_some()) {
+                let mcid = mc.mcid.unwrap();
+                if let Some(ref text) = mc.actual_text {
+                    map.actual_text.entry(mcid).or_insert_with(|| text.clone());
+                }
+                map.ops.entry(mcid).or_default().push(op.clone());
+            }
+        }
+        Ok(map)
+    }
+    pub fn for_page(page: &Page, resolve: &impl Resolve) -> Result<MarkedContentMap> {
+        let resources = page.resources().ok();
+        match page.contents {
+            Some(ref content) => {
+                let ops = t!(content.operations(resolve));
+                MarkedContentMap::from_ops(&ops, resources.map(|r| &**r), resolve)
+            }
+            None => Ok(MarkedContentMap::default()),
+        }
+    }
+
+    /// The operators inside the marked content sequence `mcid`.
+    pub fn ops(&self, mcid: i32) -> Option<&[Op]> {
+        self.ops.get(&mcid).map(|v| v.as_slice())
+    }
+
+    pub fn mcids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.ops.keys().cloned()
+    }
+
+    /// The text of marked content sequence `mcid`.
+    ///
+    /// Uses `ActualText` if present, otherwise the shown strings are decoded
+    /// with the `ToUnicode` map of the font in use (falling back to the raw bytes).
+    pub fn text(&self, mcid: i32, resources: Option<&Resources>, resolve: &impl Resolve) -> Result<Option<String>> {
+        if let Some(text) = self.actual_text.get(&mcid) {
+            return Ok(Some(text.to_string_lossy()));
+        }
+        let ops = match self.ops.get(&mcid) {
+            Some(ops) => ops,
+            None => return Ok(None),
+        };
+        let mut out = String::new();
+        let mut font: Option<(bool, Option<ToUnicodeMap>)> = None;
+        for op in ops {
+            match *op {
+                Op::TextFont { ref name, .. } => {
+                    font = match resources.and_then(|r| r.fonts.get(name)) {
+                        Some(f) => {
+                            let f = t!(f.load(resolve));
+                            Some((f.is_cid(), f.to_unicode(resolve).transpose()?))
+                        }
+                        None => None,
+                    };
+                }
+                Op::TextDraw { ref text } => decode_text(text.as_bytes(), font.as_ref(), &mut out),
+                Op::TextDrawAdjusted { ref array } => {
+                    for item in array {
+                        if let TextDrawAdjusted::Text(ref text) = *item {
+                            decode_text(text.as_bytes(), font.as_ref(), &mut out);
+                        }
+                    }
+                }
+                Op::TextNewline | Op::MoveTextPosition { .. } if !out.is_empty() && !out.ends_with(' ') => {
+                    out.push(' ');
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(out))
+    }
+}
+
+fn decode_text(data: &[u8], font: Option<&(bool, Option<ToUnicodeMap>)>, out: &mut String) {
+    match font {
+        Some(&(is_cid, Some(ref map))) => {
+            let mut push = |code: u16| match map.get(code) {
+                Some(s) => out.push_str(s),
+                None => out.push(char::REPLACEMENT_CHARACTER),
+            };
+            if is_cid {
+                for c in data.chunks_exact(2) {
+                    push(u16::from_be_bytes([c[0], c[1]]));
+                }
+            } else {
+                for &b in data {
+                    push(b as u16);
+                }
+            }
+        }
+        _ => out.extend(data.iter().map(|&b| b as char)),
+    }
+}
+
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone)]
 pub enum StructType {
     Document,
     Part,
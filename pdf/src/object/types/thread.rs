@@ -0,0 +1,105 @@
+use super::prelude::*;
+
+/// An article thread (PDF32000 12.4.3).
+#[derive(Object, ObjectWrite, Debug, DataSize)]
+#[pdf(Type = "Thread?")]
+pub struct Thread {
+    /// the first bead of the thread
+    #[pdf(key = "F")]
+    pub first: Option<Ref<Bead>>,
+
+    /// thread information, with the same entries as the document information dictionary
+    #[pdf(key = "I")]
+    pub info: Option<InfoDict>,
+}
+
+/// One rectangle of an article thread.
+///
+/// The beads of a thread form a circular list, linked by `next` and `prev`.
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone)]
+#[pdf(Type = "Bead?")]
+pub struct Bead {
+    /// the thread this bead belongs to (only required for the first bead)
+    #[pdf(key = "T")]
+    pub thread: Option<Ref<Thread>>,
+
+    #[pdf(key = "N")]
+    pub next: Ref<Bead>,
+
+    #[pdf(key = "V")]
+    pub prev: Ref<Bead>,
+
+    #[pdf(key = "P")]
+    pub page: Ref<Page>,
+
+    #[pdf(key = "R")]
+    pub rect: Rectangle,
+}
+
+impl Thread {
+    /// The beads of this thread in reading order.
+    pub fn beads<'a, R: Resolve>(&self, resolve: &'a R) -> Beads<'a, R> {
+        Beads {
+            resolve,
+            first: self.first,
+            next: self.first,
+            seen: vec![],
+        }
+    }
+
+    /// Write a new thread that runs through `beads`, given as pairs of page and rectangle.
+    pub fn create(info: Option<InfoDict>, beads: &[(Ref<Page>, Rectangle)], update: &mut impl Updater) -> Result<RcRef<Thread>> {
+        if beads.is_empty() {
+            return update.create(Thread { first: None, info });
+        }
+        let thread = update.promise::<Thread>();
+        let promises: Vec<_> = beads.iter().map(|_| update.promise::<Bead>()).collect();
+        let refs: Vec<_> = promises.iter().map(|p| p.get_ref()).collect();
+        let n = refs.len();
+
+        for (i, (promise, &(page, rect))) in promises.into_iter().zip(beads).enumerate() {
+            let bead = Bead {
+                thread: if i == 0 { Some(thread.get_ref()) } else { None },
+                next: refs[(i + 1) % n],
+                prev: refs[(i + n - 1) % n],
+                page,
+                rect,
+            };
+            update.fulfill(promise, bead)?;
+        }
+        update.fulfill(thread, Thread { first: Some(refs[0]), info })
+    }
+}
+
+/// Iterator over the beads of a [`Thread`], see [`Thread::beads`].
+///
+/// Stops when it is back at the first bead. Yields a `ReferenceCycle` error
+/// if the `N` links loop without returning to the first bead.
+pub struct Beads<'a, R> {
+    resolve: &'a R,
+    first: Option<Ref<Bead>>,
+    next: Option<Ref<Bead>>,
+    seen: Vec<PlainRef>,
+}
+impl<'a, R: Resolve> Iterator for Beads<'a, R> {
+    type Item = Result<RcRef<Bead>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let r = self.next.take()?;
+        let inner = r.get_inner();
+        if self.seen.contains(&inner) {
+            return Some(Err(PdfError::ReferenceCycle {
+                obj_nr: inner.id,
+                chain: self.seen.iter().map(|r| r.id).collect(),
+            }));
+        }
+        self.seen.push(inner);
+        let bead = match self.resolve.get(r) {
+            Ok(bead) => bead,
+            Err(e) => return Some(Err(e)),
+        };
+        if Some(bead.next) != self.first {
+            self.next = Some(bead.next);
+        }
+        Some(Ok(bead))
+    }
+}
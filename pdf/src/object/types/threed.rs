@@ -0,0 +1,229 @@
+use super::prelude::*;
+
+/// The entries of a 3D annotation (PDF32000-2 13.6.2), see [`Annot::three_d`].
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct ThreeDAnnot {
+    /// the 3D stream, or a 3D reference dictionary pointing to one that other annotations share
+    #[pdf(key = "3DD")]
+    pub data: Primitive,
+
+    /// the initial view: a view dictionary, an index or name of a view of the stream,
+    /// or `F`, `L`, `D` for the first, last or default view
+    #[pdf(key = "3DV")]
+    pub view: Option<Primitive>,
+
+    /// when the annotation is activated and deactivated
+    #[pdf(key = "3DA")]
+    pub activation: Option<ThreeDActivation>,
+
+    /// whether the user can interact with the model, or only the views of the document change it
+    #[pdf(key = "3DI", default = "true")]
+    pub interactive: bool,
+
+    /// the part of the annotation's rectangle the model is drawn in, in form space of the appearance
+    #[pdf(key = "3DB")]
+    pub view_box: Option<Rectangle>,
+}
+
+impl ThreeDAnnot {
+    /// The 3D stream, following a 3D reference dictionary if there is one.
+    pub fn stream(&self, resolve: &impl Resolve) -> Result<RcRef<ThreeDStream>> {
+        let mut data = self.data.clone();
+        // 3DRef dictionaries can only point to streams, so there is at most one of them
+        for _ in 0 .. 2 {
+            let r = match data {
+                Primitive::Reference(r) => r,
+                Primitive::Dictionary(ref dict) => match dict.get("3DD") {
+                    Some(&Primitive::Reference(r)) => r,
+                    _ => bail!("3D reference dictionary without 3D stream"),
+                },
+                p => return Err(PdfError::UnexpectedPrimitive { expected: "Reference", found: p.get_debug_name() }),
+            };
+            match t!(resolve.resolve(r)) {
+                Primitive::Stream(_) => return resolve.get(Ref::new(r)),
+                p => data = p,
+            }
+        }
+        bail!("3D reference dictionary pointing to another one")
+    }
+}
+
+/// A 3D stream, with a U3D or PRC model as data.
+pub type ThreeDStream = Stream<ThreeDDict>;
+
+/// The dictionary of a 3D stream.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "3D?")]
+pub struct ThreeDDict {
+    /// the format of the data, `U3D` or `PRC`
+    #[pdf(key = "Subtype")]
+    pub subtype: Name,
+
+    /// the predefined views
+    #[pdf(key = "VA")]
+    pub views: Option<Vec<ThreeDView>>,
+
+    /// the default view, like `3DV` of the annotation
+    #[pdf(key = "DV")]
+    pub default_view: Option<Primitive>,
+
+    /// a name tree of other 3D streams or XObjects that the model uses
+    #[pdf(key = "Resources")]
+    pub resources: Option<Primitive>,
+
+    /// a JavaScript run when the model is instantiated
+    #[pdf(key = "OnInstantiate")]
+    pub on_instantiate: Option<Primitive>,
+
+    /// the animation style
+    #[pdf(key = "AN")]
+    pub animation: Option<Primitive>,
+}
+
+impl ThreeDDict {
+    /// The usual file name extension for the data, `u3d` or `prc`, if the format is known.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self.subtype.as_str() {
+            "U3D" => Some("u3d"),
+            "PRC" => Some("prc"),
+            _ => None,
+        }
+    }
+
+    /// The view that `DV` (or `3DV` of an annotation, given as `view`) selects; the first one if there is none.
+    pub fn view(&self, view: Option<&Primitive>) -> Option<ThreeDView> {
+        let views = self.views.as_deref().unwrap_or_default();
+        match view.or(self.default_view.as_ref()) {
+            None => views.first().cloned(),
+            Some(Primitive::Integer(i)) => views.get(usize::try_from(*i).ok()?).cloned(),
+            Some(Primitive::Name(n)) => match n.as_str() {
+                "F" | "D" => views.first().cloned(),
+                "L" => views.last().cloned(),
+                _ => None,
+            },
+            Some(Primitive::String(name)) => views.iter().find(|v| v.internal_name.as_ref() == Some(name)).cloned(),
+            Some(p @ Primitive::Dictionary(_)) => ThreeDView::from_primitive(p.clone(), &NoResolve).ok(),
+            Some(_) => None,
+        }
+    }
+}
+
+/// A view of a 3D model: the position of the camera and how the model is shown.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "3DView?")]
+pub struct ThreeDView {
+    /// the name shown to the user
+    #[pdf(key = "XN")]
+    pub external_name: Option<PdfString>,
+
+    /// the name used by `3DV` and scripts
+    #[pdf(key = "IN")]
+    pub internal_name: Option<PdfString>,
+
+    /// where the camera position comes from, `M` for `camera_to_world` or `U3D` for `U3DPath`
+    #[pdf(key = "MS")]
+    pub matrix_source: Option<Name>,
+
+    /// a 3D transformation matrix (12 numbers) from camera to world coordinates
+    #[pdf(key = "C2W")]
+    pub camera_to_world: Option<Vec<f32>>,
+
+    /// the distance from the camera to the center of orbit
+    #[pdf(key = "CO")]
+    pub center_of_orbit: Option<f32>,
+
+    /// the projection, perspective or orthographic
+    #[pdf(key = "P")]
+    pub projection: Option<Dictionary>,
+
+    /// the background
+    #[pdf(key = "BG")]
+    pub background: Option<Dictionary>,
+
+    /// the render mode
+    #[pdf(key = "RM")]
+    pub render_mode: Option<Dictionary>,
+
+    /// the lighting scheme
+    #[pdf(key = "LS")]
+    pub lighting: Option<Dictionary>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+
+/// When a 3D annotation is activated (its model instantiated) and deactivated.
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct ThreeDActivation {
+    /// `PO` when the page is opened, `PV` when it becomes visible, `XA` explicitly (the default)
+    #[pdf(key = "A")]
+    pub activate: Option<Name>,
+
+    /// the state of the model when activated, `I` (instantiated) or `L` (live, the default)
+    #[pdf(key = "AIS")]
+    pub activated_state: Option<Name>,
+
+    /// `PC` when the page is closed, `PI` when it becomes invisible (the default), `XD` explicitly
+    #[pdf(key = "D")]
+    pub deactivate: Option<Name>,
+
+    /// the state of the model when deactivated, `U` (uninstantiated, the default), `I` or `L`
+    #[pdf(key = "DIS")]
+    pub deactivated_state: Option<Name>,
+
+    /// whether a toolbar is shown
+    #[pdf(key = "TB", default = "true")]
+    pub toolbar: bool,
+
+    /// whether the model tree is shown
+    #[pdf(key = "NP", default = "false")]
+    pub navigation_panel: bool,
+}
+
+impl Annot {
+    /// The 3D entries, if this is a 3D annotation.
+    pub fn three_d(&self, resolve: &impl Resolve) -> Result<Option<ThreeDAnnot>> {
+        if self.subtype.as_str() != "3D" {
+            return Ok(None);
+        }
+        ThreeDAnnot::from_dict(self.other.clone(), resolve).map(Some)
+    }
+}
+
+impl Page {
+    /// The 3D annotations of this page, with their 3D streams, so the models can be extracted.
+    pub fn three_d_models(&self, resolve: &impl Resolve) -> Result<Vec<(MaybeRef<Annot>, RcRef<ThreeDStream>)>> {
+        let mut models = vec![];
+        for annot in t!(self.annotations(resolve)) {
+            if let Some(three_d) = t!(annot.three_d(resolve)) {
+                let stream = t!(three_d.stream(resolve));
+                models.push((annot, stream));
+            }
+        }
+        Ok(models)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn views() {
+        let p = parse(b"<< /Type /3D /Subtype /PRC /DV (back) /VA [
+            << /Type /3DView /XN (Front) /IN (front) /MS /M /C2W [1 0 0 0 1 0 0 0 1 0 0 -10] /CO 10 >>
+            << /XN (Back) /IN (back) /P << /Subtype /O >> >> ] >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let dict = ThreeDDict::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(dict.extension(), Some("prc"));
+        assert_eq!(dict.view(None).unwrap().external_name.unwrap().as_bytes(), b"Back");
+        assert_eq!(dict.view(Some(&Primitive::Integer(0))).unwrap().center_of_orbit, Some(10.));
+        assert!(dict.view(Some(&Primitive::Name("F".into()))).unwrap().camera_to_world.is_some());
+        assert!(dict.view(Some(&Primitive::Integer(2))).is_none());
+
+        let p = parse(b"<< /3DD << /Type /3DRef /3DD 12 0 R >> /3DA << /A /PO /TB false >> /3DI false >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let annot = ThreeDAnnot::from_primitive(p, &NoResolve).unwrap();
+        assert!(!annot.interactive && !annot.activation.as_ref().unwrap().toolbar);
+        assert!(annot.stream(&NoResolve).is_err());
+    }
+}
@@ -25,6 +25,7 @@ impl ObjectWrite for XObject {
 pub type PostScriptXObject = Stream<PostScriptDict>;
 
 #[derive(Debug, DataSize, Clone, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ImageXObject {
     pub inner: Stream<ImageDict>,
 }
@@ -46,7 +47,10 @@ impl Deref for ImageXObject {
     }
 }
 
+/// The file formats an image can be exported as, see [`ImageXObject::export`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
+    /// The decoded samples, as returned by [`ImageXObject::image_data`].
     Raw,
     Jpeg,
     Jp2k,
@@ -78,6 +82,7 @@ impl ImageXObject {
                         StreamFilter::ASCII85Decode => false,
                         StreamFilter::LZWDecode(_) => false,
                         StreamFilter::RunLengthDecode => false,
+                        StreamFilter::ZstdDecode | StreamFilter::BrotliDecode => false,
                         StreamFilter::Crypt => true,
                         _ => true,
                     })
@@ -105,6 +110,14 @@ impl ImageXObject {
             Some(f) => f,
             None => return Ok(data),
         };
+        let max = resolve.options().limits.max_stream_size;
+        let too_large = || PdfError::LimitExceeded { what: "decoded image size", max: max as u64 };
+        // these decoders produce a byte per pixel, so the size is known before decoding
+        if matches!(filter, StreamFilter::CCITTFaxDecode(_) | StreamFilter::JBIG2Decode(_))
+            && self.inner.info.width as u64 * self.inner.info.height as u64 > max as u64
+        {
+            return Err(too_large());
+        }
         let mut data = match filter {
             StreamFilter::CCITTFaxDecode(ref params) => {
                 if self.inner.info.width != params.columns {
@@ -121,15 +134,18 @@ impl ImageXObject {
                 }
                 data
             }
-            StreamFilter::DCTDecode(ref p) => dct_decode(&data, p)?,
+            StreamFilter::DCTDecode(ref p) => dct_decode_limited(&data, p, max)?,
             StreamFilter::JPXDecode => jpx_decode(&data)?,
             StreamFilter::JBIG2Decode(ref p) => {
                 let global_data = p.globals.as_ref().map(|s| s.data(resolve)).transpose()?;
                 jbig2_decode(&data, global_data.as_deref().unwrap_or_default())?
             }
-            StreamFilter::FlateDecode(ref p) => flate_decode(&data, p)?,
+            StreamFilter::FlateDecode(_) => decode_limited(&data, filter, max)?,
             _ => unreachable!(),
         };
+        if data.len() > max {
+            return Err(too_large());
+        }
         if let Some(ref decode) = self.decode {
             if decode == &[1.0, 0.0] && self.bits_per_component == Some(1) {
                 data.iter_mut().for_each(|b| *b = !*b);
@@ -137,6 +153,518 @@ impl ImageXObject {
         }
         Ok(data.into())
     }
+
+    /// The pixels converted to 8 bit RGBA, row by row from the top.
+    ///
+    /// Image masks come out black, with the painted pixels opaque and the rest transparent.
+    /// Soft masks and `Mask` are not applied.
+    pub fn rgba_data(&self, resolve: &impl Resolve) -> Result<Vec<u8>> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let data = t!(self.image_data(resolve));
+        let bpc = if self.image_mask { 1 } else { self.bits_per_component.unwrap_or(8) as usize };
+        let space = match self.color_space {
+            Some(ref cs) => cs.clone(),
+            None if self.image_mask => ColorSpace::DeviceGray,
+            None => bail!("image without color space"),
+        };
+        let n = if self.image_mask { 1 } else { try_opt!(space.components()) };
+        let samples = t!(unpack_samples(&data, self.width, self.height, n, bpc as u8));
+        // image_data has inverted 1 bit images with a decode array of [1 0] already
+        let decode = match self.decode {
+            Some(ref d) if !(d[..] == [1., 0.] && self.bits_per_component == Some(1)) => Some(&d[..]),
+            _ => None,
+        };
+
+        // lookup table for indexed images
+        let palette = match space {
+            ColorSpace::Indexed(_, hival, _) => Some((0..=hival as usize)
+                .map(|i| space.to_rgb(&[i as f32], resolve))
+                .collect::<Result<Vec<_>>>()?),
+            _ => None,
+        };
+        let to_u8 = |v: f32| (v.clamp(0., 1.) * 255. + 0.5) as u8;
+
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        let mut components = vec![0.; n];
+        for px in samples.chunks_exact(n) {
+            if self.image_mask {
+                // sample 0 paints, unless the decode array is [1 0]
+                let inverted = matches!(decode, Some([a, _]) if *a == 1.);
+                let paint = (px[0] == 0) != inverted;
+                rgba.extend([0, 0, 0, if paint { 255 } else { 0 }]);
+                continue;
+            }
+            for (i, c) in components.iter_mut().enumerate() {
+                *c = match decode {
+                    Some(d) if d.len() >= 2 * n => decode_sample(px[i], bpc as u8, d[2 * i], d[2 * i + 1]),
+                    _ if palette.is_some() => px[i] as f32,
+                    _ => decode_sample(px[i], bpc as u8, 0., 1.),
+                };
+            }
+            let [r, g, b] = match (&palette, &space) {
+                (Some(palette), _) => palette[(components[0].max(0.) as usize).min(palette.len() - 1)],
+                (None, ColorSpace::DeviceGray) => [components[0]; 3],
+                (None, ColorSpace::DeviceRGB) => [components[0], components[1], components[2]],
+                (None, _) => space.to_rgb(&components, resolve)?,
+            };
+            rgba.extend([to_u8(r), to_u8(g), to_u8(b), 255]);
+        }
+        Ok(rgba)
+    }
+
+    /// The pixels converted to 8 bit RGBA like [`rgba_data`](Self::rgba_data), with the
+    /// transparency given by `SMask` or `Mask` applied.
+    ///
+    /// Masks of a different size are scaled to the size of the image. The `Matte` color of a
+    /// soft mask is taken out of the colors again. `Mask` is ignored if there is a soft mask.
+    pub fn masked_rgba_data(&self, resolve: &impl Resolve) -> Result<Vec<u8>> {
+        let mut rgba = t!(self.rgba_data(resolve));
+        let (width, height) = (self.width as usize, self.height as usize);
+        if let Some(r) = self.smask {
+            let smask = ImageXObject { inner: (*t!(resolve.get(r))).clone() };
+            let alpha = scaled_alpha(&t!(smask.rgba_data(resolve)), 0, &smask, width, height);
+            let matte = match (&smask.matte, &self.color_space) {
+                (Some(matte), Some(cs)) => Some(t!(cs.to_rgb(matte, resolve)).map(|c| c.clamp(0., 1.) * 255.)),
+                _ => None,
+            };
+            for (px, a) in rgba.chunks_exact_mut(4).zip(alpha) {
+                // the colors were premultiplied with alpha towards the matte color
+                if let (Some(matte), 1..) = (matte, a) {
+                    for (c, m) in px[..3].iter_mut().zip(matte) {
+                        *c = (m + (*c as f32 - m) * 255. / a as f32).clamp(0., 255.).round() as u8;
+                    }
+                }
+                px[3] = (px[3] as u16 * a as u16 / 255) as u8;
+            }
+            return Ok(rgba);
+        }
+        match self.mask {
+            Some(Mask::Stencil(r)) => {
+                let stencil = ImageXObject { inner: (*t!(resolve.get(r))).clone() };
+                if !stencil.image_mask {
+                    bail!("stencil mask without ImageMask");
+                }
+                let alpha = scaled_alpha(&t!(stencil.rgba_data(resolve)), 3, &stencil, width, height);
+                for (px, a) in rgba.chunks_exact_mut(4).zip(alpha) {
+                    px[3] = (px[3] as u16 * a as u16 / 255) as u8;
+                }
+            }
+            Some(Mask::ColorKey(ref ranges)) => {
+                let data = t!(self.image_data(resolve));
+                let bpc = self.bits_per_component.unwrap_or(8) as u8;
+                let n = match self.color_space {
+                    Some(ref cs) => try_opt!(cs.components()),
+                    None => bail!("image without color space"),
+                };
+                if ranges.len() < 2 * n {
+                    bail!("color key mask with {} values for {} components", ranges.len(), n);
+                }
+                // image_data has inverted 1 bit images with a decode array of [1 0]
+                let inverted = bpc == 1 && matches!(self.decode.as_deref(), Some([a, _]) if *a == 1.);
+                let samples = t!(unpack_samples(&data, self.width, self.height, n, bpc));
+                for (px, samples) in rgba.chunks_exact_mut(4).zip(samples.chunks_exact(n)) {
+                    let masked = samples.iter().enumerate().all(|(i, &v)| {
+                        let v = (v ^ inverted as u16) as u32;
+                        (ranges[2 * i]..=ranges[2 * i + 1]).contains(&v)
+                    });
+                    if masked {
+                        px[3] = 0;
+                    }
+                }
+            }
+            None => {}
+        }
+        Ok(rgba)
+    }
+
+    /// The format [`export`](Self::export) can write without decoding the image:
+    /// [`ImageFormat::Jpeg`] for `DCTDecode`, [`ImageFormat::Jp2k`] for `JPXDecode` and
+    /// [`ImageFormat::Png`] for everything else.
+    pub fn native_format(&self, resolve: &impl Resolve) -> Result<ImageFormat> {
+        Ok(match t!(self.raw_image_data(resolve)).1 {
+            Some(StreamFilter::DCTDecode(_)) => ImageFormat::Jpeg,
+            Some(StreamFilter::JPXDecode) => ImageFormat::Jp2k,
+            _ => ImageFormat::Png,
+        })
+    }
+
+    /// The image as a standalone file in `format`.
+    ///
+    /// JPEG and JPEG 2000 data is written as it is stored, so the `Decode` array is not applied.
+    /// PNG files are 8 bit RGBA, made from [`masked_rgba_data`](Self::masked_rgba_data).
+    /// JBIG2 and CCITT fax images can only be exported as PNG or raw data.
+    pub fn export(&self, format: ImageFormat, resolve: &impl Resolve) -> Result<Vec<u8>> {
+        match format {
+            ImageFormat::Raw => Ok(t!(self.image_data(resolve)).to_vec()),
+            ImageFormat::Png => {
+                let rgba = t!(self.masked_rgba_data(resolve));
+                png_rgba(self.width, self.height, &rgba)
+            }
+            ImageFormat::Jpeg | ImageFormat::Jp2k => {
+                let (data, filter) = t!(self.raw_image_data(resolve));
+                match (format, filter) {
+                    (ImageFormat::Jpeg, Some(StreamFilter::DCTDecode(_))) => Ok(data.to_vec()),
+                    (ImageFormat::Jp2k, Some(StreamFilter::JPXDecode)) => Ok(data.to_vec()),
+                    _ => bail!("image can't be exported as {:?} without re-encoding", format),
+                }
+            }
+            ImageFormat::Jbig2 | ImageFormat::CittFax => bail!("can't export images as {:?}", format),
+        }
+    }
+}
+
+/// Unpack the samples of an image with `bpc` bits per sample, one value per sample.
+///
+/// `data` holds `height` rows of `width` pixels with `components` samples each, every row
+/// starting on a byte boundary. Extra data at the end is ignored.
+pub fn unpack_samples(data: &[u8], width: u32, height: u32, components: usize, bpc: u8) -> Result<Vec<u16>> {
+    if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+        bail!("unsupported bits per component {}", bpc);
+    }
+    let (bpc, per_row) = (bpc as usize, width as usize * components);
+    let stride = (per_row * bpc).div_ceil(8);
+    let len = stride * height as usize;
+    if data.len() < len {
+        bail!("image data too short: {} < {}", data.len(), len);
+    }
+    let mut samples = Vec::with_capacity(per_row * height as usize);
+    for row in data[..len].chunks_exact(stride.max(1)) {
+        match bpc {
+            8 => samples.extend(row.iter().map(|&b| b as u16)),
+            16 => samples.extend(row.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]))),
+            _ => samples.extend((0..per_row).map(|i| {
+                let bit = i * bpc;
+                (row[bit / 8] >> (8 - bpc - bit % 8)) as u16 & ((1 << bpc) - 1)
+            })),
+        }
+    }
+    Ok(samples)
+}
+
+/// Map a sample with `bpc` bits to the range from `min` to `max`, like a `Decode` array does.
+pub fn decode_sample(sample: u16, bpc: u8, min: f32, max: f32) -> f32 {
+    let top = ((1u32 << bpc) - 1) as f32;
+    min + sample as f32 / top * (max - min)
+}
+
+/// Expand the samples of an image with `bpc` bits per sample to 8 bits each.
+///
+/// The layout of `data` is the same as for [`unpack_samples`]. Samples are mapped through
+/// `decode`, with a `[min max]` pair within `0..=1` for each component and `[0 1]` for all
+/// of them when it is `None`. Don't use this for indexed images, whose samples are not
+/// scaled; use [`unpack_samples`] instead.
+pub fn expand_samples(data: &[u8], width: u32, height: u32, components: usize, bpc: u8, decode: Option<&[f32]>) -> Result<Vec<u8>> {
+    if let Some(decode) = decode {
+        if decode.len() < 2 * components {
+            bail!("decode array with {} values for {} components", decode.len(), components);
+        }
+    }
+    let samples = t!(unpack_samples(data, width, height, components, bpc));
+    let to_u8 = |v: f32| (v.clamp(0., 1.) * 255. + 0.5) as u8;
+    let range = |i: usize| decode.map_or((0., 1.), |d| (d[2 * i], d[2 * i + 1]));
+    if bpc <= 8 {
+        // a lookup table for each component
+        let tables: Vec<Vec<u8>> = (0..components).map(|i| {
+            let (min, max) = range(i);
+            (0..1u16 << bpc).map(|v| to_u8(decode_sample(v, bpc, min, max))).collect()
+        }).collect();
+        Ok(samples.iter().enumerate().map(|(i, &v)| tables[i % components][v as usize]).collect())
+    } else {
+        Ok(samples.iter().enumerate().map(|(i, &v)| {
+            let (min, max) = range(i % components);
+            to_u8(decode_sample(v, bpc, min, max))
+        }).collect())
+    }
+}
+
+/// The alpha values of `mask` from `channel` of its RGBA pixels, scaled to `width` × `height`
+/// by picking the nearest pixel.
+fn scaled_alpha(rgba: &[u8], channel: usize, mask: &ImageDict, width: usize, height: usize) -> Vec<u8> {
+    let (mw, mh) = (mask.width as usize, mask.height as usize);
+    let mut alpha = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let my = y * mh / height;
+        for x in 0..width {
+            let mx = x * mw / width;
+            alpha.push(rgba.get((my * mw + mx) * 4 + channel).copied().unwrap_or(255));
+        }
+    }
+    alpha
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Encode 8 bit RGBA pixels as PNG.
+pub(crate) fn png_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // every row starts with the filter type, 0 is none
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks(width as usize * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &t!(encode(&raw, &StreamFilter::FlateDecode(Default::default()))));
+    chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+impl ImageXObject {
+    /// An image of the JPEG file `data`, which is kept as it is with `DCTDecode`.
+    ///
+    /// The size and the number of components are read from the frame header.
+    /// CMYK data written by Adobe applications is inverted, which the `Decode` array undoes.
+    pub fn from_jpeg(data: &[u8]) -> Result<ImageXObject> {
+        let info = t!(jpeg_info(data));
+        let (color_space, decode) = match info.components {
+            1 => (ColorSpace::DeviceGray, None),
+            3 => (ColorSpace::DeviceRGB, None),
+            4 if info.adobe => (ColorSpace::DeviceCMYK, Some(vec![1., 0., 1., 0., 1., 0., 1., 0.])),
+            4 => (ColorSpace::DeviceCMYK, None),
+            n => bail!("JPEG image with {} components", n),
+        };
+        let dict = ImageDict {
+            width: info.width,
+            height: info.height,
+            color_space: Some(color_space),
+            bits_per_component: Some(info.bits as i32),
+            decode,
+            ..Default::default()
+        };
+        let filter = StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None });
+        Ok(ImageXObject { inner: Stream::from_compressed(dict, data.to_vec(), vec![filter]) })
+    }
+
+    /// An image of the PNG file `data`.
+    ///
+    /// Without an alpha channel the compressed data is kept as it is, as PNG uses the same
+    /// compression and predictors as `FlateDecode`. Otherwise the alpha channel is split off
+    /// into a soft mask, which is created with `update`. Transparency given by the `tRNS` chunk
+    /// becomes a soft mask for palette images and a color key `Mask` for the others.
+    /// Interlaced images are not supported.
+    pub fn from_png(data: &[u8], update: &mut impl Updater) -> Result<ImageXObject> {
+        let png = t!(read_png(data));
+        let (width, height, bpc) = (png.width, png.height, png.bit_depth as i32);
+        let flate = |colors| StreamFilter::FlateDecode(LZWFlateParams::png(colors, bpc, width as i32));
+        let mut dict = ImageDict {
+            width,
+            height,
+            bits_per_component: Some(bpc),
+            ..Default::default()
+        };
+        let image = match png.color_type {
+            0 | 2 | 3 => {
+                let colors = if png.color_type == 2 { 3 } else { 1 };
+                dict.color_space = Some(match png.color_type {
+                    0 => ColorSpace::DeviceGray,
+                    2 => ColorSpace::DeviceRGB,
+                    _ => {
+                        let entries = png.palette.len() / 3;
+                        if entries == 0 || entries > 256 {
+                            bail!("PNG palette with {} entries", entries);
+                        }
+                        let lookup = png.palette[.. entries * 3].to_vec();
+                        ColorSpace::Indexed(Box::new(ColorSpace::DeviceRGB), (entries - 1) as u8, lookup.into())
+                    }
+                });
+                match (png.color_type, png.transparency.is_empty()) {
+                    (_, true) => {}
+                    // alpha values for the first palette entries, the others are opaque
+                    (3, false) => {
+                        let indices = t!(decode(&png.data, &flate(1)));
+                        let alpha: Vec<u8> = t!(unpack_samples(&indices, width, height, 1, bpc as u8)).into_iter()
+                            .map(|i| png.transparency.get(i as usize).copied().unwrap_or(255))
+                            .collect();
+                        dict.smask = Some(t!(soft_mask(width, height, &alpha, 8, update)));
+                    }
+                    // one 16 bit value per component, the pixels of that color are transparent
+                    (_, false) => {
+                        let key = png.transparency.chunks_exact(2)
+                            .take(colors as usize)
+                            .flat_map(|v| {
+                                let v = u16::from_be_bytes([v[0], v[1]]) as u32;
+                                [v, v]
+                            })
+                            .collect();
+                        dict.mask = Some(Mask::ColorKey(key));
+                    }
+                }
+                Stream::from_compressed(dict, png.data, vec![flate(colors)])
+            }
+            4 | 6 => {
+                let colors = if png.color_type == 6 { 3 } else { 1 };
+                dict.color_space = Some(if colors == 3 { ColorSpace::DeviceRGB } else { ColorSpace::DeviceGray });
+                let pixels = t!(decode(&png.data, &flate(colors + 1)));
+                let sample = bpc as usize / 8;
+                let pixel = (colors as usize + 1) * sample;
+                let len = width as usize * height as usize * pixel;
+                if pixels.len() < len {
+                    bail!("PNG image data too short: {} < {}", pixels.len(), len);
+                }
+                let mut color = Vec::with_capacity(len / pixel * colors as usize * sample);
+                let mut alpha = Vec::with_capacity(len / pixel * sample);
+                for px in pixels[.. len].chunks_exact(pixel) {
+                    let (c, a) = px.split_at(pixel - sample);
+                    color.extend_from_slice(c);
+                    alpha.extend_from_slice(a);
+                }
+                // no need for a mask if every pixel is opaque
+                if alpha.iter().any(|&a| a != 255) {
+                    dict.smask = Some(t!(soft_mask(width, height, &alpha, bpc, update)));
+                }
+                let filter = flate(colors);
+                Stream::from_compressed(dict, t!(encode(&color, &filter)), vec![filter])
+            }
+            t => bail!("PNG color type {}", t),
+        };
+        Ok(ImageXObject { inner: image })
+    }
+}
+
+/// A soft mask of `width` × `height` alpha values.
+fn soft_mask(width: u32, height: u32, alpha: &[u8], bpc: i32, update: &mut impl Updater) -> Result<Ref<Stream<ImageDict>>> {
+    let dict = ImageDict {
+        width,
+        height,
+        color_space: Some(ColorSpace::DeviceGray),
+        bits_per_component: Some(bpc),
+        ..Default::default()
+    };
+    let filter = StreamFilter::FlateDecode(LZWFlateParams::png(1, bpc, width as i32));
+    let mask = Stream::from_compressed(dict, t!(encode(alpha, &filter)), vec![filter]);
+    Ok(update.create(mask)?.get_ref())
+}
+
+/// What [`ImageXObject::from_jpeg`] needs to know about a JPEG file.
+struct JpegInfo {
+    width: u32,
+    height: u32,
+    components: u8,
+    bits: u8,
+    /// has an `Adobe` APP14 segment
+    adobe: bool,
+}
+
+fn jpeg_info(data: &[u8]) -> Result<JpegInfo> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        bail!("not a JPEG file");
+    }
+    let mut pos = 2;
+    let mut adobe = false;
+    loop {
+        // a marker may be preceded by any number of fill bytes
+        while data.get(pos .. pos + 2) == Some(&[0xFF, 0xFF]) {
+            pos += 1;
+        }
+        let (marker, len) = match data.get(pos .. pos + 4) {
+            Some(&[0xFF, marker, hi, lo]) => (marker, u16::from_be_bytes([hi, lo]) as usize),
+            _ => bail!("JPEG file without a frame header"),
+        };
+        let segment = match data.get(pos + 4 .. pos + 2 + len) {
+            Some(segment) if len >= 2 => segment,
+            _ => bail!("truncated JPEG segment"),
+        };
+        match marker {
+            0xEE if segment.starts_with(b"Adobe") => adobe = true,
+            // start of frame; C4, C8 and CC are other segments
+            0xC0 ..= 0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let (bits, height, width, components) = match *segment {
+                    [bits, h0, h1, w0, w1, components, ..] => (bits, u16::from_be_bytes([h0, h1]), u16::from_be_bytes([w0, w1]), components),
+                    _ => bail!("truncated JPEG frame header"),
+                };
+                if width == 0 || height == 0 {
+                    bail!("JPEG image without a size");
+                }
+                return Ok(JpegInfo { width: width as u32, height: height as u32, components, bits, adobe });
+            }
+            0xDA => bail!("JPEG file without a frame header"),
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+}
+
+/// The chunks of a PNG file that [`ImageXObject::from_png`] uses.
+struct Png {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    /// `PLTE`
+    palette: Vec<u8>,
+    /// `tRNS`
+    transparency: Vec<u8>,
+    /// the `IDAT` chunks joined, a zlib stream
+    data: Vec<u8>,
+}
+
+fn read_png(data: &[u8]) -> Result<Png> {
+    let mut rest = match data.strip_prefix(b"\x89PNG\r\n\x1a\n") {
+        Some(rest) => rest,
+        None => bail!("not a PNG file"),
+    };
+    let mut header = None;
+    let (mut palette, mut transparency, mut idat) = (vec![], vec![], vec![]);
+    // the checksums are not verified
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[.. 4].try_into().unwrap()) as usize;
+        let kind = &rest[4 .. 8];
+        let chunk = match rest.get(8 .. 8 + len) {
+            Some(chunk) if rest.len() >= len + 12 => chunk,
+            _ => bail!("truncated PNG chunk"),
+        };
+        match kind {
+            b"IHDR" => header = Some(chunk.to_vec()),
+            b"PLTE" => palette = chunk.to_vec(),
+            b"tRNS" => transparency = chunk.to_vec(),
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[len + 12 ..];
+    }
+    let (width, height, bit_depth, color_type, interlace) = match header.as_deref() {
+        Some(&[w0, w1, w2, w3, h0, h1, h2, h3, bit_depth, color_type, _, _, interlace]) =>
+            (u32::from_be_bytes([w0, w1, w2, w3]), u32::from_be_bytes([h0, h1, h2, h3]), bit_depth, color_type, interlace),
+        _ => bail!("PNG file without a valid IHDR chunk"),
+    };
+    if interlace != 0 {
+        bail!("interlaced PNG images are not supported");
+    }
+    let valid = match color_type {
+        0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid || width == 0 || height == 0 {
+        bail!("invalid PNG image: {}x{}, color type {}, bit depth {}", width, height, color_type, bit_depth);
+    }
+    Ok(Png { width, height, bit_depth, color_type, palette, transparency, data: idat })
 }
 
 #[derive(Object, Debug, DataSize, DeepClone, ObjectWrite)]
@@ -169,9 +697,8 @@ pub struct ImageDict {
     #[pdf(key = "ImageMask", default = "false")]
     pub image_mask: bool,
 
-    // Mask: stream or array
     #[pdf(key = "Mask")]
-    pub mask: Option<Primitive>,
+    pub mask: Option<Mask>,
     //
     /// Describes how to map image samples into the range of values appropriate for the image’s color space.
     /// If `image_mask`: either [0 1] or [1 0]. Else, the length must be twice the number of color
@@ -197,9 +724,66 @@ pub struct ImageDict {
     #[pdf(key = "SMask")]
     pub smask: Option<Ref<Stream<ImageDict>>>,
 
+    /// In a soft mask: the color, in the color space of the parent image, its colors have been
+    /// premultiplied with.
+    #[pdf(key = "Matte")]
+    pub matte: Option<Vec<f32>>,
+
     // OPI: dict
     // Metadata: stream
     // OC: dict
     #[pdf(other)]
     pub other: Dictionary,
 }
+
+/// The `Mask` entry of an image.
+#[derive(Debug, DataSize, Clone, DeepClone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Mask {
+    /// Pixels with every component in the range `[min max]` of the array, given for each
+    /// component in turn, are not painted.
+    ColorKey(Vec<u32>),
+    /// An image mask: pixels are only painted where it paints.
+    Stencil(Ref<Stream<ImageDict>>),
+}
+impl Object for Mask {
+    fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Reference(r) => Ok(Mask::Stencil(Ref::new(r))),
+            Primitive::Array(values) => Ok(Mask::ColorKey(values.iter()
+                .map(|v| Ok(v.as_number()?.max(0.).round() as u32))
+                .collect::<Result<_>>()?)),
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Reference or Array", found: p.get_debug_name() }),
+        }
+    }
+}
+impl ObjectWrite for Mask {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match *self {
+            Mask::ColorKey(ref ranges) => Ok(Primitive::Array(ranges.iter().map(|&v| Primitive::Integer(v as i32)).collect())),
+            Mask::Stencil(r) => r.to_primitive(update),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples() {
+        // two rows of three 2 bit samples, padded to a byte
+        let data = [0b00_01_10_00, 0b11_10_01_00];
+        assert_eq!(unpack_samples(&data, 3, 2, 1, 2).unwrap(), [0, 1, 2, 3, 2, 1]);
+        assert_eq!(expand_samples(&data, 3, 2, 1, 2, None).unwrap(), [0, 85, 170, 255, 170, 85]);
+        assert_eq!(expand_samples(&data, 3, 2, 1, 2, Some(&[1., 0.])).unwrap(), [255, 170, 85, 0, 85, 170]);
+        assert!(unpack_samples(&data, 4, 2, 1, 2).is_ok());
+        assert!(unpack_samples(&data, 5, 2, 1, 2).is_err());
+        assert!(unpack_samples(&data, 1, 1, 1, 3).is_err());
+
+        let data = [0x12, 0x34, 0xff, 0xff];
+        assert_eq!(unpack_samples(&data, 1, 1, 2, 16).unwrap(), [0x1234, 0xffff]);
+        assert_eq!(expand_samples(&data, 1, 1, 2, 16, Some(&[0., 1., 0., 0.5])).unwrap(), [18, 128]);
+        assert_eq!(decode_sample(15, 4, -1., 1.), 1.);
+    }
+}
@@ -81,15 +81,22 @@ impl<'a> StringLexer<'a> {
                         let mut char_code: u16 = 0;
 
                         // A character code must follow. 1-3 numbers.
-                        for _ in 0..3 {
+                        let mut digits = 0;
+                        while digits < 3 {
                             let c = self.peek_byte()?;
                             if (b'0'..=b'7').contains(&c) {
                                 self.next_byte()?;
                                 char_code = char_code * 8 + (c - b'0') as u16;
+                                digits += 1;
                             } else {
                                 break;
                             }
                         }
+                        if digits == 0 {
+                            // not an escape sequence, the backslash is ignored
+                            return self.next_lexeme();
+                        }
+                        // overflow of the high-order digit is ignored
                         Some(char_code as u8)
                     }
                 }
@@ -109,6 +116,13 @@ impl<'a> StringLexer<'a> {
                 }
             },
 
+            // an end-of-line marker in the string is read as a single line feed
+            b'\r' => {
+                if let Ok(b'\n') = self.peek_byte() {
+                    let _ = self.next_byte();
+                }
+                Ok(Some(b'\n'))
+            }
             c => Ok(Some(c))
 
         }
@@ -326,6 +340,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parentheses_and_line_ends() {
+        let lex = |data: &[u8]| -> Vec<u8> {
+            StringLexer::new(data).iter().map(Result::unwrap).collect()
+        };
+        // balanced parentheses need no escapes, unbalanced ones do
+        assert_eq!(lex(b"a (b (c)) d) e"), b"a (b (c)) d");
+        assert_eq!(lex(b"a \\( b) c"), b"a ( b");
+        assert_eq!(lex(b"a \\) b) c"), b"a ) b");
+        // an unknown escape is the character itself
+        assert_eq!(lex(b"\\q\\8)"), b"q8");
+        // raw end-of-line markers are line feeds
+        assert_eq!(lex(b"a\rb\r\nc\nd)"), b"a\nb\nc\nd");
+    }
+
     #[test]
     fn hex_test() {
         let input = b"901FA3>";
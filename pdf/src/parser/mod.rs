@@ -11,7 +11,7 @@ pub use self::parse_xref::*;
 use crate::error::*;
 use crate::primitive::StreamInner;
 use crate::primitive::{Primitive, Dictionary, PdfStream, PdfString};
-use crate::object::{ObjNr, GenNr, PlainRef, Resolve};
+use crate::object::{ObjNr, GenNr, PlainRef, Resolve, Warning};
 use crate::crypt::Decoder;
 use bitflags::bitflags;
 use istring::{SmallBytes, SmallString, IBytes};
@@ -99,14 +99,33 @@ fn parse_stream_object(dict: Dictionary, lexer: &mut Lexer, r: &impl Resolve, ct
         None => err!(PdfError::MissingEntry { typ: "<Stream>", field: "Length".into() }),
     };
 
+    let start = lexer.get_pos();
     let stream_substr = lexer.read_n(length);
-
-    if stream_substr.len() != length {
-        err!(PdfError::EOF)
-    }
-
-    // Finish
-    t!(lexer.next_expect("endstream"));
+    let end = match stream_substr.len() == length {
+        true => lexer.next_expect("endstream"),
+        false => Err(PdfError::EOF),
+    };
+    let stream_substr = match end {
+        Ok(()) => stream_substr,
+        Err(_) if r.options().lenient => {
+            // the data ends at `endstream`, minus the end of line in front of it
+            lexer.set_pos(start);
+            let rest = lexer.get_remaining_slice();
+            let end = match rest.windows(9).position(|w| w == b"endstream") {
+                Some(end) => end,
+                None => err!(PdfError::EOF),
+            };
+            let data = &rest[.. end];
+            let data = data.strip_suffix(b"\n").unwrap_or(data);
+            let data = data.strip_suffix(b"\r").unwrap_or(data);
+            let substr = lexer.read_n(data.len());
+            r.options().warn(Warning::StreamLength { pos: substr.file_range().start, length, actual: data.len() });
+            lexer.set_pos(start + end);
+            t!(lexer.next_expect("endstream"));
+            substr
+        }
+        Err(e) => err!(e),
+    };
 
     Ok(PdfStream {
         inner: StreamInner::InFile {
@@ -348,6 +367,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn escapes_round_trip() {
+        use crate::object::NoResolve;
+        use crate::primitive::{Primitive, PdfString, StringFormat, WriteOptions};
+        use super::ParseFlags;
+
+        let write = |p: &Primitive, options| {
+            let mut out = vec![];
+            p.serialize_with(&mut out, options).unwrap();
+            out
+        };
+        let name = Primitive::name("A B#(c)/d%ä");
+        let data = write(&name, WriteOptions::default());
+        assert_eq!(data, b"/A#20B#23#28c#29#2Fd#25#C3#A4");
+        assert_eq!(super::parse(&data, &NoResolve, ParseFlags::NAME).unwrap(), name);
+
+        let bytes: Vec<u8> = (0..0x80).chain(b"(()\\)\r\n7".iter().copied()).collect();
+        let string = Primitive::String(PdfString::new(bytes.as_slice().into()));
+        for strings in [StringFormat::Auto, StringFormat::Hex] {
+            let data = write(&string, WriteOptions { strings, ..Default::default() });
+            assert_eq!(data[0], if strings == StringFormat::Hex { b'<' } else { b'(' });
+            assert_eq!(super::parse(&data, &NoResolve, ParseFlags::STRING).unwrap(), string);
+        }
+    }
+
     #[test]
     fn dict_with_empty_name_as_key() {
         use crate::object::NoResolve;
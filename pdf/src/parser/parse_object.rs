@@ -25,10 +25,10 @@ pub fn parse_indirect_object(lexer: &mut Lexer, r: &impl Resolve, decoder: Optio
     };
     let obj = t!(parse_with_lexer_ctx(lexer, r, Some(&ctx), flags, MAX_DEPTH));
 
-    if r.options().allow_missing_endobj {
+    if r.options().allow_missing_endobj || r.options().lenient {
         let pos = lexer.get_pos();
-        if let Err(e) = lexer.next_expect("endobj") {
-            warn!("error parsing obj {} {}: {:?}", id.id, id.gen, e);
+        if lexer.next_expect("endobj").is_err() {
+            r.options().warn(Warning::MissingEndobj { obj: id });
             lexer.set_pos(pos);
         }
     } else {
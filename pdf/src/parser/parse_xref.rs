@@ -1,10 +1,11 @@
 use crate::error::*;
 use crate::parser::lexer::Lexer;
-use crate::xref::{XRef, XRefSection, XRefInfo};
+use crate::xref::{XRef, XRefSection, XRefInfo, XRefTable};
 use crate::primitive::{Primitive, Dictionary};
 use crate::object::*;
-use crate::parser::{parse_with_lexer, ParseFlags};
+use crate::parser::{parse_with_lexer, parse_dictionary_object, ParseFlags, MAX_DEPTH};
 use crate::parser::parse_object::{parse_indirect_stream};
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 
 // Just the part of Parser which reads xref sections from xref stream.
@@ -142,3 +143,115 @@ pub fn read_xref_and_trailer_at(lexer: &mut Lexer, resolve: &impl Resolve) -> Re
         parse_xref_stream_and_trailer(lexer, resolve)
     }
 }
+
+/// The result of [`rebuild_xref`].
+pub struct RebuiltXRef {
+    pub table: XRefTable,
+    /// The merged trailers and cross reference stream dictionaries, later ones taking precedence.
+    pub trailer: Dictionary,
+    /// The object streams found. The objects in them still have to be added to the table.
+    pub object_streams: Vec<ObjNr>,
+}
+
+/// Reconstruct the cross reference table of a damaged file by scanning `data` for `N G obj` headers
+/// and trailer dictionaries. Positions are relative to the start of `data`.
+///
+/// An object that occurs several times (like in incremental updates) is taken from the last occurrence.
+/// The data of streams is skipped. If no trailer names the catalog, the last object of type `Catalog` is used
+/// (the trailer has no `Root` if it is in an object stream).
+pub fn rebuild_xref(data: &[u8]) -> Result<RebuiltXRef> {
+    fn is_regular(b: u8) -> bool {
+        !matches!(b, 0 | b' ' | b'\r' | b'\n' | b'\t' | b'\x0c') && !b"()<>[]{}/%".contains(&b)
+    }
+    fn find(data: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+        data.get(from ..)?.windows(needle.len()).position(|w| w == needle).map(|i| from + i)
+    }
+    /// The number ending right before `end`, separated by whitespace, and where it starts.
+    fn number_before(data: &[u8], end: usize) -> Option<(u64, usize)> {
+        let last = data[.. end].iter().rposition(|b| !b" \r\n\t\x0c\0".contains(b))?;
+        let start = data[..= last].iter().rposition(|b| !b.is_ascii_digit()).map_or(0, |i| i + 1);
+        if last + 1 == end || start > last || (start > 0 && is_regular(data[start - 1])) {
+            return None;
+        }
+        let number = std::str::from_utf8(&data[start ..= last]).ok()?.parse().ok()?;
+        Some((number, start))
+    }
+    /// Copy the entries of a trailer (or cross reference stream dictionary) that describe the document.
+    fn merge(trailer: &mut Dictionary, dict: Dictionary) {
+        for (key, value) in dict.iter() {
+            if matches!(key.as_str(), "Root" | "Info" | "ID" | "Encrypt") {
+                trailer.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    /// The dictionary at `pos`, without the stream that may follow.
+    fn dict_at(data: &[u8], pos: usize) -> Option<Dictionary> {
+        let mut lexer = Lexer::new(data);
+        lexer.set_pos(pos);
+        if !lexer.next().ok()?.equals(b"<<") {
+            return None;
+        }
+        parse_dictionary_object(&mut lexer, &NoResolve, None, MAX_DEPTH).ok()
+    }
+
+    let mut objects: BTreeMap<ObjNr, (GenNr, usize)> = BTreeMap::new();
+    let mut trailer = Dictionary::new();
+    let mut catalog = None;
+    let mut object_streams = vec![];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        if !data[pos].is_ascii_alphabetic() || (pos > 0 && is_regular(data[pos - 1])) {
+            pos += 1;
+            continue;
+        }
+        let end = data[pos ..].iter().position(|&b| !is_regular(b)).map_or(data.len(), |i| pos + i);
+        match &data[pos .. end] {
+            b"obj" => {
+                let header = number_before(data, pos)
+                    .and_then(|(gen, start)| Some((gen, number_before(data, start)?)));
+                if let Some((gen, (id, start))) = header {
+                    objects.insert(id, (gen, start));
+                    if let Some(dict) = dict_at(data, end) {
+                        match dict.get("Type").and_then(|t| t.as_name().ok()) {
+                            Some("XRef") => merge(&mut trailer, dict),
+                            Some("ObjStm") => object_streams.push(id),
+                            Some("Catalog") => catalog = Some(PlainRef { id, gen }),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            b"stream" => {
+                // the data may contain anything, including `obj`
+                if let Some(next) = find(data, end, b"endstream") {
+                    pos = next + b"endstream".len();
+                    continue;
+                }
+            }
+            b"trailer" => {
+                if let Some(dict) = dict_at(data, end) {
+                    merge(&mut trailer, dict);
+                }
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+
+    if objects.is_empty() {
+        bail!("no objects found");
+    }
+    if let (None, Some(catalog)) = (trailer.get("Root"), catalog) {
+        trailer.insert("Root", Primitive::Reference(catalog));
+    }
+    let size = objects.keys().next_back().map_or(0, |&id| id + 1);
+    trailer.insert("Size", Primitive::Integer(size as i32));
+    let mut table = XRefTable::new(size);
+    for (&id, &(gen_nr, pos)) in &objects {
+        table.set(id, XRef::Raw { pos, gen_nr });
+    }
+    object_streams.sort_unstable();
+    object_streams.dedup();
+    Ok(RebuiltXRef { table, trailer, object_streams })
+}
@@ -1,6 +1,7 @@
+use crate::encoding::{char_to_pdf_doc, pdf_doc_to_char};
 use crate::error::*;
 use crate::object::{
-    Cloner, DeepClone, NoResolve, Object, ObjectWrite, PlainRef, Resolve, Updater,
+    Cloner, DeepClone, NoResolve, Object, ObjectWrite, PlainRef, Resolve, Updater, Warning,
 };
 
 use datasize::DataSize;
@@ -8,6 +9,8 @@ use indexmap::IndexMap;
 use istring::{IBytes, SmallString};
 use itertools::Itertools;
 use std::borrow::{Borrow, Cow};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::convert::TryInto;
 use std::ops::Deref;
 use std::ops::{Index, Range};
@@ -43,6 +46,49 @@ impl DataSize for Primitive {
     }
 }
 
+// consistent with `==`: dictionaries are equal whatever the order of their keys
+impl Hash for Primitive {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Primitive::Null => {}
+            Primitive::Integer(i) => i.hash(state),
+            // 0.0 == -0.0
+            Primitive::Number(n) => (n + 0.0).to_bits().hash(state),
+            Primitive::Boolean(b) => b.hash(state),
+            Primitive::String(ref s) => s.hash(state),
+            Primitive::Stream(ref s) => s.hash(state),
+            Primitive::Dictionary(ref d) => d.hash(state),
+            Primitive::Array(ref a) => a.hash(state),
+            Primitive::Reference(r) => r.hash(state),
+            Primitive::Name(ref n) => n.hash(state),
+        }
+    }
+}
+
+/// How [`Primitive::eq_with`] compares numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberTolerance {
+    /// whether an integer equals a real number with the same value, like `1` and `1.0`
+    pub mixed: bool,
+    /// how much two numbers may differ, relative to the larger one (or to 1 for smaller numbers)
+    pub epsilon: f32,
+}
+impl NumberTolerance {
+    /// The same as `==`.
+    pub const EXACT: NumberTolerance = NumberTolerance { mixed: false, epsilon: 0.0 };
+
+    fn same(&self, a: f32, b: f32) -> bool {
+        a == b || (a - b).abs() <= self.epsilon * a.abs().max(b.abs()).max(1.0)
+    }
+}
+impl Default for NumberTolerance {
+    /// Integers and reals are mixed, and numbers that were rounded when written are equal.
+    fn default() -> Self {
+        NumberTolerance { mixed: true, epsilon: 1e-5 }
+    }
+}
+
 impl fmt::Display for Primitive {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -60,16 +106,40 @@ impl fmt::Display for Primitive {
     }
 }
 impl Primitive {
+    /// Compare with `other` like `==`, with the numbers compared according to `tolerance`.
+    ///
+    /// Typed objects can be compared by their [`ObjectWrite::to_primitive`].
+    pub fn eq_with(&self, other: &Primitive, tolerance: NumberTolerance) -> bool {
+        match (self, other) {
+            // integers are written as they are, so there is no rounding to allow for
+            (&Primitive::Integer(a), &Primitive::Integer(b)) => a == b,
+            (&Primitive::Number(a), &Primitive::Number(b)) => tolerance.same(a, b),
+            (&Primitive::Integer(a), &Primitive::Number(b)) | (&Primitive::Number(b), &Primitive::Integer(a)) => {
+                tolerance.mixed && tolerance.same(a as f32, b)
+            }
+            (Primitive::Array(a), Primitive::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.eq_with(b, tolerance))
+            }
+            (Primitive::Dictionary(a), Primitive::Dictionary(b)) => a.eq_with(b, tolerance),
+            (Primitive::Stream(a), Primitive::Stream(b)) => a.inner == b.inner && a.info.eq_with(&b.info, tolerance),
+            (a, b) => a == b,
+        }
+    }
+
     pub fn serialize(&self, out: &mut impl io::Write) -> Result<()> {
+        self.serialize_with(out, WriteOptions::default())
+    }
+    /// Like [`serialize`](Primitive::serialize), writing numbers and strings as `options` say.
+    pub fn serialize_with(&self, out: &mut impl io::Write, options: WriteOptions) -> Result<()> {
         match self {
             Primitive::Null => write!(out, "null")?,
             Primitive::Integer(i) => write!(out, "{}", i)?,
-            Primitive::Number(n) => write!(out, "{}", n)?,
+            Primitive::Number(n) => write!(out, "{}", options.numbers.display(*n))?,
             Primitive::Boolean(b) => write!(out, "{}", b)?,
-            Primitive::String(ref s) => s.serialize(out)?,
-            Primitive::Stream(ref s) => s.serialize(out)?,
-            Primitive::Dictionary(ref d) => d.serialize(out)?,
-            Primitive::Array(ref arr) => serialize_list(arr, out)?,
+            Primitive::String(ref s) => s.serialize_with(out, options.strings)?,
+            Primitive::Stream(ref s) => s.serialize_with(out, options)?,
+            Primitive::Dictionary(ref d) => d.serialize(out, options)?,
+            Primitive::Array(ref arr) => serialize_list(arr, out, options)?,
             Primitive::Reference(r) => write!(out, "{} {} R", r.id, r.gen)?,
             Primitive::Name(ref s) => serialize_name(s, out)?,
         }
@@ -91,34 +161,104 @@ impl Primitive {
     }
 }
 
-fn serialize_list(arr: &[Primitive], out: &mut impl io::Write) -> Result<()> {
+/// How real numbers are written, see [`Primitive::serialize_with`] and
+/// [`serialize_ops_with`](crate::content::serialize_ops_with).
+///
+/// Fewer digits make smaller files, at the cost of reading back slightly different numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Digits after the decimal point. `None` writes the shortest digits that read back as the same `f32`.
+    pub precision: Option<u8>,
+    /// Remove zeros at the end of the fraction, and the decimal point if no digits are left after it.
+    pub trim_zeros: bool,
+}
+impl NumberFormat {
+    /// Read back exactly, the default.
+    pub const EXACT: NumberFormat = NumberFormat { precision: None, trim_zeros: true };
+
+    /// Round to `precision` digits after the decimal point and remove trailing zeros.
+    pub const fn precision(precision: u8) -> NumberFormat {
+        NumberFormat { precision: Some(precision), trim_zeros: true }
+    }
+    /// Write `n` with this format, exponents are never used.
+    pub fn display(self, n: f32) -> impl fmt::Display {
+        FormattedNumber { n, format: self }
+    }
+}
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat::EXACT
+    }
+}
+
+/// How strings are written, see [`PdfString::serialize_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringFormat {
+    /// Literal strings, or hexadecimal strings for those with bytes outside of ASCII.
+    #[default]
+    Auto,
+    /// Always hexadecimal strings, like for binary data and signatures.
+    Hex,
+}
+
+/// How primitives are written, see [`Primitive::serialize_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteOptions {
+    pub numbers: NumberFormat,
+    pub strings: StringFormat,
+}
+
+struct FormattedNumber {
+    n: f32,
+    format: NumberFormat,
+}
+impl fmt::Display for FormattedNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = match self.format.precision {
+            Some(precision) => precision as usize,
+            None => return write!(f, "{}", self.n),
+        };
+        let s = format!("{:.*}", precision, self.n);
+        let mut s = s.as_str();
+        if self.format.trim_zeros && s.contains('.') {
+            s = s.trim_end_matches('0').trim_end_matches('.');
+        }
+        // small negative numbers are rounded to -0
+        if s.starts_with('-') && s.bytes().all(|b| matches!(b, b'-' | b'0' | b'.')) {
+            s = &s[1..];
+        }
+        f.write_str(s)
+    }
+}
+
+fn serialize_list(arr: &[Primitive], out: &mut impl io::Write, options: WriteOptions) -> Result<()> {
     let mut parts = arr.iter();
     write!(out, "[")?;
     if let Some(first) = parts.next() {
-        first.serialize(out)?;
+        first.serialize_with(out, options)?;
     }
     for p in parts {
         write!(out, " ")?;
-        p.serialize(out)?;
+        p.serialize_with(out, options)?;
     }
     write!(out, "]")?;
     Ok(())
 }
 
+/// Write `/` and the name, with `#` escapes for delimiters, white space and bytes outside of ASCII.
 pub fn serialize_name(s: &str, out: &mut impl io::Write) -> Result<()> {
     write!(out, "/")?;
-    for b in s.chars() {
+    for &b in s.as_bytes() {
         match b {
-            '\\' | '(' | ')' => write!(out, r"\")?,
-            c if c > '~' => panic!("only ASCII"),
-            _ => (),
+            b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' | b'#' => write!(out, "#{:02X}", b)?,
+            0x21 ..= 0x7e => out.write_all(&[b])?,
+            _ => write!(out, "#{:02X}", b)?,
         }
-        write!(out, "{}", b)?;
     }
     Ok(())
 }
 
-/// Primitive Dictionary type.
+/// Primitive Dictionary type. Equality and hashing do not depend on the order of the keys.
 #[derive(Default, Clone, PartialEq)]
 pub struct Dictionary {
     dict: IndexMap<Name, Primitive>,
@@ -138,15 +278,28 @@ impl Dictionary {
     pub fn get(&self, key: &str) -> Option<&Primitive> {
         self.dict.get(key)
     }
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Primitive> {
+        self.dict.get_mut(key)
+    }
     pub fn insert(&mut self, key: impl Into<Name>, val: impl Into<Primitive>) -> Option<Primitive> {
         self.dict.insert(key.into(), val.into())
     }
     pub fn iter(&self) -> impl Iterator<Item = (&Name, &Primitive)> {
         self.dict.iter()
     }
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Primitive> {
+        self.dict.values_mut()
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Name, &mut Primitive)> {
+        self.dict.iter_mut()
+    }
     pub fn remove(&mut self, key: &str) -> Option<Primitive> {
         self.dict.swap_remove(key)
     }
+    /// Like [`remove`](Self::remove), but keeps the order of the other keys.
+    pub fn shift_remove(&mut self, key: &str) -> Option<Primitive> {
+        self.dict.shift_remove(key)
+    }
     /// like remove, but takes the name of the calling type and returns `PdfError::MissingEntry` if the entry is not found
     pub fn require(&mut self, typ: &'static str, key: &str) -> Result<Primitive> {
         self.remove(key).ok_or(PdfError::MissingEntry {
@@ -181,6 +334,24 @@ impl Dictionary {
         self.dict.extend(other.dict);
     }
 }
+// independent of the order of the keys, like `==`
+impl Hash for Dictionary {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let entries = self.iter().fold(0u64, |sum, entry| {
+            let mut hasher = DefaultHasher::new();
+            entry.hash(&mut hasher);
+            sum.wrapping_add(hasher.finish())
+        });
+        self.len().hash(state);
+        entries.hash(state);
+    }
+}
+impl Dictionary {
+    /// Compare with `other` like `==`, with the numbers compared according to `tolerance`.
+    pub fn eq_with(&self, other: &Dictionary, tolerance: NumberTolerance) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k).is_some_and(|w| v.eq_with(w, tolerance)))
+    }
+}
 impl DataSize for Dictionary {
     const IS_DYNAMIC: bool = true;
     const STATIC_HEAP_SIZE: usize = std::mem::size_of::<Self>();
@@ -213,11 +384,12 @@ impl Deref for Dictionary {
     }
 }
 impl Dictionary {
-    fn serialize(&self, out: &mut impl io::Write) -> Result<()> {
+    fn serialize(&self, out: &mut impl io::Write, options: WriteOptions) -> Result<()> {
         writeln!(out, "<<")?;
         for (key, val) in self.iter() {
-            write!(out, "{} ", key)?;
-            val.serialize(out)?;
+            serialize_name(key, out)?;
+            write!(out, " ")?;
+            val.serialize_with(out, options)?;
             writeln!(out)?;
         }
         writeln!(out, ">>")?;
@@ -265,13 +437,13 @@ impl<'a> IntoIterator for &'a Dictionary {
 }
 
 /// Primitive Stream (as opposed to the higher-level `Stream`)
-#[derive(Clone, Debug, PartialEq, DataSize)]
+#[derive(Clone, Debug, PartialEq, Hash, DataSize)]
 pub struct PdfStream {
     pub info: Dictionary,
     pub(crate) inner: StreamInner,
 }
 
-#[derive(Clone, Debug, PartialEq, DataSize)]
+#[derive(Clone, Debug, PartialEq, Hash, DataSize)]
 pub enum StreamInner {
     InFile {
         id: PlainRef,
@@ -303,13 +475,16 @@ impl ObjectWrite for PdfStream {
 }
 impl PdfStream {
     pub fn serialize(&self, out: &mut impl io::Write) -> Result<()> {
-
+        self.serialize_with(out, WriteOptions::default())
+    }
+    /// Like [`serialize`](PdfStream::serialize), writing the dictionary as `options` say.
+    pub fn serialize_with(&self, out: &mut impl io::Write, options: WriteOptions) -> Result<()> {
         match self.inner {
             StreamInner::InFile { id, .. } => {
                 Primitive::Reference(id).serialize(out)?;
             }
             StreamInner::Pending { ref data } => {
-                self.info.serialize(out)?;
+                self.info.serialize(out, options)?;
                 writeln!(out, "stream")?;
                 out.write_all(data)?;
                 writeln!(out, "\nendstream")?;
@@ -452,7 +627,14 @@ impl ObjectWrite for PdfString {
 
 impl PdfString {
     pub fn serialize(&self, out: &mut impl io::Write) -> Result<()> {
-        if self.data.iter().any(|&b| b >= 0x80) {
+        self.serialize_with(out, StringFormat::Auto)
+    }
+    /// Write the string as a literal or hexadecimal string, see [`StringFormat`].
+    ///
+    /// Literal strings escape backslashes, all parentheses, and control characters,
+    /// which readers would otherwise change (like a carriage return into a line feed).
+    pub fn serialize_with(&self, out: &mut impl io::Write, format: StringFormat) -> Result<()> {
+        if format == StringFormat::Hex || self.data.iter().any(|&b| b >= 0x80) {
             write!(out, "<")?;
             for &b in self.data.as_slice() {
                 write!(out, "{:02x}", b)?;
@@ -462,10 +644,16 @@ impl PdfString {
             write!(out, r"(")?;
             for &b in self.data.as_slice() {
                 match b {
-                    b'\\' | b'(' | b')' => write!(out, r"\")?,
-                    _ => (),
+                    b'\\' | b'(' | b')' => out.write_all(&[b'\\', b])?,
+                    b'\n' => out.write_all(br"\n")?,
+                    b'\r' => out.write_all(br"\r")?,
+                    b'\t' => out.write_all(br"\t")?,
+                    0x08 => out.write_all(br"\b")?,
+                    0x0c => out.write_all(br"\f")?,
+                    // always three digits, so a digit that follows is not taken as part of it
+                    0 ..= 0x1f | 0x7f => write!(out, "\\{:03o}", b)?,
+                    _ => out.write_all(&[b])?,
                 }
-                out.write_all(&[b])?;
             }
             write!(out, r")")?;
         }
@@ -488,30 +676,49 @@ impl PdfString {
     pub fn into_bytes(self) -> IBytes {
         self.data
     }
-    /// without encoding information the PdfString cannot be decoded into a String
-    /// therefore only lossy decoding is possible replacing unknown characters.
-    /// For decoding correctly see
-    /// pdf_tools/src/lib.rs
+    /// Decode a text string, replacing invalid characters.
+    ///
+    /// Text strings start with a byte order mark for UTF-16BE or (since PDF 2.0) UTF-8, and are
+    /// in PDFDocEncoding otherwise. Strings without a byte order mark that are valid UTF-8 are
+    /// read as UTF-8, as many writers produce them.
     pub fn to_string_lossy(&self) -> String {
-        if self.data.starts_with(&[0xfe, 0xff]) {
-            crate::font::utf16be_to_string_lossy(&self.data[2..])
+        if let Some(data) = self.data.strip_prefix(&[0xfe, 0xff]) {
+            crate::font::utf16be_to_string_lossy(data)
+        } else if let Some(data) = self.data.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+            String::from_utf8_lossy(data).into()
+        } else if let Ok(s) = str::from_utf8(&self.data) {
+            s.into()
         } else {
-            String::from_utf8_lossy(&self.data).into()
+            self.data.iter().map(|&b| pdf_doc_to_char(b).unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
         }
     }
-    /// without encoding information the PdfString cannot be sensibly decoded into a String
-    /// converts to a Rust String but only works for valid UTF-8, UTF-16BE and ASCII characters
-    /// if invalid bytes found an Error is returned
+    /// Decode a text string like [`PdfString::to_string_lossy`], but fail on invalid characters.
     pub fn to_string(&self) -> Result<String> {
-        if self.data.starts_with(&[0xfe, 0xff]) {
-            Ok(String::from(
-                std::str::from_utf8(crate::font::utf16be_to_string(&self.data[2..])?.as_bytes())
-                    .map_err(|_| PdfError::Utf8Decode)?,
-            ))
+        if let Some(data) = self.data.strip_prefix(&[0xfe, 0xff]) {
+            Ok(crate::font::utf16be_to_string(data)?.into())
+        } else if let Some(data) = self.data.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+            Ok(str::from_utf8(data).map_err(|_| PdfError::Utf8Decode)?.into())
+        } else if let Ok(s) = str::from_utf8(&self.data) {
+            Ok(s.into())
         } else {
-            Ok(String::from(
-                std::str::from_utf8(&self.data).map_err(|_| PdfError::Utf8Decode)?,
-            ))
+            self.data.iter()
+                .map(|&b| pdf_doc_to_char(b).ok_or_else(|| other!("byte {:#04x} is undefined in PDFDocEncoding", b)))
+                .collect()
+        }
+    }
+    /// A text string for `s`, in PDFDocEncoding if possible and UTF-16BE otherwise.
+    pub fn from_text(s: &str) -> PdfString {
+        let pdf_doc: Option<Vec<u8>> = s.chars().map(char_to_pdf_doc).collect();
+        match pdf_doc {
+            // would be read as UTF-8 or with a byte order mark
+            Some(data) if data.is_ascii() || (str::from_utf8(&data).is_err() && !data.starts_with(&[0xfe, 0xff]) && !data.starts_with(&[0xef, 0xbb, 0xbf])) => {
+                PdfString { data: data.into() }
+            }
+            _ => {
+                let mut data = vec![0xfe, 0xff];
+                data.extend(s.encode_utf16().flat_map(u16::to_be_bytes));
+                PdfString { data: data.into() }
+            }
         }
     }
 }
@@ -605,6 +812,12 @@ impl Primitive {
             p => unexpected_primitive!(Array, p.get_debug_name()),
         }
     }
+    pub fn as_dictionary(&self) -> Result<&Dictionary> {
+        match self {
+            Primitive::Dictionary(ref dict) => Ok(dict),
+            p => unexpected_primitive!(Dictionary, p.get_debug_name()),
+        }
+    }
     pub fn into_reference(self) -> Result<PlainRef> {
         match self {
             Primitive::Reference(id) => Ok(id),
@@ -767,11 +980,14 @@ impl<'a> TryInto<String> for &'a Primitive {
     }
 }
 
-fn parse_or<T: str::FromStr + Clone>(buffer: &str, range: Range<usize>, default: T) -> T {
-    buffer
-        .get(range)
-        .map(|s| str::parse::<T>(s).unwrap_or_else(|_| default.clone()))
-        .unwrap_or(default)
+/// Read `n` ASCII digits at `pos`.
+fn digits(bytes: &[u8], pos: &mut usize, n: usize) -> Option<u32> {
+    let d = bytes.get(*pos .. *pos + n)?;
+    if !d.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    *pos += n;
+    Some(d.iter().fold(0, |acc, &b| acc * 10 + (b - b'0') as u32))
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -795,52 +1011,123 @@ pub enum TimeRel {
 }
 datasize::non_dynamic_const_heap_size!(Date, std::mem::size_of::<Date>());
 
+impl Date {
+    /// The current time, in UTC.
+    pub fn now() -> Date {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Date::from_unix(secs)
+    }
+    /// Convert seconds since the unix epoch to a UTC date.
+    pub fn from_unix(secs: u64) -> Date {
+        let days = (secs / 86400) as i64;
+        let time = secs % 86400;
+
+        // civil_from_days, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            rel: TimeRel::Universal,
+            tz_hour: 0,
+            tz_minute: 0,
+        }
+    }
+    /// Parse a date string like `D:199812231952-08'00`.
+    ///
+    /// Everything after the year is optional, and so is the `D:` prefix. The time zone may
+    /// separate hours and minutes by `'`, `:` or nothing, and anything after it is ignored.
+    pub fn parse(s: &str) -> Result<Date> {
+        let s = s.trim();
+        let s = s.strip_prefix("D:").unwrap_or(s);
+        let bytes = s.as_bytes();
+        let mut pos = 0;
+        let year = match digits(bytes, &mut pos, 4) {
+            Some(year) => year as u16,
+            None => bail!("Missing obligatory year in date"),
+        };
+        let mut fields = [1, 1, 0, 0, 0];
+        for field in fields.iter_mut() {
+            match digits(bytes, &mut pos, 2) {
+                Some(n) => *field = n as u8,
+                None => break,
+            }
+        }
+        let [month, day, hour, minute, second] = fields;
+
+        let rest = &s[pos..];
+        let rel = match rest.chars().next() {
+            Some('+') => TimeRel::Later,
+            Some('-' | '\u{2212}') => TimeRel::Earlier,
+            _ => TimeRel::Universal,
+        };
+        let (mut tz_hour, mut tz_minute) = (0, 0);
+        if rel != TimeRel::Universal {
+            let zone = &rest.as_bytes()[rest.chars().next().map_or(0, char::len_utf8)..];
+            let mut pos = 0;
+            tz_hour = digits(zone, &mut pos, 2).unwrap_or(0) as u8;
+            if matches!(zone.get(pos), Some(b'\'' | b':')) {
+                pos += 1;
+            }
+            tz_minute = digits(zone, &mut pos, 2).unwrap_or(0) as u8;
+        }
+
+        let date = Date { year, month, day, hour, minute, second, rel, tz_hour, tz_minute };
+        if !date.is_valid() {
+            bail!("invalid date {:?}", s);
+        }
+        Ok(date)
+    }
+    /// Whether all fields are in range. The number of days in the month is not checked.
+    pub fn is_valid(&self) -> bool {
+        self.year <= 9999
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour <= 23
+            && self.minute <= 59
+            && self.second <= 59
+            && self.tz_hour <= 23
+            && self.tz_minute <= 59
+    }
+    /// Format as used by XMP (ISO 8601), like `2024-03-01T12:30:00Z`.
+    pub fn to_xmp(&self) -> String {
+        let Date { year, month, day, hour, minute, second, rel, tz_hour, tz_minute } = *self;
+        let zone = match rel {
+            TimeRel::Universal => "Z".to_string(),
+            TimeRel::Earlier => format!("-{tz_hour:02}:{tz_minute:02}"),
+            TimeRel::Later => format!("+{tz_hour:02}:{tz_minute:02}"),
+        };
+        format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}{zone}")
+    }
+}
+
 impl Object for Date {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         match p.resolve(r)? {
-            Primitive::String(PdfString { data }) => {
-                let s = str::from_utf8(&data)?;
-                if s.starts_with("D:") {
-                    let year = match s.get(2..6) {
-                        Some(year) => str::parse::<u16>(year)?,
-                        None => bail!("Missing obligatory year in date"),
-                    };
-
-                    let (time, rel, zone) = match s.find(['+', '-', 'Z']) {
-                        Some(p) => {
-                            let rel = match &s[p..p + 1] {
-                                "-" => TimeRel::Earlier,
-                                "+" => TimeRel::Later,
-                                "Z" => TimeRel::Universal,
-                                _ => unreachable!(),
-                            };
-                            (&s[..p], rel, &s[p + 1..])
-                        }
-                        None => (s, TimeRel::Universal, ""),
-                    };
-
-                    let month = parse_or(time, 6..8, 1);
-                    let day = parse_or(time, 8..10, 1);
-                    let hour = parse_or(time, 10..12, 0);
-                    let minute = parse_or(time, 12..14, 0);
-                    let second = parse_or(time, 14..16, 0);
-                    let tz_hour = parse_or(zone, 0..2, 0);
-                    let tz_minute = parse_or(zone, 3..5, 0);
-
-                    Ok(Date {
-                        year,
-                        month,
-                        day,
-                        hour,
-                        minute,
-                        second,
-                        tz_hour,
-                        tz_minute,
-                        rel,
-                    })
-                } else {
-                    bail!("Failed parsing date");
+            Primitive::String(string) => {
+                let s = match r.options().lenient {
+                    true => string.to_string_lossy(),
+                    false => string.to_string()?,
+                };
+                if r.options().lenient && !s.starts_with("D:") {
+                    r.options().warn(Warning::MalformedDate { date: s.clone() });
                 }
+                Date::parse(&s)
             }
             p => unexpected_primitive!(String, p.get_debug_name()),
         }
@@ -849,35 +1136,118 @@ impl Object for Date {
 
 impl ObjectWrite for Date {
     fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
-        let Date {
-            year,
-            month,
-            day,
-            hour,
-            minute,
-            second,
-            tz_hour,
-            tz_minute,
-            rel,
-        } = *self;
-        if year > 9999
-            || day > 99
-            || hour > 23
-            || minute >= 60
-            || second >= 60
-            || tz_hour >= 24
-            || tz_minute >= 60
-        {
-            bail!("not a valid date");
-        }
-        let o = match rel {
-            TimeRel::Earlier => "-",
-            TimeRel::Later => "+",
-            TimeRel::Universal => "Z",
+        if !self.is_valid() {
+            bail!("not a valid date: {:?}", self);
+        }
+        Ok(Primitive::String(PdfString { data: self.to_string().into() }))
+    }
+}
+
+/// The PDF form, like `D:199812231952-08'00`.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Date { year, month, day, hour, minute, second, rel, tz_hour, tz_minute } = *self;
+        write!(f, "D:{year:04}{month:02}{day:02}{hour:02}{minute:02}{second:02}")?;
+        match rel {
+            TimeRel::Universal => write!(f, "Z"),
+            TimeRel::Earlier => write!(f, "-{tz_hour:02}'{tz_minute:02}"),
+            TimeRel::Later => write!(f, "+{tz_hour:02}'{tz_minute:02}"),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Date> for time::OffsetDateTime {
+    type Error = PdfError;
+    fn try_from(date: Date) -> Result<Self> {
+        let offset = |h: u8, m: u8| match date.rel {
+            TimeRel::Earlier => time::UtcOffset::from_hms(-(h as i8), -(m as i8), 0),
+            _ => time::UtcOffset::from_hms(h as i8, m as i8, 0),
         };
+        let month = time::Month::try_from(date.month).map_err(|e| other!("{}", e))?;
+        let day = time::Date::from_calendar_date(date.year as i32, month, date.day).map_err(|e| other!("{}", e))?;
+        let time = time::Time::from_hms(date.hour, date.minute, date.second).map_err(|e| other!("{}", e))?;
+        let offset = offset(date.tz_hour, date.tz_minute).map_err(|e| other!("{}", e))?;
+        Ok(time::PrimitiveDateTime::new(day, time).assume_offset(offset))
+    }
+}
 
-        let s = format!("D:{year:04}{month:02}{day:02}{hour:02}{minute:02}{second:02}{o}{tz_hour:02}'{tz_minute:02}");
-        Ok(Primitive::String(PdfString { data: s.into() }))
+/// The date is rounded down to whole seconds. Years outside of 0..=9999 can't be written.
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Date {
+    fn from(t: time::OffsetDateTime) -> Date {
+        let (tz_hour, tz_minute, _) = t.offset().as_hms();
+        let rel = match t.offset() {
+            o if o.is_utc() => TimeRel::Universal,
+            o if o.is_negative() => TimeRel::Earlier,
+            _ => TimeRel::Later,
+        };
+        Date {
+            year: t.year().clamp(0, u16::MAX as i32) as u16,
+            month: t.month() as u8,
+            day: t.day(),
+            hour: t.hour(),
+            minute: t.minute(),
+            second: t.second(),
+            rel,
+            tz_hour: tz_hour.unsigned_abs(),
+            tz_minute: tz_minute.unsigned_abs(),
+        }
+    }
+}
+
+/// Names and strings become strings, references `"5 0 R"`, and streams their dictionary.
+#[cfg(feature = "serde")]
+mod ser {
+    use super::*;
+    use serde::ser::{Serialize, SerializeMap, SerializeStruct, Serializer};
+
+    impl Serialize for Primitive {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            match *self {
+                Primitive::Null => serializer.serialize_unit(),
+                Primitive::Integer(i) => serializer.serialize_i32(i),
+                Primitive::Number(n) => serializer.serialize_f32(n),
+                Primitive::Boolean(b) => serializer.serialize_bool(b),
+                Primitive::String(ref s) => Serialize::serialize(s, serializer),
+                Primitive::Stream(ref s) => Serialize::serialize(s, serializer),
+                Primitive::Dictionary(ref d) => Serialize::serialize(d, serializer),
+                Primitive::Array(ref parts) => Serialize::serialize(parts, serializer),
+                Primitive::Reference(ref r) => Serialize::serialize(r, serializer),
+                Primitive::Name(ref name) => serializer.serialize_str(name),
+            }
+        }
+    }
+    impl Serialize for Dictionary {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.len()))?;
+            for (key, val) in self.iter() {
+                map.serialize_entry(key.as_str(), val)?;
+            }
+            map.end()
+        }
+    }
+    impl Serialize for PdfStream {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("PdfStream", 1)?;
+            s.serialize_field("info", &self.info)?;
+            s.end()
+        }
+    }
+    impl Serialize for Name {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+    impl Serialize for PdfString {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.to_string_lossy())
+        }
+    }
+    impl Serialize for Date {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
     }
 }
 
@@ -889,6 +1259,28 @@ mod tests {
     };
 
     use super::Date;
+    #[test]
+    fn number_format() {
+        use super::{NumberFormat, Primitive, WriteOptions};
+
+        let write = |p: Primitive, format| {
+            let mut out = vec![];
+            p.serialize_with(&mut out, WriteOptions { numbers: format, ..Default::default() }).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let array = Primitive::Array(vec![
+            Primitive::Number(0.1),
+            Primitive::Number(2.5),
+            Primitive::Number(-0.0001),
+            Primitive::Number(1234.5),
+            Primitive::Integer(3),
+        ]);
+        assert_eq!(write(array.clone(), NumberFormat::EXACT), "[0.1 2.5 -0.0001 1234.5 3]");
+        assert_eq!(write(array.clone(), NumberFormat::precision(2)), "[0.1 2.5 0 1234.5 3]");
+        let fixed = NumberFormat { precision: Some(2), trim_zeros: false };
+        assert_eq!(write(array, fixed), "[0.10 2.50 0.00 1234.50 3]");
+    }
+
     #[test]
     fn utf16be_string() {
         let s = PdfString::new([0xfe, 0xff, 0x20, 0x09].as_slice().into());
@@ -922,13 +1314,25 @@ mod tests {
         let s = PdfString::new([b'm', b'i', b't', 0xc3, 0xa4 /*ä*/].as_slice().into());
         assert_eq!(s.to_string_lossy(), "mitä");
         assert_eq!(s.to_string().unwrap(), "mitä");
-        // verify valid ISO-8859-1 bytestream with umlaut fails
-        let s = PdfString::new([b'm', b'i', b't', 0xe4 /*ä in latin1*/].as_slice().into());
-        let repl_ch = ['m', 'i', 't', std::char::REPLACEMENT_CHARACTER]
-            .iter()
-            .collect::<String>();
-        assert_eq!(s.to_string_lossy(), repl_ch);
-        assert!(s.to_string().is_err()); // FIXME verify it is a PdfError::Utf16Decode
+        // verify PDFDocEncoding is used for anything else
+        let s = PdfString::new([b'm', b'i', b't', 0xe4 /*ä*/, 0x93 /*ﬁ*/].as_slice().into());
+        assert_eq!(s.to_string_lossy(), "mitäﬁ");
+        assert_eq!(s.to_string().unwrap(), "mitäﬁ");
+        let s = PdfString::new([b'm', b'i', b't', 0xe4, 0x9f /*undefined*/].as_slice().into());
+        assert_eq!(s.to_string_lossy(), "mitä\u{fffd}");
+        assert!(s.to_string().is_err());
+        // UTF-8 with a byte order mark
+        let s = PdfString::new([0xef, 0xbb, 0xbf, b'm', 0xc3, 0xa4].as_slice().into());
+        assert_eq!(s.to_string().unwrap(), "mä");
+    }
+
+    #[test]
+    fn pdfstring_from_text() {
+        for (text, len) in [("plain", 5), ("Grüße – €5", 10), ("Ã¤", 6), ("日本", 6)] {
+            let s = PdfString::from_text(text);
+            assert_eq!(s.as_bytes().len(), len, "{}", text);
+            assert_eq!(s.to_string().unwrap(), text);
+        }
     }
 
     #[test]
@@ -949,4 +1353,77 @@ mod tests {
         };
         assert_eq!(d.unwrap(), d2);
     }
+
+    #[test]
+    fn date_variants() {
+        let parse = |s: &str| Date::parse(s).map(|d| d.to_string()).map_err(|_| s.to_owned());
+        assert_eq!(parse("D:1998"), Ok("D:19980101000000Z".into()));
+        assert_eq!(parse("D:19981223"), Ok("D:19981223000000Z".into()));
+        assert_eq!(parse("D:19981223195207Z"), Ok("D:19981223195207Z".into()));
+        assert_eq!(parse("D:19981223195207Z00'00'"), Ok("D:19981223195207Z".into()));
+        assert_eq!(parse("D:19981223195207+05'30'"), Ok("D:19981223195207+05'30".into()));
+        assert_eq!(parse("D:19981223195207-0800"), Ok("D:19981223195207-08'00".into()));
+        assert_eq!(parse("D:19981223195207+01:00"), Ok("D:19981223195207+01'00".into()));
+        assert_eq!(parse("D:19981223195207\u{2212}03"), Ok("D:19981223195207-03'00".into()));
+        assert_eq!(parse("19981223195207"), Ok("D:19981223195207Z".into()));
+        assert_eq!(parse("D:19981223195207 garbage"), Ok("D:19981223195207Z".into()));
+        assert!(parse("D:19981323").is_err());
+        assert!(parse("D:98").is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn date_time() {
+        let d = Date::parse("D:199812231952-08'30").unwrap();
+        let t = time::OffsetDateTime::try_from(d.clone()).unwrap();
+        assert_eq!(t.unix_timestamp(), 914_442_720 + 8 * 3600 + 1800);
+        assert_eq!(Date::from(t), d);
+    }
+
+    #[test]
+    fn date_from_unix() {
+        let d = Date::from_unix(951_782_400 + 3661);
+        assert_eq!((d.year, d.month, d.day, d.hour, d.minute, d.second), (2000, 2, 29, 1, 1, 1));
+        assert_eq!(d.to_xmp(), "2000-02-29T01:01:01Z");
+    }
+
+    #[test]
+    fn structural_eq() {
+        use super::{NumberTolerance, Primitive};
+        use crate::parser::{parse, ParseFlags};
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let p = |s: &str| parse(s.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap();
+        let hash = |p: &Primitive| {
+            let mut hasher = DefaultHasher::new();
+            p.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (a, b) = (p("<< /A 1 /B [0 (x)] /C << /D /E >> >>"), p("<< /C << /D /E >> /B [0 (x)] /A 1 >>"));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&p("<< /A 2 /B [0 (x)] /C << /D /E >> >>")));
+        assert_eq!(hash(&Primitive::Number(0.0)), hash(&Primitive::Number(-0.0)));
+
+        let (a, b) = (p("[1 0.333333 /N]"), p("[1.0 0.33333334 /N]"));
+        assert_ne!(a, b);
+        assert!(!a.eq_with(&b, NumberTolerance::EXACT));
+        assert!(a.eq_with(&b, NumberTolerance::default()));
+        assert!(!a.eq_with(&b, NumberTolerance { mixed: false, ..Default::default() }));
+        assert!(!p("<< /A 1 >>").eq_with(&p("<< /A 1.1 >>"), NumberTolerance::default()));
+    }
+
+    #[test]
+    fn integers_eq_exactly() {
+        use super::{NumberTolerance, Primitive};
+
+        // the same as f32
+        let (a, b) = (Primitive::Integer(16777217), Primitive::Integer(16777216));
+        assert!(!a.eq_with(&b, NumberTolerance::EXACT));
+        assert!(!a.eq_with(&b, NumberTolerance::default()));
+        let tolerance = NumberTolerance { epsilon: 0.5, ..Default::default() };
+        assert!(!Primitive::Integer(3).eq_with(&Primitive::Integer(4), tolerance));
+        assert!(Primitive::Integer(4).eq_with(&Primitive::Integer(4), tolerance));
+    }
 }
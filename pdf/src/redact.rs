@@ -0,0 +1,574 @@
+//! Removing content from pages for good.
+//!
+//! Drawing a black box over a secret leaves the secret in the file. A redaction takes out
+//! the glyphs of text show operators, the pixels of images and the annotations under an area,
+//! and only then paints the area over.
+//!
+//! Redacting happens in two steps so a single [`File`](crate::file::File) can be both the
+//! source and the target: [`Redaction::new`] reads the page, [`Redaction::apply`] writes the result.
+//!
+//! Saving with [`File::save_to`](crate::file::File::save_to) appends an update and keeps the old
+//! objects, and with them what was redacted. The redacted document has to be written with
+//! [`File::rewrite`](crate::file::File::rewrite), which leaves out replaced objects and older revisions.
+//!
+//! ```no_run
+//! # use pdf::{file::FileOptions, object::PageRc, redact::{redaction_areas, Redaction}};
+//! let mut file = FileOptions::cached().open("secret.pdf").unwrap();
+//! let page = file.get_page(0).unwrap();
+//! let redaction = {
+//!     let resolver = file.resolver();
+//!     let areas = redaction_areas(&page, &resolver).unwrap();
+//!     Redaction::new(&page, &areas, &resolver).unwrap()
+//! };
+//! let redacted = redaction.apply(&mut file).unwrap();
+//! PageRc::update(redacted, &page, &mut file).unwrap();
+//! std::fs::write("redacted.pdf", file.rewrite().unwrap()).unwrap();
+//! ```
+//!
+//! Limits:
+//! - Form XObjects are not rewritten: one that shows text or images inside an area is removed as a whole.
+//! - Paths and shadings are kept.
+//! - Glyph boxes are estimated from the advance widths, not the glyph outlines.
+//! - The structure tree is kept; only the `ActualText`, `Alt` and `E` entries of the marked
+//!   content around removed glyphs are dropped.
+
+use crate::content::{Color, Content, Matrix, Op, OpTransformer, Point, Rgb, TextDrawAdjusted, ViewRect, Winding};
+use crate::enc::{encode, LZWFlateParams, StreamFilter};
+use crate::error::*;
+use crate::interpret::{ContentInterpreter, Event, Glyph};
+use crate::object::*;
+use crate::primitive::{Name, PdfString, Primitive};
+
+/// An area to redact, in the default user space of the page.
+#[derive(Debug, Clone, Copy)]
+pub struct RedactArea {
+    pub rect: Rectangle,
+    /// The color painted over the area. `None` leaves it empty.
+    pub fill: Option<[f32; 3]>,
+}
+
+impl RedactArea {
+    /// An area painted black.
+    pub fn new(rect: Rectangle) -> Self {
+        RedactArea { rect, fill: Some([0.; 3]) }
+    }
+}
+
+/// The areas marked by the `Redact` annotations of `page`.
+///
+/// Every quadrilateral in `QuadPoints` is an area, without them `Rect` is.
+/// The interior color `IC` becomes the fill.
+pub fn redaction_areas(page: &Page, resolve: &impl Resolve) -> Result<Vec<RedactArea>> {
+    let annots = t!(page.annotations.load(resolve));
+    let mut areas = vec![];
+    for annot in annots.iter() {
+        if annot.subtype.as_str() != "Redact" {
+            continue;
+        }
+        let fill = annot.other.get("IC").and_then(|ic| color(ic, resolve));
+        let quads = t!(annot.quad_points(resolve)).unwrap_or_default();
+        if !quads.is_empty() {
+            for quad in quads {
+                areas.push(RedactArea { rect: bounds(quad.into_iter()), fill });
+            }
+        } else if let Some(rect) = annot.rect {
+            areas.push(RedactArea { rect, fill });
+        }
+    }
+    Ok(areas)
+}
+
+fn color(p: &Primitive, resolve: &impl Resolve) -> Option<[f32; 3]> {
+    let c: Vec<f32> = p.clone().resolve(resolve).ok()?.into_array().ok()?.iter().filter_map(|n| n.as_number().ok()).collect();
+    match *c {
+        [g] => Some([g; 3]),
+        [r, g, b] => Some([r, g, b]),
+        [c, m, y, k] => Some([(1. - c) * (1. - k), (1. - m) * (1. - k), (1. - y) * (1. - k)]),
+        _ => None,
+    }
+}
+
+/// The result of redacting a page, see the [module documentation](self).
+pub struct Redaction {
+    page: Page,
+    content: Content,
+    resources: Resources,
+    images: Vec<(Name, RedactedImage)>,
+    annotations: Vec<MaybeRef<Annot>>,
+}
+
+/// An image with the redacted pixels made transparent.
+struct RedactedImage {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+    alpha: Vec<u8>,
+}
+
+impl Redaction {
+    /// Remove everything under `areas` from `page`.
+    pub fn new(page: &Page, areas: &[RedactArea], resolve: &impl Resolve) -> Result<Redaction> {
+        let areas: Vec<RedactArea> = areas.iter().map(|a| RedactArea { rect: a.rect.normalize(), ..*a }).collect();
+        let resources = page.resources().map(|r| (**r).clone()).unwrap_or_default();
+        let content = page.contents.clone().unwrap_or(Content { parts: vec![] });
+
+        let mut redactor = Redactor {
+            interpreter: t!(ContentInterpreter::for_page(page, resolve)),
+            resolve,
+            areas: &areas,
+            images: vec![],
+            marked: vec![],
+        };
+        let (content, resources) = t!(content.rewrite(&mut redactor, &resources, resolve));
+        let images = redactor.images;
+
+        let annots = t!(page.annotations.load(resolve));
+        let annotations = annots.iter()
+            .filter(|a| !a.rect.is_some_and(|r| areas.iter().any(|area| r.intersection(&area.rect).is_some())))
+            .cloned()
+            .collect();
+
+        let mut page = page.clone();
+        // it would show the old content
+        page.thumb = None;
+        Ok(Redaction { page, content, resources, images, annotations })
+    }
+
+    /// Create the new objects and return the redacted page.
+    ///
+    /// The page still has to replace the old one, and the file be written with
+    /// [`File::rewrite`](crate::file::File::rewrite) so that the old objects are gone.
+    pub fn apply(self, update: &mut impl Updater) -> Result<Page> {
+        let Redaction { mut page, content, mut resources, images, annotations } = self;
+        for (name, image) in images {
+            let image = t!(image.into_xobject(update));
+            resources.xobjects.insert(name, update.create(XObject::Image(image))?.get_ref());
+        }
+        page.resources = Some(MaybeRef::Indirect(update.create(resources)?));
+        page.contents = Some(content);
+        page.annotations = t!(Lazy::safe(annotations, update));
+        Ok(page)
+    }
+}
+
+impl RedactedImage {
+    fn into_xobject(self, update: &mut impl Updater) -> Result<ImageXObject> {
+        let png = |colors| StreamFilter::FlateDecode(LZWFlateParams::png(colors, 8, self.width as i32));
+        let mask = ImageDict {
+            width: self.width,
+            height: self.height,
+            color_space: Some(ColorSpace::DeviceGray),
+            bits_per_component: Some(8),
+            ..Default::default()
+        };
+        let filter = png(1);
+        let mask = Stream::from_compressed(mask, t!(encode(&self.alpha, &filter)), vec![filter]);
+        let dict = ImageDict {
+            width: self.width,
+            height: self.height,
+            color_space: Some(ColorSpace::DeviceRGB),
+            bits_per_component: Some(8),
+            smask: Some(update.create(mask)?.get_ref()),
+            ..Default::default()
+        };
+        let filter = png(3);
+        let data = t!(encode(&self.rgb, &filter));
+        Ok(ImageXObject { inner: Stream::from_compressed(dict, data, vec![filter]) })
+    }
+}
+
+enum ImageRedaction {
+    Keep,
+    Remove,
+    Replace(RedactedImage),
+}
+
+struct Redactor<'a, R> {
+    interpreter: ContentInterpreter<'a, R>,
+    resolve: &'a R,
+    areas: &'a [RedactArea],
+    images: Vec<(Name, RedactedImage)>,
+    /// The open marked-content sequences: the index of their `BDC` in the output,
+    /// and whether glyphs were removed inside.
+    marked: Vec<(usize, bool)>,
+}
+
+impl<R: Resolve> OpTransformer for Redactor<'_, R> {
+    fn transform(&mut self, op: Op, resources: &mut Resources, out: &mut Vec<Op>) -> Result<()> {
+        let areas = self.areas;
+        let image = match op {
+            Op::TextDraw { .. } | Op::TextDrawAdjusted { .. } => {
+                let mut shown = None;
+                t!(self.interpreter.op(&op, &mut |event, state| {
+                    if let Event::TextShown { glyphs, .. } = *event {
+                        // vertical displacements are not scaled horizontally
+                        let vertical = glyphs.first().is_some_and(|g| g.vertical);
+                        let scale = state.text.font_size * if vertical { 1. } else { state.text.horiz_scale };
+                        shown = Some((glyphs.to_vec(), scale));
+                    }
+                    Ok(())
+                }));
+                match shown {
+                    Some((glyphs, scale)) => {
+                        let removed: Vec<bool> = glyphs.iter().map(|g| glyph_hit(g, scale, areas)).collect();
+                        if removed.contains(&true) {
+                            // the replacement text of the enclosing sequences would still tell
+                            for (_, hit) in &mut self.marked {
+                                *hit = true;
+                            }
+                            out.push(remove_glyphs(op, &glyphs, &removed, scale));
+                        } else {
+                            out.push(op);
+                        }
+                    }
+                    None => out.push(op),
+                }
+                return Ok(());
+            }
+            Op::BeginMarkedContent { .. } => {
+                t!(self.interpreter.op(&op, &mut |_, _| Ok(())));
+                self.marked.push((out.len(), false));
+                out.push(op);
+                return Ok(());
+            }
+            Op::EndMarkedContent => {
+                t!(self.interpreter.op(&op, &mut |_, _| Ok(())));
+                if let Some((start, true)) = self.marked.pop() {
+                    strip_replacement_text(&mut out[start], resources);
+                }
+                out.push(op);
+                return Ok(());
+            }
+            Op::InlineImage { ref image } => Some(image.image.clone()),
+            Op::XObject { ref name } => {
+                let xobject = match resources.xobjects.get(name) {
+                    Some(&r) => Some(t!(self.resolve.get(r))),
+                    None => None,
+                };
+                match xobject.as_deref() {
+                    Some(XObject::Image(image)) => Some(std::sync::Arc::new(image.clone())),
+                    Some(XObject::Form(_)) => {
+                        let mut hit = false;
+                        t!(self.interpreter.op(&op, &mut |event, state| {
+                            hit |= event_hit(event, state.text.font_size * state.text.horiz_scale, areas);
+                            Ok(())
+                        }));
+                        if !hit {
+                            out.push(op);
+                        }
+                        return Ok(());
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let image = match image {
+            Some(image) => image,
+            None => {
+                t!(self.interpreter.op(&op, &mut |_, _| Ok(())));
+                out.push(op);
+                return Ok(());
+            }
+        };
+
+        let state = self.interpreter.state();
+        match redact_image(&image, &state.ctm, state.fill_rgb(), areas, self.resolve) {
+            ImageRedaction::Keep => out.push(op),
+            ImageRedaction::Remove => {}
+            ImageRedaction::Replace(redacted) => {
+                let name = (0..).map(|i| Name::from(format!("Redacted{}", i)))
+                    .find(|n| !resources.xobjects.contains_key(n) && !self.images.iter().any(|(m, _)| m == n))
+                    .unwrap();
+                self.images.push((name.clone(), redacted));
+                out.push(Op::XObject { name });
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, resources: &mut Resources, out: &mut Vec<Op>) -> Result<()> {
+        // sequences left open by the content
+        for (start, hit) in self.marked.drain(..) {
+            if hit {
+                strip_replacement_text(&mut out[start], resources);
+            }
+        }
+        out.insert(0, Op::Save);
+        out.push(Op::Restore);
+        for area in self.areas {
+            let [red, green, blue] = match area.fill {
+                Some(fill) => fill,
+                None => continue,
+            };
+            let r = area.rect;
+            out.extend([
+                Op::Save,
+                Op::FillColor { color: Color::Rgb(Rgb { red, green, blue }) },
+                Op::Rect { rect: ViewRect { x: r.left, y: r.bottom, width: r.right - r.left, height: r.top - r.bottom } },
+                Op::Fill { winding: Winding::NonZero },
+                Op::Restore,
+            ]);
+        }
+        Ok(())
+    }
+}
+
+/// Drop the entries of the property list of a `BDC` that give the text of its content.
+///
+/// A named property list is copied to a new name, it may be used elsewhere.
+fn strip_replacement_text(op: &mut Op, resources: &mut Resources) {
+    const KEYS: [&str; 3] = ["ActualText", "Alt", "E"];
+    let properties = match *op {
+        Op::BeginMarkedContent { properties: Some(ref mut properties), .. } => properties,
+        _ => return,
+    };
+    match *properties {
+        Primitive::Dictionary(ref mut dict) => {
+            for key in KEYS {
+                dict.remove(key);
+            }
+        }
+        Primitive::Name(ref name) => {
+            let mut dict = match resources.properties.get(name.as_str()) {
+                Some(dict) if KEYS.iter().any(|&key| dict.get(key).is_some()) => (**dict).clone(),
+                _ => return,
+            };
+            for key in KEYS {
+                dict.remove(key);
+            }
+            let name = (0..).map(|i| Name::from(format!("Redacted{}", i)))
+                .find(|n| !resources.properties.contains_key(n))
+                .unwrap();
+            resources.properties.insert(name.clone(), MaybeRef::Direct(Shared::new(dict)));
+            *properties = name.into();
+        }
+        _ => {}
+    }
+}
+
+/// Rebuild a text show operator as `TJ`, with the removed glyphs replaced by their advance.
+fn remove_glyphs(op: Op, glyphs: &[Glyph], removed: &[bool], scale: f32) -> Op {
+    let items = match op {
+        Op::TextDraw { text } => vec![TextDrawAdjusted::Text(text)],
+        Op::TextDrawAdjusted { array } => array,
+        op => return op,
+    };
+    let mut array = vec![];
+    let mut glyphs = glyphs.iter().zip(removed).peekable();
+    for item in items {
+        let text = match item {
+            TextDrawAdjusted::Spacing(n) => {
+                push_spacing(&mut array, n);
+                continue;
+            }
+            TextDrawAdjusted::Text(text) => text,
+        };
+        let mut kept = vec![];
+        let mut data = text.as_bytes();
+        // trailing bytes too short for a code were not shown either
+        while let Some(&(glyph, &removed)) = glyphs.peek() {
+            if data.is_empty() || glyph.code_len > data.len() {
+                break;
+            }
+            glyphs.next();
+            let (code, rest) = data.split_at(glyph.code_len);
+            data = rest;
+            if !removed {
+                kept.extend_from_slice(code);
+                continue;
+            }
+            if !kept.is_empty() {
+                array.push(TextDrawAdjusted::Text(PdfString::new(std::mem::take(&mut kept).into())));
+            }
+            if scale != 0. {
+                // a positive adjustment moves back, but down in vertical writing
+                let n = glyph.advance * 1000. / scale;
+                push_spacing(&mut array, if glyph.vertical { n } else { -n });
+            }
+        }
+        if !kept.is_empty() {
+            array.push(TextDrawAdjusted::Text(PdfString::new(kept.into())));
+        }
+    }
+    Op::TextDrawAdjusted { array }
+}
+
+fn push_spacing(array: &mut Vec<TextDrawAdjusted>, n: f32) {
+    match array.last_mut() {
+        Some(TextDrawAdjusted::Spacing(m)) => *m += n,
+        _ => array.push(TextDrawAdjusted::Spacing(n)),
+    }
+}
+
+/// Whether the estimated box of `glyph` overlaps an area.
+///
+/// The box spans the advance (half an em if it is unknown) and from `-0.2` to `0.8` em vertically.
+fn glyph_hit(glyph: &Glyph, scale: f32, areas: &[RedactArea]) -> bool {
+    let width = if scale != 0. { glyph.advance / scale } else { 0. };
+    let width = if width > 0. { width } else { 0.5 };
+    let rect = Rectangle { left: 0., bottom: -0.2, right: width, top: 0.8 }.transform(&glyph.matrix);
+    areas.iter().any(|a| rect.intersection(&a.rect).is_some())
+}
+
+fn event_hit(event: &Event, text_scale: f32, areas: &[RedactArea]) -> bool {
+    match *event {
+        Event::TextShown { glyphs, .. } => glyphs.iter().any(|g| glyph_hit(g, text_scale, areas)),
+        Event::ImageDrawn { matrix, .. } => {
+            let rect = Rectangle { left: 0., bottom: 0., right: 1., top: 1. }.transform(&matrix);
+            areas.iter().any(|a| rect.intersection(&a.rect).is_some())
+        }
+        _ => false,
+    }
+}
+
+/// Find the pixels of `image` (drawn with `matrix`) that overlap an area.
+fn redact_image(image: &ImageXObject, matrix: &Matrix, fill: [f32; 3], areas: &[RedactArea], resolve: &impl Resolve) -> ImageRedaction {
+    let unit = Rectangle { left: 0., bottom: 0., right: 1., top: 1. }.transform(matrix);
+    let areas: Vec<&RedactArea> = areas.iter().filter(|a| unit.intersection(&a.rect).is_some()).collect();
+    if areas.is_empty() {
+        return ImageRedaction::Keep;
+    }
+    let (width, height) = (image.width as usize, image.height as usize);
+    let covered: Vec<bool> = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // image space has the first row at the top
+            let pixel = Rectangle {
+                left: x as f32 / width as f32,
+                right: (x + 1) as f32 / width as f32,
+                bottom: 1. - (y + 1) as f32 / height as f32,
+                top: 1. - y as f32 / height as f32,
+            };
+            let rect = pixel.transform(matrix);
+            areas.iter().any(|a| rect.intersection(&a.rect).is_some())
+        })
+        .collect();
+    if !covered.contains(&true) {
+        return ImageRedaction::Keep;
+    }
+    if !covered.contains(&false) {
+        return ImageRedaction::Remove;
+    }
+
+    let mut rgba = match image.masked_rgba_data(resolve) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("can't decode image to redact it, removing it: {}", e);
+            return ImageRedaction::Remove;
+        }
+    };
+    if image.image_mask {
+        let fill = fill.map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+        for px in rgba.chunks_exact_mut(4) {
+            px[..3].copy_from_slice(&fill);
+        }
+    }
+    for (px, &covered) in rgba.chunks_exact_mut(4).zip(&covered) {
+        if covered {
+            px.fill(0);
+        }
+    }
+    ImageRedaction::Replace(RedactedImage {
+        width: image.width,
+        height: image.height,
+        rgb: rgba.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect(),
+        alpha: rgba.chunks_exact(4).map(|px| px[3]).collect(),
+    })
+}
+
+fn bounds(points: impl Iterator<Item=Point>) -> Rectangle {
+    points.fold(
+        Rectangle { left: f32::INFINITY, bottom: f32::INFINITY, right: f32::NEG_INFINITY, top: f32::NEG_INFINITY },
+        |r, p| Rectangle { left: r.left.min(p.x), bottom: r.bottom.min(p.y), right: r.right.max(p.x), top: r.top.max(p.y) },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Dictionary;
+
+    fn glyph(x: f32, advance: f32) -> Glyph {
+        // 10pt text
+        Glyph { code: 0, code_len: 1, cid: None, unicode: None, matrix: Matrix { a: 10., b: 0., c: 0., d: 10., e: x, f: 0. }, advance, vertical: false }
+    }
+
+    fn texts(op: Op) -> Vec<Result<Vec<u8>, f32>> {
+        match op {
+            Op::TextDrawAdjusted { array } => array.into_iter().map(|item| match item {
+                TextDrawAdjusted::Text(s) => Ok(s.as_bytes().to_vec()),
+                TextDrawAdjusted::Spacing(n) => Err(n),
+            }).collect(),
+            op => panic!("{:?}", op),
+        }
+    }
+
+    #[test]
+    fn glyphs_become_spacing() {
+        let glyphs = [glyph(0., 5.), glyph(5., 5.), glyph(10., 5.)];
+        let area = [RedactArea::new(Rectangle { left: 6., bottom: 0., right: 9., top: 5. })];
+        let removed: Vec<bool> = glyphs.iter().map(|g| glyph_hit(g, 10., &area)).collect();
+        assert_eq!(removed, [false, true, false]);
+
+        let op = Op::TextDrawAdjusted { array: vec![
+            TextDrawAdjusted::Text(PdfString::new(b"ab"[..].into())),
+            TextDrawAdjusted::Spacing(-100.),
+            TextDrawAdjusted::Text(PdfString::new(b"c"[..].into())),
+        ] };
+        let glyphs = [glyph(0., 5.), glyph(5., 5.), glyph(20., 5.)];
+        let array = match remove_glyphs(op, &glyphs, &[false, true, false], 10.) {
+            Op::TextDrawAdjusted { array } => array,
+            op => panic!("{:?}", op),
+        };
+        assert_eq!(array.len(), 3);
+        assert!(matches!(array[0], TextDrawAdjusted::Text(ref s) if s.as_bytes() == b"a"));
+        // the advance of `b` (500) and the kerning are merged
+        assert!(matches!(array[1], TextDrawAdjusted::Spacing(n) if n == -600.));
+        assert!(matches!(array[2], TextDrawAdjusted::Text(ref s) if s.as_bytes() == b"c"));
+    }
+
+    #[test]
+    fn mixed_code_lengths() {
+        // a CMap with one and two byte codes, like `a`, `<8140>` and `b`
+        let text = || Op::TextDraw { text: PdfString::new(b"a\x81\x40b"[..].into()) };
+        let glyphs = [glyph(0., 5.), Glyph { code_len: 2, ..glyph(5., 5.) }, glyph(10., 5.)];
+
+        let items = texts(remove_glyphs(text(), &glyphs, &[false, true, false], 10.));
+        assert_eq!(items, [Ok(b"a".to_vec()), Err(-500.), Ok(b"b".to_vec())]);
+
+        let items = texts(remove_glyphs(text(), &glyphs, &[true, false, true], 10.));
+        assert_eq!(items, [Err(-500.), Ok(b"\x81\x40".to_vec()), Err(-500.)]);
+    }
+
+    #[test]
+    fn replacement_text_is_dropped() {
+        let mut dict = Dictionary::new();
+        dict.insert("ActualText", PdfString::new(b"secret"[..].into()));
+        dict.insert("MCID", 3);
+        let mut resources = Resources::default();
+        resources.properties.insert("P0".into(), MaybeRef::Direct(Shared::new(dict.clone())));
+
+        // a named property list is copied, the original may be used elsewhere
+        let mut op = Op::BeginMarkedContent { tag: "Span".into(), properties: Some(Primitive::Name("P0".into())) };
+        strip_replacement_text(&mut op, &mut resources);
+        let name = match op {
+            Op::BeginMarkedContent { properties: Some(Primitive::Name(ref name)), .. } => name.clone(),
+            ref op => panic!("{:?}", op),
+        };
+        assert_eq!(name.as_str(), "Redacted0");
+        let copy = &resources.properties[name.as_str()];
+        assert!(copy.get("ActualText").is_none());
+        assert_eq!(copy.get("MCID"), Some(&Primitive::Integer(3)));
+        assert!(resources.properties["P0"].get("ActualText").is_some());
+
+        let mut op = Op::BeginMarkedContent { tag: "Span".into(), properties: Some(dict.into()) };
+        strip_replacement_text(&mut op, &mut resources);
+        match op {
+            Op::BeginMarkedContent { properties: Some(Primitive::Dictionary(ref dict)), .. } => {
+                assert!(dict.get("ActualText").is_none());
+                assert!(dict.get("MCID").is_some());
+            }
+            ref op => panic!("{:?}", op),
+        }
+    }
+}
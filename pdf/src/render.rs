@@ -0,0 +1,495 @@
+//! Rasterizing pages to RGBA pixels.
+//!
+//! This is a small scanline renderer on top of [`ContentInterpreter`](crate::interpret::ContentInterpreter),
+//! good enough for thumbnails. Paths, clipping, images and text with embedded TrueType or CFF fonts are drawn.
+//! Not supported: shadings and patterns (drawn in gray), dashes, soft masks and blend modes,
+//! Type 1 and Type 3 fonts and the standard 14 fonts without embedded data.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::content::{Matrix, Point, TextMode, Winding};
+use crate::error::*;
+use crate::interpret::{device_matrix, Clip, ContentInterpreter, Event, GraphicsState, ImageSource, OutlineCache, Path, PathSegment};
+use crate::object::*;
+
+/// Vertical samples per pixel.
+const SUBSAMPLES: usize = 4;
+
+/// An RGBA image with 8 bits per channel, rows from top to bottom.
+#[derive(Debug, Clone)]
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Render `page` on a white background, at `dpi` pixels per inch.
+///
+/// The crop box is rendered and `Rotate` is applied.
+pub fn render_page(page: &Page, resolve: &impl Resolve, dpi: f32) -> Result<Pixmap> {
+    let (ctm, width, height) = t!(device_matrix(page, dpi / 72.));
+    let (width, height) = (width.round().max(1.) as usize, height.round().max(1.) as usize);
+    if width * height > 1 << 28 {
+        bail!("page too large to render: {}x{}", width, height);
+    }
+
+    let mut renderer = Renderer {
+        width,
+        height,
+        rgb: vec![1.; width * height * 3],
+        clip_masks: HashMap::new(),
+        outlines: OutlineCache::new(),
+    };
+    if let Some(ref contents) = page.contents {
+        let ops = t!(contents.operations(resolve));
+        let mut interpreter = ContentInterpreter::new(page.resources().ok().cloned(), ctm, resolve);
+        t!(interpreter.run(&ops, &mut |event, state| renderer.event(event, state, resolve)));
+    }
+
+    let data = renderer.rgb.chunks_exact(3)
+        .flat_map(|c| [to_u8(c[0]), to_u8(c[1]), to_u8(c[2]), 255])
+        .collect();
+    Ok(Pixmap { width: width as u32, height: height as u32, data })
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0., 1.) * 255. + 0.5) as u8
+}
+
+/// A line segment of a flattened path.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    p0: Point,
+    p1: Point,
+}
+
+fn flatten_curve(p0: Point, c1: Point, c2: Point, p: Point, out: &mut Vec<Point>) {
+    let len = dist(p0, c1) + dist(c1, c2) + dist(c2, p);
+    let n = (len / 2.).ceil().clamp(1., 100.) as usize;
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let u = 1. - t;
+        let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+        out.push(Point {
+            x: a * p0.x + b * c1.x + c * c2.x + d * p.x,
+            y: a * p0.y + b * c1.y + c * c2.y + d * p.y,
+        });
+    }
+}
+fn dist(a: Point, b: Point) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Split the path into polylines, one per subpath, and whether the subpath was closed.
+fn polylines(path: &Path) -> Vec<(Vec<Point>, bool)> {
+    let mut lines = vec![];
+    let mut current: Vec<Point> = vec![];
+    for segment in &path.segments {
+        match *segment {
+            PathSegment::MoveTo(p) => {
+                if current.len() > 1 {
+                    lines.push((std::mem::take(&mut current), false));
+                }
+                current = vec![p];
+            }
+            PathSegment::LineTo(p) => {
+                if current.is_empty() {
+                    current.push(p);
+                }
+                current.push(p);
+            }
+            PathSegment::CurveTo(c1, c2, p) => {
+                let p0 = match current.last() {
+                    Some(&p0) => p0,
+                    None => {
+                        current.push(c1);
+                        c1
+                    }
+                };
+                flatten_curve(p0, c1, c2, p, &mut current);
+            }
+            PathSegment::Close => {
+                if let Some(&first) = current.first() {
+                    lines.push((std::mem::take(&mut current), true));
+                    // drawing continues at the start of the closed subpath
+                    current.push(first);
+                }
+            }
+        }
+    }
+    if current.len() > 1 {
+        lines.push((current, false));
+    }
+    lines
+}
+
+fn fill_edges(path: &Path) -> Vec<Edge> {
+    let mut edges = vec![];
+    for (points, _) in polylines(path) {
+        // subpaths are closed implicitly for filling
+        for (i, &p0) in points.iter().enumerate() {
+            let p1 = points[(i + 1) % points.len()];
+            edges.push(Edge { p0, p1 });
+        }
+    }
+    edges
+}
+
+fn polygon_edges(points: &[Point], edges: &mut Vec<Edge>) {
+    // all polygons are made to have the same orientation, so the nonzero rule gives their union
+    let area: f32 = points.iter().zip(points.iter().cycle().skip(1)).map(|(a, b)| a.x * b.y - b.x * a.y).sum();
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if area >= 0. {
+            edges.push(Edge { p0: a, p1: b });
+        } else {
+            edges.push(Edge { p0: b, p1: a });
+        }
+    }
+}
+
+fn disc(center: Point, r: f32) -> Vec<Point> {
+    let n = (r * 2.).ceil().clamp(8., 64.) as usize;
+    (0..n).map(|i| {
+        let a = i as f32 / n as f32 * std::f32::consts::TAU;
+        Point { x: center.x + r * a.cos(), y: center.y + r * a.sin() }
+    }).collect()
+}
+
+/// Outline the stroke of `path` as polygons. Joins are round, dashes are ignored.
+fn stroke_edges(path: &Path, state: &GraphicsState) -> Vec<Edge> {
+    let m = &state.ctm;
+    let scale = (m.a * m.d - m.b * m.c).abs().sqrt();
+    let half = (state.line_width * scale).max(1.) / 2.;
+    let mut edges = vec![];
+    for (points, closed) in polylines(path) {
+        for pair in points.windows(2) {
+            let (mut a, mut b) = (pair[0], pair[1]);
+            let d = dist(a, b);
+            if d < 1e-6 {
+                continue;
+            }
+            let (dx, dy) = ((b.x - a.x) / d * half, (b.y - a.y) / d * half);
+            if !closed && state.line_cap == crate::content::LineCap::Square {
+                if a == points[0] {
+                    a = Point { x: a.x - dx, y: a.y - dy };
+                }
+                if b == points[points.len() - 1] {
+                    b = Point { x: b.x + dx, y: b.y + dy };
+                }
+            }
+            polygon_edges(&[
+                Point { x: a.x - dy, y: a.y + dx },
+                Point { x: b.x - dy, y: b.y + dx },
+                Point { x: b.x + dy, y: b.y - dx },
+                Point { x: a.x + dy, y: a.y - dx },
+            ], &mut edges);
+        }
+        if half > 1. {
+            let joins = if closed || state.line_cap == crate::content::LineCap::Round {
+                &points[..]
+            } else if points.len() > 2 {
+                &points[1..points.len() - 1]
+            } else {
+                &[]
+            };
+            for &p in joins {
+                polygon_edges(&disc(p, half), &mut edges);
+            }
+        }
+    }
+    edges
+}
+
+/// Anti-aliased coverage of a region of the canvas.
+struct Coverage {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+impl Coverage {
+    fn get(&self, x: usize, y: usize) -> f32 {
+        if x < self.x0 || y < self.y0 || x >= self.x0 + self.width || y >= self.y0 + self.height {
+            return 0.;
+        }
+        self.data[(y - self.y0) * self.width + x - self.x0]
+    }
+}
+
+fn rasterize(edges: &[Edge], winding: Winding, width: usize, height: usize) -> Option<Coverage> {
+    let mut min = Point { x: f32::INFINITY, y: f32::INFINITY };
+    let mut max = Point { x: f32::NEG_INFINITY, y: f32::NEG_INFINITY };
+    for e in edges {
+        for p in [e.p0, e.p1] {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+    }
+    if !(min.x.is_finite() && min.y.is_finite() && max.x.is_finite() && max.y.is_finite()) {
+        return None;
+    }
+    let x0 = min.x.floor().clamp(0., width as f32) as usize;
+    let x1 = max.x.ceil().clamp(0., width as f32) as usize;
+    let y0 = min.y.floor().clamp(0., height as f32) as usize;
+    let y1 = max.y.ceil().clamp(0., height as f32) as usize;
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+    let (w, h) = (x1 - x0, y1 - y0);
+    let mut data = vec![0f32; w * h];
+
+    // edges sorted by their top, with the direction they go in
+    let mut sorted: Vec<(f32, f32, f32, f32, i32)> = edges.iter()
+        .filter(|e| e.p0.y != e.p1.y)
+        .map(|e| if e.p0.y < e.p1.y {
+            (e.p0.y, e.p1.y, e.p0.x, e.p1.x, 1)
+        } else {
+            (e.p1.y, e.p0.y, e.p1.x, e.p0.x, -1)
+        })
+        .collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let weight = 1. / SUBSAMPLES as f32;
+    let mut crossings: Vec<(f32, i32)> = vec![];
+    for row in 0..h {
+        let acc = &mut data[row * w..(row + 1) * w];
+        for sub in 0..SUBSAMPLES {
+            let y = (y0 + row) as f32 + (sub as f32 + 0.5) * weight;
+            crossings.clear();
+            for &(top, bottom, xt, xb, dir) in &sorted {
+                if top > y {
+                    break;
+                }
+                if y < bottom {
+                    crossings.push((xt + (y - top) / (bottom - top) * (xb - xt), dir));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut wind = 0;
+            let mut start = 0.;
+            for &(x, dir) in &crossings {
+                let inside = |wind: i32| match winding {
+                    Winding::NonZero => wind != 0,
+                    Winding::EvenOdd => wind % 2 != 0,
+                };
+                let was_inside = inside(wind);
+                wind += dir;
+                match (was_inside, inside(wind)) {
+                    (false, true) => start = x,
+                    (true, false) => add_span(acc, start - x0 as f32, x - x0 as f32, weight),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Some(Coverage { x0, y0, width: w, height: h, data })
+}
+
+fn add_span(acc: &mut [f32], a: f32, b: f32, weight: f32) {
+    let len = acc.len() as f32;
+    let (a, b) = (a.clamp(0., len), b.clamp(0., len));
+    if a >= b {
+        return;
+    }
+    let (ia, ib) = (a as usize, b as usize);
+    if ia == ib {
+        acc[ia] += (b - a) * weight;
+        return;
+    }
+    acc[ia] += (ia as f32 + 1. - a) * weight;
+    for v in &mut acc[ia + 1..ib] {
+        *v += weight;
+    }
+    if ib < acc.len() {
+        acc[ib] += (b - ib as f32) * weight;
+    }
+}
+
+type ClipMasks = HashMap<Vec<usize>, (Vec<Arc<Clip>>, Arc<Coverage>)>;
+
+struct Renderer {
+    width: usize,
+    height: usize,
+    /// background is white, so there is no alpha
+    rgb: Vec<f32>,
+    /// combined clipping masks, keyed by the clip paths they are made of (which are kept alive)
+    clip_masks: ClipMasks,
+    outlines: OutlineCache,
+}
+
+impl Renderer {
+    fn clip_mask(&mut self, clip: &[Arc<Clip>]) -> Option<Arc<Coverage>> {
+        if clip.is_empty() {
+            return None;
+        }
+        let key: Vec<usize> = clip.iter().map(|c| Arc::as_ptr(c) as usize).collect();
+        if let Some((_, mask)) = self.clip_masks.get(&key) {
+            return Some(mask.clone());
+        }
+        let mut mask = Coverage { x0: 0, y0: 0, width: self.width, height: self.height, data: vec![1.; self.width * self.height] };
+        for c in clip {
+            let coverage = rasterize(&fill_edges(&c.path), c.winding, self.width, self.height);
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let v = coverage.as_ref().map_or(0., |cov| cov.get(x, y).min(1.));
+                    mask.data[y * self.width + x] *= v;
+                }
+            }
+        }
+        let mask = Arc::new(mask);
+        self.clip_masks.insert(key, (clip.to_vec(), mask.clone()));
+        Some(mask)
+    }
+
+    fn composite(&mut self, coverage: &Coverage, color: [f32; 3], alpha: f32, clip: Option<&Coverage>) {
+        for row in 0..coverage.height {
+            let y = coverage.y0 + row;
+            for col in 0..coverage.width {
+                let x = coverage.x0 + col;
+                let mut a = coverage.data[row * coverage.width + col].min(1.) * alpha;
+                if let Some(clip) = clip {
+                    a *= clip.get(x, y);
+                }
+                if a <= 0. {
+                    continue;
+                }
+                let px = &mut self.rgb[(y * self.width + x) * 3..][..3];
+                for (p, c) in px.iter_mut().zip(color) {
+                    *p = *p * (1. - a) + c * a;
+                }
+            }
+        }
+    }
+
+    fn fill(&mut self, edges: &[Edge], winding: Winding, color: [f32; 3], alpha: f32, state: &GraphicsState) {
+        if let Some(coverage) = rasterize(edges, winding, self.width, self.height) {
+            let clip = self.clip_mask(&state.clip);
+            self.composite(&coverage, color, alpha, clip.as_deref());
+        }
+    }
+
+    fn image(&mut self, image: &ImageXObject, matrix: &Matrix, state: &GraphicsState, resolve: &impl Resolve) -> Result<()> {
+        let (width, height) = (image.width as usize, image.height as usize);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let pixels = t!(image.rgba_data(resolve));
+        // stencil masks are painted with the fill color
+        let fill = image.image_mask.then(|| state.fill_rgb());
+        let inverse = match matrix.invert() {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let corners = [(0., 0.), (1., 0.), (1., 1.), (0., 1.)].map(|(x, y)| matrix.transform_point(Point { x, y }));
+        let x0 = corners.iter().map(|p| p.x).fold(f32::INFINITY, f32::min).floor().clamp(0., self.width as f32) as usize;
+        let x1 = corners.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max).ceil().clamp(0., self.width as f32) as usize;
+        let y0 = corners.iter().map(|p| p.y).fold(f32::INFINITY, f32::min).floor().clamp(0., self.height as f32) as usize;
+        let y1 = corners.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max).ceil().clamp(0., self.height as f32) as usize;
+        let clip = self.clip_mask(&state.clip);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = inverse.transform_point(Point { x: x as f32 + 0.5, y: y as f32 + 0.5 });
+                if !(0. ..1.).contains(&p.x) || !(0. ..1.).contains(&p.y) {
+                    continue;
+                }
+                // the first row of the image is at the top of the unit square
+                let col = ((p.x * width as f32) as usize).min(width - 1);
+                let row = (((1. - p.y) * height as f32) as usize).min(height - 1);
+                let px = &pixels[(row * width + col) * 4..][..4];
+                let rgb = fill.unwrap_or([px[0], px[1], px[2]].map(|c| c as f32 / 255.));
+                let a = px[3] as f32 / 255. * state.fill_alpha * clip.as_ref().map_or(1., |c| c.get(x, y));
+                if a <= 0. {
+                    continue;
+                }
+                let px = &mut self.rgb[(y * self.width + x) * 3..][..3];
+                for (p, c) in px.iter_mut().zip(rgb) {
+                    *p = *p * (1. - a) + c * a;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event, state: &GraphicsState, resolve: &impl Resolve) -> Result<()> {
+        match *event {
+            Event::PathFilled { path, winding } => {
+                let color = state.fill_rgb();
+                self.fill(&fill_edges(path), winding, color, state.fill_alpha, state);
+            }
+            Event::PathStroked { path } => {
+                let color = state.stroke_rgb();
+                self.fill(&stroke_edges(path, state), Winding::NonZero, color, state.stroke_alpha, state);
+            }
+            Event::TextShown { glyphs, font } => {
+                let (stroke, fill) = match state.text.render_mode {
+                    TextMode::Fill | TextMode::FillAndClip => (false, true),
+                    TextMode::Stroke | TextMode::StrokeAndClip => (true, false),
+                    TextMode::FillThenStroke => (true, true),
+                    TextMode::Invisible => (false, false),
+                };
+                let font = match font {
+                    Some(font) if stroke || fill => font,
+                    _ => return Ok(()),
+                };
+                for g in glyphs {
+                    let path = match self.outlines.glyph_path(font, g, resolve) {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    if fill {
+                        let color = state.fill_rgb();
+                        self.fill(&fill_edges(&path), Winding::NonZero, color, state.fill_alpha, state);
+                    }
+                    if stroke {
+                        let color = state.stroke_rgb();
+                        self.fill(&stroke_edges(&path, state), Winding::NonZero, color, state.stroke_alpha, state);
+                    }
+                }
+            }
+            Event::ImageDrawn { image, ref matrix } => {
+                let image = match image {
+                    ImageSource::XObject { image, .. } => image,
+                    ImageSource::Inline(inline) => &*inline.image,
+                };
+                if let Err(e) = self.image(image, matrix, state, resolve) {
+                    warn!("can't draw image: {:?}", e);
+                }
+            }
+            Event::ShadingPainted { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_rule() {
+        let square = |x0: f32, y0: f32, x1: f32, y1: f32| vec![
+            Edge { p0: Point { x: x0, y: y0 }, p1: Point { x: x1, y: y0 } },
+            Edge { p0: Point { x: x1, y: y0 }, p1: Point { x: x1, y: y1 } },
+            Edge { p0: Point { x: x1, y: y1 }, p1: Point { x: x0, y: y1 } },
+            Edge { p0: Point { x: x0, y: y1 }, p1: Point { x: x0, y: y0 } },
+        ];
+        let mut edges = square(0., 0., 8., 8.);
+        edges.extend(square(2., 2., 6., 6.));
+        let nonzero = rasterize(&edges, Winding::NonZero, 10, 10).unwrap();
+        let even_odd = rasterize(&edges, Winding::EvenOdd, 10, 10).unwrap();
+        assert_eq!(nonzero.get(4, 4), 1.);
+        assert_eq!(even_odd.get(4, 4), 0.);
+        assert_eq!(even_odd.get(1, 1), 1.);
+        assert_eq!(nonzero.get(9, 9), 0.);
+
+        // half a pixel
+        let half = rasterize(&square(0., 0., 1.5, 1.), Winding::NonZero, 10, 10).unwrap();
+        assert_eq!(half.get(1, 0), 0.5);
+    }
+}
@@ -1,42 +1,283 @@
+//! Rewriting damaged files.
+//!
+//! [`repair`] reads a file with the most lenient [`ParseOptions`] and writes everything that can be
+//! reached from its trailer into a new file. On the way, stream lengths are recomputed, the page tree
+//! is rebuilt without entries that are not pages or appear more than once, and `/Count` is set to the
+//! actual number of pages. Objects that nothing refers to, and older definitions of objects that were
+//! defined more than once, are left out.
 
-fn build_xref_table() {
-    warn!("can't read xref table: {:?}", e);
-    let start_offset = t!(backend.locate_start_offset());
-    let mut lexer = Lexer::new(t!(backend.read(..)));
-    let mut objects = Vec::new();
-
-    (|| -> Result<()> { loop {
-        let offset = lexer.get_pos();
-        let w1 = t!(lexer.next());
-        let w2 = t!(lexer.next());
-        let w3 = t!(lexer.next_expect("obj"));
-        try_opt!(lexer.seek_substr("endobj"));
-
-        objects.push((t!(w1.to::<ObjNr>()), t!(w2.to::<GenNr>()), offset));
-    }})();
-
-    objects.sort_unstable();
-    let mut first_id = objects.first().map(|&(n, _, _)| n).unwrap_or(0);
-    let mut last_id = objects.last().map(|&(n, _, _)| n).unwrap_or(0);
-    let mut xref = XRefTable::new(1 + last_id - first_id);
-    for &(obj_nr, gen_nr, offset) in objects.iter() {
-        for n in first_id + 1 .. obj_nr {
-            xref.push(XRef::Free { next_obj_nr: obj_nr, gen_nr: 0 });
-        }
-        if obj_nr == last_id {
-            warn!("duplicate obj_nr {}", obj_nr);
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::error::*;
+use crate::file::{NoCache, NoLog, PromisedRef, Storage, Trailer};
+use crate::object::*;
+use crate::primitive::*;
+
+/// What [`repair`] changed.
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// Problems the parser recovered from while reading the file.
+    pub warnings: Vec<Warning>,
+    /// Objects that could not be read and were replaced by `null`.
+    pub unreadable: Vec<PlainRef>,
+    /// Streams whose `Length` did not match their data.
+    pub stream_lengths: usize,
+    /// Page tree nodes whose `Count` was wrong.
+    pub page_counts: usize,
+    /// Page tree entries that were removed because they are not pages or appear more than once.
+    pub pruned_kids: usize,
+    /// Objects that nothing refers to, which were left out.
+    pub orphans: Vec<ObjNr>,
+}
+
+/// Read `data` as leniently as possible and write it as a clean file.
+///
+/// The new file has a single cross reference stream and no encryption.
+pub fn repair(data: &[u8]) -> Result<(Vec<u8>, RepairReport)> {
+    let warnings = Warnings::new();
+    let options = ParseOptions::lenient().collect_warnings(&warnings);
+    let mut storage = t!(Storage::with_cache(data, options, NoCache, NoCache, NoLog));
+    let trailer = t!(storage.load_storage_and_trailer());
+    let resolve = storage.resolver();
+
+    let root = match trailer.get("Root") {
+        Some(&Primitive::Reference(r)) => r,
+        _ => bail!("the trailer has no catalog"),
+    };
+    let info = match trailer.get("Info") {
+        Some(&Primitive::Reference(r)) => Some(r),
+        _ => None,
+    };
+
+    let mut header = match storage.version() {
+        Ok(version) if version.starts_with("PDF-") => format!("%{}\n", version).into_bytes(),
+        _ => b"%PDF-1.7\n".to_vec(),
+    };
+    header.extend_from_slice(b"%\xe2\xe3\xcf\xd3\n");
+    let out = t!(Storage::with_cache(header, ParseOptions::lenient(), NoCache, NoCache, NoLog));
+
+    let mut repairer = Repairer {
+        resolve: &resolve,
+        out,
+        map: HashMap::new(),
+        queue: VecDeque::new(),
+        report: RepairReport::default(),
+    };
+
+    let catalog = t!(t!(resolve.resolve(root)).into_dictionary());
+    let pages = match catalog.get("Pages") {
+        Some(&Primitive::Reference(r)) => r,
+        _ => bail!("the catalog has no page tree"),
+    };
+    let mut seen = HashSet::new();
+    let tree = match repairer.collect_pages(pages, &mut seen, 0) {
+        Some(tree) => tree,
+        None => bail!("the page tree root {:?} is not a page tree node", pages),
+    };
+    let tree = repairer.promise_pages(tree);
+    t!(repairer.write_pages(tree, None));
+
+    let new_root = repairer.map_ref(root);
+    t!(repairer.drain());
+
+    let mut report = repairer.report;
+    let mut out = repairer.out;
+    let copied: HashSet<ObjNr> = repairer.map.keys().map(|r| r.id).collect();
+    for id in storage.object_numbers() {
+        if copied.contains(&id) || Some(id) == info.map(|r| r.id) {
             continue;
         }
-        xref.push(XRef::Raw {
-            pos: offset - start_offset,
-            gen_nr
-        });
-        last_id = obj_nr;
+        let structural = match resolve.resolve(PlainRef { id, gen: 0 }) {
+            Ok(Primitive::Stream(ref s)) => matches!(s.info.get("Type"), Some(Primitive::Name(n)) if n == "ObjStm" || n == "XRef"),
+            _ => false,
+        };
+        if !structural {
+            report.orphans.push(id);
+        }
     }
 
-    return t!(Err(e));
+    let info_dict = match info {
+        Some(r) => match resolve.resolve(r).and_then(|p| InfoDict::from_primitive(p, &resolve)) {
+            Ok(info) => Some(info),
+            Err(e) => {
+                warn!("dropping the info dictionary: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let id = match trailer.get("ID") {
+        Some(Primitive::Array(parts)) => parts.iter().filter_map(|p| p.as_string().ok().cloned()).collect(),
+        _ => vec![],
+    };
+    let mut trailer = Trailer {
+        size: 0,
+        prev_trailer_pos: None,
+        root: t!(out.resolver().get(Ref::new(new_root))),
+        encrypt_dict: None,
+        info_dict,
+        id,
+    };
+    t!(out.save(&mut trailer));
+
+    report.warnings = warnings.take();
+    Ok((out.into_inner(), report))
 }
 
-fn build_catalog() {
-    
+enum PageNode<T> {
+    Pages { old: PlainRef, new: T, dict: Dictionary, kids: Vec<PageNode<T>> },
+    Page { old: PlainRef, new: T, dict: Dictionary },
+}
+impl<T> PageNode<T> {
+    fn count(&self) -> u32 {
+        match *self {
+            PageNode::Pages { ref kids, .. } => kids.iter().map(|k| k.count()).sum(),
+            PageNode::Page { .. } => 1,
+        }
+    }
+}
+
+struct Repairer<'a, R> {
+    resolve: &'a R,
+    out: Storage<Vec<u8>, NoCache, NoCache, NoLog>,
+    // old -> new
+    map: HashMap<PlainRef, PlainRef>,
+    // objects that have a new reference, but have not been written yet
+    queue: VecDeque<(PlainRef, PromisedRef<Primitive>)>,
+    report: RepairReport,
+}
+impl<'a, R: Resolve> Repairer<'a, R> {
+    /// Read the page tree below `old`, leaving out everything that is not a page or was seen before.
+    fn collect_pages(&mut self, old: PlainRef, seen: &mut HashSet<PlainRef>, depth: usize) -> Option<PageNode<()>> {
+        if depth > self.resolve.options().max_depth || !seen.insert(old) {
+            return None;
+        }
+        let mut dict = self.resolve.resolve(old).and_then(Primitive::into_dictionary).ok()?;
+        let typ = dict.get("Type").and_then(|t| t.as_name().ok()).map(String::from);
+        match (typ.as_deref(), dict.remove("Kids")) {
+            (Some("Page"), _) | (None, None) => Some(PageNode::Page { old, new: (), dict }),
+            (_, Some(kids)) => {
+                let kids = kids.resolve(self.resolve).and_then(Primitive::into_array).unwrap_or_default();
+                let mut nodes = Vec::with_capacity(kids.len());
+                for kid in kids {
+                    match kid {
+                        Primitive::Reference(r) => match self.collect_pages(r, seen, depth + 1) {
+                            Some(node) => nodes.push(node),
+                            None => self.report.pruned_kids += 1,
+                        },
+                        _ => self.report.pruned_kids += 1,
+                    }
+                }
+                Some(PageNode::Pages { old, new: (), dict, kids: nodes })
+            }
+            _ => None,
+        }
+    }
+    /// Give every node of the page tree its new reference, so other objects can point to pages.
+    fn promise_pages(&mut self, node: PageNode<()>) -> PageNode<PromisedRef<Primitive>> {
+        let promise = |repairer: &mut Self, old: PlainRef| {
+            let promise = repairer.out.promise::<Primitive>();
+            repairer.map.insert(old, promise.get_inner());
+            promise
+        };
+        match node {
+            PageNode::Pages { old, dict, kids, .. } => {
+                let new = promise(self, old);
+                let kids = kids.into_iter().map(|k| self.promise_pages(k)).collect();
+                PageNode::Pages { old, new, dict, kids }
+            }
+            PageNode::Page { old, dict, .. } => PageNode::Page { old, new: promise(self, old), dict },
+        }
+    }
+    fn write_pages(&mut self, node: PageNode<PromisedRef<Primitive>>, parent: Option<PlainRef>) -> Result<()> {
+        let count = node.count();
+        let (new, mut dict, kids) = match node {
+            PageNode::Pages { new, mut dict, kids, .. } => {
+                if dict.get("Count").and_then(|c| c.as_integer().ok()) != Some(count as i32) {
+                    self.report.page_counts += 1;
+                }
+                dict.insert("Type", Primitive::name("Pages"));
+                dict.insert("Count", Primitive::Integer(count as i32));
+                (new, dict, Some(kids))
+            }
+            PageNode::Page { new, mut dict, .. } => {
+                dict.insert("Type", Primitive::name("Page"));
+                (new, dict, None)
+            }
+        };
+        dict.remove("Parent");
+        let mut dict = self.map_dict(dict)?;
+        if let Some(parent) = parent {
+            dict.insert("Parent", Primitive::Reference(parent));
+        }
+        let new_ref = new.get_inner();
+        let kids = kids.unwrap_or_default();
+        if !matches!(dict.get("Type"), Some(Primitive::Name(n)) if n == "Page") {
+            let refs = kids.iter().map(|k| match *k {
+                PageNode::Pages { ref new, .. } | PageNode::Page { ref new, .. } => Primitive::Reference(new.get_inner()),
+            }).collect();
+            dict.insert("Kids", Primitive::Array(refs));
+        }
+        for kid in kids {
+            t!(self.write_pages(kid, Some(new_ref)));
+        }
+        t!(self.out.fulfill(new, Primitive::Dictionary(dict)));
+        Ok(())
+    }
+
+    /// The new reference for `old`. The object is written by [`Repairer::drain`].
+    fn map_ref(&mut self, old: PlainRef) -> PlainRef {
+        if let Some(&new) = self.map.get(&old) {
+            return new;
+        }
+        let promise = self.out.promise::<Primitive>();
+        let new = promise.get_inner();
+        self.map.insert(old, new);
+        self.queue.push_back((old, promise));
+        new
+    }
+    fn map(&mut self, p: Primitive) -> Result<Primitive> {
+        Ok(match p {
+            Primitive::Reference(r) => Primitive::Reference(self.map_ref(r)),
+            Primitive::Array(parts) => Primitive::Array(parts.into_iter().map(|p| self.map(p)).collect::<Result<_>>()?),
+            Primitive::Dictionary(dict) => Primitive::Dictionary(self.map_dict(dict)?),
+            Primitive::Stream(stream) => Primitive::Stream(self.map_stream(stream)?),
+            p => p,
+        })
+    }
+    fn map_dict(&mut self, dict: Dictionary) -> Result<Dictionary> {
+        let mut new = Dictionary::new();
+        for (key, val) in dict {
+            new.insert(key, self.map(val)?);
+        }
+        Ok(new)
+    }
+    fn map_stream(&mut self, stream: PdfStream) -> Result<PdfStream> {
+        let data = t!(stream.raw_data(self.resolve));
+        let PdfStream { mut info, .. } = stream;
+        let length = info.remove("Length")
+            .and_then(|l| l.resolve(self.resolve).ok())
+            .and_then(|l| l.as_usize().ok());
+        if length != Some(data.len()) {
+            self.report.stream_lengths += 1;
+        }
+        let mut info = self.map_dict(info)?;
+        info.insert("Length", Primitive::Integer(data.len() as i32));
+        Ok(PdfStream { info, inner: StreamInner::Pending { data } })
+    }
+    /// Write all objects that were given a new reference, and those they refer to.
+    fn drain(&mut self) -> Result<()> {
+        while let Some((old, promise)) = self.queue.pop_front() {
+            let p = match self.resolve.resolve(old).and_then(|p| self.map(p)) {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("replacing {:?} by null: {}", old, e);
+                    self.report.unreadable.push(old);
+                    Primitive::Null
+                }
+            };
+            t!(self.out.fulfill(promise, p));
+        }
+        Ok(())
+    }
 }
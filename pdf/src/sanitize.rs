@@ -0,0 +1,334 @@
+//! Finding and removing active content, see [`File::javascript`](crate::file::File::javascript)
+//! and [`File::sanitize`](crate::file::File::sanitize).
+//!
+//! Actions are found wherever they are reachable: the `OpenAction`, the additional actions (`AA`)
+//! of the document, pages, annotations and fields, the actions of links, outlines and widgets,
+//! `Next` chains and the document-level scripts in the `JavaScript` name tree.
+//! Scripts inside XFA forms are not handled.
+
+use crate::error::*;
+use crate::object::{Object, PlainRef, Resolve, Stream};
+use crate::primitive::{Dictionary, PdfString, Primitive};
+use crate::visit::{ObjectVisitor, Path, PathItem};
+
+/// Primitives nested deeper than this are left alone.
+const MAX_DEPTH: usize = 64;
+
+/// A JavaScript action found in the document.
+#[derive(Debug, Clone)]
+pub struct FoundScript {
+    /// Where the action is, like `trailer/Root/OpenAction` or `12 0 R/AA/K`.
+    pub path: String,
+    pub code: String,
+}
+
+/// Whether `dict` is an action that runs JavaScript.
+pub fn is_javascript(dict: &Dictionary) -> bool {
+    matches!(dict.get("S"), Some(Primitive::Name(s)) if s == "JavaScript")
+}
+
+fn script_code(action: &Dictionary, resolve: &impl Resolve) -> Result<String> {
+    let js = match action.get("JS") {
+        Some(js) => t!(js.clone().resolve(resolve)),
+        None => return Ok(String::new()),
+    };
+    match js {
+        Primitive::String(s) => Ok(s.to_string_lossy()),
+        p => {
+            let stream = t!(Stream::<()>::from_primitive(p, resolve));
+            let data = t!(stream.data(resolve));
+            Ok(PdfString::new(data.as_ref().into()).to_string_lossy())
+        }
+    }
+}
+
+/// Every JavaScript action reachable from `root` (usually the trailer).
+pub fn find_javascript(root: &Primitive, resolve: &impl Resolve) -> Vec<FoundScript> {
+    struct Finder<'a, R> {
+        resolve: &'a R,
+        found: Vec<FoundScript>,
+    }
+    impl<R: Resolve> ObjectVisitor for Finder<'_, R> {
+        fn visit(&mut self, path: Path, primitive: &Primitive) -> bool {
+            let dict = match *primitive {
+                Primitive::Dictionary(ref dict) => dict,
+                _ => return true,
+            };
+            let code = if is_javascript(dict) {
+                script_code(dict, self.resolve)
+            } else if matches!(dict.get("S"), Some(Primitive::Name(s)) if s == "Rendition") && dict.get("JS").is_some() {
+                // rendition actions can run a script as well
+                script_code(dict, self.resolve)
+            } else {
+                return true;
+            };
+            let code = code.unwrap_or_else(|e| {
+                warn!("can't read the script at {}: {}", path, e);
+                String::new()
+            });
+            self.found.push(FoundScript { path: path.to_string(), code });
+            true
+        }
+    }
+    let mut finder = Finder { resolve, found: vec![] };
+    crate::visit::visit(root, resolve, &mut finder);
+    finder.found
+}
+
+/// The objects reachable from `root`.
+pub(crate) fn reachable_objects(root: &Primitive, resolve: &impl Resolve) -> Vec<PlainRef> {
+    struct Objects(Vec<PlainRef>);
+    impl ObjectVisitor for Objects {
+        fn visit(&mut self, path: Path, _: &Primitive) -> bool {
+            if let (Some(r), true) = (path.obj, path.items.is_empty()) {
+                self.0.push(r);
+            }
+            true
+        }
+    }
+    let mut objects = Objects(vec![]);
+    crate::visit::visit(root, resolve, &mut objects);
+    objects.0
+}
+
+/// The kinds of active content that [`File::sanitize`](crate::file::File::sanitize) removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActiveContent {
+    /// JavaScript actions, scripts of rendition actions and the `JavaScript` name tree
+    JavaScript,
+    /// `Launch` actions, which start programs or open files
+    Launch,
+    /// `SubmitForm` actions, which send form data somewhere
+    SubmitForm,
+    /// `ImportData` actions, which load form data from a file
+    ImportData,
+    /// `GoToR` actions, which open another PDF file
+    RemoteGoTo,
+    /// the `EmbeddedFiles` name tree, file attachment annotations and the embedded data of file specifications
+    EmbeddedFile,
+    /// `RichMedia` annotations and the older multimedia annotations (`Screen`, `Movie` and `Sound`)
+    RichMedia,
+}
+
+/// What [`File::sanitize`](crate::file::File::sanitize) removes. By default, everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SanitizeOptions {
+    pub javascript: bool,
+    pub launch: bool,
+    /// `SubmitForm` and `ImportData` actions
+    pub form_actions: bool,
+    pub remote_goto: bool,
+    pub embedded_files: bool,
+    pub rich_media: bool,
+}
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        SanitizeOptions {
+            javascript: true,
+            launch: true,
+            form_actions: true,
+            remote_goto: true,
+            embedded_files: true,
+            rich_media: true,
+        }
+    }
+}
+impl SanitizeOptions {
+    /// Only remove JavaScript.
+    pub fn javascript() -> Self {
+        SanitizeOptions {
+            javascript: true,
+            launch: false,
+            form_actions: false,
+            remote_goto: false,
+            embedded_files: false,
+            rich_media: false,
+        }
+    }
+
+    pub fn removes(&self, kind: ActiveContent) -> bool {
+        match kind {
+            ActiveContent::JavaScript => self.javascript,
+            ActiveContent::Launch => self.launch,
+            ActiveContent::SubmitForm | ActiveContent::ImportData => self.form_actions,
+            ActiveContent::RemoteGoTo => self.remote_goto,
+            ActiveContent::EmbeddedFile => self.embedded_files,
+            ActiveContent::RichMedia => self.rich_media,
+        }
+    }
+
+    /// The kind of `dict` if it is an action that has to go.
+    fn action(&self, dict: &Dictionary) -> Option<ActiveContent> {
+        let kind = match dict.get("S")? {
+            Primitive::Name(s) => match s.as_str() {
+                "JavaScript" => ActiveContent::JavaScript,
+                "Launch" => ActiveContent::Launch,
+                "SubmitForm" => ActiveContent::SubmitForm,
+                "ImportData" => ActiveContent::ImportData,
+                "GoToR" => ActiveContent::RemoteGoTo,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.removes(kind).then_some(kind)
+    }
+
+    /// The kind of `dict` if it is an annotation that has to go.
+    fn annotation(&self, dict: &Dictionary) -> Option<ActiveContent> {
+        let kind = match dict.get("Subtype")? {
+            Primitive::Name(s) => match s.as_str() {
+                "FileAttachment" => ActiveContent::EmbeddedFile,
+                "RichMedia" | "Screen" | "Movie" | "Sound" => ActiveContent::RichMedia,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.removes(kind).then_some(kind)
+    }
+}
+
+/// Something that [`File::sanitize`](crate::file::File::sanitize) removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removal {
+    /// Where it was, like `3 0 R/AA/O` or `12 0 R/Annots[2]`.
+    pub path: String,
+    pub kind: ActiveContent,
+}
+
+/// What [`File::sanitize`](crate::file::File::sanitize) removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SanitizeReport {
+    pub removed: Vec<Removal>,
+}
+impl SanitizeReport {
+    pub fn count(&self, kind: ActiveContent) -> usize {
+        self.removed.iter().filter(|r| r.kind == kind).count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+    fn add(&mut self, obj: PlainRef, path: &[PathItem], kind: ActiveContent) {
+        let path = Path { obj: Some(obj), items: path }.to_string();
+        self.removed.push(Removal { path, kind });
+    }
+}
+
+/// Remove the active content from `p` (object `obj`) and the primitives inside of it, but not from other objects.
+///
+/// Entries that hold an action (or a reference to one) are removed, actions are taken out of `Next` chains,
+/// annotations out of `Annots` arrays, and the `JavaScript` and `EmbeddedFiles` name trees are dropped.
+pub(crate) fn sanitize(p: &mut Primitive, obj: PlainRef, options: &SanitizeOptions, resolve: &impl Resolve, report: &mut SanitizeReport) {
+    let mut path = vec![];
+    sanitize_inner(p, obj, &mut path, options, resolve, report, 0);
+}
+
+fn sanitize_inner(p: &mut Primitive, obj: PlainRef, path: &mut Vec<PathItem>, options: &SanitizeOptions, resolve: &impl Resolve, report: &mut SanitizeReport, depth: usize) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    // direct or referenced dictionaries
+    let dict_of = |p: &Primitive| match *p {
+        Primitive::Dictionary(ref dict) => Some(dict.clone()),
+        Primitive::Reference(r) => resolve.resolve(r).ok().and_then(|p| p.into_dictionary().ok()),
+        _ => None,
+    };
+
+    let dict = match *p {
+        Primitive::Dictionary(ref mut dict) => dict,
+        Primitive::Stream(ref mut stream) => &mut stream.info,
+        Primitive::Array(ref mut parts) => {
+            for (i, part) in parts.iter_mut().enumerate() {
+                path.push(PathItem::Index(i));
+                sanitize_inner(part, obj, path, options, resolve, report, depth + 1);
+                path.pop();
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    let mut removals = vec![];
+    for (key, val) in dict.iter() {
+        let kind = match key.as_str() {
+            "JavaScript" if options.javascript => Some(ActiveContent::JavaScript),
+            "EmbeddedFiles" | "EF" if options.embedded_files => Some(ActiveContent::EmbeddedFile),
+            "JS" if options.javascript && matches!(dict.get("S"), Some(Primitive::Name(s)) if s == "Rendition") => Some(ActiveContent::JavaScript),
+            _ => dict_of(val).and_then(|d| options.action(&d)),
+        };
+        if let Some(kind) = kind {
+            removals.push((key.clone(), kind));
+        }
+    }
+    for (key, kind) in removals {
+        path.push(PathItem::Key(key.clone()));
+        report.add(obj, path, kind);
+        path.pop();
+        dict.remove(key.as_str());
+    }
+
+    for (key, filter) in [("Next", SanitizeOptions::action as fn(&SanitizeOptions, &Dictionary) -> Option<ActiveContent>), ("Annots", SanitizeOptions::annotation)] {
+        if let Some(Primitive::Array(ref mut parts)) = dict.get_mut(key) {
+            path.push(PathItem::Key(key.into()));
+            let mut i = 0;
+            parts.retain(|part| {
+                let kind = dict_of(part).and_then(|d| filter(options, &d));
+                if let Some(kind) = kind {
+                    path.push(PathItem::Index(i));
+                    report.add(obj, path, kind);
+                    path.pop();
+                }
+                i += 1;
+                kind.is_none()
+            });
+            path.pop();
+        }
+    }
+
+    for (key, val) in dict.iter_mut() {
+        path.push(PathItem::Key(key.clone()));
+        sanitize_inner(val, obj, path, options, resolve, report, depth + 1);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::NoResolve;
+    use crate::primitive::Name;
+
+    fn action(s: &str) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.insert("S", Name::from(s));
+        dict
+    }
+
+    #[test]
+    fn strip() {
+        let mut js = action("JavaScript");
+        js.insert("JS", PdfString::from("app.alert(1)"));
+        let mut uri = action("URI");
+        uri.insert("Next", Primitive::Array(vec![js.clone().into()]));
+        let mut aa = Dictionary::new();
+        aa.insert("O", js.clone());
+        aa.insert("C", uri);
+        let mut page = Dictionary::new();
+        page.insert("AA", aa);
+        page.insert("Type", Name::from("Page"));
+        let mut page = Primitive::Dictionary(page);
+
+        let found = find_javascript(&page, &NoResolve);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].path, "trailer/AA/O");
+        assert_eq!(found[0].code, "app.alert(1)");
+
+        let mut report = SanitizeReport::default();
+        sanitize(&mut page, PlainRef { id: 3, gen: 0 }, &SanitizeOptions::javascript(), &NoResolve, &mut report);
+        assert_eq!(report.count(ActiveContent::JavaScript), 2);
+        assert_eq!(report.removed[0].path, "3 0 R/AA/O");
+        assert!(find_javascript(&page, &NoResolve).is_empty());
+        let aa = page.as_dictionary().unwrap().get("AA").unwrap().as_dictionary().unwrap();
+        assert!(aa.get("O").is_none());
+        assert!(aa.get("C").is_some());
+    }
+}
@@ -0,0 +1,388 @@
+//! Converting pages to SVG.
+//!
+//! Paths, clipping, images and text are written as SVG elements in the coordinate system of the page,
+//! in points. Shadings, dashes on non-uniformly scaled strokes, soft masks and blend modes are not supported.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+use crate::content::{LineCap, LineJoin, Matrix, Op, Point, TextMode, Winding};
+use crate::enc::StreamFilter;
+use crate::error::*;
+use crate::font::Font;
+use crate::geom::ops_to_svg_path;
+use crate::interpret::{device_matrix, Clip, ContentInterpreter, Event, GraphicsState, Glyph, ImageSource, OutlineCache, Path, PathSegment};
+use crate::object::*;
+
+/// How to write text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SvgText {
+    /// Glyph outlines as paths. Looks like the PDF, but the text can't be selected.
+    /// Fonts without embedded outlines are written as `<text>`.
+    #[default]
+    Paths,
+    /// `<text>` elements referring to the font by name, which the viewer may not have.
+    Fonts,
+}
+
+/// Convert `page` to an SVG document.
+///
+/// The crop box is converted and `Rotate` is applied.
+pub fn page_to_svg(page: &Page, resolve: &impl Resolve, text: SvgText) -> Result<String> {
+    let (ctm, width, height) = t!(device_matrix(page, 1.));
+    let mut writer = SvgWriter {
+        out: String::new(),
+        text,
+        clips: HashMap::new(),
+        outlines: OutlineCache::new(),
+    };
+    let _ = writeln!(
+        writer.out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}pt" height="{h}pt" viewBox="0 0 {w} {h}">"#,
+        w = Num(width),
+        h = Num(height)
+    );
+    if let Some(ref contents) = page.contents {
+        let ops = t!(contents.operations(resolve));
+        let mut interpreter = ContentInterpreter::new(page.resources().ok().cloned(), ctm, resolve);
+        t!(interpreter.run(&ops, &mut |event, state| writer.event(event, state, resolve)));
+    }
+    writer.out.push_str("</svg>\n");
+    Ok(writer.out)
+}
+
+/// Numbers rounded to three decimals, without trailing zeros.
+struct Num(f32);
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // adding zero turns -0 into 0
+        write!(f, "{}", (self.0 * 1000.).round() / 1000. + 0.)
+    }
+}
+
+struct Color([f32; 3]);
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.0.map(|c| (c.clamp(0., 1.) * 255. + 0.5) as u8);
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+struct SvgMatrix(Matrix);
+impl fmt::Display for SvgMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = &self.0;
+        write!(f, "matrix({} {} {} {} {} {})", Num(m.a), Num(m.b), Num(m.c), Num(m.d), Num(m.e), Num(m.f))
+    }
+}
+
+/// Escapes text for use in content and attribute values.
+struct Escaped<'a>(&'a str);
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                // not allowed in XML
+                '\0'..='\x08' | '\x0b' | '\x0c' | '\x0e'..='\x1f' => {}
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn path_data(path: &Path) -> String {
+    let ops: Vec<Op> = path.segments.iter().map(|s| match *s {
+        PathSegment::MoveTo(p) => Op::MoveTo { p },
+        PathSegment::LineTo(p) => Op::LineTo { p },
+        PathSegment::CurveTo(c1, c2, p) => Op::CurveTo { c1, c2, p },
+        PathSegment::Close => Op::Close,
+    }).collect();
+    ops_to_svg_path(&ops, Matrix::default())
+}
+
+fn fill_rule(winding: Winding) -> &'static str {
+    match winding {
+        Winding::NonZero => "nonzero",
+        Winding::EvenOdd => "evenodd",
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// The image as a data URL. JPEG images are passed through, everything else is converted to PNG.
+fn image_url(image: &ImageXObject, fill: [f32; 3], resolve: &impl Resolve) -> Result<String> {
+    let jpeg = !image.image_mask
+        && image.decode.is_none()
+        && image.smask.is_none()
+        && image.mask.is_none()
+        && matches!(image.color_space, Some(ColorSpace::DeviceGray | ColorSpace::DeviceRGB));
+    if jpeg {
+        if let (data, Some(StreamFilter::DCTDecode(_))) = t!(image.raw_image_data(resolve)) {
+            return Ok(format!("data:image/jpeg;base64,{}", base64(&data)));
+        }
+    }
+    let mut rgba = t!(image.masked_rgba_data(resolve));
+    if image.image_mask {
+        let color = fill.map(|c| (c.clamp(0., 1.) * 255. + 0.5) as u8);
+        for px in rgba.chunks_exact_mut(4) {
+            px[..3].copy_from_slice(&color);
+        }
+    }
+    let png = t!(png_rgba(image.width, image.height, &rgba));
+    Ok(format!("data:image/png;base64,{}", base64(&png)))
+}
+
+/// The family to ask the viewer for, without the subset prefix.
+fn font_family(font: &Font) -> &str {
+    let name = font.name.as_deref().unwrap_or("serif");
+    match name.split_once('+') {
+        Some((prefix, rest)) if prefix.len() == 6 && prefix.bytes().all(|b| b.is_ascii_uppercase()) => rest,
+        _ => name,
+    }
+}
+
+struct SvgWriter {
+    out: String,
+    text: SvgText,
+    /// ids of the clip paths written so far, keyed by the chain of clips they intersect (which is kept alive)
+    clips: HashMap<Vec<usize>, (Vec<Arc<Clip>>, usize)>,
+    outlines: OutlineCache,
+}
+
+impl SvgWriter {
+    /// Write the clip paths needed for `clip` and return the attribute referring to them.
+    fn clip_attr(&mut self, clip: &[Arc<Clip>]) -> String {
+        let mut parent = None;
+        for n in 1..=clip.len() {
+            let key: Vec<usize> = clip[..n].iter().map(|c| Arc::as_ptr(c) as usize).collect();
+            let id = match self.clips.get(&key) {
+                Some(&(_, id)) => id,
+                None => {
+                    let id = self.clips.len();
+                    let c = &clip[n - 1];
+                    let _ = write!(self.out, r#"<clipPath id="c{}""#, id);
+                    if let Some(parent) = parent {
+                        let _ = write!(self.out, r#" clip-path="url(#c{})""#, parent);
+                    }
+                    let _ = writeln!(self.out, r#"><path d="{}" clip-rule="{}"/></clipPath>"#, path_data(&c.path), fill_rule(c.winding));
+                    self.clips.insert(key, (clip[..n].to_vec(), id));
+                    id
+                }
+            };
+            parent = Some(id);
+        }
+        match parent {
+            Some(id) => format!(r#" clip-path="url(#c{})""#, id),
+            None => String::new(),
+        }
+    }
+
+    fn fill_attrs(&self, winding: Winding, state: &GraphicsState) -> String {
+        let mut attrs = format!(r#"fill="{}""#, Color(state.fill_rgb()));
+        if winding == Winding::EvenOdd {
+            attrs.push_str(r#" fill-rule="evenodd""#);
+        }
+        if state.fill_alpha < 1. {
+            let _ = write!(attrs, r#" fill-opacity="{}""#, Num(state.fill_alpha));
+        }
+        attrs
+    }
+
+    fn stroke_attrs(&self, state: &GraphicsState) -> String {
+        // paths are in device space already, so the line width is scaled too
+        let m = &state.ctm;
+        let scale = (m.a * m.d - m.b * m.c).abs().sqrt();
+        let mut attrs = format!(
+            r#"stroke="{}" stroke-width="{}""#,
+            Color(state.stroke_rgb()),
+            Num(state.line_width * scale)
+        );
+        match state.line_cap {
+            LineCap::Butt => {}
+            LineCap::Round => attrs.push_str(r#" stroke-linecap="round""#),
+            LineCap::Square => attrs.push_str(r#" stroke-linecap="square""#),
+        }
+        match state.line_join {
+            LineJoin::Miter => {
+                if state.miter_limit != 4. {
+                    let _ = write!(attrs, r#" stroke-miterlimit="{}""#, Num(state.miter_limit.max(1.)));
+                }
+            }
+            LineJoin::Round => attrs.push_str(r#" stroke-linejoin="round""#),
+            LineJoin::Bevel => attrs.push_str(r#" stroke-linejoin="bevel""#),
+        }
+        if !state.dash_pattern.is_empty() && state.dash_pattern.iter().any(|&d| d > 0.) {
+            let dashes: Vec<String> = state.dash_pattern.iter().map(|&d| Num(d * scale).to_string()).collect();
+            let _ = write!(attrs, r#" stroke-dasharray="{}""#, dashes.join(" "));
+            if state.dash_phase != 0. {
+                let _ = write!(attrs, r#" stroke-dashoffset="{}""#, Num(state.dash_phase * scale));
+            }
+        }
+        if state.stroke_alpha < 1. {
+            let _ = write!(attrs, r#" stroke-opacity="{}""#, Num(state.stroke_alpha));
+        }
+        attrs
+    }
+
+    /// Write `glyphs` as one `<text>` element, placing each glyph if the font tells their widths.
+    fn text_run(&mut self, glyphs: &[&Glyph], font: &Font, attrs: &str, clip: &str) {
+        let glyphs: Vec<(&Glyph, &str)> = glyphs.iter()
+            .filter_map(|g| Some((*g, g.unicode.as_deref()?)))
+            .collect();
+        let (first, inverse) = match glyphs.first() {
+            Some(&(g, _)) => match g.matrix.invert() {
+                Some(inverse) => (g, inverse),
+                None => return,
+            },
+            None => return,
+        };
+        // SVG text has the y axis pointing down
+        let m = &first.matrix;
+        let m = Matrix { a: m.a, b: m.b, c: -m.c, d: -m.d, e: m.e, f: m.f };
+        let _ = write!(
+            self.out,
+            r#"<text transform="{}" font-family="{}" font-size="1" {}{}"#,
+            SvgMatrix(m), Escaped(font_family(font)), attrs, clip
+        );
+        if glyphs.iter().all(|(g, _)| g.advance == 0.) {
+            // the glyphs are not placed, let the viewer lay them out
+            let text: String = glyphs.iter().map(|(_, s)| *s).collect();
+            let _ = writeln!(self.out, r#" xml:space="preserve">{}</text>"#, Escaped(&text));
+            return;
+        }
+        self.out.push('>');
+        for (g, text) in glyphs {
+            if text.trim().is_empty() {
+                continue;
+            }
+            let origin = inverse.transform_point(Point { x: g.matrix.e, y: g.matrix.f });
+            let _ = write!(self.out, r#"<tspan x="{}" y="{}">{}</tspan>"#, Num(origin.x), Num(-origin.y), Escaped(text));
+        }
+        self.out.push_str("</text>\n");
+    }
+
+    fn text(&mut self, glyphs: &[Glyph], font: &MaybeRef<Font>, state: &GraphicsState, resolve: &impl Resolve) {
+        let (stroke, fill) = match state.text.render_mode {
+            TextMode::Fill | TextMode::FillAndClip => (false, true),
+            TextMode::Stroke | TextMode::StrokeAndClip => (true, false),
+            TextMode::FillThenStroke => (true, true),
+            TextMode::Invisible => (false, false),
+        };
+        let clip = self.clip_attr(&state.clip);
+        let attrs = match (fill, stroke) {
+            (true, true) => format!("{} {}", self.fill_attrs(Winding::NonZero, state), self.stroke_attrs(state)),
+            (true, false) => self.fill_attrs(Winding::NonZero, state),
+            (false, true) => format!(r#"fill="none" {}"#, self.stroke_attrs(state)),
+            // keep invisible text, so it can still be selected
+            (false, false) => r#"fill-opacity="0""#.into(),
+        };
+        // <text> is scaled to the font size, the stroke width is not
+        let text_attrs = match stroke {
+            true => format!(r#"{} vector-effect="non-scaling-stroke""#, attrs),
+            false => attrs.clone(),
+        };
+        let mut unpainted = vec![];
+        for glyph in glyphs {
+            if self.text == SvgText::Paths && (fill || stroke) {
+                if let Some(path) = self.outlines.glyph_path(font, glyph, resolve) {
+                    if !path.segments.is_empty() {
+                        let _ = writeln!(self.out, r#"<path d="{}" {}{}/>"#, path_data(&path), attrs, clip);
+                    }
+                    continue;
+                }
+            }
+            unpainted.push(glyph);
+        }
+        self.text_run(&unpainted, font, &text_attrs, &clip);
+    }
+
+    fn image(&mut self, image: &ImageXObject, matrix: &Matrix, state: &GraphicsState, resolve: &impl Resolve) -> Result<()> {
+        if image.width == 0 || image.height == 0 {
+            return Ok(());
+        }
+        let url = t!(image_url(image, state.fill_rgb(), resolve));
+        let clip = self.clip_attr(&state.clip);
+        // the first row of the image is at the top of the unit square
+        let flip = Matrix { a: 1., b: 0., c: 0., d: -1., e: 0., f: 1. };
+        let _ = write!(
+            self.out,
+            r#"<image width="1" height="1" preserveAspectRatio="none" transform="{}""#,
+            SvgMatrix(flip * *matrix)
+        );
+        if state.fill_alpha < 1. {
+            let _ = write!(self.out, r#" opacity="{}""#, Num(state.fill_alpha));
+        }
+        if !image.interpolate {
+            self.out.push_str(r#" style="image-rendering:pixelated""#);
+        }
+        let _ = writeln!(self.out, r#"{} href="{}"/>"#, clip, url);
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event, state: &GraphicsState, resolve: &impl Resolve) -> Result<()> {
+        match *event {
+            Event::PathFilled { path, winding } => {
+                let clip = self.clip_attr(&state.clip);
+                let attrs = self.fill_attrs(winding, state);
+                let _ = writeln!(self.out, r#"<path d="{}" {}{}/>"#, path_data(path), attrs, clip);
+            }
+            Event::PathStroked { path } => {
+                let clip = self.clip_attr(&state.clip);
+                let attrs = self.stroke_attrs(state);
+                let _ = writeln!(self.out, r#"<path d="{}" fill="none" {}{}/>"#, path_data(path), attrs, clip);
+            }
+            Event::TextShown { glyphs, font: Some(font) } => {
+                self.text(glyphs, font, state, resolve);
+            }
+            Event::TextShown { font: None, .. } => {}
+            Event::ImageDrawn { image, ref matrix } => {
+                let image = match image {
+                    ImageSource::XObject { image, .. } => image,
+                    ImageSource::Inline(inline) => &*inline.image,
+                };
+                if let Err(e) = self.image(image, matrix, state, resolve) {
+                    warn!("can't convert image: {:?}", e);
+                }
+            }
+            Event::ShadingPainted { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(Num(0.1 + 0.2).to_string(), "0.3");
+        assert_eq!(Num(-0.0001).to_string(), "0");
+        assert_eq!(Escaped("a<b & \"c\"").to_string(), "a&lt;b &amp; &quot;c&quot;");
+    }
+}
@@ -0,0 +1,725 @@
+//! Operations on the text of content streams.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cmap::CMap;
+use crate::content::{Matrix, Op, Point, TextDrawAdjusted};
+use crate::encoding::BaseEncoding;
+use crate::encoding::Encoding;
+use crate::error::*;
+use crate::font::{Font, FontData, FontType, TFont, VerticalMetric, VerticalMetrics, Widths};
+use crate::interpret::{ContentInterpreter, Event};
+use crate::object::*;
+use crate::primitive::{Dictionary, Name, PdfString};
+
+/// Mapping between character codes and unicode for one font.
+pub(crate) struct FontCodec {
+    pub(crate) two_byte: bool,
+    /// splits the strings of CID fonts into codes, without it they are two bytes each
+    cmap: Option<Arc<CMap>>,
+    /// set when the CMap writes vertically
+    vertical: Option<VerticalMetrics>,
+    decode: HashMap<u16, String>,
+    encode: HashMap<char, u16>,
+    widths: Option<Widths>,
+}
+impl FontCodec {
+    pub(crate) fn new(font: &Font, resolve: &impl Resolve) -> Result<FontCodec> {
+        let two_byte = font.is_cid();
+        let mut decode = HashMap::new();
+        if let Some(map) = font.to_unicode(resolve).transpose()? {
+            for (code, s) in map.iter() {
+                decode.insert(code, s.to_string());
+            }
+        } else if !two_byte {
+            for code in 0..=255u8 {
+                match (font.decode_byte(code), font.glyph_name(code)) {
+                    (Some(s), _) => {
+                        decode.insert(code as u16, s);
+                    }
+                    // guess ASCII if the encoding is unknown
+                    (None, None) if (0x20..0x7f).contains(&code) => {
+                        decode.insert(code as u16, (code as char).to_string());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // prefer the lowest code if a character can be encoded more than one way
+        let mut codes: Vec<_> = decode.iter().collect();
+        codes.sort_by_key(|&(&code, _)| std::cmp::Reverse(code));
+        let encode = codes.into_iter()
+            .filter_map(|(&code, s)| {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some((c, code)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        // an unavailable CMap is not fatal, most of them use two byte codes
+        let cmap = font.cmap(resolve).unwrap_or_else(|e| {
+            resolve.options().warn(Warning::Ignored { error: e.to_string() });
+            None
+        });
+
+        let vertical = match cmap {
+            Some(ref cmap) if cmap.is_vertical() => font.vertical_metrics(resolve)?,
+            _ => None,
+        };
+
+        Ok(FontCodec {
+            two_byte,
+            cmap,
+            vertical,
+            decode,
+            encode,
+            widths: font.widths(resolve)?,
+        })
+    }
+    pub(crate) fn codes(&self, data: &[u8]) -> Vec<u16> {
+        self.codes_with_len(data).into_iter().map(|(code, _)| code).collect()
+    }
+    /// The codes in `data` with the number of bytes each takes.
+    pub(crate) fn codes_with_len(&self, data: &[u8]) -> Vec<(u16, usize)> {
+        if let Some(ref cmap) = self.cmap {
+            cmap.codes(data).map(|(code, len)| (code as u16, len)).collect()
+        } else if self.two_byte {
+            data.chunks_exact(2).map(|c| (u16::from_be_bytes([c[0], c[1]]), 2)).collect()
+        } else {
+            data.iter().map(|&b| (b as u16, 1)).collect()
+        }
+    }
+    pub(crate) fn bytes(&self, codes: &[u16]) -> PdfString {
+        let mut data = Vec::with_capacity(codes.len() * 2);
+        for &code in codes {
+            let len = match self.cmap {
+                Some(ref cmap) => cmap.code_len(code as u32).unwrap_or(2),
+                None if self.two_byte => 2,
+                None => 1,
+            };
+            if len > 1 {
+                data.extend_from_slice(&(code as u32).to_be_bytes()[4 - len..]);
+            } else {
+                data.push(code as u8);
+            }
+        }
+        PdfString::new(data.as_slice().into())
+    }
+    pub(crate) fn decode(&self, code: u16) -> Option<&str> {
+        self.decode.get(&code).map(|s| s.as_str())
+    }
+    fn encode(&self, text: &str) -> Option<Vec<u16>> {
+        text.chars().map(|c| self.encode.get(&c).cloned()).collect()
+    }
+    /// The CID of `code` in a CID font.
+    pub(crate) fn cid(&self, code: u16) -> Option<u16> {
+        match self.cmap {
+            Some(ref cmap) => {
+                let len = cmap.code_len(code as u32)?;
+                Some(cmap.cid(code as u32, len).unwrap_or(0) as u16)
+            }
+            None if self.two_byte => Some(code),
+            None => None,
+        }
+    }
+    /// Whether word spacing applies to `code`: it is the single byte code 32.
+    pub(crate) fn is_space(&self, code: u16) -> bool {
+        code == 32 && match self.cmap {
+            Some(ref cmap) => cmap.code_len(32) == Some(1),
+            None => !self.two_byte,
+        }
+    }
+    /// glyph width in thousandths of text space units
+    pub(crate) fn width(&self, code: u16) -> Option<f32> {
+        let index = self.cid(code).unwrap_or(code);
+        self.widths.as_ref().map(|w| w.get(index as usize))
+    }
+    pub(crate) fn is_vertical(&self) -> bool {
+        self.vertical.is_some()
+    }
+    /// The vertical metrics of `code` if the font writes vertically.
+    pub(crate) fn vertical(&self, code: u16) -> Option<VerticalMetric> {
+        let metrics = self.vertical.as_ref()?;
+        Some(metrics.get(self.cid(code)? as usize, self.width(code).unwrap_or(1000.)))
+    }
+}
+
+/// The text state parameters that are part of the graphics state.
+#[derive(Clone)]
+struct TextParams {
+    font: Option<(Name, f32)>,
+    char_space: f32,
+    word_space: f32,
+    horiz_scale: f32,
+    leading: f32,
+}
+
+/// Result of [`replace_text`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaceStats {
+    /// Occurrences that were re-encoded with the font of the text run.
+    pub reencoded: usize,
+    /// Occurrences that could not be encoded with the original font.
+    /// They were removed and drawn on top with a standard font instead.
+    pub overlaid: usize,
+    /// Occurrences that were left alone, because the glyph widths of the font are unknown,
+    /// or neither the font nor the standard font can encode the replacement.
+    pub skipped: usize,
+}
+
+struct Replacer<'a, R, U> {
+    find: &'a str,
+    replace: &'a str,
+    resources: &'a mut Resources,
+    resolve: &'a R,
+    update: &'a mut U,
+    codecs: HashMap<Name, Option<FontCodec>>,
+    fallback_codec: Option<FontCodec>,
+    fallback_font: Option<Name>,
+    stats: ReplaceStats,
+
+    params: TextParams,
+    stack: Vec<TextParams>,
+    line_matrix: Matrix,
+    /// offset of the current text position from the start of the line, in text space
+    x: f32,
+
+    out: Vec<Op>,
+}
+
+fn translate(x: f32, y: f32, m: &Matrix) -> Matrix {
+    Matrix {
+        e: x * m.a + y * m.c + m.e,
+        f: x * m.b + y * m.d + m.f,
+        ..*m
+    }
+}
+
+impl<'a, R: Resolve, U: Updater> Replacer<'a, R, U> {
+    fn codec(&mut self) -> Result<Option<(&FontCodec, Name, f32)>> {
+        let (name, size) = match self.params.font {
+            Some((ref name, size)) => (name.clone(), size),
+            None => return Ok(None),
+        };
+        if !self.codecs.contains_key(&name) {
+            let codec = match self.resources.fonts.get(&name) {
+                Some(font) => Some(FontCodec::new(&*font.load(self.resolve)?, self.resolve)?),
+                None => None,
+            };
+            self.codecs.insert(name.clone(), codec);
+        }
+        Ok(self.codecs[&name].as_ref().map(|c| (c, name, size)))
+    }
+
+    fn advance(&self, codec: &FontCodec, size: f32, codes: &[u16]) -> Option<f32> {
+        let mut sum = 0.;
+        for &code in codes {
+            let w = codec.width(code)?;
+            let word_space = if codec.is_space(code) { self.params.word_space } else { 0. };
+            sum += (w / 1000. * size + self.params.char_space + word_space) * self.params.horiz_scale;
+        }
+        Some(sum)
+    }
+
+    /// The name of the standard font in the resources and the replacement encoded with it,
+    /// if it can be.
+    fn fallback(&mut self) -> Result<Option<(Name, PdfString)>> {
+        if self.fallback_codec.is_none() {
+            self.fallback_codec = Some(t!(FontCodec::new(&fallback_font(), self.resolve)));
+        }
+        let codec = self.fallback_codec.as_ref().unwrap();
+        let text = match codec.encode(self.replace) {
+            Some(codes) => codec.bytes(&codes),
+            None => return Ok(None),
+        };
+        if let Some(ref name) = self.fallback_font {
+            return Ok(Some((name.clone(), text)));
+        }
+        let mut n = 0;
+        let name: Name = loop {
+            let name = Name::from(format!("ReplF{}", n));
+            if !self.resources.fonts.contains_key(&name) {
+                break name;
+            }
+            n += 1;
+        };
+        self.resources.fonts.insert(name.clone(), Lazy::safe(fallback_font(), &mut *self.update)?);
+        self.fallback_font = Some(name.clone());
+        Ok(Some((name, text)))
+    }
+
+    fn show(&mut self, items: &[TextDrawAdjusted], single: bool) -> Result<()> {
+        let (size, name) = match self.codec()? {
+            Some((_, name, size)) => (size, name),
+            None => {
+                self.out.push(show_op(items.to_vec(), single));
+                return Ok(());
+            }
+        };
+        let codec = self.codecs.remove(&name).flatten().unwrap();
+        let result = self.show_with(&codec, &name, size, items, single);
+        self.codecs.insert(name, Some(codec));
+        result
+    }
+
+    fn show_with(&mut self, codec: &FontCodec, font: &Name, size: f32, items: &[TextDrawAdjusted], single: bool) -> Result<()> {
+        let scale = size * self.params.horiz_scale;
+        let mut pending: Vec<TextDrawAdjusted> = vec![];
+        let mut changed = false;
+
+        for item in items {
+            let text = match *item {
+                TextDrawAdjusted::Spacing(n) => {
+                    self.x -= n / 1000. * scale;
+                    pending.push(item.clone());
+                    continue;
+                }
+                TextDrawAdjusted::Text(ref text) => text,
+            };
+            let codes = codec.codes(text.as_bytes());
+
+            // the text of each glyph, and the byte offsets into the decoded string
+            let mut decoded = String::new();
+            let mut starts = Vec::with_capacity(codes.len() + 1);
+            for &code in &codes {
+                starts.push(decoded.len());
+                decoded.push_str(codec.decode(code).unwrap_or("\u{FFFD}"));
+            }
+            starts.push(decoded.len());
+
+            let mut pos = 0; // in glyphs
+            let mut search = 0; // in bytes
+            while let Some(found) = decoded[search..].find(self.find).map(|i| i + search) {
+                let end = found + self.find.len();
+                let (i, j) = match (starts.binary_search(&found), starts.binary_search(&end)) {
+                    (Ok(i), Ok(j)) if i < j => (i, j),
+                    _ => {
+                        search = found + 1;
+                        while !decoded.is_char_boundary(search) {
+                            search += 1;
+                        }
+                        continue;
+                    }
+                };
+                search = end;
+
+                let old_width = self.advance(codec, size, &codes[i..j]);
+                let prefix = &codes[pos..i];
+                if !prefix.is_empty() {
+                    pending.push(TextDrawAdjusted::Text(codec.bytes(prefix)));
+                    self.x += self.advance(codec, size, prefix).unwrap_or(0.);
+                }
+                pos = j;
+
+                match (codec.encode(self.replace), old_width) {
+                    (Some(new_codes), old_width) => {
+                        pending.push(TextDrawAdjusted::Text(codec.bytes(&new_codes)));
+                        if let (Some(old), Some(new)) = (old_width, self.advance(codec, size, &new_codes)) {
+                            // keep the following text where it was
+                            if (old - new).abs() > 1e-3 {
+                                pending.push(TextDrawAdjusted::Spacing(-(old - new) * 1000. / scale));
+                            }
+                            self.x += old;
+                        }
+                        self.stats.reencoded += 1;
+                    }
+                    (None, Some(old)) => {
+                        let (fallback, text) = match self.fallback()? {
+                            Some(fallback) => fallback,
+                            None => {
+                                pending.push(TextDrawAdjusted::Text(codec.bytes(&codes[i..j])));
+                                self.x += old;
+                                self.stats.skipped += 1;
+                                continue;
+                            }
+                        };
+                        if !pending.is_empty() {
+                            self.out.push(show_op(std::mem::take(&mut pending), false));
+                        }
+                        self.out.push(Op::TextFont { name: fallback, size });
+                        self.out.push(Op::SetTextMatrix { matrix: translate(self.x, 0., &self.line_matrix) });
+                        self.out.push(Op::TextDraw { text });
+                        self.out.push(Op::SetTextMatrix { matrix: self.line_matrix });
+                        self.out.push(Op::TextFont { name: font.clone(), size });
+                        self.x += old;
+                        pending.push(TextDrawAdjusted::Spacing(-self.x * 1000. / scale));
+                        self.stats.overlaid += 1;
+                    }
+                    (None, None) => {
+                        pending.push(TextDrawAdjusted::Text(codec.bytes(&codes[i..j])));
+                        self.stats.skipped += 1;
+                    }
+                }
+                changed = true;
+            }
+            let rest = &codes[pos..];
+            if !rest.is_empty() {
+                pending.push(TextDrawAdjusted::Text(codec.bytes(rest)));
+                self.x += self.advance(codec, size, rest).unwrap_or(0.);
+            }
+        }
+
+        if !pending.is_empty() {
+            self.out.push(show_op(pending, single && !changed));
+        }
+        Ok(())
+    }
+
+    fn op(&mut self, op: &Op) -> Result<()> {
+        match *op {
+            Op::Save => self.stack.push(self.params.clone()),
+            Op::Restore => {
+                if let Some(params) = self.stack.pop() {
+                    self.params = params;
+                }
+            }
+            Op::BeginText => {
+                self.line_matrix = Matrix::default();
+                self.x = 0.;
+            }
+            Op::MoveTextPosition { translation } => {
+                self.line_matrix = translate(translation.x, translation.y, &self.line_matrix);
+                self.x = 0.;
+            }
+            Op::SetTextMatrix { matrix } => {
+                self.line_matrix = matrix;
+                self.x = 0.;
+            }
+            Op::TextNewline => {
+                self.line_matrix = translate(0., -self.params.leading, &self.line_matrix);
+                self.x = 0.;
+            }
+            Op::Leading { leading } => self.params.leading = leading,
+            Op::CharSpacing { char_space } => self.params.char_space = char_space,
+            Op::WordSpacing { word_space } => self.params.word_space = word_space,
+            Op::TextScaling { horiz_scale } => self.params.horiz_scale = horiz_scale / 100.,
+            Op::TextFont { ref name, size } => self.params.font = Some((name.clone(), size)),
+            Op::TextDraw { ref text } => {
+                return self.show(&[TextDrawAdjusted::Text(text.clone())], true);
+            }
+            Op::TextDrawAdjusted { ref array } => {
+                return self.show(array, false);
+            }
+            _ => {}
+        }
+        self.out.push(op.clone());
+        Ok(())
+    }
+}
+
+/// Helvetica with `WinAnsiEncoding`, for replacements the font of the text can't encode.
+fn fallback_font() -> Font {
+    Font {
+        subtype: FontType::Type1,
+        name: Some("Helvetica".into()),
+        data: FontData::Type1(TFont {
+            base_font: Some("Helvetica".into()),
+            first_char: None,
+            last_char: None,
+            widths: None,
+            font_descriptor: None,
+        }),
+        encoding: Some(Encoding {
+            base: BaseEncoding::WinAnsiEncoding,
+            differences: HashMap::new(),
+        }),
+        to_unicode: None,
+        _other: Dictionary::new(),
+    }
+}
+
+fn show_op(mut items: Vec<TextDrawAdjusted>, single: bool) -> Op {
+    if single && items.len() == 1 {
+        if let Some(TextDrawAdjusted::Text(text)) = items.pop() {
+            return Op::TextDraw { text };
+        }
+    }
+    Op::TextDrawAdjusted { array: items }
+}
+
+/// Replace all occurrences of `find` in the text shown by `ops` with `replace`.
+///
+/// The replacement is encoded with the font of the text run if possible, and
+/// the following glyphs are moved to compensate for the difference in width.
+/// Otherwise the matched glyphs are removed and the replacement is drawn on top
+/// with Helvetica in `WinAnsiEncoding`, which is added to `resources`. Occurrences
+/// that can't be encoded that way either are left alone.
+///
+/// Only occurrences within a single string operand are found, text that is split across
+/// several `Tj` operators or `TJ` array elements is not matched.
+pub fn replace_text(
+    ops: &[Op],
+    resources: &mut Resources,
+    find: &str,
+    replace: &str,
+    resolve: &impl Resolve,
+    update: &mut impl Updater,
+) -> Result<(Vec<Op>, ReplaceStats)> {
+    if find.is_empty() {
+        return Ok((ops.to_vec(), ReplaceStats::default()));
+    }
+    let mut replacer = Replacer {
+        find,
+        replace,
+        resources,
+        resolve,
+        update,
+        codecs: HashMap::new(),
+        fallback_codec: None,
+        fallback_font: None,
+        stats: ReplaceStats::default(),
+        params: TextParams {
+            font: None,
+            char_space: 0.,
+            word_space: 0.,
+            horiz_scale: 1.,
+            leading: 0.,
+        },
+        stack: vec![],
+        line_matrix: Matrix::default(),
+        x: 0.,
+        out: Vec::with_capacity(ops.len()),
+    };
+    for op in ops {
+        replacer.op(op)?;
+    }
+    Ok((replacer.out, replacer.stats))
+}
+
+/// An occurrence found by [`Page::search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    /// The text as shown on the page.
+    pub text: String,
+    /// One quadrilateral for each line the match is on, in the default user space of the page.
+    ///
+    /// The corners are upper left, upper right, lower left and lower right, as seen in
+    /// reading direction, which is the order of `QuadPoints` in markup annotations.
+    pub quads: Vec<[Point; 4]>,
+}
+
+/// A glyph with its text, see [`Page::search`].
+struct Char {
+    matrix: Matrix,
+    /// the advance in em
+    width: f32,
+    line: usize,
+    vertical: bool,
+}
+
+impl Page {
+    /// Find all occurrences of `pattern` in the text of this page.
+    ///
+    /// The text is put together in content stream order. Gaps between glyphs and line breaks
+    /// count as spaces and every run of white space in the text and in `pattern` matches any other.
+    /// Glyphs without a known unicode value can't be matched.
+    pub fn search(&self, pattern: &str, resolve: &impl Resolve) -> Result<Vec<TextMatch>> {
+        let pattern = pattern.split_whitespace().collect::<Vec<_>>().join(" ");
+        if pattern.is_empty() {
+            return Ok(vec![]);
+        }
+        let ops = match self.contents {
+            Some(ref contents) => t!(contents.operations(resolve)),
+            None => return Ok(vec![]),
+        };
+
+        let mut text = String::new();
+        // the glyph each byte of `text` belongs to, `None` for inferred spaces
+        let mut owners: Vec<Option<usize>> = vec![];
+        let mut chars: Vec<Char> = vec![];
+        let mut push = |s: &str, owner: Option<usize>, text: &mut String| {
+            for c in s.chars() {
+                let c = if c.is_whitespace() { ' ' } else { c };
+                if c == ' ' && (text.is_empty() || text.ends_with(' ')) {
+                    continue;
+                }
+                text.push(c);
+                owners.extend(std::iter::repeat_n(owner, c.len_utf8()));
+            }
+        };
+        let mut interpreter = t!(ContentInterpreter::for_page(self, resolve));
+        t!(interpreter.run(&ops, &mut |event, state| {
+            let glyphs = match *event {
+                Event::TextShown { glyphs, .. } => glyphs,
+                _ => return Ok(()),
+            };
+            for glyph in glyphs {
+                // vertical displacements are not scaled horizontally
+                let scale = state.text.font_size * if glyph.vertical { 1. } else { state.text.horiz_scale };
+                let width = if scale != 0. { glyph.advance / scale } else { 0. };
+                let mut line = chars.last().map_or(0, |c| c.line);
+                if let Some(prev) = chars.last() {
+                    // the origin of this glyph in the em space of the previous one
+                    let origin = prev.matrix.invert().map(|m| m.transform_point(Point { x: glyph.matrix.e, y: glyph.matrix.f }));
+                    // vertical text runs down the page in columns
+                    let origin = match origin {
+                        Some(p) if prev.vertical => Some(Point { x: -p.y, y: p.x }),
+                        p => p,
+                    };
+                    match origin {
+                        Some(p) if p.y.abs() < 0.5 && p.x > -0.5 && glyph.vertical == prev.vertical => {
+                            if p.x - prev.width > 0.2 {
+                                push(" ", None, &mut text);
+                            }
+                        }
+                        _ => {
+                            line += 1;
+                            push(" ", None, &mut text);
+                        }
+                    }
+                }
+                if let Some(ref unicode) = glyph.unicode {
+                    push(unicode, Some(chars.len()), &mut text);
+                }
+                chars.push(Char { matrix: glyph.matrix, width, line, vertical: glyph.vertical });
+            }
+            Ok(())
+        }));
+
+        let mut matches = vec![];
+        for (start, m) in text.match_indices(&pattern) {
+            let mut glyphs: Vec<usize> = owners[start..start + m.len()].iter().flatten().cloned().collect();
+            glyphs.dedup();
+            let mut quads = vec![];
+            for run in glyphs.chunk_by(|&a, &b| chars[a].line == chars[b].line) {
+                let (first, last) = (&chars[run[0]], &chars[run[run.len() - 1]]);
+                let corner = |c: &Char, x: f32, y: f32| c.matrix.transform_point(Point { x, y });
+                if first.vertical {
+                    // the glyphs hang from their top, the right side is the top of the line
+                    quads.push([
+                        corner(first, 1., 0.88),
+                        corner(last, 1., 0.88 - last.width),
+                        corner(first, 0., 0.88),
+                        corner(last, 0., 0.88 - last.width),
+                    ]);
+                    continue;
+                }
+                quads.push([
+                    corner(first, 0., 0.8),
+                    corner(last, last.width, 0.8),
+                    corner(first, 0., -0.2),
+                    corner(last, last.width, -0.2),
+                ]);
+            }
+            matches.push(TextMatch { text: m.to_string(), quads });
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::PageBuilder;
+    use crate::content::parse_ops;
+
+    /// A font in `StandardEncoding` where `i` is narrow, `m` is wide and everything else is 500.
+    fn resources() -> Resources {
+        let widths = (32..127u8).map(|c| match c {
+            b'i' => 250.,
+            b'm' => 1000.,
+            _ => 500.,
+        }).collect();
+        let font = Font {
+            subtype: FontType::Type1,
+            name: Some("Times-Roman".into()),
+            data: FontData::Type1(TFont {
+                base_font: Some("Times-Roman".into()),
+                first_char: Some(32),
+                last_char: Some(126),
+                widths: Some(widths),
+                font_descriptor: None,
+            }),
+            encoding: Some(Encoding::standard()),
+            to_unicode: None,
+            _other: Dictionary::new(),
+        };
+        let mut resources = Resources::default();
+        resources.fonts.insert("F1".into(), Lazy::safe(font, &mut NoUpdate).unwrap());
+        resources
+    }
+
+    fn replace(content: &str, find: &str, replace: &str) -> (Vec<Op>, Resources, ReplaceStats) {
+        let ops = parse_ops(content.as_bytes(), &NoResolve).unwrap();
+        let mut resources = resources();
+        let (ops, stats) = replace_text(&ops, &mut resources, find, replace, &NoResolve, &mut NoUpdate).unwrap();
+        (ops, resources, stats)
+    }
+
+    fn items(op: &Op) -> Vec<Result<&[u8], f32>> {
+        match *op {
+            Op::TextDrawAdjusted { ref array } => array.iter().map(|item| match *item {
+                TextDrawAdjusted::Text(ref s) => Ok(s.as_bytes()),
+                TextDrawAdjusted::Spacing(n) => Err(n),
+            }).collect(),
+            Op::TextDraw { ref text } => vec![Ok(text.as_bytes())],
+            ref op => panic!("{:?}", op),
+        }
+    }
+
+    #[test]
+    fn reencode() {
+        let (ops, resources, stats) = replace("BT /F1 10 Tf (Hello World) Tj ET", "World", "Earth");
+        assert_eq!(stats, ReplaceStats { reencoded: 1, overlaid: 0, skipped: 0 });
+        // same width, nothing to adjust
+        assert_eq!(items(&ops[2]), [Ok(&b"Hello "[..]), Ok(&b"Earth"[..])]);
+        assert_eq!(resources.fonts.len(), 1);
+    }
+
+    #[test]
+    fn adjust_width() {
+        // `ii` is 5 wide at 10pt, `mm` 20: the rest moves back by the difference
+        let (ops, _, stats) = replace("BT /F1 10 Tf [(hii) -100 (!)] TJ ET", "ii", "mm");
+        assert_eq!(stats.reencoded, 1);
+        assert_eq!(items(&ops[2]), [Ok(&b"h"[..]), Ok(&b"mm"[..]), Err(1500.), Err(-100.), Ok(&b"!"[..])]);
+
+        let (ops, _, _) = replace("BT /F1 10 Tf (mm) Tj ET", "mm", "i");
+        assert_eq!(items(&ops[2]), [Ok(&b"i"[..]), Err(-1750.)]);
+    }
+
+    #[test]
+    fn overlay() {
+        // StandardEncoding has no é, WinAnsiEncoding has it at 0xE9
+        let (ops, resources, stats) = replace("BT /F1 10 Tf 1 0 0 1 72 700 Tm (a cafe) Tj ET", "cafe", "café");
+        assert_eq!(stats, ReplaceStats { reencoded: 0, overlaid: 1, skipped: 0 });
+        assert_eq!(resources.fonts.len(), 2);
+        let fallback = Name::from("ReplF0");
+        assert!(resources.fonts.contains_key(&fallback));
+
+        assert_eq!(items(&ops[3]), [Ok(&b"a "[..])]);
+        assert!(matches!(ops[4], Op::TextFont { ref name, size } if *name == fallback && size == 10.));
+        // drawn where the match was: 2 glyphs of 5pt after the line start
+        assert!(matches!(ops[5], Op::SetTextMatrix { matrix } if matrix.e == 82. && matrix.f == 700.));
+        assert_eq!(items(&ops[6]), [Ok(&b"caf\xe9"[..])]);
+        assert!(matches!(ops[8], Op::TextFont { ref name, .. } if name.as_str() == "F1"));
+        // the original glyphs are skipped over
+        assert_eq!(items(&ops[9]), [Err(-3000.)]);
+    }
+
+    #[test]
+    fn not_encodable() {
+        // neither font has an arrow
+        let (ops, resources, stats) = replace("BT /F1 10 Tf (a -> b) Tj ET", "->", "\u{2192}");
+        assert_eq!(stats, ReplaceStats { reencoded: 0, overlaid: 0, skipped: 1 });
+        assert_eq!(resources.fonts.len(), 1);
+        let text: Vec<u8> = items(&ops[2]).into_iter().flat_map(|item| item.unwrap().to_vec()).collect();
+        assert_eq!(text, b"a -> b");
+    }
+
+    #[test]
+    fn page_builder() {
+        let mut page = PageBuilder {
+            ops: parse_ops(b"BT /F1 10 Tf (one two one) Tj ET", &NoResolve).unwrap(),
+            resources: resources(),
+            ..Default::default()
+        };
+        let stats = page.replace_text("one", "three", &NoResolve, &mut NoUpdate).unwrap();
+        assert_eq!(stats.reencoded, 2);
+        let text: Vec<u8> = items(&page.ops[2]).into_iter().filter_map(|item| item.ok()).flatten().copied().collect();
+        assert_eq!(text, b"three two three");
+    }
+}
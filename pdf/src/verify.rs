@@ -0,0 +1,409 @@
+//! Checking a document against rules of the specification that reading does not enforce,
+//! see [`strict`] and [`File::verify`](crate::file::File::verify).
+//!
+//! The parser accepts a lot that a conforming writer must not produce. [`strict`] reports:
+//! - names longer than 127 bytes, strings longer than 32767 bytes and real numbers beyond
+//!   ±3.403 × 10³⁸, the implementation limits of PDF 1.7 (Annex C),
+//! - missing required entries of the trailer, the catalog, the page tree, fonts,
+//!   font descriptors, annotations, actions and XObjects,
+//! - `Type` values that are wrong where they are found,
+//! - integers out of their range, like a `Rotate` that is not a multiple of 90
+//!   or a `Count` of the page tree that does not match the pages.
+//!
+//! It is meant for producers validating their own output, not as a full validator.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::object::{PlainRef, Resolve};
+use crate::primitive::{Dictionary, Name, Primitive};
+use crate::visit::{ObjectVisitor, Path};
+
+/// The longest name allowed, in bytes.
+pub const MAX_NAME_LEN: usize = 127;
+/// The longest string allowed, in bytes.
+pub const MAX_STRING_LEN: usize = 32767;
+/// The largest magnitude of real numbers, about 3.403 × 10³⁸.
+pub const MAX_REAL: f32 = f32::MAX;
+
+/// Page trees nested deeper than this are not followed.
+const MAX_DEPTH: usize = 64;
+
+/// What is wrong, see [`Issue`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// A name (a key or a value) longer than [`MAX_NAME_LEN`].
+    NameTooLong { len: usize },
+    /// A string longer than [`MAX_STRING_LEN`].
+    StringTooLong { len: usize },
+    /// A real number beyond [`MAX_REAL`], or not a number at all.
+    RealOutOfRange { value: f32 },
+    /// An integer entry outside of the values it may take.
+    OutOfRange { key: Name, value: i32, expected: &'static str },
+    /// A required entry is missing.
+    MissingKey { typ: &'static str, key: &'static str },
+    /// The `Type` of a dictionary is not the one its place calls for.
+    WrongType { expected: &'static [&'static str], found: String },
+    /// An entry has the wrong kind of primitive.
+    WrongKind { key: Name, expected: &'static str, found: &'static str },
+    /// The `Count` of a page tree node is not the number of pages below it.
+    CountMismatch { count: i32, pages: usize },
+}
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IssueKind::NameTooLong { len } => write!(f, "name of {} bytes, at most {} are allowed", len, MAX_NAME_LEN),
+            IssueKind::StringTooLong { len } => write!(f, "string of {} bytes, at most {} are allowed", len, MAX_STRING_LEN),
+            IssueKind::RealOutOfRange { value } => write!(f, "real number {} out of range", value),
+            IssueKind::OutOfRange { ref key, value, expected } => write!(f, "{} is {}, expected {}", key, value, expected),
+            IssueKind::MissingKey { typ, key } => write!(f, "{} without the required entry /{}", typ, key),
+            IssueKind::WrongType { expected, ref found } => write!(f, "/Type is /{}, expected /{}", found, expected.join(" or /")),
+            IssueKind::WrongKind { ref key, expected, found } => write!(f, "{} is a {}, expected a {}", key, found, expected),
+            IssueKind::CountMismatch { count, pages } => write!(f, "/Count is {}, but there are {} pages", count, pages),
+        }
+    }
+}
+
+/// One violation found by [`strict`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Where it is, like `trailer/Root` or `12 0 R/Resources/Font/F1`.
+    pub path: String,
+    pub kind: IssueKind,
+}
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// Check everything reachable from `trailer`, see the [module documentation](self).
+pub fn strict(trailer: &Primitive, resolve: &impl Resolve) -> Vec<Issue> {
+    let mut checker = Checker { resolve, issues: vec![], pages: HashSet::new() };
+    if let Primitive::Dictionary(ref trailer) = *trailer {
+        checker.trailer(trailer);
+    }
+    let mut limits = Limits { issues: vec![] };
+    crate::visit::visit(trailer, resolve, &mut limits);
+
+    let mut issues = checker.issues;
+    issues.extend(limits.issues);
+    issues
+}
+
+/// The checks that don't depend on where a primitive is.
+struct Limits {
+    issues: Vec<Issue>,
+}
+impl Limits {
+    fn issue(&mut self, path: String, kind: IssueKind) {
+        self.issues.push(Issue { path, kind });
+    }
+    fn stream(&mut self, path: &Path, dict: &Dictionary) {
+        if let Some(&Primitive::Integer(length)) = dict.get("Length") {
+            if length < 0 {
+                self.issue(path.to_string(), IssueKind::OutOfRange { key: "Length".into(), value: length, expected: "at least 0" });
+            }
+        }
+        let subtype = match dict.get("Subtype") {
+            Some(Primitive::Name(subtype)) => subtype.as_str(),
+            _ => return,
+        };
+        match subtype {
+            "Image" => {
+                for key in ["Width", "Height"] {
+                    match dict.get(key) {
+                        None => self.issue(path.to_string(), IssueKind::MissingKey { typ: "image", key }),
+                        Some(&Primitive::Integer(n)) if n <= 0 => {
+                            self.issue(path.to_string(), IssueKind::OutOfRange { key: key.into(), value: n, expected: "more than 0" });
+                        }
+                        _ => {}
+                    }
+                }
+                let mask = matches!(dict.get("ImageMask"), Some(Primitive::Boolean(true)));
+                let jpx = match dict.get("Filter") {
+                    Some(Primitive::Name(f)) => f == "JPXDecode",
+                    Some(Primitive::Array(filters)) => filters.iter().any(|f| matches!(f, Primitive::Name(f) if f == "JPXDecode")),
+                    _ => false,
+                };
+                if !mask && !jpx {
+                    for key in ["ColorSpace", "BitsPerComponent"] {
+                        if dict.get(key).is_none() {
+                            self.issue(path.to_string(), IssueKind::MissingKey { typ: "image", key });
+                        }
+                    }
+                }
+                if let Some(&Primitive::Integer(bpc)) = dict.get("BitsPerComponent") {
+                    if ![1, 2, 4, 8, 16].contains(&bpc) {
+                        self.issue(path.to_string(), IssueKind::OutOfRange { key: "BitsPerComponent".into(), value: bpc, expected: "1, 2, 4, 8 or 16" });
+                    }
+                }
+            }
+            "Form" if dict.get("BBox").is_none() => {
+                self.issue(path.to_string(), IssueKind::MissingKey { typ: "form XObject", key: "BBox" });
+            }
+            _ => {}
+        }
+    }
+    fn dict(&mut self, path: &Path, dict: &Dictionary) {
+        for key in dict.keys() {
+            if key.len() > MAX_NAME_LEN {
+                self.issue(path.to_string(), IssueKind::NameTooLong { len: key.len() });
+            }
+        }
+        let (typ, required): (_, &[&'static str]) = match dict.get("Type") {
+            Some(Primitive::Name(t)) if t == "FontDescriptor" => ("font descriptor", &["FontName", "Flags", "ItalicAngle"]),
+            Some(Primitive::Name(t)) if t == "Action" => ("action", &["S"]),
+            _ => return,
+        };
+        for &key in required {
+            if dict.get(key).is_none() {
+                self.issue(path.to_string(), IssueKind::MissingKey { typ, key });
+            }
+        }
+    }
+}
+impl ObjectVisitor for Limits {
+    fn visit(&mut self, path: Path, primitive: &Primitive) -> bool {
+        match *primitive {
+            Primitive::Name(ref name) if name.len() > MAX_NAME_LEN => {
+                self.issue(path.to_string(), IssueKind::NameTooLong { len: name.len() });
+            }
+            Primitive::String(ref s) if s.as_bytes().len() > MAX_STRING_LEN => {
+                self.issue(path.to_string(), IssueKind::StringTooLong { len: s.as_bytes().len() });
+            }
+            Primitive::Number(value) if !value.is_finite() || value.abs() > MAX_REAL => {
+                self.issue(path.to_string(), IssueKind::RealOutOfRange { value });
+            }
+            Primitive::Dictionary(ref dict) => self.dict(&path, dict),
+            Primitive::Stream(ref stream) => {
+                self.dict(&path, &stream.info);
+                self.stream(&path, &stream.info);
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+/// The checks that depend on where a dictionary is: in the trailer, the catalog and the page tree.
+struct Checker<'a, R> {
+    resolve: &'a R,
+    issues: Vec<Issue>,
+    /// page tree nodes already checked
+    pages: HashSet<PlainRef>,
+}
+impl<R: Resolve> Checker<'_, R> {
+    fn issue(&mut self, path: &str, kind: IssueKind) {
+        self.issues.push(Issue { path: path.into(), kind });
+    }
+    /// Resolve `p` to a dictionary (or the dictionary of a stream).
+    /// Its path is the reference if it is an indirect object, `path` otherwise.
+    fn dict(&self, p: &Primitive, path: String) -> Option<(Dictionary, String)> {
+        let (p, path) = match *p {
+            Primitive::Reference(r) => (self.resolve.resolve(r).ok()?, format!("{} {} R", r.id, r.gen)),
+            ref p => (p.clone(), path),
+        };
+        match p {
+            Primitive::Dictionary(dict) => Some((dict, path)),
+            Primitive::Stream(stream) => Some((stream.info.clone(), path)),
+            _ => None,
+        }
+    }
+    fn get(&self, dict: &Dictionary, key: &str) -> Option<Primitive> {
+        dict.get(key).and_then(|p| p.clone().resolve(self.resolve).ok())
+    }
+    fn require(&mut self, dict: &Dictionary, path: &str, typ: &'static str, keys: &[&'static str]) {
+        for &key in keys {
+            if dict.get(key).is_none() {
+                self.issue(path, IssueKind::MissingKey { typ, key });
+            }
+        }
+    }
+    /// Check that `Type` is one of `expected`, returns the one it is.
+    fn expect_type(&mut self, dict: &Dictionary, path: &str, expected: &'static [&'static str], required: bool) -> Option<&'static str> {
+        match dict.get("Type") {
+            Some(Primitive::Name(found)) => match expected.iter().find(|&&t| t == found.as_str()) {
+                Some(&t) => Some(t),
+                None => {
+                    self.issue(path, IssueKind::WrongType { expected, found: found.to_string() });
+                    None
+                }
+            },
+            Some(p) => {
+                self.issue(path, IssueKind::WrongKind { key: "Type".into(), expected: "Name", found: p.get_debug_name() });
+                None
+            }
+            None => {
+                if required {
+                    self.issue(path, IssueKind::MissingKey { typ: expected[0], key: "Type" });
+                }
+                None
+            }
+        }
+    }
+
+    fn trailer(&mut self, trailer: &Dictionary) {
+        let path = "trailer";
+        self.require(trailer, path, "trailer", &["Root"]);
+        if trailer.get("Encrypt").is_some() {
+            self.require(trailer, path, "trailer of an encrypted file", &["ID"]);
+        }
+        match self.get(trailer, "ID") {
+            Some(Primitive::Array(ref id)) if id.len() == 2 && id.iter().all(|p| matches!(p, Primitive::String(_))) => {}
+            Some(p) => self.issue(path, IssueKind::WrongKind { key: "ID".into(), expected: "array of two strings", found: p.get_debug_name() }),
+            None => {}
+        }
+        let (catalog, path) = match trailer.get("Root").and_then(|p| self.dict(p, "trailer/Root".into())) {
+            Some(catalog) => catalog,
+            None => return,
+        };
+        self.expect_type(&catalog, &path, &["Catalog"], true);
+        self.require(&catalog, &path, "Catalog", &["Pages"]);
+        if let Some(pages) = catalog.get("Pages") {
+            let root_ref = match *pages {
+                Primitive::Reference(r) => Some(r),
+                _ => None,
+            };
+            if let Some((root, path)) = self.dict(pages, format!("{}/Pages", path)) {
+                self.page_tree(&root, &path, None, root_ref, Inherited::default(), 0);
+            }
+        }
+    }
+
+    /// Check a node of the page tree and the nodes below it. Returns the number of pages.
+    fn page_tree(&mut self, node: &Dictionary, path: &str, parent: Option<PlainRef>, this: Option<PlainRef>, mut inherited: Inherited, depth: usize) -> usize {
+        if depth > MAX_DEPTH {
+            return 0;
+        }
+        let typ = self.expect_type(node, path, &["Pages", "Page"], true);
+        if let Some(parent) = parent {
+            match node.get("Parent") {
+                Some(&Primitive::Reference(r)) if r == parent => {}
+                Some(_) => self.issue(path, IssueKind::WrongKind { key: "Parent".into(), expected: "reference to the parent node", found: "different object" }),
+                None => self.issue(path, IssueKind::MissingKey { typ: typ.unwrap_or("page tree node"), key: "Parent" }),
+            }
+        }
+        if let Some(Primitive::Integer(rotate)) = self.get(node, "Rotate") {
+            if rotate % 90 != 0 {
+                self.issue(path, IssueKind::OutOfRange { key: "Rotate".into(), value: rotate, expected: "a multiple of 90" });
+            }
+        }
+        inherited.media_box |= node.get("MediaBox").is_some();
+        inherited.resources |= node.get("Resources").is_some();
+
+        let is_page = match typ {
+            Some(t) => t == "Page",
+            None => node.get("Kids").is_none(),
+        };
+        if is_page {
+            if !inherited.media_box {
+                self.issue(path, IssueKind::MissingKey { typ: "Page", key: "MediaBox" });
+            }
+            if !inherited.resources {
+                self.issue(path, IssueKind::MissingKey { typ: "Page", key: "Resources" });
+            }
+            self.page(node, path);
+            return 1;
+        }
+
+        self.require(node, path, "Pages", &["Kids", "Count"]);
+        let kids = match self.get(node, "Kids") {
+            Some(Primitive::Array(kids)) => kids,
+            Some(p) => {
+                self.issue(path, IssueKind::WrongKind { key: "Kids".into(), expected: "Array", found: p.get_debug_name() });
+                return 0;
+            }
+            None => return 0,
+        };
+        let mut pages = 0;
+        for (i, kid) in kids.iter().enumerate() {
+            let kid_ref = match *kid {
+                Primitive::Reference(r) => r,
+                ref p => {
+                    self.issue(&format!("{}/Kids[{}]", path, i), IssueKind::WrongKind { key: "Kids".into(), expected: "Reference", found: p.get_debug_name() });
+                    continue;
+                }
+            };
+            if !self.pages.insert(kid_ref) {
+                continue;
+            }
+            if let Some((kid, kid_path)) = self.dict(kid, String::new()) {
+                pages += self.page_tree(&kid, &kid_path, this, Some(kid_ref), inherited, depth + 1);
+            }
+        }
+        match self.get(node, "Count") {
+            Some(Primitive::Integer(count)) if count < 0 => {
+                self.issue(path, IssueKind::OutOfRange { key: "Count".into(), value: count, expected: "at least 0" });
+            }
+            Some(Primitive::Integer(count)) if count as usize != pages => {
+                self.issue(path, IssueKind::CountMismatch { count, pages });
+            }
+            _ => {}
+        }
+        pages
+    }
+
+    /// The resources and annotations of a page.
+    fn page(&mut self, page: &Dictionary, path: &str) {
+        let resources = page.get("Resources").and_then(|p| self.dict(p, format!("{}/Resources", path)));
+        if let Some((resources, path)) = resources {
+            let fonts = resources.get("Font").and_then(|p| self.dict(p, format!("{}/Font", path)));
+            if let Some((fonts, path)) = fonts {
+                for (name, font) in fonts.iter() {
+                    if let Some((font, path)) = self.dict(font, format!("{}/{}", path, name)) {
+                        self.font(&font, &path);
+                    }
+                }
+            }
+        }
+        if let Some(Primitive::Array(annots)) = self.get(page, "Annots") {
+            for (i, annot) in annots.iter().enumerate() {
+                if let Some((annot, path)) = self.dict(annot, format!("{}/Annots[{}]", path, i)) {
+                    self.expect_type(&annot, &path, &["Annot"], false);
+                    self.require(&annot, &path, "annotation", &["Subtype", "Rect"]);
+                }
+            }
+        }
+    }
+
+    fn font(&mut self, font: &Dictionary, path: &str) {
+        self.expect_type(font, path, &["Font"], true);
+        self.require(font, path, "Font", &["Subtype"]);
+        let subtype = match font.get("Subtype") {
+            Some(Primitive::Name(subtype)) => subtype.as_str(),
+            _ => return,
+        };
+        match subtype {
+            "Type3" => self.require(font, path, "Type 3 font", &["FontBBox", "FontMatrix", "CharProcs", "Encoding", "FirstChar", "LastChar", "Widths"]),
+            "Type0" => self.require(font, path, "Type 0 font", &["BaseFont", "Encoding", "DescendantFonts"]),
+            "CIDFontType0" | "CIDFontType2" => self.require(font, path, "CID font", &["BaseFont", "CIDSystemInfo", "FontDescriptor"]),
+            _ => self.require(font, path, "Font", &["BaseFont"]),
+        }
+        if subtype == "Type0" {
+            if let Some(Primitive::Array(descendants)) = self.get(font, "DescendantFonts") {
+                for (i, descendant) in descendants.iter().enumerate() {
+                    if let Some((descendant, path)) = self.dict(descendant, format!("{}/DescendantFonts[{}]", path, i)) {
+                        self.font(&descendant, &path);
+                    }
+                }
+            }
+        }
+        if subtype == "Type3" {
+            return;
+        }
+        let descriptor = font.get("FontDescriptor").and_then(|p| self.dict(p, format!("{}/FontDescriptor", path)));
+        if let Some((descriptor, path)) = descriptor {
+            self.expect_type(&descriptor, &path, &["FontDescriptor"], true);
+            // the generic checks cover the entries required for all fonts
+            self.require(&descriptor, &path, "font descriptor", &["FontBBox", "Ascent", "Descent", "StemV"]);
+        }
+    }
+}
+
+/// Inheritable entries of page tree nodes that are required for pages.
+#[derive(Debug, Clone, Copy, Default)]
+struct Inherited {
+    media_box: bool,
+    resources: bool,
+}
@@ -0,0 +1,101 @@
+//! Walking all objects that can be reached from the trailer, see [`File::visit`](crate::file::File::visit).
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::error::PdfError;
+use crate::object::{PlainRef, Resolve};
+use crate::primitive::{Name, Primitive};
+
+/// One step from a primitive to a primitive inside of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathItem {
+    Key(Name),
+    Index(usize),
+}
+
+/// Where a primitive is: the object it is in and the keys and indices leading to it.
+#[derive(Debug, Clone, Copy)]
+pub struct Path<'a> {
+    /// The object, or `None` for the trailer.
+    pub obj: Option<PlainRef>,
+    pub items: &'a [PathItem],
+}
+/// Like `5 0 R/Resources/Font/F1` or `trailer/Root`.
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.obj {
+            Some(r) => write!(f, "{} {} R", r.id, r.gen)?,
+            None => write!(f, "trailer")?,
+        }
+        for item in self.items {
+            match *item {
+                PathItem::Key(ref key) => write!(f, "{}", key)?,
+                PathItem::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait ObjectVisitor {
+    /// Called for every primitive, before the primitives inside of it.
+    ///
+    /// Return `false` to skip what is inside, including the objects it refers to
+    /// (unless they are reached in another way).
+    fn visit(&mut self, path: Path, primitive: &Primitive) -> bool;
+
+    /// Called when object `r`, referred to at `path`, can't be read.
+    fn error(&mut self, path: Path, r: PlainRef, error: PdfError) {
+        let _ = (path, r, error);
+    }
+}
+
+/// Visit `root` and every object reachable from it. Each object is visited once.
+pub fn visit(root: &Primitive, resolve: &impl Resolve, visitor: &mut impl ObjectVisitor) {
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    let mut path = vec![];
+    walk(root, None, &mut path, visitor, &mut queue);
+
+    while let Some((r, from, items)) = queue.pop_front() {
+        if !seen.insert(r) {
+            continue;
+        }
+        match resolve.resolve(r) {
+            Ok(p) => walk(&p, Some(r), &mut path, visitor, &mut queue),
+            Err(e) => visitor.error(Path { obj: from, items: &items }, r, e),
+        }
+    }
+}
+
+// references found, with where they were found
+type Queue = VecDeque<(PlainRef, Option<PlainRef>, Vec<PathItem>)>;
+
+fn walk(p: &Primitive, obj: Option<PlainRef>, path: &mut Vec<PathItem>, visitor: &mut impl ObjectVisitor, queue: &mut Queue) {
+    if !visitor.visit(Path { obj, items: path }, p) {
+        return;
+    }
+    let dict = match *p {
+        Primitive::Reference(r) => {
+            queue.push_back((r, obj, path.clone()));
+            return;
+        }
+        Primitive::Array(ref parts) => {
+            for (i, part) in parts.iter().enumerate() {
+                path.push(PathItem::Index(i));
+                walk(part, obj, path, visitor, queue);
+                path.pop();
+            }
+            return;
+        }
+        Primitive::Dictionary(ref dict) => dict,
+        Primitive::Stream(ref stream) => &stream.info,
+        _ => return,
+    };
+    for (key, val) in dict.iter() {
+        path.push(PathItem::Key(key.clone()));
+        walk(val, obj, path, visitor, queue);
+        path.pop();
+    }
+}
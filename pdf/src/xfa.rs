@@ -0,0 +1,182 @@
+//! XFA forms: the XML that the `XFA` entry of the interactive form dictionary holds.
+//!
+//! The entry is either a single stream with the whole XDP document, or an array of names and streams
+//! that splits it into packets (`preamble`, `config`, `template`, `datasets`, ..., `postamble`).
+//! [`Xfa`] always holds packets; a single stream is split at its top level elements.
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::*;
+use crate::xml::{self, Event};
+
+/// The packets of an XFA form, in document order. Concatenated they make up the XDP document.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Xfa {
+    pub packets: Vec<(String, Vec<u8>)>,
+}
+
+impl Xfa {
+    /// Split an XDP document into packets: the part up to the root element's start tag is the `preamble`,
+    /// each child element of the root (with the whitespace before it) is a packet named after it,
+    /// and the rest is the `postamble`.
+    pub fn from_xdp(data: &[u8]) -> Result<Xfa> {
+        let text = std::str::from_utf8(data).map_err(|_| other!("XFA data is not UTF-8"))?;
+        let mut packets = vec![];
+        let mut depth = 0i32;
+        // end of the last packet, and start and name of the current one
+        let mut last = 0;
+        let mut current = None;
+        for (range, event) in xml::events(text)? {
+            match event {
+                Event::Start { name, .. } => {
+                    depth += 1;
+                    match depth {
+                        1 => {
+                            packets.push(("preamble".to_string(), data[..range.end].to_vec()));
+                            last = range.end;
+                        }
+                        2 => current = Some((last, name)),
+                        _ => {}
+                    }
+                }
+                Event::End(_) => {
+                    if depth == 2 {
+                        if let Some((start, name)) = current.take() {
+                            packets.push((name.to_string(), data[start..range.end].to_vec()));
+                            last = range.end;
+                        }
+                    }
+                    depth -= 1;
+                    if depth < 0 {
+                        bail!("unbalanced XML in XFA data");
+                    }
+                }
+                Event::Text(_) => {}
+            }
+        }
+        if packets.is_empty() {
+            bail!("XFA data without root element");
+        }
+        packets.push(("postamble".to_string(), data[last..].to_vec()));
+        Ok(Xfa { packets })
+    }
+
+    /// The whole XDP document.
+    pub fn to_xdp(&self) -> Vec<u8> {
+        self.packets.iter().flat_map(|(_, data)| data.iter().copied()).collect()
+    }
+
+    pub fn packet(&self, name: &str) -> Option<&[u8]> {
+        self.packets.iter().find(|(n, _)| n == name).map(|(_, data)| data.as_slice())
+    }
+
+    pub fn template(&self) -> Option<&[u8]> {
+        self.packet("template")
+    }
+
+    pub fn datasets(&self) -> Option<&[u8]> {
+        self.packet("datasets")
+    }
+
+    pub fn config(&self) -> Option<&[u8]> {
+        self.packet("config")
+    }
+
+    /// Replace the packet `name`, or add it before the `postamble` if there is none.
+    pub fn set_packet(&mut self, name: &str, data: Vec<u8>) {
+        if let Some(packet) = self.packets.iter_mut().find(|(n, _)| n == name) {
+            packet.1 = data;
+            return;
+        }
+        let i = self.packets.iter().position(|(n, _)| n == "postamble").unwrap_or(self.packets.len());
+        self.packets.insert(i, (name.to_string(), data));
+    }
+
+    /// Replace the form data, an `<xfa:datasets>` element.
+    pub fn set_datasets(&mut self, data: Vec<u8>) {
+        self.set_packet("datasets", data)
+    }
+}
+
+impl Object for Xfa {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Array(parts) => {
+                let mut packets = Vec::with_capacity(parts.len() / 2);
+                for pair in parts.chunks(2) {
+                    let (name, stream) = match pair {
+                        [name, stream] => (name, stream),
+                        _ => bail!("XFA array with an odd number of entries"),
+                    };
+                    let name = t!(name.clone().resolve(resolve)?.into_string()).to_string_lossy();
+                    let stream = t!(Stream::<()>::from_primitive(stream.clone(), resolve));
+                    packets.push((name, t!(stream.data(resolve)).to_vec()));
+                }
+                Ok(Xfa { packets })
+            }
+            p @ Primitive::Stream(_) => {
+                let stream = t!(Stream::<()>::from_primitive(p, resolve));
+                Xfa::from_xdp(&t!(stream.data(resolve)))
+            }
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Array or Stream", found: p.get_debug_name() }),
+        }
+    }
+}
+
+impl ObjectWrite for Xfa {
+    /// Always writes the array form, with a stream for each packet.
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut parts = Vec::with_capacity(2 * self.packets.len());
+        for (name, data) in &self.packets {
+            let stream = update.create(Stream::new((), data.clone()))?;
+            parts.push(PdfString::from(name.as_str()).into());
+            parts.push(stream.get_ref().get_inner().into());
+        }
+        Ok(Primitive::Array(parts))
+    }
+}
+
+impl InteractiveFormDictionary {
+    /// The XFA form, if there is one.
+    pub fn xfa(&self, resolve: &impl Resolve) -> Result<Option<Xfa>> {
+        match self.xfa {
+            Some(ref p) => Xfa::from_primitive(p.clone(), resolve).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace the XFA form, writing its packets as new streams.
+    pub fn set_xfa(&mut self, xfa: &Xfa, update: &mut impl Updater) -> Result<()> {
+        self.xfa = Some(xfa.to_primitive(update)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XDP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xdp:xdp xmlns:xdp="http://ns.adobe.com/xdp/">
+<template xmlns="http://www.xfa.org/schema/xfa-template/3.3/"><subform name="form1"/></template>
+<xfa:datasets xmlns:xfa="http://www.xfa.org/schema/xfa-data/1.0/"><xfa:data><form1/></xfa:data></xfa:datasets>
+</xdp:xdp>
+"#;
+
+    #[test]
+    fn packets() {
+        let mut xfa = Xfa::from_xdp(XDP.as_bytes()).unwrap();
+        let names: Vec<_> = xfa.packets.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["preamble", "template", "datasets", "postamble"]);
+        assert!(xfa.datasets().unwrap().starts_with(b"\n<xfa:datasets"));
+        assert!(xfa.packet("postamble").unwrap().starts_with(b"\n</xdp:xdp>"));
+        assert_eq!(xfa.to_xdp(), XDP.as_bytes());
+
+        let datasets = b"<xfa:datasets xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\"/>".to_vec();
+        xfa.set_datasets(datasets.clone());
+        xfa.set_packet("config", b"<config/>".to_vec());
+        assert_eq!(xfa.datasets(), Some(&datasets[..]));
+        assert_eq!(xfa.packets[3].0, "config");
+        assert!(Xfa::from_xdp(&xfa.to_xdp()).is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+//! A minimal XML tokenizer, enough for the XML formats used next to PDF (XFDF and XFA).
+
+use std::ops::Range;
+
+use crate::error::*;
+
+/// The parts of an XML document. Element and attribute names are without namespace prefix.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Event<'a> {
+    /// An opening tag. Empty elements (`<a/>`) are followed by an `End`.
+    Start { name: &'a str, attrs: Vec<(&'a str, String)> },
+    End(&'a str),
+    /// Text with the entities replaced.
+    Text(String),
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+pub(crate) fn attr<'a>(attrs: &'a [(&str, String)], name: &str) -> Option<&'a str> {
+    attrs.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+}
+
+/// Split `xml` into tags and text, with the byte range each of them covers.
+///
+/// This is no validating parser: comments, processing instructions and the document type are skipped,
+/// and mismatched tags are not noticed. The `End` following an empty element has an empty range.
+pub(crate) fn events(xml: &str) -> Result<Vec<(Range<usize>, Event<'_>)>> {
+    let mut events = vec![];
+    let mut rest = xml;
+    while !rest.is_empty() {
+        let pos = xml.len() - rest.len();
+        let start = match rest.find('<') {
+            Some(start) => start,
+            None => {
+                events.push((pos .. xml.len(), Event::Text(unescape(rest))));
+                break;
+            }
+        };
+        if start > 0 {
+            events.push((pos .. pos + start, Event::Text(unescape(&rest[..start]))));
+        }
+        rest = &rest[start..];
+        let pos = xml.len() - rest.len();
+
+        let skip = [("<!--", "-->"), ("<?", "?>"), ("<![CDATA[", "]]>"), ("<!", ">")].into_iter()
+            .find(|(open, _)| rest.starts_with(open));
+        if let Some((open, close)) = skip {
+            let end = rest.find(close).ok_or_else(|| other!("unterminated {} in XML", open))?;
+            if open == "<![CDATA[" {
+                events.push((pos .. pos + end + close.len(), Event::Text(rest[open.len()..end].to_owned())));
+            }
+            rest = &rest[end + close.len()..];
+            continue;
+        }
+
+        let end = tag_end(rest).ok_or_else(|| other!("unterminated XML tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            events.push((pos .. pos + end + 1, Event::End(local_name(name.trim()))));
+            continue;
+        }
+        let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(|c: char| c.is_ascii_whitespace()).unwrap_or(tag.len());
+        let name = local_name(&tag[..name_end]);
+        let mut attrs = vec![];
+        let mut s = tag[name_end..].trim_start();
+        while let Some(eq) = s.find('=') {
+            let key = local_name(s[..eq].trim());
+            let value = s[eq + 1..].trim_start();
+            let quote = match value.chars().next() {
+                Some(q @ '"') | Some(q @ '\'') => q,
+                _ => bail!("unquoted XML attribute {}", key),
+            };
+            let len = value[1..].find(quote).ok_or_else(|| other!("unterminated XML attribute {}", key))?;
+            attrs.push((key, unescape(&value[1..1 + len])));
+            s = value[len + 2..].trim_start();
+        }
+        events.push((pos .. pos + end + 1, Event::Start { name, attrs }));
+        if empty {
+            events.push((pos + end + 1 .. pos + end + 1, Event::End(name)));
+        }
+    }
+    Ok(events)
+}
+
+/// The position of the `>` that closes the tag at the start of `s`, skipping quoted attribute values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+pub(crate) fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let c = entity.and_then(|(entity, _)| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()).and_then(char::from_u32),
+            },
+        });
+        match (c, entity) {
+            (Some(c), Some((_, end))) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,143 @@
+//! Minimal editing of XMP metadata packets.
+//!
+//! This is not a full XML implementation. It only knows enough about the
+//! usual serialization of XMP to keep simple properties (like `pdf:Producer`)
+//! in sync with the document information dictionary.
+
+/// Set the simple property `name` (i.e. `pdf:Producer`) to `value`, or remove it if `value` is `None`.
+///
+/// Both the element form (`<pdf:Producer>..</pdf:Producer>`) and the attribute form
+/// (`pdf:Producer=".."`) are handled.
+/// If the property is missing, it is added to the first `rdf:Description`.
+pub fn set_property(xml: &str, name: &str, value: Option<&str>) -> String {
+    let mut out = String::with_capacity(xml.len());
+    let mut found = false;
+    let mut rest = xml;
+
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let attr = format!("{}=", name);
+
+    loop {
+        let elem = rest.find(&open);
+        let attribute = rest.find(&attr).filter(|&i| i > 0 && rest.as_bytes()[i - 1].is_ascii_whitespace());
+        match (elem, attribute) {
+            (Some(start), a) if a.map(|a| start < a).unwrap_or(true) => {
+                let end = match rest[start..].find(&close) {
+                    Some(end) => start + end + close.len(),
+                    None => break,
+                };
+                out.push_str(&rest[..start]);
+                if let Some(value) = value {
+                    out.push_str(&open);
+                    escape(value, &mut out);
+                    out.push_str(&close);
+                }
+                rest = &rest[end..];
+                found = true;
+            }
+            (_, Some(start)) => {
+                let quote_pos = start + attr.len();
+                let quote = match rest[quote_pos..].chars().next() {
+                    Some(q @ '"') | Some(q @ '\'') => q,
+                    _ => break,
+                };
+                let end = match rest[quote_pos + 1..].find(quote) {
+                    Some(end) => quote_pos + 1 + end + 1,
+                    None => break,
+                };
+                // strip the leading whitespace as well when removing
+                out.push_str(&rest[..start - 1]);
+                if let Some(value) = value {
+                    out.push_str(&rest[start - 1..quote_pos]);
+                    out.push('"');
+                    escape(value, &mut out);
+                    out.push('"');
+                }
+                rest = &rest[end..];
+                found = true;
+            }
+            _ => break,
+        }
+    }
+    out.push_str(rest);
+
+    match value {
+        Some(value) if !found => insert_property(&out, name, value).unwrap_or(out),
+        _ => out,
+    }
+}
+
+fn insert_property(xml: &str, name: &str, value: &str) -> Option<String> {
+    let start = xml.find("<rdf:Description")?;
+    let end = start + xml[start..].find('>')?;
+    if xml[..end].ends_with('/') {
+        return None;
+    }
+    let prefix = name.split(':').next()?;
+    let mut out = String::with_capacity(xml.len() + 2 * name.len() + value.len() + 64);
+    out.push_str(&xml[..end]);
+    // make sure the namespace is declared
+    if !xml[start..end].contains(&format!("xmlns:{}=", prefix)) {
+        if let Some(ns) = namespace(prefix) {
+            out.push_str(&format!(" xmlns:{}=\"{}\"", prefix, ns));
+        }
+    }
+    out.push('>');
+    out.push_str(&format!("<{}>", name));
+    escape(value, &mut out);
+    out.push_str(&format!("</{}>", name));
+    out.push_str(&xml[end + 1..]);
+    Some(out)
+}
+
+fn namespace(prefix: &str) -> Option<&'static str> {
+    match prefix {
+        "pdf" => Some("http://ns.adobe.com/pdf/1.3/"),
+        "xmp" => Some("http://ns.adobe.com/xap/1.0/"),
+        "dc" => Some("http://purl.org/dc/elements/1.1/"),
+        _ => None,
+    }
+}
+
+pub(crate) fn escape(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element() {
+        let xml = r#"<rdf:Description rdf:about=""><pdf:Producer>old</pdf:Producer></rdf:Description>"#;
+        assert_eq!(set_property(xml, "pdf:Producer", Some("new & better")),
+            r#"<rdf:Description rdf:about=""><pdf:Producer>new &amp; better</pdf:Producer></rdf:Description>"#);
+        assert_eq!(set_property(xml, "pdf:Producer", None),
+            r#"<rdf:Description rdf:about=""></rdf:Description>"#);
+    }
+
+    #[test]
+    fn attribute() {
+        let xml = r#"<rdf:Description rdf:about="" pdf:Producer="old"/>"#;
+        assert_eq!(set_property(xml, "pdf:Producer", Some("new")),
+            r#"<rdf:Description rdf:about="" pdf:Producer="new"/>"#);
+        assert_eq!(set_property(xml, "pdf:Producer", None),
+            r#"<rdf:Description rdf:about=""/>"#);
+    }
+
+    #[test]
+    fn insert() {
+        let xml = r#"<rdf:Description rdf:about=""></rdf:Description>"#;
+        assert_eq!(set_property(xml, "pdf:Producer", Some("new")),
+            r#"<rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/"><pdf:Producer>new</pdf:Producer></rdf:Description>"#);
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Formatter};
 use crate::error::*;
 use crate::object::*;
+use crate::enc::{encode, LZWFlateParams, StreamFilter};
 use crate as pdf;
 use datasize::DataSize;
 
@@ -8,7 +9,7 @@ use datasize::DataSize;
 // Cross-reference table //
 ///////////////////////////
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum XRef {
     /// Not currently used.
     Free {
@@ -136,6 +137,8 @@ impl XRefTable {
                 XRef::Free { next_obj_nr, gen_nr } => (0, next_obj_nr, gen_nr),
                 XRef::Raw { pos, gen_nr } => (1, pos as u64, gen_nr),
                 XRef::Stream { stream_id, index } => (2, stream_id, index as u64),
+                // numbers that were never used, like the one a previous save reserved for the info dictionary
+                XRef::Invalid => (0, 0, 0),
                 x => bail!("invalid xref entry: {:?}", x)
             };
             data.push(t);
@@ -148,8 +151,11 @@ impl XRefTable {
             prev: None,
             w: vec![1, a_w, b_w],
         };
-        
-        Ok(Stream::new(info, data))
+        // the entries of neighbouring objects differ little, which the Up predictor turns into zeros
+        let params = LZWFlateParams { predictor: 12, columns: (1 + a_w + b_w) as i32, ..Default::default() };
+        let filter = StreamFilter::FlateDecode(params);
+        let data = t!(encode(&data, &filter));
+        Ok(Stream::from_compressed(info, data, vec![filter]))
     }
 }
 
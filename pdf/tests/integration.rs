@@ -1,4 +1,4 @@
-use pdf::file::FileOptions;
+use pdf::file::{FileOptions, Storage};
 use pdf::object::*;
 use pdf::parser::{parse, ParseFlags};
 use std::path::{Path, PathBuf};
@@ -24,6 +24,10 @@ fn files() -> PathBuf {
 fn file_path(s: &str) -> PathBuf {
     files().join(s)
 }
+/// A path in the temp directory that no other test or concurrent run writes to.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pdf_{}_{}.pdf", name, std::process::id()))
+}
 fn dir_pdfs(path: PathBuf) -> impl Iterator<Item = PathBuf> {
     path.read_dir()
         .unwrap()
@@ -59,6 +63,2053 @@ fn read_pages() {
     }
 }
 
+#[cfg(feature = "cache")]
+#[test]
+fn bounded_cache() {
+    let file = run!(FileOptions::cached_bounded(4, 0).open(file_path("xelatex.pdf")));
+    for i in 0..file.num_pages() {
+        let page = run!(file.get_page(i));
+        if let Some(ref contents) = page.contents {
+            let _ = run!(contents.operations(&file.resolver()));
+        }
+    }
+    let objects = file.cache_stats().objects.unwrap();
+    assert!(objects.entries <= 4);
+    assert!(objects.evictions > 0);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn cache_budget() {
+    use pdf::file::{CacheLimits, Eviction};
+
+    let streams = CacheLimits::bytes(4096).eviction(Eviction::LargestFirst);
+    let file = run!(FileOptions::cached_limited(CacheLimits::entries(100), streams).open(file_path("xelatex.pdf")));
+    for _ in 0..2 {
+        for i in 0..file.num_pages() {
+            let page = run!(file.get_page(i));
+            if let Some(ref contents) = page.contents {
+                let _ = run!(contents.operations(&file.resolver()));
+            }
+        }
+    }
+    let stats = file.cache_stats();
+    let (objects, streams) = (stats.objects.unwrap(), stats.streams.unwrap());
+    assert!(objects.hits > 0 && objects.entries <= 100);
+    assert!(streams.misses > 0 && streams.size <= 4096);
+}
+
+#[cfg(all(feature = "parallel", feature = "cache"))]
+#[test]
+fn decode_streams() {
+    use pdf::file::CacheLimits;
+
+    let limits = CacheLimits::default();
+    let file = run!(FileOptions::cached_limited(limits, limits).open(file_path("xelatex.pdf")));
+    let decoded = file.decode_streams();
+    assert!(decoded > 0);
+    assert_eq!(file.cache_stats().streams.unwrap().entries, decoded);
+
+    let page = run!(file.get_page(0));
+    let _ = run!(page.contents.as_ref().unwrap().operations(&file.resolver()));
+    let streams = file.cache_stats().streams.unwrap();
+    assert_eq!(streams.misses, decoded as u64);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn interpret_pages() {
+    use pdf::interpret::{ContentInterpreter, Event};
+
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let resolver = file.resolver();
+    let mut glyphs = 0;
+    for page in file.pages() {
+        let page = run!(page);
+        let ops = match page.contents {
+            Some(ref contents) => run!(contents.operations(&resolver)),
+            None => continue,
+        };
+        let mut interpreter = run!(ContentInterpreter::for_page(&page, &resolver));
+        run!(interpreter.run(&ops, &mut |event, _| {
+            if let Event::TextShown { glyphs: shown, .. } = *event {
+                glyphs += shown.len();
+            }
+            Ok(())
+        }));
+    }
+    assert!(glyphs > 0);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn read_backend() {
+    use pdf::backend::ReadBackend;
+
+    for name in ["xelatex.pdf", "example.pdf", "libreoffice.pdf"] {
+        let in_memory = run!(FileOptions::cached().open(file_path(name)));
+        let lazy = run!(FileOptions::cached_bounded(16, 4).open_lazy(file_path(name)));
+        // tiny windows, so objects have to be read again with larger ones
+        let reader = std::fs::File::open(file_path(name)).unwrap();
+        let tiny = run!(FileOptions::uncached().load(run!(ReadBackend::new(reader)).with_window(16)));
+
+        // the files have different types
+        macro_rules! content {
+            ($file:expr, $i:expr) => {{
+                let page = run!($file.get_page($i));
+                let ops = run!(page.contents.as_ref().unwrap().operations(&$file.resolver()));
+                run!(pdf::content::serialize_ops(&ops))
+            }};
+        }
+        assert_eq!(in_memory.num_pages(), lazy.num_pages());
+        for i in 0..in_memory.num_pages() {
+            let expected = content!(in_memory, i);
+            assert_eq!(content!(lazy, i), expected);
+            assert_eq!(content!(tiny, i), expected);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_file() {
+    use pdf::async_file::{AsyncBackend, AsyncFile};
+    use std::future::Future;
+    use std::ops::Range;
+
+    // the futures below never wait, so polling once is enough
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match std::pin::pin!(f).poll(&mut cx) {
+            std::task::Poll::Ready(v) => v,
+            std::task::Poll::Pending => panic!("pending"),
+        }
+    }
+    struct Remote(Vec<u8>);
+    impl AsyncBackend for Remote {
+        async fn size(&self) -> pdf::error::Result<usize> {
+            Ok(self.0.len())
+        }
+        async fn read(&self, range: Range<usize>) -> pdf::error::Result<Vec<u8>> {
+            Ok(self.0[range].to_vec())
+        }
+    }
+
+    let data = std::fs::read(file_path("xelatex.pdf")).unwrap();
+    let expected = run!(FileOptions::cached().load(data.clone()));
+    let len = data.len();
+    let file = run!(block_on(AsyncFile::load(FileOptions::cached(), Remote(data), 1024)));
+    let ops = run!(block_on(file.with(|file| {
+        let page = file.get_page(0)?;
+        page.contents.as_ref().unwrap().operations(&file.resolver())
+    })));
+    let page = run!(expected.get_page(0));
+    assert_eq!(ops.len(), run!(page.contents.as_ref().unwrap().operations(&expected.resolver())).len());
+    assert!(file.blocks().loaded() < len);
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn render_pages() {
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let pixmap = run!(pdf::render::render_page(&page, &resolver, 36.));
+    assert_eq!(pixmap.data.len(), (pixmap.width * pixmap.height * 4) as usize);
+    assert!(pixmap.data.chunks(4).any(|px| px[..3] != [255, 255, 255]));
+}
+
+#[test]
+fn structure_tree() {
+    use pdf::content::parse_ops;
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let mut storage = FileOptions::uncached().storage();
+    let mut refs = || storage.promise::<Primitive>().get_inner();
+    let (root, doc, para, span, link, page, annot) = (refs(), refs(), refs(), refs(), refs(), refs(), refs());
+    let dict = |entries: &[(&str, Primitive)]| {
+        let mut dict = Dictionary::new();
+        for (key, value) in entries {
+            dict.insert(*key, value.clone());
+        }
+        Primitive::Dictionary(dict)
+    };
+    let objects = [
+        (root, dict(&[
+            ("Type", Primitive::name("StructTreeRoot")),
+            ("K", Primitive::Array(vec![doc.into()])),
+            // page 0 with MCIDs 0 to 2, the annotation with StructParent 5
+            ("ParentTree", dict(&[("Nums", Primitive::Array(vec![
+                0.into(), Primitive::Array(vec![para.into(), para.into(), span.into()]),
+                5.into(), link.into(),
+            ]))])),
+            ("RoleMap", dict(&[("Para", Primitive::name("P")), ("Loop", Primitive::name("Loop2")), ("Loop2", Primitive::name("Loop"))])),
+        ])),
+        (doc, dict(&[("S", Primitive::name("Document")), ("P", root.into()), ("K", Primitive::Array(vec![para.into(), link.into()]))])),
+        (para, dict(&[
+            ("S", Primitive::name("Para")), ("P", doc.into()), ("Pg", page.into()),
+            ("K", Primitive::Array(vec![0.into(), 1.into(), span.into()])),
+        ])),
+        (span, dict(&[
+            ("S", Primitive::name("Loop")), ("P", para.into()),
+            ("K", dict(&[("Type", Primitive::name("MCR")), ("Pg", page.into()), ("MCID", 2.into())])),
+        ])),
+        (link, dict(&[
+            ("S", Primitive::name("Link")), ("P", doc.into()),
+            ("K", dict(&[("Type", Primitive::name("OBJR")), ("Obj", annot.into())])),
+        ])),
+        (page, dict(&[("Type", Primitive::name("Page"))])),
+        (annot, dict(&[("Type", Primitive::name("Annot")), ("Subtype", Primitive::name("Link"))])),
+    ];
+    for (r, object) in objects {
+        run!(storage.update(r, object));
+    }
+
+    let resolver = storage.resolver();
+    let root = run!(resolver.get::<StructTreeRoot>(Ref::new(root)));
+    assert_eq!(run!(root.parent_of(0, 0, &resolver)), Some(para));
+    assert_eq!(run!(root.parent_of(0, 1, &resolver)), Some(para));
+    assert_eq!(run!(root.parent_of(0, 2, &resolver)), Some(span));
+    assert_eq!(run!(root.parent_of(0, 3, &resolver)), None);
+    assert_eq!(run!(root.parent_of(5, 0, &resolver)), Some(link));
+    assert_eq!(run!(root.parent_of(1, 0, &resolver)), None);
+
+    let doc = &root.children[0];
+    assert!(matches!(root.standard_type(doc), StructType::Document));
+    let elems: Vec<_> = doc.children.iter().map(|kid| match *kid {
+        StructKid::Elem(ref elem) => run!(elem.load(&resolver)),
+        ref kid => panic!("{:?}", kid),
+    }).collect();
+    let (para_elem, link_elem) = (&elems[0], &elems[1]);
+    assert!(matches!(root.standard_type(para_elem), StructType::P));
+    assert!(matches!(link_elem.children[..], [StructKid::Object { obj, page: None }] if obj == annot));
+
+    // the MCIDs inherit the page of the element
+    let page_ref = Ref::<Page>::new(page);
+    let mcids: Vec<_> = para_elem.marked_content().collect();
+    assert_eq!(mcids, [(Some(page_ref), 0), (Some(page_ref), 1)]);
+    let span_elem = match para_elem.children[2] {
+        StructKid::Elem(ref elem) => run!(elem.load(&resolver)),
+        ref kid => panic!("{:?}", kid),
+    };
+    assert_eq!(span_elem.marked_content().collect::<Vec<_>>(), [(Some(page_ref), 2)]);
+    // a cyclic role map ends at a nonstandard type
+    assert!(matches!(root.standard_type(&span_elem), StructType::Other(_)));
+
+    // the innermost sequence with an MCID owns the content
+    let ops = run!(parse_ops(
+        b"/P <</MCID 0>> BDC (a) Tj EMC /P <</MCID 1>> BDC (b) Tj /Span <</MCID 2 /ActualText (c!)>> BDC (c) Tj EMC EMC",
+        &resolver,
+    ));
+    let map = run!(MarkedContentMap::from_ops(&ops, None, &resolver));
+    let mut mcids: Vec<_> = map.mcids().collect();
+    mcids.sort();
+    assert_eq!(mcids, [0, 1, 2]);
+    let shown = |mcid| map.ops(mcid).unwrap().iter().filter_map(|op| match *op {
+        pdf::content::Op::TextDraw { ref text } => Some(text.as_bytes().to_vec()),
+        _ => None,
+    }).collect::<Vec<_>>();
+    assert_eq!(shown(1), [b"b"]);
+    assert_eq!(shown(2), [b"c"]);
+    assert_eq!(run!(map.text(0, None, &resolver)).as_deref(), Some("a"));
+    assert_eq!(run!(map.text(1, None, &resolver)).as_deref(), Some("b"));
+    assert_eq!(run!(map.text(2, None, &resolver)).as_deref(), Some("c!"));
+    assert_eq!(run!(map.text(3, None, &resolver)), None);
+}
+
+#[test]
+fn dangling_ref_in_option() {
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let mut storage = FileOptions::cached().storage();
+    let obj = run!(storage.create(Dictionary::new())).get_ref().get_inner();
+    // object 0 is always free
+    let free = PlainRef { id: 0, gen: 0 };
+    let resolver = storage.resolver();
+    // a lookup through the cache wraps the error, and Option still treats it as absent, every time
+    for r in [free, free] {
+        assert!(resolver.get::<Dictionary>(Ref::new(r)).is_err());
+        let found = run!(Option::<RcRef<Dictionary>>::from_primitive(Primitive::Reference(r), &resolver));
+        assert!(found.is_none());
+    }
+    // other errors are not hidden
+    let e = Option::<RcRef<Pattern>>::from_primitive(Primitive::Reference(obj), &resolver);
+    assert!(e.is_err());
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn dangling_struct_tree_root() {
+    // the catalog of ep.pdf points /StructTreeRoot at the free object 133
+    let file = run!(FileOptions::cached().open(file_path("ep.pdf")));
+    assert!(file.get_root().struct_tree_root.is_none());
+    assert!(file.num_pages() > 0);
+}
+
+#[test]
+fn deep_structure_tree() {
+    use pdf::error::PdfError;
+    use pdf::primitive::{Dictionary, Primitive};
+
+    // deeper than ParseOptions::max_depth
+    let depth = 200;
+    let mut storage = FileOptions::uncached().storage();
+    let root = storage.promise::<Primitive>().get_inner();
+    let elems: Vec<_> = (0..depth).map(|_| storage.promise::<Primitive>().get_inner()).collect();
+    let elem = |i: usize, kid: Primitive| {
+        let mut dict = Dictionary::new();
+        dict.insert("S", Primitive::name("Div"));
+        dict.insert("P", if i == 0 { root } else { elems[i - 1] });
+        dict.insert("K", kid);
+        Primitive::Dictionary(dict)
+    };
+    let mut root_dict = Dictionary::new();
+    root_dict.insert("Type", Primitive::name("StructTreeRoot"));
+    root_dict.insert("K", Primitive::Array(vec![elems[0].into()]));
+    run!(storage.update(root, Primitive::Dictionary(root_dict)));
+    for i in 0..depth {
+        let kid = if i + 1 < depth { elems[i + 1].into() } else { 0.into() };
+        run!(storage.update(elems[i], elem(i, kid)));
+    }
+    let mut depths = vec![];
+    {
+        let resolver = storage.resolver();
+        let tree = run!(resolver.get::<StructTreeRoot>(Ref::new(root)));
+        run!(tree.children[0].walk(&resolver, &mut |_, depth| depths.push(depth)));
+    }
+    assert_eq!(depths, (0..depth).collect::<Vec<_>>());
+
+    // an element below itself
+    run!(storage.update(elems[depth - 1], elem(depth - 1, elems[100].into())));
+    let resolver = storage.resolver();
+    let tree = run!(resolver.get::<StructTreeRoot>(Ref::new(root)));
+    let e = tree.children[0].walk(&resolver, &mut |_, _| {}).unwrap_err();
+    assert!(matches!(e, PdfError::ReferenceCycle { obj_nr, .. } if obj_nr == elems[100].id), "{}", e);
+}
+
+#[test]
+fn structure_builder() {
+    use pdf::build::{CatalogBuilder, PageBuilder, PdfBuilder, StructureBuilder};
+    use pdf::content::Op;
+    use pdf::primitive::PdfString;
+
+    let text = |s: &str| Op::TextDraw { text: PdfString::new(s.as_bytes().into()) };
+    let mut structure = StructureBuilder::new();
+    let doc = structure.add(None, StructType::Document);
+    let heading = structure.add(Some(doc), StructType::H1);
+    let para = structure.add(Some(doc), StructType::P);
+    let mut first = structure.mark(heading, 0, [text("Title")]);
+    first.extend(structure.mark(para, 0, [text("first")]));
+    // the paragraph continues on the third page, the second page is untagged
+    let third = structure.mark(para, 2, [text("second")]);
+
+    let pages = vec![
+        PageBuilder { ops: first, ..PageBuilder::default() },
+        PageBuilder { ops: vec![text("untagged")], ..PageBuilder::default() },
+        PageBuilder { ops: third, ..PageBuilder::default() },
+    ];
+    let builder = PdfBuilder::new(FileOptions::cached());
+    let data = run!(builder.build(CatalogBuilder::from_pages(pages).structure(structure)));
+
+    let file = run!(FileOptions::cached().load(data));
+    let resolver = file.resolver();
+    let catalog = file.get_root();
+    assert!(catalog.mark_info.as_ref().unwrap().marked);
+    let root = catalog.struct_tree_root.as_ref().unwrap();
+    assert_eq!(root.parent_tree_next_key, Some(3));
+
+    let pages: Vec<_> = file.pages().map(|p| run!(p)).collect();
+    let struct_parents: Vec<_> = pages.iter().map(|p| p.struct_parents).collect();
+    assert_eq!(struct_parents, [Some(0), None, Some(2)]);
+
+    let elem = |kid: &StructKid| match *kid {
+        StructKid::Elem(ref elem) => run!(elem.load(&resolver)),
+        ref kid => panic!("{:?}", kid),
+    };
+    let doc = root.children[0].clone();
+    assert!(matches!(doc.struct_type, StructType::Document));
+    let (heading, para) = (elem(&doc.children[0]), elem(&doc.children[1]));
+    assert!(matches!(heading.struct_type, StructType::H1));
+    assert!(matches!(para.struct_type, StructType::P));
+
+    // the MCIDs count per page, and map back to their element through the parent tree
+    let heading_ref = heading.as_ref().unwrap().get_inner();
+    let para_ref = para.as_ref().unwrap().get_inner();
+    assert_eq!(run!(root.parent_of(0, 0, &resolver)), Some(heading_ref));
+    assert_eq!(run!(root.parent_of(0, 1, &resolver)), Some(para_ref));
+    assert_eq!(run!(root.parent_of(2, 0, &resolver)), Some(para_ref));
+    assert_eq!(run!(root.parent_of(1, 0, &resolver)), None);
+
+    // the content on another page than the element's /Pg names its page
+    let page_refs: Vec<_> = pages.iter().map(|p| Ref::<Page>::new(p.get_ref().get_inner())).collect();
+    assert_eq!(para.page.map(|p| p.get_inner()), Some(page_refs[0].get_inner()));
+    assert_eq!(para.marked_content().collect::<Vec<_>>(), [(Some(page_refs[0]), 1), (Some(page_refs[2]), 0)]);
+
+    let map = run!(MarkedContentMap::from_ops(&run!(pages[2].contents.as_ref().unwrap().operations(&resolver)), None, &resolver));
+    assert_eq!(run!(map.text(0, None, &resolver)).as_deref(), Some("second"));
+}
+
+#[test]
+fn page_boxes() {
+    use pdf::content::{Op, Point};
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [100 100 712 892] /CropBox [50 80 650 850] /TrimBox [600 800 150 150]
+    /Rotate -90 /Resources << >> /Contents 5 0 R
+    /Annots [4 0 R << /Type /Annot /Subtype /Ink /Rect [100 100 200 200] /InkList [[110 110 190 190]] >>
+        << /Type /Annot /Subtype /FreeText /Rect [300 300 400 400] /CL [150 150 250 250 300 350] >>] >> endobj
+4 0 obj << /Type /Annot /Subtype /Highlight /Rect [200 200 300 220] /QuadPoints [200 220 300 220 200 200 300 200] >> endobj
+5 0 obj << /Length 18 >> stream
+0 0 m 100 100 l S
+endstream endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    // resolve from one handle and write to the other, both have the same objects
+    let load = || FileOptions::cached().parse_options(ParseOptions::tolerant()).load(data.to_vec());
+    let file = run!(load());
+    let mut out = run!(load());
+    let page = run!(file.get_page(0));
+    assert_eq!(run!(page.audit_boxes()), [
+        PageBoxIssue::ExceedsMediaBox { name: "CropBox" },
+        PageBoxIssue::Inverted { name: "TrimBox" },
+        PageBoxIssue::MediaBoxOrigin { left: 100., bottom: 100. },
+        PageBoxIssue::Rotation { rotate: -90 },
+    ]);
+
+    let normalized = run!(page.normalize_boxes(&file.resolver(), &mut out));
+    assert_eq!(run!(normalized.audit_boxes()), []);
+    assert_eq!(run!(normalized.media_box()), Rectangle { left: 0., bottom: 0., right: 612., top: 792. });
+    // clipped to the MediaBox
+    assert_eq!(run!(normalized.crop_box()), Rectangle { left: 0., bottom: 0., right: 550., top: 750. });
+    assert_eq!(normalized.trim_box, Some(Rectangle { left: 50., bottom: 50., right: 500., top: 700. }));
+    assert_eq!(normalized.rotate, 270);
+    let ops = run!(normalized.contents.as_ref().unwrap().operations(&file.resolver()));
+    assert!(matches!(ops[1], Op::Transform { matrix } if matrix.e == -100. && matrix.f == -100.));
+
+    // all of the geometry of the annotations moves along, the indirect one is updated in place
+    let p = |x, y| Point { x, y };
+    let highlight = run!(out.get::<Annot>(Ref::new(PlainRef { id: 4, gen: 0 })));
+    assert_eq!(highlight.rect, Some(Rectangle { left: 100., bottom: 100., right: 200., top: 120. }));
+    assert_eq!(run!(highlight.quad_points(&out)), Some(vec![[p(100., 120.), p(200., 120.), p(100., 100.), p(200., 100.)]]));
+    let annots = run!(normalized.annotations(&out));
+    assert_eq!(annots[0].as_ref().map(|r| r.get_inner().id), Some(4));
+    assert_eq!(run!(annots[1].ink_list(&out)), Some(vec![vec![p(10., 10.), p(90., 90.)]]));
+    assert_eq!(run!(annots[2].callout_line(&out)), Some(vec![p(50., 50.), p(150., 150.), p(200., 250.)]));
+    assert_eq!(annots[2].rect, Some(Rectangle { left: 200., bottom: 200., right: 300., top: 300. }));
+
+    // nothing to move
+    let again = run!(normalized.normalize_boxes(&out.resolver(), &mut NoUpdate));
+    assert_eq!(run!(again.media_box()), run!(normalized.media_box()));
+}
+
+#[test]
+fn svg_export() {
+    use pdf::svg::{page_to_svg, SvgText};
+
+    let file = run!(FileOptions::cached().open(file_path("jpeg.pdf")));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let svg = run!(page_to_svg(&page, &resolver, SvgText::Paths));
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains("<image "));
+}
+
+#[test]
+fn page_thumbnail() {
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let old_page = run!(file.get_page(0));
+    let mut page = (*old_page).clone();
+    assert!(run!(page.thumbnail(&file.resolver())).is_none());
+
+    run!(page.set_thumbnail(2, 1, &[255, 0, 0, 0, 0, 255], &mut file));
+    let page = run!(PageRc::update(page, &old_page, &mut file));
+    let thumb = run!(page.thumbnail(&file.resolver())).unwrap();
+    assert_eq!(run!(thumb.rgba_data(&file.resolver())), [255, 0, 0, 255, 0, 0, 255, 255]);
+}
+
+#[test]
+fn page_annotations() {
+    use pdf::content::Point;
+
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let old_page = run!(file.get_page(0));
+    let before = run!(old_page.annotations(&file)).len();
+
+    let mut page = (*old_page).clone();
+    let quad = [Point { x: 10., y: 20. }, Point { x: 50., y: 20. }, Point { x: 10., y: 10. }, Point { x: 50., y: 10. }];
+    let annot = run!(Annot::text_markup(TextMarkup::Underline, &[quad], [0., 0., 1.], &mut file));
+    let annot = run!(page.add_annotation(annot, &mut file));
+    run!(PageRc::update(page, &old_page, &mut file));
+
+    let path = temp_path("page_annotations");
+    run!(file.save_to(&path));
+    let mut file = run!(FileOptions::cached().open(&path));
+    let page = run!(file.get_page(0));
+    let annots = run!(page.annotations(&file));
+    assert_eq!(annots.len(), before + 1);
+    assert_eq!(annots[before].subtype.as_str(), "Underline");
+    assert_eq!(annots[before].as_ref().map(|r| r.get_inner()), Some(annot.get_ref().get_inner()));
+
+    let mut page = (*page).clone();
+    let removed = run!(page.remove_annotation(before, &mut file));
+    assert_eq!(removed.subtype.as_str(), "Underline");
+    assert_eq!(run!(page.annotations(&file)).len(), before);
+    assert!(page.remove_annotation(before, &mut file).is_err());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn article_threads() {
+    use pdf::build::{CatalogBuilder, PageBuilder};
+    use pdf::error::PdfError;
+    use pdf::primitive::PdfString;
+
+    let mut storage = FileOptions::uncached().storage();
+    let pages: Vec<_> = (0..2).map(|_| PageBuilder::default()).collect();
+    let mut catalog = run!(CatalogBuilder::from_pages(pages).build(&mut storage));
+    // a document without threads has no /Threads entry
+    let written = run!(catalog.to_primitive(&mut storage).and_then(|p| p.into_dictionary()));
+    assert!(written.get("Threads").is_none());
+
+    let page_refs: Vec<Ref<Page>> = catalog.pages.kids.iter().map(|r| Ref::new(r.get_inner())).collect();
+    let rect = |top| Rectangle { left: 0., bottom: top - 10., right: 100., top };
+    let info = InfoDict { title: Some(PdfString::from("Story")), ..InfoDict::default() };
+    let beads = [(page_refs[0], rect(100.)), (page_refs[0], rect(50.)), (page_refs[1], rect(100.))];
+    let thread = run!(Thread::create(Some(info), &beads, &mut storage));
+    let empty = run!(Thread::create(None, &[], &mut storage));
+    catalog.threads = Some(vec![thread.get_ref(), empty.get_ref()]);
+    let root = run!(storage.create(catalog)).get_ref();
+
+    // the last bead links back to the second instead of the first
+    let broken = run!(Thread::create(None, &beads, &mut storage));
+    let ids: Vec<_> = broken.beads(&storage.resolver()).map(|b| run!(b).get_ref()).collect();
+    let mut last = (*run!(storage.resolver().get(ids[2]))).clone();
+    last.next = ids[1];
+    run!(storage.update(ids[2].get_inner(), last));
+
+    let resolver = storage.resolver();
+    let catalog = run!(resolver.get(root));
+    let threads = catalog.threads.as_ref().unwrap();
+    let thread = run!(resolver.get(threads[0]));
+    assert_eq!(thread.info.as_ref().unwrap().title.as_ref().unwrap().to_string_lossy(), "Story");
+    let read: Vec<_> = thread.beads(&resolver).map(|b| run!(b)).collect();
+    assert_eq!(read.iter().map(|b| (b.page, b.rect)).collect::<Vec<_>>(), beads);
+    // only the first bead points to the thread, and the links are circular
+    assert_eq!(read[0].thread, Some(threads[0]));
+    assert!(read[1..].iter().all(|b| b.thread.is_none()));
+    assert_eq!(read[0].prev, read[2].get_ref());
+    assert_eq!(read[2].next, read[0].get_ref());
+    assert_eq!(run!(resolver.get(threads[1])).beads(&resolver).count(), 0);
+
+    let e = broken.beads(&resolver).find_map(|b| b.err()).unwrap();
+    assert!(matches!(e, PdfError::ReferenceCycle { obj_nr, .. } if obj_nr == ids[1].get_inner().id), "{}", e);
+}
+
+#[test]
+fn lang_and_output_intents() {
+    use pdf::build::{CatalogBuilder, PageBuilder};
+    use pdf::primitive::PdfString;
+
+    let mut storage = FileOptions::uncached().storage();
+    let mut catalog = run!(CatalogBuilder::from_pages(vec![PageBuilder::default()]).build(&mut storage));
+    let written = run!(catalog.to_primitive(&mut storage).and_then(|p| p.into_dictionary()));
+    assert!(written.get("OutputIntents").is_none());
+    assert!(written.get("Lang").is_none());
+
+    let icc = IccInfo { components: 4, alternate: None, range: None, metadata: None };
+    let profile = run!(storage.create(Stream::new(icc, b"icc profile".to_vec())));
+    catalog.lang = Some(PdfString::from("de-CH"));
+    catalog.output_intents = Some(vec![
+        OutputIntent {
+            subtype: OutputIntentSubtype::PdfX,
+            output_condition: Some(PdfString::from("Coated FOGRA39")),
+            output_condition_identifier: PdfString::from("FOGRA39"),
+            registry_name: Some(PdfString::from("http://www.color.org")),
+            info: None,
+            dest_output_profile: Some(profile),
+        },
+        OutputIntent {
+            subtype: OutputIntentSubtype::Other("ACME_X".into()),
+            output_condition: None,
+            output_condition_identifier: PdfString::from("Custom"),
+            registry_name: None,
+            info: Some(PdfString::from("in-house press")),
+            dest_output_profile: None,
+        },
+    ]);
+    let root = run!(storage.create(catalog)).get_ref();
+
+    let resolver = storage.resolver();
+    let catalog = run!(resolver.get(root));
+    assert_eq!(catalog.lang.as_ref().unwrap().to_string_lossy(), "de-CH");
+    let intents = catalog.output_intents.as_ref().unwrap();
+    assert!(matches!(intents[0].subtype, OutputIntentSubtype::PdfX));
+    assert_eq!(intents[0].output_condition_identifier.to_string_lossy(), "FOGRA39");
+    let profile = intents[0].dest_output_profile.as_ref().unwrap();
+    assert_eq!(profile.info.components, 4);
+    assert_eq!(&*run!((**profile).data(&resolver)), b"icc profile");
+    assert!(matches!(intents[1].subtype, OutputIntentSubtype::Other(ref s) if s == "ACME_X"));
+    assert_eq!(intents[1].info.as_ref().unwrap().to_string_lossy(), "in-house press");
+    assert!(intents[1].dest_output_profile.is_none());
+}
+
+#[test]
+fn open_action() {
+    use pdf::build::{CatalogBuilder, PageBuilder, PdfBuilder};
+    use pdf::primitive::Name;
+
+    let pages = || (0..3).map(|_| PageBuilder::default()).collect::<Vec<_>>();
+    let builder = PdfBuilder::new(FileOptions::cached());
+    let catalog = CatalogBuilder::from_pages(pages()).open_at(1, DestView::FitH { top: 500. });
+    let data = run!(builder.build(catalog));
+    let file = run!(FileOptions::cached().load(data));
+    let second = run!(file.get_page(1)).get_ref().get_inner();
+    match file.get_root().open_action {
+        Some(OpenAction::Dest(Dest { page: Some(page), view: DestView::FitH { top } })) => {
+            assert_eq!(page.get_inner(), second);
+            assert_eq!(top, 500.);
+        }
+        ref action => panic!("{:?}", action),
+    }
+
+    let builder = PdfBuilder::new(FileOptions::cached());
+    assert!(builder.build(CatalogBuilder::from_pages(pages()).open_at(3, DestView::Fit)).is_err());
+
+    let mut storage = FileOptions::uncached().storage();
+    let mut catalog = run!(CatalogBuilder::from_pages(pages()).build(&mut storage));
+    catalog.open_action = Some(Action::Named(Name::from("Print")).into());
+    let root = run!(storage.create(catalog)).get_ref();
+    let catalog = run!(storage.resolver().get(root));
+    assert!(matches!(catalog.open_action, Some(OpenAction::Action(Action::Named(ref name))) if name.as_str() == "Print"));
+}
+
+#[test]
+fn viewer_preferences() {
+    use pdf::build::{CatalogBuilder, PageBuilder};
+    use pdf::primitive::Primitive;
+
+    let mut storage = FileOptions::uncached().storage();
+    let mut catalog = run!(CatalogBuilder::from_pages(vec![PageBuilder::default()]).build(&mut storage));
+    let mut prefs = ViewerPreferences {
+        hide_toolbar: true,
+        display_doc_title: true,
+        non_full_screen_page_mode: Some(NonFullScreenPageMode::UseOutlines),
+        direction: Some(ReadingDirection::R2L),
+        print_area: Some(PageBoundary::TrimBox),
+        print_scaling: Some(PrintScaling::NoScaling),
+        duplex: Some(Duplex::DuplexFlipLongEdge),
+        num_copies: Some(2),
+        ..ViewerPreferences::default()
+    };
+    prefs.set_print_page_ranges(&[0..=2, 5..=5]);
+    assert_eq!(prefs.print_page_range, Some(vec![1, 3, 6, 6]));
+    catalog.viewer_preferences = Some(prefs);
+    catalog.page_layout = Some(PageLayout::TwoPageRight);
+    catalog.page_mode = Some(PageMode::FullScreen);
+
+    let written = run!(catalog.to_primitive(&mut storage).and_then(|p| p.into_dictionary()));
+    let written_prefs = run!(written.get("ViewerPreferences").unwrap().clone().into_dictionary());
+    assert_eq!(written_prefs.get("PrintScaling"), Some(&Primitive::name("None")));
+    assert_eq!(written_prefs.get("PrintPageRange"), Some(&Primitive::Array(vec![1.into(), 3.into(), 6.into(), 6.into()])));
+    assert_eq!(written.get("PageLayout"), Some(&Primitive::name("TwoPageRight")));
+    let root = run!(storage.create(catalog)).get_ref();
+
+    let resolver = storage.resolver();
+    let catalog = run!(resolver.get(root));
+    assert_eq!(catalog.page_layout, Some(PageLayout::TwoPageRight));
+    assert_eq!(catalog.page_mode, Some(PageMode::FullScreen));
+    let prefs = catalog.viewer_preferences.as_ref().unwrap();
+    assert!(prefs.hide_toolbar && prefs.display_doc_title && !prefs.hide_menubar);
+    assert_eq!(prefs.non_full_screen_page_mode, Some(NonFullScreenPageMode::UseOutlines));
+    assert_eq!(prefs.direction, Some(ReadingDirection::R2L));
+    assert_eq!((prefs.print_area, prefs.print_clip), (Some(PageBoundary::TrimBox), None));
+    assert_eq!(prefs.print_scaling, Some(PrintScaling::NoScaling));
+    assert_eq!(prefs.duplex, Some(Duplex::DuplexFlipLongEdge));
+    assert_eq!(prefs.num_copies, Some(2));
+    assert_eq!(prefs.print_page_ranges(), [0..=2, 5..=5]);
+
+    // page numbers count from one, and ranges that end before they start are skipped
+    let prefs = ViewerPreferences { print_page_range: Some(vec![0, 2, 4, 3, 7, 9, 11]), ..ViewerPreferences::default() };
+    assert_eq!(prefs.print_page_ranges(), [6..=8]);
+}
+
+#[test]
+fn invalid_catalog_hints() {
+    use pdf::object::{Warning, Warnings};
+    use pdf::primitive::Primitive;
+
+    let mut file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let root = file.trailer.root.get_ref().get_inner();
+    let mut dict = run!(run!(file.resolver().resolve(root)).into_dictionary());
+    for key in ["PageMode", "PageLayout", "ViewerPreferences", "OpenAction", "Threads", "MarkInfo", "Lang",
+        "OutputIntents", "Collection", "Requirements", "Extensions", "Perms", "PieceInfo"] {
+        dict.insert(key, Primitive::name("Foo"));
+    }
+    run!(file.update(root, dict));
+    let path = temp_path("invalid_catalog_hints");
+    run!(file.save_to(&path));
+    let data = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    // the document can still be read with the default options, without the invalid entries
+    let warnings = Warnings::new();
+    let file = run!(FileOptions::uncached().parse_options(ParseOptions::strict().collect_warnings(&warnings)).load(data));
+    let catalog = file.get_root();
+    assert!(catalog.page_mode.is_none() && catalog.page_layout.is_none() && catalog.viewer_preferences.is_none());
+    assert!(catalog.open_action.is_none() && catalog.threads.is_none() && catalog.lang.is_none());
+    assert!(catalog.requirements.is_empty() && catalog.output_intents.is_none());
+    assert_eq!(file.num_pages(), 1);
+    let warnings = warnings.take();
+    assert!(warnings.iter().any(|w| matches!(w, Warning::Ignored { error } if error.contains("PageMode"))));
+}
+
+#[test]
+fn comment_threads() {
+    use pdf::content::Point;
+
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let old_page = run!(file.get_page(0));
+    let mut page = (*old_page).clone();
+    let quad = [Point { x: 10., y: 20. }, Point { x: 50., y: 20. }, Point { x: 10., y: 10. }, Point { x: 50., y: 10. }];
+    let note = run!(Annot::text_markup(TextMarkup::Highlight, &[quad], [1., 1., 0.], &mut file));
+    let note = run!(page.add_annotation(note, &mut file));
+    let reply = run!(page.add_reply(&note, "agreed", &mut file));
+    run!(page.add_reply(&reply, "me too", &mut file));
+    let popup_rect = Rectangle { left: 60., bottom: 10., right: 160., top: 60. };
+    let (note, popup) = run!(page.add_popup(&note, popup_rect, &mut file));
+    run!(PageRc::update(page, &old_page, &mut file));
+
+    let path = temp_path("comment_threads");
+    run!(file.save_to(&path));
+    let file = run!(FileOptions::cached().open(&path));
+    let page = run!(file.get_page(0));
+    let threads = run!(page.comment_threads(&file));
+    let thread = threads.iter().find(|t| t.annot.as_ref() == Some(note.get_ref())).unwrap();
+    assert_eq!(thread.annot.popup, Some(popup.get_ref()));
+    assert_eq!(thread.replies.len(), 1);
+    assert_eq!(thread.replies[0].annot.contents.as_ref().unwrap().to_string_lossy(), "agreed");
+    assert_eq!(thread.replies[0].replies[0].annot.in_reply_to, Some(reply.get_ref()));
+    assert!(threads.iter().all(|t| t.annot.subtype.as_str() != "Popup" && !t.annot.is_reply()));
+    assert_eq!(run!(page.replies(note.get_ref(), &file)).len(), 1);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn stamp_page() {
+    use pdf::content::{FormXObject, Op};
+
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let page = run!(file.get_page(0));
+    let dict = FormDict { bbox: Rectangle { left: 0., bottom: 0., right: 10., top: 10. }, ..Default::default() };
+    let form = FormXObject { stream: Stream::new(dict, b"0 0 m 10 10 l S".to_vec()) };
+    let form = run!(file.create(XObject::Form(form)));
+
+    let over = run!(page.stamp_overlay(&form, StampPlacement::Center, &mut file));
+    let under = run!(over.stamp_underlay(&form, StampPlacement::Fit { margin: 10. }, &mut file));
+    let ops = run!(under.contents.as_ref().unwrap().operations(&file.resolver()));
+    let names: Vec<_> = ops.iter().enumerate().filter_map(|(i, op)| match op {
+        Op::XObject { name } => Some((i, name.clone())),
+        _ => None,
+    }).collect();
+    // both stamps use the same resource, the first and the last thing drawn
+    assert_eq!(names.len(), 2);
+    assert_eq!((names[0].0, names[1].0), (2, ops.len() - 2));
+    assert_eq!(names[0].1, names[1].1);
+    assert_eq!(run!(under.resources()).xobjects[&names[0].1], form.get_ref());
+
+    // the content is split in several streams now
+    run!(PageRc::update(under, &page, &mut file));
+    let path = temp_path("stamp_page");
+    run!(file.save_to(&path));
+    let copy = run!(FileOptions::cached().open(&path));
+    let page = run!(copy.get_page(0));
+    assert_eq!(run!(page.contents.as_ref().unwrap().operations(&copy.resolver())).len(), ops.len());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn place_images() {
+    use pdf::content::Op;
+    use pdf::enc::{encode, LZWFlateParams, StreamFilter};
+
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let page = run!(file.get_page(0));
+
+    // a red and a half transparent blue pixel; the checksums are not checked
+    let filter = StreamFilter::FlateDecode(LZWFlateParams::png(4, 8, 2));
+    let idat = run!(encode(&[255, 0, 0, 255, 0, 0, 255, 128], &filter));
+    let chunk = |kind: &[u8], data: &[u8]| [&(data.len() as u32).to_be_bytes()[..], kind, data, &[0; 4]].concat();
+    let png = [
+        &b"\x89PNG\r\n\x1a\n"[..],
+        &chunk(b"IHDR", &[0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+        &chunk(b"IDAT", &idat),
+        &chunk(b"IEND", &[]),
+    ].concat();
+    let png = run!(ImageXObject::from_png(&png, &mut file));
+    assert_eq!(run!(png.rgba_data(&file.resolver())), [255, 0, 0, 255, 0, 0, 255, 255]);
+    let smask = run!(file.get(png.smask.unwrap()));
+    assert_eq!(&*run!(Stream::data(&smask, &file.resolver())), &[255, 128]);
+
+    // only the headers are read
+    let jpeg = b"\xff\xd8\xff\xee\x00\x0eAdobe\x00\x64\x00\x00\x00\x00\x02\xff\xc0\x00\x14\x08\x00\x02\x00\x03\x04";
+    let jpeg = run!(ImageXObject::from_jpeg(&[&jpeg[..], &[0; 12]].concat()));
+    assert_eq!((jpeg.width, jpeg.height), (3, 2));
+    assert!(matches!(jpeg.color_space, Some(ColorSpace::DeviceCMYK)));
+    assert_eq!(jpeg.decode.as_deref(), Some(&[1., 0., 1., 0., 1., 0., 1., 0.][..]));
+    assert!(ImageXObject::from_jpeg(b"not a jpeg").is_err());
+
+    let png = run!(file.create(XObject::Image(png)));
+    let jpeg = run!(file.create(XObject::Image(jpeg)));
+    let rect = Rectangle { left: 100., bottom: 200., right: 300., top: 250. };
+    let new = run!(page.place_image(&png, rect, &mut file));
+    let new = run!(new.place_image(&jpeg, rect, &mut file));
+    let ops = run!(new.contents.as_ref().unwrap().operations(&file.resolver()));
+    let names: Vec<_> = ops.iter().filter_map(|op| match op {
+        Op::XObject { name } => Some(name.as_str().to_owned()),
+        _ => None,
+    }).collect();
+    assert_eq!(names.len(), 2);
+    assert_ne!(names[0], names[1]);
+    assert!(ops.iter().any(|op| matches!(op, Op::Transform { matrix } if (matrix.a, matrix.d, matrix.e, matrix.f) == (200., 50., 100., 200.))));
+}
+
+#[test]
+fn image_masks() {
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let gray = |width| ImageDict {
+        width,
+        height: 1,
+        color_space: Some(ColorSpace::DeviceGray),
+        bits_per_component: Some(8),
+        ..Default::default()
+    };
+    let image = |dict, data: &[u8]| ImageXObject { inner: Stream::new(dict, data.to_vec()) };
+
+    // soft masks with a matte color, scaled to the image
+    let smask = run!(file.create(Stream::new(ImageDict { matte: Some(vec![1.]), ..gray(2) }, vec![255, 128])));
+    let masked = image(ImageDict { smask: Some(smask.get_ref()), ..gray(4) }, &[0, 0, 192, 192]);
+    let rgba = run!(masked.masked_rgba_data(&file.resolver()));
+    assert_eq!(rgba, [0, 0, 0, 255, 0, 0, 0, 255, 129, 129, 129, 128, 129, 129, 129, 128]);
+
+    // color key masks compare the samples
+    let masked = image(ImageDict { mask: Some(Mask::ColorKey(vec![15, 25])), ..gray(3) }, &[10, 20, 30]);
+    let alpha: Vec<_> = run!(masked.masked_rgba_data(&file.resolver())).chunks(4).map(|px| px[3]).collect();
+    assert_eq!(alpha, [255, 0, 255]);
+    let p = run!(masked.inner.info.to_primitive(&mut file));
+    let dict = run!(ImageDict::from_primitive(p, &file.resolver()));
+    assert!(matches!(dict.mask, Some(Mask::ColorKey(ref key)) if key == &[15, 25]));
+
+    // stencil masks paint where the sample is 0
+    let stencil = ImageDict { width: 2, height: 1, image_mask: true, bits_per_component: Some(1), ..Default::default() };
+    let stencil = run!(file.create(Stream::new(stencil, vec![0b0100_0000])));
+    let masked = image(ImageDict { mask: Some(Mask::Stencil(stencil.get_ref())), ..gray(2) }, &[1, 2]);
+    let alpha: Vec<_> = run!(masked.masked_rgba_data(&file.resolver())).chunks(4).map(|px| px[3]).collect();
+    assert_eq!(alpha, [255, 0]);
+}
+
+#[test]
+fn extract_images() {
+    let file = run!(FileOptions::cached().open(file_path("jpeg.pdf")));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let images = run!(page.images(&resolver));
+    assert!(!images.is_empty());
+    for placed in &images {
+        let image = &placed.image;
+        let bounds = placed.bounds();
+        assert!(bounds.right > bounds.left && bounds.top > bounds.bottom);
+        assert_eq!(run!(image.native_format(&resolver)), ImageFormat::Jpeg);
+        let jpeg = run!(image.export(ImageFormat::Jpeg, &resolver));
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+        assert!(image.export(ImageFormat::Jp2k, &resolver).is_err());
+        let png = run!(image.export(ImageFormat::Png, &resolver));
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&png[16..24], &[&image.width.to_be_bytes()[..], &image.height.to_be_bytes()].concat()[..]);
+    }
+}
+
+#[test]
+fn embedded_fonts() {
+    use pdf::font::FontFormat;
+
+    let file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let resolver = file.resolver();
+    let mut formats = vec![];
+    for page in file.pages() {
+        let page = run!(page);
+        for font in run!(page.resources()).fonts.values() {
+            let font = run!(font.load(&resolver));
+            if let Some(embedded) = font.embedded_data(&resolver) {
+                let embedded = run!(embedded);
+                assert!(!embedded.data.is_empty());
+                if embedded.format == FontFormat::Type1 {
+                    assert!(embedded.to_pfb().is_some());
+                }
+                formats.push(embedded.format);
+            }
+        }
+    }
+    assert!(!formats.is_empty());
+}
+
+#[test]
+fn n_up() {
+    use pdf::build::{CatalogBuilder, Importer, NUp, PageBuilder, PdfBuilder};
+    use pdf::content::Op;
+
+    let file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let pages: Vec<_> = file.pages().take(3).map(|p| run!(p)).collect();
+    let sheet = Rectangle { left: 0., bottom: 0., right: 842., top: 595. };
+    let forms = |page: &PageBuilder| page.ops.iter().filter(|op| matches!(op, Op::XObject { .. })).count();
+
+    let mut builder = PdfBuilder::new(FileOptions::cached());
+    let mut importer = Importer::new(file.resolver(), &mut builder.storage);
+    let two_up = run!(PageBuilder::n_up(&pages, &NUp::two_up(sheet), &mut importer));
+    assert_eq!(two_up.iter().map(forms).collect::<Vec<_>>(), [2, 1]);
+
+    // padded to four pages: the first sheet has the (blank) last page and the first page
+    let booklet = run!(PageBuilder::n_up(&pages, &NUp::booklet(sheet), &mut importer));
+    assert_eq!(booklet.iter().map(forms).collect::<Vec<_>>(), [1, 2]);
+    assert!(booklet[0].resources.xobjects.contains_key("P1"));
+
+    let data = run!(builder.build(CatalogBuilder::from_pages(two_up)));
+    let copy = run!(FileOptions::uncached().load(data));
+    let page = run!(copy.get_page(0));
+    assert_eq!(run!(page.media_box()).right, 842.);
+    let svg = run!(pdf::svg::page_to_svg(&page, &copy.resolver(), pdf::svg::SvgText::Fonts));
+    assert!(svg.contains("<text "));
+}
+
+#[test]
+fn clone_annotations() {
+    use pdf::build::{CatalogBuilder, Importer, PageBuilder, PdfBuilder};
+    use pdf::primitive::Primitive;
+
+    // the annotations point back at the page, and the note and its popup at each other
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << >> /Annots [4 0 R 5 0 R] >> endobj
+4 0 obj << /Type /Annot /Subtype /Text /Rect [10 10 20 20] /P 3 0 R /Popup 5 0 R /Contents (note) >> endobj
+5 0 obj << /Type /Annot /Subtype /Popup /Rect [20 20 80 80] /P 3 0 R /Parent 4 0 R >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let page = run!(file.get_page(0));
+
+    let mut builder = PdfBuilder::new(FileOptions::cached());
+    let mut importer = Importer::new(file.resolver(), &mut builder.storage);
+    let mut new_page = run!(PageBuilder::clone_page(&page, &mut importer));
+    run!(new_page.clone_annotations(&page, &mut importer));
+    let data = run!(builder.build(CatalogBuilder::from_pages(vec![new_page])));
+
+    let copy = run!(FileOptions::cached().load(data));
+    let resolver = copy.resolver();
+    let page = run!(copy.get_page(0));
+    let annots = run!(page.annotations.load(&resolver));
+    assert_eq!(annots.len(), 2);
+    for annot in annots.iter() {
+        assert_eq!(annot.page.as_ref().unwrap().get_plain_ref(), page.get_plain_ref());
+    }
+    let note = annots[0].as_ref().unwrap().get_inner();
+    assert_eq!(annots[1].other.get("Parent"), Some(&Primitive::Reference(note)));
+    assert_eq!(annots[0].popup.map(|r| r.get_inner()), Some(annots[1].as_ref().unwrap().get_inner()));
+}
+
+#[test]
+fn failed_clone() {
+    use pdf::build::{Importer, PdfBuilder};
+
+    // 4 refers to an object that doesn't exist, and 6 to 4
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >> endobj
+4 0 obj << /A 5 0 R >> endobj
+6 0 obj << /B 4 0 R >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let mut builder = PdfBuilder::new(FileOptions::cached());
+    let mut importer = Importer::new(file.resolver(), &mut builder.storage);
+    let r = |id| PlainRef { id, gen: 0 };
+    assert!(importer.clone_plainref(r(4)).is_err());
+    // the reference reserved for the clone of 4 is not used, as it never got any content
+    assert!(importer.clone_plainref(r(6)).is_err());
+    assert!(importer.clone_plainref(r(3)).is_ok());
+}
+
+#[test]
+fn redact_page() {
+    use pdf::content::Op;
+    use pdf::interpret::{ContentInterpreter, Event};
+    use pdf::redact::{RedactArea, Redaction};
+
+    // the origins of all glyphs shown
+    fn origins(page: &Page, resolve: &impl Resolve) -> Vec<(f32, f32)> {
+        let ops = run!(page.contents.as_ref().unwrap().operations(resolve));
+        let mut origins = vec![];
+        run!(run!(ContentInterpreter::for_page(page, resolve)).run(&ops, &mut |event, _| {
+            if let Event::TextShown { glyphs, .. } = event {
+                origins.extend(glyphs.iter().map(|g| (g.matrix.e, g.matrix.f)));
+            }
+            Ok(())
+        }));
+        origins
+    }
+
+    let mut file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let page = run!(file.get_page(0));
+    let rect = Rectangle { left: 100., bottom: 400., right: 300., top: 600. };
+    let inside = |&(x, y): &(f32, f32)| x > rect.left && x < rect.right && y > rect.bottom && y < rect.top;
+    let before = origins(&page, &file.resolver());
+    assert!(before.iter().any(inside));
+
+    let redaction = run!(Redaction::new(&page, &[RedactArea::new(rect)], &file.resolver()));
+    let redacted = run!(redaction.apply(&mut file));
+    let after = origins(&redacted, &file.resolver());
+    assert!(!after.iter().any(inside));
+    assert!(after.len() < before.len());
+    // whatever is far away stays
+    let far = |&(_, y): &(f32, f32)| !(350. ..650.).contains(&y);
+    assert_eq!(before.iter().filter(|o| far(o)).count(), after.iter().filter(|o| far(o)).count());
+
+    let ops = run!(redacted.contents.as_ref().unwrap().operations(&file.resolver()));
+    assert!(matches!(ops[ops.len() - 3], Op::Rect { .. }));
+
+    // the secret is gone from the file only when it is rewritten
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 300 300] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >> endobj
+4 0 obj << /Length 74 >>
+stream
+BT /F1 12 Tf 20 200 Td (SECRET) Tj ET BT /F1 12 Tf 20 50 Td (PUBLIC) Tj ET
+endstream
+endobj
+5 0 obj << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> endobj
+trailer << /Root 1 0 R >>
+%%EOF";
+    let mut file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(data.to_vec()));
+    let page = run!(file.get_page(0));
+    assert_eq!(origins(&page, &file.resolver()).len(), 12);
+    let rect = Rectangle { left: 0., bottom: 150., right: 300., top: 250. };
+    let redaction = run!(Redaction::new(&page, &[RedactArea::new(rect)], &file.resolver()));
+    let redacted = run!(redaction.apply(&mut file));
+    run!(PageRc::update(redacted, &page, &mut file));
+    let contains = |data: &[u8], s: &[u8]| data.windows(s.len()).any(|w| w == s);
+    let path = temp_path("redact_page");
+    run!(file.save_to(&path));
+    assert!(contains(&std::fs::read(&path).unwrap(), b"SECRET"));
+    let _ = std::fs::remove_file(path);
+    let rewritten = run!(file.rewrite());
+    assert!(!contains(&rewritten, b"SECRET"));
+
+    let file = run!(FileOptions::cached().load(rewritten));
+    let page = run!(file.get_page(0));
+    let after = origins(&page, &file.resolver());
+    assert_eq!(after.len(), 6);
+    assert!(after.iter().all(|&(_, y)| y == 50.));
+}
+
+#[test]
+fn search_page() {
+    let file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let page = run!(file.get_page(0));
+    let matches = run!(page.search("Problem", &file.resolver()));
+    assert!(matches.len() >= 2);
+    assert_eq!(matches[0].text, "Problem");
+
+    // the words are separated by kerning only
+    let matches = run!(page.search("Problem  Set 2", &file.resolver()));
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].quads.len(), 1);
+    let [ul, ur, ll, lr] = matches[0].quads[0];
+    assert!(ul.x < ur.x && ll.x < lr.x);
+    assert!(ll.y < ul.y && lr.y < ur.y);
+    // the title is on the upper half of the page
+    assert!(ll.y > 421.);
+
+    assert!(run!(page.search("no such text", &file.resolver())).is_empty());
+}
+
+#[test]
+fn highlight_search_result() {
+    use pdf::content::Op;
+
+    let mut file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let page = run!(file.get_page(0));
+    let found = run!(page.search("Problem", &file.resolver()));
+    let mut annots: Vec<_> = run!(page.annotations.load(&file.resolver())).iter().cloned().collect();
+    for kind in [TextMarkup::Highlight, TextMarkup::Squiggly] {
+        let annot = run!(Annot::text_markup(kind, &found[0].quads, [1., 1., 0.], &mut file));
+        annots.push(MaybeRef::Indirect(run!(file.create(annot))));
+    }
+    let mut new_page = (*page).clone();
+    new_page.annotations = run!(Lazy::safe(annots, &mut file));
+    let page = run!(PageRc::update(new_page, &page, &mut file));
+
+    let resolver = file.resolver();
+    let annots = run!(page.annotations.load(&resolver));
+    let highlight = &annots[annots.len() - 2];
+    assert_eq!(highlight.subtype.as_str(), "Highlight");
+    assert_eq!(run!(highlight.other["QuadPoints"].as_array()).len(), 8);
+    let quad = found[0].quads[0];
+    let rect = highlight.rect.unwrap();
+    assert!(rect.left <= quad[0].x && rect.top >= quad[0].y);
+
+    let appearance = run!(resolver.get(highlight.appearance_streams.as_ref().unwrap().normal));
+    let ops = match *appearance {
+        AppearanceStreamEntry::Single(ref form) => run!(form.operations(&resolver)),
+        _ => panic!("expected a form"),
+    };
+    assert!(matches!(ops.last(), Some(Op::Fill { .. })));
+    assert_eq!(annots[annots.len() - 1].subtype.as_str(), "Squiggly");
+}
+
+#[test]
+fn rebuild_xref() {
+    for name in ["example.pdf", "xelatex.pdf", "libreoffice.pdf"] {
+        let data = std::fs::read(file_path(name)).unwrap();
+        let expected = run!(FileOptions::cached().load(data.clone()));
+
+        // point startxref somewhere else
+        let pos = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+        let mut damaged = data[..pos].to_vec();
+        damaged.extend_from_slice(b"startxref\n12\n%%EOF\n");
+        assert!(FileOptions::cached().load(damaged.clone()).is_err());
+
+        let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(damaged));
+        assert_eq!(file.num_pages(), expected.num_pages());
+        for i in 0..file.num_pages() {
+            let ops = |file: &pdf::file::CachedFile<Vec<u8>>| {
+                let page = run!(file.get_page(i));
+                let ops = run!(page.contents.as_ref().unwrap().operations(&file.resolver()));
+                run!(pdf::content::serialize_ops(&ops))
+            };
+            assert_eq!(ops(&file), ops(&expected));
+        }
+    }
+}
+
+#[test]
+fn lenient_parsing() {
+    use pdf::object::{Warning, Warnings};
+
+    // no xref table, a missing endobj, a wrong stream length, a name as number and a date without `D:`
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count /1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Contents 4 0 R >>
+4 0 obj << /Length 100 >>
+stream
+0 0 m 10 10 l S
+endstream
+endobj
+5 0 obj << /CreationDate (20200102120000Z) >> endobj
+trailer << /Root 1 0 R /Info 5 0 R >>
+%%EOF
+";
+    assert!(FileOptions::uncached().load(&data[..]).is_err());
+
+    let warnings = Warnings::new();
+    let options = ParseOptions::lenient().collect_warnings(&warnings);
+    let file = run!(FileOptions::uncached().parse_options(options).load(&data[..]));
+    assert_eq!(file.num_pages(), 1);
+    let page = run!(file.get_page(0));
+    let ops = run!(page.contents.as_ref().unwrap().operations(&file.resolver()));
+    assert_eq!(ops.len(), 3);
+    let date = file.trailer.info_dict.as_ref().unwrap().creation_date.clone().unwrap();
+    assert_eq!((date.year, date.month, date.day), (2020, 1, 2));
+
+    let warnings = warnings.take();
+    assert!(warnings.iter().any(|w| matches!(w, Warning::MissingEndobj { obj } if obj.id == 3)));
+    assert!(warnings.contains(&Warning::StreamLength { pos: 229, length: 100, actual: 15 }));
+    assert!(warnings.iter().any(|w| matches!(w, Warning::TypeMismatch { expected: "Number", .. })));
+    assert!(warnings.iter().any(|w| matches!(w, Warning::MalformedDate { .. })));
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn limits() {
+    use pdf::error::ErrorKind;
+
+    let options = ParseOptions::strict().limits(Limits { max_stream_size: 16, ..Limits::untrusted() });
+    // the xref stream is too large already
+    let e = FileOptions::cached().parse_options(options.clone()).open(file_path("xelatex.pdf")).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::Limit);
+    let file = run!(FileOptions::cached().parse_options(options).open(file_path("example.pdf")));
+    assert!(file.get_page(0).is_ok());
+
+    let options = ParseOptions::strict().limits(Limits { max_objects: 2, ..Limits::unlimited() });
+    let e = FileOptions::cached().parse_options(options).open(file_path("example.pdf")).err().unwrap();
+    assert!(matches!(e.root_cause(), pdf::error::PdfError::LimitExceeded { max: 2, .. }));
+
+    // the size of fax and JBIG2 images is known before they are decoded
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >> endobj
+4 0 obj << /Type /XObject /Subtype /Image /Width 20000 /Height 20000 /ImageMask true
+/Filter /CCITTFaxDecode /DecodeParms << /K -1 /Columns 20000 /Rows 20000 >> /Length 4 >>
+stream
+abcd
+endstream
+endobj
+5 0 obj << /Type /XObject /Subtype /Image /Width 20000 /Height 20000 /ImageMask true /Filter /JBIG2Decode /Length 4 >>
+stream
+abcd
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let options = ParseOptions::tolerant().limits(Limits { max_stream_size: 1 << 20, ..Limits::untrusted() });
+    let file = run!(FileOptions::cached().parse_options(options).load(&data[..]));
+    let resolver = file.resolver();
+    for id in [4, 5] {
+        let image = match *run!(resolver.get(Ref::<XObject>::new(PlainRef { id, gen: 0 }))) {
+            XObject::Image(ref image) => image.clone(),
+            ref xobject => panic!("{:?}", xobject),
+        };
+        let e = image.image_data(&resolver).err().unwrap();
+        assert!(matches!(e.root_cause(), pdf::error::PdfError::LimitExceeded { what: "decoded image size", .. }), "{}", e);
+    }
+}
+
+#[test]
+fn total_decoded_counts_streams_once() {
+    use pdf::object::Stream;
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [] /Count 0 >> endobj
+3 0 obj << /Filter /ASCIIHexDecode /Length 11 >>
+stream
+48656c6c6f>
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    // without a stream cache every read decodes again, and one read is within the limit
+    let options = ParseOptions::tolerant().limits(Limits { max_total_decoded: 8, ..Limits::untrusted() });
+    let file = run!(FileOptions::uncached().parse_options(options).load(&data[..]));
+    let resolver = file.resolver();
+    for _ in 0..10 {
+        let stream = run!(resolver.get(Ref::<Stream<()>>::new(PlainRef { id: 3, gen: 0 })));
+        assert_eq!(&*run!((*stream).data(&resolver)), b"Hello");
+    }
+}
+
+#[test]
+fn media() {
+    use pdf::object::MediaData;
+
+    let data = b"%PDF-1.5
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << >> /Annots [4 0 R 5 0 R 6 0 R] >> endobj
+4 0 obj << /Type /Annot /Subtype /RichMedia /Rect [0 0 100 100] /RichMediaContent << /Assets << /Names [(clip.mp4) 7 0 R] >>
+  /Configurations [<< /Subtype /Video /Instances [<< /Asset 7 0 R >>] >>] >> >> endobj
+5 0 obj << /Type /Annot /Subtype /Screen /Rect [0 100 100 200] /A << /S /Rendition /OP 0 /R << /S /SR /R [
+  << /S /MR /N (intro) /C << /S /MCS /D << /S /MCD /CT (video/mp4) /D (intro.mp4) >> >> >> ] >> >> >> endobj
+6 0 obj << /Type /Annot /Subtype /Sound /Rect [100 0 120 20] /Sound 9 0 R >> endobj
+7 0 obj << /Type /Filespec /F (clip.mp4) /UF (clip.mp4) /EF << /F 8 0 R >> >> endobj
+8 0 obj << /Type /EmbeddedFile /Length 4 >>
+stream
+mp4!
+endstream
+endobj
+9 0 obj << /Type /Sound /R 8000 /B 16 /Length 2 >>
+stream
+ab
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let media = run!(page.media(&resolver));
+    assert_eq!(media.len(), 3);
+    assert_eq!(media[0].name.as_deref(), Some("clip.mp4"));
+    match media[0].data {
+        MediaData::File(ref spec) => {
+            let stream = run!(spec.embedded_file(&resolver)).unwrap();
+            assert_eq!(&*run!((*stream).data(&resolver)), b"mp4!");
+        }
+        ref d => panic!("{:?}", d),
+    }
+    assert_eq!((media[1].name.as_deref(), media[1].content_type.as_deref()), (Some("intro"), Some("video/mp4")));
+    assert!(matches!(media[1].data, MediaData::File(ref spec) if spec.file_name.as_ref().unwrap().as_bytes() == b"intro.mp4"));
+    match media[2].data {
+        MediaData::Sound(ref sound) => assert_eq!((sound.rate, sound.bits_per_sample, sound.channels), (8000., 16, 1)),
+        ref d => panic!("{:?}", d),
+    }
+}
+
+#[test]
+fn portfolio() {
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let mut item = CollectionItem::default();
+    item.set("Owner", CollectionValue::Text("Jane".into()));
+    let files = vec![
+        PortfolioFile { name: "a.txt", data: b"first", description: Some("the first file"), item: Some(item), ..Default::default() },
+        PortfolioFile { name: "b.txt", data: b"second", ..Default::default() },
+    ];
+    let field = |subtype: &str, name: &str, order| CollectionField {
+        subtype: subtype.into(), name: name.into(), order: Some(order), visible: true, editable: false,
+    };
+    let collection = Collection {
+        schema: Some(CollectionSchema { fields: vec![("Owner".into(), field("S", "Owner", 1)), ("F".into(), field("F", "Name", 0))] }),
+        initial: Some("a.txt".into()),
+        ..Collection::default()
+    };
+    run!(file.make_portfolio(files, collection));
+
+    let path = temp_path("portfolio");
+    run!(file.save_to(&path));
+    let file = run!(FileOptions::cached().open(&path));
+    let resolver = file.resolver();
+    let catalog = file.get_root();
+    let collection = catalog.collection.as_ref().unwrap();
+    assert_eq!(collection.schema.as_ref().unwrap().ordered()[0].0.as_str(), "F");
+    let entries = run!(catalog.portfolio_entries(&resolver));
+    let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "b.txt"]);
+    assert_eq!(run!(collection.value(&entries[0].file, "Owner", &resolver)), Some(CollectionValue::Text("Jane".into())));
+    assert_eq!(run!(collection.value(&entries[0].file, "F", &resolver)), Some(CollectionValue::Text("a.txt".into())));
+    assert_eq!(run!(collection.value(&entries[1].file, "Owner", &resolver)), None);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn reference_cycles() {
+    use pdf::error::PdfError;
+
+    // the page tree nodes are each other's kids, and so are the name tree nodes
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /Names << /JavaScript 4 0 R >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Pages /Parent 2 0 R /Kids [2 0 R] /Count 1 >> endobj
+4 0 obj << /Kids [5 0 R] >> endobj
+5 0 obj << /Kids [4 0 R] >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let e = file.get_page(0).err().unwrap();
+    assert!(matches!(e.root_cause(), PdfError::ReferenceCycle { obj_nr: 3, .. }), "{}", e);
+
+    let names = file.get_root().names.as_ref().unwrap();
+    let e = names.javascript.as_ref().unwrap().walk(&file.resolver(), &mut |_, _| {}).unwrap_err();
+    assert!(matches!(e.root_cause(), PdfError::ReferenceCycle { obj_nr: 5, .. }), "{}", e);
+
+    // the /Parent links of the first page loop, those of the second page are just long,
+    // and the outline items are each other's /Next
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /Outlines 20 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >> endobj
+3 0 obj << /Type /Page /Parent 5 0 R /MediaBox [0 0 10 10] >> endobj
+4 0 obj << /Type /Page /Parent 10 0 R /MediaBox [0 0 10 10] >> endobj
+5 0 obj << /Type /Pages /Parent 6 0 R /Kids [3 0 R] /Count 1 >> endobj
+6 0 obj << /Type /Pages /Parent 5 0 R /Kids [5 0 R] /Count 1 >> endobj
+10 0 obj << /Type /Pages /Parent 11 0 R /Kids [4 0 R] /Count 1 >> endobj
+11 0 obj << /Type /Pages /Parent 12 0 R /Kids [10 0 R] /Count 1 >> endobj
+12 0 obj << /Type /Pages /Parent 13 0 R /Kids [11 0 R] /Count 1 >> endobj
+13 0 obj << /Type /Pages /Parent 14 0 R /Kids [12 0 R] /Count 1 >> endobj
+14 0 obj << /Type /Pages /Kids [13 0 R] /Count 1 >> endobj
+20 0 obj << /Type /Outlines /First 21 0 R /Last 22 0 R /Count 2 >> endobj
+21 0 obj << /Title (a) /Parent 20 0 R /Next 22 0 R >> endobj
+22 0 obj << /Title (b) /Parent 20 0 R /Prev 21 0 R /Next 21 0 R >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    // errors in optional entries are not ignored, so the broken parents are reported
+    let options = ParseOptions { allow_error_in_option: false, max_depth: 4, ..ParseOptions::tolerant() };
+    let file = run!(FileOptions::cached().parse_options(options).load(&data[..]));
+    let e = file.get_page(0).err().unwrap();
+    assert!(matches!(e.root_cause(), PdfError::ReferenceCycle { obj_nr: 5, .. }), "{}", e);
+    let e = file.resolver().get(Ref::<PagesNode>::new(PlainRef { id: 4, gen: 0 })).err().unwrap();
+    assert!(matches!(e.root_cause(), PdfError::MaxDepth), "{}", e);
+
+    let outlines = file.get_root().outlines.as_ref().unwrap();
+    let e = outlines.children(&file.resolver()).err().unwrap();
+    assert!(matches!(e.root_cause(), PdfError::ReferenceCycle { obj_nr: 21, ref chain } if chain == &[21, 22]), "{}", e);
+}
+
+#[test]
+fn diagnostics() {
+    use pdf::object::{PlainRef, Resolve};
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [] /Count 0 >> endobj
+3 0 obj << /Title (broken) /Author >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let e = file.resolver().resolve(PlainRef { id: 3, gen: 0 }).unwrap_err();
+    let diagnostic = e.diagnostic();
+    println!("{}", diagnostic);
+    assert_eq!(diagnostic.obj, Some(PlainRef { id: 3, gen: 0 }));
+    // right after the `>>` where the value of /Author should be
+    assert_eq!(diagnostic.pos, Some(147));
+    assert!(diagnostic.message.starts_with("Expecting an object, encountered >>"));
+}
+
+#[test]
+fn repair() {
+    use pdf::repair::repair;
+
+    // no xref table, a wrong /Count, a page listed twice, a font among the kids,
+    // a wrong stream length, an object defined twice and an orphan
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R 3 0 R 6 0 R 8 0 R] /Count 7 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Contents 4 0 R >> endobj
+4 0 obj << /Length 100 >>
+stream
+0 0 m 10 10 l S
+endstream
+endobj
+6 0 obj << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> endobj
+7 0 obj (nobody needs me) endobj
+8 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >> endobj
+8 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 300 300] >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, report) = run!(repair(&data[..]));
+    assert_eq!(report.stream_lengths, 1);
+    assert_eq!(report.page_counts, 1);
+    assert_eq!(report.pruned_kids, 2);
+    assert_eq!(report.orphans, [6, 7]);
+    assert!(report.unreadable.is_empty());
+
+    let file = run!(FileOptions::uncached().load(repaired));
+    assert_eq!(file.num_pages(), 2);
+    let page = run!(file.get_page(0));
+    let ops = run!(page.contents.as_ref().unwrap().operations(&file.resolver()));
+    assert_eq!(ops.len(), 3);
+    assert_eq!(run!(file.get_page(1)).media_box().unwrap().right, 300.);
+}
+
+#[test]
+fn dedup() {
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let mut storage = FileOptions::uncached().storage();
+    let font = |storage: &mut Storage<_, _, _, _>| {
+        let mut info = Dictionary::new();
+        info.insert("Length1", 12);
+        let file = run!(storage.create(Stream::new(info, &b"font program"[..])));
+        let mut dict = Dictionary::new();
+        dict.insert("Type", Primitive::name("Font"));
+        dict.insert("FontFile2", file.get_ref().get_inner());
+        run!(storage.create(dict)).get_ref().get_inner()
+    };
+    let a = font(&mut storage);
+    let b = font(&mut storage);
+    let mut page = Dictionary::new();
+    page.insert("Type", Primitive::name("Page"));
+    let page_a = run!(storage.create(page.clone())).get_ref().get_inner();
+    let page_b = run!(storage.create(page)).get_ref().get_inner();
+    let both = run!(storage.create(Primitive::Array(vec![a.into(), b.into(), page_a.into(), page_b.into()])));
+
+    let merged = storage.dedup();
+    // the font and its font program
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[&b.id], a);
+    let both = run!(storage.resolver().resolve(both.get_ref().get_inner()));
+    assert_eq!(both, Primitive::Array(vec![a.into(), a.into(), page_a.into(), page_b.into()]));
+}
+
+#[test]
+fn dedup_merges_only_resources() {
+    use pdf::primitive::{Dictionary, PdfString, Primitive};
+
+    let mut storage = FileOptions::uncached().storage();
+    // widgets without /Type, which are still separate fields
+    let mut widget = Dictionary::new();
+    widget.insert("Subtype", Primitive::name("Widget"));
+    widget.insert("FT", Primitive::name("Tx"));
+    widget.insert("T", PdfString::from("name"));
+    let widget_a = run!(storage.create(widget.clone())).get_ref().get_inner();
+    let widget_b = run!(storage.create(widget)).get_ref().get_inner();
+    // color spaces are arrays
+    let gray = || Primitive::Array(vec![Primitive::name("CalGray"), Primitive::Dictionary(Dictionary::new())]);
+    let gray_a = run!(storage.create(gray())).get_ref().get_inner();
+    let gray_b = run!(storage.create(gray())).get_ref().get_inner();
+
+    let merged = storage.dedup();
+    assert!(!merged.contains_key(&widget_b.id));
+    assert_eq!(merged[&gray_b.id], gray_a);
+    assert_eq!(merged.len(), 1);
+    let resolver = storage.resolver();
+    assert!(run!(resolver.resolve(widget_a)).as_dictionary().is_ok());
+    assert!(run!(resolver.resolve(widget_b)).as_dictionary().is_ok());
+}
+
+#[test]
+fn info_policy() {
+    use pdf::file::{InfoPolicy, InfoValue};
+    use pdf::primitive::Primitive;
+
+    let xml = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description rdf:about=""></rdf:Description></rdf:RDF></x:xmpmeta>"#;
+    let data = format!("%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /Metadata 4 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >> endobj
+4 0 obj << /Type /Metadata /Subtype /XML /Source (scanner) /Length {} >>
+stream
+{}
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+", xml.len(), xml);
+    let mut file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(data.into_bytes()));
+    assert!(file.trailer.info_dict.is_none());
+    file.set_info_policy(InfoPolicy { producer: InfoValue::Set("pdf-rs".into()), ..Default::default() });
+    run!(file.apply_info_policy());
+
+    // the trailer had no /Info, which is created for the new producer
+    let info = file.trailer.info_dict.as_ref().unwrap();
+    assert_eq!(info.producer.as_ref().unwrap().to_string_lossy(), "pdf-rs");
+    assert!(info.mod_date.is_some());
+
+    let metadata = file.get_root().metadata.unwrap().get_inner();
+    let stream = match run!(file.resolver().resolve(metadata)) {
+        Primitive::Stream(stream) => stream,
+        p => panic!("{:?}", p),
+    };
+    assert_eq!(stream.info.get("Source"), Some(&Primitive::String("scanner".into())));
+    assert_eq!(stream.info.get("Subtype"), Some(&Primitive::name("XML")));
+    let resolver = file.resolver();
+    let stream = run!(resolver.get(file.get_root().metadata.unwrap()));
+    let xmp = run!((*stream).data(&resolver));
+    assert!(std::str::from_utf8(&xmp).unwrap().contains("<pdf:Producer>pdf-rs</pdf:Producer>"));
+}
+
+#[test]
+fn round_trip() {
+    let data = std::fs::read(file_path("example.pdf")).unwrap();
+    let mut file = run!(FileOptions::uncached().load(data.clone()));
+    file.set_round_trip(true);
+    file.set_info_policy(pdf::file::InfoPolicy { keep_mod_date: true, ..Default::default() });
+
+    // written back unchanged, and with a new rotation
+    let page_ref = run!(file.get_page(0)).get_ref().get_inner();
+    let page = run!(file.resolver().resolve(page_ref));
+    run!(file.update(page_ref, page.clone()));
+    let mut rotated = run!(page.into_dictionary());
+    rotated.insert("Rotate", 90);
+    let path = temp_path("round_trip");
+    run!(file.save_to(&path));
+    let unchanged = std::fs::read(&path).unwrap();
+    assert!(unchanged.starts_with(&data));
+    let update = String::from_utf8_lossy(&unchanged[data.len()..]).into_owned();
+    let header = format!("{} {} obj", page_ref.id, page_ref.gen);
+    assert!(!update.lines().any(|l| l == header));
+
+    run!(file.update(page_ref, rotated));
+    run!(file.save_to(&path));
+    let saved = std::fs::read(&path).unwrap();
+    let update = String::from_utf8_lossy(&saved[unchanged.len()..]).into_owned();
+    assert!(update.lines().any(|l| l == header));
+    let _ = std::fs::remove_file(path);
+
+    let file = run!(FileOptions::uncached().load(saved));
+    assert_eq!(run!(file.get_page(0)).rotate, 90);
+}
+
+#[test]
+fn visit() {
+    use pdf::primitive::Primitive;
+    use pdf::visit::{ObjectVisitor, Path};
+
+    #[derive(Default)]
+    struct Paths(Vec<String>);
+    impl ObjectVisitor for Paths {
+        fn visit(&mut self, path: Path, primitive: &Primitive) -> bool {
+            self.0.push(format!("{} {}", path, primitive.get_debug_name()));
+            // don't look into fonts
+            !matches!(primitive, Primitive::Dictionary(d) if d.get("Type") == Some(&Primitive::name("Font")))
+        }
+    }
+
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let mut paths = Paths::default();
+    file.visit(&mut paths);
+    for path in ["trailer Dictionary", "trailer/Root Reference", "1 0 R/Pages Reference", "2 0 R/Kids[0] Reference",
+        "6 0 R/Resources/Font/F0 Reference", "6 0 R/MediaBox[3] Integer", "3 0 R Stream", "5 0 R Dictionary"] {
+        assert!(paths.0.iter().any(|p| p == path), "{} not in {:?}", path, paths.0);
+    }
+    assert!(!paths.0.iter().any(|p| p.starts_with("5 0 R/")));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize() {
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let page = run!(file.get_page(0));
+    let json = serde_json::to_value(&*page).unwrap();
+    assert_eq!(json["parent"], "2 0 R");
+    assert_eq!(json["media_box"], serde_json::json!({"left": 0.0, "bottom": 0.0, "right": 180.0, "top": 240.0}));
+    let fonts = &json["resources"]["fonts"];
+    assert!(fonts["F0"].is_string(), "{}", fonts);
+
+    let trailer = run!(file.trailer.to_primitive(&mut NoUpdate));
+    let json = serde_json::to_value(&trailer).unwrap();
+    assert_eq!(json["Root"], "1 0 R");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json() {
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let json = run!(file.to_json());
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["version"], 2);
+    let objects = &value["qpdf"][1];
+    assert_eq!(objects["trailer"]["value"]["/Root"], "1 0 R");
+    assert_eq!(objects["obj:2 0 R"]["value"]["/Type"], "/Pages");
+    assert!(objects["obj:3 0 R"]["stream"]["data"].is_string());
+
+    let copy = run!(FileOptions::cached().load_json(&json));
+    assert_eq!(copy.num_pages(), file.num_pages());
+    let resolver = file.resolver();
+    let copy_resolver = copy.resolver();
+    let page = run!(file.get_page(0));
+    let copy_page = run!(copy.get_page(0));
+    assert_eq!(
+        run!(copy_page.contents.as_ref().unwrap().parts[0].data(&copy_resolver)),
+        run!(page.contents.as_ref().unwrap().parts[0].data(&resolver))
+    );
+    let copy_value: serde_json::Value = serde_json::from_str(&run!(copy.to_json())).unwrap();
+    assert_eq!(copy_value["qpdf"][1].as_object().unwrap().len(), objects.as_object().unwrap().len());
+}
+
+#[test]
+fn fdf() {
+    use pdf::fdf::FormData;
+    use pdf::repair::repair;
+
+    // a text field `person.name` and a check box with one widget
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 6 0 R] >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Annots [5 0 R 7 0 R] >> endobj
+4 0 obj << /T (person) /Kids [5 0 R] >> endobj
+5 0 obj << /T (name) /Parent 4 0 R /FT /Tx /V (Jane) /Subtype /Widget /Rect [0 0 50 10] >> endobj
+6 0 obj << /T (agree) /FT /Btn /V /Off /Kids [7 0 R] >> endobj
+7 0 obj << /Parent 6 0 R /Subtype /Widget /Rect [0 20 10 30] /AS /Off /AP << /N << /Yes 8 0 R /Off 8 0 R >> >> >> endobj
+8 0 obj << /Type /XObject /Subtype /Form /BBox [0 0 10 10] /Length 0 >>
+stream
+
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, _) = run!(repair(&data[..]));
+    let mut file = run!(FileOptions::cached().load(repaired));
+    let forms = file.get_root().forms.as_ref().unwrap();
+    let exported = run!(forms.export_fdf(&file.resolver()));
+    assert_eq!(exported.fields.len(), 2);
+    assert_eq!(exported.fields[0], ("person.name".to_string(), FieldValue::Text("Jane".into())));
+
+    let xfdf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/"><fields>
+<field name="person"><field name="name"><value>John</value></field></field>
+<field name="agree"><value>Yes</value></field>
+</fields></xfdf>"#;
+    let filled = run!(FormData::parse_xfdf(xfdf));
+    run!(file.import_fdf(&filled));
+    let path = temp_path("fdf");
+    run!(file.save_to(&path));
+
+    let file = run!(FileOptions::cached().open(&path));
+    let _ = std::fs::remove_file(path);
+    let forms = file.get_root().forms.as_ref().unwrap();
+    assert!(forms.need_appearences);
+    let exported = run!(forms.export_fdf(&file.resolver()));
+    assert_eq!(exported.fields, [
+        ("person.name".to_string(), FieldValue::Text("John".into())),
+        ("agree".to_string(), FieldValue::State("Yes".into())),
+    ]);
+    let widget = run!(file.resolver().get(forms.fields[1].kids[0]));
+    assert_eq!(widget.other.get("AS").and_then(|s| s.as_name().ok()), Some("Yes"));
+
+    let fdf = run!(exported.to_fdf());
+    assert_eq!(run!(FormData::parse_fdf(&fdf)), exported);
+}
+
+#[test]
+fn xfdf_annotations() {
+    use pdf::fdf::FormData;
+
+    let xfdf = r##"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/"><annots>
+<text page="0" name="reply" inreplyto="note" rect="10,20,30,40"><contents>indeed</contents></text>
+<highlight page="0" name="note" color="#FFFF00" coords="10,30,50,30,10,20,50,20" flags="print" title="Jane">
+<contents>look</contents><popup rect="60,10,160,60" open="yes"/>
+</highlight>
+<square page="0" rect="100,100,200,150" width="2" interior-color="#0000FF"/>
+<ink page="0" rect="0,0,20,20"><inklist><gesture>1,1;10,10;19,1</gesture></inklist></ink>
+</annots></xfdf>"##;
+    let data = run!(FormData::parse_xfdf(xfdf));
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let before = run!(run!(file.get_page(0)).annotations(&file)).len();
+    let created = run!(file.import_annotations(&data.annots));
+    assert_eq!(created.len(), 4);
+    assert!(created.iter().all(|a| a.appearance_streams.is_some()));
+
+    let path = temp_path("xfdf_annotations");
+    run!(file.save_to(&path));
+    let file = run!(FileOptions::cached().open(&path));
+    let page = run!(file.get_page(0));
+    // the popup comes right after its highlight
+    assert_eq!(run!(page.annotations(&file)).len(), before + 5);
+    let threads = run!(page.comment_threads(&file));
+    let note = threads.iter().find(|t| t.annot.subtype.as_str() == "Highlight").unwrap();
+    assert_eq!(note.replies.len(), 1);
+    assert!(note.annot.popup.is_some());
+
+    let exported = run!(file.export_annotations());
+    let subtypes: Vec<_> = exported.iter().map(|a| a.subtype.as_str()).collect();
+    assert_eq!(subtypes, ["Text", "Highlight", "Square", "Ink"]);
+    assert_eq!(exported[0].attr("inreplyto"), Some("note"));
+    assert_eq!(exported[1].attr("title"), Some("Jane"));
+    assert_eq!(exported[1].attr("coords"), Some("10,30,50,30,10,20,50,20"));
+    assert_eq!(exported[1].popup.as_ref().unwrap()[1], ("open".to_string(), "yes".to_string()));
+    assert_eq!(exported[2].attr("interior-color"), Some("#0000FF"));
+    assert_eq!(exported[3].ink_list, data.annots[3].ink_list);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn certification() {
+    use pdf::fdf::FormData;
+    use pdf::mdp::ModificationKind;
+
+    let xfdf = r#"<xfdf xmlns="http://ns.adobe.com/xfdf/"><annots><square page="0" rect="10,10,50,50"/></annots></xfdf>"#;
+    let annots = run!(FormData::parse_xfdf(xfdf)).annots;
+    for p in [2, 3] {
+        // the signature covers the whole original file, whose length is padded to a fixed width
+        let template = format!("%PDF-1.7
+1 0 obj << /Type /Catalog /Pages 2 0 R /Perms << /DocMDP 4 0 R >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << >> >> endobj
+4 0 obj << /Type /Sig /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached /ByteRange [0 0 0 LENGTH____] /Contents <00>
+  /Reference [<< /Type /SigRef /TransformMethod /DocMDP /TransformParams << /Type /TransformParams /P {} /V /1.2 >> >>] >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+", p);
+        let data = template.replace("LENGTH____", &format!("{:010}", template.len()));
+        let mut file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(data.into_bytes()));
+        let check = run!(file.check_certification()).unwrap();
+        assert!(check.modifications.is_empty());
+
+        run!(file.import_annotations(&annots));
+        let path = temp_path(&format!("certification_{}", p));
+        run!(file.save_to(&path));
+        let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).open(&path));
+        let check = run!(file.check_certification()).unwrap();
+        assert!(check.modifications.iter().any(|m| m.kind == ModificationKind::Annotation && !m.existed));
+        assert!(check.modifications.iter().all(|m| m.kind != ModificationKind::Other), "{:?}", check.modifications);
+        assert_eq!(check.is_allowed(), p == 3);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn revisions() {
+    use pdf::fdf::FormData;
+
+    let xfdf = r#"<xfdf xmlns="http://ns.adobe.com/xfdf/"><annots><square page="0" rect="10,10,50,50"/></annots></xfdf>"#;
+    let annots = run!(FormData::parse_xfdf(xfdf)).annots;
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let revisions = run!(file.revisions());
+    let before = run!(run!(file.get_page(0)).annotations(&file)).len();
+
+    // two incremental updates, with an annotation each
+    let path = temp_path("revisions");
+    run!(file.import_annotations(&annots));
+    run!(file.save_to(&path));
+    let mut file = run!(FileOptions::cached().open(&path));
+    run!(file.import_annotations(&annots));
+    run!(file.save_to(&path));
+
+    let file = run!(FileOptions::cached().open(&path));
+    let updated = run!(file.revisions());
+    assert_eq!(updated.len(), revisions.len() + 2);
+    assert_eq!(&updated[.. revisions.len()], &revisions[..]);
+    assert_eq!(updated.last().unwrap().len, std::fs::metadata(&path).unwrap().len() as usize);
+    for (i, revision) in updated[revisions.len() - 1 ..].iter().enumerate() {
+        let old = run!(file.load_revision(revision));
+        assert_eq!(run!(run!(old.get_page(0)).annotations(&old)).len(), before + i);
+    }
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn diff() {
+    use pdf::diff::{compare, Change};
+    use pdf::fdf::FormData;
+    use pdf::repair::repair;
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Annots [4 0 R] >> endobj
+4 0 obj << /T (name) /FT /Tx /Subtype /Widget /Rect [0 0 50 10] /P 3 0 R >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, _) = run!(repair(&data[..]));
+    let mut file = run!(FileOptions::cached().load(repaired));
+    let xfdf = r#"<xfdf xmlns="http://ns.adobe.com/xfdf/">
+<fields><field name="name"><value>Jane</value></field></fields>
+<annots><square page="0" name="box" rect="10,10,50,50"/></annots></xfdf>"#;
+    let data = run!(FormData::parse_xfdf(xfdf));
+    run!(file.import_fdf(&data));
+    run!(file.import_annotations(&data.annots));
+    let path = temp_path("diff");
+    run!(file.save_to(&path));
+
+    let file = run!(FileOptions::cached().open(&path));
+    assert!(run!(compare(&file, &file)).is_empty());
+    let revisions = run!(file.revisions());
+    let old = run!(file.load_revision(&revisions[0]));
+    let diff = run!(compare(&old, &file));
+    assert!(diff.pages.is_empty(), "{:?}", diff.pages);
+    // the widget is the field, whose value changed
+    let annots: Vec<_> = diff.annotations.iter().map(|a| (a.subtype.as_deref(), a.change)).collect();
+    assert_eq!(annots, [(Some("Widget"), Change::Changed), (Some("Square"), Change::Added)]);
+    assert_eq!(diff.fields.len(), 1);
+    assert!(diff.fields[0].is_filled());
+    assert!(diff.objects.iter().any(|o| o.change == Change::Changed));
+    assert!(diff.objects.iter().any(|o| o.change == Change::Added));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn diff_unreadable_object() {
+    use pdf::diff::{compare, Change, ObjectChange};
+
+    // an object that can't be read is a change, not an error
+    let load = |obj: &str| {
+        let data = format!("%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >> endobj
+5 0 obj {} endobj
+trailer << /Root 1 0 R >>
+%%EOF
+", obj);
+        run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(data.into_bytes()))
+    };
+    let diff = run!(compare(&load("<< /A 1 >>"), &load("<< /A [1 >>")));
+    assert_eq!(diff.objects, [ObjectChange { id: 5, change: Change::Changed }]);
+}
+
+#[test]
+fn xfa() {
+    use pdf::repair::repair;
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [] /XFA 4 0 R >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] >> endobj
+4 0 obj << /Length 74 >>
+stream
+<xdp:xdp><template><subform/></template><datasets><a>1</a></datasets></xdp:xdp>
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, _) = run!(repair(&data[..]));
+    let mut file = run!(FileOptions::cached().load(repaired));
+    let mut xfa = run!(file.get_root().forms.as_ref().unwrap().xfa(&file.resolver())).unwrap();
+    assert_eq!(xfa.datasets(), Some(&b"<datasets><a>1</a></datasets>"[..]));
+    xfa.set_datasets(b"<datasets><a>2</a></datasets>".to_vec());
+    run!(file.set_xfa(&xfa));
+    let path = temp_path("xfa");
+    run!(file.save_to(&path));
+
+    let file = run!(FileOptions::cached().open(&path));
+    let _ = std::fs::remove_file(path);
+    let forms = file.get_root().forms.as_ref().unwrap();
+    assert!(matches!(forms.xfa, Some(pdf::primitive::Primitive::Array(_))));
+    let copy = run!(forms.xfa(&file.resolver())).unwrap();
+    assert_eq!(copy, xfa);
+}
+
+#[test]
+fn name_tree_editing() {
+    use pdf::primitive::{PdfString, Primitive};
+
+    let s = |s: &str| PdfString::from(s);
+    let mut storage = FileOptions::uncached().storage();
+    let low = run!(storage.create(NameTree {
+        limits: Some((s("a"), s("c"))),
+        node: NameTreeNode::Leaf(vec![(s("a"), 1.into()), (s("c"), 3.into())]),
+    }));
+    let high = run!(storage.create(NameTree {
+        limits: Some((s("m"), s("z"))),
+        node: NameTreeNode::Leaf(vec![(s("m"), 13.into()), (s("z"), 26.into())]),
+    }));
+    let mut tree = NameTree::<Primitive> {
+        limits: None,
+        node: NameTreeNode::Intermediate(vec![low.get_ref(), high.get_ref()]),
+    };
+
+    // intermediate nodes are written with their limits and kids
+    let written = run!((*low).to_primitive(&mut NoUpdate).and_then(|p| p.into_dictionary()));
+    assert_eq!(written.get("Limits"), Some(&Primitive::Array(vec![s("a").into(), s("c").into()])));
+    let written = run!(tree.to_primitive(&mut NoUpdate).and_then(|p| p.into_dictionary()));
+    assert_eq!(written.get("Kids"), Some(&Primitive::Array(vec![low.get_ref().get_inner().into(), high.get_ref().get_inner().into()])));
+
+    let resolver = storage.resolver();
+    assert_eq!(run!(tree.get(b"m", &resolver)), Some(13.into()));
+    assert_eq!(run!(tree.get(b"c", &resolver)), Some(3.into()));
+    assert_eq!(run!(tree.get(b"d", &resolver)), None);
+
+    // editing collapses the kids into a single sorted node
+    assert_eq!(run!(tree.insert(s("b"), 2.into(), &resolver)), None);
+    assert!(tree.limits.is_none());
+    let names = |tree: &NameTree<Primitive>| match tree.node {
+        NameTreeNode::Leaf(ref items) => items.iter().map(|(name, _)| name.to_string_lossy()).collect::<Vec<_>>(),
+        NameTreeNode::Intermediate(_) => panic!("not collapsed"),
+    };
+    assert_eq!(names(&tree), ["a", "b", "c", "m", "z"]);
+    assert_eq!(run!(tree.insert(s("m"), 12.into(), &resolver)), Some(13.into()));
+    assert_eq!(run!(tree.remove(b"c", &resolver)), Some(3.into()));
+    assert_eq!(run!(tree.remove(b"c", &resolver)), None);
+    assert!(run!(tree.rename(b"a", s("y"), &resolver)));
+    assert!(!run!(tree.rename(b"a", s("x"), &resolver)));
+    assert!(tree.rename(b"z", s("m"), &resolver).is_err());
+    assert_eq!(names(&tree), ["b", "m", "y", "z"]);
+
+    let written = run!(tree.to_primitive(&mut NoUpdate));
+    let tree = run!(NameTree::<Primitive>::from_primitive(written, &resolver));
+    let entries = run!(tree.entries(&resolver));
+    assert_eq!(entries.iter().map(|(_, value)| value.clone()).collect::<Vec<_>>(), [2.into(), 12.into(), 1.into(), 26.into()]);
+
+}
+
+#[test]
+fn document_scripts() {
+    use pdf::object::NoResolve;
+    use pdf::primitive::Dictionary;
+
+    let mut storage = FileOptions::uncached().storage();
+    let mut names = run!(NameDictionary::from_primitive(Dictionary::new().into(), &NoResolve));
+    assert!(run!(names.scripts(&NoResolve)).is_empty());
+    run!(names.add_script("open", "app.alert(1);", false, &NoResolve, &mut storage));
+    run!(names.add_script("init", "var x = 1;", true, &NoResolve, &mut storage));
+    assert!(run!(names.rename_script("open", "main", &NoResolve)));
+    assert!(names.rename_script("main", "init", &NoResolve).is_err());
+
+    let written = run!(names.to_primitive(&mut NoUpdate));
+    let resolver = storage.resolver();
+    let mut names = run!(NameDictionary::from_primitive(written, &resolver));
+    let scripts = run!(names.scripts(&resolver));
+    let code: Vec<_> = scripts.iter().map(|script| (script.name.to_string_lossy(), run!(script.code(&resolver)))).collect();
+    assert_eq!(code, [("init".into(), "var x = 1;".into()), ("main".into(), "app.alert(1);".into())]);
+    assert!(matches!(scripts[0].source, ScriptSource::Stream(_)));
+    assert!(matches!(run!(names.script("main", &resolver)).unwrap().source, ScriptSource::String(_)));
+
+    assert!(run!(names.remove_script("init", &resolver)));
+    assert!(!run!(names.remove_script("init", &resolver)));
+    assert_eq!(run!(names.scripts(&resolver)).len(), 1);
+}
+
+#[test]
+fn strip_javascript() {
+    use pdf::repair::repair;
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /OpenAction 4 0 R /Names << /JavaScript << /Names [(init) 5 0 R] >> >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /AA << /O << /S /JavaScript /JS (this.print()) >> >> >> endobj
+4 0 obj << /S /JavaScript /JS 6 0 R >> endobj
+5 0 obj << /S /JavaScript /JS (var x = 1;) >> endobj
+6 0 obj << /Length 14 >>
+stream
+app.alert(42);
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, _) = run!(repair(&data[..]));
+    let mut file = run!(FileOptions::cached().load(repaired));
+    let mut code: Vec<_> = file.javascript().into_iter().map(|s| s.code).collect();
+    code.sort();
+    assert_eq!(code, ["app.alert(42);", "this.print()", "var x = 1;"]);
+
+    assert_eq!(run!(file.strip_javascript()), 3);
+    assert!(file.get_root().open_action.is_none());
+    let path = temp_path("strip_javascript");
+    run!(file.save_to(&path));
+
+    let file = run!(FileOptions::cached().open(&path));
+    let _ = std::fs::remove_file(path);
+    assert!(file.javascript().is_empty());
+    assert_eq!(file.num_pages(), 1);
+}
+
+#[test]
+fn sanitize() {
+    use pdf::repair::repair;
+    use pdf::sanitize::{ActiveContent, SanitizeOptions};
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R /OpenAction << /S /JavaScript /JS (1) >>
+  /Names << /EmbeddedFiles << /Names [(a.txt) << /Type /Filespec /F (a.txt) /EF << /F 6 0 R >> >>] >> >> >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Annots [4 0 R 5 0 R] >> endobj
+4 0 obj << /Type /Annot /Subtype /Link /Rect [0 0 10 10] /A << /S /Launch /F (calc.exe) /Next << /S /GoToR /F (b.pdf) /D [0 /Fit] >> >> >> endobj
+5 0 obj << /Type /Annot /Subtype /FileAttachment /Rect [20 0 30 10] /FS << /Type /Filespec /F (a.txt) /EF << /F 6 0 R >> >> >> endobj
+6 0 obj << /Type /EmbeddedFile /Length 5 >>
+stream
+hello
+endstream
+endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let (repaired, _) = run!(repair(&data[..]));
+    let mut file = run!(FileOptions::cached().load(repaired));
+    let options = SanitizeOptions { javascript: false, ..Default::default() };
+    let report = run!(file.sanitize(&options));
+    assert_eq!(report.count(ActiveContent::JavaScript), 0);
+    assert_eq!(report.count(ActiveContent::Launch), 1);
+    assert_eq!(report.count(ActiveContent::RemoteGoTo), 0, "inside the removed Launch action");
+    // the name tree, the attachment annotation and the file inside of it
+    assert_eq!(report.count(ActiveContent::EmbeddedFile), 3);
+    assert_eq!(file.javascript().len(), 1);
+
+    let page = run!(file.get_page(0));
+    let annots = run!(page.annotations.load(&file.resolver()));
+    assert_eq!(annots.len(), 1);
+    assert!(annots[0].other.get("A").is_none());
+    assert!(file.get_root().names.is_some());
+}
+
 #[test]
 fn user_password() {
     for path in dir_pdfs(file_path("password_protected")) {
@@ -89,6 +2140,48 @@ fn owner_password() {
     }
 }
 
+#[cfg(feature = "cache")]
+#[test]
+fn page_snapshot() {
+    use pdf::build::PageSnapshot;
+
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let snapshots: Vec<_> = file
+        .pages()
+        .map(|page| run!(PageSnapshot::new(&*run!(page), file.resolver())))
+        .collect();
+    let handles: Vec<_> = snapshots
+        .into_iter()
+        .map(|snapshot| {
+            std::thread::spawn(move || {
+                let page = run!(snapshot.page());
+                let ops = run!(page.contents.as_ref().unwrap().operations(&snapshot.resolver()));
+                (ops.len(), run!(snapshot.into_pdf()))
+            })
+        })
+        .collect();
+    for handle in handles {
+        let (num_ops, data) = handle.join().unwrap();
+        assert!(num_ops > 0);
+        let copy = run!(FileOptions::uncached().load(data));
+        assert_eq!(copy.num_pages(), 1);
+    }
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn extract_page_pdf() {
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let data = run!(file.extract_page_pdf(0));
+    let copy = run!(FileOptions::uncached().load(data));
+    assert_eq!(copy.num_pages(), 1);
+
+    let original = run!(file.get_page(0));
+    let page = run!(copy.get_page(0));
+    assert_eq!(run!(page.media_box()).right, run!(original.media_box()).right);
+    assert!(run!(page.resources()).fonts.len() <= run!(original.resources()).fonts.len());
+}
+
 // Test for invalid PDFs found by fuzzing.
 // We don't care if they give an Err or Ok, as long as they don't panic.
 #[cfg(feature = "cache")]
@@ -128,4 +2221,132 @@ fn parse_objects_from_stream() {
     }
 }
 
+#[cfg(feature = "zstd")]
+#[test]
+fn vendor_filters() {
+    let mut file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let content = |file: &pdf::file::File<_, _, _, _>| {
+        let page = run!(file.get_page(0));
+        run!(page.contents.as_ref().unwrap().data(file))
+    };
+    let before = content(&file);
+
+    let n = run!(file.recompress_streams(&StreamOptions::zstd(), |_| true));
+    assert!(n > 0);
+    let path = temp_path("vendor_filters");
+    run!(file.save_to(&path));
+    let data = std::fs::read(&path).unwrap();
+    assert!(data.windows(16).any(|w| w == b"PDFRS_ZstdDecode"));
+
+    let mut file = run!(FileOptions::cached().open(&path));
+    assert_eq!(content(&file), before);
+    assert_eq!(run!(file.downgrade_filters()), n);
+    run!(file.save_to(&path));
+    let mut file = run!(FileOptions::cached().open(&path));
+    assert_eq!(content(&file), before);
+    assert_eq!(run!(file.downgrade_filters()), 0);
+    let _ = std::fs::remove_file(path);
+}
+
 // TODO test decoding
+
+#[test]
+fn plugin_providers() {
+    use pdf::crypt::{SecurityHandler, SecurityHandlerProvider};
+    use pdf::enc::FilterProvider;
+    use pdf::error::Result;
+    use pdf::primitive::Dictionary;
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct Xor;
+    impl FilterProvider for Xor {
+        fn decode(&self, name: &str, params: &Dictionary, data: &[u8], _max: usize) -> Option<Result<Vec<u8>>> {
+            if name != "XORDecode" {
+                return None;
+            }
+            let key = params.get("Key").map_or(Ok(0), |k| k.as_u32()).map(|k| k as u8);
+            Some(key.map(|key| data.iter().map(|b| b ^ key).collect()))
+        }
+    }
+    // "encrypts" by reversing the bytes
+    struct Reverse;
+    impl SecurityHandler for Reverse {
+        fn decrypt<'buf>(&self, _id: PlainRef, data: &'buf mut [u8]) -> Result<&'buf [u8]> {
+            data.reverse();
+            Ok(data)
+        }
+    }
+    #[derive(Debug)]
+    struct Handlers;
+    impl SecurityHandlerProvider for Handlers {
+        fn handler(&self, name: &str, _dict: &Dictionary, _id: &[u8], password: &[u8]) -> Option<Result<Arc<dyn SecurityHandler>>> {
+            match name {
+                "Test.Reverse" if password == b"secret" => Some(Ok(Arc::new(Reverse))),
+                "Test.Reverse" => Some(Err(pdf::error::PdfError::InvalidPassword)),
+                _ => None,
+            }
+        }
+    }
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [] /Count 0 >> endobj
+3 0 obj << /Length 5 /Filter /XORDecode /DecodeParms << /Key 32 >> >>
+stream
+olleh
+endstream
+endobj
+4 0 obj << /Title (olleh) >> endobj
+trailer << /Root 1 0 R /Info 4 0 R /ID [<01> <01>] /Encrypt << /Filter /Test.Reverse /V 4 >> >>
+%%EOF
+";
+    let options = || FileOptions::cached().parse_options(ParseOptions::tolerant());
+    assert!(options().load(&data[..]).is_err());
+    assert!(options().security_handler_provider(Arc::new(Handlers)).load(&data[..]).is_err());
+
+    let file = run!(options()
+        .password(b"secret")
+        .security_handler_provider(Arc::new(Handlers))
+        .load(&data[..]));
+    let title = file.trailer.info_dict.as_ref().and_then(|info| info.title.as_ref());
+    assert_eq!(title.map(|t| t.to_string_lossy()).as_deref(), Some("hello"));
+    let stream = run!(file.resolver().get(Ref::<Stream<()>>::new(PlainRef { id: 3, gen: 0 })));
+    // the filter is unknown without a provider
+    assert!((*stream).data(&file.resolver()).is_err());
+
+    let file = run!(options()
+        .password(b"secret")
+        .security_handler_provider(Arc::new(Handlers))
+        .filter_provider(Arc::new(Xor))
+        .load(&data[..]));
+    let stream = run!(file.resolver().get(Ref::<Stream<()>>::new(PlainRef { id: 3, gen: 0 })));
+    assert_eq!(&*run!((*stream).data(&file.resolver())), b"HELLO");
+}
+
+#[test]
+fn verify_strict() {
+    use pdf::verify::IssueKind;
+
+    let data = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 3 /MediaBox [0 0 200 200] >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /Rotate 45 /Resources << /Font << /F1 5 0 R >> >> >> endobj
+4 0 obj << /Type /Pagee /Parent 2 0 R /Resources << >> >> endobj
+5 0 obj << /Type /Font /BaseFont /Helvetica /ThisNameIsMuchTooLongForAnyConformingReaderToAcceptBecauseItExceedsTheLimitOfOneHundredAndTwentySevenBytesByFarAndThenSomeMoreBytes true >> endobj
+trailer << /Root 1 0 R >>
+%%EOF
+";
+    let file = run!(FileOptions::cached().parse_options(ParseOptions::tolerant()).load(&data[..]));
+    let issues = file.verify();
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let has = |path: &str, kind: IssueKind| issues.iter().any(|i| i.path == path && i.kind == kind);
+    assert!(has("3 0 R", IssueKind::OutOfRange { key: "Rotate".into(), value: 45, expected: "a multiple of 90" }));
+    assert!(has("4 0 R", IssueKind::WrongType { expected: &["Pages", "Page"], found: "Pagee".into() }));
+    assert!(has("5 0 R", IssueKind::MissingKey { typ: "Font", key: "Subtype" }));
+    assert!(has("5 0 R", IssueKind::NameTooLong { len: 131 }));
+    assert!(has("2 0 R", IssueKind::CountMismatch { count: 3, pages: 2 }));
+    assert_eq!(issues.len(), 5);
+}
@@ -42,6 +42,13 @@
 //! a few of those in the PDF specs - one or many). However, as stated, it accepts absense of the
 //! entry, so **required** fields of type array aren't yet facilitated for.
 //!
+//! `Option<T>` can't tell a missing entry from one that is `null`. Fields of type `Nullable<T>`
+//! are `Absent` if the key is missing and `Null` if it is `null`, and are written back the same way.
+//!
+//! Fields marked `lenient` don't fail the whole dictionary when their value is invalid: a warning
+//! is logged and the field is constructed as if the entry was missing. This suits hints that a
+//! reader can do without, like the page mode of a document.
+//!
 //! Lastly, for each field, it's possible to define a default value by setting the `default`
 //! attribute to a string that can parse as Rust code.
 //!
@@ -87,6 +94,10 @@
 //!
 //! In this case, `StreamFilter::from_primitive(primitive)` will return Ok(_) only if the primitive
 //! is `Primitive::Name` and matches one of the enum variants
+//!
+//! ## serde
+//! `#[derive(Object)]` also implements `serde::Serialize` when the `serde` feature of `pdf` is enabled,
+//! which enables the `serde` feature of this crate.
 #![recursion_limit = "128"]
 
 extern crate proc_macro;
@@ -137,6 +148,7 @@ struct FieldAttrs {
     skip: bool,
     other: bool,
     indirect: bool,
+    lenient: bool,
 }
 impl FieldAttrs {
     fn new() -> FieldAttrs {
@@ -147,6 +159,7 @@ impl FieldAttrs {
             skip: false,
             other: false,
             indirect: false,
+            lenient: false,
         }
     }
     fn key(&self) -> &LitStr {
@@ -194,6 +207,11 @@ impl FieldAttrs {
                     return Ok(());
                 }
 
+                if meta.path.is_ident("lenient") {
+                    attrs.lenient = true;
+                    return Ok(());
+                }
+
                 Err(meta.error("unsupported key"))
             })
             .expect("parse error");
@@ -272,13 +290,15 @@ impl GlobalAttrs {
 
 fn impl_object(ast: &DeriveInput) -> TokenStream {
     let attrs = GlobalAttrs::from_ast(ast);
-    match (attrs.is_stream, &ast.data) {
-        (true, Data::Struct(ref data)) => impl_object_for_stream(ast, &data.fields).into(),
-        (false, Data::Struct(ref data)) => impl_object_for_struct(ast, &data.fields).into(),
-        (true, Data::Enum(ref variants)) => impl_enum_from_stream(ast, variants, &attrs).into(),
-        (false, Data::Enum(ref variants)) => impl_object_for_enum(ast, variants).into(),
+    let object = match (attrs.is_stream, &ast.data) {
+        (true, Data::Struct(ref data)) => impl_object_for_stream(ast, &data.fields),
+        (false, Data::Struct(ref data)) => impl_object_for_struct(ast, &data.fields),
+        (true, Data::Enum(ref variants)) => impl_enum_from_stream(ast, variants, &attrs),
+        (false, Data::Enum(ref variants)) => impl_object_for_enum(ast, variants),
         (_, _) => unimplemented!(),
-    }
+    };
+    let serialize = impl_serialize(ast, &attrs);
+    quote!(#object #serialize).into()
 }
 fn impl_objectwrite(ast: &DeriveInput) -> TokenStream {
     let attrs = GlobalAttrs::from_ast(ast);
@@ -572,6 +592,14 @@ fn impl_enum_from_stream(ast: &DeriveInput, data: &DataEnum, attrs: &GlobalAttrs
     }
 }
 
+/// `Nullable<T>` keeps apart a missing key and `null`, so it needs to be handled here.
+fn is_nullable(f: &Field) -> bool {
+    match f.ty {
+        Type::Path(ref p) => p.path.segments.last().is_some_and(|s| s.ident == "Nullable"),
+        _ => false,
+    }
+}
+
 fn is_option(f: &Field) -> Option<Type> {
     match f.ty {
         Type::Path(ref p) => {
@@ -621,7 +649,34 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
         let key = attrs.key();
 
         let ty = field.ty.clone();
-        if let Some(ref default) = attrs.default() {
+        let on_error = if attrs.lenient {
+            quote! {{
+                let e = pdf::error::PdfError::FromPrimitive { typ: #typ, field: #key, source: Box::new(e) };
+                resolve.options().warn(pdf::object::Warning::Ignored { error: e.to_string() });
+                <#ty as pdf::object::Object>::from_primitive(pdf::primitive::Primitive::Null, resolve)?
+            }}
+        } else {
+            quote! {
+                return Err(pdf::error::PdfError::FromPrimitive {
+                    typ: #typ,
+                    field: #key,
+                    source: Box::new(e)
+                })
+            }
+        };
+        if is_nullable(field) {
+            quote! {
+                let #name: #ty = match dict.remove(#key) {
+                    Some(primitive) => <#ty as pdf::object::Object>::from_primitive(primitive, resolve).map_err(|e|
+                        pdf::error::PdfError::FromPrimitive {
+                            typ: #typ,
+                            field: #key,
+                            source: Box::new(e)
+                        })?,
+                    None => pdf::object::Nullable::Absent,
+                };
+            }
+        } else if let Some(ref default) = attrs.default() {
             quote! {
                 let #name = {
                     let primitive: Option<pdf::primitive::Primitive>
@@ -630,7 +685,7 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
                         Some(primitive) => <#ty as pdf::object::Object>::from_primitive(primitive, resolve).map_err(|e|
                             pdf::error::PdfError::FromPrimitive {
                                 typ: #typ,
-                                field: stringify!(#name),
+                                field: #key,
                                 source: Box::new(e)
                             })?,
                         None => #default,
@@ -645,18 +700,14 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
                         Some(primitive) =>
                             match <#ty as pdf::object::Object>::from_primitive(primitive, resolve) {
                                 Ok(obj) => obj,
-                                Err(e) => return Err(pdf::error::PdfError::FromPrimitive {
-                                    typ: stringify!(#ty),
-                                    field: stringify!(#name),
-                                    source: Box::new(e)
-                                })
+                                Err(e) => #on_error
                             }
                         None =>  // Try to construct T from Primitive::Null
                             match <#ty as pdf::object::Object>::from_primitive(pdf::primitive::Primitive::Null, resolve) {
                                 Ok(obj) => obj,
                                 Err(_) => return Err(pdf::error::PdfError::MissingEntry {
                                     typ: #typ,
-                                    field: String::from(stringify!(#name)),
+                                    field: String::from(#key),
                                 })
                             },
                     }
@@ -726,14 +777,22 @@ fn impl_objectwrite_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream
             (
                 field.ident.clone(),
                 FieldAttrs::parse(&field.attrs),
-                is_option(field),
+                is_nullable(field),
             )
         })
         .collect();
 
-    let fields_ser = parts.iter().map(|(field, attrs, _opt)| {
+    let fields_ser = parts.iter().map(|(field, attrs, nullable)| {
         if attrs.skip | attrs.other {
             quote!()
+        } else if *nullable {
+            let key = attrs.key();
+            quote! {
+                if !self.#field.is_absent() {
+                    let val = pdf::object::ObjectWrite::to_primitive(&self.#field, updater)?;
+                    dict.insert(#key, val);
+                }
+            }
         } else {
             let key = attrs.key();
             let tr = if attrs.indirect {
@@ -832,6 +891,101 @@ fn impl_deepclone_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
     }
 }
 
+/// `serde::Serialize`, only generated with the `serde` feature.
+///
+/// Structs become maps of their (not skipped) fields. Enums read from a name or an integer are written
+/// as that name or integer, and stream enums as `{"Variant": inner}`.
+fn impl_serialize(ast: &DeriveInput, attrs: &GlobalAttrs) -> SynStream {
+    if !cfg!(feature = "serde") {
+        return quote! {};
+    }
+    let id = &ast.ident;
+    let mut generics = ast.generics.clone();
+    for g in generics.params.iter_mut() {
+        if let GenericParam::Type(p) = g {
+            p.bounds.push(parse_quote!(pdf::serde::Serialize));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match ast.data {
+        Data::Struct(ref data) => {
+            let fields: Vec<_> = data
+                .fields
+                .iter()
+                .filter(|field| !FieldAttrs::parse(&field.attrs).skip)
+                .map(|field| field.ident.as_ref().expect("tuple structs are not supported"))
+                .collect();
+            let names = fields.iter().map(|f| f.to_string());
+            let len = fields.len();
+            quote! {
+                use pdf::serde::ser::SerializeStruct;
+                let mut s = serializer.serialize_struct(stringify!(#id), #len)?;
+                #( s.serialize_field(#names, &self.#fields)?; )*
+                s.end()
+            }
+        }
+        Data::Enum(ref data) if attrs.is_stream => {
+            let parts = data.variants.iter().enumerate().map(|(i, var)| {
+                let var_ident = &var.ident;
+                let i = i as u32;
+                quote! {
+                    #id::#var_ident(ref inner) => serializer.serialize_newtype_variant(stringify!(#id), #i, stringify!(#var_ident), inner)
+                }
+            });
+            quote! {
+                match *self {
+                    #( #parts, )*
+                }
+            }
+        }
+        Data::Enum(ref data) if data.variants.iter().any(|var| var.discriminant.is_some()) => {
+            let parts = data.variants.iter().map(|var| {
+                let var_ident = &var.ident;
+                let (_, ref expr) = *var.discriminant.as_ref().unwrap();
+                quote! {
+                    #id::#var_ident => serializer.serialize_i32(#expr)
+                }
+            });
+            quote! {
+                match *self {
+                    #( #parts, )*
+                }
+            }
+        }
+        Data::Enum(ref data) => {
+            let (pairs, other) = enum_pairs(ast, data);
+            let mut parts: Vec<_> = pairs
+                .iter()
+                .map(|(name, var)| {
+                    quote! {
+                        #var => serializer.serialize_str(#name)
+                    }
+                })
+                .collect();
+            if let Some(other_tokens) = other {
+                parts.push(quote! {
+                    #other_tokens(ref name) => serializer.serialize_str(name)
+                });
+            }
+            quote! {
+                match *self {
+                    #( #parts, )*
+                }
+            }
+        }
+        Data::Union(_) => unimplemented!(),
+    };
+
+    quote! {
+        impl #impl_generics pdf::serde::Serialize for #id #ty_generics #where_clause {
+            fn serialize<S: pdf::serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                #body
+            }
+        }
+    }
+}
+
 /// Note: must have info and dict (TODO explain in docs)
 fn impl_object_for_stream(ast: &DeriveInput, fields: &Fields) -> SynStream {
     let id = &ast.ident;
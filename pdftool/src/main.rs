@@ -0,0 +1,269 @@
+//! `pdftool`: the pdf crate on the command line.
+//!
+//! Linearizing and signing are not supported by the crate, so there are no commands for them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use pdf::build::{CatalogBuilder, Importer, PageBuilder, PdfBuilder};
+use pdf::error::PdfError;
+use pdf::file::{CachedFile, FileOptions};
+use pdf::interpret::{ContentInterpreter, Event};
+use pdf::object::*;
+use pdf::primitive::{PdfString, Primitive};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Password of encrypted input files
+    #[arg(short, long, global = true, default_value = "")]
+    password: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the version, pages, encryption and document information
+    Info { input: PathBuf },
+    /// Print the text of each page
+    ExtractText { input: PathBuf },
+    /// Write the images of each page as PNG (or JPEG, if they are stored as such) into a directory
+    ExtractImages {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Write the file without encryption
+    Decrypt {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Put the pages of several files into one
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Write each page as a file of its own into a directory
+    Split {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Embed files in the document
+    Attach {
+        input: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), PdfError> {
+    let open = |path: &Path| -> Result<CachedFile<Vec<u8>>, PdfError> {
+        FileOptions::cached().password(args.password.as_bytes()).open(path)
+    };
+    match args.command {
+        Command::Info { ref input } => info(&open(input)?),
+        Command::ExtractText { ref input } => extract_text(&open(input)?),
+        Command::ExtractImages { ref input, ref output } => extract_images(&open(input)?, output),
+        Command::Decrypt { ref input, ref output } => {
+            let json = open(input)?.to_json()?;
+            fs::write(output, pdf::json::import(&json)?)?;
+            Ok(())
+        }
+        Command::Merge { ref inputs, ref output } => {
+            let files = inputs.iter().map(|path| open(path)).collect::<Result<Vec<_>, _>>()?;
+            fs::write(output, merge(&files)?)?;
+            Ok(())
+        }
+        Command::Split { ref input, ref output } => split(&open(input)?, input, output),
+        Command::Attach { ref input, ref files, ref output } => {
+            let mut file = open(input)?;
+            attach(&mut file, files)?;
+            file.save_to(output)
+        }
+    }
+}
+
+fn info(file: &CachedFile<Vec<u8>>) -> Result<(), PdfError> {
+    println!("Version: {}", file.version()?);
+    println!("Pages: {}", file.num_pages());
+    println!("Encrypted: {}", if file.trailer.encrypt_dict.is_some() { "yes" } else { "no" });
+    if let Some(ref info) = file.trailer.info_dict {
+        let entries = [
+            ("Title", &info.title),
+            ("Author", &info.author),
+            ("Subject", &info.subject),
+            ("Keywords", &info.keywords),
+            ("Creator", &info.creator),
+            ("Producer", &info.producer),
+        ];
+        for (key, value) in entries {
+            if let Some(value) = value {
+                println!("{}: {}", key, value.to_string_lossy());
+            }
+        }
+        if let Some(ref date) = info.creation_date {
+            println!("CreationDate: {}", date);
+        }
+        if let Some(ref date) = info.mod_date {
+            println!("ModDate: {}", date);
+        }
+    }
+    if let Ok(page) = file.get_page(0) {
+        let r = page.media_box()?;
+        println!("Page size: {} x {} pt", r.right - r.left, r.top - r.bottom);
+    }
+    Ok(())
+}
+
+/// Glyphs on a different baseline start a new line, gaps of more than a fifth of the font size become spaces.
+/// Vertical text is read top to bottom, each column is a line.
+fn extract_text(file: &CachedFile<Vec<u8>>) -> Result<(), PdfError> {
+    let resolver = file.resolver();
+    for (n, page) in file.pages().enumerate() {
+        let page = page?;
+        let ops = match page.contents {
+            Some(ref contents) => contents.operations(&resolver)?,
+            None => continue,
+        };
+        let mut text = String::new();
+        // baseline (or column) and end of the last glyph along it, and the font size, in device space
+        let mut last: Option<(f32, f32, f32)> = None;
+        let mut interpreter = ContentInterpreter::for_page(&page, &resolver)?;
+        interpreter.run(&ops, &mut |event, state| {
+            let glyphs = match *event {
+                Event::TextShown { glyphs, .. } => glyphs,
+                _ => return Ok(()),
+            };
+            for glyph in glyphs {
+                let m = glyph.matrix;
+                let size = m.c.hypot(m.d);
+                // position across and along the line, which runs down for vertical text
+                let (across, along) = if glyph.vertical { (m.e, -m.f) } else { (m.f, m.e) };
+                if let Some((line, end, last_size)) = last {
+                    if (across - line).abs() > 0.5 * last_size.max(size) {
+                        text.push('\n');
+                    } else if along - end > 0.2 * size && !text.ends_with(' ') {
+                        text.push(' ');
+                    }
+                }
+                if let Some(ref unicode) = glyph.unicode {
+                    text.push_str(unicode);
+                }
+                let (scale, extent) = match glyph.vertical {
+                    true => (state.text.font_size, m.d),
+                    false => (state.text.font_size * state.text.horiz_scale, m.a),
+                };
+                let width = if scale != 0. { glyph.advance / scale } else { 0. };
+                last = Some((across, along + width * extent, size));
+            }
+            Ok(())
+        })?;
+        println!("--- page {} ---", n + 1);
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+fn extract_images(file: &CachedFile<Vec<u8>>, dir: &Path) -> Result<(), PdfError> {
+    fs::create_dir_all(dir)?;
+    let resolver = file.resolver();
+    let mut seen = std::collections::HashSet::new();
+    for (n, page) in file.pages().enumerate() {
+        let page = page?;
+        let resources = page.resources()?;
+        let mut names: Vec<_> = resources.xobjects.iter().collect();
+        names.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, &r) in names {
+            if !seen.insert(r.get_inner()) {
+                continue;
+            }
+            let image = match *resolver.get(r)? {
+                XObject::Image(ref image) => image.clone(),
+                _ => continue,
+            };
+            let base = dir.join(format!("page{}-{}", n + 1, name.as_str()));
+            if let (data, Some(pdf::enc::StreamFilter::DCTDecode(_))) = image.raw_image_data(&resolver)? {
+                fs::write(base.with_extension("jpg"), data)?;
+                continue;
+            }
+            let rgba = match image.rgba_data(&resolver) {
+                Ok(rgba) => rgba,
+                Err(e) => {
+                    eprintln!("skipping {} on page {}: {}", name, n + 1, e);
+                    continue;
+                }
+            };
+            let png = image::RgbaImage::from_raw(image.width, image.height, rgba)
+                .ok_or_else(|| PdfError::Other { msg: format!("image {} has the wrong size", name) })?;
+            png.save(base.with_extension("png")).map_err(|e| PdfError::Other { msg: e.to_string() })?;
+        }
+    }
+    Ok(())
+}
+
+fn merge(files: &[CachedFile<Vec<u8>>]) -> Result<Vec<u8>, PdfError> {
+    let mut builder = PdfBuilder::new(FileOptions::cached());
+    let mut pages = Vec::new();
+    for file in files {
+        let mut importer = Importer::new(file.resolver(), &mut builder.storage);
+        for page in file.pages() {
+            pages.push(PageBuilder::clone_page(&*page?, &mut importer)?);
+        }
+        importer.finish();
+    }
+    builder.build(CatalogBuilder::from_pages(pages))
+}
+
+fn split(file: &CachedFile<Vec<u8>>, input: &Path, dir: &Path) -> Result<(), PdfError> {
+    fs::create_dir_all(dir)?;
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("page");
+    for n in 0..file.num_pages() {
+        let data = file.extract_page_pdf(n)?;
+        fs::write(dir.join(format!("{}-{}.pdf", stem, n + 1)), data)?;
+    }
+    Ok(())
+}
+
+fn attach(file: &mut CachedFile<Vec<u8>>, paths: &[PathBuf]) -> Result<(), PdfError> {
+    let mut specs = Vec::with_capacity(paths.len());
+    for path in paths {
+        let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("attachment").to_string();
+        let data = fs::read(path)?;
+        specs.push((name.clone(), FileSpec::embed(&name, &data, EmbedOptions::default(), file)?));
+    }
+
+    // read the catalog and name dictionary again, to get versions that can be changed
+    let root = file.trailer.root.get_ref().get_inner();
+    let (mut catalog, names) = {
+        let resolver = file.resolver();
+        let dict = resolver.resolve(root)?.into_dictionary()?;
+        let mut names = match dict.get("Names") {
+            Some(names) => NameDictionary::from_primitive(names.clone(), &resolver)?,
+            None => NameDictionary::from_primitive(Primitive::Dictionary(Default::default()), &resolver)?,
+        };
+        let tree = names.embedded_files.get_or_insert_with(|| NameTree::from_entries(vec![]));
+        for (name, spec) in specs {
+            tree.insert(PdfString::from(name.as_str()), spec, &resolver)?;
+        }
+        (Catalog::from_primitive(Primitive::Dictionary(dict), &resolver)?, names)
+    };
+    catalog.names = Some(MaybeRef::Indirect(file.create(names)?));
+    file.update_catalog(catalog)
+}
@@ -0,0 +1,97 @@
+use pdf::file::FileOptions;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn file_path(s: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .join("files")
+        .join(s)
+}
+
+/// A directory in the temp directory that no other test or concurrent run writes to.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pdftool_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run pdftool with `args` and return what it printed, failing the test if it didn't succeed.
+fn pdftool(args: &[&Path]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_pdftool")).args(args).output().unwrap();
+    assert!(output.status.success(), "pdftool {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn info() {
+    let out = pdftool(&["info".as_ref(), &file_path("example.pdf")]);
+    assert!(out.contains("Pages: 1\n"));
+    assert!(out.contains("Encrypted: no\n"));
+    assert!(out.contains("Page size: 180 x 240 pt\n"));
+}
+
+#[test]
+fn extract_text() {
+    let out = pdftool(&["extract-text".as_ref(), &file_path("example.pdf")]);
+    assert!(out.starts_with("--- page 1 ---\n"));
+    assert!(out.contains("Hello World!"));
+}
+
+#[test]
+fn extract_images() {
+    let dir = temp_dir("extract_images");
+    pdftool(&["extract-images".as_ref(), &file_path("jpeg.pdf"), "-o".as_ref(), &dir]);
+    let images: Vec<_> = dir.read_dir().unwrap().map(|e| e.unwrap().path()).collect();
+    assert!(!images.is_empty());
+    assert!(images.iter().all(|p| p.extension().unwrap() == "jpg" || p.extension().unwrap() == "png"));
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn decrypt() {
+    let dir = temp_dir("decrypt");
+    let output = dir.join("decrypted.pdf");
+    let input = file_path("password_protected/passwords_aes_128.pdf");
+    pdftool(&["--password".as_ref(), "userpassword".as_ref(), "decrypt".as_ref(), &input, "-o".as_ref(), &output]);
+    let file = FileOptions::cached().open(&output).unwrap();
+    assert!(file.trailer.encrypt_dict.is_none());
+    assert_eq!(file.num_pages(), 1);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn merge() {
+    let dir = temp_dir("merge");
+    let output = dir.join("merged.pdf");
+    pdftool(&["merge".as_ref(), &file_path("example.pdf"), &file_path("xelatex.pdf"), "-o".as_ref(), &output]);
+    let file = FileOptions::cached().open(&output).unwrap();
+    assert_eq!(file.num_pages(), 5);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn split() {
+    let dir = temp_dir("split");
+    pdftool(&["split".as_ref(), &file_path("xelatex.pdf"), "-o".as_ref(), &dir]);
+    for n in 1..=4 {
+        let file = FileOptions::cached().open(dir.join(format!("xelatex-{}.pdf", n))).unwrap();
+        assert_eq!(file.num_pages(), 1);
+    }
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn attach() {
+    let dir = temp_dir("attach");
+    let (attachment, output) = (dir.join("notes.txt"), dir.join("attached.pdf"));
+    std::fs::write(&attachment, b"some notes").unwrap();
+    pdftool(&["attach".as_ref(), &file_path("example.pdf"), &attachment, "-o".as_ref(), &output]);
+    let file = FileOptions::cached().open(&output).unwrap();
+    let resolver = file.resolver();
+    let names = file.get_root().names.as_ref().unwrap();
+    let tree = names.embedded_files.as_ref().unwrap();
+    assert!(tree.get(b"notes.txt", &resolver).unwrap().is_some());
+    let _ = std::fs::remove_dir_all(dir);
+}